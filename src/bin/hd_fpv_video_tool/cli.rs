@@ -1,8 +1,8 @@
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
-use hd_fpv_video_tool::prelude::*;
-use getset::CopyGetters;
+use hd_fpv_video_tool::{prelude::*, flight_path, osd::{frame_shift::FrameShift, msp::{self, FlightControllerFirmware}, overlay::{convert::OverlayConversionCodec, image_sequence, style::Color}}, cli::{generate_overlay_args::osd_hide_items_arg_help, font_options::FontOptions}, video::{transcoder::TranscodePreset, frame_extraction::ImageFormat, remux::MetadataEntry, resolution::{OutputScale, TargetResolution}}};
+use getset::{CopyGetters, Getters};
 
 use crate::shell_autocompletion::*;
 
@@ -12,7 +12,7 @@ use crate::shell_autocompletion::*;
 ///
 /// Each command is aliased to the concatenation of the first letter of each word of the command{n}
 /// Example: the `generate-overlay-frames` command is aliased to `gof`
-#[derive(Parser, CopyGetters)]
+#[derive(Parser, CopyGetters, Getters)]
 #[clap(version, about, long_about)]
 pub struct Cli {
     #[clap(short, long, value_parser, default_value_t = LogLevel::Info)]
@@ -20,6 +20,32 @@ pub struct Cli {
     #[getset(get_copy = "pub")]
     log_level: LogLevel,
 
+    /// print the FFMpeg and MPV command lines and the planned intermediate files instead of running them
+    ///
+    /// The input files are still read so the printed command lines are the exact ones which would be run.
+    #[clap(long, global = true)]
+    #[getset(get_copy = "pub")]
+    dry_run: bool,
+
+    /// append the FFMpeg command lines and their error output to this file
+    #[clap(long, global = true, value_name = "FILE")]
+    #[getset(get = "pub")]
+    log_file: Option<PathBuf>,
+
+    /// maximum number of jobs run concurrently, used when cutting several segments and by the watch command
+    #[clap(short, long, global = true, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    #[getset(get_copy = "pub")]
+    jobs: u16,
+
+    /// directory where the intermediate files are written, e.g. the segments cut before being joined, next to the
+    /// output file by default
+    ///
+    /// The directory is created if it does not exist. The space needed by the intermediate files is checked before
+    /// starting and they are removed once the output is written or when the command fails or is interrupted.
+    #[clap(long, global = true, value_name = "DIR")]
+    #[getset(get = "pub")]
+    temp_dir: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -28,7 +54,38 @@ pub struct Cli {
 pub enum Commands {
     /// Display information about the specified OSD file
     #[clap(alias = "dofi")]
-    DisplayOSDFileInfo { osd_file: PathBuf },
+    DisplayOSDFileInfo {
+        /// also display the timeline of the OSD frames: gaps between the frames, update rate changes and recording
+        /// interruptions, which explain an overlay drifting from the video mid-flight
+        #[clap(long, value_parser)]
+        timeline: bool,
+
+        osd_file: PathBuf,
+    },
+
+    /// Display information about the specified video file: resolution, frame rate, duration, codecs, audio layout
+    #[clap(alias = "vi")]
+    VideoInfo {
+        /// output the information as JSON
+        #[clap(long, value_parser)]
+        json: bool,
+
+        video_file: PathBuf,
+    },
+
+    /// Manipulate OSD files
+    #[clap(alias = "o")]
+    Osd {
+        #[command(subcommand)]
+        command: OSDCommands,
+    },
+
+    /// Inspect the installed fonts
+    #[clap(alias = "f")]
+    Fonts {
+        #[command(subcommand)]
+        command: FontsCommands,
+    },
 
     /// Generate a transparent overlay frame sequence as PNG files from a .osd file
     ///
@@ -52,6 +109,10 @@ pub enum Commands {
         #[clap(flatten)]
         common_args: GenerateOverlayArgs,
 
+        /// PNG compression level, use `fast` to speed up writing the frames at the cost of bigger files
+        #[clap(long, value_parser, default_value = "default")]
+        png_compression: PngCompression,
+
         /// directory in which the OSD frames will be written
         output_dir: Option<PathBuf>,
     },
@@ -69,6 +130,7 @@ pub enum Commands {
     ///
     /// VP8 or VP9 codecs can be selected with the --codec option. Files generated with the VP9 codec are smaller
     /// but also it is roughly twice as slow as encoding with the VP8 codec which is already unfortunately pretty slow.
+    /// The encoding speed can be tuned with the --encoder-threads, --encoder-speed, --no-row-mt and --tile-columns options.
     ///
     /// Fonts are loaded either from the directory specified with the --font-dir option or
     /// from the directory found in the environment variable FONTS_DIR or
@@ -84,7 +146,27 @@ pub enum Commands {
         #[clap(short, long, default_value = "vp8")]
         codec: OverlayVideoCodec,
 
-        /// path of the video file to generate
+        #[clap(flatten)]
+        encoder_speed: OverlayVideoEncoderSpeedArgs,
+
+        /// constant frame rate of the overlay video at which the OSD frames are sampled, the OSD is only updated 10 to
+        /// 30 times per second so using its update rate instead of 60 FPS greatly reduces the encoding time and file
+        /// size without visible difference
+        #[clap(long, default_value_t = 60, value_parser = clap::value_parser!(u16).range(1..=60), value_name = "FPS")]
+        overlay_fps: u16,
+
+        /// container of the overlay video, `webm` or `mkv` which can store its transparency, inferred from the
+        /// extension of the video file by default
+        #[clap(long, value_enum, value_name = "CONTAINER")]
+        container: Option<video::container::Container>,
+
+        /// generate a few seconds of the overlay video first to estimate the encoding time and output size of the
+        /// whole overlay video, then ask whether to continue
+        #[clap(long, value_parser)]
+        estimate: bool,
+
+        /// path of the video file to generate, `-` writes the video to the standard output in the matroska format
+        /// unless the container is set
         video_file: Option<PathBuf>,
 
         /// overwrite output file if it exists
@@ -92,15 +174,278 @@ pub enum Commands {
         overwrite: bool,
     },
 
-    /// Cut a video file without transcoding by specifying the desired start and/or end timestamp
+    /// Re-encode an existing overlay video to another codec keeping its transparency
+    ///
+    /// The OSD file is not needed. VP8 and VP9 overlay videos are decoded with libvpx which reads their alpha channel,
+    /// the FFMpeg default decoders ignore it. ProRes 4444 is the transparent codec expected by most video editors.
+    ///
+    /// The container is inferred from the extension of the output file: `webm` or `mkv` for VP8/VP9, `mov` or `mkv`
+    /// for ProRes. By default the output file is the input file with the codec appended to its name.
+    #[clap(alias = "co")]
+    ConvertOverlay {
+        #[clap(short, long, value_enum)]
+        codec: OverlayConversionCodec,
+
+        #[clap(flatten)]
+        encoder_speed: OverlayVideoEncoderSpeedArgs,
+
+        /// container of the output video, inferred from the extension of the output file by default
+        #[clap(long, value_enum, value_name = "CONTAINER")]
+        container: Option<video::container::Container>,
+
+        /// overlay video to convert
+        input_video_file: PathBuf,
+
+        output_video_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Encode a numbered sequence of transparent PNG images into an overlay video
+    ///
+    /// The images are the frames generated by `generate-overlay-frames` or by other tools, the number at the end of
+    /// their file name being the index of the video frame they are displayed from. Missing numbers are allowed, an image
+    /// is displayed until the frame of the next one. By default the output file is the directory with the extension of
+    /// the container.
+    #[clap(alias = "oftv")]
+    OverlayFramesToVideo {
+        #[clap(short, long, value_enum, default_value_t = OverlayConversionCodec::Vp8)]
+        codec: OverlayConversionCodec,
+
+        #[clap(flatten)]
+        encoder_speed: OverlayVideoEncoderSpeedArgs,
+
+        /// frame rate of the overlay video
+        #[clap(long, value_parser, default_value_t = image_sequence::DEFAULT_FRAME_RATE)]
+        fps: u16,
+
+        /// container of the output video, inferred from the extension of the output file by default
+        #[clap(long, value_enum, value_name = "CONTAINER")]
+        container: Option<video::container::Container>,
+
+        /// directory containing the numbered PNG images
+        frames_dir: PathBuf,
+
+        output_video_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Cut a video file by specifying the desired start and/or end timestamp
+    ///
+    /// Note that in the default `copy` mode the video is not transcoded so it can only be cut at the nearest key frame
+    /// and the cuts may not be at exactly the start/end points. In `smart` mode only the frames between the cut points
+    /// and the nearest key frames are re-encoded so the cuts are frame accurate while most of the video is copied
+    /// losslessly. In `reencode` mode the whole video is re-encoded.
     ///
-    /// Note that without transcoding videos can only be cut at the nearest P-frame so the cuts may not
-    /// be at exactly the start/end points. If you need precise slicing use the `transcode` command instead.
+    /// With --cut-osd the OSD file associated to the input video is also cut and written next to the output video
+    /// with the same name and `osd` extension so that the OSD can still be rendered in sync with the cut video.
+    ///
+    /// Several segments can be cut at once with the --segment option instead of --start/--end, each segment is
+    /// written to a separate file named after the output file with the segment number appended unless --join
+    /// is used in which case the segments are joined into a single file, e.g. to make a highlight reel.
     #[clap(alias = "cv")]
     CutVideo {
         #[clap(flatten)]
         start_end: StartEndArgs,
 
+        #[clap(flatten)]
+        trim_disarmed_args: TrimDisarmedArgs,
+
+        /// also cut the OSD file associated to the input video, see the `transcode-video` command --osd option
+        /// for how the OSD file is found
+        #[clap(long, value_parser)]
+        cut_osd: bool,
+
+        /// cutting mode, `copy` is fast and lossless but the cut points snap to the key frames,
+        /// `smart` and `reencode` are frame accurate
+        #[clap(short, long, value_enum, default_value_t = video::CutMode::Copy)]
+        mode: video::CutMode,
+
+        /// segment to cut, can be repeated to cut several segments
+        #[clap(long = "segment", value_parser, value_name = "[START]..[END]", conflicts_with_all = ["start", "end", "trim_disarmed"])]
+        segments: Vec<video::TimestampRange>,
+
+        /// join the segments into a single file
+        #[clap(long, value_parser, requires = "segments")]
+        join: bool,
+
+        /// resume an interrupted cut of several segments into separate files, the segments which were completely
+        /// cut are skipped
+        #[clap(long, value_parser, requires = "segments", conflicts_with = "join")]
+        resume: bool,
+
+        /// do not copy the container metadata (creation time, GPS location, ...) and the modification time of the
+        /// input video to the output video
+        #[clap(long, value_parser)]
+        strip_metadata: bool,
+
+        /// input video file path or HTTP(S) URL
+        input_video_file: PathBuf,
+
+        /// output video file path
+        output_video_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Detect the flights recorded in a video file and optionally write chapters marking them
+    ///
+    /// The flights are detected from the OSD file associated to the video like with the `split-flights` command.
+    /// The chapters cover the whole video, one per flight and one per disarmed section, so that a video with
+    /// several flights can be navigated once uploaded.
+    #[clap(alias = "fl")]
+    Flights {
+        /// write the chapters in the YouTube description format to this file, `-` for the standard output
+        #[clap(long, value_parser, value_name = "FILE")]
+        chapters: Option<PathBuf>,
+
+        /// write a copy of the video with the chapters embedded into it to this file, e.g. a MKV file
+        #[clap(long, value_parser, value_name = "FILE")]
+        embed_chapters: Option<PathBuf>,
+
+        /// overwrite output files if they exist
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+
+        /// input video file path
+        input_video_file: PathBuf,
+    },
+
+    /// Cut a video file containing several flights into one file per flight
+    ///
+    /// The flights are detected from the OSD file associated to the video, see the `transcode-video` command --osd
+    /// option for how the OSD file is found: the quad is considered armed while the fly time displayed by the OSD is
+    /// running and disarmed when the `DISARMED` warning is displayed.
+    ///
+    /// The flights are written to files named after the output file with the flight number appended, or after the
+    /// input file with the `_flight` suffix and the flight number appended if the output file is not provided. The
+    /// OSD file is also cut for each flight so that the OSD can still be rendered in sync with the flight videos.
+    #[clap(alias = "sf")]
+    SplitFlights {
+        /// seconds kept before the arming and after the disarming
+        #[clap(short, long, value_parser, value_name = "SECONDS", default_value_t = 2.0)]
+        padding: f64,
+
+        /// cutting mode, see the `cut-video` command
+        #[clap(short, long, value_enum, default_value_t = video::CutMode::Copy)]
+        mode: video::CutMode,
+
+        /// do not copy the container metadata and the modification time of the input video to the output videos
+        #[clap(long, value_parser)]
+        strip_metadata: bool,
+
+        /// also write the chapters marking the flights in the input video in the YouTube description format, to a
+        /// file named after the output file with the `_chapters.txt` suffix
+        #[clap(long, value_parser)]
+        chapters: bool,
+
+        /// input video file path
+        input_video_file: PathBuf,
+
+        /// output video file path, the flight number is appended to its name
+        output_video_file: Option<PathBuf>,
+
+        /// overwrite output files if they exist
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Join several video files into a single video file, e.g. to upload the clips of a session as one video
+    ///
+    /// The videos are joined without re-encoding when they have the same codecs, resolution and frame rate.
+    /// Otherwise they are re-encoded, scaled and padded to the resolution and frame rate of the first video, the
+    /// videos without audio being given silent audio.
+    ///
+    /// With --crossfade the videos fade into each other, which requires re-encoding them.
+    #[clap(alias = "cat")]
+    ConcatVideos {
+        /// duration in seconds of the crossfade between the videos
+        #[clap(short, long, value_parser, value_name = "SECONDS")]
+        crossfade: Option<f64>,
+
+        /// re-encode the videos even when they could be joined without re-encoding
+        #[clap(long, value_parser)]
+        reencode: bool,
+
+        /// output video file path
+        #[clap(short, long = "output", value_parser, value_name = "FILE")]
+        output_video_file: PathBuf,
+
+        /// input video files, in the order they are joined
+        #[clap(value_parser, num_args = 2.., required = true)]
+        input_video_files: Vec<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Copy a video file into another container without re-encoding it, e.g. from MP4 to MKV
+    ///
+    /// The output container is chosen from the extension of the output file or with --container, the output file
+    /// then defaulting to the input file with the extension of the container. The streams are checked against the
+    /// container before remuxing, streams the container cannot store need to be transcoded with `transcode-video`.
+    ///
+    /// The subtitle streams are only kept when remuxing to MKV.
+    #[clap(alias = "rm")]
+    Remux {
+        /// output container, required if the output file is not provided
+        #[clap(long, value_enum, required_unless_present = "output_video_file")]
+        container: Option<video::container::Container>,
+
+        /// move the index of MP4/MOV files to the beginning of the file so that they can be played while downloaded
+        #[clap(long, value_parser)]
+        faststart: bool,
+
+        /// drop the audio streams
+        #[clap(long, value_parser)]
+        no_audio: bool,
+
+        /// drop the subtitle streams
+        #[clap(long, value_parser)]
+        no_subtitles: bool,
+
+        /// do not copy the container metadata and the modification time of the input video to the output video
+        #[clap(long, value_parser)]
+        strip_metadata: bool,
+
+        /// set a container metadata entry, e.g. `title=Bando session`, can be repeated
+        #[clap(long = "metadata", value_parser, value_name = "KEY=VALUE")]
+        metadata: Vec<MetadataEntry>,
+
+        /// input video file path or HTTP(S) URL
+        input_video_file: PathBuf,
+
+        /// output video file path
+        output_video_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Recover a DVR recording which cannot be read because it was interrupted, e.g. by a power loss
+    ///
+    /// The index of the MP4 files is only written when the recording stops so the interrupted recordings have all
+    /// their frames but cannot even be probed. The frames are extracted and muxed into a new file using the decoder
+    /// configuration of a healthy recording made with the same unit and the same video settings (resolution, frame
+    /// rate and codec). The audio is not recovered.
+    ///
+    /// If the output video file is not provided the output video will be written in the same directory
+    /// as the input video with the same file name with suffix `_repaired`
+    #[clap(alias = "rv")]
+    RepairVideo {
+        /// healthy recording made with the same unit and video settings as the video to repair
+        #[clap(short, long, value_parser, value_name = "VIDEO_FILE")]
+        reference: PathBuf,
+
         /// input video file path
         input_video_file: PathBuf,
 
@@ -129,6 +474,16 @@ pub enum Commands {
         #[clap(short, long, value_parser)]
         volume: bool,
 
+        /// analyse the audio to decide whether the sync and volume need to be fixed and by how much
+        ///
+        /// The drift of the audio is measured from the durations of the audio and video streams and the gain from the
+        /// peak level of the audio. A warning is displayed when the video does not look like an original recording.
+        #[clap(short, long, value_parser, conflicts_with_all(&["sync", "volume"]))]
+        auto: bool,
+
+        #[clap(flatten)]
+        audio_adjustment_args: AudioAdjustmentArgs,
+
         /// input video file path
         input_video_file: PathBuf,
 
@@ -152,6 +507,107 @@ pub enum Commands {
 
         #[clap(flatten)]
         transcode_args: TranscodeVideoArgs,
+
+        /// transcode a few seconds from the middle of the video first to estimate the encoding time and output size
+        /// of the whole video, then ask whether to continue
+        #[clap(long, value_parser)]
+        estimate: bool,
+    },
+
+    /// Generate a thumbnail image from a video frame with the OSD burnt onto it
+    ///
+    /// The frame displayed at the given timestamp is extracted and the OSD frame displayed at the same time is drawn
+    /// onto it, see the `transcode-video` command for how the OSD file is found. A title can optionally be drawn
+    /// at the top of the image.
+    ///
+    /// The image format depends on the extension of the output file, PNG and JPEG are the most common choices.
+    /// If the output file is not specified it defaults to <basename of the video file>_thumbnail.png
+    #[clap(alias = "gt")]
+    GenerateThumbnail {
+        #[clap(flatten)]
+        osd_args: TranscodeVideoOSDArgs,
+
+        /// timestamp of the video frame to use, a number of seconds can also be used
+        #[clap(short, long, value_parser, value_name = "[[HH:]MM:]SS[.mmm]", default_value = "0")]
+        timestamp: video::Timestamp,
+
+        /// title drawn at the top of the thumbnail
+        #[clap(long, value_parser)]
+        title: Option<String>,
+
+        /// path to the font file used to draw the title, default is the FFMpeg default font
+        #[clap(long, value_parser, value_name = "font file path")]
+        title_font: Option<PathBuf>,
+
+        /// scale the thumbnail to the given resolution, YouTube recommends 1280x720
+        #[clap(long, value_parser, value_names = OutputScale::valid_list())]
+        scale: Option<OutputScale>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+
+        video_file: PathBuf,
+
+        output_file: Option<PathBuf>,
+    },
+
+    /// Extract video frames into image files, optionally with the OSD burnt onto them
+    ///
+    /// The frames are selected either at the given timestamps or at a regular interval from the start of the video.
+    /// The images are named after the video file with the timestamp of the frame appended, e.g.
+    /// DJIG0000_00-01-23.456.png, and written in the same directory as the video unless --output-dir is used.
+    #[clap(alias = "ef")]
+    ExtractFrames {
+        #[clap(flatten)]
+        osd_args: TranscodeVideoOSDArgs,
+
+        /// timestamp of a frame to extract, can be repeated, a number of seconds can also be used
+        #[clap(short, long = "timestamp", value_parser, value_name = "[[HH:]MM:]SS[.mmm]", required_unless_present = "interval")]
+        timestamps: Vec<video::Timestamp>,
+
+        /// extract a frame every interval, a number of seconds can also be used
+        #[clap(long, value_parser, value_name = "[[HH:]MM:]SS[.mmm]", conflicts_with = "timestamps")]
+        interval: Option<video::Timestamp>,
+
+        /// image format
+        #[clap(long, value_enum, default_value_t = ImageFormat::Png)]
+        format: ImageFormat,
+
+        /// scale the images to the given resolution
+        #[clap(long, value_parser, value_names = OutputScale::valid_list())]
+        scale: Option<OutputScale>,
+
+        /// directory where the images are written
+        #[clap(long, value_parser)]
+        output_dir: Option<PathBuf>,
+
+        /// overwrite the images if they exist
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+
+        video_file: PathBuf,
+    },
+
+    /// Export a video into a MKV file with the OSD as an image subtitle track
+    ///
+    /// The OSD frames are encoded as a PGS (Blu-ray) subtitle stream muxed alongside the untouched video and audio
+    /// streams, the OSD can then be toggled like subtitles in the players supporting image subtitles, e.g. MPV or VLC.
+    /// See the `transcode-video` command for how the OSD file is found.
+    ///
+    /// If the output file is not specified it defaults to <basename of the video file>_with_osd.mkv
+    #[clap(alias = "emwot")]
+    ExportMkvWithOSDTrack {
+        #[clap(flatten)]
+        osd_args: TranscodeVideoOSDArgs,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+
+        video_file: PathBuf,
+
+        output_file: Option<PathBuf>,
     },
 
     /// Play a video with OSD by overlaying a transparent OSD video in real time
@@ -160,13 +616,265 @@ pub enum Commands {
     ///
     /// If the <OSD_VIDEO_FILE> argument is not provided it will try to use the file with the same base name
     /// as the <VIDEO_FILE> argument with suffix `_osd` and with `webm` extension.
+    ///
+    /// With --live the OSD is rendered on the fly from the OSD file instead so no overlay video needs to be
+    /// generated beforehand. The OSD file is found automatically with --osd or specified with --osd-file.
+    /// Seeking is not possible in this mode since the video with the OSD is streamed to MPV.
+    ///
+    /// When playing an OSD overlay video the following MPV key bindings adjust the OSD live: Alt+o toggles the OSD,
+    /// Alt+Up/Alt+Down change its opacity, Alt+Left/Alt+Right shift it by one frame and
+    /// Alt+Shift+Left/Alt+Shift+Right by ten frames. The resulting frame shift is logged when MPV exits.
     #[clap(alias = "pvwo")]
     PlayVideoWithOSD {
+        /// render the OSD on the fly from the OSD file instead of using an OSD overlay video
+        #[clap(long, value_parser, conflicts_with = "osd_video_file")]
+        live: bool,
+
+        /// write the OSD frame shift chosen with the key bindings into this file when MPV exits
+        #[clap(long, value_parser, value_name = "file", conflicts_with = "live")]
+        save_frame_shift: Option<PathBuf>,
+
+        #[clap(flatten)]
+        osd_args: TranscodeVideoOSDArgs,
+
+        /// video file path or HTTP(S) URL
         video_file: PathBuf,
 
         osd_video_file: Option<PathBuf>,
     },
 
+    /// Render a clip to find the OSD frame shift syncing the OSD with the video
+    ///
+    /// A short section of the video is rendered with the OSD once per candidate frame shift, each repetition being
+    /// labelled with its frame shift at the top of the video. Watch the clip and use the frame shift of the
+    /// repetition where the OSD is in sync with the video with --osd-frame-shift when burning the OSD. Pick a section
+    /// where the OSD changes at a visible moment, e.g. when the quad is armed or punched out.
+    ///
+    /// The candidate frame shifts are centered on the frame shift set with --osd-frame-shift or, when not set, on the
+    /// frame shift chosen automatically. See the `transcode-video` command for how the OSD file is found.
+    ///
+    /// If the output file is not specified it defaults to <basename of the video file>_osd_sync.<extension of the video file>
+    #[clap(alias = "cos")]
+    CalibrateOSDSync {
+        #[clap(flatten)]
+        osd_args: TranscodeVideoOSDArgs,
+
+        /// start of the rendered section of the video, a number of seconds can also be used
+        #[clap(long, value_parser, value_name = "[[HH:]MM:]SS[.mmm]", default_value = "0")]
+        start: video::Timestamp,
+
+        /// duration in seconds of the rendered section of the video
+        #[clap(long, value_parser, default_value_t = 5.0)]
+        duration: f64,
+
+        /// the clip is rendered with the frame shifts from -MAX_SHIFT to +MAX_SHIFT OSD frames around the center frame shift
+        #[clap(long, value_parser, default_value_t = 5)]
+        max_shift: u8,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+
+        video_file: PathBuf,
+
+        output_video_file: Option<PathBuf>,
+    },
+
+    /// Stream a live input to a RTMP/SRT/UDP endpoint, optionally compositing the OSD in real time
+    ///
+    /// The input is either a capture device when --input-format is given, e.g. `--input-format v4l2 /dev/video0`,
+    /// or a video file read at its native frame rate. With --follow the file is read while it is still being
+    /// recorded, e.g. a DVR recording in a streamable format like MPEG-TS.
+    ///
+    /// The OSD is rendered from the OSD file as the stream goes, see the `transcode-video` command for how the OSD
+    /// file is found. The resolution of capture devices must be given with --capture-resolution to render the OSD.
+    ///
+    /// The container format is chosen from the URL scheme: FLV for rtmp:// and rtmps://, MPEG-TS for srt:// and udp://.
+    #[clap(alias = "s")]
+    Stream {
+        #[clap(flatten)]
+        osd_args: TranscodeVideoOSDArgs,
+
+        /// FFMpeg input format of the capture device, e.g. v4l2, dshow or avfoundation
+        #[clap(long, value_parser)]
+        input_format: Option<String>,
+
+        /// resolution of the capture device
+        #[clap(long, value_parser, value_names = TargetResolution::valid_list(), requires = "input_format")]
+        capture_resolution: Option<TargetResolution>,
+
+        /// keep reading the input file while it grows
+        #[clap(long, value_parser, conflicts_with = "input_format")]
+        follow: bool,
+
+        /// video encoder to use, directly passed to the `-c:v` FFMpeg argument
+        #[clap(long, value_parser, default_value = video::stream::DEFAULT_STREAM_VIDEO_ENCODER)]
+        video_encoder: String,
+
+        /// video bitrate, examples: 6M, 2500k
+        #[clap(long, visible_alias = "bitrate", value_parser, default_value = video::stream::DEFAULT_STREAM_VIDEO_BITRATE)]
+        video_bitrate: String,
+
+        /// audio bitrate, the audio of capture devices is not streamed
+        #[clap(long, value_parser, default_value = video::stream::DEFAULT_STREAM_AUDIO_BITRATE)]
+        audio_bitrate: String,
+
+        /// capture device or video file path
+        input: PathBuf,
+
+        /// URL of the endpoint to stream to, e.g. rtmp://live.example.com/app/<stream key>
+        output_url: String,
+    },
+
+    /// Ground station DVR: display and/or record a capture device video with the OSD received live from the flight controller
+    ///
+    /// The OSD is received with the MSP DisplayPort protocol on a serial port connected to a flight controller UART
+    /// configured for MSP DisplayPort and drawn with the font of the flight controller firmware, see the --font-dir option.
+    /// The video with the OSD is displayed with MPV unless --no-display is used and recorded into a MKV file with --record.
+    #[clap(alias = "gs")]
+    GroundStation {
+        #[clap(flatten)]
+        font_options: FontOptions,
+
+        /// FFMpeg input format of the capture device, e.g. v4l2, dshow or avfoundation
+        #[clap(long, value_parser)]
+        input_format: String,
+
+        /// resolution of the capture device
+        #[clap(long, value_parser, value_names = TargetResolution::valid_list())]
+        capture_resolution: TargetResolution,
+
+        /// serial port connected to the flight controller, e.g. /dev/ttyUSB0 or COM3
+        #[clap(long, value_parser, value_name = "path")]
+        serial_port: PathBuf,
+
+        /// serial port baud rate
+        #[clap(long, value_parser, default_value_t = msp::DEFAULT_BAUD_RATE)]
+        baud_rate: u32,
+
+        /// flight controller firmware, determines the font used to draw the OSD
+        #[clap(long, value_enum, default_value_t = FlightControllerFirmware::Betaflight)]
+        firmware: FlightControllerFirmware,
+
+        /// record the video with the OSD into this MKV file
+        #[clap(long, value_parser, value_name = "file")]
+        record: Option<PathBuf>,
+
+        /// do not display the video, only record it
+        #[clap(long, value_parser, requires = "record")]
+        no_display: bool,
+
+        /// capture device, e.g. /dev/video0
+        capture_device: PathBuf,
+    },
+
+    /// Render the flight path from the GPS coordinates displayed in the OSD
+    ///
+    /// The GPS coordinates are read from the latitude and longitude OSD items which are only located for the INAV
+    /// and Ardupilot fonts. A transparent PNG image of the whole path is rendered when the output file extension is
+    /// .png and a transparent WebM overlay video in which the path is drawn as the aircraft moves when it is .webm.
+    ///
+    /// With --osm-background the path is drawn over OpenStreetMap tiles downloaded with curl, the attribution of the
+    /// map data which has to be credited when the rendered files are published is logged.
+    #[clap(alias = "rfp")]
+    RenderFlightPath {
+        /// width of the rendered image/video
+        #[clap(long, value_parser, default_value_t = flight_path::DEFAULT_WIDTH)]
+        width: u32,
+
+        /// height of the rendered image/video
+        #[clap(long, value_parser, default_value_t = flight_path::DEFAULT_HEIGHT)]
+        height: u32,
+
+        /// width of the path line in pixels
+        #[clap(long, value_parser, default_value_t = flight_path::DEFAULT_LINE_WIDTH)]
+        line_width: u32,
+
+        /// color of the path line, see the --osd-background option of the `transcode-video` command for the format
+        #[clap(long, value_parser, value_name = "color[@alpha]", default_value = flight_path::DEFAULT_COLOR)]
+        color: Color,
+
+        /// draw the path over an OpenStreetMap background
+        #[clap(long, value_parser)]
+        osm_background: bool,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+
+        /// path to FPV.WTF or Walksnail Avatar .osd file
+        osd_file: PathBuf,
+
+        /// PNG image or WebM video file to generate, defaults to the OSD file name with the `_flight_path.png` suffix
+        output_file: Option<PathBuf>,
+    },
+
+    /// Watch a directory and automatically transcode the new videos
+    ///
+    /// The directory is polled for new `.mp4` files, a file is processed once its size did not change between two polls
+    /// so that files still being copied are not processed. The processed files are recorded in a state file in the
+    /// watched directory so that they are not processed again and a lock file prevents running two watch processes
+    /// on the same directory.
+    ///
+    /// With --osd the OSD is burnt onto the videos for which an OSD file is found, see the `transcode-video` command
+    /// for how the OSD file is found. The output files are named like with the `transcode-video` command.
+    #[clap(alias = "w")]
+    Watch {
+        /// burn the OSD onto the videos for which an OSD file is found
+        #[clap(long, value_parser)]
+        osd: bool,
+
+        /// path to the directory containing font sets
+        #[clap(short = 'd', long, value_parser, value_name = "dirpath")]
+        osd_font_dir: Option<PathBuf>,
+
+        /// fix DJI AU audio sync + volume for the videos containing audio
+        #[clap(short, long, value_parser)]
+        fix_audio: bool,
+
+        /// encoding settings preset, see the `transcode-video` command
+        #[clap(long, value_parser)]
+        preset: Option<TranscodePreset>,
+
+        /// interval between directory polls
+        #[clap(long, value_parser, value_name = "seconds", default_value_t = 10)]
+        interval: u64,
+
+        /// directory in which the processed videos are written, defaults to the watched directory
+        #[clap(short, long, value_parser, value_name = "dirpath")]
+        output_dir: Option<PathBuf>,
+
+        /// directory to watch
+        watched_dir: PathBuf,
+    },
+
+    /// Interactive mode: pick a recording and the action to run on it from menus
+    ///
+    /// Lists the videos found in the directory with their associated OSD file then lets you choose
+    /// between burning the OSD, transcoding, cutting, fixing the audio or displaying the OSD file information.
+    #[clap(alias = "i")]
+    Interactive {
+        /// path to the directory containing font sets
+        #[clap(short = 'd', long, value_parser, value_name = "dirpath")]
+        osd_font_dir: Option<PathBuf>,
+
+        /// directory containing the recordings, defaults to the current directory
+        #[clap(default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Check the environment and print fixes for the problems found
+    ///
+    /// Checks that ffmpeg, ffprobe and mpv are available and prints their versions, that the font directory contains
+    /// the SD and HD fonts for the common font variants and that FFMpeg supports the codecs used by this tool:
+    /// VP8/VP9 with alpha channel for OSD overlay videos, libx264/libx265 and NVENC for transcoding.
+    #[clap(alias = "d")]
+    Doctor {
+        /// path to the directory containing font sets
+        #[clap(short = 'd', long, value_parser, value_name = "dirpath")]
+        font_dir: Option<PathBuf>,
+    },
+
     #[clap(hide(true))]
     GenerateShellAutocompletionFiles {
         #[clap(value_parser = generate_shell_autocompletion_files_arg_parser)]
@@ -176,3 +884,352 @@ pub enum Commands {
     #[clap(hide(true))]
     GenerateManPages,
 }
+
+#[derive(Subcommand)]
+pub enum OSDCommands {
+    /// Edit an OSD file and write the result into a new OSD file
+    ///
+    /// Only DJI FPV.WTF OSD files are supported.
+    ///
+    /// The transformations are applied in this order: hiding regions/items, shifting frames, trimming.
+    /// When trimming the frames are re-indexed so that the OSD file stays in sync with a video cut with the same
+    /// start/end timestamps.
+    ///
+    /// If the output OSD file is not provided the output file will be written in the same directory
+    /// as the input file with the same file name with suffix `_edited`
+    #[clap(alias = "e")]
+    Edit {
+        /// hide rectangular regions from the OSD
+        ///
+        /// The parameter is a `;` separated list of regions.{n}
+        /// The format for a region is: <left_x>,<top_y>[:<width>x<height>]{n}
+        /// If the size is not specified it will default to 1x1
+        #[clap(long, value_parser, value_delimiter = ';', value_name = "REGIONS")]
+        hide_regions: Vec<osd::Region>,
+
+        /// hide items from the OSD
+        #[clap(long, value_parser, value_delimiter = ',', value_name = "ITEM_NAMES", help = osd_hide_items_arg_help())]
+        hide_items: Vec<String>,
+
+        /// shift the OSD frames by that number of frames, a duration in milliseconds can also be used, e.g. 120ms
+        #[clap(short = 'o', long, value_parser, value_name = "frames|<ms>ms", allow_hyphen_values(true), default_value = "0")]
+        frame_shift: FrameShift,
+
+        #[clap(flatten)]
+        start_end: StartEndArgs,
+
+        /// input OSD file path
+        input_osd_file: PathBuf,
+
+        /// output OSD file path
+        output_osd_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Display statistics about the tile indices used in an OSD file
+    ///
+    /// Lists the tile indices used in the OSD file with the number of times and the number of frames
+    /// in which they are used. Tile indices outside of the range of the font which would be used to
+    /// render the OSD file and tiles which are empty in the font are flagged, which helps diagnosing
+    /// OSD rendering problems caused by the font.
+    #[clap(alias = "s")]
+    Stats {
+        #[clap(flatten)]
+        font_options: FontOptions,
+
+        /// OSD file path
+        osd_file: PathBuf,
+    },
+
+    /// Dump the OSD frames as text
+    ///
+    /// Each OSD frame is written as a grid of characters preceded by a line with its video frame index.
+    /// The tiles drawing letters, digits and punctuation are written as these characters and the other
+    /// tiles (symbols) are written with the character specified with --symbol-char.
+    ///
+    /// If the output file is not provided the text is written to the standard output.
+    #[clap(alias = "dt")]
+    DumpText {
+        /// character used for the tiles which do not correspond to a character
+        #[clap(long, value_parser, value_name = "char", default_value = "·")]
+        symbol_char: char,
+
+        /// OSD file path
+        osd_file: PathBuf,
+
+        /// output text file path
+        output_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Export the values of the numeric OSD items of each OSD frame as CSV
+    ///
+    /// The values are decoded from the characters displayed by the OSD items which are located for the font variant
+    /// of the OSD file, e.g. altitude, speed, battery voltage, current or GPS satellites, see the `--osd-hide-items`
+    /// option of the `transcode-video` command for the located items. The columns are the video frame index, the time
+    /// in seconds then one column per item which is empty when the item is not displayed in the frame.
+    ///
+    /// With `--updates` the CSV has instead one row per video frame telling whether the OSD is updated at this frame:
+    /// the video frame index, the time in seconds, 1 if there is an OSD frame else 0 and the number of video frames
+    /// since the last update, to plot where the OSD updates stalled.
+    ///
+    /// If the output file is not provided the CSV is written to the standard output.
+    #[clap(alias = "ec")]
+    ExportCSV {
+        /// items to export, defaults to all the located numeric items
+        #[clap(long, value_parser, value_delimiter = ',', value_name = "OSD_ITEM_NAMES")]
+        items: Vec<String>,
+
+        /// export whether each video frame has an OSD update instead of the values of the items
+        #[clap(long, value_parser, conflicts_with = "items")]
+        updates: bool,
+
+        /// OSD file path
+        osd_file: PathBuf,
+
+        /// output CSV file path
+        output_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Export the header and the frames of an OSD file as JSON
+    ///
+    /// The JSON document can be transformed or generated by external tools and written back into an OSD file with
+    /// the `osd import-json` command. The tile indices of each frame are stored column by column, 0 meaning no tile.
+    ///
+    /// If the output file is not provided the JSON is written to the standard output.
+    #[clap(alias = "ej")]
+    ExportJSON {
+        /// OSD file path
+        osd_file: PathBuf,
+
+        /// output JSON file path
+        output_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Write an OSD file from a JSON document in the format written by `osd export-json`
+    ///
+    /// Only DJI FPV.WTF OSD files can be written. The frames are sorted by video frame index and their tile indices
+    /// must fill either the full 60x22 grid or the grid of the OSD dimensions of the header.
+    ///
+    /// If the input file is `-` the JSON document is read from the standard input.
+    #[clap(alias = "ij")]
+    ImportJSON {
+        /// input JSON file path
+        json_file: PathBuf,
+
+        /// output OSD file path
+        output_osd_file: PathBuf,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Generate an OSD file from flight telemetry
+    ///
+    /// For the flights whose OSD was not recorded by the goggles, e.g. to burn the telemetry onto the footage of an
+    /// action camera. The telemetry can be a Betaflight blackbox log (.bbl/.bfl), an EdgeTX log, a CSV file with a
+    /// `time` column in seconds or a GPX track. The OSD items are placed on the 60x22 grid of the DJI FakeHD OSD
+    /// according to the layout template, see the README for its format. The OSD frame 0 is at the start of the
+    /// telemetry, shifted by --telemetry-offset.
+    ///
+    /// The generated OSD file can be used like a recorded one, e.g. with `generate-overlay-video` or with the
+    /// `--osd-file` option of `transcode-video`.
+    ///
+    /// If the output OSD file is not provided the output file will be written in the same directory
+    /// as the telemetry file with the same file name and `osd` extension
+    #[clap(alias = "sy")]
+    Synthesize {
+        /// layout template file, a default layout displaying the link quality, altitude, battery voltage, speed and
+        /// fly time is used if not provided
+        #[clap(long, value_parser, value_name = "FILE")]
+        layout: Option<PathBuf>,
+
+        /// font variant of the generated OSD file, determines the font used to render it
+        #[clap(long, value_enum, default_value = "betaflight")]
+        font_variant: osd::FontVariant,
+
+        /// number of OSD frames per second
+        #[clap(long, value_parser = clap::value_parser!(u32).range(1..=60), value_name = "Hz", default_value_t = osd::synthesize::DEFAULT_UPDATE_RATE)]
+        update_rate: u32,
+
+        /// index of the log in the blackbox file starting at 1, a log is recorded for each arming
+        #[clap(long, value_parser, default_value_t = 1, value_name = "INDEX")]
+        telemetry_log: usize,
+
+        /// seconds added to the telemetry time of the OSD frame 0, use it to sync the OSD with the video
+        #[clap(long, value_parser, allow_negative_numbers(true), default_value_t = 0.0, value_name = "SECONDS")]
+        telemetry_offset: f64,
+
+        /// telemetry file path
+        telemetry_file: PathBuf,
+
+        /// output OSD file path
+        output_osd_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Record the MSP DisplayPort OSD feed of a flight controller into an OSD file
+    ///
+    /// The feed is read from a serial port connected to a flight controller UART configured for MSP DisplayPort or
+    /// from a TCP connection with a `tcp://<host>:<port>` source, e.g. `tcp://localhost:5761` for the second UART of
+    /// a Betaflight SITL. An OSD frame is recorded each time the flight controller draws the screen, timestamped from
+    /// the start of the recording, which lets HDZero and analog users produce OSD files compatible with the other
+    /// commands. The recording stops after --duration, when the feed ends or with Ctrl-C, the file is kept complete.
+    #[clap(alias = "rec")]
+    Record {
+        /// serial port baud rate
+        #[clap(long, value_parser, default_value_t = msp::DEFAULT_BAUD_RATE)]
+        baud_rate: u32,
+
+        /// flight controller firmware, determines the font variant of the OSD file
+        #[clap(long, value_enum, default_value_t = FlightControllerFirmware::Betaflight)]
+        firmware: FlightControllerFirmware,
+
+        /// stop recording after this number of seconds
+        #[clap(long, value_parser, value_name = "seconds")]
+        duration: Option<u64>,
+
+        /// serial port connected to the flight controller, e.g. /dev/ttyUSB0 or COM3, or tcp://<host>:<port>
+        source: msp::recorder::Source,
+
+        /// output OSD file path
+        output_osd_file: PathBuf,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Check the integrity of an OSD file
+    ///
+    /// The header fields are validated and the frames are walked to detect a truncated last frame, which happens when
+    /// the goggles lose power while recording, and corrupted data. The command fails if an issue preventing the file
+    /// from being read is found, use the `osd repair` command to recover the complete frames.
+    #[clap(alias = "v")]
+    Verify {
+        /// OSD file path
+        osd_file: PathBuf,
+    },
+
+    /// Recover the complete frames of a damaged OSD file into a new OSD file
+    ///
+    /// The header and all the complete frames are copied, the truncated last frame and the corrupted data found
+    /// between frames are left out. Files whose header is invalid cannot be repaired.
+    ///
+    /// If the output OSD file is not provided the output file will be written in the same directory
+    /// as the input file with the same file name with suffix `_repaired`
+    #[clap(alias = "r")]
+    Repair {
+        /// input OSD file path
+        osd_file: PathBuf,
+
+        /// output OSD file path
+        output_osd_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+
+    /// Compare two OSD files
+    ///
+    /// The header fields are compared then the frames with the same video frame index are compared tile by tile.
+    /// The differences are summarized: the frames which only exist in one of the files and the frames with different
+    /// tiles, the differing tiles of the first frames being listed as `x,y: first tile index -> second tile index`
+    /// with 0 meaning no tile. The command fails if the files differ so it can be used in scripts, e.g. to check that
+    /// an edit or repair round-trip preserved the OSD data.
+    #[clap(alias = "d")]
+    Diff {
+        /// maximum number of differing frames whose tiles are listed
+        #[clap(long, value_parser, value_name = "count", default_value_t = 10)]
+        max_listed_frames: usize,
+
+        /// first OSD file path
+        first_osd_file: PathBuf,
+
+        /// second OSD file path
+        second_osd_file: PathBuf,
+    },
+
+    /// Generate a downscaled animated preview of the OSD over a checkerboard
+    ///
+    /// The preview is written as an animated GIF or as a MP4 video depending on the output file extension.
+    /// It is meant to quickly check and share what an OSD file looks like so it only covers a short time range,
+    /// the first 10 seconds after the start timestamp if no end timestamp is specified.
+    ///
+    /// If the output file is not provided the preview is written in the same directory as the OSD file
+    /// with the same file name with suffix `_preview` and `gif` extension.
+    #[clap(alias = "pg")]
+    PreviewGif {
+        #[clap(flatten)]
+        common_args: GenerateOverlayArgs,
+
+        /// width of the preview in pixels, the height is calculated to keep the OSD aspect ratio
+        #[clap(long, value_parser = clap::value_parser!(u32).range(16..), value_name = "px", default_value_t = 480)]
+        width: u32,
+
+        /// frame rate of the preview
+        #[clap(long, value_parser = clap::value_parser!(u32).range(1..=60), default_value_t = 15)]
+        fps: u32,
+
+        /// path of the preview file to generate, .gif or .mp4
+        #[clap(long, value_parser, value_name = "file path")]
+        output_file: Option<PathBuf>,
+
+        /// overwrite output file if it exists
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+}
+
+
+#[derive(Subcommand)]
+pub enum FontsCommands {
+    /// Render the tiles of a font into labelled font sheet images
+    ///
+    /// Draws the SD and HD tiles of the font in a grid of 16 tiles per row with the tile index above each tile and a
+    /// gap between the 256 tiles pages. Use it to check which font variant and pages are actually installed, the font
+    /// is loaded the same way as when rendering an OSD file, including the fallback to the generic font.
+    ///
+    /// The sheets are written to `<output file stem>_sd.png` and `<output file stem>_hd.png`, a size whose font is
+    /// not installed is skipped.
+    #[clap(alias = "p")]
+    Preview {
+        #[clap(flatten)]
+        font_options: FontOptions,
+
+        /// font variant whose font is previewed when no font identifier is forced with --font-ident
+        #[clap(long, value_enum, default_value = "generic")]
+        font_variant: osd::FontVariant,
+
+        /// number of 256 tiles font pages to load
+        #[clap(long, value_parser = clap::value_parser!(u8).range(1..=2), default_value_t = 1)]
+        pages: u8,
+
+        /// output file path, the tile size and the `.png` extension are appended to its stem
+        #[clap(default_value = "font_preview.png")]
+        output_file: PathBuf,
+
+        /// overwrite output files if they exist
+        #[clap(short = 'y', long, value_parser)]
+        overwrite: bool,
+    },
+}
\ No newline at end of file
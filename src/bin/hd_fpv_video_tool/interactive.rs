@@ -0,0 +1,315 @@
+
+use std::{
+    io::{self, Write, BufRead},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use console::{Key, Term, style, truncate_str};
+use hd_fpv_video_tool::{
+    prelude::*,
+    cli::font_options::font_dir_base,
+    osd::file::find_associated_to_video_file,
+    video::{Timestamp, transcoder::{OSDSettings, TranscodePreset}},
+};
+use strum::IntoEnumIterator;
+
+
+/// Video file found in the directory with its associated OSD file if any
+struct Recording {
+    video_file: PathBuf,
+    osd_file: Option<PathBuf>,
+}
+
+impl Recording {
+    fn name(&self) -> String {
+        self.video_file.file_name().unwrap().to_string_lossy().to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, strum::Display, strum::EnumIter)]
+enum Action {
+    #[strum(serialize = "burn OSD")]
+    BurnOSD,
+    #[strum(serialize = "transcode without OSD")]
+    Transcode,
+    #[strum(serialize = "cut")]
+    Cut,
+    #[strum(serialize = "fix audio")]
+    FixAudio,
+    #[strum(serialize = "show OSD file info")]
+    ShowOSDInfo,
+    #[strum(serialize = "back")]
+    Back,
+}
+
+fn prompt(message: &str) -> anyhow::Result<String> {
+    print!("{message}");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(anyhow!("end of input"));
+    }
+    Ok(line.trim().to_owned())
+}
+
+pub(crate) fn prompt_yes_no(message: &str, default: bool) -> anyhow::Result<bool> {
+    let choices = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match prompt(&format!("{message} {choices} "))?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("please answer y or n"),
+        }
+    }
+}
+
+fn prompt_timestamp(message: &str) -> anyhow::Result<Option<Timestamp>> {
+    loop {
+        let answer = prompt(message)?;
+        if answer.is_empty() { return Ok(None) }
+        match Timestamp::from_str(&answer) {
+            Ok(timestamp) => return Ok(Some(timestamp)),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
+
+/// Prints the numbered items and returns the index of the item chosen, `None` if the user chose to quit
+///
+/// Used instead of the menus when the standard input or output is not a terminal, e.g. when the answers are piped.
+fn choose<T: ToString>(items: &[T], quit_label: &str) -> anyhow::Result<Option<usize>> {
+    for (index, item) in items.iter().enumerate() {
+        println!("  {:>3}) {}", index + 1, item.to_string());
+    }
+    println!("    q) {quit_label}");
+    loop {
+        let answer = prompt("> ")?;
+        if answer == "q" { return Ok(None) }
+        match answer.parse::<usize>() {
+            Ok(choice) if (1..=items.len()).contains(&choice) => return Ok(Some(choice - 1)),
+            _ => println!("invalid choice, enter a number between 1 and {} or q", items.len()),
+        }
+    }
+}
+
+/// Menu drawn in place: the arrow keys (or k/j) move the highlighted item, enter picks it and escape (or q) returns
+/// `None`. The details of the highlighted item are displayed below the items, the items are scrolled when they do not fit
+/// in the terminal.
+fn select<T: ToString>(term: &Term, title: &str, items: &[T], quit_label: &str, mut details: impl FnMut(usize) -> Vec<String>)
+        -> anyhow::Result<Option<usize>> {
+    if items.is_empty() { return Ok(None) }
+    let mut selected = 0;
+    let mut drawn_lines = 0;
+    term.hide_cursor()?;
+    let choice = loop {
+        let (rows, columns) = term.size();
+        let fit = |line: &str| truncate_str(line, columns as usize - 1, "…").into_owned();
+        let details_lines = details(selected);
+        let visible_items = (rows as usize).saturating_sub(details_lines.len() + 5).max(3).min(items.len());
+        let first_visible = selected.saturating_sub(visible_items - 1).min(items.len() - visible_items);
+
+        let mut lines = vec![style(title).bold().to_string()];
+        for (index, item) in items.iter().enumerate().skip(first_visible).take(visible_items) {
+            let label = fit(&format!("  {}", item.to_string()));
+            lines.push(if index == selected { style(label).reverse().to_string() } else { label });
+        }
+        lines.push(String::new());
+        lines.extend(details_lines.iter().map(|line| fit(line)));
+        lines.push(style(fit(&format!("↑/↓ move, enter select, esc {quit_label}"))).dim().to_string());
+
+        term.clear_last_lines(drawn_lines)?;
+        for line in &lines {
+            term.write_line(line)?;
+        }
+        drawn_lines = lines.len();
+
+        match term.read_key()? {
+            Key::ArrowUp | Key::Char('k') => selected = selected.checked_sub(1).unwrap_or(items.len() - 1),
+            Key::ArrowDown | Key::Char('j') => selected = (selected + 1) % items.len(),
+            Key::Home | Key::PageUp => selected = 0,
+            Key::End | Key::PageDown => selected = items.len() - 1,
+            Key::Enter => break Some(selected),
+            Key::Escape | Key::Char('q') => break None,
+            _ => {},
+        }
+    };
+    term.show_cursor()?;
+    Ok(choice)
+}
+
+/// Lets the user pick one of the items with a menu when running in a terminal, otherwise from a numbered list
+fn pick<T: ToString>(title: &str, items: &[T], quit_label: &str, details: impl FnMut(usize) -> Vec<String>) -> anyhow::Result<Option<usize>> {
+    let term = Term::stdout();
+    if term.is_term() && console::user_attended() && Term::stdin().is_term() {
+        select(&term, title, items, quit_label, details)
+    } else {
+        println!();
+        println!("{title}");
+        choose(items, quit_label)
+    }
+}
+
+fn find_recordings(dir: &Path) -> anyhow::Result<Vec<Recording>> {
+    let mut recordings = fs_err::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && matches!(path.extension(), Some(extension) if extension.eq_ignore_ascii_case("mp4")))
+        .map(|video_file| {
+            let osd_file = find_associated_to_video_file(&video_file);
+            Recording { video_file, osd_file }
+        })
+        .collect::<Vec<_>>();
+    recordings.sort_by(|r1, r2| r1.video_file.cmp(&r2.video_file));
+    Ok(recordings)
+}
+
+fn recording_summary(recording: &Recording) -> String {
+    let video_summary = match video::probe(&recording.video_file) {
+        Ok(info) => {
+            let fps = info.frame_rate().numerator() as f64 / info.frame_rate().denominator() as f64;
+            let duration = Timestamp::from_milliseconds((info.frame_count() as f64 * 1000.0 / fps) as u64);
+            let audio = if info.has_audio() { ", audio" } else { "" };
+            format!("{} {fps:.0}fps {duration}{audio}", info.resolution())
+        },
+        Err(_) => "unreadable video".to_owned(),
+    };
+    let osd_summary = match &recording.osd_file {
+        Some(osd_file) => format!("OSD: {}", osd_file.file_name().unwrap().to_string_lossy()),
+        None => "no OSD".to_owned(),
+    };
+    format!("{} ({video_summary}, {osd_summary})", recording.name())
+}
+
+/// lines describing the OSD file of the recording shown under the menus
+fn osd_file_details(recording: &Recording) -> Vec<String> {
+    let osd_file = match &recording.osd_file {
+        Some(osd_file) => osd_file,
+        None => return vec!["No OSD file found for this recording".to_owned()],
+    };
+    let mut reader = match osd::file::open(osd_file) {
+        Ok(reader) => reader,
+        Err(error) => return vec![format!("OSD file {}: {error}", osd_file.to_string_lossy())],
+    };
+    let (file_type, font_variant) = match &reader {
+        osd::file::Reader::DJI(reader) => ("DJI FPV", reader.header().font_variant()),
+        osd::file::Reader::WSA(reader) => ("Walksnail Avatar", reader.header().font_variant()),
+    };
+    let mut details = vec![
+        format!("OSD file: {}", osd_file.to_string_lossy()),
+        format!("{file_type} OSD, {font_variant} font variant"),
+    ];
+    match reader.frames() {
+        Ok(frames) => if let Some(last_frame) = frames.last() {
+            let duration = Timestamp::from_milliseconds(last_frame.index() as u64 * 1000 / osd::FRAME_RATE as u64);
+            details.push(format!("{} OSD frames over {duration}", frames.len()));
+        },
+        Err(error) => details.push(format!("failed reading the OSD frames: {error}")),
+    }
+    details
+}
+
+fn choose_preset() -> anyhow::Result<Option<TranscodePreset>> {
+    let presets = TranscodePreset::value_variants();
+    let labels = presets.iter().map(|preset| preset.to_possible_value().unwrap().get_name().to_owned()).collect::<Vec<_>>();
+    let help = |index: usize| presets[index].to_possible_value().unwrap().get_help().map(|help| vec![help.to_string()]).unwrap_or_default();
+    Ok(pick("Encoding preset:", &labels, "default settings", help)?.map(|index| presets[index]))
+}
+
+async fn transcode(recording: &Recording, font_dir: &Option<PathBuf>, burn_osd: bool) -> anyhow::Result<()> {
+    let mut output_file_stem = recording.video_file.file_stem().unwrap().to_os_string();
+    output_file_stem.push(if burn_osd { "_with_osd" } else { "_transcoded" });
+    let output_video_file = recording.video_file.with_file_name(output_file_stem).with_extension("mp4");
+
+    let mut transcoder = Transcoder::new(&recording.video_file, &output_video_file);
+    if let Some(preset) = choose_preset()? {
+        transcoder.set_encoding_settings(&preset.encoding_settings());
+    }
+    transcoder
+        .set_start(prompt_timestamp("Start timestamp (empty for beginning): ")?)
+        .set_end(prompt_timestamp("End timestamp (empty for end): ")?);
+
+    if video::probe(&recording.video_file)?.has_audio() && prompt_yes_no("Fix DJI AU audio sync and volume?", true)? {
+        transcoder.set_audio_fix(Some(VideoAudioFixType::SyncAndVolume));
+    }
+
+    if burn_osd {
+        let osd_file = recording.osd_file.as_ref().ok_or_else(|| anyhow!("no OSD file found for this recording"))?;
+        transcoder.set_osd(Some(OSDSettings::new(osd_file, font_dir_base(font_dir)?)));
+    }
+
+    if output_video_file.exists() {
+        if ! prompt_yes_no(&format!("{} exists, overwrite?", output_video_file.to_string_lossy()), false)? { return Ok(()) }
+        transcoder.set_overwrite(true);
+    }
+
+    transcoder.transcode().await?;
+    Ok(())
+}
+
+async fn run_action(action: Action, recording: &Recording, font_dir: &Option<PathBuf>) -> anyhow::Result<()> {
+    match action {
+        Action::BurnOSD => transcode(recording, font_dir, true).await?,
+        Action::Transcode => transcode(recording, font_dir, false).await?,
+        Action::Cut => {
+            let start_end = StartEndArgs::new(
+                prompt_timestamp("Start timestamp (empty for beginning): ")?,
+                prompt_timestamp("End timestamp (empty for end): ")?
+            );
+            start_end.check_valid()?;
+            let cut_osd = recording.osd_file.is_some() && prompt_yes_no("Also cut the OSD file?", true)?;
+            let mode = if prompt_yes_no("Frame accurate cut (slower, re-encodes the frames around the cut points)?", false)? {
+                video::CutMode::Smart
+            } else {
+                video::CutMode::Copy
+            };
+            video::cut(&recording.video_file, &None::<PathBuf>, false, &start_end, cut_osd, mode, false).await?;
+        },
+        Action::FixAudio =>
+            video::fix_dji_air_unit_audio(&recording.video_file, &None::<PathBuf>, false, Some(VideoAudioFixType::SyncAndVolume), &Default::default()).await?,
+        Action::ShowOSDInfo => match &recording.osd_file {
+            Some(osd_file) => crate::display_osd_file_info_command(osd_file, false)?,
+            None => println!("no OSD file found for this recording"),
+        },
+        Action::Back => {},
+    }
+    Ok(())
+}
+
+pub async fn interactive_command(dir: &Path, font_dir: &Option<PathBuf>) -> anyhow::Result<()> {
+    loop {
+        let recordings = find_recordings(dir)?;
+        if recordings.is_empty() {
+            println!("no recordings found in {}", dir.to_string_lossy());
+            return Ok(());
+        }
+
+        let summaries = recordings.iter().map(recording_summary).collect::<Vec<_>>();
+        // the OSD files are only read once their recording is highlighted
+        let mut osd_details = vec![None; recordings.len()];
+        let details = |index: usize| osd_details[index].get_or_insert_with(|| osd_file_details(&recordings[index])).clone();
+        let recording = match pick(&format!("Recordings in {}:", dir.to_string_lossy()), &summaries, "quit", details)? {
+            Some(index) => &recordings[index],
+            None => return Ok(()),
+        };
+
+        loop {
+            let actions = Action::iter().filter(|action| match action {
+                Action::BurnOSD | Action::ShowOSDInfo => recording.osd_file.is_some(),
+                _ => true,
+            }).collect::<Vec<_>>();
+            let summary = recording_summary(recording);
+            let action = match pick(&format!("{}:", recording.name()), &actions, "quit", |_| vec![summary.clone()])? {
+                Some(index) => actions[index],
+                None => return Ok(()),
+            };
+            if let Action::Back = action { break }
+            // errors are reported and the user can choose another action
+            if let Err(error) = run_action(action, recording, font_dir).await {
+                log::error!("{error}");
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@ use std::{
     process::exit,
     path::{Path, PathBuf},
     env::current_exe,
+    time::Duration,
 };
 
 use clap::Parser;
@@ -13,18 +14,21 @@ use env_logger::fmt::Color;
 use strum::IntoEnumIterator;
 
 use anyhow::anyhow;
+use hd_fpv_osd_font_tool::prelude::tile;
 
 
-use hd_fpv_video_tool::{prelude::*, osd::file::GenericReader};
+use hd_fpv_video_tool::{prelude::*, flight_path::{self, FlightPathRenderer}, osd::file::{GenericReader, sorted_frames::GetFramesExt}, video::timestamp::StartEndOverlayFrameIndex, cli::font_options::{font_dir_base, FontOptions},
+    watch::{self, WatchSettings}, video::{GroundStation, LiveStreamer, ThumbnailGenerator, estimate::{DEFAULT_SAMPLE_DURATION, Estimate}, frame_extraction::FrameSelection, remux::Remuxer, transcoder::DEFAULT_ENCODING_SETTINGS}};
 mod shell_autocompletion;
 mod man_pages;
 mod cli;
+mod interactive;
 
 use {cli::*, man_pages::*, shell_autocompletion::*};
 
 
-fn display_osd_file_info_command<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
-    let mut reader = osd::file::open(path)?;
+fn display_osd_file_info_command<P: AsRef<Path>>(path: P, timeline: bool) -> anyhow::Result<()> {
+    let mut reader = osd::file::open(&path)?;
 
     println!();
     match &reader {
@@ -57,27 +61,399 @@ fn display_osd_file_info_command<P: AsRef<Path>>(path: P) -> anyhow::Result<()>
         let refresh_freq = 60.0 / refresh_interval_frames;
         println!("OSD update rate: {refresh_percent_frames:.0}% of the video frames ({refresh_freq:.1}Hz or approximately {refresh_interval_frames_str})");
     }
+
+    if timeline {
+        // the frames are read again in the order they are stored since the sorted frames hide the index resets
+        let timeline = osd::file::timeline::Timeline::read(&mut osd::file::open(&path)?)?;
+        println!();
+        println!("Usual interval between OSD frames: {} video frames", timeline.median_interval());
+        println!("Update rate:");
+        for rate_segment in timeline.rate_segments() {
+            println!("  {rate_segment}");
+        }
+        if timeline.events().is_empty() {
+            println!("No gap nor recording interruption found");
+        } else {
+            println!("Events ({} recording interruptions):", timeline.interruption_count());
+            for event in timeline.events() {
+                println!("  {event}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn video_info_command<P: AsRef<Path>>(video_file: P, json: bool) -> anyhow::Result<()> {
+    let info = video::probe(video_file)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info.to_json())?);
+        return Ok(());
+    }
+
+    let fps = |rate: ffmpeg_next::Rational| rate.numerator() as f64 / rate.denominator() as f64;
+    println!();
+    println!("Resolution: {}", info.resolution());
+    print!("Frame rate: {:.3} fps", fps(info.frame_rate()));
+    if info.is_variable_frame_rate() {
+        print!(" (variable, average {:.3} fps)", fps(info.avg_frame_rate()));
+    }
+    println!();
+    println!("Frame count: {}", info.frame_count());
+    println!("Duration: {}", video::Timestamp::from_milliseconds((info.duration() * 1000.0) as u64));
+    println!("Video codec: {}", info.video_codec().as_deref().unwrap_or("unknown"));
+    if let Some(bitrate) = info.video_bitrate() {
+        println!("Video bitrate: {} kb/s", bitrate / 1000);
+    }
+    match info.audio() {
+        Some(audio) => {
+            print!("Audio: {}, {}, {} Hz", audio.codec().as_deref().unwrap_or("unknown codec"), audio.channel_layout(), audio.sample_rate());
+            if let Some(bitrate) = audio.bitrate() {
+                print!(", {} kb/s", bitrate / 1000);
+            }
+            println!();
+        },
+        None => println!("Audio: none"),
+    }
+    Ok(())
+}
+
+fn osd_edit_command(command: &OSDCommands) -> anyhow::Result<()> {
+    if let OSDCommands::Edit { hide_regions, hide_items, frame_shift, start_end, input_osd_file, output_osd_file, overwrite } = command {
+        start_end.check_valid()?;
+
+        let output_osd_file = match output_osd_file {
+            Some(output_osd_file) => output_osd_file.clone(),
+            None => {
+                let mut output_file_stem = Path::new(input_osd_file.file_stem().ok_or_else(|| anyhow!("input OSD file has no file name"))?).as_os_str().to_os_string();
+                output_file_stem.push("_edited");
+                input_osd_file.with_file_name(output_file_stem).with_extension("osd")
+            },
+        };
+        if *input_osd_file == output_osd_file { return Err(anyhow!("input file and output file are the same file")) }
+
+        let mut reader = match osd::file::open(input_osd_file)? {
+            osd::file::Reader::DJI(reader) => reader,
+            osd::file::Reader::WSA(_) => return Err(anyhow!("only DJI FPV OSD files can be edited")),
+        };
+
+        log::info!("editing OSD file: {} -> {}", input_osd_file.to_string_lossy(), output_osd_file.to_string_lossy());
+
+        let frames = reader.frames()?
+            .with_erased_regions(hide_regions)
+            .with_erased_osd_items(hide_items)?
+            .shifted(frame_shift.osd_frames())
+            .trimmed(start_end.start().start_overlay_frame_count(), start_end.end().end_overlay_frame_index());
+        if ! hd_fpv_video_tool::process::plan_file_write(&output_osd_file, "edited OSD file") { return Ok(()) }
+
+        let mut writer = OSDFileWriter::create(&output_osd_file, reader.header(), *overwrite)?;
+        writer.write_frames(frames.iter())?;
+        writer.finish()?;
+
+        log::info!("OSD file written successfully: {} frames", frames.len());
+    }
+    Ok(())
+}
+
+fn print_osd_integrity_report(report: &osd::file::integrity::Report) {
+    println!();
+    println!("OSD file type: {}", report.format());
+    println!("File size: {} bytes", report.file_len());
+    println!("Complete frames: {}", report.frame_count());
+    if report.issues().is_empty() {
+        println!("No issue found");
+    } else {
+        println!("Issues:");
+        for issue in report.issues() {
+            println!("  {}{issue}", if issue.is_warning() { "warning: " } else { "" });
+        }
+    }
+}
+
+fn osd_export_json_command(osd_file: &Path, output_file: &Option<PathBuf>, overwrite: bool) -> anyhow::Result<()> {
+    let mut output: Box<dyn Write> = match output_file {
+        Some(output_file) => {
+            if ! overwrite && output_file.exists() { return Err(anyhow!("output file exists: {}", output_file.to_string_lossy())) }
+            Box::new(std::io::BufWriter::new(fs_err::File::create(output_file)?))
+        },
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let _partial_file = output_file.as_ref().map(hd_fpv_video_tool::process::PartialFile::new);
+    osd::file::json::export(osd_file, &mut output)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn osd_import_json_command(json_file: &Path, output_osd_file: &Path, overwrite: bool) -> anyhow::Result<()> {
+    let input: Box<dyn std::io::Read> = if json_file == Path::new("-") {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::io::BufReader::new(fs_err::File::open(json_file)?))
+    };
+    let frame_count = osd::file::json::import(input, output_osd_file, overwrite)?;
+    if hd_fpv_video_tool::process::dry_run() { return Ok(()) }
+    log::info!("OSD file written successfully: {} frames -> {}", frame_count, output_osd_file.to_string_lossy());
+    Ok(())
+}
+
+fn osd_synthesize_command(command: &OSDCommands) -> anyhow::Result<()> {
+    if let OSDCommands::Synthesize { layout, font_variant, update_rate, telemetry_log, telemetry_offset, telemetry_file, output_osd_file, overwrite } = command {
+        let layout = match layout {
+            Some(layout_file) => osd::synthesize::Layout::read(layout_file)?,
+            None => osd::synthesize::Layout::default(),
+        };
+        let output_osd_file = output_osd_file.clone().unwrap_or_else(|| telemetry_file.with_extension("osd"));
+        log::info!("generating OSD file from telemetry: {} -> {}", telemetry_file.to_string_lossy(), output_osd_file.to_string_lossy());
+        let telemetry_log = hd_fpv_video_tool::telemetry::read(telemetry_file, *telemetry_log)?;
+        let frame_count = osd::synthesize::write_osd_file(telemetry_log, *telemetry_offset, &layout, *font_variant, *update_rate,
+            &output_osd_file, *overwrite)?;
+        log::info!("OSD file generated successfully: {frame_count} frames");
+    }
+    Ok(())
+}
+
+async fn osd_record_command(command: &OSDCommands) -> anyhow::Result<()> {
+    if let OSDCommands::Record { baud_rate, firmware, duration, source, output_osd_file, overwrite } = command {
+        let mut recorder = osd::msp::recorder::Recorder::new(source.clone());
+        recorder
+            .set_baud_rate(*baud_rate)
+            .set_firmware(*firmware)
+            .set_duration(duration.map(Duration::from_secs));
+        let frame_count = recorder.record(output_osd_file, *overwrite).await?;
+        log::info!("OSD recorded successfully: {frame_count} frames -> {}", output_osd_file.to_string_lossy());
+    }
+    Ok(())
+}
+
+fn osd_verify_command(osd_file: &Path) -> anyhow::Result<()> {
+    let report = osd::file::integrity::verify(osd_file)?;
+    print_osd_integrity_report(&report);
+    if report.is_repairable() {
+        println!();
+        println!("{} bytes can be recovered with the `osd repair` command", report.recoverable_len());
+    }
+    if ! report.is_valid() { return Err(anyhow!("the OSD file is damaged")) }
+    Ok(())
+}
+
+fn osd_repair_command(osd_file: &Path, output_osd_file: &Option<PathBuf>, overwrite: bool) -> anyhow::Result<()> {
+    let output_osd_file = match output_osd_file {
+        Some(output_osd_file) => output_osd_file.clone(),
+        None => {
+            let mut output_file_stem = Path::new(osd_file.file_stem().ok_or_else(|| anyhow!("input OSD file has no file name"))?).as_os_str().to_os_string();
+            output_file_stem.push("_repaired");
+            osd_file.with_file_name(output_file_stem).with_extension("osd")
+        },
+    };
+    log::info!("repairing OSD file: {} -> {}", osd_file.to_string_lossy(), output_osd_file.to_string_lossy());
+    let report = osd::file::integrity::repair(osd_file, &output_osd_file, overwrite)?;
+    print_osd_integrity_report(&report);
+    if hd_fpv_video_tool::process::dry_run() { return Ok(()) }
+    if report.is_valid() {
+        log::info!("the OSD file has no issue, it has been copied as is");
+    } else {
+        log::info!("OSD file repaired successfully: {} frames recovered", report.frame_count());
+    }
+    Ok(())
+}
+
+/// number and range of the video frame indices of the OSD frames, e.g. `3 (frame indices 6 to 18)`
+fn frame_indices_summary(frame_indices: &[u32]) -> String {
+    match (frame_indices.first(), frame_indices.last()) {
+        (Some(first), Some(last)) if first != last => format!("{} (frame indices {first} to {last})", frame_indices.len()),
+        (Some(first), _) => format!("1 (frame index {first})"),
+        _ => "0".to_owned(),
+    }
+}
+
+fn osd_diff_command(first_osd_file: &Path, second_osd_file: &Path, max_listed_frames: usize) -> anyhow::Result<()> {
+    let diff = osd::file::diff::diff(first_osd_file, second_osd_file)?;
+
+    println!();
+    if diff.header_differences().is_empty() {
+        println!("Headers: identical");
+    } else {
+        println!("Headers:");
+        for difference in diff.header_differences() {
+            println!("  {difference}");
+        }
+    }
+    println!("Number of OSD frames: {} / {}", diff.first_frame_count(), diff.second_frame_count());
+    println!("Identical frames: {}", diff.identical_frame_count());
+    if ! diff.only_in_first().is_empty() {
+        println!("Frames only in first file: {}", frame_indices_summary(diff.only_in_first()));
+    }
+    if ! diff.only_in_second().is_empty() {
+        println!("Frames only in second file: {}", frame_indices_summary(diff.only_in_second()));
+    }
+    if ! diff.differing_frames().is_empty() {
+        println!("Frames with different tiles: {}", diff.differing_frames().len());
+        for frame in diff.differing_frames().iter().take(max_listed_frames) {
+            println!("  frame {}: {} tiles: {}", frame.index(), frame.tiles().len(), frame.tiles().iter().map(ToString::to_string).collect::<Vec<_>>().join(", "));
+        }
+        if diff.differing_frames().len() > max_listed_frames {
+            println!("  ... {} more frames", diff.differing_frames().len() - max_listed_frames);
+        }
+    }
+
+    if ! diff.is_identical() { return Err(anyhow!("the OSD files differ")) }
+    println!();
+    println!("The OSD files are identical");
+    Ok(())
+}
+
+fn fonts_preview_command(font_options: &FontOptions, font_variant: osd::FontVariant, pages: u8, output_file: &Path, overwrite: bool) -> anyhow::Result<()> {
+    let mut font_dir = FontDir::new(font_options.font_dir()?);
+    font_dir.set_premultiplied_alpha(font_options.font_premultiplied_alpha());
+    let max_used_tile_index = (pages as usize * osd::font_dir::PAGE_TILE_COUNT - 1) as osd::tile_indices::TileIndex;
+    let output_file_stem = output_file.file_stem().unwrap_or_default().to_string_lossy();
+    let mut written_count = 0;
+    for tile_kind in tile::Kind::iter() {
+        let tiles = match font_options.font_ident() {
+            Some(font_ident) => font_dir.load_with_fallback(tile_kind, &font_ident, max_used_tile_index),
+            None => font_dir.load_variant_with_fallback(tile_kind, &font_variant, max_used_tile_index),
+        };
+        let tiles = match tiles {
+            Ok(tiles) => tiles,
+            Err(error) => {
+                log::warn!("skipping {tile_kind} font: {error}");
+                continue;
+            },
+        };
+        let sheet_file = output_file.with_file_name(format!("{output_file_stem}_{}.png", tile_kind.to_string().to_lowercase()));
+        osd::font_preview::write(&tiles, &sheet_file, overwrite)?;
+        log::info!("{tile_kind} font sheet with {} tiles written: {}", tiles.len(), sheet_file.to_string_lossy());
+        written_count += 1;
+    }
+    if written_count == 0 { return Err(anyhow!("no font found")) }
+    Ok(())
+}
+
+fn osd_stats_command(font_options: &FontOptions, osd_file: &Path) -> anyhow::Result<()> {
+    let frames = osd::file::open(osd_file)?.frames()?;
+    let tile_usage = frames.tile_usage();
+    let highest_used_tile_index = frames.highest_used_tile_index().unwrap_or(0);
+
+    // when the font covering all the used tiles cannot be loaded fall back to the base font to still report the missing tiles
+    let mut font_dir = FontDir::new(font_options.font_dir()?);
+    font_dir.set_premultiplied_alpha(font_options.font_premultiplied_alpha());
+    let tile_kind = frames.kind().tile_kind();
+    let load_font = |max_used_tile_index| match font_options.font_ident() {
+        Some(font_ident) => font_dir.load_with_fallback(tile_kind, &font_ident, max_used_tile_index),
+        None => font_dir.load_variant_with_fallback(tile_kind, &frames.font_variant(), max_used_tile_index),
+    };
+    let font_tiles = match load_font(highest_used_tile_index) {
+        Ok(tiles) => Some(tiles),
+        Err(error) => {
+            log::warn!("failed to load font covering all the used tiles: {error}");
+            load_font(0).map_err(|error| log::warn!("failed to load base font: {error}")).ok()
+        },
+    };
+
+    println!();
+    println!("OSD layout: {}, font variant: {}", frames.kind(), frames.font_variant());
+    println!("Number of OSD frames: {}", frames.len());
+    match &font_tiles {
+        Some(font_tiles) => println!("Font: {} tiles", font_tiles.len()),
+        None => println!("Font: not loaded"),
+    }
+    println!();
+    println!("{:>5} {:>6} {:>12} {:>8}", "index", "hex", "occurrences", "frames");
+
+    let mut out_of_range_count = 0;
+    let mut empty_glyph_count = 0;
+    for (tile_index, usage) in &tile_usage {
+        let flag = match &font_tiles {
+            Some(font_tiles) => match font_tiles.get(*tile_index as usize) {
+                None => { out_of_range_count += 1; "outside of font range" },
+                Some(tile_image) if tile_image.pixels().all(|pixel| pixel.0[3] == 0) => { empty_glyph_count += 1; "empty in font" },
+                Some(_) => "",
+            },
+            None => "",
+        };
+        println!("{tile_index:>5} {tile_index:>#6x} {:>12} {:>8}  {flag}", usage.occurrences(), usage.frames());
+    }
+
+    println!();
+    println!("{} distinct tile indices used, highest: {highest_used_tile_index}", tile_usage.len());
+    if let Some(font_tiles) = &font_tiles {
+        println!("{out_of_range_count} tile indices outside of the font range (0-{}), {empty_glyph_count} tile indices empty in the font",
+            font_tiles.len().saturating_sub(1));
+    }
+    Ok(())
+}
+
+fn osd_dump_text_command(osd_file: &Path, output_file: &Option<PathBuf>, overwrite: bool, symbol_char: char) -> anyhow::Result<()> {
+    let frames = osd::file::open(osd_file)?.frames()?;
+    let mut output: Box<dyn Write> = match output_file {
+        Some(output_file) => {
+            if ! overwrite && output_file.exists() { return Err(anyhow!("output file exists: {}", output_file.to_string_lossy())) }
+            Box::new(std::io::BufWriter::new(fs_err::File::create(output_file)?))
+        },
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let _partial_file = output_file.as_ref().map(hd_fpv_video_tool::process::PartialFile::new);
+    let dimensions = frames.kind().dimensions_tiles();
+    for frame in frames.iter() {
+        writeln!(output, "frame {}", frame.index())?;
+        writeln!(output, "{}", frame.to_text(frames.font_variant(), dimensions, symbol_char))?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn osd_export_csv_command(osd_file: &Path, output_file: &Option<PathBuf>, overwrite: bool, items: &[String], updates: bool) -> anyhow::Result<()> {
+    let frames = osd::file::open(osd_file)?.frames()?;
+    let item_names = if updates {
+        vec![]
+    } else if items.is_empty() {
+        osd::item_series::numeric_item_names(frames.font_variant())
+    } else {
+        items.iter().map(String::as_str).collect()
+    };
+    if item_names.is_empty() && ! updates {
+        return Err(anyhow!("no OSD item is located for the `{}` font variant", frames.font_variant()));
+    }
+    let mut output: Box<dyn Write> = match output_file {
+        Some(output_file) => {
+            if ! overwrite && output_file.exists() { return Err(anyhow!("output file exists: {}", output_file.to_string_lossy())) }
+            Box::new(std::io::BufWriter::new(fs_err::File::create(output_file)?))
+        },
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let _partial_file = output_file.as_ref().map(hd_fpv_video_tool::process::PartialFile::new);
+    if updates {
+        osd::file::timeline::write_updates_csv(frames.iter().map(|frame| frame.index()), &mut output)?;
+    } else {
+        osd::item_series::write_csv(frames.iter(), frames.font_variant(), &item_names, &mut output)?;
+    }
+    output.flush()?;
     Ok(())
 }
 
 fn generate_overlay_prepare_generator(common_args: &GenerateOverlayArgs) -> anyhow::Result<OverlayGenerator> {
     let scaling = Scaling::try_from_scaling_args(common_args.scaling_args(), common_args.target_video_file())?;
-    let mut osd_file_reader = osd::file::open(common_args.osd_file())?;
-    let font_dir = FontDir::new(common_args.font_options().font_dir()?);
-    let overlay_generator = OverlayGenerator::new(
-        osd_file_reader.frames()?,
-        osd_file_reader.font_variant(),
+    let osd_file_reader = osd::file::open(common_args.osd_file())?;
+    let mut font_dir = FontDir::new(common_args.font_options().font_dir()?);
+    font_dir.set_premultiplied_alpha(common_args.font_options().font_premultiplied_alpha());
+    let font_variant = osd_file_reader.font_variant();
+    let mut overlay_generator = OverlayGenerator::new(
+        osd_file_reader,
+        font_variant,
         &font_dir,
         &common_args.font_options().font_ident(),
         scaling,
         common_args.hide_regions(),
         common_args.hide_items()
     )?;
+    overlay_generator.apply_style(&common_args.style_args().style()?);
+    overlay_generator.set_tile_blit_method(common_args.tile_blit_method());
+    if common_args.scaling_args().explain_scaling() { overlay_generator.log_scaling_explanation() }
     Ok(overlay_generator)
 }
 
 fn generate_overlay_frames_command(command: &Commands) -> anyhow::Result<()> {
-    if let Commands::GenerateOverlayFrames { common_args, output_dir } = command {
+    if let Commands::GenerateOverlayFrames { common_args, output_dir, png_compression } = command {
         common_args.check_valid()?;
         let output_dir = match (output_dir, common_args.target_video_file()) {
             (Some(output_dir), _) => output_dir.clone(),
@@ -94,56 +470,373 @@ fn generate_overlay_frames_command(command: &Commands) -> anyhow::Result<()> {
                 osd_file.with_file_name(output_dir_name)
             }
         };
+        let (first_frame, last_frame) = common_args.frame_range()?;
         let mut overlay_generator = generate_overlay_prepare_generator(common_args)?;
-        overlay_generator.save_frames_to_dir(common_args.start_end().start(), common_args.start_end().end(), output_dir, common_args.frame_shift()?)?;
+        overlay_generator.save_frames_to_dir(first_frame, last_frame, output_dir, common_args.frame_shift()?, *png_compression)?;
     }
     Ok(())
 }
 
+/// prints the estimate of the job and asks whether to start it
+fn confirm_estimate(estimate: &Estimate) -> anyhow::Result<bool> {
+    println!("{estimate}");
+    interactive::prompt_yes_no("Continue?", true)
+}
+
 async fn generate_overlay_video_command(command: &Commands) -> anyhow::Result<()> {
-    if let Commands::GenerateOverlayVideo { common_args, video_file, overwrite, codec } = command {
+    if let Commands::GenerateOverlayVideo { common_args, video_file, overwrite, codec, encoder_speed, overlay_fps, container, estimate } = command {
         common_args.check_valid()?;
+        let output_extension = container.unwrap_or(video::container::Container::Webm).extension();
         let output_video_path = match (video_file, common_args.target_video_file()) {
             (Some(output_video_file), _) => output_video_file.clone(),
             (None, Some(target_video_file)) => {
                 let target_video_file_stem = target_video_file.file_stem().ok_or_else(|| anyhow!("target video file has no file name"))?;
                 let mut output_file_stem = target_video_file_stem.to_os_string();
                 output_file_stem.push("_osd");
-                Path::new(&output_file_stem).with_extension("webm")
+                Path::new(&output_file_stem).with_extension(output_extension)
             },
             (None, None) => {
                 let osd_file = common_args.osd_file();
                 let mut output_file_stem = Path::new(osd_file.file_stem().ok_or_else(|| anyhow!("OSD file has no file name"))?).as_os_str().to_os_string();
                 output_file_stem.push("_osd");
-                osd_file.with_file_name(output_file_stem).with_extension("webm")
+                osd_file.with_file_name(output_file_stem).with_extension(output_extension)
             }
         };
+        let (first_frame, last_frame) = common_args.frame_range()?;
         let mut overlay_generator = generate_overlay_prepare_generator(common_args)?;
-        overlay_generator.generate_overlay_video(*codec, common_args.start_end().start(), common_args.start_end().end(), output_video_path, common_args.frame_shift()?, *overwrite).await?;
+        if *estimate {
+            if file::is_stdout(&output_video_path) { return Err(anyhow!("cannot estimate the overlay video when writing it to the standard output")) }
+            if ! *overwrite && output_video_path.exists() { return Err(anyhow!("target video file exists: {}", output_video_path.to_string_lossy())) }
+            let estimate = overlay_generator.estimate_overlay_video(*codec, &encoder_speed.settings(), first_frame, last_frame, *overlay_fps, *container,
+                &output_video_path, common_args.frame_shift()?, DEFAULT_SAMPLE_DURATION).await?;
+            if ! confirm_estimate(&estimate)? { return Ok(()) }
+        }
+        overlay_generator.generate_overlay_video(*codec, &encoder_speed.settings(), first_frame, last_frame, *overlay_fps, *container, output_video_path,
+            common_args.frame_shift()?, *overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn convert_overlay_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::ConvertOverlay { codec, encoder_speed, container, input_video_file, output_video_file, overwrite } = command {
+        let output_video_file = output_video_file.clone()
+            .unwrap_or_else(|| osd::overlay::convert::default_output_video_file(input_video_file, *codec, *container));
+        osd::overlay::convert::convert(input_video_file, &output_video_file, *codec, &encoder_speed.settings(), *container, *overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn overlay_frames_to_video_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::OverlayFramesToVideo { codec, encoder_speed, fps, container, frames_dir, output_video_file, overwrite } = command {
+        let output_video_file = output_video_file.clone()
+            .unwrap_or_else(|| osd::overlay::image_sequence::default_output_video_file(frames_dir, *codec, *container));
+        osd::overlay::image_sequence::encode(frames_dir, &output_video_file, *codec, &encoder_speed.settings(), *fps, *container, *overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn play_video_with_osd_command(live: bool, save_frame_shift: &Option<PathBuf>, osd_args: &TranscodeVideoOSDArgs, video_file: &Path,
+        osd_video_file: &Option<PathBuf>) -> anyhow::Result<()> {
+    if live {
+        let osd_settings = osd_args.osd_settings(video_file)?
+            .ok_or_else(|| anyhow!("live mode requires an OSD file, use --osd to find it automatically or --osd-file"))?;
+        video::play_with_live_osd(video_file, &osd_settings).await?;
+    } else {
+        video::play_with_osd(video_file, osd_video_file, save_frame_shift.as_deref())?;
+    }
+    Ok(())
+}
+
+/// duration of the OSD preview when no end timestamp is specified
+const DEFAULT_PREVIEW_FRAME_COUNT: u32 = 10 * 60;
+
+async fn osd_preview_gif_command(command: &OSDCommands) -> anyhow::Result<()> {
+    if let OSDCommands::PreviewGif { common_args, width, fps, output_file, overwrite } = command {
+        common_args.check_valid()?;
+        let output_file = match output_file {
+            Some(output_file) => output_file.clone(),
+            None => {
+                let osd_file = common_args.osd_file();
+                let mut output_file_stem = Path::new(osd_file.file_stem().ok_or_else(|| anyhow!("OSD file has no file name"))?).as_os_str().to_os_string();
+                output_file_stem.push("_preview");
+                osd_file.with_file_name(output_file_stem).with_extension("gif")
+            },
+        };
+        let (first_frame, last_frame) = common_args.frame_range()?;
+        let last_frame = last_frame.unwrap_or(first_frame + DEFAULT_PREVIEW_FRAME_COUNT);
+        let overlay_generator = generate_overlay_prepare_generator(common_args)?;
+        overlay_generator.generate_preview(first_frame, Some(last_frame), output_file, common_args.frame_shift()?, *width, *fps, *overwrite).await?;
     }
     Ok(())
 }
 
 async fn transcode_video_command(command: &Commands) -> anyhow::Result<()> {
-    if let Commands::TranscodeVideo { osd_args, transcode_args } = command {
+    if let Commands::TranscodeVideo { osd_args, transcode_args, estimate } = command {
 
         transcode_args.start_end().check_valid()?;
 
-        match osd_args.osd_file_path(transcode_args.input_video_file())? {
-            Some(osd_file_path) => video::transcode_burn_osd(transcode_args, osd_file_path, osd_args).await?,
-            None => video::transcode(transcode_args).await?,
+        let transcoder = transcode_args.transcoder(osd_args)?;
+        if *estimate {
+            let output_video_file = transcoder.output_video_file();
+            if file::is_stdout(output_video_file) { return Err(anyhow!("cannot estimate the transcoding when writing the video to the standard output")) }
+            if ! transcoder.overwrite() && output_video_file.exists() { return Err(anyhow!("output video file exists")) }
+            if ! confirm_estimate(&transcoder.estimate(DEFAULT_SAMPLE_DURATION).await?)? { return Ok(()) }
         }
+        transcoder.transcode().await?;
+    }
+    Ok(())
+}
+
+async fn generate_thumbnail_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::GenerateThumbnail { osd_args, timestamp, title, title_font, scale, overwrite, video_file, output_file } = command {
+        let output_file = match output_file {
+            Some(output_file) => output_file.clone(),
+            None => {
+                let mut output_file_stem = video_file.file_stem().ok_or_else(|| anyhow!("video file has no file name"))?.to_os_string();
+                output_file_stem.push("_thumbnail");
+                video_file.with_file_name(output_file_stem).with_extension("png")
+            },
+        };
+        let mut thumbnail_generator = ThumbnailGenerator::new(video_file, output_file);
+        thumbnail_generator
+            .set_overwrite(*overwrite)
+            .set_timestamp(*timestamp)
+            .set_title(title.clone())
+            .set_title_font_file(title_font.clone())
+            .set_scale(*scale)
+            .set_osd(osd_args.osd_settings(video_file)?);
+        thumbnail_generator.generate().await?;
+    }
+    Ok(())
+}
+
+async fn remux_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::Remux { container, faststart, no_audio, no_subtitles, strip_metadata, metadata, input_video_file, output_video_file, overwrite } = command {
+        let output_video_file = match (output_video_file, container) {
+            (Some(output_video_file), Some(container)) => {
+                container.check_output_file(output_video_file)?;
+                output_video_file.clone()
+            },
+            (Some(output_video_file), None) => output_video_file.clone(),
+            (None, Some(container)) => file::local_path(input_video_file).with_extension(container.extension()),
+            (None, None) => unreachable!("the container is required by clap when the output file is not provided"),
+        };
+        let mut remuxer = Remuxer::new(input_video_file, output_video_file);
+        remuxer
+            .set_overwrite(*overwrite)
+            .set_faststart(*faststart)
+            .set_drop_audio(*no_audio)
+            .set_drop_subtitles(*no_subtitles)
+            .set_strip_metadata(*strip_metadata)
+            .set_metadata(metadata.clone());
+        remuxer.remux().await?;
+    }
+    Ok(())
+}
+
+async fn repair_video_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::RepairVideo { reference, input_video_file, output_video_file, overwrite } = command {
+        let output_video_file = match output_video_file {
+            Some(output_video_file) => output_video_file.clone(),
+            None => {
+                let mut output_file_stem = Path::new(input_video_file.file_stem().ok_or_else(|| anyhow!("input video file has no file name"))?).as_os_str().to_os_string();
+                output_file_stem.push("_repaired");
+                input_video_file.with_file_name(output_file_stem).with_extension("mp4")
+            },
+        };
+        video::repair::repair_video(input_video_file, reference, output_video_file, *overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn flights_command(input_video_file: &Path, chapters_file: &Option<PathBuf>, embed_chapters_file: &Option<PathBuf>,
+        overwrite: bool) -> anyhow::Result<()> {
+    let chapters = video::chapters::video_flight_chapters(input_video_file)?;
+    for chapter in chapters.iter().filter(|chapter| chapter.title() != "Disarmed") {
+        log::info!("{}: {} - {}", chapter.title(), chapter.start(), chapter.end());
+    }
+    if let Some(chapters_file) = chapters_file {
+        video::chapters::write_youtube_chapters(&chapters, chapters_file, overwrite)?;
+    }
+    if let Some(embed_chapters_file) = embed_chapters_file {
+        video::chapters::embed_chapters(input_video_file, embed_chapters_file, &chapters, overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn extract_frames_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::ExtractFrames { osd_args, timestamps, interval, format, scale, output_dir, overwrite, video_file } = command {
+        let selection = match interval {
+            Some(interval) => FrameSelection::Interval(*interval),
+            None => FrameSelection::Timestamps(timestamps.clone()),
+        };
+        let osd_settings = osd_args.osd_settings(video_file)?;
+        video::frame_extraction::extract_frames(video_file, output_dir.as_deref(), &selection, *format, *scale, osd_settings, *overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn calibrate_osd_sync_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::CalibrateOSDSync { osd_args, start, duration, max_shift, overwrite, video_file, output_video_file } = command {
+        let output_video_file = match output_video_file {
+            Some(output_video_file) => output_video_file.clone(),
+            None => {
+                let mut output_file_stem = video_file.file_stem().ok_or_else(|| anyhow!("video file has no file name"))?.to_os_string();
+                output_file_stem.push("_osd_sync");
+                video_file.with_file_name(output_file_stem).with_extension(video_file.extension().unwrap_or_default())
+            },
+        };
+        let osd_settings = osd_args.osd_settings(video_file)?
+            .ok_or_else(|| anyhow!("an OSD file is required, use --osd to find it automatically or --osd-file"))?;
+        video::osd_sync_calibration::calibrate_osd_sync(video_file, output_video_file, &osd_settings, *start, *duration, *max_shift,
+            *overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn stream_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::Stream { osd_args, input_format, capture_resolution, follow, video_encoder, video_bitrate, audio_bitrate, input, output_url } = command {
+        let mut live_streamer = LiveStreamer::new(input, output_url);
+        live_streamer
+            .set_input_format(input_format.clone())
+            .set_capture_resolution(capture_resolution.map(|resolution| resolution.dimensions()))
+            .set_follow(*follow)
+            .set_video_encoder(video_encoder.clone())
+            .set_video_bitrate(video_bitrate.clone())
+            .set_audio_bitrate(audio_bitrate.clone())
+            .set_osd(osd_args.osd_settings(input)?);
+        live_streamer.stream().await?;
+    }
+    Ok(())
+}
+
+async fn ground_station_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::GroundStation { font_options, input_format, capture_resolution, serial_port, baud_rate, firmware, record, no_display,
+            capture_device } = command {
+        let mut ground_station = GroundStation::new(capture_device, input_format, capture_resolution.dimensions(), serial_port,
+            font_options.font_dir()?);
+        ground_station
+            .set_baud_rate(*baud_rate)
+            .set_firmware(*firmware)
+            .set_font_ident(font_options.font_ident().map(|ident| ident.map(str::to_owned)))
+            .set_font_premultiplied_alpha(font_options.font_premultiplied_alpha())
+            .set_record_file(record.clone())
+            .set_display(! no_display);
+        ground_station.run().await?;
+    }
+    Ok(())
+}
+
+async fn render_flight_path_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::RenderFlightPath { width, height, line_width, color, osm_background, overwrite, osd_file, output_file } = command {
+        let output_file = match output_file {
+            Some(output_file) => output_file.clone(),
+            None => {
+                let mut output_file_stem = Path::new(osd_file.file_stem().ok_or_else(|| anyhow!("OSD file has no file name"))?).as_os_str().to_os_string();
+                output_file_stem.push("_flight_path");
+                osd_file.with_file_name(output_file_stem).with_extension("png")
+            },
+        };
+
+        let frames = osd::file::open(osd_file)?.frames()?;
+        let positions = flight_path::gps_positions(frames.iter(), frames.font_variant())?;
+        let last_frame_index = frames.last().map(|frame| frame.index()).unwrap_or(0);
+        log::info!("{} GPS positions found", positions.len());
+
+        let mut renderer = FlightPathRenderer::default();
+        renderer
+            .set_width(*width)
+            .set_height(*height)
+            .set_line_width(*line_width)
+            .set_color(*color)
+            .set_osm_background(*osm_background);
+        renderer.render(&positions, last_frame_index, &output_file, *overwrite).await?;
     }
     Ok(())
 }
 
-async fn fix_video_audio_command<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: &Option<Q>, overwrite: bool, sync: bool, volume: bool) -> anyhow::Result<()> {
+async fn export_mkv_with_osd_track_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::ExportMkvWithOSDTrack { osd_args, overwrite, video_file, output_file } = command {
+        let output_file = match output_file {
+            Some(output_file) => output_file.clone(),
+            None => {
+                let mut output_file_stem = video_file.file_stem().ok_or_else(|| anyhow!("video file has no file name"))?.to_os_string();
+                output_file_stem.push("_with_osd");
+                video_file.with_file_name(output_file_stem).with_extension("mkv")
+            },
+        };
+        let osd_settings = osd_args.osd_settings(video_file)?
+            .ok_or_else(|| anyhow!("an OSD file is required, use --osd to find it automatically or --osd-file"))?;
+        video::export_mkv_with_osd_track(video_file, output_file, &osd_settings, *overwrite).await?;
+    }
+    Ok(())
+}
+
+async fn fix_video_audio_command<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: &Option<Q>, overwrite: bool, sync: bool, volume: bool,
+        auto: bool, audio_adjustment_args: &AudioAdjustmentArgs) -> anyhow::Result<()> {
+    let audio_adjustments = audio_adjustment_args.audio_adjustments();
+    if auto {
+        let analysis = video::audio_analysis::analyze(&input_video_file).await?;
+        log::info!("audio peak level: {:.1} dBFS", analysis.peak_level());
+        if analysis.cut() {
+            log::warn!("the video does not look like an original recording, it may have been cut, fixing the audio sync would then make it worse");
+        }
+        match analysis.tempo() {
+            Some(tempo) => log::info!("audio drift: {:+.3}%, fixing the audio sync", (tempo - 1.0) * 100.0),
+            None => log::info!("the audio is in sync"),
+        }
+        match analysis.gain() {
+            Some(gain) => log::info!("fixing the audio volume with {gain:.1} dB gain"),
+            None => log::info!("the audio volume is normal"),
+        }
+        if ! analysis.is_fix_needed() && audio_adjustments.is_empty() {
+            log::info!("the audio does not need to be fixed");
+            return Ok(());
+        }
+        video::fix_dji_air_unit_audio(input_video_file, output_video_file, overwrite, None, &analysis.adjustments(&audio_adjustments)).await?;
+        return Ok(());
+    }
     let fix_type = match (sync, volume) {
         (true, true) | (false, false) => VideoAudioFixType::SyncAndVolume,
         (true, false) => VideoAudioFixType::Sync,
         (false, true) => VideoAudioFixType::Volume,
     };
-    video::fix_dji_air_unit_audio(input_video_file, output_video_file, overwrite, fix_type).await?;
+    video::fix_dji_air_unit_audio(input_video_file, output_video_file, overwrite, Some(fix_type), &audio_adjustments).await?;
+    Ok(())
+}
+
+async fn watch_command(command: &Commands) -> anyhow::Result<()> {
+    if let Commands::Watch { osd, osd_font_dir, fix_audio, preset, interval, output_dir, watched_dir } = command {
+        let mut settings = WatchSettings::new(watched_dir);
+        settings
+            .set_output_dir(output_dir.clone())
+            .set_poll_interval(Duration::from_secs(*interval))
+            .set_fix_audio(*fix_audio)
+            .set_encoding_settings(preset.map(|preset| preset.encoding_settings()).unwrap_or(DEFAULT_ENCODING_SETTINGS));
+        if *osd {
+            settings.set_osd_font_dir(Some(font_dir_base(osd_font_dir)?));
+        }
+        watch::watch(&settings).await?;
+    }
+    Ok(())
+}
+
+fn doctor_command(font_dir: &Option<PathBuf>) -> anyhow::Result<()> {
+    use hd_fpv_video_tool::doctor::{self, CheckStatus};
+    let results = doctor::run_checks(font_dir_base(font_dir));
+    for result in &results {
+        println!("[{:^7}] {}: {}", result.status(), result.name(), result.details());
+        if let Some(fix) = result.fix() {
+            println!("          fix: {fix}");
+        }
+    }
+    let error_count = results.iter().filter(|result| result.status() == CheckStatus::Error).count();
+    let warning_count = results.iter().filter(|result| result.status() == CheckStatus::Warning).count();
+    println!();
+    println!("{error_count} error(s), {warning_count} warning(s)");
+    if error_count > 0 {
+        return Err(anyhow!("environment check found {error_count} error(s)"));
+    }
     Ok(())
 }
 
@@ -188,21 +881,105 @@ async fn main() {
         .parse_filters(cli.log_level().to_string().as_str())
         .init();
 
+    hd_fpv_video_tool::process::set_dry_run(cli.dry_run());
+    hd_fpv_video_tool::process::handle_interrupt();
+    hd_fpv_video_tool::jobs::set_max_jobs(cli.jobs() as usize);
+    if let Some(log_file) = cli.log_file() {
+        if let Err(error) = hd_fpv_video_tool::ffmpeg::set_log_file(log_file) {
+            log::error!("failed opening log file: {error}");
+            exit(1);
+        }
+    }
+    if let Some(temp_dir) = cli.temp_dir() {
+        if let Err(error) = hd_fpv_video_tool::temp_dir::set_temp_dir(temp_dir) {
+            log::error!("failed setting temporary directory: {error}");
+            exit(1);
+        }
+    }
+
     let command_result = match &cli.command {
 
         command @ Commands::GenerateOverlayFrames {..} => generate_overlay_frames_command(command),
         command @ Commands::GenerateOverlayVideo {..} => generate_overlay_video_command(command).await,
+        command @ Commands::ConvertOverlay {..} => convert_overlay_command(command).await,
+        command @ Commands::OverlayFramesToVideo {..} => overlay_frames_to_video_command(command).await,
         command @ Commands::TranscodeVideo {..} => transcode_video_command(command).await,
-        Commands::DisplayOSDFileInfo { osd_file } => display_osd_file_info_command(osd_file),
+        Commands::DisplayOSDFileInfo { timeline, osd_file } => display_osd_file_info_command(osd_file, *timeline),
+        Commands::VideoInfo { json, video_file } => video_info_command(video_file, *json),
+
+        Commands::Osd { command: command @ OSDCommands::Edit {..} } => osd_edit_command(command),
+        Commands::Osd { command: OSDCommands::Stats { font_options, osd_file } } => osd_stats_command(font_options, osd_file),
+        Commands::Osd { command: OSDCommands::ExportJSON { osd_file, output_file, overwrite } } =>
+            osd_export_json_command(osd_file, output_file, *overwrite),
+        Commands::Osd { command: OSDCommands::ImportJSON { json_file, output_osd_file, overwrite } } =>
+            osd_import_json_command(json_file, output_osd_file, *overwrite),
+        Commands::Osd { command: command @ OSDCommands::Synthesize {..} } => osd_synthesize_command(command),
+        Commands::Osd { command: command @ OSDCommands::Record {..} } => osd_record_command(command).await,
+        Commands::Osd { command: OSDCommands::Verify { osd_file } } => osd_verify_command(osd_file),
+        Commands::Osd { command: OSDCommands::Repair { osd_file, output_osd_file, overwrite } } => osd_repair_command(osd_file, output_osd_file, *overwrite),
+        Commands::Osd { command: OSDCommands::Diff { max_listed_frames, first_osd_file, second_osd_file } } =>
+            osd_diff_command(first_osd_file, second_osd_file, *max_listed_frames),
+        Commands::Osd { command: command @ OSDCommands::PreviewGif {..} } => osd_preview_gif_command(command).await,
+        Commands::Osd { command: OSDCommands::DumpText { symbol_char, osd_file, output_file, overwrite } } =>
+            osd_dump_text_command(osd_file, output_file, *overwrite, *symbol_char),
+
+        Commands::Osd { command: OSDCommands::ExportCSV { items, updates, osd_file, output_file, overwrite } } =>
+            osd_export_csv_command(osd_file, output_file, *overwrite, items, *updates),
+
+        Commands::Fonts { command: FontsCommands::Preview { font_options, font_variant, pages, output_file, overwrite } } =>
+            fonts_preview_command(font_options, *font_variant, *pages, output_file, *overwrite),
+
+        Commands::CutVideo { start_end, trim_disarmed_args, cut_osd, mode, segments, join, resume, strip_metadata, input_video_file, output_video_file, overwrite } =>
+            if segments.is_empty() {
+                match trim_disarmed_args.start_end(input_video_file, start_end) {
+                    Ok(start_end) =>
+                        video::cut(input_video_file, output_video_file, *overwrite, &start_end, *cut_osd, *mode, *strip_metadata).await.map_err(anyhow::Error::new),
+                    Err(error) => Err(anyhow::Error::new(error)),
+                }
+            } else {
+                video::cut_segments(input_video_file, output_video_file, *overwrite, segments, *cut_osd, *mode, *join, *resume, *strip_metadata).await
+                    .map_err(anyhow::Error::new)
+            },
+
+        Commands::Flights { chapters, embed_chapters, overwrite, input_video_file } =>
+            flights_command(input_video_file, chapters, embed_chapters, *overwrite).await,
+
+        Commands::SplitFlights { padding, mode, strip_metadata, chapters, input_video_file, output_video_file, overwrite } =>
+            video::split_flights(input_video_file, output_video_file, *overwrite, *padding, *mode, *strip_metadata, *chapters).await
+                .map_err(anyhow::Error::new),
+
+        Commands::ConcatVideos { crossfade, reencode, output_video_file, input_video_files, overwrite } =>
+            video::concat::concat_videos(input_video_files, output_video_file, *overwrite, *crossfade, *reencode).await
+                .map_err(anyhow::Error::new),
+
+        command @ Commands::Remux {..} => remux_command(command).await,
+        command @ Commands::RepairVideo {..} => repair_video_command(command).await,
+
+        Commands::FixVideoAudio { input_video_file, output_video_file, overwrite, sync, volume, auto, audio_adjustment_args } =>
+            fix_video_audio_command(input_video_file, output_video_file, *overwrite, *sync, *volume, *auto, audio_adjustment_args).await,
+
+        command @ Commands::GenerateThumbnail {..} => generate_thumbnail_command(command).await,
+
+        command @ Commands::ExtractFrames {..} => extract_frames_command(command).await,
+
+        command @ Commands::ExportMkvWithOSDTrack {..} => export_mkv_with_osd_track_command(command).await,
+
+        Commands::PlayVideoWithOSD { live, save_frame_shift, osd_args, video_file, osd_video_file } =>
+            play_video_with_osd_command(*live, save_frame_shift, osd_args, video_file, osd_video_file).await,
+
+        command @ Commands::CalibrateOSDSync {..} => calibrate_osd_sync_command(command).await,
+
+        command @ Commands::Stream {..} => stream_command(command).await,
+
+        command @ Commands::GroundStation {..} => ground_station_command(command).await,
+
+        command @ Commands::RenderFlightPath {..} => render_flight_path_command(command).await,
 
-        Commands::CutVideo { start_end, input_video_file, output_video_file, overwrite } =>
-            video::cut(input_video_file, output_video_file, *overwrite, start_end).await.map_err(anyhow::Error::new),
+        command @ Commands::Watch {..} => watch_command(command).await,
 
-        Commands::FixVideoAudio { input_video_file, output_video_file, overwrite, sync, volume } =>
-            fix_video_audio_command(input_video_file, output_video_file, *overwrite, *sync, *volume).await,
+        Commands::Interactive { osd_font_dir, dir } => interactive::interactive_command(dir, osd_font_dir).await,
 
-        Commands::PlayVideoWithOSD { video_file, osd_video_file } =>
-            video::play_with_osd(video_file, osd_video_file).map_err(anyhow::Error::new),
+        Commands::Doctor { font_dir } => doctor_command(font_dir),
 
         Commands::GenerateShellAutocompletionFiles { shell } => generate_shell_autocompletion_files_command(shell),
 
@@ -0,0 +1,586 @@
+
+//! Betaflight blackbox logs (`.bbl`/`.bfl` files) recorded by the flight controller
+//!
+//! A file can contain several logs, one per arming when logging while armed. Each log starts with text header lines
+//! describing the fields of each kind of frame followed by the binary frames. Only the main frames (intra `I` and
+//! inter `P` frames) are kept, the slow `S` frames are only used to find when the quad was armed and the GPS and
+//! event frames are skipped.
+
+use std::collections::HashMap;
+use std::io::Error as IOError;
+use std::path::Path;
+
+use derive_more::From;
+use getset::{CopyGetters, Getters};
+use thiserror::Error;
+
+
+const LOG_START_MARKER: &[u8] = b"H Product:Blackbox flight data recorder by Nicholas Sherlock\n";
+
+const FRAME_MARKERS: &[u8] = b"IPSGHE";
+
+/// bit of the `flightModeFlags` slow frame field set while the arm switch is on
+const ARM_FLIGHT_MODE_FLAG: i64 = 1;
+
+#[derive(Debug, Error, From)]
+pub enum BlackboxError {
+    #[error("failed reading blackbox log file: {0}")]
+    IOError(IOError),
+    #[error("not a blackbox log file, no log found")]
+    NoLog,
+    #[error("blackbox log {index} not found, the file contains {count} log(s)")]
+    #[from(ignore)]
+    LogNotFound { index: usize, count: usize },
+    #[error("invalid blackbox log header: {0}")]
+    #[from(ignore)]
+    InvalidHeader(String),
+    #[error("blackbox log has no `{0}` field")]
+    #[from(ignore)]
+    MissingField(String),
+}
+
+/// predictors of the field values from the previously decoded values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Predictor {
+    Zero,
+    Previous,
+    StraightLine,
+    Average2,
+    MinThrottle,
+    Motor0,
+    Increment,
+    Constant1500,
+    VBatRef,
+    MinMotor,
+    /// GPS predictors, the GPS frames are skipped
+    Other,
+}
+
+impl Predictor {
+    fn from_header_value(value: i64) -> Self {
+        use Predictor::*;
+        match value {
+            0 => Zero,
+            1 => Previous,
+            2 => StraightLine,
+            3 => Average2,
+            4 => MinThrottle,
+            5 => Motor0,
+            6 => Increment,
+            8 => Constant1500,
+            9 => VBatRef,
+            11 => MinMotor,
+            _ => Other,
+        }
+    }
+}
+
+/// how the field values are encoded in the frames, the tagged encodings group several fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    SignedVB,
+    UnsignedVB,
+    Neg14Bit,
+    Tag8_8SVB,
+    Tag2_3S32,
+    Tag8_4S16,
+    Null,
+    Tag2_3SVariable,
+}
+
+impl Encoding {
+    fn from_header_value(value: i64) -> Option<Self> {
+        use Encoding::*;
+        Some(match value {
+            0 => SignedVB,
+            1 => UnsignedVB,
+            3 => Neg14Bit,
+            6 => Tag8_8SVB,
+            7 => Tag2_3S32,
+            8 => Tag8_4S16,
+            9 => Null,
+            10 => Tag2_3SVariable,
+            _ => return None,
+        })
+    }
+}
+
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    let shift = 32 - bits;
+    ((value << shift) as i32 >> shift) as i64
+}
+
+/// cursor over the binary frames
+struct FrameReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> FrameReader<'a> {
+
+    fn read_byte(&mut self) -> Option<u8> {
+        let byte = *self.data.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn peek_byte(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn read_unsigned_vb(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.read_byte()?;
+            value |= ((byte & 0x7F) as u32) << shift;
+            if byte & 0x80 == 0 { return Some(value) }
+        }
+        None
+    }
+
+    fn read_signed_vb(&mut self) -> Option<i64> {
+        let value = self.read_unsigned_vb()?;
+        Some(((value >> 1) as i32 ^ -((value & 1) as i32)) as i64)
+    }
+
+    /// reads the values of the fields grouped by the encoding into `values`
+    fn read_group(&mut self, encoding: Encoding, values: &mut [i64]) -> Option<()> {
+        use Encoding::*;
+        match encoding {
+            SignedVB => values[0] = self.read_signed_vb()?,
+            UnsignedVB => values[0] = self.read_unsigned_vb()? as i64,
+            Neg14Bit => values[0] = -sign_extend(self.read_unsigned_vb()?, 14),
+            Null => values[0] = 0,
+            Tag8_8SVB => {
+                if values.len() == 1 {
+                    values[0] = self.read_signed_vb()?;
+                } else {
+                    let header = self.read_byte()?;
+                    for (index, value) in values.iter_mut().enumerate() {
+                        *value = if header & (1 << index) != 0 { self.read_signed_vb()? } else { 0 };
+                    }
+                }
+            },
+            Tag2_3S32 => {
+                let lead = self.read_byte()? as u32;
+                match lead >> 6 {
+                    0 => {
+                        values[0] = sign_extend((lead >> 4) & 0x03, 2);
+                        values[1] = sign_extend((lead >> 2) & 0x03, 2);
+                        values[2] = sign_extend(lead & 0x03, 2);
+                    },
+                    1 => {
+                        values[0] = sign_extend(lead & 0x0F, 4);
+                        let byte = self.read_byte()? as u32;
+                        values[1] = sign_extend(byte >> 4, 4);
+                        values[2] = sign_extend(byte & 0x0F, 4);
+                    },
+                    2 => {
+                        values[0] = sign_extend(lead & 0x3F, 6);
+                        values[1] = sign_extend(self.read_byte()? as u32 & 0x3F, 6);
+                        values[2] = sign_extend(self.read_byte()? as u32 & 0x3F, 6);
+                    },
+                    _ => self.read_sized_values(lead, values)?,
+                }
+            },
+            Tag2_3SVariable => {
+                let lead = self.read_byte()? as u32;
+                match lead >> 6 {
+                    0 => {
+                        values[0] = sign_extend((lead >> 4) & 0x03, 2);
+                        values[1] = sign_extend((lead >> 2) & 0x03, 2);
+                        values[2] = sign_extend(lead & 0x03, 2);
+                    },
+                    1 => {
+                        let byte = self.read_byte()? as u32;
+                        values[0] = sign_extend((lead & 0x3E) >> 1, 5);
+                        values[1] = sign_extend(((lead & 0x01) << 4) | (byte >> 4), 5);
+                        values[2] = sign_extend(byte & 0x0F, 4);
+                    },
+                    2 => {
+                        let (byte1, byte2) = (self.read_byte()? as u32, self.read_byte()? as u32);
+                        values[0] = sign_extend(((lead & 0x3F) << 2) | (byte1 >> 6), 8);
+                        values[1] = sign_extend(((byte1 & 0x3F) << 1) | (byte2 >> 7), 7);
+                        values[2] = sign_extend(byte2 & 0x7F, 7);
+                    },
+                    _ => self.read_sized_values(lead, values)?,
+                }
+            },
+            Tag8_4S16 => {
+                let mut selectors = self.read_byte()?;
+                let mut nibble_buffer = None;
+                for value in values.iter_mut() {
+                    *value = match selectors & 0x03 {
+                        0 => 0,
+                        1 => match nibble_buffer.take() {
+                            None => {
+                                let byte = self.read_byte()?;
+                                nibble_buffer = Some(byte);
+                                sign_extend(byte as u32 >> 4, 4)
+                            },
+                            Some(byte) => sign_extend(byte as u32 & 0x0F, 4),
+                        },
+                        2 => match nibble_buffer {
+                            None => sign_extend(self.read_byte()? as u32, 8),
+                            Some(byte) => {
+                                let next_byte = self.read_byte()?;
+                                nibble_buffer = Some(next_byte);
+                                sign_extend(((byte as u32 & 0x0F) << 4) | (next_byte as u32 >> 4), 8)
+                            },
+                        },
+                        _ => match nibble_buffer {
+                            None => {
+                                let (byte1, byte2) = (self.read_byte()? as u32, self.read_byte()? as u32);
+                                sign_extend((byte1 << 8) | byte2, 16)
+                            },
+                            Some(byte) => {
+                                let (byte1, byte2) = (self.read_byte()?, self.read_byte()?);
+                                nibble_buffer = Some(byte2);
+                                sign_extend(((byte as u32 & 0x0F) << 12) | ((byte1 as u32) << 4) | (byte2 as u32 >> 4), 16)
+                            },
+                        },
+                    };
+                    selectors >>= 2;
+                }
+            },
+        }
+        Some(())
+    }
+
+    /// 3 values whose sizes of 8, 16, 24 or 32 bits are given by 2 bits each of the lead byte, stored little endian
+    fn read_sized_values(&mut self, lead: u32, values: &mut [i64]) -> Option<()> {
+        for (index, value) in values.iter_mut().enumerate() {
+            let byte_count = ((lead >> (index * 2)) & 0x03) + 1;
+            let mut raw = 0u32;
+            for byte_index in 0..byte_count {
+                raw |= (self.read_byte()? as u32) << (byte_index * 8);
+            }
+            *value = sign_extend(raw, byte_count * 8);
+        }
+        Some(())
+    }
+
+}
+
+/// definitions of the fields of a kind of frame read from the `Field <kind> ...` header lines
+#[derive(Debug, Clone, Default)]
+struct FieldDefinitions {
+    names: Vec<String>,
+    predictors: Vec<Predictor>,
+    encodings: Vec<Encoding>,
+}
+
+impl FieldDefinitions {
+
+    fn parse(headers: &HashMap<String, String>, kind: char, names_kind: char) -> Result<Option<Self>, BlackboxError> {
+        let header = |name: &str| headers.get(&format!("Field {kind} {name}"));
+        let names = match headers.get(&format!("Field {names_kind} name")) {
+            Some(names) => names.split(',').map(str::to_owned).collect::<Vec<_>>(),
+            None => return Ok(None),
+        };
+        let numbers = |name: &str| -> Result<Vec<i64>, BlackboxError> {
+            let invalid = || BlackboxError::InvalidHeader(format!("Field {kind} {name}"));
+            let values = header(name).ok_or_else(invalid)?;
+            let numbers = values.split(',').map(|value| value.trim().parse::<i64>()).collect::<Result<Vec<_>, _>>().map_err(|_| invalid())?;
+            if numbers.len() != names.len() { return Err(invalid()) }
+            Ok(numbers)
+        };
+        let predictors = numbers("predictor")?.into_iter().map(Predictor::from_header_value).collect();
+        let encodings = numbers("encoding")?.into_iter()
+            .map(|value| Encoding::from_header_value(value).ok_or_else(|| BlackboxError::InvalidHeader(format!("unknown field encoding: {value}"))))
+            .collect::<Result<_, _>>()?;
+        Ok(Some(Self { names, predictors, encodings }))
+    }
+
+    /// raw values of the fields before the predictions are applied
+    fn read_raw_values(&self, reader: &mut FrameReader) -> Option<Vec<i64>> {
+        let mut values = vec![0; self.encodings.len()];
+        let mut index = 0;
+        while index < values.len() {
+            let encoding = self.encodings[index];
+            let group_size = match encoding {
+                Encoding::Tag8_8SVB => self.encodings[index..].iter().take(8).take_while(|field_encoding| **field_encoding == encoding).count(),
+                Encoding::Tag2_3S32 | Encoding::Tag2_3SVariable => 3,
+                Encoding::Tag8_4S16 => 4,
+                _ => 1,
+            };
+            let mut group = vec![0; group_size];
+            reader.read_group(encoding, &mut group)?;
+            let group_end = (index + group_size).min(values.len());
+            values[index..group_end].copy_from_slice(&group[..group_end - index]);
+            index += group_size;
+        }
+        Some(values)
+    }
+
+    /// applies the predictors to the raw values, `skipped_frames` is the number of loop iterations not logged since
+    /// the previous frame
+    fn predict(&self, mut values: Vec<i64>, history: &History, skipped_frames: i64, context: &PredictionContext) -> Vec<i64> {
+        for (index, predictor) in self.predictors.iter().enumerate() {
+            let previous = history.previous.as_ref().map(|previous| previous[index]).unwrap_or(0);
+            let previous_2 = history.previous_2.as_ref().map(|previous_2| previous_2[index]).unwrap_or(0);
+            use Predictor::*;
+            let prediction = match predictor {
+                Zero | Other => 0,
+                Previous => previous,
+                StraightLine => 2 * previous - previous_2,
+                Average2 => (previous + previous_2) / 2,
+                MinThrottle => context.min_throttle,
+                Motor0 => context.motor_0_index.filter(|motor_0_index| *motor_0_index < index).map(|motor_0_index| values[motor_0_index]).unwrap_or(0),
+                Increment => previous + skipped_frames + 1,
+                Constant1500 => 1500,
+                VBatRef => context.vbat_ref,
+                MinMotor => context.min_motor,
+            };
+            values[index] += prediction;
+        }
+        values
+    }
+
+}
+
+/// header values used by the predictors
+#[derive(Debug, Clone, Copy)]
+struct PredictionContext {
+    min_throttle: i64,
+    min_motor: i64,
+    vbat_ref: i64,
+    motor_0_index: Option<usize>,
+}
+
+/// main frames history used by the predictors: the last two frames
+#[derive(Default)]
+struct History {
+    previous: Option<Vec<i64>>,
+    previous_2: Option<Vec<i64>>,
+}
+
+/// Log of a single flight, the main frames values are stored by field in the order of the field names
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct Log {
+    #[getset(get = "pub")]
+    headers: HashMap<String, String>,
+    #[getset(get = "pub")]
+    field_names: Vec<String>,
+    #[getset(get = "pub")]
+    frames: Vec<Vec<i64>>,
+    /// time in microseconds of the first main frame after the arm switch was seen on
+    #[getset(get_copy = "pub")]
+    arm_time: Option<i64>,
+}
+
+impl Log {
+
+    fn parse(data: &[u8]) -> Result<Self, BlackboxError> {
+        let mut headers = HashMap::new();
+        let mut position = 0;
+        while data[position..].starts_with(b"H ") {
+            let line_end = data[position..].iter().position(|byte| *byte == b'\n').map(|index| position + index).unwrap_or(data.len());
+            let line = String::from_utf8_lossy(&data[position + 2..line_end]);
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_owned(), value.trim().to_owned());
+            }
+            position = (line_end + 1).min(data.len());
+        }
+
+        let intra_fields = FieldDefinitions::parse(&headers, 'I', 'I')?
+            .ok_or_else(|| BlackboxError::InvalidHeader("no main frame fields".to_owned()))?;
+        let inter_fields = FieldDefinitions::parse(&headers, 'P', 'I')?;
+        let slow_fields = FieldDefinitions::parse(&headers, 'S', 'S')?;
+        let gps_fields = FieldDefinitions::parse(&headers, 'G', 'G')?;
+        let gps_home_fields = FieldDefinitions::parse(&headers, 'H', 'H')?;
+
+        let header_number = |name: &str| headers.get(name).and_then(|value| value.split(',').next()?.trim().parse::<i64>().ok());
+        let context = PredictionContext {
+            min_throttle: header_number("minthrottle").unwrap_or(1150),
+            min_motor: header_number("motorOutput").unwrap_or(header_number("minthrottle").unwrap_or(1150)),
+            vbat_ref: header_number("vbatref").unwrap_or(4095),
+            motor_0_index: intra_fields.names.iter().position(|name| name == "motor[0]"),
+        };
+        let intra_interval = header_number("I interval").unwrap_or(1).max(1);
+        // either `<num>/<denom>` in older logs or the number of loop iterations between the frames
+        let (inter_num, inter_denom) = match headers.get("P interval").and_then(|value| value.split_once('/')) {
+            Some((num, denom)) => (num.trim().parse::<i64>().unwrap_or(1), denom.trim().parse::<i64>().unwrap_or(1)),
+            None => (1, header_number("P interval").unwrap_or(1)),
+        };
+        let (inter_num, inter_denom) = (inter_num.max(1), inter_denom.max(1));
+        let should_have_frame = |iteration: i64| (iteration % intra_interval + inter_num - 1) % inter_denom < inter_num;
+        let iteration_index = intra_fields.names.iter().position(|name| name == "loopIteration");
+        let time_index = intra_fields.names.iter().position(|name| name == "time");
+        let flight_mode_index = slow_fields.as_ref().and_then(|fields| fields.names.iter().position(|name| name == "flightModeFlags"));
+
+        let mut reader = FrameReader { data: &data[position..], position: 0 };
+        let mut frames = vec![];
+        let mut history = History::default();
+        let mut arm_seen = false;
+        let mut arm_time = None;
+        while let Some(marker) = reader.read_byte() {
+            let frame_start = reader.position - 1;
+            let frame = match marker {
+                b'I' => intra_fields.read_raw_values(&mut reader).map(|values| Some(intra_fields.predict(values, &History::default(), 0, &context))),
+                b'P' => match (&inter_fields, &history.previous) {
+                    (Some(inter_fields), Some(previous)) => {
+                        let mut skipped_frames = 0;
+                        if let Some(iteration_index) = iteration_index {
+                            while ! should_have_frame(previous[iteration_index] + 1 + skipped_frames) && skipped_frames < intra_interval {
+                                skipped_frames += 1;
+                            }
+                        }
+                        inter_fields.read_raw_values(&mut reader).map(|values| Some(inter_fields.predict(values, &history, skipped_frames, &context)))
+                    },
+                    _ => None,
+                },
+                b'S' => slow_fields.as_ref().and_then(|slow_fields| slow_fields.read_raw_values(&mut reader)).map(|values| {
+                    if let Some(flight_mode_index) = flight_mode_index {
+                        arm_seen |= values[flight_mode_index] & ARM_FLIGHT_MODE_FLAG != 0;
+                    }
+                    None
+                }),
+                b'G' => gps_fields.as_ref().and_then(|gps_fields| gps_fields.read_raw_values(&mut reader)).map(|_| None),
+                b'H' => gps_home_fields.as_ref().and_then(|gps_home_fields| gps_home_fields.read_raw_values(&mut reader)).map(|_| None),
+                b'E' => match Self::skip_event(&mut reader) {
+                    Some(true) => break,
+                    Some(false) => Some(None),
+                    None => None,
+                },
+                _ => None,
+            };
+            // a frame is only valid when followed by another frame or the end of the log, otherwise the data is
+            // corrupted and the frames are searched from the next byte, the predictions need a new intra frame
+            let next_is_frame = reader.peek_byte().map(|byte| FRAME_MARKERS.contains(&byte)).unwrap_or(true);
+            match frame {
+                Some(Some(frame)) if next_is_frame => {
+                    if arm_seen && arm_time.is_none() {
+                        arm_time = time_index.map(|time_index| frame[time_index]);
+                    }
+                    // the history before an intra frame is not used, the next inter frame is predicted from it only
+                    if marker == b'I' {
+                        history.previous_2 = Some(frame.clone());
+                        history.previous = Some(frame.clone());
+                    } else {
+                        history.previous_2 = history.previous.replace(frame.clone());
+                    }
+                    frames.push(frame);
+                },
+                Some(None) if next_is_frame => (),
+                _ => {
+                    log::debug!("skipping corrupted blackbox frame at offset {frame_start}");
+                    reader.position = frame_start + 1;
+                    history = History::default();
+                },
+            }
+        }
+
+        Ok(Self { headers, field_names: intra_fields.names, frames, arm_time })
+    }
+
+    /// skips the event frame data, returns true for the end of log event
+    fn skip_event(reader: &mut FrameReader) -> Option<bool> {
+        match reader.read_byte()? {
+            // sync beep
+            0 => { reader.read_unsigned_vb()?; },
+            // in-flight adjustment, the value is a float when the function has the high bit set
+            13 => if reader.read_byte()? & 0x80 != 0 {
+                for _ in 0..4 { reader.read_byte()?; }
+            } else {
+                reader.read_signed_vb()?;
+            },
+            // logging resume: loop iteration and time
+            14 => { reader.read_unsigned_vb()?; reader.read_unsigned_vb()?; },
+            // disarm: reason
+            15 => { reader.read_unsigned_vb()?; },
+            // flight mode: flags and previous flags
+            30 => { reader.read_unsigned_vb()?; reader.read_unsigned_vb()?; },
+            255 => return Some(true),
+            _ => return None,
+        }
+        Some(false)
+    }
+
+    pub fn field_index(&self, name: &str) -> Result<usize, BlackboxError> {
+        self.field_names.iter().position(|field_name| field_name == name).ok_or_else(|| BlackboxError::MissingField(name.to_owned()))
+    }
+
+    /// values of the field in the main frames, the `time` field is in microseconds since the flight controller was
+    /// powered on
+    pub fn field_values(&self, name: &str) -> Result<impl Iterator<Item = i64> + '_, BlackboxError> {
+        let index = self.field_index(name)?;
+        Ok(self.frames.iter().map(move |frame| frame[index]))
+    }
+
+}
+
+/// parses all the logs of the blackbox file content
+pub fn parse(data: &[u8]) -> Result<Vec<Log>, BlackboxError> {
+    let mut log_starts = vec![];
+    let mut position = 0;
+    while let Some(index) = data[position..].windows(LOG_START_MARKER.len()).position(|window| window == LOG_START_MARKER) {
+        log_starts.push(position + index);
+        position += index + LOG_START_MARKER.len();
+    }
+    if log_starts.is_empty() { return Err(BlackboxError::NoLog) }
+    log_starts.iter().enumerate().map(|(log_index, log_start)| {
+        let log_end = log_starts.get(log_index + 1).copied().unwrap_or(data.len());
+        Log::parse(&data[*log_start..log_end])
+    }).collect()
+}
+
+pub fn read<P: AsRef<Path>>(blackbox_file: P) -> Result<Vec<Log>, BlackboxError> {
+    parse(&fs_err::read(blackbox_file.as_ref())?)
+}
+
+/// reads the log with the 1 based index from the blackbox file
+pub fn read_log<P: AsRef<Path>>(blackbox_file: P, index: usize) -> Result<Log, BlackboxError> {
+    let mut logs = read(blackbox_file)?;
+    let count = logs.len();
+    if index == 0 || index > count { return Err(BlackboxError::LogNotFound { index, count }) }
+    Ok(logs.swap_remove(index - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_byte_values() {
+        let mut reader = FrameReader { data: &[0x96, 0x01, 0x03, 0x7F], position: 0 };
+        assert_eq!(reader.read_unsigned_vb(), Some(150));
+        assert_eq!(reader.read_signed_vb(), Some(-2));
+        assert_eq!(reader.read_signed_vb(), Some(-64));
+        assert_eq!(reader.read_unsigned_vb(), None);
+    }
+
+    #[test]
+    fn tag8_4s16_values() {
+        // selectors: 4 bits, 8 bits, 16 bits, zero
+        let mut reader = FrameReader { data: &[0b00_11_10_01, 0xF1, 0x23, 0x45, 0x60], position: 0 };
+        let mut values = [0; 4];
+        reader.read_group(Encoding::Tag8_4S16, &mut values).unwrap();
+        assert_eq!(values, [-1, 0x12, 0x3456, 0]);
+        assert_eq!(reader.position, 5);
+    }
+
+    #[test]
+    fn parse_log() {
+        let mut data = LOG_START_MARKER.to_vec();
+        data.extend_from_slice(b"H Field I name:loopIteration,time,rcCommand[0]\n\
+            H Field I signed:0,0,1\n\
+            H Field I predictor:0,0,0\n\
+            H Field I encoding:1,1,0\n\
+            H Field P predictor:6,2,1\n\
+            H Field P encoding:9,0,0\n\
+            H Field S name:flightModeFlags\n\
+            H Field S signed:0\n\
+            H Field S predictor:0\n\
+            H Field S encoding:1\n\
+            H I interval:32\n\
+            H P interval:1/1\n");
+        data.extend_from_slice(&[b'S', 0x01, b'I', 0x00, 0xE8, 0x07, 0x14, b'P', 0x02, 0x03, b'E', 0xFF]);
+        let logs = parse(&data).unwrap();
+        assert_eq!(logs.len(), 1);
+        let log = &logs[0];
+        assert_eq!(log.frames(), &vec![vec![0, 1000, 10], vec![1, 1001, 8]]);
+        assert_eq!(log.arm_time(), Some(1000));
+    }
+
+}
@@ -2,4 +2,14 @@
 pub mod font_options;
 pub mod transcode_video_args;
 pub mod generate_overlay_args;
-pub mod start_end_args;
\ No newline at end of file
+pub mod start_end_args;
+pub mod audio_adjustment_args;
+pub mod srt_telemetry_args;
+pub mod telemetry_args;
+pub mod trim_disarmed_args;
+pub mod osd_style_args;
+pub mod osd_graphs_args;
+pub mod osd_sticks_args;
+pub mod osd_script_args;
+pub mod overlay_video_encoder_args;
+pub mod value_enums;
\ No newline at end of file
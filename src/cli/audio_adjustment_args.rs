@@ -0,0 +1,27 @@
+use clap::Args;
+use getset::CopyGetters;
+
+use crate::video::AudioAdjustments;
+
+
+#[derive(Args, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct AudioAdjustmentArgs {
+
+    /// change the audio volume by this gain in dB, replaces the volume fix when the DJI AU audio volume is also fixed
+    #[clap(long, value_parser, value_name = "dB", allow_negative_numbers(true))]
+    audio_gain: Option<f64>,
+
+    /// delay the audio by this number of milliseconds, negative values make the audio play earlier
+    #[clap(long, value_parser, value_name = "ms", allow_negative_numbers(true))]
+    audio_delay: Option<i64>,
+
+}
+
+impl AudioAdjustmentArgs {
+
+    pub fn audio_adjustments(&self) -> AudioAdjustments {
+        AudioAdjustments::new(self.audio_gain, self.audio_delay)
+    }
+
+}
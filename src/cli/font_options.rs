@@ -18,6 +18,10 @@ pub struct FontOptions {
     /// force using this font identifier when loading fonts, default is automatic
     #[clap(short = 'i', long, value_parser, value_name = "ident")]
     font_ident: Option<String>,
+
+    /// the color channels of the font tiles are premultiplied by their alpha, as exported by some color icon packs
+    #[clap(long, value_parser)]
+    font_premultiplied_alpha: bool,
 }
 
 #[derive(Args)]
@@ -29,6 +33,10 @@ pub struct OSDFontOptions {
     /// force using this font identifier when loading fonts, default is automatic
     #[clap(short = 'i', long, value_parser, value_name = "ident")]
     osd_font_ident: Option<String>,
+
+    /// the color channels of the font tiles are premultiplied by their alpha, as exported by some color icon packs
+    #[clap(long, value_parser)]
+    osd_font_premultiplied_alpha: bool,
 }
 
 #[derive(Debug, Error, From)]
@@ -42,7 +50,8 @@ pub enum OSDFontDirError {
     },
 }
 
-fn font_dir_base(font_dir: &Option<PathBuf>) -> Result<PathBuf, OSDFontDirError> {
+/// Resolves the font directory: the provided path, then the DJI_OSD_FONTS_DIR environment variable, then the default directory in the home directory
+pub fn font_dir_base(font_dir: &Option<PathBuf>) -> Result<PathBuf, OSDFontDirError> {
     let font_dir = match font_dir {
         Some(font_dir) => font_dir.clone(),
         None => {
@@ -73,6 +82,10 @@ impl FontOptions {
         }
     }
 
+    pub fn font_premultiplied_alpha(&self) -> bool {
+        self.font_premultiplied_alpha
+    }
+
 }
 
 impl OSDFontOptions {
@@ -89,4 +102,8 @@ impl OSDFontOptions {
         }
     }
 
+    pub fn osd_font_premultiplied_alpha(&self) -> bool {
+        self.osd_font_premultiplied_alpha
+    }
+
 }
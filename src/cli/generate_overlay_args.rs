@@ -6,9 +6,11 @@ use anyhow::anyhow;
 use itertools::Itertools;
 use strum::IntoEnumIterator;
 
-use crate::{prelude::ScalingArgs, video, osd::{item::LocationData, font_variant::FontVariant}};
+use ffmpeg_next::Rational;
 
-use super::{font_options::FontOptions, start_end_args::StartEndArgs};
+use crate::{prelude::ScalingArgs, video::{self, timestamp::StartEndOverlayFrameIndex}, osd::{item::LocationData, font_variant::FontVariant, frame_shift::FrameShift}};
+
+use super::{font_options::FontOptions, start_end_args::StartEndArgs, osd_style_args::StyleArgs};
 use crate::osd;
 
 
@@ -43,17 +45,27 @@ pub struct GenerateOverlayArgs {
     #[clap(flatten)]
     font_options: FontOptions,
 
+    #[clap(flatten)]
+    style_args: StyleArgs,
+
+    /// method used to copy the OSD tiles onto the overlay frames
+    #[clap(long, value_parser, value_enum, default_value_t = osd::overlay::TileBlitMethod::default())]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    tile_blit_method: osd::overlay::TileBlitMethod,
+
     /// Shift the output by that number of frames. Use this option to sync the OSD to a particular video.
-    #[clap(short = 'o', long, value_parser, value_name = "frames", allow_negative_numbers(true))]
+    /// A duration in milliseconds can also be used, e.g. 120ms, it is converted into 60 FPS OSD frames.
+    #[clap(short = 'o', long, value_parser, value_name = "frames|<ms>ms", allow_hyphen_values(true))]
     #[getset(skip)]
-    frame_shift: Option<i32>,
+    frame_shift: Option<FrameShift>,
 
     /// path to FPV.WTF .osd file
     osd_file: PathBuf,
 
 }
 
-pub(crate) fn osd_hide_items_arg_help() -> StyledStr {
+pub fn osd_hide_items_arg_help() -> StyledStr {
     let mut help = indoc::indoc! {"
         hide items from the OSD
 
@@ -81,9 +93,25 @@ impl GenerateOverlayArgs {
         Ok(())
     }
 
+    /// returns the first and last overlay frame indices corresponding to the start/end timestamps.
+    /// The timestamps are converted using the target video file frame rate if it is provided else using 60 FPS.
+    pub fn frame_range(&self) -> anyhow::Result<(u32, Option<u32>)> {
+        let frame_rate = self.frame_rate()?;
+        Ok((self.start_end.start().start_frame_count(frame_rate), self.start_end.end().end_frame_index(frame_rate)))
+    }
+
+    /// frame rate of the target video file if it is provided else 60 FPS
+    fn frame_rate(&self) -> anyhow::Result<Rational> {
+        Ok(match &self.target_video_file {
+            Some(target_video_file) => video::probe(target_video_file)?.frame_rate(),
+            None => Rational::new(60, 1),
+        })
+    }
+
+    /// frame shift in OSD frames, a duration being converted at the OSD frame rate
     pub fn frame_shift(&self) -> anyhow::Result<i32> {
         Ok(match (self.frame_shift, &self.target_video_file) {
-            (Some(frame_shift), _) => frame_shift,
+            (Some(frame_shift), _) => frame_shift.osd_frames(),
             (None, Some(target_video_file)) => {
                 if video::probe(target_video_file)?.has_audio() {
                     let frame_shift = crate::osd::dji::AU_OSD_FRAME_SHIFT;
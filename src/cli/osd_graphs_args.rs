@@ -0,0 +1,33 @@
+use clap::Args;
+use getset::{CopyGetters, Getters};
+
+use crate::osd::overlay::graphs::{GraphedItem, GraphsPosition, GraphsSettings};
+
+
+#[derive(Args, Getters, CopyGetters)]
+pub struct GraphsArgs {
+
+    /// draw small graphs of OSD item values over the last 30 seconds next to the OSD
+    ///
+    /// The values are read from the OSD so the items need to be displayed in the OSD and located for the font variant
+    /// of the OSD file, see --osd-hide-items for the located items.
+    #[clap(long, value_enum, value_delimiter = ',', value_name = "ITEMS")]
+    #[getset(get = "pub")]
+    osd_graphs: Vec<GraphedItem>,
+
+    /// corner of the OSD in which the graphs are drawn
+    #[clap(long, value_enum, default_value_t = GraphsPosition::default(), requires = "osd_graphs")]
+    #[getset(get_copy = "pub")]
+    osd_graphs_position: GraphsPosition,
+
+}
+
+impl GraphsArgs {
+
+    pub fn settings(&self) -> GraphsSettings {
+        let mut settings = GraphsSettings::default();
+        settings.set_items(self.osd_graphs.clone()).set_position(self.osd_graphs_position);
+        settings
+    }
+
+}
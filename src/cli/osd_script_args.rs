@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use getset::Getters;
+
+
+#[derive(Args, Getters)]
+#[getset(get = "pub")]
+pub struct ScriptArgs {
+
+    /// draw custom widgets defined by a Rhai script on top of the OSD
+    ///
+    /// The script defines a `draw(canvas, frame)` function called for every frame with the OSD item values and the
+    /// flight telemetry values, see the README for the available drawing methods.
+    #[clap(long, value_parser, value_name = "SCRIPT_FILE")]
+    osd_script: Option<PathBuf>,
+
+}
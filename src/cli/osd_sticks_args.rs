@@ -0,0 +1,31 @@
+use clap::Args;
+use getset::CopyGetters;
+
+use crate::osd::overlay::sticks::{SticksPosition, SticksSettings};
+
+
+#[derive(Args, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct SticksArgs {
+
+    /// draw the stick positions read from the telemetry file next to the OSD
+    ///
+    /// The sticks are drawn for a mode 2 radio.
+    #[clap(long, value_parser, requires = "telemetry")]
+    osd_sticks: bool,
+
+    /// position of the sticks on the OSD
+    #[clap(long, value_enum, default_value_t = SticksPosition::default(), requires = "osd_sticks")]
+    osd_sticks_position: SticksPosition,
+
+}
+
+impl SticksArgs {
+
+    pub fn settings(&self) -> SticksSettings {
+        let mut settings = SticksSettings::default();
+        settings.set_enabled(self.osd_sticks).set_position(self.osd_sticks_position);
+        settings
+    }
+
+}
@@ -0,0 +1,73 @@
+use clap::Args;
+use getset::CopyGetters;
+use thiserror::Error;
+
+use crate::osd::overlay::style::{Color, ColorRemap, InvalidColorError, InvalidOpacityError, Outline, Style};
+
+
+#[derive(Debug, Error)]
+pub enum StyleArgsError {
+    #[error("invalid OSD outline width: {0}")]
+    InvalidOutlineWidth(String),
+    #[error(transparent)]
+    InvalidOutlineColor(#[from] InvalidColorError),
+    #[error(transparent)]
+    InvalidOpacity(#[from] InvalidOpacityError),
+}
+
+#[derive(Args, CopyGetters)]
+pub struct StyleArgs {
+
+    /// draw an outline of this width in pixels and color around the OSD characters, e.g. `--osd-outline 2 black`
+    ///
+    /// Useful to make the OSD readable over bright footage like snow or clouds. See --osd-background for the color format
+    #[clap(long, num_args = 2, value_names = ["px", "color[@alpha]"])]
+    osd_outline: Option<Vec<String>>,
+
+    /// opacity of the OSD in percent
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=100), value_name = "percent", default_value = "100")]
+    #[getset(get_copy = "pub")]
+    osd_opacity: u8,
+
+    /// draw a box of this color behind the OSD characters to improve readability, e.g. `black@0.4`
+    ///
+    /// The color is either a name (black, white, gray, red, green, blue, yellow) or an hexadecimal RRGGBB value
+    /// optionally followed by @ and the alpha value between 0 (transparent) and 1 (opaque)
+    #[clap(long, value_parser, value_name = "color[@alpha]")]
+    #[getset(get_copy = "pub")]
+    osd_background: Option<Color>,
+
+    /// tint the OSD characters with this color, white pixels become this color and black pixels stay black
+    ///
+    /// See --osd-background for the color format, the alpha is ignored
+    #[clap(long, value_parser, value_name = "color")]
+    #[getset(get_copy = "pub")]
+    osd_tint: Option<Color>,
+
+    /// replace the OSD characters pixels of a color with another color, e.g. `white=yellow`
+    ///
+    /// The parameter is a `,` separated list of remaps, the pixels with a color close to the first color are
+    /// remapped, before the tint is applied. See --osd-background for the color format
+    #[clap(long, value_parser, value_delimiter = ',', value_name = "color=color[@alpha]")]
+    osd_color_remap: Vec<ColorRemap>,
+
+}
+
+impl StyleArgs {
+
+    pub fn outline(&self) -> Result<Option<Outline>, StyleArgsError> {
+        Ok(match self.osd_outline.as_deref() {
+            Some([width, color]) => {
+                let width = width.parse::<u32>().map_err(|_| StyleArgsError::InvalidOutlineWidth(width.clone()))?;
+                Some(Outline::new(width, color.parse()?))
+            },
+            _ => None,
+        })
+    }
+
+    pub fn style(&self) -> Result<Style, StyleArgsError> {
+        Ok(Style::new(self.outline()?, self.osd_opacity, self.osd_background)?
+            .with_colors(self.osd_color_remap.clone(), self.osd_tint))
+    }
+
+}
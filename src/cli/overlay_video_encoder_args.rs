@@ -0,0 +1,41 @@
+use clap::Args;
+
+use crate::osd::overlay::{DEFAULT_OVERLAY_VIDEO_ENCODER_SPEED, OverlayVideoEncoderSpeed};
+
+
+/// speed related options of the VP8/VP9 overlay video encoders
+#[derive(Args, Debug, Clone, Copy)]
+pub struct OverlayVideoEncoderSpeedArgs {
+
+    /// number of threads used by the encoder, defaults to the number of CPUs
+    #[clap(long, value_parser, value_name = "count")]
+    encoder_threads: Option<u32>,
+
+    /// speed of the encoder (libvpx -cpu-used) from 0 (slowest, best quality) to 8 (fastest)
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=8), value_name = "speed", default_value_t = DEFAULT_OVERLAY_VIDEO_ENCODER_SPEED)]
+    encoder_speed: u8,
+
+    /// disable the row based multithreading of the VP9 encoder
+    #[clap(long, value_parser)]
+    no_row_mt: bool,
+
+    /// log2 of the number of tile columns used by the VP9 encoder, more tiles allow using more threads,
+    /// defaults to the libvpx default
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=6), value_name = "log2")]
+    tile_columns: Option<u8>,
+
+}
+
+impl OverlayVideoEncoderSpeedArgs {
+
+    pub fn settings(&self) -> OverlayVideoEncoderSpeed {
+        let mut settings = OverlayVideoEncoderSpeed::default();
+        settings
+            .set_encoder_threads(self.encoder_threads)
+            .set_encoder_speed(self.encoder_speed)
+            .set_no_row_mt(self.no_row_mt)
+            .set_tile_columns(self.tile_columns);
+        settings
+    }
+
+}
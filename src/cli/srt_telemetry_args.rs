@@ -0,0 +1,56 @@
+
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use crate::video::srt_telemetry::{self, SrtTelemetryError, SrtTelemetryPosition, SrtTelemetrySettings};
+
+
+#[derive(Args)]
+pub struct SrtTelemetryArgs {
+
+    /// draw the telemetry recorded by the DJI O3 or Walksnail goggles into the .srt file named after the video file
+    ///
+    /// The telemetry (signal, bitrate, latency, battery voltages...) is drawn as a line of text independently of
+    /// the OSD
+    #[clap(long, value_parser)]
+    srt_telemetry: bool,
+
+    /// path to the .srt file with the telemetry to draw onto the video
+    #[clap(long, value_parser, value_name = "SRT file path")]
+    srt_file: Option<PathBuf>,
+
+    /// format of the telemetry line with `{key}` placeholders replaced with the telemetry values, e.g. "{bitrate} {delay}"
+    ///
+    /// The keys are the ones of the .srt file, their case is ignored. By default the telemetry line is drawn as recorded
+    #[clap(long, value_parser, value_name = "FORMAT")]
+    srt_format: Option<String>,
+
+    /// position of the telemetry line on the video
+    #[clap(long, value_enum, default_value = "bottom")]
+    srt_position: SrtTelemetryPosition,
+
+    /// font size of the telemetry line, relative to a 288 lines high video
+    #[clap(long, value_parser, default_value_t = srt_telemetry::DEFAULT_FONT_SIZE)]
+    srt_font_size: u16,
+
+}
+
+impl SrtTelemetryArgs {
+
+    /// SRT telemetry settings corresponding to the arguments, `None` if drawing the telemetry was not requested
+    pub fn settings<P: AsRef<Path>>(&self, video_file_path: P) -> Result<Option<SrtTelemetrySettings>, SrtTelemetryError> {
+        let srt_file = match (self.srt_telemetry, &self.srt_file) {
+            (_, Some(srt_file)) => srt_file.clone(),
+            (true, None) => srt_telemetry::find_associated_to_video_file(video_file_path).ok_or(SrtTelemetryError::SrtFileNotFound)?,
+            (false, None) => return Ok(None),
+        };
+        let mut settings = SrtTelemetrySettings::new(srt_file);
+        settings
+            .set_format(self.srt_format.clone())
+            .set_position(self.srt_position)
+            .set_font_size(self.srt_font_size);
+        Ok(Some(settings))
+    }
+
+}
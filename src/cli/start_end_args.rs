@@ -9,12 +9,12 @@ use crate::video::timestamp::Timestamp;
 #[getset(get_copy = "pub")]
 pub struct StartEndArgs {
 
-    /// start timestamp
-    #[clap(long, value_parser, value_name = "[HH:]MM:SS")]
+    /// start timestamp, a number of seconds can also be used
+    #[clap(long, value_parser, value_name = "[[HH:]MM:]SS[.mmm]")]
     start: Option<Timestamp>,
 
-    /// end timestamp
-    #[clap(long, value_parser, value_name = "[HH:]MM:SS")]
+    /// end timestamp, a number of seconds can also be used
+    #[clap(long, value_parser, value_name = "[[HH:]MM:]SS[.mmm]")]
     end: Option<Timestamp>,
 
 }
@@ -25,6 +25,10 @@ pub struct StartGreaterThanEndError;
 
 impl StartEndArgs {
 
+    pub fn new(start: Option<Timestamp>, end: Option<Timestamp>) -> Self {
+        Self { start, end }
+    }
+
     pub fn are_valid(&self) -> bool {
         if let (Some(start), Some(end)) = (self.start, self.end) {
             return start < end;
@@ -0,0 +1,48 @@
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::telemetry::{TelemetrySettings, TelemetrySync};
+
+
+#[derive(Args)]
+pub struct TelemetryArgs {
+
+    /// flight telemetry file used by the OSD graphs and sticks: Betaflight blackbox log (.bbl/.bfl), EdgeTX log,
+    /// CSV file with a `time` column in seconds or GPX track
+    ///
+    /// The telemetry is aligned with the OSD on the arming of the quad, see --telemetry-sync. The OSD items which are
+    /// not displayed by the OSD are graphed from the telemetry.
+    #[clap(long, value_parser, value_name = "FILE")]
+    telemetry: Option<PathBuf>,
+
+    /// index of the log in the blackbox file starting at 1, a log is recorded for each arming
+    #[clap(long, value_parser, default_value_t = 1, requires = "telemetry", value_name = "INDEX")]
+    telemetry_log: usize,
+
+    /// how the telemetry is aligned with the OSD
+    #[clap(long, value_enum, default_value = "osd-arm", requires = "telemetry")]
+    telemetry_sync: TelemetrySync,
+
+    /// seconds added to the telemetry time matched with the OSD, increase it when the telemetry lags behind the video
+    #[clap(long, value_parser, allow_negative_numbers(true), default_value_t = 0.0, requires = "telemetry", value_name = "SECONDS")]
+    telemetry_offset: f64,
+
+}
+
+impl TelemetryArgs {
+
+    /// telemetry settings corresponding to the arguments, `None` if no telemetry file was provided
+    pub fn settings(&self) -> Option<TelemetrySettings> {
+        self.telemetry.as_ref().map(|telemetry_file| {
+            let mut settings = TelemetrySettings::new(telemetry_file);
+            settings
+                .set_log_index(self.telemetry_log)
+                .set_sync(self.telemetry_sync)
+                .set_offset(self.telemetry_offset);
+            settings
+        })
+    }
+
+}
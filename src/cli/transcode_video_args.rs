@@ -1,13 +1,30 @@
 
+use std::ffi::OsStr;
 use std::path::{PathBuf, Path};
 
 use clap::Args;
 use getset::{Getters, CopyGetters};
 use thiserror::Error;
 
-use crate::{osd::{self, overlay::scaling::OSDScalingArgs, file::find_associated_to_video_file}, video};
+use crate::{file, osd::{self, frame_shift::FrameShift, overlay::{composition::OSDSource, scaling::OSDScalingArgs}, file::pairing::{OSDFileDiscovery, find_osd_file}}, video::{self, encoder, resolution::OutputScale, TranscodeVideoError}};
+use crate::video::transcoder::{AudioMode, Transcoder, OSDSettings};
+use crate::video::watermark::{WatermarkPosition, WatermarkSettings};
+use crate::video::pip::PipSettings;
+use crate::video::text_annotation::{TextAnnotation, TextPosition};
+use crate::video::clock::{self, ClockSettings, ClockSource};
+use crate::video::layers::LayerConfig;
+use crate::video::undistort::LensProfile;
+use crate::video::vertical::VerticalSettings;
+use crate::video::container::Container;
+use crate::video::card::{self, Card, CardContent};
+use crate::ffmpeg::HwAccel;
+use crate::video::gpu::GpuApi;
+use crate::video::verify::{QualityMetric, QualitySettings};
 
-use super::{font_options::OSDFontOptions, start_end_args::StartEndArgs, generate_overlay_args};
+pub use crate::video::transcoder::{TranscodePreset, EncodingSettings, DEFAULT_ENCODING_SETTINGS, VideoRotation, DenoiseFilter};
+
+use super::{osd_graphs_args::GraphsArgs as OSDGraphsArgs, osd_sticks_args::SticksArgs as OSDSticksArgs, osd_script_args::ScriptArgs as OSDScriptArgs, osd_style_args::StyleArgs as OSDStyleArgs};
+use super::{font_options::OSDFontOptions, start_end_args::StartEndArgs, trim_disarmed_args::TrimDisarmedArgs, audio_adjustment_args::AudioAdjustmentArgs, srt_telemetry_args::SrtTelemetryArgs, telemetry_args::TelemetryArgs, generate_overlay_args};
 
 
 #[derive(Args, Getters, CopyGetters)]
@@ -21,9 +38,12 @@ pub struct TranscodeVideoOSDArgs {
     /// DJIG0000_something.mp4 => DJIG0000.osd{n}
     /// DJIU0000.mp4 => DJIG0000.osd{n}
     /// DJIU0000_something.mp4 => DJIG0000.osd{n}
-    #[clap(long, value_parser)]
+    /// The name and extension case is ignored. With --osd=auto if no file is found this way the OSD file of the
+    /// directory of the video file whose recording duration and modification time are the closest to the ones
+    /// of the video is used. The matched file is reported.
+    #[clap(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "name", value_name = "DISCOVERY")]
     #[getset(get_copy = "pub")]
-    osd: bool,
+    osd: Option<OSDFileDiscovery>,
 
     #[clap(flatten)]
     #[getset(get = "pub")]
@@ -33,10 +53,45 @@ pub struct TranscodeVideoOSDArgs {
     #[getset(get = "pub")]
     osd_font_options: OSDFontOptions,
 
-    /// shift frames to sync OSD with video
-    #[clap(short = 'o', long, value_parser, allow_negative_numbers(true), value_name = "frames")]
+    #[clap(flatten)]
+    #[getset(get = "pub")]
+    osd_style_args: OSDStyleArgs,
+
+    #[clap(flatten)]
+    #[getset(get = "pub")]
+    telemetry_args: TelemetryArgs,
+
+    #[clap(flatten)]
+    #[getset(get = "pub")]
+    osd_graphs_args: OSDGraphsArgs,
+
+    #[clap(flatten)]
+    #[getset(get = "pub")]
+    osd_sticks_args: OSDSticksArgs,
+
+    #[clap(flatten)]
+    #[getset(get = "pub")]
+    osd_script_args: OSDScriptArgs,
+
+    /// shift frames to sync OSD with video, a duration in milliseconds can also be used, e.g. 120ms
+    ///
+    /// The frames are OSD frames, counted at 60 FPS whatever the video frame rate
+    #[clap(short = 'o', long, value_parser, allow_hyphen_values(true), value_name = "frames|<ms>ms")]
+    #[getset(get_copy = "pub")]
+    osd_frame_shift: Option<FrameShift>,
+
+    /// blend the OSD frames between the OSD updates instead of holding each OSD frame until the next update
+    ///
+    /// Smoothes the moving OSD elements like the crosshair or the artificial horizon, the OSD frames are then
+    /// rendered at the video frame rate, e.g. 120 FPS.
+    #[clap(long, value_parser)]
+    #[getset(get_copy = "pub")]
+    osd_interpolate: bool,
+
+    /// method used to copy the OSD tiles onto the overlay frames
+    #[clap(long, value_parser, value_enum, default_value_t = osd::overlay::TileBlitMethod::default())]
     #[getset(get_copy = "pub")]
-    osd_frame_shift: Option<i32>,
+    osd_tile_blit_method: osd::overlay::TileBlitMethod,
 
     /// hide rectangular regions from the OSD
     ///
@@ -53,8 +108,16 @@ pub struct TranscodeVideoOSDArgs {
     osd_hide_items: Vec<String>,
 
     /// path to FPV.WTF .osd file to use to generate OSD frames to burn onto video
+    ///
+    /// The format is <OSD file>[|area=<area>][|shift=<frames|<ms>ms>]. With transcode-video the option can be
+    /// repeated to burn several OSD files onto a video composed of several videos, e.g. the OSD of two pilots onto a
+    /// split screen video with "pilot1.osd|area=left" and "pilot2.osd|area=right".{n}
+    /// The area is left, right, top, bottom, top-left, top-right, bottom-left, bottom-right or
+    /// <x>,<y>,<width>,<height> in percent of the video, the whole video by default. The OSD is scaled to fit its area
+    /// and centered in it. The shift overrides --osd-frame-shift for this OSD file. The telemetry widgets are only
+    /// drawn with the first OSD file, the other commands only use the first OSD file
     #[clap(long, value_parser, value_name = "OSD file path")]
-    osd_file: Option<PathBuf>,
+    osd_file: Vec<OSDSource>,
 }
 
 #[derive(Debug, Error)]
@@ -64,14 +127,41 @@ pub struct RequestedOSDButNoFileProvidedNorFound;
 impl TranscodeVideoOSDArgs {
 
     pub fn osd_file_path<P: AsRef<Path>>(&self, video_file_path: P) -> Result<Option<PathBuf>, RequestedOSDButNoFileProvidedNorFound> {
-        let osd_file_path = match (self.osd, &self.osd_file) {
-            (true, None) => Some(find_associated_to_video_file(video_file_path).ok_or(RequestedOSDButNoFileProvidedNorFound)?),
-            (_, Some(osd_file_path)) => Some(osd_file_path.clone()),
-            (false, None) => None,
+        let osd_file_path = match (self.osd, self.osd_file.first()) {
+            (Some(discovery), None) =>
+                Some(find_osd_file(video_file_path, discovery).ok_or(RequestedOSDButNoFileProvidedNorFound)?.into_osd_file()),
+            (_, Some(osd_source)) => Some(osd_source.file().clone()),
+            (None, None) => None,
         };
         Ok(osd_file_path)
     }
 
+    /// OSD settings corresponding to the arguments, `None` if burning the OSD was not requested
+    pub fn osd_settings<P: AsRef<Path>>(&self, video_file_path: P) -> Result<Option<OSDSettings>, TranscodeVideoError> {
+        let osd_file_path = match self.osd_file_path(video_file_path)? {
+            Some(osd_file_path) => osd_file_path,
+            None => return Ok(None),
+        };
+        let mut osd_settings = OSDSettings::new(osd_file_path, self.osd_font_options.osd_font_dir()?);
+        osd_settings
+            .set_font_ident(self.osd_font_options.osd_font_ident().map(|ident| ident.map(str::to_owned)))
+            .set_font_premultiplied_alpha(self.osd_font_options.osd_font_premultiplied_alpha())
+            .set_scaling(self.osd_scaling_args.settings()?)
+            .set_style(self.osd_style_args.style()?)
+            .set_frame_shift(self.osd_file.first().and_then(OSDSource::frame_shift).or(self.osd_frame_shift))
+            .set_area(self.osd_file.first().and_then(OSDSource::area))
+            .set_other_sources(self.osd_file.iter().skip(1).cloned().collect())
+            .set_hide_regions(self.osd_hide_regions.clone())
+            .set_hide_items(self.osd_hide_items.clone())
+            .set_telemetry(self.telemetry_args.settings())
+            .set_graphs(self.osd_graphs_args.settings())
+            .set_sticks(self.osd_sticks_args.settings())
+            .set_script(self.osd_script_args.osd_script().clone())
+            .set_interpolate(self.osd_interpolate)
+            .set_tile_blit_method(self.osd_tile_blit_method);
+        Ok(Some(osd_settings))
+    }
+
 }
 
 #[derive(Args, Getters, CopyGetters)]
@@ -95,22 +185,95 @@ pub struct TranscodeVideoArgs {
     #[getset(get_copy = "pub")]
     fix_audio_sync: bool,
 
+    #[clap(flatten)]
+    audio_adjustment_args: AudioAdjustmentArgs,
+
+    /// encoding settings preset
+    ///
+    /// Presets bundle the video encoder, constant quality setting, encoder preset, audio bitrate, faststart and key frame interval settings.
+    /// Any of these settings explicitly provided with the other options override the value from the preset.{n}
+    /// Without preset the defaults are: libx265 video encoder, 25M video bitrate, CRF 25, aac audio encoder with 192k bitrate
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    preset: Option<TranscodePreset>,
+
     /// video encoder to use
     ///
     /// This value is directly passed to the `-c:v` FFMpeg argument.{n}
     /// Run `ffmpeg -encoders` for a list of available encoders
-    #[clap(long, value_parser, default_value = "libx265")]
-    video_encoder: String,
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    video_encoder: Option<String>,
 
     /// video max bitrate
-    #[clap(long, value_parser, default_value = "25M")]
-    video_bitrate: String,
+    ///
+    /// This value is directly passed to the `-b:v` FFMpeg argument, examples: 8M, 2500k
+    #[clap(long, visible_alias = "bitrate", value_parser)]
+    #[getset(skip)]
+    video_bitrate: Option<String>,
 
     /// video constant quality setting
-    #[clap(long, value_parser, default_value_t = 25)]
+    ///
+    /// The valid range depends on the encoder, 0-51 for libx264/libx265, 0-63 for VP9/AV1.
+    /// Not supported by the NVENC encoders
+    #[clap(long, visible_alias = "crf", value_parser)]
+    #[getset(skip)]
+    video_crf: Option<u8>,
+
+    /// video encoder preset, trades encoding speed for compression efficiency
+    ///
+    /// This value is directly passed to the `-preset` FFMpeg argument.{n}
+    /// Examples: ultrafast to veryslow for libx264/libx265, p1 to p7 for NVENC encoders, 0 to 13 for libsvtav1
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    encoder_preset: Option<String>,
+
+    /// decode the input video on the GPU, e.g. for 4K H.265 videos whose decoding is too slow on the CPU
+    ///
+    /// Without value FFMpeg uses the first hardware decoding API available, the API can also be chosen, e.g.
+    /// --hw-decode=cuda for NVIDIA GPUs or --hw-decode=vaapi for AMD and Intel GPUs on Linux.
+    /// The decoded frames are copied back to the system memory for the filters and the encoder
+    #[clap(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "auto", value_name = "API")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    hw_decode: Option<HwAccel>,
+
+    /// experimental: decode the video, overlay the OSD and encode on the GPU without copying the video frames to
+    /// the system memory, which speeds up burning the OSD onto 4K videos
+    ///
+    /// Requires a hardware encoder of the same API, e.g. --video-encoder hevc_nvenc with cuda or hevc_vaapi with
+    /// vaapi, and an FFMpeg build with the overlay_cuda or overlay_vaapi filter. Only the scaling and the OSD are
+    /// supported, the other video filters and layers cannot be used.
+    #[clap(long, value_enum, conflicts_with = "hw_decode", value_name = "API")]
     #[getset(skip)]
     #[getset(get_copy = "pub")]
-    video_crf: u8,
+    gpu: Option<GpuApi>,
+
+    /// video encoder tuning
+    ///
+    /// This value is directly passed to the `-tune` FFMpeg argument.{n}
+    /// Examples: film, grain, animation for libx264, grain, animation for libx265, hq, ll for NVENC encoders
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    tune: Option<String>,
+
+    /// move the index of MP4/MOV files to the beginning so that they can be played while being downloaded,
+    /// enabled by the youtube and draft presets
+    #[clap(long, value_parser, conflicts_with = "no_faststart")]
+    #[getset(skip)]
+    faststart: bool,
+
+    /// do not move the index of MP4/MOV files to the beginning even if the preset does it
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    no_faststart: bool,
+
+    /// maximum interval between key frames in seconds so that the video can be seeked quickly when streamed,
+    /// 2 seconds with the youtube preset, encoder default otherwise
+    #[clap(long, value_parser, value_name = "seconds")]
+    #[getset(skip)]
+    keyframe_interval: Option<f64>,
 
     /// remove video defects
     ///
@@ -122,24 +285,284 @@ pub struct TranscodeVideoArgs {
     #[clap(long, value_parser, value_delimiter = ';', value_name = "REGIONS")]
     remove_video_defects: Vec<video::Region>,
 
+    /// crop the video before scaling it
+    ///
+    /// The format for the crop region is: <left_x>,<top_y>:<width>x<height>{n}
+    /// Coordinates are relative to the input video
+    #[clap(long, value_parser, value_name = "REGION")]
+    crop: Option<video::Region>,
+
+    /// detect the black bars padding the picture and crop them, e.g. 4:3 camera feeds recorded into 16:9 videos
+    ///
+    /// The bars are detected on a few samples spread over the transcoded section of the video. The OSD is then scaled
+    /// to the cropped video
+    #[clap(long, value_parser, conflicts_with = "crop")]
+    #[getset(skip)]
+    autocrop: bool,
+
+    /// scale the video to the given resolution, after cropping and rotating
+    ///
+    /// Standard resolutions keep the aspect ratio of the (cropped) input video.
+    /// When burning the OSD it is rendered at the output resolution
+    #[clap(long, value_parser, value_names = OutputScale::valid_list())]
+    scale: Option<OutputScale>,
+
+    /// rotate the camera video clockwise, the OSD is not rotated
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    rotate: Option<VideoRotation>,
+
+    /// flip the camera video horizontally, the OSD is not flipped
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    hflip: bool,
+
+    /// flip the camera video vertically, the OSD is not flipped
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    vflip: bool,
+
+    /// deinterlace the camera video, keeping its frame rate
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    deinterlace: bool,
+
+    /// remove the noise of the camera video, e.g. of low-light footage, 4 being a moderate strength
+    ///
+    /// The noise is removed before the OSD is overlaid so that the OSD stays sharp
+    #[clap(long, value_parser, value_name = "strength")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    denoise: Option<f64>,
+
+    /// filter used to remove the noise
+    #[clap(long, value_enum, default_value = "hqdn3d", requires = "denoise")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    denoise_filter: DenoiseFilter,
+
+    /// change the playback speed, lower than 1 for slow motion (e.g. 0.5) and greater than 1 for timelapse (e.g. 4)
+    ///
+    /// The OSD is overlaid before changing the speed so that it stays in sync with the video.
+    /// The audio tempo is changed to match the video speed
+    #[clap(long, value_parser, value_name = "factor")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    speed: Option<f64>,
+
+    /// correct the lens distortion of the camera video, the OSD is not changed
+    ///
+    /// The profile is a camera preset: dji-air-unit (also Caddx Vista), dji-o3, walksnail, gopro-wide; or custom
+    /// k1,k2 coefficients of the FFMpeg lenscorrection filter, e.g. "-0.25,0.03". Negative coefficients correct the
+    /// barrel distortion of wide angle lenses
+    #[clap(long, value_parser, allow_hyphen_values = true, value_name = "profile")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    undistort: Option<LensProfile>,
+
+    /// export a vertical 9:16 video for short video platforms, the OSD items which would be cropped off are moved
+    /// into the visible area
+    ///
+    /// The video is cropped after the crop, rotation and scaling, see --vertical-pan for the position of the crop
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    vertical: bool,
+
+    /// horizontal position of the vertical crop in percent, from -100 (left edge of the video) to 100 (right edge)
+    #[clap(long, value_parser = clap::value_parser!(i8).range(-100..=100), allow_negative_numbers(true), value_name = "percent", default_value = "0",
+        requires = "vertical")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    vertical_pan: i8,
+
+    /// grade the colors of the video with this 3D LUT file, e.g. a .cube file converting D-Cinelike footage to Rec.709
+    ///
+    /// The LUT is applied after the crop, rotation and scaling and before the OSD is overlaid so that the OSD colors
+    /// are not changed
+    #[clap(long, value_parser, value_name = "LUT file")]
+    lut: Option<PathBuf>,
+
+    /// composite this image, e.g. a PNG logo with transparency, onto the video in the same pass as the OSD
+    #[clap(long, value_parser, value_name = "image")]
+    watermark: Option<PathBuf>,
+
+    /// position of the watermark on the video
+    #[clap(long, value_enum, default_value = "bottom-right", requires = "watermark")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    watermark_position: WatermarkPosition,
+
+    /// opacity of the watermark in percent
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=100), value_name = "percent", default_value = "100", requires = "watermark")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    watermark_opacity: u8,
+
+    /// inset a second video, e.g. the feed of a GoPro or HD camera, over the video in the same pass as the OSD
+    ///
+    /// The format is <video>[:position[:size[:offset]]], e.g. "gopro.mp4:top-left:25:-3.5".{n}
+    /// Positions: top-left, top-right (default), bottom-left, bottom-right, center.{n}
+    /// The size is the width of the inset in percent of the video width, 30 by default.{n}
+    /// The offset is the position in seconds of the video at which the inset video starts, negative to skip the
+    /// beginning of the inset video, 0 by default. The inset is drawn below the OSD, see --layers. The audio of the
+    /// inset video is not used
+    #[clap(long, value_parser, value_name = "PIP")]
+    pip: Option<PipSettings>,
+
+    /// draw a text annotation like the pilot name or a title onto the video, can be repeated
+    ///
+    /// The format is <text>[|position=<position>][|size=<px>][|color=<color[@alpha]>][|time=[START]..[END]],
+    /// e.g. "PILOT: foo|position=top-left|time=..0:10".{n}
+    /// Positions: top-left, top, top-right, center, bottom-left (default), bottom, bottom-right.{n}
+    /// The default size is 1/20 of the video height. See --osd-background for the color format.
+    /// The time range is relative to the input video, by default the text is drawn during the whole video
+    #[clap(long, value_parser, value_name = "ANNOTATION")]
+    text: Vec<TextAnnotation>,
+
+    /// draw the wall-clock time of the recording, the recording start time plus the playback position
+    ///
+    /// The recording start time is taken from the creation time metadata of the video or from the modification time
+    /// of the file minus the video duration. By default the metadata is used when valid, the goggles without a set
+    /// clock writing dates in 1970. The time is drawn in the local time zone
+    #[clap(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "auto", value_name = "SOURCE")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    clock: Option<ClockSource>,
+
+    /// position of the wall-clock time on the video
+    #[clap(long, value_enum, default_value = "bottom-right", requires = "clock")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    clock_position: TextPosition,
+
+    /// strftime format of the wall-clock time
+    #[clap(long, value_parser, default_value = clock::DEFAULT_FORMAT, requires = "clock")]
+    clock_format: String,
+
+    /// z-order, opacity and blend mode of the layers drawn onto the video
+    ///
+    /// The parameter is a `;` separated list of layer settings.{n}
+    /// The format for a layer is: <layer>[|z=<z-order>][|opacity=<0-1>][|blend=<blend mode>], e.g. "watermark|z=-1|blend=screen".{n}
+    /// Layers (default z-order): pip (-10), osd (0), srt-telemetry (10), text (20), clock (30), watermark (40).{n}
+    /// OSD overlay layers ordered with the OSD tiles: graphs (1), sticks (2), script (3).{n}
+    /// Blend modes: normal (default), addition, screen, multiply, lighten, darken, overlay, difference.
+    /// The layers are drawn from the lowest to the highest z-order
+    #[clap(long, value_parser, value_delimiter = ';', value_name = "LAYERS")]
+    layers: Vec<LayerConfig>,
+
+    /// show a card before the video, either an image or lines of text
+    ///
+    /// The format is <image file>, text:<line>[|<line>...] or flight-stats, e.g. "text:Morning session|Bando #3".
+    /// The image is scaled to fit the video and centered on a black background, the text is drawn in white on a
+    /// black background. The flight-stats card shows the flight time, the maximum speed and altitude and the
+    /// capacity used during the flights of the transcoded section, read from the OSD file in the units of the OSD.
+    /// The card fades in and out and is encoded with the same settings as the video, with silence when the video has
+    /// audio
+    #[clap(long, value_parser, value_name = "CARD")]
+    intro_card: Option<CardContent>,
+
+    /// show a card after the video, e.g. "flight-stats" for a summary of the flight, see --intro-card for the format
+    #[clap(long, value_parser, value_name = "CARD")]
+    outro_card: Option<CardContent>,
+
+    /// duration of the intro and outro cards in seconds
+    #[clap(long, value_parser, value_name = "seconds", default_value_t = card::DEFAULT_DURATION)]
+    #[getset(skip)]
+    card_duration: f64,
+
+    /// container of the output video, inferred from the extension of the output file by default
+    ///
+    /// The container is checked against the extension of the output file and the codecs of the video and audio
+    /// encoders. The default output file has the extension of the container
+    #[clap(long, value_enum, value_name = "CONTAINER")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    container: Option<Container>,
+
+    /// do not copy the container metadata (creation time, GPS location, ...) and the modification time of the input
+    /// video to the output video
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    strip_metadata: bool,
+
+    /// transcode the video in chunks of this many minutes then join them so that an interrupted transcoding can be
+    /// resumed with `--resume`
+    ///
+    /// The chunks are written into a hidden directory next to the output file, or in the temporary directory if set,
+    /// which is removed once they are joined. Each chunk starts with a key frame
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..), value_name = "minutes")]
+    #[getset(skip)]
+    checkpoint_interval: Option<u32>,
+
+    /// resume an interrupted checkpointed transcoding, the chunks already completed are kept
+    #[clap(long, value_parser, requires = "checkpoint_interval")]
+    #[getset(skip)]
+    resume: bool,
+
+    /// once transcoded, check the duration, the number of frames and the audio sync of the output video, failing if
+    /// they are not the expected ones
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    verify: bool,
+
+    /// also measure the quality of the output video against the input video on a few samples, implies `--verify`
+    ///
+    /// The input video is scaled to the output resolution, the quality cannot be measured when the video is cropped,
+    /// rotated, flipped, undistorted or sped up. VMAF needs FFMpeg built with libvmaf
+    #[clap(long, value_enum, value_name = "METRIC")]
+    #[getset(skip)]
+    verify_quality: Option<QualityMetric>,
+
+    /// minimum quality score of each sample, 0.9 for SSIM and 80 for VMAF by default
+    #[clap(long, value_parser, value_name = "SCORE", requires = "verify_quality")]
+    #[getset(skip)]
+    verify_min_score: Option<f64>,
+
+    /// what to do with the audio: copy it without re-encoding, re-encode it or remove it
+    ///
+    /// The audio can only be fixed, adjusted or sped up when it is re-encoded
+    #[clap(long, value_enum, default_value = "reencode", value_name = "MODE")]
+    #[getset(skip)]
+    #[getset(get_copy = "pub")]
+    audio: AudioMode,
+
     /// audio encoder to use
     ///
     /// This value is directly passed to the `-c:a` FFMpeg argument.{n}
     /// Run `ffmpeg -encoders` for a list of available encoders
-    #[clap(long, value_parser, default_value = "libx265")]
-    audio_encoder: String,
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    audio_encoder: Option<String>,
 
     /// max audio bitrate
-    #[clap(long, value_parser, default_value = "25M")]
-    audio_bitrate: String,
+    #[clap(long, value_parser)]
+    #[getset(skip)]
+    audio_bitrate: Option<String>,
 
     #[clap(flatten)]
     start_end: StartEndArgs,
 
-    /// input video file path
+    #[clap(flatten)]
+    trim_disarmed_args: TrimDisarmedArgs,
+
+    #[clap(flatten)]
+    srt_telemetry_args: SrtTelemetryArgs,
+
+    /// input video file path or HTTP(S) URL
+    ///
+    /// For URLs the output file and the OSD file are looked for in the current directory,
+    /// named after the file name at the end of the URL
     input_video_file: PathBuf,
 
     /// output video file path
+    ///
+    /// Use `-` to write the video to the standard output in the matroska format, e.g. to pipe it into another tool
     #[getset(skip)]
     output_video_file: Option<PathBuf>,
 
@@ -160,6 +583,62 @@ pub enum OutputVideoFileError {
 
 impl TranscodeVideoArgs {
 
+    pub fn encoding_settings(&self) -> EncodingSettings {
+        self.preset.map(|preset| preset.encoding_settings()).unwrap_or(DEFAULT_ENCODING_SETTINGS)
+    }
+
+    pub fn video_encoder(&self) -> &str {
+        self.video_encoder.as_deref().unwrap_or_else(|| self.encoding_settings().video_encoder())
+    }
+
+    pub fn video_bitrate(&self) -> Option<&str> {
+        self.video_bitrate.as_deref().or_else(|| self.encoding_settings().video_bitrate())
+    }
+
+    /// returns the CRF value to use, the value from the preset is not used if the encoder does not support it
+    pub fn video_crf(&self) -> Option<u8> {
+        if self.video_crf.is_some() { return self.video_crf }
+        let default_crf = self.encoding_settings().video_crf();
+        match encoder::capabilities(self.video_encoder()) {
+            Some(capabilities) if ! capabilities.supports_crf(default_crf) => None,
+            _ => Some(default_crf),
+        }
+    }
+
+    /// returns the encoder preset to use, the value from the preset is only used if the encoder is known to support it
+    pub fn video_encoder_preset(&self) -> Option<&str> {
+        if self.encoder_preset.is_some() { return self.encoder_preset.as_deref() }
+        let default_preset = self.encoding_settings().video_encoder_preset()?;
+        match encoder::capabilities(self.video_encoder()) {
+            Some(capabilities) if capabilities.supports_preset(default_preset) => Some(default_preset),
+            _ => None,
+        }
+    }
+
+    pub fn video_encoder_tune(&self) -> Option<&str> {
+        self.tune.as_deref()
+    }
+
+    pub fn audio_encoder(&self) -> &str {
+        self.audio_encoder.as_deref().unwrap_or_else(|| self.encoding_settings().audio_encoder())
+    }
+
+    pub fn audio_bitrate(&self) -> &str {
+        self.audio_bitrate.as_deref().unwrap_or_else(|| self.encoding_settings().audio_bitrate())
+    }
+
+    pub fn faststart(&self) -> bool {
+        match (self.faststart, self.no_faststart) {
+            (true, _) => true,
+            (false, true) => false,
+            (false, false) => self.encoding_settings().faststart(),
+        }
+    }
+
+    pub fn keyframe_interval(&self) -> Option<f64> {
+        self.keyframe_interval.or_else(|| self.encoding_settings().keyframe_interval())
+    }
+
     pub fn video_audio_fix(&self) -> Option<video::AudioFixType> {
         use video::AudioFixType::*;
         match (self.fix_audio, self.fix_audio_sync, self.fix_audio_volume) {
@@ -170,6 +649,76 @@ impl TranscodeVideoArgs {
         }
     }
 
+    /// creates the transcoder corresponding to the arguments, burning the OSD if requested by the OSD arguments
+    pub fn transcoder(&self, osd_args: &TranscodeVideoOSDArgs) -> Result<Transcoder, TranscodeVideoError> {
+        let osd_settings = osd_args.osd_settings(&self.input_video_file)?;
+        let output_video_file = self.output_video_file(osd_settings.is_some())?;
+        let srt_telemetry_settings = self.srt_telemetry_args.settings(&self.input_video_file)?;
+        let clock_settings = self.clock.map(|clock_source| {
+            let mut clock_settings = ClockSettings::new(clock_source);
+            clock_settings.set_position(self.clock_position).set_format(self.clock_format.clone());
+            clock_settings
+        });
+        let watermark_settings = self.watermark.as_ref().map(|watermark| {
+            let mut watermark_settings = WatermarkSettings::new(watermark);
+            watermark_settings.set_position(self.watermark_position).set_opacity(self.watermark_opacity);
+            watermark_settings
+        });
+
+        let start_end = self.trim_disarmed_args.start_end(&self.input_video_file, &self.start_end)?;
+
+        let mut transcoder = Transcoder::new(&self.input_video_file, output_video_file);
+        transcoder
+            .set_overwrite(self.overwrite)
+            .set_hw_decode(self.hw_decode)
+            .set_gpu(self.gpu)
+            .set_start(start_end.start())
+            .set_end(start_end.end())
+            .set_video_encoder(self.video_encoder().to_owned())
+            .set_video_bitrate(self.video_bitrate().map(str::to_owned))
+            .set_video_crf(self.video_crf())
+            .set_video_encoder_preset(self.video_encoder_preset().map(str::to_owned))
+            .set_video_encoder_tune(self.tune.clone())
+            .set_audio_encoder(self.audio_encoder().to_owned())
+            .set_audio_bitrate(self.audio_bitrate().to_owned())
+            .set_audio(self.audio)
+            .set_strip_metadata(self.strip_metadata)
+            .set_container(self.container)
+            .set_faststart(self.faststart())
+            .set_keyframe_interval(self.keyframe_interval())
+            .set_audio_fix(self.video_audio_fix())
+            .set_audio_adjustments(self.audio_adjustment_args.audio_adjustments())
+            .set_remove_video_defects(self.remove_video_defects.clone())
+            .set_crop(self.crop.clone())
+            .set_autocrop(self.autocrop)
+            .set_scale(self.scale)
+            .set_rotate(self.rotate)
+            .set_hflip(self.hflip)
+            .set_vflip(self.vflip)
+            .set_deinterlace(self.deinterlace)
+            .set_denoise(self.denoise)
+            .set_denoise_filter(self.denoise_filter)
+            .set_speed(self.speed)
+            .set_undistort(self.undistort)
+            .set_lut(self.lut.clone())
+            .set_vertical(Some(VerticalSettings::new(self.vertical_pan)).filter(|_| self.vertical))
+            .set_osd(osd_settings)
+            .set_srt_telemetry(srt_telemetry_settings)
+            .set_watermark(watermark_settings)
+            .set_pip(self.pip.clone())
+            .set_text_annotations(self.text.clone())
+            .set_clock(clock_settings)
+            .set_layers(self.layers.clone())
+            .set_intro_card(self.intro_card.clone().map(|content| Card::new(content, self.card_duration)))
+            .set_outro_card(self.outro_card.clone().map(|content| Card::new(content, self.card_duration)))
+            .set_checkpoint_interval(self.checkpoint_interval.map(|minutes| f64::from(minutes) * 60.0))
+            .set_resume(self.resume)
+            .set_verify(self.verify || self.verify_quality.is_some())
+            .set_verify_quality(self.verify_quality.map(|metric| QualitySettings::new(metric, self.verify_min_score)));
+
+        Ok(transcoder)
+    }
+
     pub fn output_video_file_provided(&self) -> bool {
         self.output_video_file.is_some()
     }
@@ -178,11 +727,15 @@ impl TranscodeVideoArgs {
         Ok(match &self.output_video_file {
             Some(output_video_file) => output_video_file.clone(),
             None => {
-                let mut output_file_stem = Path::new(self.input_video_file.file_stem().ok_or(OutputVideoFileError::InputHasNoFileName)?).as_os_str().to_os_string();
+                let input_video_file = file::local_path(&self.input_video_file);
+                let mut output_file_stem = Path::new(input_video_file.file_stem().ok_or(OutputVideoFileError::InputHasNoFileName)?).as_os_str().to_os_string();
                 let suffix = if with_osd { "_with_osd" } else { "_transcoded" };
                 output_file_stem.push(suffix);
-                let input_file_extension = self.input_video_file.extension().ok_or(OutputVideoFileError::InputHasNoExtension)?;
-                self.input_video_file.with_file_name(output_file_stem).with_extension(input_file_extension)
+                let output_file_extension = match self.container {
+                    Some(container) => OsStr::new(container.extension()),
+                    None => input_video_file.extension().ok_or(OutputVideoFileError::InputHasNoExtension)?,
+                };
+                input_video_file.with_file_name(output_file_stem).with_extension(output_file_extension)
             }
         })
     }
@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use clap::Args;
+use getset::CopyGetters;
+
+use crate::osd::flights::FlightDetectionError;
+
+use super::start_end_args::StartEndArgs;
+
+
+#[derive(Args, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct TrimDisarmedArgs {
+
+    /// remove the sections before the first arming and after the last disarming, e.g. bench time or walking to the
+    /// spot, detected from the OSD file associated to the video
+    #[clap(long, value_parser, conflicts_with_all = ["start", "end"])]
+    trim_disarmed: bool,
+
+    /// seconds kept before the first arming and after the last disarming when trimming the disarmed sections
+    #[clap(long, value_parser, value_name = "SECONDS", default_value_t = 2.0, requires = "trim_disarmed")]
+    trim_padding: f64,
+
+}
+
+impl TrimDisarmedArgs {
+
+    /// start and end of the video, trimmed of the disarmed sections if requested otherwise the provided ones
+    pub fn start_end<P: AsRef<Path>>(&self, video_file: P, start_end: &StartEndArgs) -> Result<StartEndArgs, FlightDetectionError> {
+        if self.trim_disarmed {
+            crate::video::trim_disarmed_start_end(video_file, self.trim_padding)
+        } else {
+            Ok(StartEndArgs::new(start_end.start(), start_end.end()))
+        }
+    }
+
+}
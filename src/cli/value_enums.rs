@@ -0,0 +1,298 @@
+
+//! Command line values of the library enums
+//!
+//! The enums are parsed from the command line here so that the library API does not depend on clap.
+
+use clap::{ValueEnum, builder::PossibleValue};
+
+use crate::{
+    ffmpeg::HwAccel,
+    image::PngCompression,
+    log_level::LogLevel,
+    osd::{
+        font_variant::FontVariant,
+        file::pairing::OSDFileDiscovery,
+        msp::FlightControllerFirmware,
+        overlay::{OverlayVideoCodec, TileBlitMethod, convert::OverlayConversionCodec, graphs::{GraphedItem, GraphsPosition}, sticks::SticksPosition},
+    },
+    telemetry::TelemetrySync,
+    video::{
+        clock::ClockSource,
+        container::Container,
+        cut::CutMode,
+        frame_extraction::ImageFormat,
+        gpu::GpuApi,
+        layers::{BlendMode, LayerKind},
+        srt_telemetry::SrtTelemetryPosition,
+        text_annotation::TextPosition,
+        transcoder::{AudioMode, DenoiseFilter, TranscodePreset, VideoRotation},
+        undistort::LensPreset,
+        verify::QualityMetric,
+        watermark::WatermarkPosition,
+    },
+};
+
+
+/// implements [`ValueEnum`] for an enum from its variants with their command line name and optional help
+macro_rules! value_enum {
+    ($enum:ident { $($variant:ident => $name:literal $(: $help:literal)?),+ $(,)? }) => {
+        impl ValueEnum for $enum {
+            fn value_variants<'a>() -> &'a [Self] {
+                &[$($enum::$variant),+]
+            }
+
+            fn to_possible_value(&self) -> Option<PossibleValue> {
+                Some(match self {
+                    $($enum::$variant => PossibleValue::new($name)$(.help($help))?,)+
+                })
+            }
+        }
+    };
+}
+
+value_enum!(LogLevel {
+    Off => "off",
+    Error => "error",
+    Warn => "warn",
+    Info => "info",
+    Debug => "debug",
+    Trace => "trace",
+});
+
+value_enum!(OverlayVideoCodec {
+    Vp8 => "vp8",
+    Vp9 => "vp9",
+});
+
+value_enum!(TileBlitMethod {
+    Pixels => "pixels": "pixel by pixel copy, only useful to compare the results",
+    Rows => "rows": "row by row copy of the raw pixel data",
+});
+
+value_enum!(TranscodePreset {
+    Archival => "archival": "high quality H.265 encoding for long term storage",
+    Youtube => "youtube": "H.264 encoding with settings suited for uploading to YouTube and other video platforms",
+    Draft => "draft": "fast H.264 encoding with low quality, useful to quickly preview the result",
+});
+
+value_enum!(AudioMode {
+    Copy => "copy": "copy the audio stream without re-encoding it, the audio cannot be fixed nor adjusted",
+    Reencode => "reencode": "re-encode the audio stream with the audio encoder settings",
+    None => "none": "remove the audio",
+});
+
+value_enum!(VideoRotation {
+    Rotate90 => "90",
+    Rotate180 => "180",
+    Rotate270 => "270",
+});
+
+value_enum!(DenoiseFilter {
+    Hqdn3d => "hqdn3d": "fast 3D denoiser, smoothing the noise in space and time",
+    Nlmeans => "nlmeans": "non-local means denoiser, keeping more details but much slower",
+});
+
+value_enum!(PngCompression {
+    Fast => "fast": "no filtering and fastest deflate settings",
+    Default => "default",
+    Best => "best": "best compression, slowest",
+});
+
+value_enum!(HwAccel {
+    Auto => "auto",
+    Cuda => "cuda",
+    Vaapi => "vaapi",
+    Qsv => "qsv",
+    Videotoolbox => "videotoolbox",
+    D3d11va => "d3d11va",
+    Dxva2 => "dxva2",
+});
+
+value_enum!(OSDFileDiscovery {
+    Name => "name": "from the name of the video file",
+    Auto => "auto": "from the name of the video file then by looking for the OSD file of the directory with the duration and modification time closest to the ones of the video file",
+});
+
+value_enum!(SticksPosition {
+    TopLeft => "top-left",
+    TopRight => "top-right",
+    BottomLeft => "bottom-left",
+    Bottom => "bottom",
+    BottomRight => "bottom-right",
+});
+
+value_enum!(GraphedItem {
+    Altitude => "altitude",
+    Speed => "speed",
+    RSSI => "rssi",
+    BatteryVoltage => "battery-voltage",
+});
+
+value_enum!(GraphsPosition {
+    TopLeft => "top-left",
+    TopRight => "top-right",
+    BottomLeft => "bottom-left",
+    BottomRight => "bottom-right",
+});
+
+value_enum!(OverlayConversionCodec {
+    Vp8 => "vp8",
+    Vp9 => "vp9",
+    Prores => "prores": "ProRes 4444, for video editors",
+});
+
+value_enum!(FlightControllerFirmware {
+    Betaflight => "betaflight",
+    INAV => "inav",
+    Ardupilot => "ardupilot",
+    KISSUltra => "kiss-ultra",
+});
+
+impl ValueEnum for FontVariant {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Generic, Self::Ardupilot, Self::Betaflight, Self::INAV, Self::KISSUltra]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            Self::Generic => "generic",
+            Self::Ardupilot => "ardupilot",
+            Self::Betaflight => "betaflight",
+            Self::INAV => "inav",
+            Self::KISSUltra => "kiss-ultra",
+            Self::Unknown => return None,
+        }))
+    }
+}
+
+value_enum!(TelemetrySync {
+    OsdArm => "osd-arm": "arming seen in the OSD: start of the fly time displayed by the OSD",
+    ArmBeep => "arm-beep": "arming heard in the audio of the video: first beep standing out of the background noise",
+    Start => "start": "start of the telemetry aligned with the start of the OSD",
+});
+
+value_enum!(LayerKind {
+    Pip => "pip",
+    Osd => "osd",
+    SrtTelemetry => "srt-telemetry",
+    Text => "text",
+    Clock => "clock",
+    Watermark => "watermark",
+});
+
+value_enum!(BlendMode {
+    Normal => "normal",
+    Addition => "addition",
+    Screen => "screen",
+    Multiply => "multiply",
+    Lighten => "lighten",
+    Darken => "darken",
+    Overlay => "overlay",
+    Difference => "difference",
+});
+
+value_enum!(LensPreset {
+    DjiAirUnit => "dji-air-unit": "DJI FPV Air Unit and Caddx Vista",
+    DjiO3 => "dji-o3": "DJI O3 Air Unit",
+    Walksnail => "walksnail": "Walksnail Avatar HD camera",
+    GoproWide => "gopro-wide": "GoPro in wide mode",
+});
+
+value_enum!(CutMode {
+    Copy => "copy": "stream copy, fast and lossless but the cut points snap to the key frames",
+    Smart => "smart": "frame accurate, only the frames between the cut points and the nearest key frames are re-encoded",
+    Reencode => "reencode": "frame accurate, the whole video is re-encoded",
+});
+
+value_enum!(QualityMetric {
+    Ssim => "ssim": "structural similarity, from 0 to 1",
+    Vmaf => "vmaf": "Netflix video multi-method assessment fusion, from 0 to 100, needs FFMpeg built with libvmaf",
+});
+
+value_enum!(TextPosition {
+    TopLeft => "top-left",
+    Top => "top",
+    TopRight => "top-right",
+    Center => "center",
+    BottomLeft => "bottom-left",
+    Bottom => "bottom",
+    BottomRight => "bottom-right",
+});
+
+value_enum!(GpuApi {
+    Cuda => "cuda": "NVIDIA GPUs: NVDEC decoding, CUDA filters and NVENC encoding",
+    Vaapi => "vaapi": "AMD and Intel GPUs on Linux",
+});
+
+value_enum!(ImageFormat {
+    Png => "png",
+    Jpeg => "jpeg",
+});
+
+value_enum!(Container {
+    Mp4 => "mp4",
+    Mkv => "mkv",
+    Mov => "mov",
+    Webm => "webm",
+});
+
+value_enum!(ClockSource {
+    Auto => "auto": "creation time metadata if valid, otherwise modification time",
+    Metadata => "metadata": "`creation_time` metadata of the video file",
+    ModificationTime => "modification-time": "modification time of the video file minus the video duration, the file being last modified when the recording stopped",
+});
+
+value_enum!(WatermarkPosition {
+    TopLeft => "top-left",
+    TopRight => "top-right",
+    BottomLeft => "bottom-left",
+    BottomRight => "bottom-right",
+    Center => "center",
+});
+
+value_enum!(SrtTelemetryPosition {
+    TopLeft => "top-left",
+    Top => "top",
+    TopRight => "top-right",
+    BottomLeft => "bottom-left",
+    Bottom => "bottom",
+    BottomRight => "bottom-right",
+});
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn command_line_names_parsed_by_the_library() {
+        for kind in LayerKind::value_variants() {
+            let name = kind.to_possible_value().unwrap().get_name().to_owned();
+            assert_eq!(LayerKind::from_str(&name).unwrap(), *kind, "{name}");
+        }
+        for mode in BlendMode::value_variants() {
+            let name = mode.to_possible_value().unwrap().get_name().to_owned();
+            assert_eq!(BlendMode::from_str(&name).unwrap(), *mode, "{name}");
+        }
+        for preset in LensPreset::value_variants() {
+            let name = preset.to_possible_value().unwrap().get_name().to_owned();
+            assert_eq!(LensPreset::from_str(&name).unwrap(), *preset, "{name}");
+        }
+        for position in TextPosition::value_variants() {
+            let name = position.to_possible_value().unwrap().get_name().to_owned();
+            assert_eq!(TextPosition::from_str(&name.to_uppercase()).unwrap(), *position, "{name}");
+        }
+        for position in WatermarkPosition::value_variants() {
+            let name = position.to_possible_value().unwrap().get_name().to_owned();
+            assert_eq!(WatermarkPosition::from_str(&name).unwrap(), *position, "{name}");
+        }
+    }
+
+    #[test]
+    fn unknown_font_variant_not_selectable() {
+        assert!(FontVariant::Unknown.to_possible_value().is_none());
+        assert!(! FontVariant::value_variants().contains(&FontVariant::Unknown));
+    }
+
+}
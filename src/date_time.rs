@@ -0,0 +1,61 @@
+
+//! Dates and times written by the recorders, e.g. the creation time of the videos or the time of the GPX track points
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+
+lazy_static! {
+    static ref ISO8601_REGEX: Regex = Regex::new(
+        r"\A(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2}):(\d{2}(?:\.\d+)?)(?:Z|([+-])(\d{2}):?(\d{2}))?\z"
+    ).unwrap();
+}
+
+/// days since the Unix epoch of the date in the proleptic Gregorian calendar, see
+/// <http://howardhinnant.github.io/date_algorithms.html>
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// parses an ISO 8601 date and time like `2023-06-01T14:23:45.120Z` into seconds since the Unix epoch, the time is
+/// UTC when no offset is provided
+pub(crate) fn parse_iso8601(value: &str) -> Option<f64> {
+    let captures = ISO8601_REGEX.captures(value.trim())?;
+    let number = |index: usize| captures.get(index).map_or(Some(0), |capture| capture.as_str().parse::<i64>().ok());
+    let days = days_from_civil(number(1)?, number(2)?, number(3)?);
+    let mut seconds = days * 86400 + number(4)? * 3600 + number(5)? * 60;
+    if let Some(sign) = captures.get(7) {
+        let utc_offset = number(8)? * 3600 + number(9)? * 60;
+        seconds -= if sign.as_str() == "-" { -utc_offset } else { utc_offset };
+    }
+    Some(seconds as f64 + captures[6].parse::<f64>().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn days_since_the_epoch() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+
+    #[test]
+    fn iso8601_dates_parsed_to_epoch_seconds() {
+        assert_eq!(parse_iso8601("2023-05-06T12:34:56+02:00"), Some(1_683_369_296.0));
+        assert_eq!(parse_iso8601("2023-05-06T08:04:56-0230"), Some(1_683_369_296.0));
+        assert_eq!(parse_iso8601(" 2023-05-06 10:34:56.5Z"), Some(1_683_369_296.5));
+        assert_eq!(parse_iso8601("2023-05-06T10:34:56"), Some(1_683_369_296.0));
+        assert_eq!(parse_iso8601("1970-01-01T00:00:00Z"), Some(0.0));
+        assert_eq!(parse_iso8601("2023-05-06"), None);
+    }
+
+}
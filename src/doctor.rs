@@ -0,0 +1,175 @@
+
+use std::path::PathBuf;
+
+use getset::{Getters, CopyGetters};
+use hd_fpv_osd_font_tool::prelude::*;
+use strum::{Display, IntoEnumIterator};
+
+use crate::cli::font_options::OSDFontDirError;
+use crate::osd::{FontDir, font_variant::FontVariant};
+use crate::process::Command as ProcessCommand;
+
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    #[strum(serialize = "OK")]
+    Ok,
+    #[strum(serialize = "WARNING")]
+    Warning,
+    #[strum(serialize = "ERROR")]
+    Error,
+}
+
+/// Result of a single environment check with an actionable fix when the check did not pass
+#[derive(Debug, Getters, CopyGetters)]
+pub struct CheckResult {
+    #[getset(get = "pub")]
+    name: String,
+    #[getset(get_copy = "pub")]
+    status: CheckStatus,
+    #[getset(get = "pub")]
+    details: String,
+    #[getset(get = "pub")]
+    fix: Option<String>,
+}
+
+impl CheckResult {
+
+    fn ok(name: &str, details: impl Into<String>) -> Self {
+        Self { name: name.to_owned(), status: CheckStatus::Ok, details: details.into(), fix: None }
+    }
+
+    fn warning(name: &str, details: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name: name.to_owned(), status: CheckStatus::Warning, details: details.into(), fix: Some(fix.into()) }
+    }
+
+    fn error(name: &str, details: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name: name.to_owned(), status: CheckStatus::Error, details: details.into(), fix: Some(fix.into()) }
+    }
+
+}
+
+/// Runs the command and returns its standard output if it exited successfully
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = ProcessCommand::new(program);
+    command.args(args);
+    log::debug!("running: {command}");
+    match command.output() {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        _ => None,
+    }
+}
+
+fn check_program_version(program: &str, version_arg: &str, required: bool, fix: &str) -> CheckResult {
+    match command_output(program, &[version_arg]) {
+        Some(output) => CheckResult::ok(program, output.lines().next().unwrap_or_default().trim()),
+        None if required => CheckResult::error(program, "not found", fix),
+        None => CheckResult::warning(program, "not found", fix),
+    }
+}
+
+fn check_font_dir(font_dir: Result<PathBuf, OSDFontDirError>) -> Vec<CheckResult> {
+    let font_dir_path = match font_dir {
+        Ok(font_dir_path) => font_dir_path,
+        Err(error) => return vec![CheckResult::error(
+            "font directory",
+            error.to_string(),
+            "download the fonts with `hd_fpv_font_tool` or from https://github.com/fpv-wtf/msp-osd and \
+                provide the directory with --font-dir or the DJI_OSD_FONTS_DIR environment variable"
+        )],
+    };
+
+    let mut results = vec![CheckResult::ok("font directory", font_dir_path.to_string_lossy())];
+    let font_dir = FontDir::new(&font_dir_path);
+
+    let variants = FontVariant::iter().filter(|variant| *variant != FontVariant::Unknown);
+    for variant in variants {
+        for tile_kind in tile::Kind::iter() {
+            let name = format!("{variant} {tile_kind} font");
+            let result = match font_dir.load(tile_kind, &variant.font_set_ident(), 0) {
+                Ok(_) => CheckResult::ok(&name, "found"),
+                Err(error) => {
+                    let fix = format!("add the {variant} {tile_kind} font files to {}", font_dir_path.to_string_lossy());
+                    match (variant, error.because_file_is_missing()) {
+                        (FontVariant::Generic, _) | (_, false) => CheckResult::error(&name, error.to_string(), fix),
+                        (_, true) => CheckResult::warning(&name, "not found, the generic font will be used instead", fix),
+                    }
+                },
+            };
+            results.push(result);
+        }
+    }
+
+    results
+}
+
+/// Checks that an encoder actually works by encoding a single frame, this detects encoders compiled in
+/// FFMpeg but not usable on this system like NVENC encoders without a supported GPU
+fn encoder_works(encoder: &str, pixel_format: &str) -> bool {
+    command_output("ffmpeg", &[
+        "-hide_banner", "-loglevel", "error",
+        "-f", "lavfi", "-i", "color=size=256x256",
+        "-frames:v", "1", "-pix_fmt", pixel_format, "-c:v", encoder,
+        "-f", "null", "-"
+    ]).is_some()
+}
+
+fn check_codecs() -> Vec<CheckResult> {
+    let encoders = match command_output("ffmpeg", &["-hide_banner", "-encoders"]) {
+        Some(encoders) => encoders,
+        None => return vec![],
+    };
+    let decoders = command_output("ffmpeg", &["-hide_banner", "-decoders"]).unwrap_or_default();
+    let has_codec = |list: &str, codec: &str| list.lines().any(|line| line.split_whitespace().nth(1) == Some(codec));
+
+    let mut results = vec![];
+
+    for (encoder, usage) in [("libx265", "default transcoding encoder"), ("libx264", "used by the youtube and draft presets")] {
+        let name = format!("{encoder} encoder");
+        results.push(if has_codec(&encoders, encoder) {
+            CheckResult::ok(&name, "available")
+        } else {
+            CheckResult::warning(&name, format!("not available ({usage})"), format!("install an FFMpeg build with {encoder} support or select another encoder with --video-encoder"))
+        });
+    }
+
+    for (codec, name) in [("libvpx", "VP8"), ("libvpx-vp9", "VP9")] {
+        let check_name = format!("{name} with alpha channel");
+        let fix = "install an FFMpeg build with libvpx support, it is required to generate and play OSD overlay videos";
+        results.push(match (has_codec(&encoders, codec), has_codec(&decoders, codec)) {
+            (true, true) if encoder_works(codec, "yuva420p") => CheckResult::ok(&check_name, "encoding and decoding available"),
+            (true, true) => CheckResult::error(&check_name, "encoder failed to encode a frame with alpha channel", fix),
+            (true, false) => CheckResult::warning(&check_name, "encoder available but decoder missing, `play-video-with-osd` will not work", fix),
+            (false, _) => CheckResult::error(&check_name, "encoder not available", fix),
+        });
+    }
+
+    for encoder in ["h264_nvenc", "hevc_nvenc"] {
+        let name = format!("{encoder} encoder");
+        results.push(match has_codec(&encoders, encoder) {
+            true if encoder_works(encoder, "yuv420p") => CheckResult::ok(&name, "available"),
+            true => CheckResult::warning(&name, "compiled in FFMpeg but not usable",
+                "NVENC requires an NVIDIA GPU with an up to date driver, software encoders can be used instead"),
+            false => CheckResult::warning(&name, "not available",
+                "install an FFMpeg build with NVENC support to use hardware encoding, software encoders can be used instead"),
+        });
+    }
+
+    results
+}
+
+/// Checks the environment: required external programs, fonts and FFMpeg codecs support
+pub fn run_checks(font_dir: Result<PathBuf, OSDFontDirError>) -> Vec<CheckResult> {
+    let ffmpeg_result = check_program_version("ffmpeg", "-version", true, "install FFMpeg (https://ffmpeg.org/download.html) and make sure it is in the PATH");
+    let ffmpeg_found = ffmpeg_result.status() == CheckStatus::Ok;
+    let mut results = vec![
+        ffmpeg_result,
+        check_program_version("ffprobe", "-version", true, "install FFMpeg (https://ffmpeg.org/download.html), ffprobe is distributed with it"),
+        check_program_version("mpv", "--version", false, "install mpv (https://mpv.io) to use the `play-video-with-osd` command"),
+    ];
+    results.extend(check_font_dir(font_dir));
+    if ffmpeg_found {
+        results.extend(check_codecs());
+    }
+    results
+}
@@ -1,32 +1,136 @@
 
-use std::{process, path::{Path, PathBuf}, ffi::OsString, fmt::Display, io::{Error as IOError, Read}};
+use std::{process, path::{Path, PathBuf}, ffi::OsString, fmt::Display, io::{Error as IOError, Read, Write}, sync::Mutex};
 
-use derive_more::{Deref, DerefMut};
+use derive_more::{Deref, DerefMut, From};
 use getset::{Getters, Setters, CopyGetters};
-use indicatif::{ProgressStyle, ProgressBar};
+use indicatif::{MultiProgress, ProgressStyle, ProgressBar};
 use regex::Regex;
 use thiserror::Error;
 use lazy_static::lazy_static;
 use tokio::task::JoinHandle;
+use ffmpeg_next::Rational;
 use ringbuffer::{self, ConstGenericRingBuffer, RingBufferWrite, RingBufferExt};
 
-use crate::video::{self, Resolution, Timestamp};
+use crate::video::{self, Resolution, Timestamp, container::Container};
 use crate::process::Command as ProcessCommand;
+use crate::file;
 
 
 const DEFAULT_BINARY_PATH: &str = "ffmpeg";
 
+lazy_static! {
+    static ref LOG_FILE: Mutex<Option<fs_err::File>> = Mutex::new(None);
+    static ref PROGRESS_BARS: MultiProgress = MultiProgress::new();
+}
+
+/// progress bars of the FFMpeg processes, shared so that the ones of the processes running concurrently are stacked
+pub(crate) fn progress_bars() -> &'static MultiProgress {
+    &PROGRESS_BARS
+}
+
+/// Appends the command lines of the FFMpeg processes spawned from now on and their error output to the file
+///
+/// The error output of the processes spawned with [`Command::spawn`] is not captured since it is displayed.
+pub fn set_log_file<P: AsRef<Path>>(path: P) -> Result<(), IOError> {
+    let file = fs_err::OpenOptions::new().create(true).append(true).open(path.as_ref())?;
+    *LOG_FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+fn write_to_log_file(process_id: u32, text: &str) {
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        let result = text.lines().try_for_each(|line| writeln!(file, "[ffmpeg {process_id}] {line}"));
+        if let Err(error) = result {
+            log::warn!("failed writing to log file: {error}");
+        }
+    }
+}
+
+/// lines of the FFMpeg error output explaining why it failed, from the first line reporting an error to the end
+///
+/// The lines following the first error line are kept whether they look like errors or not since the last lines
+/// usually hold the actual error, e.g. the encoder option which could not be applied.
+fn failure_reason(stderr_content: &str) -> Option<String> {
+    lazy_static! {
+        static ref FAILURE_RE: Regex = Regex::new(r"(?i)error|invalid|no such file|not found|permission denied|unknown|unrecognized|could not|cannot|failed|does not").unwrap();
+    }
+    let lines = stderr_content.lines().map(str::trim).filter(|line| ! line.is_empty()).collect::<Vec<_>>();
+    let first_reason_line = lines.iter().position(|line| FAILURE_RE.is_match(line))?;
+    let mut reason_lines: Vec<&str> = vec![];
+    for line in lines[first_reason_line..].iter().copied() {
+        if reason_lines.last() != Some(&line) {
+            reason_lines.push(line);
+        }
+    }
+    Some(reason_lines.join("\n"))
+}
+
+/// escapes the text for use as a filter option value inside a filter graph
+pub(crate) fn escape_filter_option_value(text: &str) -> String {
+    let escape = |text: &str, special_chars: &[char]| text.chars().fold(String::new(), |mut escaped, c| {
+        if special_chars.contains(&c) { escaped.push('\\') }
+        escaped.push(c);
+        escaped
+    });
+    // first level for the filter option value, second level for the filter graph
+    escape(&escape(text, &['\\', '\'', ':']), &['\\', '\'', '[', ']', ',', ';'])
+}
+
+/// Hardware decoding API, `auto` letting FFMpeg pick the first one available
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum HwAccel {
+    Auto,
+    Cuda,
+    Vaapi,
+    Qsv,
+    Videotoolbox,
+    D3d11va,
+    Dxva2,
+}
+
 #[derive(Debug, Clone)]
 pub enum Input {
     File {
         path: PathBuf,
         start: Option<Timestamp>,
         end: Option<Timestamp>,
+        /// the video is decoded by the GPU with this API, the decoded frames are copied back to the system memory
+        /// for the filters and the encoder unless `gpu_frames` is set
+        hwaccel: Option<HwAccel>,
+        /// the decoded frames stay in the GPU memory for the GPU filters and the hardware encoder
+        gpu_frames: bool,
+        /// decoder forced instead of the FFMpeg default one, e.g. `libvpx` to decode the alpha channel of VP8 videos
+        decoder: Option<String>,
     },
     StdinPipedRaw {
         resolution: Resolution,
         frame_rate: u16,
-    }
+    },
+    /// list of files to concatenate with the concat demuxer
+    ConcatList {
+        path: PathBuf,
+    },
+    /// raw video bitstream, e.g. `h264` or `hevc`, whose frames are timestamped at the frame rate
+    ElementaryStream {
+        path: PathBuf,
+        format: String,
+        frame_rate: Rational,
+    },
+    /// Input read in real time
+    ///
+    /// With a format the input is a capture device read with this FFMpeg input format, e.g. `v4l2`,
+    /// otherwise it is a file read at its native frame rate and followed while it grows if `follow` is set.
+    Live {
+        path: PathBuf,
+        format: Option<String>,
+        video_size: Option<Resolution>,
+        follow: bool,
+    },
+    /// frames generated by a libavfilter source filter graph, e.g. `color=c=black:s=1920x1080:d=3`
+    Lavfi {
+        graph: String,
+    },
 }
 
 impl Input {
@@ -34,7 +138,19 @@ impl Input {
         let mut args = vec![];
         match self {
 
-            Input::File { path, start, end } => {
+            Input::File { path, start, end, hwaccel, gpu_frames, decoder } => {
+                if let (Some(HwAccel::Vaapi), true) = (hwaccel, gpu_frames) {
+                    // the device is shared by the decoder and the filters uploading frames to the GPU
+                    args.append(&mut ["-init_hw_device", "vaapi=gpu", "-filter_hw_device", "gpu", "-hwaccel_device", "gpu"].map(Into::into).into());
+                }
+                if let Some(hwaccel) = hwaccel {
+                    args.push("-hwaccel".into());
+                    args.push(hwaccel.to_string().into());
+                    if *gpu_frames {
+                        args.push("-hwaccel_output_format".into());
+                        args.push(hwaccel.to_string().into());
+                    }
+                }
                 if let Some(start) = start {
                     args.push("-ss".into());
                     args.push(start.to_ffmpeg_position().into());
@@ -43,6 +159,10 @@ impl Input {
                     args.push("-to".into());
                     args.push(end.to_ffmpeg_position().into());
                 }
+                if let Some(decoder) = decoder {
+                    args.push("-c:v".into());
+                    args.push(decoder.into());
+                }
                 args.push("-i".into());
                 args.push(path.clone().into_os_string());
             },
@@ -55,6 +175,41 @@ impl Input {
                 args.append(&mut ["-i", "pipe:0"].map(Into::into).into());
             },
 
+            Input::ConcatList { path } => {
+                args.append(&mut ["-f", "concat", "-safe", "0", "-i"].map(Into::into).into());
+                args.push(path.clone().into_os_string());
+            },
+
+            Input::ElementaryStream { path, format, frame_rate } => {
+                args.push("-f".into());
+                args.push(format.into());
+                args.push("-framerate".into());
+                args.push(format!("{}/{}", frame_rate.numerator(), frame_rate.denominator()).into());
+                args.push("-i".into());
+                args.push(path.clone().into_os_string());
+            },
+
+            Input::Live { path, format, video_size, follow } => {
+                match format {
+                    Some(format) => args.append(&mut ["-thread_queue_size", "1024", "-f", format].map(Into::into).into()),
+                    None => args.push("-re".into()),
+                }
+                if let Some(video_size) = video_size {
+                    args.push("-video_size".into());
+                    args.push(video_size.to_string().into());
+                }
+                if *follow {
+                    args.append(&mut ["-follow", "1"].map(Into::into).into());
+                }
+                args.push("-i".into());
+                args.push(path.clone().into_os_string());
+            },
+
+            Input::Lavfi { graph } => {
+                args.append(&mut ["-f", "lavfi", "-i"].map(Into::into).into());
+                args.push(graph.into());
+            },
+
         }
         args
     }
@@ -112,6 +267,10 @@ pub struct VideoOutputSettings {
     common: CommonOutputStreamSettings,
     #[getset(get = "pub", set = "pub(self)")]
     crf: Option<u8>,
+    #[getset(get = "pub", set = "pub(self)")]
+    preset: Option<String>,
+    #[getset(get = "pub", set = "pub(self)")]
+    tune: Option<String>,
 }
 
 impl VideoOutputSettings {
@@ -129,6 +288,14 @@ impl VideoOutputSettings {
             args.push("-crf".into());
             args.push(crf.to_string().into());
         }
+        if let Some(preset) = self.preset() {
+            args.push("-preset".into());
+            args.push(preset.into());
+        }
+        if let Some(tune) = self.tune() {
+            args.push("-tune".into());
+            args.push(tune.into());
+        }
         args
     }
 }
@@ -187,6 +354,9 @@ pub struct BuildCommandError(&'static str);
 #[error("only one stdin input possible")]
 pub struct CommandHasAlreadyOneStdinInput;
 
+/// container format used when writing to the standard output, it can be streamed and supports all the codecs
+pub const STDOUT_CONTAINER_FORMAT: &str = "matroska";
+
 #[derive(Default, Getters, Clone)]
 #[getset(get = "pub")]
 pub struct CommandBuilder {
@@ -196,6 +366,10 @@ pub struct CommandBuilder {
     mappings: Vec<Mapping>,
     video_output_settings: VideoOutputSettings,
     audio_output_settings: AudioOutputSettings,
+    /// input the container metadata are copied from, `-1` to strip them
+    map_metadata: Option<i32>,
+    /// flags of the MP4/MOV muxer, combined into a single `-movflags` argument
+    movflags: Vec<String>,
     args: Vec<String>,
     output: Option<PathBuf>,
     overwrite_output_file: bool,
@@ -209,7 +383,19 @@ impl CommandBuilder {
     }
 
     pub fn add_input_file_slice<P: AsRef<Path>>(&mut self, file_path: P, start: Option<Timestamp>, end: Option<Timestamp>) -> &mut Self {
-        self.inputs.push(Input::File { path: file_path.as_ref().to_path_buf(), start, end });
+        self.add_hw_decoded_input_file_slice(file_path, start, end, None)
+    }
+
+    pub fn add_hw_decoded_input_file_slice<P: AsRef<Path>>(&mut self, file_path: P, start: Option<Timestamp>, end: Option<Timestamp>,
+            hwaccel: Option<HwAccel>) -> &mut Self {
+        self.inputs.push(Input::File { path: file_path.as_ref().to_path_buf(), start, end, hwaccel, gpu_frames: false, decoder: None });
+        self
+    }
+
+    /// input decoded by the GPU whose frames stay in the GPU memory, the filters and the encoder must support them
+    pub fn add_gpu_decoded_input_file_slice<P: AsRef<Path>>(&mut self, file_path: P, start: Option<Timestamp>, end: Option<Timestamp>,
+            hwaccel: HwAccel) -> &mut Self {
+        self.inputs.push(Input::File { path: file_path.as_ref().to_path_buf(), start, end, hwaccel: Some(hwaccel), gpu_frames: true, decoder: None });
         self
     }
 
@@ -218,6 +404,33 @@ impl CommandBuilder {
         self
     }
 
+    /// input whose video is decoded with the decoder instead of the FFMpeg default one
+    pub fn add_input_file_with_decoder<P: AsRef<Path>>(&mut self, file_path: P, decoder: &str) -> &mut Self {
+        self.inputs.push(Input::File { path: file_path.as_ref().to_path_buf(), start: None, end: None, hwaccel: None, gpu_frames: false,
+            decoder: Some(decoder.to_owned()) });
+        self
+    }
+
+    pub fn add_concat_list_input<P: AsRef<Path>>(&mut self, list_file_path: P) -> &mut Self {
+        self.inputs.push(Input::ConcatList { path: list_file_path.as_ref().to_path_buf() });
+        self
+    }
+
+    pub fn add_elementary_stream_input<P: AsRef<Path>>(&mut self, path: P, format: &str, frame_rate: Rational) -> &mut Self {
+        self.inputs.push(Input::ElementaryStream { path: path.as_ref().to_path_buf(), format: format.to_owned(), frame_rate });
+        self
+    }
+
+    pub fn add_lavfi_input(&mut self, graph: &str) -> &mut Self {
+        self.inputs.push(Input::Lavfi { graph: graph.to_owned() });
+        self
+    }
+
+    pub fn add_live_input<P: AsRef<Path>>(&mut self, path: P, format: Option<&str>, video_size: Option<Resolution>, follow: bool) -> &mut Self {
+        self.inputs.push(Input::Live { path: path.as_ref().to_path_buf(), format: format.map(str::to_owned), video_size, follow });
+        self
+    }
+
     pub fn has_stdin_input(&self) -> bool {
         self.inputs().iter().any(|input| matches!(input, Input::StdinPipedRaw {..}))
     }
@@ -286,6 +499,16 @@ impl CommandBuilder {
         self
     }
 
+    pub fn set_output_video_preset(&mut self, preset: Option<&str>) -> &mut Self {
+        self.video_output_settings.set_preset(preset.map(str::to_string));
+        self
+    }
+
+    pub fn set_output_video_tune(&mut self, tune: Option<&str>) -> &mut Self {
+        self.video_output_settings.set_tune(tune.map(str::to_string));
+        self
+    }
+
     pub fn set_output_video_settings(&mut self, codec: Option<&str>, bitrate: Option<&str>, crf: Option<u8>) -> &mut Self {
         self
             .set_output_video_codec(codec)
@@ -309,6 +532,26 @@ impl CommandBuilder {
             .set_output_audio_bitrate(bitrate)
     }
 
+    /// copies the container metadata of the input, e.g. the creation time or the GPS location, to the output
+    pub fn copy_metadata_from(&mut self, input_index: usize) -> &mut Self {
+        self.map_metadata = Some(input_index as i32);
+        // the MP4/MOV muxer only writes the metadata it knows about otherwise
+        self.add_movflag("use_metadata_tags")
+    }
+
+    /// writes the output without the container metadata of the inputs
+    pub fn strip_metadata(&mut self) -> &mut Self {
+        self.map_metadata = Some(-1);
+        self
+    }
+
+    pub fn add_movflag(&mut self, flag: &str) -> &mut Self {
+        if ! self.movflags.iter().any(|movflag| movflag == flag) {
+            self.movflags.push(flag.to_owned());
+        }
+        self
+    }
+
     pub fn add_arg(&mut self, arg: &str) -> &mut Self {
         self.args.push(arg.to_string());
         self
@@ -329,6 +572,16 @@ impl CommandBuilder {
         self
     }
 
+    /// writes the output to the standard output of the process with the given container format, e.g. `matroska` or `nut`
+    pub fn set_output_stdout(&mut self, format: &str) -> &mut Self {
+        self.add_args(&["-f", format]);
+        self.set_output_file(file::STDOUT_PATH)
+    }
+
+    fn output_is_stdout(&self) -> bool {
+        matches!(&self.output, Some(output) if file::is_stdout(output))
+    }
+
     pub fn build(&self) -> Result<Command, BuildCommandError> {
         let binary_path = self.bin_path.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_BINARY_PATH));
         let mut pcommand = ProcessCommand::new(binary_path);
@@ -349,6 +602,17 @@ impl CommandBuilder {
         pcommand.args(self.audio_output_settings.to_args());
         pcommand.args(self.video_output_settings.to_args());
 
+        if let Some(map_metadata) = self.map_metadata {
+            pcommand.args(["-map_metadata", map_metadata.to_string().as_str()]);
+        }
+        let output_uses_movflags = match &self.output {
+            Some(output) => ! file::is_stdout(output) && Container::from_extension(output).map_or(true, |container| container.uses_movflags()),
+            None => false,
+        };
+        if ! self.movflags.is_empty() && output_uses_movflags {
+            pcommand.args(["-movflags", self.movflags.iter().map(|flag| format!("+{flag}")).collect::<String>().as_str()]);
+        }
+
         pcommand.args(self.args.iter().map(OsString::from).collect::<Vec<_>>());
 
         if self.overwrite_output_file { pcommand.arg("-y"); }
@@ -358,7 +622,8 @@ impl CommandBuilder {
             None => return Err(BuildCommandError("no output")),
         };
 
-        Ok(Command { command: pcommand, has_stdin_input: self.has_stdin_input() })
+        let partial_output_file = self.output.clone().filter(|output| ! file::is_stdout(output) && ! file::is_url(output));
+        Ok(Command { command: pcommand, has_stdin_input: self.has_stdin_input(), output_to_stdout: self.output_is_stdout(), partial_output_file })
     }
 
 }
@@ -368,6 +633,11 @@ pub struct Command {
     command: ProcessCommand,
     #[getset(get_copy = "pub")]
     has_stdin_input: bool,
+    /// the output is written to the standard output which is then inherited instead of being discarded
+    #[getset(get_copy = "pub")]
+    output_to_stdout: bool,
+    /// output file removed if the process is interrupted before the file is complete
+    partial_output_file: Option<PathBuf>,
 }
 
 #[derive(Debug, Error)]
@@ -380,19 +650,30 @@ pub struct SpawnError {
 impl Command {
 
     fn spawn_base(mut self, output_type: ProcessOutputType) -> Result<Process, SpawnError> {
+        if crate::process::dry_run() {
+            println!("{self}");
+            return Ok(Process::dry_run());
+        }
         log::debug!("spawning process: {self}");
         let stdin_stdio = if self.has_stdin_input() { process::Stdio::piped() } else { process::Stdio::null() };
         let (stdout_stdio, stderr_stdio) = match output_type {
             ProcessOutputType::Inherited => (process::Stdio::inherit(), process::Stdio::inherit()),
+            ProcessOutputType::Progress {..} | ProcessOutputType::None if self.output_to_stdout() =>
+                (process::Stdio::inherit(), process::Stdio::piped()),
             ProcessOutputType::Progress {..} | ProcessOutputType::None =>
                 (process::Stdio::null(), process::Stdio::piped()),
+            ProcessOutputType::PipedStdout => (process::Stdio::piped(), process::Stdio::piped()),
         };
         let mut process_handle = self.command
             .stdin(stdin_stdio).stdout(stdout_stdio).stderr(stderr_stdio)
             .spawn()
             .map_err(|error| SpawnError { error, bin_path: self.command.get_program().to_string_lossy().to_string() })?;
+        write_to_log_file(process_handle.id(), &format!("{self}"));
+        crate::process::register_child_process(process_handle.id());
+        // the temporary files are already registered until removed
+        let partial_output_file = self.partial_output_file.filter(crate::process::register_partial_file);
         let process_stdin = if self.has_stdin_input() { process_handle.stdin.take() } else { None };
-        Ok(Process::new(process_handle, process_stdin, output_type))
+        Ok(Process::new(process_handle, process_stdin, output_type, partial_output_file))
     }
 
     pub fn spawn(self) -> Result<Process, SpawnError> {
@@ -403,6 +684,12 @@ impl Command {
         self.spawn_base(ProcessOutputType::None)
     }
 
+    /// spawns the process with its standard output piped so that it can be read with [`Process::take_stdout`],
+    /// used with the `-` output file to pass the output to another process
+    pub fn spawn_piped_stdout(self) -> Result<Process, SpawnError> {
+        self.spawn_base(ProcessOutputType::PipedStdout)
+    }
+
     pub fn spawn_with_progress(self, frame_count: u64) -> Result<Process, SpawnError> {
         let output_type = if frame_count == 0 {
             ProcessOutputType::None
@@ -419,6 +706,7 @@ pub enum ProcessOutputType {
     Inherited,
     Progress { frame_count: u64 },
     None,
+    PipedStdout,
 }
 
 impl Display for Command {
@@ -434,37 +722,74 @@ pub struct ProcessError {
     stderr_content: Option<String>,
 }
 
+impl ProcessError {
+
+    /// lines of the FFMpeg error output explaining why it failed if any could be found
+    pub fn failure_reason(&self) -> Option<String> {
+        self.stderr_content.as_deref().and_then(failure_reason)
+    }
+
+}
+
 impl Display for ProcessError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "ffmpeg process exited with an error: {}", self.exit_status)?;
-        if let Some(stderr_content) = &self.stderr_content {
-            f.write_str("\n\nFFMpeg last lines:\n\n")?;
-            f.write_str(stderr_content)?;
+        match (self.failure_reason(), &self.stderr_content) {
+            (Some(failure_reason), _) => {
+                f.write_str("\n\nFFMpeg failure reason:\n\n")?;
+                f.write_str(&failure_reason)?;
+            },
+            (None, Some(stderr_content)) => {
+                f.write_str("\n\nFFMpeg last lines:\n\n")?;
+                f.write_str(stderr_content)?;
+            },
+            (None, None) => {},
         }
         Ok(())
     }
 }
 
+/// FFMpeg process, in dry run mode there is no process: it has no standard input/output and waiting for it succeeds
 pub struct Process {
-    handle: process::Child,
+    handle: Option<process::Child>,
     monitor_handle: Option<JoinHandle<Vec<String>>>,
     stdin: Option<process::ChildStdin>,
+    partial_output_file: Option<PathBuf>,
 }
 
 impl Process {
 
-    fn new(mut handle: process::Child, stdin: Option<process::ChildStdin>, output_type: ProcessOutputType) -> Self {
+    fn new(mut handle: process::Child, stdin: Option<process::ChildStdin>, output_type: ProcessOutputType, partial_output_file: Option<PathBuf>) -> Self {
+        let process_id = handle.id();
         let monitor_handle = match output_type {
             ProcessOutputType::Inherited => None,
             ProcessOutputType::Progress { frame_count } =>
-                Some(tokio::spawn(Self::monitor(handle.stderr.take().unwrap(), Some(frame_count)))),
-            ProcessOutputType::None =>
-                Some(tokio::spawn(Self::monitor(handle.stderr.take().unwrap(), None))),
+                Some(Self::spawn_monitor(handle.stderr.take().unwrap(), process_id, Some(frame_count))),
+            ProcessOutputType::None | ProcessOutputType::PipedStdout =>
+                Some(Self::spawn_monitor(handle.stderr.take().unwrap(), process_id, None)),
         };
-        Process { handle, monitor_handle, stdin }
+        Process { handle: Some(handle), monitor_handle, stdin, partial_output_file }
+    }
+
+    fn dry_run() -> Self {
+        Process { handle: None, monitor_handle: None, stdin: None, partial_output_file: None }
     }
 
-    async fn monitor(mut ffmpeg_stderr: process::ChildStderr, frame_count: Option<u64>) -> Vec<String> {
+    /// the process is not terminated anymore when interrupted and its output file is not removed once complete
+    fn exited(&self, process_id: u32, success: bool) {
+        crate::process::unregister_child_process(process_id);
+        if let Some(partial_output_file) = self.partial_output_file.as_ref().filter(|_| success) {
+            crate::process::unregister_partial_file(partial_output_file);
+        }
+    }
+
+    /// the error output is read with blocking reads so the monitor runs on the blocking threads to not starve the
+    /// other tasks when several processes are running
+    fn spawn_monitor(ffmpeg_stderr: process::ChildStderr, process_id: u32, frame_count: Option<u64>) -> JoinHandle<Vec<String>> {
+        tokio::task::spawn_blocking(move || Self::monitor(ffmpeg_stderr, process_id, frame_count))
+    }
+
+    fn monitor(mut ffmpeg_stderr: process::ChildStderr, process_id: u32, frame_count: Option<u64>) -> Vec<String> {
 
         let mut output_buf = String::new();
         let mut read_buf = [0; 1024];
@@ -472,7 +797,7 @@ impl Process {
 
         let progress_bar = frame_count.map(|frame_count| {
             let progress_style = ProgressStyle::with_template("{wide_bar} {percent:>3}% [ETA {eta:>3}]").unwrap();
-            let progress_bar = ProgressBar::new(frame_count).with_style(progress_style);
+            let progress_bar = PROGRESS_BARS.add(ProgressBar::new(frame_count).with_style(progress_style));
             progress_bar.set_position(0);
             progress_bar
         });
@@ -500,10 +825,15 @@ impl Process {
                 }
             }
 
-            last_lines.extend(lines);
+            // the progress lines ending with a carriage return are not logged
+            for line in lines {
+                write_to_log_file(process_id, &line);
+                last_lines.push(line);
+            }
             output_buf.clear();
 
             if last_line.ends_with('\n') {
+                write_to_log_file(process_id, &last_line);
                 last_lines.push(last_line);
             } else {
                 let last_cr_line = last_cr_lines.last().unwrap();
@@ -516,6 +846,7 @@ impl Process {
 
         if let Some(progress_bar) = progress_bar {
             progress_bar.finish_and_clear();
+            PROGRESS_BARS.remove(&progress_bar);
         }
 
         last_lines.to_vec()
@@ -525,8 +856,13 @@ impl Process {
         self.stdin.take()
     }
 
-    pub fn id(&self) -> u32 {
-        self.handle.id()
+    pub fn take_stdout(&mut self) -> Option<process::ChildStdout> {
+        self.handle.as_mut()?.stdout.take()
+    }
+
+    /// process id, `None` in dry run mode
+    pub fn id(&self) -> Option<u32> {
+        self.handle.as_ref().map(process::Child::id)
     }
 
     async fn last_output_lines(&mut self) -> Option<String> {
@@ -537,30 +873,108 @@ impl Process {
     }
 
     pub async fn try_wait(&mut self) -> Result<bool, ProcessError> {
-        match self.handle.try_wait().unwrap() {
-            Some(exit_status) =>
+        let handle = match &mut self.handle {
+            Some(handle) => handle,
+            None => return Ok(true),
+        };
+        match handle.try_wait().unwrap() {
+            Some(exit_status) => {
+                let process_id = handle.id();
+                write_to_log_file(process_id, &format!("exited: {exit_status}"));
+                self.exited(process_id, exit_status.success());
                 if exit_status.success() {
                     Ok(true)
                 } else {
                     Err(ProcessError { exit_status, stderr_content: self.last_output_lines().await })
-                },
+                }
+            },
             None => Ok(false),
         }
     }
 
     pub async fn wait(&mut self) -> Result<(), ProcessError> {
-        match self.handle.wait().unwrap() {
+        let mut handle = match self.handle.take() {
+            Some(handle) => handle,
+            None => return Ok(()),
+        };
+        // waiting blocks so it runs on the blocking threads, which are also available on a current thread runtime
+        let (handle, exit_status) = tokio::task::spawn_blocking(move || {
+            let exit_status = handle.wait().unwrap();
+            (handle, exit_status)
+        }).await.unwrap();
+        let process_id = handle.id();
+        self.handle = Some(handle);
+        write_to_log_file(process_id, &format!("exited: {exit_status}"));
+        self.exited(process_id, exit_status.success());
+        match exit_status {
             exit_status if exit_status.success() => Ok(()),
             exit_status => Err(ProcessError { exit_status, stderr_content: self.last_output_lines().await })
         }
     }
 
     pub fn kill(mut self) -> Result<(), IOError> {
-        self.handle.kill()
+        match &mut self.handle {
+            Some(handle) => {
+                crate::process::unregister_child_process(handle.id());
+                handle.kill()
+            },
+            None => Ok(()),
+        }
     }
 
 }
 
+impl Drop for Process {
+    /// a process still running, e.g. when the job using it returned early or panicked, is killed so that it does not
+    /// outlive the job, the process id may be reused once the process is waited for
+    fn drop(&mut self) {
+        if let Some(handle) = &mut self.handle {
+            if let Ok(None) = handle.try_wait() {
+                log::debug!("killing FFMpeg process {} dropped while running", handle.id());
+                let _ = handle.kill();
+                let _ = handle.wait();
+            }
+            crate::process::unregister_child_process(handle.id());
+        }
+    }
+}
+
+#[derive(Debug, Error, From)]
+pub enum ReadAudioError {
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(SpawnError),
+    #[error("failed reading the audio decoded by FFMpeg: {0}")]
+    ReadError(IOError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ProcessError),
+}
+
+/// Mono samples of the audio of the video decoded at the sample rate, after the audio filter if any
+///
+/// The whole audio stream is decoded, the samples are empty in dry run mode since FFMpeg is not run.
+pub async fn read_audio_samples<P: AsRef<Path>>(video_file: P, sample_rate: u32, audio_filter: Option<&str>) -> Result<Vec<f32>, ReadAudioError> {
+    let mut ffmpeg_command = CommandBuilder::default();
+    ffmpeg_command.add_input_file(video_file).add_mapping("0:a");
+    if let Some(audio_filter) = audio_filter {
+        ffmpeg_command.add_audio_filter(audio_filter);
+    }
+    ffmpeg_command
+        .set_output_audio_codec(Some("pcm_f32le"))
+        .add_args(&["-ac", "1", "-ar", &sample_rate.to_string()])
+        .set_output_stdout("f32le");
+    let mut ffmpeg_process = ffmpeg_command.build().unwrap().spawn_piped_stdout()?;
+    let audio = match ffmpeg_process.take_stdout() {
+        // reading blocks until FFMpeg is done so it runs on the blocking threads like waiting for the process
+        Some(mut ffmpeg_stdout) => tokio::task::spawn_blocking(move || {
+            let mut audio = vec![];
+            ffmpeg_stdout.read_to_end(&mut audio).map(|_| audio)
+        }).await.unwrap()?,
+        None => vec![],
+    };
+    ffmpeg_process.wait().await?;
+    Ok(audio.chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect())
+}
+
 impl video::Region {
     pub fn to_ffmpeg_filter_string(&self) -> String {
         format!(
@@ -571,4 +985,33 @@ impl video::Region {
             self.dimensions().height
         )
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn failure_reason_keeps_the_tail_from_the_first_error() {
+        let stderr_content = "Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'in.mp4':\n\
+            [libx264 @ 0x1] Error setting option crf to value -5.\n\
+            [libx264 @ 0x1] Error setting option crf to value -5.\n\
+            Error initializing output stream 0:0 --\n\
+            width not divisible by 2 (1281x720)\n\n";
+        assert_eq!(failure_reason(stderr_content).unwrap(), "[libx264 @ 0x1] Error setting option crf to value -5.\n\
+            Error initializing output stream 0:0 --\nwidth not divisible by 2 (1281x720)");
+        assert_eq!(failure_reason("frame=  10 fps=0.0\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn running_process_killed_when_dropped() {
+        let handle = process::Command::new("sleep").arg("60").spawn().unwrap();
+        let process_id = handle.id();
+        crate::process::register_child_process(process_id);
+        drop(Process::new(handle, None, ProcessOutputType::Inherited, None));
+        assert!(! crate::process::is_running(process_id));
+    }
+
+}
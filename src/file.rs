@@ -9,6 +9,7 @@ use std::{
     },
 };
 
+use filetime::FileTime;
 use thiserror::Error;
 
 
@@ -25,6 +26,47 @@ pub enum TouchError {
     CreateError(#[from] IOError),
 }
 
+/// path used to designate the standard output instead of a file
+pub const STDOUT_PATH: &str = "-";
+
+pub fn is_stdout<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().as_os_str() == STDOUT_PATH
+}
+
+/// schemes of the input video URLs handed through to FFMpeg and MPV
+const URL_SCHEMES: &[&str] = &["http://", "https://"];
+
+pub fn is_url<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().to_str() {
+        Some(path) => URL_SCHEMES.iter().any(|scheme| path.get(..scheme.len()).map_or(false, |prefix| prefix.eq_ignore_ascii_case(scheme))),
+        None => false,
+    }
+}
+
+/// returns whether the input file exists, URLs are assumed to exist and are checked when probing them
+pub fn input_exists<P: AsRef<Path>>(path: P) -> bool {
+    is_url(&path) || path.as_ref().exists()
+}
+
+/// Local path corresponding to an input
+///
+/// For URLs this is the file name at the end of the URL path in the current directory, it is used to name the
+/// output files and to look for the associated files like the OSD file. Other paths are returned unchanged.
+pub fn local_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    if ! is_url(path) { return path.to_path_buf() }
+    let url = path.to_string_lossy();
+    let url_path = url.split(['?', '#']).next().unwrap_or_default();
+    PathBuf::from(url_path.rsplit('/').next().unwrap_or_default())
+}
+
+/// sets the modification time of the target file to the one of the source file
+pub fn copy_modification_time<P: AsRef<Path>, Q: AsRef<Path>>(source: P, target: Q) -> Result<(), IOError> {
+    if crate::process::dry_run() { return Ok(()) }
+    let source_modification_time = FileTime::from_last_modification_time(&fs_err::metadata(source)?);
+    filetime::set_file_mtime(target, source_modification_time)
+}
+
 pub fn touch<P: AsRef<Path>>(path: P) -> Result<(), TouchError> {
     let path = path.as_ref();
     let dir = path.parent().ok_or_else(|| TouchError::InvalidPath(path.to_path_buf()))?;
@@ -34,6 +76,8 @@ pub fn touch<P: AsRef<Path>>(path: P) -> Result<(), TouchError> {
             dir_path: dir.to_path_buf()
         })
     }
-    File::create(path)?;
+    if ! crate::process::dry_run() {
+        File::create(path)?;
+    }
     Ok(())
 }
\ No newline at end of file
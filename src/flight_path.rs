@@ -0,0 +1,389 @@
+
+//! Flight path traced from the GPS coordinates displayed in the OSD
+
+use std::f64::consts::PI;
+use std::io::{Error as IOError, Write};
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use getset::{CopyGetters, Setters};
+use image::{Rgba, RgbaImage};
+use thiserror::Error;
+
+use crate::ffmpeg;
+use crate::image::{draw_disc, draw_line};
+use crate::file::{self, TouchError};
+use crate::osd::{
+    self,
+    FontVariant,
+    file::{Frame, ReadError},
+    overlay::{OverlayVideoCodec, style::Color},
+    tile_indices::UnknownOSDItem,
+};
+use crate::process::Command as ProcessCommand;
+use crate::video::{FrameIndex as VideoFrameIndex, Resolution};
+
+
+pub const LATITUDE_OSD_ITEM: &str = "gpslat";
+pub const LONGITUDE_OSD_ITEM: &str = "gpslon";
+
+pub const DEFAULT_WIDTH: u32 = 1024;
+pub const DEFAULT_HEIGHT: u32 = 1024;
+pub const DEFAULT_LINE_WIDTH: u32 = 4;
+pub const DEFAULT_COLOR: &str = "yellow";
+
+const OSM_TILE_SIZE: u32 = 256;
+const OSM_MAX_ZOOM: u32 = 18;
+const OSM_TILE_URL: &str = "https://tile.openstreetmap.org";
+/// credit required by the OpenStreetMap license when publishing renderings of the map data
+pub const OSM_ATTRIBUTION: &str = "© OpenStreetMap contributors";
+
+/// margin around the path in pixels
+const MARGIN: u32 = 32;
+
+const START_MARKER_COLOR: Rgba<u8> = Rgba([0, 200, 0, 255]);
+const END_MARKER_COLOR: Rgba<u8> = Rgba([220, 0, 0, 255]);
+const CURRENT_POSITION_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+#[derive(Debug, Error, From)]
+pub enum RenderFlightPathError {
+    #[error(transparent)]
+    ReadError(ReadError),
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+    #[error("the `{0}` font variant has no GPS coordinates OSD items")]
+    NoGPSOSDItems(FontVariant),
+    #[error("no GPS position found in the OSD file")]
+    NoGPSPositions,
+    #[error("output file extension needs to be .png or .webm")]
+    UnsupportedOutputFileExtension,
+    #[error("output file exists: {0}")]
+    #[from(ignore)]
+    OutputFileExists(PathBuf),
+    #[error("failed downloading OpenStreetMap tile {0}")]
+    #[from(ignore)]
+    FailedDownloadingOSMTile(String),
+    #[error("failed loading OpenStreetMap tile: {0}")]
+    FailedLoadingOSMTile(image::ImageError),
+    #[error("failed saving image: {0}")]
+    #[from(ignore)]
+    FailedSavingImage(image::ImageError),
+    #[error(transparent)]
+    IOError(IOError),
+    #[error(transparent)]
+    TouchError(TouchError),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+}
+
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct GPSPosition {
+    frame_index: VideoFrameIndex,
+    latitude: f64,
+    longitude: f64,
+}
+
+/// GPS positions displayed in the OSD frames, positions without a fix (0, 0) and repeated positions are skipped
+pub fn gps_positions<'a>(frames: impl IntoIterator<Item = &'a Frame>, font_variant: FontVariant) -> Result<Vec<GPSPosition>, RenderFlightPathError> {
+    if font_variant.find_osd_item_location_data(LATITUDE_OSD_ITEM).is_none() || font_variant.find_osd_item_location_data(LONGITUDE_OSD_ITEM).is_none() {
+        return Err(RenderFlightPathError::NoGPSOSDItems(font_variant));
+    }
+
+    let mut positions: Vec<GPSPosition> = vec![];
+    for frame in frames {
+        let latitude = frame.osd_item_value(font_variant, LATITUDE_OSD_ITEM)?;
+        let longitude = frame.osd_item_value(font_variant, LONGITUDE_OSD_ITEM)?;
+        if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+            if latitude == 0.0 && longitude == 0.0 { continue }
+            if matches!(positions.last(), Some(last) if last.latitude == latitude && last.longitude == longitude) { continue }
+            positions.push(GPSPosition { frame_index: frame.index(), latitude, longitude });
+        }
+    }
+    Ok(positions)
+}
+
+/// Web Mercator projection of the position in pixels of the world map at zoom level 0
+fn project(latitude: f64, longitude: f64) -> (f64, f64) {
+    let size = OSM_TILE_SIZE as f64;
+    let latitude = latitude.clamp(-85.0511, 85.0511).to_radians();
+    let x = (longitude + 180.0) / 360.0 * size;
+    let y = (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0 * size;
+    (x, y)
+}
+
+/// placement of the projected positions in the image
+struct Viewport {
+    center: (f64, f64),
+    scale: f64,
+    dimensions: Resolution,
+}
+
+impl Viewport {
+
+    fn to_image(&self, world_coordinates: (f64, f64)) -> (f64, f64) {
+        (
+            (world_coordinates.0 - self.center.0) * self.scale + self.dimensions.width as f64 / 2.0,
+            (world_coordinates.1 - self.center.1) * self.scale + self.dimensions.height as f64 / 2.0,
+        )
+    }
+
+    fn to_world(&self, image_coordinates: (f64, f64)) -> (f64, f64) {
+        (
+            (image_coordinates.0 - self.dimensions.width as f64 / 2.0) / self.scale + self.center.0,
+            (image_coordinates.1 - self.dimensions.height as f64 / 2.0) / self.scale + self.center.1,
+        )
+    }
+
+}
+
+/// Renders the flight path as a transparent image or as an overlay video where the path is drawn progressively
+///
+/// The path is fitted in the image with the Web Mercator projection. With the OpenStreetMap background the zoom level
+/// is rounded down to the closest level of the map tiles which are downloaded with `curl`.
+#[derive(Debug, Clone, CopyGetters, Setters)]
+#[getset(get_copy = "pub", set = "pub")]
+pub struct FlightPathRenderer {
+    width: u32,
+    height: u32,
+    line_width: u32,
+    color: Color,
+    osm_background: bool,
+}
+
+impl Default for FlightPathRenderer {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            line_width: DEFAULT_LINE_WIDTH,
+            color: DEFAULT_COLOR.parse().unwrap(),
+            osm_background: false,
+        }
+    }
+}
+
+impl FlightPathRenderer {
+
+    fn dimensions(&self) -> Resolution {
+        Resolution::new(self.width, self.height)
+    }
+
+    fn viewport(&self, positions: &[GPSPosition]) -> Viewport {
+        let projected = positions.iter().map(|position| project(position.latitude, position.longitude)).collect::<Vec<_>>();
+        let (min_x, max_x) = projected.iter().fold((f64::MAX, f64::MIN), |(min, max), (x, _)| (min.min(*x), max.max(*x)));
+        let (min_y, max_y) = projected.iter().fold((f64::MAX, f64::MIN), |(min, max), (_, y)| (min.min(*y), max.max(*y)));
+        let available_width = self.width.saturating_sub(2 * MARGIN).max(1) as f64;
+        let available_height = self.height.saturating_sub(2 * MARGIN).max(1) as f64;
+        let max_scale = 2f64.powi(OSM_MAX_ZOOM as i32);
+        let scale = (available_width / (max_x - min_x)).min(available_height / (max_y - min_y)).min(max_scale);
+        // the map tiles are only available for integer zoom levels
+        let scale = if self.osm_background { 2f64.powi(scale.log2().floor().max(0.0) as i32) } else { scale };
+        Viewport { center: ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0), scale, dimensions: self.dimensions() }
+    }
+
+    fn download_osm_tile(zoom: u32, x: u32, y: u32) -> Result<RgbaImage, RenderFlightPathError> {
+        // the tiles are cached to comply with the OpenStreetMap tile usage policy
+        let tile_path: PathBuf = [std::env::temp_dir(), "hd_fpv_video_tool_osm_tiles".into(), zoom.to_string().into(), x.to_string().into(),
+            format!("{y}.png").into()].iter().collect();
+        let tile_url = format!("{OSM_TILE_URL}/{zoom}/{x}/{y}.png");
+        if ! tile_path.is_file() {
+            log::debug!("downloading OpenStreetMap tile: {tile_url}");
+            fs_err::create_dir_all(tile_path.parent().unwrap())?;
+            // downloaded to a file specific to this process then renamed so that other processes sharing the cache
+            // never read a partially downloaded tile
+            let partial_tile_path = tile_path.with_extension(format!("{}.part", std::process::id()));
+            crate::process::register_partial_file(&partial_tile_path);
+            let user_agent = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+            let status = ProcessCommand::new("curl")
+                .args(["--silent", "--fail", "--location", "--user-agent", user_agent, "--output"])
+                .arg(&partial_tile_path)
+                .arg(&tile_url)
+                .status();
+            let result = match status {
+                Ok(status) if status.success() => fs_err::rename(&partial_tile_path, &tile_path).map_err(Into::into),
+                Ok(_) => Err(RenderFlightPathError::FailedDownloadingOSMTile(tile_url)),
+                Err(error) => Err(error.into()),
+            };
+            if result.is_err() {
+                let _ = fs_err::remove_file(&partial_tile_path);
+            }
+            crate::process::unregister_partial_file(&partial_tile_path);
+            result?;
+        }
+        Ok(image::open(&tile_path)?.into_rgba8())
+    }
+
+    /// the tiles are downloaded by a blocking task so that the runtime is not blocked while `curl` runs
+    async fn background(&self, viewport: &Viewport) -> Result<RgbaImage, RenderFlightPathError> {
+        let mut image = RgbaImage::new(self.width, self.height);
+        if ! self.osm_background { return Ok(image) }
+
+        let zoom = viewport.scale.log2().round() as u32;
+        let tile_count = 2u32.pow(zoom);
+        let tile_world_size = OSM_TILE_SIZE as f64 / tile_count as f64;
+        let (top_left, bottom_right) = (viewport.to_world((0.0, 0.0)), viewport.to_world((self.width as f64, self.height as f64)));
+        let tile_range = |from: f64, to: f64| (from / tile_world_size).floor().max(0.0) as u32..=((to / tile_world_size).floor() as u32).min(tile_count - 1);
+        log::info!("downloading OpenStreetMap tiles at zoom level {zoom}");
+        log::warn!("the map data is {OSM_ATTRIBUTION} and has to be credited when publishing the result");
+        for tile_x in tile_range(top_left.0, bottom_right.0) {
+            for tile_y in tile_range(top_left.1, bottom_right.1) {
+                let tile = tokio::task::spawn_blocking(move || Self::download_osm_tile(zoom, tile_x, tile_y)).await.unwrap()?;
+                let (x, y) = viewport.to_image((tile_x as f64 * tile_world_size, tile_y as f64 * tile_world_size));
+                image::imageops::overlay(&mut image, &tile, x.round() as i64, y.round() as i64);
+            }
+        }
+        Ok(image)
+    }
+
+    /// renders the whole path with markers at the start and the end
+    pub async fn render_image(&self, positions: &[GPSPosition]) -> Result<RgbaImage, RenderFlightPathError> {
+        let (first, last) = match (positions.first(), positions.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(RenderFlightPathError::NoGPSPositions),
+        };
+        let viewport = self.viewport(positions);
+        let points = positions.iter().map(|position| viewport.to_image(project(position.latitude, position.longitude))).collect::<Vec<_>>();
+
+        let mut path = RgbaImage::new(self.width, self.height);
+        for segment in points.windows(2) {
+            draw_line(&mut path, segment[0], segment[1], self.line_width, self.color.rgba());
+        }
+        let marker_radius = self.line_width as f64 * 1.5;
+        draw_disc(&mut path, viewport.to_image(project(first.latitude, first.longitude)), marker_radius, START_MARKER_COLOR);
+        draw_disc(&mut path, viewport.to_image(project(last.latitude, last.longitude)), marker_radius, END_MARKER_COLOR);
+
+        let mut image = self.background(&viewport).await?;
+        image::imageops::overlay(&mut image, &path, 0, 0);
+        Ok(image)
+    }
+
+    fn check_output_file(output_file: &Path, overwrite: bool) -> Result<(), RenderFlightPathError> {
+        if ! overwrite && output_file.exists() {
+            return Err(RenderFlightPathError::OutputFileExists(output_file.to_path_buf()));
+        }
+        file::touch(output_file)?;
+        Ok(())
+    }
+
+    pub async fn save_image<P: AsRef<Path>>(&self, positions: &[GPSPosition], output_file: P, overwrite: bool) -> Result<(), RenderFlightPathError> {
+        let output_file = output_file.as_ref();
+        Self::check_output_file(output_file, overwrite)?;
+        log::info!("rendering flight path image: {}", output_file.to_string_lossy());
+        let image = self.render_image(positions).await?;
+        if crate::process::plan_file_write(output_file, "flight path image") {
+            image.save(output_file).map_err(RenderFlightPathError::FailedSavingImage)?;
+        }
+        Ok(())
+    }
+
+    /// generates a transparent WebM overlay video where the path is drawn as the positions are reached in the OSD
+    pub async fn generate_video<P: AsRef<Path>>(&self, positions: &[GPSPosition], last_frame_index: VideoFrameIndex, output_file: P,
+            overwrite: bool) -> Result<(), RenderFlightPathError> {
+        let output_file = output_file.as_ref();
+        if positions.is_empty() { return Err(RenderFlightPathError::NoGPSPositions) }
+        Self::check_output_file(output_file, overwrite)?;
+        log::info!("generating flight path overlay video: {}", output_file.to_string_lossy());
+
+        let viewport = self.viewport(positions);
+        let background = self.background(&viewport).await?;
+        let points = positions.iter()
+            .map(|position| (position.frame_index, viewport.to_image(project(position.latitude, position.longitude))))
+            .collect::<Vec<_>>();
+
+        let codec_params = OverlayVideoCodec::Vp8.params();
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command
+            .add_stdin_input(self.dimensions(), osd::FRAME_RATE as u16).unwrap()
+            .set_output_video_settings(Some(codec_params.encoder()), codec_params.bitrate(), codec_params.crf())
+            .add_args(codec_params.additional_args())
+            .set_output_file(output_file)
+            .set_overwrite_output_file(true);
+        let frame_count = last_frame_index + 1;
+        let mut ffmpeg_process = ffmpeg_command.build().unwrap().spawn_with_progress(frame_count as u64)?;
+        // no process in dry run mode
+        let mut ffmpeg_stdin = match ffmpeg_process.take_stdin() {
+            Some(ffmpeg_stdin) => ffmpeg_stdin,
+            None => return Ok(()),
+        };
+
+        let mut path = RgbaImage::new(self.width, self.height);
+        let mut reached_count = 0;
+        for frame_index in 0..frame_count {
+            while reached_count < points.len() && points[reached_count].0 <= frame_index {
+                let from = points[reached_count.saturating_sub(1)].1;
+                draw_line(&mut path, from, points[reached_count].1, self.line_width, self.color.rgba());
+                reached_count += 1;
+            }
+
+            let mut frame = background.clone();
+            image::imageops::overlay(&mut frame, &path, 0, 0);
+            if reached_count > 0 {
+                draw_disc(&mut frame, points[reached_count - 1].1, self.line_width as f64 * 1.5, CURRENT_POSITION_COLOR);
+            }
+            if let Err(error) = ffmpeg_stdin.write_all(frame.as_raw()) {
+                log::error!("failed sending flight path frames to ffmpeg: {error}");
+                break;
+            }
+        }
+        drop(ffmpeg_stdin);
+        ffmpeg_process.wait().await?;
+
+        log::info!("flight path overlay video generation completed: {frame_count} frames");
+        Ok(())
+    }
+
+    /// renders a PNG image or a WebM overlay video depending on the extension of the output file
+    pub async fn render<P: AsRef<Path>>(&self, positions: &[GPSPosition], last_frame_index: VideoFrameIndex, output_file: P,
+            overwrite: bool) -> Result<(), RenderFlightPathError> {
+        let output_file = output_file.as_ref();
+        match output_file.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("png") => self.save_image(positions, output_file, overwrite).await,
+            Some(extension) if extension.eq_ignore_ascii_case("webm") => self.generate_video(positions, last_frame_index, output_file, overwrite).await,
+            _ => Err(RenderFlightPathError::UnsupportedOutputFileExtension),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(latitude: f64, longitude: f64) -> GPSPosition {
+        GPSPosition { frame_index: 0, latitude, longitude }
+    }
+
+    #[test]
+    fn web_mercator_projection() {
+        assert_eq!(project(0.0, 0.0), (128.0, 128.0));
+        let (x, y) = project(85.0511, 180.0);
+        assert_eq!(x, 256.0);
+        assert!(y.abs() < 0.001);
+    }
+
+    #[test]
+    fn viewport_maps_image_and_world_coordinates() {
+        let renderer = FlightPathRenderer::default();
+        let viewport = renderer.viewport(&[position(46.0, 6.0), position(46.01, 6.02)]);
+        let world = project(46.005, 6.01);
+        let image = viewport.to_image(world);
+        assert!((image.0 - DEFAULT_WIDTH as f64 / 2.0).abs() < 1.0);
+        let back = viewport.to_world(image);
+        assert!((back.0 - world.0).abs() < 1e-9 && (back.1 - world.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn osm_background_scale_is_a_zoom_level() {
+        let mut renderer = FlightPathRenderer::default();
+        let positions = [position(46.0, 6.0), position(46.01, 6.02)];
+        let scale = renderer.viewport(&positions).scale;
+        renderer.set_osm_background(true);
+        let zoom_scale = renderer.viewport(&positions).scale;
+        assert!(zoom_scale <= scale);
+        assert_eq!(zoom_scale.log2().fract(), 0.0);
+    }
+
+}
@@ -4,7 +4,10 @@ use std::{
         Path,
         PathBuf
     },
-    io::Error as IOError,
+    io::{
+        Error as IOError,
+        BufWriter,
+    },
     ops::Deref
 };
 
@@ -16,7 +19,15 @@ use image::{
     EncodableLayout,
     ImageBuffer,
     PixelWithColorType,
-    io::Reader as ImageReader
+    Rgba,
+    RgbaImage,
+    ImageEncoder,
+    io::Reader as ImageReader,
+    codecs::png::{
+        PngEncoder,
+        CompressionType as PngCompressionType,
+        FilterType as PngFilterType,
+    },
 };
 
 
@@ -65,8 +76,33 @@ impl WriteError {
     }
 }
 
+/// PNG compression level used when writing image files
+///
+/// Encoding PNG files is the bottleneck when writing overlay frames to disk, lower compression levels
+/// are much faster to encode at the cost of larger files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngCompression {
+    /// no filtering and fastest deflate settings
+    Fast,
+    Default,
+    /// best compression, slowest
+    Best,
+}
+
+impl PngCompression {
+    fn encoder_settings(&self) -> (PngCompressionType, PngFilterType) {
+        use PngCompression::*;
+        match self {
+            Fast => (PngCompressionType::Fast, PngFilterType::NoFilter),
+            Default => (PngCompressionType::Default, PngFilterType::Adaptive),
+            Best => (PngCompressionType::Best, PngFilterType::Adaptive),
+        }
+    }
+}
+
 pub trait WriteImageFile {
     fn write_image_file<Q: AsRef<Path>>(&self, path: Q) -> Result<(), WriteError>;
+    fn write_png_file<Q: AsRef<Path>>(&self, path: Q, compression: PngCompression) -> Result<(), WriteError>;
 }
 
 impl<P, Container> WriteImageFile for ImageBuffer<P, Container>
@@ -78,4 +114,77 @@ where
     fn write_image_file<Q: AsRef<Path>>(&self, path: Q) -> Result<(), WriteError> {
         self.save(&path).map_err(|error| WriteError::new(&path, error) )
     }
+
+    fn write_png_file<Q: AsRef<Path>>(&self, path: Q, compression: PngCompression) -> Result<(), WriteError> {
+        let file = std::fs::File::create(&path).map_err(|error| WriteError::new(&path, ImageError::IoError(error)))?;
+        let (compression_type, filter_type) = compression.encoder_settings();
+        let encoder = PngEncoder::new_with_quality(BufWriter::new(file), compression_type, filter_type);
+        let subpixels: &[P::Subpixel] = self;
+        encoder.write_image(subpixels.as_bytes(), self.width(), self.height(), P::COLOR_TYPE)
+            .map_err(|error| WriteError::new(&path, error))
+    }
+}
+
+/// draws a disc, the pixels are replaced and not blended
+pub fn draw_disc(image: &mut RgbaImage, center: (f64, f64), radius: f64, color: Rgba<u8>) {
+    let radius = radius.max(0.5);
+    let x_range = (center.0 - radius).floor().max(0.0) as u32..=((center.0 + radius).ceil().max(0.0) as u32).min(image.width().saturating_sub(1));
+    for y in (center.1 - radius).floor().max(0.0) as u32..=((center.1 + radius).ceil().max(0.0) as u32).min(image.height().saturating_sub(1)) {
+        for x in x_range.clone() {
+            let (dx, dy) = (x as f64 + 0.5 - center.0, y as f64 + 0.5 - center.1);
+            if dx * dx + dy * dy <= radius * radius {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// draws a line with a round brush, the pixels are replaced and not blended
+pub fn draw_line(image: &mut RgbaImage, from: (f64, f64), to: (f64, f64), width: u32, color: Rgba<u8>) {
+    let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+    let steps = length.ceil().max(1.0) as u32;
+    for step in 0..=steps {
+        let t = step as f64 / steps as f64;
+        draw_disc(image, (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t), width as f64 / 2.0, color);
+    }
+}
+
+/// 3x5 pixels glyphs of the digits, one row per byte with the leftmost pixel in the third bit
+const DIGIT_GLYPHS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// size in pixels of a number drawn with [`draw_number`]
+pub fn number_dimensions(number: u32, scale: u32) -> (u32, u32) {
+    let digit_count = number.to_string().len() as u32;
+    ((digit_count * 4 - 1) * scale, 5 * scale)
+}
+
+/// draws a number with a small built-in digits font, each font pixel being a square of `scale` pixels, the pixels are
+/// replaced and not blended
+pub fn draw_number(image: &mut RgbaImage, position: (u32, u32), number: u32, scale: u32, color: Rgba<u8>) {
+    for (digit_index, digit) in number.to_string().bytes().enumerate() {
+        let glyph = DIGIT_GLYPHS[(digit - b'0') as usize];
+        let digit_x = position.0 + digit_index as u32 * 4 * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 { continue }
+                for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                    let (x, y) = (digit_x + column * scale + dx, position.1 + row as u32 * scale + dy);
+                    if x < image.width() && y < image.height() {
+                        image.put_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+    }
 }
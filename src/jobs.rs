@@ -0,0 +1,319 @@
+
+//! Scheduler running jobs such as FFMpeg processes concurrently
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::Error as IOError;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+
+use derive_more::From;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::{json, Value as JsonValue};
+use thiserror::Error;
+use tokio::task::JoinSet;
+
+use crate::ffmpeg;
+use crate::video::probe;
+
+
+pub const STATE_FILE_NAME: &str = ".hd_fpv_video_tool_jobs.json";
+
+/// tolerance when comparing the duration of an output file with the expected one
+const DURATION_TOLERANCE_SECONDS: f64 = 0.5;
+
+
+static MAX_JOBS: AtomicUsize = AtomicUsize::new(1);
+
+/// sets the maximum number of jobs run concurrently by the schedulers, for the whole process
+pub fn set_max_jobs(max_jobs: usize) {
+    MAX_JOBS.store(max_jobs.max(1), Ordering::Relaxed);
+}
+
+pub fn max_jobs() -> usize {
+    MAX_JOBS.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Error, From)]
+pub enum JobStateError {
+    #[error("failed accessing jobs state file: {0}")]
+    IOError(IOError),
+    #[error("invalid jobs state file: {0}")]
+    JSONError(serde_json::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputState {
+    /// the job producing the output was started but did not complete, the output is partial or missing
+    Started,
+    Completed { size: u64, duration: Option<f64> },
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    path.metadata().ok().map(|metadata| metadata.len())
+}
+
+fn duration_matches(path: &Path, expected_duration: f64) -> bool {
+    matches!(probe(path), Ok(video_info) if (video_info.duration() - expected_duration).abs() <= DURATION_TOLERANCE_SECONDS)
+}
+
+/// State of the outputs of the jobs persisted as JSON so that an interrupted run can be resumed
+///
+/// The outputs of the jobs which completed are skipped when resuming as long as their size and duration did not
+/// change, the ones of the jobs which were started are kept only if their duration is the expected one.
+#[derive(Debug)]
+pub struct JobState {
+    path: PathBuf,
+    outputs: BTreeMap<PathBuf, OutputState>,
+}
+
+impl JobState {
+
+    /// new state stored in the directory, replacing the previous one if any
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self { path: dir.as_ref().join(STATE_FILE_NAME), outputs: BTreeMap::new() }
+    }
+
+    /// loads the state stored in the directory, a missing state is empty
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, JobStateError> {
+        let mut state = Self::new(dir);
+        let content = match fs_err::read_to_string(&state.path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(state),
+            Err(error) => return Err(error.into()),
+        };
+        let json: JsonValue = serde_json::from_str(&content)?;
+        if let Some(outputs) = json["outputs"].as_object() {
+            for (output_file, output_json) in outputs {
+                let output_state = match (output_json["status"].as_str(), output_json["size"].as_u64()) {
+                    (Some("completed"), Some(size)) => OutputState::Completed { size, duration: output_json["duration"].as_f64() },
+                    _ => OutputState::Started,
+                };
+                state.outputs.insert(PathBuf::from(output_file), output_state);
+            }
+        }
+        Ok(state)
+    }
+
+    /// the outputs with paths which are not valid UTF-8 cannot be stored in JSON, they are not recorded and are then
+    /// produced again when resuming
+    fn save(&self) -> Result<(), JobStateError> {
+        if crate::process::dry_run() { return Ok(()) }
+        let outputs = self.outputs.iter().filter_map(|(output_file, output_state)| {
+            let output_file = match output_file.to_str() {
+                Some(output_file) => output_file,
+                None => {
+                    log::warn!("cannot record the state of output with non UTF-8 path: {}", output_file.to_string_lossy());
+                    return None;
+                },
+            };
+            let output_json = match output_state {
+                OutputState::Started => json!({ "status": "started" }),
+                OutputState::Completed { size, duration } => json!({ "status": "completed", "size": size, "duration": duration }),
+            };
+            Some((output_file.to_owned(), output_json))
+        }).collect::<serde_json::Map<_, _>>();
+        fs_err::write(&self.path, serde_json::to_string_pretty(&json!({ "outputs": outputs }))?)?;
+        Ok(())
+    }
+
+    /// removes the state file, done once all the jobs completed
+    pub fn remove(&self) -> Result<(), JobStateError> {
+        match fs_err::remove_file(&self.path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// whether the output file has been produced by a previous run
+    pub fn is_output_complete(&self, output_file: &Path, expected_duration: Option<f64>) -> bool {
+        match self.outputs.get(output_file) {
+            Some(OutputState::Completed { size, duration }) if file_size(output_file) == Some(*size) =>
+                match duration {
+                    Some(duration) => duration_matches(output_file, *duration),
+                    None => true,
+                },
+            Some(_) => matches!(expected_duration, Some(expected_duration) if duration_matches(output_file, expected_duration)),
+            None => false,
+        }
+    }
+
+    /// whether a previous run started writing the output file
+    pub fn is_output_recorded(&self, output_file: &Path) -> bool {
+        self.outputs.contains_key(output_file)
+    }
+
+    fn set_started(&mut self, output_file: &Path) -> Result<(), JobStateError> {
+        self.outputs.insert(output_file.to_path_buf(), OutputState::Started);
+        self.save()
+    }
+
+    fn set_completed(&mut self, output_file: &Path) -> Result<(), JobStateError> {
+        let size = file_size(output_file).unwrap_or(0);
+        let duration = probe(output_file).ok().map(|video_info| video_info.duration());
+        self.outputs.insert(output_file.to_path_buf(), OutputState::Completed { size, duration });
+        self.save()
+    }
+
+}
+
+type Job<E> = std::pin::Pin<Box<dyn Future<Output = Result<(), E>> + Send>>;
+
+/// Runs the jobs added to it with at most [`max_jobs`] of them running at the same time
+///
+/// When more than one job is run an overall progress bar counting the finished jobs is displayed above the
+/// FFMpeg progress bars. With a [`JobState`] the state of the outputs of the jobs is recorded so that the run
+/// can be resumed.
+pub struct Scheduler<E> {
+    jobs: Vec<(String, Job<E>)>,
+    state: Option<Arc<Mutex<JobState>>>,
+}
+
+impl<E: Send + 'static> Scheduler<E> {
+
+    pub fn new() -> Self {
+        Self { jobs: vec![], state: None }
+    }
+
+    pub fn with_state(state: JobState) -> Self {
+        Self { jobs: vec![], state: Some(Arc::new(Mutex::new(state))) }
+    }
+
+    /// adds a job, the name is used in the log messages
+    pub fn add<S: Into<String>, F: Future<Output = Result<(), E>> + Send + 'static>(&mut self, name: S, job: F) {
+        self.jobs.push((name.into(), Box::pin(job)));
+    }
+
+    /// adds a job writing the output file, skipped if the state shows the output file is already complete
+    ///
+    /// Failing to record the state of the output is only reported since it does not prevent the job from running.
+    pub fn add_with_output<F: Future<Output = Result<(), E>> + Send + 'static>(&mut self, output_file: PathBuf,
+            expected_duration: Option<f64>, job: F) {
+        let name = output_file.to_string_lossy().to_string();
+        let state = match &self.state {
+            Some(state) => state.clone(),
+            None => return self.add(name, job),
+        };
+        if state.lock().unwrap().is_output_complete(&output_file, expected_duration) {
+            log::info!("skipping job, output already complete: {name}");
+            return;
+        }
+        self.add(name, async move {
+            if let Err(error) = state.lock().unwrap().set_started(&output_file) {
+                log::warn!("{error}");
+            }
+            job.await?;
+            if let Err(error) = state.lock().unwrap().set_completed(&output_file) {
+                log::warn!("{error}");
+            }
+            Ok(())
+        });
+    }
+
+    /// whether the state shows a previous run started writing the output file which can then be overwritten
+    pub fn is_output_recorded(&self, output_file: &Path) -> bool {
+        matches!(&self.state, Some(state) if state.lock().unwrap().is_output_recorded(output_file))
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// runs all the jobs even if some of them fail, returns their results in the order they were added
+    pub async fn run(self) -> Vec<Result<(), E>> {
+        let job_count = self.jobs.len();
+        let max_jobs = max_jobs();
+
+        let overall_progress_bar = if job_count > 1 && max_jobs > 1 {
+            let progress_style = ProgressStyle::with_template("jobs {pos}/{len} {wide_bar}").unwrap();
+            let progress_bar = ffmpeg::progress_bars().insert(0, ProgressBar::new(job_count as u64).with_style(progress_style));
+            Some(progress_bar)
+        } else {
+            None
+        };
+
+        let mut results: Vec<Option<Result<(), E>>> = (0..job_count).map(|_| None).collect();
+        let mut pending_jobs = self.jobs.into_iter().enumerate();
+        let mut running_jobs = JoinSet::new();
+
+        loop {
+            while running_jobs.len() < max_jobs {
+                match pending_jobs.next() {
+                    Some((index, (name, job))) => {
+                        log::debug!("starting job: {name}");
+                        running_jobs.spawn(async move { (index, job.await) });
+                    },
+                    None => break,
+                }
+            }
+            let (index, result) = match running_jobs.join_next().await {
+                Some(join_result) => join_result.unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic())),
+                None => break,
+            };
+            results[index] = Some(result);
+            if let Some(progress_bar) = &overall_progress_bar {
+                progress_bar.inc(1);
+            }
+        }
+
+        if let Some(progress_bar) = overall_progress_bar {
+            progress_bar.finish_and_clear();
+        }
+
+        let results: Vec<Result<(), E>> = results.into_iter().map(Option::unwrap).collect();
+        if let Some(state) = &self.state {
+            if results.iter().all(Result::is_ok) {
+                if let Err(error) = state.lock().unwrap().remove() {
+                    log::warn!("{error}");
+                }
+            }
+        }
+        results
+    }
+
+    /// runs all the jobs and returns the first error if any of them failed
+    pub async fn run_all_ok(self) -> Result<(), E> {
+        self.run().await.into_iter().collect()
+    }
+
+}
+
+impl<E: Send + 'static> Default for Scheduler<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn state_saved_and_loaded_by_output_path() {
+        let dir = std::env::temp_dir().join(format!("hd_fpv_video_tool_jobs_test_{}", std::process::id()));
+        fs_err::create_dir_all(&dir).unwrap();
+        let (completed_file, started_file) = (dir.join("part 1.mp4"), dir.join("part 2.mp4"));
+        fs_err::write(&completed_file, b"complete output").unwrap();
+
+        let mut state = JobState::new(&dir);
+        state.outputs.insert(completed_file.clone(), OutputState::Completed { size: file_size(&completed_file).unwrap(), duration: None });
+        state.set_started(&started_file).unwrap();
+
+        let state = JobState::load(&dir).unwrap();
+        assert!(state.is_output_complete(&completed_file, None));
+        assert!(state.is_output_recorded(&started_file));
+        assert!(! state.is_output_complete(&started_file, None));
+        assert!(! state.is_output_recorded(&dir.join("part 3.mp4")));
+
+        fs_err::write(&completed_file, b"output changed since").unwrap();
+        assert!(! state.is_output_complete(&completed_file, None));
+
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+
+}
@@ -1,6 +1,17 @@
 
 // #![forbid(unsafe_code)]
 
+//! Library for working with videos recorded with the DJI and Walksnail Avatar FPV systems and their `.osd` files
+//!
+//! The `hd_fpv_video_tool` command line tool is a thin front-end over this library, the main entry points are:
+//!
+//! - [`OsdFile`]: reads DJI and Walksnail Avatar OSD files, use [`OsdFile::open`] then the [`osd::file::GenericReader`] methods
+//! - [`OverlayGenerator`]: renders the OSD frames read from an OSD file into transparent overlay images
+//! - [`Transcoder`]: transcodes a video with FFMpeg, optionally burning the OSD onto it
+//! - [`VideoProbe`]: video file information: resolution, frame rate, frame count, codec, ...
+//!
+//! The argument types in the [`cli`] module are only used to build these types from command line arguments.
+
 pub mod log_level;
 pub mod osd;
 pub mod create_path;
@@ -10,4 +21,17 @@ pub mod video;
 pub mod prelude;
 pub mod cli;
 pub mod ffmpeg;
-pub mod process;
\ No newline at end of file
+pub mod process;
+pub mod flight_path;
+pub mod blackbox;
+pub mod telemetry;
+pub mod doctor;
+pub mod watch;
+pub mod jobs;
+pub mod temp_dir;
+pub(crate) mod date_time;
+
+pub use osd::file::Reader as OsdFile;
+pub use osd::overlay::Generator as OverlayGenerator;
+pub use video::Transcoder;
+pub use video::probe::Result as VideoProbe;
\ No newline at end of file
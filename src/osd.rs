@@ -2,6 +2,7 @@
 pub mod file;
 pub mod font_variant;
 pub mod font_dir;
+pub mod font_preview;
 pub mod kind;
 pub mod overlay;
 pub mod dji;
@@ -12,11 +13,22 @@ pub mod coordinates;
 pub mod item;
 pub mod tile_indices;
 pub mod wsa;
+pub mod msp;
+pub mod item_series;
+pub mod flights;
+pub mod flight_stats;
+pub mod frame_shift;
+pub mod synthesize;
+pub(crate) mod dimensions_serde;
 
 use hd_fpv_osd_font_tool::dimensions::Dimensions as GenericDimensions;
 
 pub type Dimensions = GenericDimensions<u32>;
 
+/// frame rate the OSD frames are indexed at: the OSD frame indices are the indices of the 60 FPS video frames whatever
+/// the frame rate of the recorded video
+pub const FRAME_RATE: u32 = 60;
+
 pub use region::Region as Region;
 pub use coordinates::{
     Coordinate,
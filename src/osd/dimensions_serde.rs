@@ -0,0 +1,22 @@
+
+//! Serialization of the dimensions types of the font tool as `{ "width": .., "height": .. }` objects, to be used with
+//! `#[serde(with = "crate::osd::dimensions_serde")]`
+
+use hd_fpv_osd_font_tool::dimensions::Dimensions;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+
+#[derive(Serialize, Deserialize)]
+struct DimensionsFields {
+    width: u32,
+    height: u32,
+}
+
+pub fn serialize<S: Serializer>(dimensions: &Dimensions<u32>, serializer: S) -> Result<S::Ok, S::Error> {
+    DimensionsFields { width: dimensions.width, height: dimensions.height }.serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Dimensions<u32>, D::Error> {
+    let DimensionsFields { width, height } = DimensionsFields::deserialize(deserializer)?;
+    Ok(Dimensions::new(width, height))
+}
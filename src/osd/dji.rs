@@ -8,4 +8,6 @@ pub mod dimensions {
     pub const SD: Dimensions = Dimensions::new(30, 15);
     pub const FAKE_HD: Dimensions = Dimensions::new(60, 22);
     pub const HD: Dimensions = Dimensions::new(50, 18);
+    /// HD canvas used by Betaflight 4.4+ and INAV HD builds
+    pub const HD_CANVAS: Dimensions = Dimensions::new(53, 20);
 }
@@ -3,12 +3,12 @@ use std::{
     fmt::Display,
     io::{
         Error as IOError,
-        SeekFrom, Read, Seek,
+        SeekFrom, Read, Seek, Write, BufWriter,
     },
     path::{
         Path,
         PathBuf,
-    }, ops::RangeInclusive,
+    },
 };
 
 use byte_struct::*;
@@ -17,6 +17,7 @@ use getset::{Getters, CopyGetters};
 use derive_more::From;
 use itertools::Itertools;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use lazy_static::lazy_static;
 use fs_err::File;
@@ -25,13 +26,16 @@ use hd_fpv_osd_font_tool::prelude::*;
 
 use crate::{
     osd::{
-        Dimensions, FontVariant, file::{ReadError, Frame, sorted_frames::SortedUniqFrames, GenericReader}, Kind, TileIndices, tile_indices::TileIndex, kind::InvalidDimensionsError,
+        Dimensions, FontVariant, file::{ReadError, Frame, sorted_frames::SortedUniqFrames, GenericReader, integrity::{Issue, Scan}}, Kind, TileIndices, tile_indices::{self, TileIndex}, kind::InvalidDimensionsError,
     },
     video::FrameIndex as VideoFrameIndex,
 };
 
 const SIGNATURE: &str = "MSPOSD\x00";
-const SUPPORTED_FORMAT_VERSIONS: RangeInclusive<u16> = 1..=1;
+
+/// maximum gap between the index of the last complete frame and the index of a frame header found after corrupted
+/// data for it to be considered as the next frame rather than random bytes, 10 minutes of 60 FPS video
+const MAX_RESYNC_FRAME_INDEX_GAP: VideoFrameIndex = 10 * 60 * 60;
 
 #[derive(Debug, Error, From)]
 pub enum OpenError {
@@ -41,8 +45,8 @@ pub enum OpenError {
     InvalidSignature { file_path: PathBuf },
     #[error("invalid OSD dimensions in OSD file {file_path}: {dimensions}")]
     InvalidOSDDimensions { file_path: PathBuf, dimensions: Dimensions },
-    #[error("unsupported OSD file format version: {0}")]
-    UnsupportedFileFormatVersion(u16),
+    #[error(transparent)]
+    UnsupportedFileFormatVersion(UnsupportedFormatVersionError),
 }
 
 impl OpenError {
@@ -57,10 +61,111 @@ impl OpenError {
 
 }
 
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("unsupported OSD file format version {0}, supported: {}", FormatVersion::supported_list())]
+pub struct UnsupportedFormatVersionError(pub u16);
+
+/// Revisions of the DJI OSD file format, the format version follows the signature and determines the layout of the
+/// rest of the header and of the frames, each revision having its own decoders so that the files written with an
+/// unknown revision are rejected instead of being misparsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum FormatVersion {
+    /// format of the msp-osd WTFOS module: the header holds the OSD grid and tile dimensions, the offset of the OSD
+    /// and the font variant, each frame is made of its video frame index and of the tile indices of the full 60x22
+    /// grid or of the OSD grid
+    V1,
+}
+
+impl FormatVersion {
+
+    pub const ALL: [Self; 1] = [Self::V1];
+
+    pub fn number(&self) -> u16 {
+        match self {
+            Self::V1 => 1,
+        }
+    }
+
+    fn supported_list() -> String {
+        Self::ALL.iter().map(FormatVersion::number).join(", ")
+    }
+
+    /// length of the header fields following the format version
+    fn header_fields_len(&self) -> usize {
+        match self {
+            Self::V1 => FileHeaderV1Raw::BYTE_LEN,
+        }
+    }
+
+    fn first_frame_file_pos(&self) -> u64 {
+        (SIGNATURE.len() + u16::BYTE_LEN + self.header_fields_len()) as u64
+    }
+
+    fn decode_header_fields(&self, bytes: &[u8]) -> FileHeader {
+        match self {
+            Self::V1 => FileHeader::from(FileHeaderV1Raw::read_bytes(bytes)),
+        }
+    }
+
+    fn encode_header_fields(&self, header: &FileHeader) -> Vec<u8> {
+        match self {
+            Self::V1 => {
+                let mut bytes = vec![0; FileHeaderV1Raw::BYTE_LEN];
+                FileHeaderV1Raw::from(header).write_bytes(&mut bytes);
+                bytes
+            },
+        }
+    }
+
+    fn frame_header_len(&self) -> usize {
+        match self {
+            Self::V1 => FrameHeader::BYTE_LEN,
+        }
+    }
+
+    fn decode_frame_header(&self, bytes: &[u8]) -> FrameHeader {
+        match self {
+            Self::V1 => FrameHeader::read_bytes(bytes),
+        }
+    }
+
+    fn encode_frame_header(&self, frame_header: &FrameHeader) -> Vec<u8> {
+        match self {
+            Self::V1 => {
+                let mut bytes = vec![0; FrameHeader::BYTE_LEN];
+                frame_header.write_bytes(&mut bytes);
+                bytes
+            },
+        }
+    }
+
+}
+
+impl TryFrom<u16> for FormatVersion {
+    type Error = UnsupportedFormatVersionError;
+
+    fn try_from(number: u16) -> Result<Self, Self::Error> {
+        Self::ALL.into_iter().find(|version| version.number() == number).ok_or(UnsupportedFormatVersionError(number))
+    }
+}
+
+impl From<FormatVersion> for u16 {
+    fn from(format_version: FormatVersion) -> Self {
+        format_version.number()
+    }
+}
+
+impl Display for FormatVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.number())
+    }
+}
+
+/// header fields following the format version of the version 1 files
 #[derive(ByteStruct, Debug)]
 #[byte_struct_le]
-struct FileHeaderRaw {
-    format_version: u16,
+struct FileHeaderV1Raw {
     width_tiles: u8,
     height_tiles: u8,
     tile_width: u8,
@@ -70,7 +175,7 @@ struct FileHeaderRaw {
     font_variant: u8
 }
 
-#[derive(Debug, Getters)]
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
 #[getset(get = "pub")]
 pub struct Offset {
     x: u16,
@@ -87,17 +192,33 @@ impl Display for Offset {
 #[error("unknown font variant ID: {0}")]
 pub struct UnknownFontVariantID(pub u8);
 
-#[derive(Debug, Getters)]
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
 #[getset(get = "pub")]
 pub struct FileHeader {
-    format_version: u16,
+    format_version: FormatVersion,
+    #[serde(with = "crate::osd::dimensions_serde")]
     osd_dimensions: Dimensions,
+    #[serde(with = "crate::osd::dimensions_serde")]
     tile_dimensions: TileDimensions,
     offset: Offset,
     font_variant_id: u8
 }
 
 impl FileHeader {
+
+    /// header of the files written with the latest format version, with no offset
+    pub fn new(osd_dimensions: Dimensions, tile_dimensions: TileDimensions, font_variant: FontVariant) -> Self {
+        use FontVariant::*;
+        let font_variant_id = match font_variant {
+            Generic | Unknown => 0,
+            Betaflight => 1,
+            INAV => 2,
+            Ardupilot => 3,
+            KISSUltra => 4,
+        };
+        Self { format_version: FormatVersion::V1, osd_dimensions, tile_dimensions, offset: Offset { x: 0, y: 0 }, font_variant_id }
+    }
+
     pub fn font_variant(&self) -> FontVariant {
         use FontVariant::*;
         match self.font_variant_id {
@@ -111,10 +232,10 @@ impl FileHeader {
     }
 }
 
-impl From<FileHeaderRaw> for FileHeader {
-    fn from(fhr: FileHeaderRaw) -> Self {
+impl From<FileHeaderV1Raw> for FileHeader {
+    fn from(fhr: FileHeaderV1Raw) -> Self {
         Self {
-            format_version: fhr.format_version,
+            format_version: FormatVersion::V1,
             osd_dimensions: Dimensions::new(fhr.width_tiles as u32, fhr.height_tiles as u32),
             tile_dimensions: TileDimensions { width: fhr.tile_width as u32, height: fhr.tile_height as u32 },
             offset: Offset { x: fhr.x_offset, y: fhr.y_offset },
@@ -123,6 +244,21 @@ impl From<FileHeaderRaw> for FileHeader {
     }
 }
 
+impl From<&FileHeader> for FileHeaderV1Raw {
+    fn from(fh: &FileHeader) -> Self {
+        Self {
+            width_tiles: fh.osd_dimensions.width as u8,
+            height_tiles: fh.osd_dimensions.height as u8,
+            tile_width: fh.tile_dimensions.width as u8,
+            tile_height: fh.tile_dimensions.height as u8,
+            x_offset: fh.offset.x,
+            y_offset: fh.offset.y,
+            font_variant: fh.font_variant_id
+        }
+    }
+}
+
+/// frame header of the version 1 files
 #[derive(ByteStruct, Debug, CopyGetters)]
 #[getset(get_copy = "pub")]
 #[byte_struct_le]
@@ -131,7 +267,11 @@ pub struct FrameHeader {
     data_len: u32
 }
 
-const FIRST_FRAME_FILE_POS: u64 = (SIGNATURE.len() + FileHeaderRaw::BYTE_LEN) as u64;
+impl FrameHeader {
+    pub fn new(frame_index: VideoFrameIndex, data_len: u32) -> Self {
+        Self { frame_index, data_len }
+    }
+}
 
 #[derive(Getters, CopyGetters)]
 pub struct Reader {
@@ -153,20 +293,20 @@ impl Reader {
         Ok(())
     }
 
-    fn read_header(file: &mut File) -> Result<FileHeaderRaw, OpenError> {
-        let mut header_bytes = [0; FileHeaderRaw::BYTE_LEN];
-        file.read_exact(&mut header_bytes)?;
-        let header = FileHeaderRaw::read_bytes(&header_bytes);
-        if ! SUPPORTED_FORMAT_VERSIONS.contains(&header.format_version) {
-            return Err(OpenError::UnsupportedFileFormatVersion(header.format_version));
-        }
-        Ok(header)
+    /// reads the format version then the header fields with the decoder of this version
+    fn read_header(file: &mut File) -> Result<FileHeader, OpenError> {
+        let mut format_version_bytes = [0; u16::BYTE_LEN];
+        file.read_exact(&mut format_version_bytes)?;
+        let format_version = FormatVersion::try_from(u16::from_le_bytes(format_version_bytes))?;
+        let mut header_fields_bytes = vec![0; format_version.header_fields_len()];
+        file.read_exact(&mut header_fields_bytes)?;
+        Ok(format_version.decode_header_fields(&header_fields_bytes))
     }
 
     pub fn open<P: AsRef<Path>>(file_path: P) -> Result<Self, OpenError> {
         let mut file = File::open(&file_path)?;
         Self::check_signature(&file_path,&mut file)?;
-        let header: FileHeader = Self::read_header(&mut file).unwrap().into();
+        let header = Self::read_header(&mut file)?;
         let osd_kind = Kind::try_from(header.osd_dimensions()).map_err(|error| {
             let InvalidDimensionsError(dimensions) = error;
             OpenError::invalid_osd_dimensions(&file_path, dimensions)
@@ -176,10 +316,11 @@ impl Reader {
     }
 
     fn read_frame_header(&mut self) -> Result<Option<FrameHeader>, ReadError> {
-        let mut frame_header_bytes = [0; FrameHeader::BYTE_LEN];
+        let format_version = self.header.format_version;
+        let mut frame_header_bytes = vec![0; format_version.frame_header_len()];
         match self.file.read(&mut frame_header_bytes)? {
             0 => Ok(None),
-            FrameHeader::BYTE_LEN => Ok(Some(FrameHeader::read_bytes(&frame_header_bytes))),
+            len if len == frame_header_bytes.len() => Ok(Some(format_version.decode_frame_header(&frame_header_bytes))),
             _ => Err(ReadError::unexpected_eof(self.file.path()))
         }
     }
@@ -211,8 +352,15 @@ impl Reader {
     //     Ok(SortedUniqFrames::new(osd_kind, font_variant, frames))
     // }
 
+    /// opens another reader of the same file positioned at the first frame
+    pub fn try_clone(&self) -> Result<Self, IOError> {
+        let mut file = File::open(self.file.path())?;
+        file.seek(SeekFrom::Start(self.header.format_version.first_frame_file_pos()))?;
+        Ok(Self { file, header: self.header.clone(), osd_kind: self.osd_kind })
+    }
+
     pub fn rewind(&mut self) -> Result<(), IOError> {
-        self.file.seek(SeekFrom::Start(FIRST_FRAME_FILE_POS))?;
+        self.file.seek(SeekFrom::Start(self.header.format_version.first_frame_file_pos()))?;
         Ok(())
     }
 
@@ -243,6 +391,17 @@ impl Reader {
         self.into_iter()
     }
 
+    /// grid in which the tile indices of a frame are stored: frames with the full 60x22 grid are used by every OSD kind,
+    /// files recorded with the HD canvas layouts can also store only the tiles of the OSD
+    fn frame_grid_dimensions(&self, data_len: u32) -> Result<Dimensions, ReadError> {
+        let osd_dimensions = self.header.osd_dimensions;
+        match data_len as usize {
+            tile_indices::COUNT => Ok(tile_indices::DIMENSIONS),
+            data_len if data_len == (osd_dimensions.width * osd_dimensions.height) as usize => Ok(osd_dimensions),
+            _ => Err(ReadError::invalid_frame_data_length(self.file.path(), data_len)),
+        }
+    }
+
 }
 
 impl GenericReader for Reader {
@@ -251,10 +410,14 @@ impl GenericReader for Reader {
             Some(header) => header,
             None => return Ok(None),
         };
+        let grid_dimensions = self.frame_grid_dimensions(header.data_len())?;
         let mut data_bytes= vec![0; header.data_len() as usize * 2];
-        self.file.read_exact(&mut data_bytes)?;
-        let tile_indices = TileIndices::new(data_bytes.chunks_exact(u16::BYTE_LEN)
-            .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap())).collect());
+        self.file.read_exact(&mut data_bytes).map_err(|error| match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => ReadError::unexpected_eof(self.file.path()),
+            _ => error.into(),
+        })?;
+        let tile_indices = TileIndices::with_grid_dimensions(data_bytes.chunks_exact(u16::BYTE_LEN)
+            .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap())).collect(), grid_dimensions);
         Ok(Some(Frame::new(header.frame_index(), tile_indices)))
     }
 
@@ -336,20 +499,164 @@ impl<'a> IntoIterator for &'a mut Reader {
     }
 }
 
+#[derive(Debug, Error, From)]
+pub enum CreateError {
+    #[error(transparent)]
+    FileError(IOError),
+    #[error("output OSD file exists: {0}")]
+    FileExists(PathBuf),
+}
+
+/// Writes OSD files with the format version of the header
+pub struct Writer {
+    file: BufWriter<File>,
+    format_version: FormatVersion,
+    /// dropped after the file is flushed
+    partial_file: Option<crate::process::PartialFile>,
+}
+
+impl Writer {
+
+    pub fn create<P: AsRef<Path>>(file_path: P, header: &FileHeader, overwrite: bool) -> Result<Self, CreateError> {
+        let file_path = file_path.as_ref();
+        if ! overwrite && file_path.exists() { return Err(CreateError::FileExists(file_path.to_path_buf())) }
+        let partial_file = Some(crate::process::PartialFile::new(file_path));
+        let mut file = BufWriter::new(File::create(file_path)?);
+        file.write_all(SIGNATURE.as_bytes())?;
+        file.write_all(&header.format_version.number().to_le_bytes())?;
+        file.write_all(&header.format_version.encode_header_fields(header))?;
+        Ok(Self { file, format_version: header.format_version, partial_file })
+    }
+
+    /// the file is kept when the process is interrupted instead of being removed, for the files whose frames are
+    /// flushed as they are written
+    pub fn keep_when_interrupted(&mut self) {
+        self.partial_file = None;
+    }
+
+    pub fn write_frame(&mut self, frame: &Frame) -> Result<(), IOError> {
+        let frame_header = FrameHeader::new(frame.index(), frame.tile_indices().len() as u32);
+        self.file.write_all(&self.format_version.encode_frame_header(&frame_header))?;
+        let data_bytes = frame.tile_indices().iter().flat_map(|tile_index| tile_index.to_le_bytes()).collect::<Vec<_>>();
+        self.file.write_all(&data_bytes)
+    }
+
+    pub fn write_frames<'a>(&mut self, frames: impl IntoIterator<Item = &'a Frame>) -> Result<(), IOError> {
+        for frame in frames {
+            self.write_frame(frame)?;
+        }
+        Ok(())
+    }
+
+    /// writes the buffered frames into the file, e.g. to keep the file complete while recording
+    pub fn flush(&mut self) -> Result<(), IOError> {
+        self.file.flush()
+    }
+
+    pub fn finish(mut self) -> Result<(), IOError> {
+        self.file.flush()
+    }
+
+}
+
+enum ScannedFrame {
+    Complete { end: usize, index: VideoFrameIndex },
+    Truncated,
+    Invalid,
+}
+
+/// frame starting at `offset` in the bytes of the file, valid if its data length is one of the frame grids
+fn scan_frame(bytes: &[u8], offset: usize, file_header: &FileHeader) -> ScannedFrame {
+    let format_version = file_header.format_version;
+    let frame_header_len = format_version.frame_header_len();
+    let header = match bytes.get(offset..offset + frame_header_len) {
+        Some(header_bytes) => format_version.decode_frame_header(header_bytes),
+        None => return ScannedFrame::Truncated,
+    };
+    let osd_dimensions = file_header.osd_dimensions;
+    let data_len = header.data_len() as usize;
+    if data_len != tile_indices::COUNT && data_len != (osd_dimensions.width * osd_dimensions.height) as usize {
+        return ScannedFrame::Invalid;
+    }
+    let end = offset + frame_header_len + data_len * u16::BYTE_LEN;
+    if end > bytes.len() { return ScannedFrame::Truncated }
+    ScannedFrame::Complete { end, index: header.frame_index() }
+}
+
+/// checks the header and the frames of the bytes of a DJI OSD file, `None` if they do not start with the signature
+///
+/// After corrupted data the frames are looked for byte by byte, the next frame being the first valid frame whose
+/// index follows the index of the last complete frame.
+pub(crate) fn scan(bytes: &[u8]) -> Option<Scan> {
+    if ! bytes.starts_with(SIGNATURE.as_bytes()) { return None }
+    let format_version_offset = SIGNATURE.len();
+    let mut scan = Scan::new("DJI FPV", bytes.len());
+    let format_version = match bytes.get(format_version_offset..format_version_offset + u16::BYTE_LEN) {
+        Some(format_version_bytes) => u16::from_le_bytes(format_version_bytes.try_into().unwrap()),
+        None => {
+            scan.issues.push(Issue::TruncatedHeader);
+            return Some(scan);
+        },
+    };
+    let format_version = match FormatVersion::try_from(format_version) {
+        Ok(format_version) => format_version,
+        Err(UnsupportedFormatVersionError(format_version)) => {
+            scan.issues.push(Issue::UnsupportedFormatVersion(format_version));
+            return Some(scan);
+        },
+    };
+    let header_len = format_version.first_frame_file_pos() as usize;
+    let header = match bytes.get(format_version_offset + u16::BYTE_LEN..header_len) {
+        Some(header_fields_bytes) => format_version.decode_header_fields(header_fields_bytes),
+        None => {
+            scan.issues.push(Issue::TruncatedHeader);
+            return Some(scan);
+        },
+    };
+    scan.header_len = header_len;
+    if Kind::try_from(&header.osd_dimensions).is_err() {
+        scan.issues.push(Issue::InvalidOSDDimensions(header.osd_dimensions));
+        return Some(scan);
+    }
+    if header.font_variant() == FontVariant::Unknown {
+        scan.issues.push(Issue::UnknownFontVariant(header.font_variant_id.to_string()));
+    }
+
+    let mut offset = header_len;
+    let mut last_index = None;
+    while offset < bytes.len() {
+        match scan_frame(bytes, offset, &header) {
+            ScannedFrame::Complete { end, index } => {
+                scan.frames.push(offset..end);
+                last_index = Some(index);
+                offset = end;
+            },
+            ScannedFrame::Truncated => {
+                scan.issues.push(Issue::TruncatedFrame { offset: offset as u64, len: (bytes.len() - offset) as u64 });
+                break;
+            },
+            ScannedFrame::Invalid => {
+                let next_frame_offset = (offset + 1..bytes.len()).find(|candidate_offset|
+                    matches!(scan_frame(bytes, *candidate_offset, &header), ScannedFrame::Complete { index, .. }
+                        if last_index.map_or(true, |last_index| index >= last_index && index - last_index <= MAX_RESYNC_FRAME_INDEX_GAP))
+                ).unwrap_or(bytes.len());
+                scan.issues.push(Issue::CorruptedData { offset: offset as u64, len: (next_frame_offset - offset) as u64 });
+                offset = next_frame_offset;
+            },
+        }
+    }
+    Some(scan)
+}
+
 pub fn find_associated_to_video_file<P: AsRef<Path>>(video_file_path: P) -> Option<PathBuf> {
     let video_file_path = video_file_path.as_ref();
     let file_stem = video_file_path.file_stem()?.to_string_lossy();
-    lazy_static! { static ref DJI_VIDEO_FILE_RE: Regex = Regex::new(r"\A(?:DJI(?:G|U)(\d{4}))").unwrap(); }
+    // the files renamed by the file managers when copied keep the prefix, e.g. `DJIG0000 (1).mp4`, `djig0000.mp4`
+    lazy_static! { static ref DJI_VIDEO_FILE_RE: Regex = Regex::new(r"(?i)\A(?:DJI(?:G|U)(\d{4}))").unwrap(); }
 
     if let Some(captures) = DJI_VIDEO_FILE_RE.captures(&file_stem) {
         let dji_file_number = captures.get(1).unwrap().as_str();
-        let osd_file_path = video_file_path.with_file_name(format!("DJIG{dji_file_number}")).with_extension("osd");
-        if osd_file_path.is_file() {
-            log::info!("found: {}", osd_file_path.to_string_lossy());
-            return Some(osd_file_path);
-        } else {
-            log::info!("not found: {}", osd_file_path.to_string_lossy());
-        }
+        return crate::osd::file::find_with_osd_extension(&video_file_path.with_file_name(format!("DJIG{dji_file_number}")));
     }
 
     None
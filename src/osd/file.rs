@@ -1,6 +1,6 @@
 
 
-use std::{io::Error as IOError, path::{PathBuf, Path}};
+use std::{ffi::OsStr, io::Error as IOError, path::{PathBuf, Path}};
 
 use derive_more::From;
 use thiserror::Error;
@@ -8,25 +8,40 @@ use ambassador::{delegatable_trait, Delegate};
 
 pub mod frame;
 pub mod sorted_frames;
+pub mod frames_stream;
+pub mod pairing;
+pub mod integrity;
+pub mod timeline;
+pub mod diff;
+pub mod json;
+#[cfg(test)]
+pub(crate) mod test_files;
 
 pub use frame::Frame;
 
 pub use self::sorted_frames::SortedUniqFrames;
+pub use self::frames_stream::FramesStream;
 
-use super::{tile_indices::TileIndex, FontVariant};
+use super::{tile_indices::TileIndex, FontVariant, Kind};
 
 #[derive(Debug, Error, From)]
 pub enum ReadError {
     #[error(transparent)]
     FileError(IOError),
-    #[error("Unexpected end of file: {file_path}")]
-    UnexpectedEOF { file_path: PathBuf }
+    #[error("unexpected end of file: {file_path}, the last frame is truncated, the complete frames can be recovered with `osd repair`")]
+    UnexpectedEOF { file_path: PathBuf },
+    #[error("invalid frame data length in {file_path}: {data_len} tiles")]
+    InvalidFrameDataLength { file_path: PathBuf, data_len: u32 },
 }
 
 impl ReadError {
     pub fn unexpected_eof<P: AsRef<Path>>(file_path: P) -> Self {
         Self::UnexpectedEOF { file_path: file_path.as_ref().to_path_buf() }
     }
+
+    pub fn invalid_frame_data_length<P: AsRef<Path>>(file_path: P, data_len: u32) -> Self {
+        Self::InvalidFrameDataLength { file_path: file_path.as_ref().to_path_buf(), data_len }
+    }
 }
 
 #[delegatable_trait]
@@ -38,23 +53,46 @@ pub trait GenericReader {
     fn font_variant(&self) -> FontVariant;
 }
 
+/// whether the file name is the file stem with the `osd` extension, both compared ignoring the case
+fn is_osd_file_name(file_name: &OsStr, file_stem: &OsStr) -> bool {
+    let (file_name, file_stem) = (file_name.to_string_lossy().to_lowercase(), file_stem.to_string_lossy().to_lowercase());
+    matches!(file_name.strip_suffix(".osd"), Some(name_stem) if name_stem == file_stem)
+}
+
+/// the file of the directory of the path with the same file stem and the `osd` extension ignoring the case if it
+/// exists, the files copied from the goggles SD card sometimes have upper case names or extensions
+pub(crate) fn find_with_osd_extension(path: &Path) -> Option<PathBuf> {
+    let osd_file_path = path.with_extension("osd");
+    let found = if osd_file_path.is_file() {
+        Some(osd_file_path)
+    } else {
+        let (dir, file_stem) = (path.parent()?, path.file_stem()?);
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        fs_err::read_dir(dir).ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .find(|entry_path| entry_path.is_file() && matches!(entry_path.file_name(), Some(file_name) if is_osd_file_name(file_name, file_stem)))
+    };
+    match &found {
+        Some(osd_file_path) => log::info!("found: {}", osd_file_path.to_string_lossy()),
+        None => log::info!("not found: {}", path.with_extension("osd").to_string_lossy()),
+    }
+    found
+}
+
 pub fn find_associated_to_video_file<P: AsRef<Path>>(video_file_path: P) -> Option<PathBuf> {
-    let video_file_path = video_file_path.as_ref();
+    let video_file_path = &crate::file::local_path(video_file_path);
     log::info!("looking for OSD file associated to video file: {}", video_file_path.to_string_lossy());
 
-    let osd_file_path = video_file_path.with_extension("osd");
-    if osd_file_path.is_file() {
-        log::info!("found: {}", osd_file_path.to_string_lossy());
+    if let Some(osd_file_path) = find_with_osd_extension(video_file_path) {
         return Some(osd_file_path);
-    } else {
-        log::info!("not found: {}", osd_file_path.to_string_lossy());
     }
 
-    let file_stem = video_file_path.file_stem()?.to_string_lossy();
+    let file_stem = video_file_path.file_stem()?.to_string_lossy().to_lowercase();
 
-    if file_stem.starts_with("DJI") {
+    if file_stem.starts_with("dji") {
         super::dji::file::find_associated_to_video_file(video_file_path)
-    } else if file_stem.starts_with("Avatar") {
+    } else if file_stem.starts_with("avatar") {
         super::wsa::file::find_associated_to_video_file(video_file_path)
     } else {
         None
@@ -68,6 +106,31 @@ pub enum Reader {
     WSA(crate::osd::wsa::file::Reader),
 }
 
+impl Reader {
+
+    /// opens a DJI or Walksnail Avatar OSD file, see [`open`]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, UnrecognizedOSDFile> {
+        open(path)
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::DJI(reader) => reader.osd_kind(),
+            Self::WSA(_) => Kind::WSA,
+        }
+    }
+
+    /// stream of the frames read lazily from another reader of the same file, see [`FramesStream`]
+    pub fn frames_stream(&self) -> Result<FramesStream, ReadError> {
+        let reader = match self {
+            Self::DJI(reader) => Self::DJI(reader.try_clone()?),
+            Self::WSA(reader) => Self::WSA(reader.try_clone()?),
+        };
+        Ok(FramesStream::new(reader))
+    }
+
+}
+
 #[derive(Debug, Error)]
 #[error("unrecognized OSD file: {0}")]
 pub struct UnrecognizedOSDFile(PathBuf);
@@ -96,4 +159,26 @@ pub fn open(path: impl AsRef<Path>) -> Result<Reader, UnrecognizedOSDFile> {
     }
 
     Err(UnrecognizedOSDFile(path.to_owned()))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn osd_file_found_ignoring_the_case() {
+        assert!(is_osd_file_name(OsStr::new("djig0001.OSD"), OsStr::new("DJIG0001")));
+        assert!(! is_osd_file_name(OsStr::new("DJIG0001.mp4"), OsStr::new("DJIG0001")));
+        assert!(! is_osd_file_name(OsStr::new("DJIG00011.osd"), OsStr::new("DJIG0001")));
+
+        let dir = std::env::temp_dir().join(format!("hd_fpv_video_tool_osd_find_test_{}", std::process::id()));
+        fs_err::create_dir_all(&dir).unwrap();
+        fs_err::write(dir.join("djig0001.OSD"), b"").unwrap();
+        let found = find_with_osd_extension(&dir.join("DJIG0001")).unwrap();
+        assert!(found.file_name().unwrap().eq_ignore_ascii_case("djig0001.osd"));
+        assert_eq!(find_with_osd_extension(&dir.join("DJIG0002")), None);
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+
+}
@@ -0,0 +1,219 @@
+
+//! Comparison of two OSD files: header fields and tile grids of the frames with the same video frame index
+//!
+//! Useful to check what changed between two firmware versions or that an edit or repair round-trip preserved the
+//! frames. The tiles are compared by position on the screen so frames stored in grids of different dimensions are
+//! compared correctly.
+
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters};
+use itertools::{EitherOrBoth, Itertools};
+use thiserror::Error;
+
+use crate::{osd::{Coordinate, tile_indices::TileIndex}, video::FrameIndex as VideoFrameIndex};
+
+use super::{Frame, GenericReader, ReadError, Reader, SortedUniqFrames, UnrecognizedOSDFile};
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub")]
+pub struct HeaderDifference {
+    field: &'static str,
+    /// value in the first file, `-` if the field does not exist in this file format
+    first: String,
+    second: String,
+}
+
+impl Display for HeaderDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.first, self.second)
+    }
+}
+
+/// tile at the same position with different tile indices, 0 meaning that there is no tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct TileDifference {
+    x: Coordinate,
+    y: Coordinate,
+    first: TileIndex,
+    second: TileIndex,
+}
+
+impl Display for TileDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}: {} -> {}", self.x, self.y, self.first, self.second)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct FrameDifference {
+    #[getset(get_copy = "pub")]
+    index: VideoFrameIndex,
+    #[getset(get = "pub")]
+    tiles: Vec<TileDifference>,
+}
+
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct Diff {
+    #[getset(get = "pub")]
+    header_differences: Vec<HeaderDifference>,
+    #[getset(get_copy = "pub")]
+    first_frame_count: usize,
+    #[getset(get_copy = "pub")]
+    second_frame_count: usize,
+    /// video frame indices of the frames which only exist in the first file
+    #[getset(get = "pub")]
+    only_in_first: Vec<VideoFrameIndex>,
+    #[getset(get = "pub")]
+    only_in_second: Vec<VideoFrameIndex>,
+    /// frames existing in both files with different tiles
+    #[getset(get = "pub")]
+    differing_frames: Vec<FrameDifference>,
+}
+
+impl Diff {
+
+    pub fn is_identical(&self) -> bool {
+        self.header_differences.is_empty() && self.only_in_first.is_empty() && self.only_in_second.is_empty() &&
+            self.differing_frames.is_empty()
+    }
+
+    /// number of frames existing in both files with the same tiles
+    pub fn identical_frame_count(&self) -> usize {
+        self.first_frame_count - self.only_in_first.len() - self.differing_frames.len()
+    }
+
+}
+
+#[derive(Debug, Error, From)]
+pub enum DiffError {
+    #[error(transparent)]
+    UnrecognizedOSDFile(UnrecognizedOSDFile),
+    #[error(transparent)]
+    ReadError(ReadError),
+}
+
+fn header_fields(reader: &Reader) -> Vec<(&'static str, String)> {
+    match reader {
+        Reader::DJI(reader) => {
+            let header = reader.header();
+            vec![
+                ("file type", "DJI FPV".to_owned()),
+                ("format version", header.format_version().to_string()),
+                ("OSD size", header.osd_dimensions().to_string()),
+                ("tile dimensions", header.tile_dimensions().to_string()),
+                ("video offset", header.offset().to_string()),
+                ("font variant", format!("{} ({})", header.font_variant_id(), header.font_variant())),
+            ]
+        },
+        Reader::WSA(reader) => {
+            let header = reader.header();
+            vec![
+                ("file type", "Walksnail Avatar".to_owned()),
+                ("OSD size", header.osd_dimensions().to_string()),
+                ("font variant", format!("{} ({})", header.font_variant_id(), header.font_variant())),
+            ]
+        },
+    }
+}
+
+fn diff_headers(first: &Reader, second: &Reader) -> Vec<HeaderDifference> {
+    let (first_fields, second_fields) = (header_fields(first), header_fields(second));
+    let value = |fields: &[(&'static str, String)], field: &str| fields.iter().find(|(name, _)| *name == field)
+        .map(|(_, value)| value.clone()).unwrap_or_else(|| "-".to_owned());
+    first_fields.iter().chain(second_fields.iter()).map(|(field, _)| *field).unique()
+        .map(|field| HeaderDifference { field, first: value(&first_fields, field), second: value(&second_fields, field) })
+        .filter(|difference| difference.first != difference.second)
+        .collect()
+}
+
+/// tiles of the frame by screen position
+fn frame_tiles(frame: &Frame) -> BTreeMap<(Coordinate, Coordinate), TileIndex> {
+    frame.enumerate_tile_indices().map(|(coordinates, tile_index)| ((coordinates.x, coordinates.y), tile_index)).collect()
+}
+
+fn diff_frame(first: &Frame, second: &Frame) -> Option<FrameDifference> {
+    let (first_tiles, second_tiles) = (frame_tiles(first), frame_tiles(second));
+    let tiles = first_tiles.iter().merge_join_by(second_tiles.iter(), |(first_position, _), (second_position, _)| first_position.cmp(second_position))
+        .filter_map(|tiles| {
+            let ((x, y), first, second) = match tiles {
+                EitherOrBoth::Both((position, first), (_, second)) => (*position, *first, *second),
+                EitherOrBoth::Left((position, first)) => (*position, *first, 0),
+                EitherOrBoth::Right((position, second)) => (*position, 0, *second),
+            };
+            (first != second).then(|| TileDifference { x, y, first, second })
+        })
+        .collect::<Vec<_>>();
+    if tiles.is_empty() { return None }
+    Some(FrameDifference { index: first.index(), tiles })
+}
+
+/// compares the frames with the same video frame index
+pub fn diff_frames(first: &SortedUniqFrames, second: &SortedUniqFrames) -> (Vec<VideoFrameIndex>, Vec<VideoFrameIndex>, Vec<FrameDifference>) {
+    let mut only_in_first = vec![];
+    let mut only_in_second = vec![];
+    let mut differing_frames = vec![];
+    for frames in first.iter().merge_join_by(second.iter(), |first, second| first.index().cmp(&second.index())) {
+        match frames {
+            EitherOrBoth::Both(first, second) => differing_frames.extend(diff_frame(first, second)),
+            EitherOrBoth::Left(first) => only_in_first.push(first.index()),
+            EitherOrBoth::Right(second) => only_in_second.push(second.index()),
+        }
+    }
+    (only_in_first, only_in_second, differing_frames)
+}
+
+/// compares the headers and the frames of two DJI or Walksnail Avatar OSD files
+pub fn diff<P: AsRef<Path>, Q: AsRef<Path>>(first_osd_file: P, second_osd_file: Q) -> Result<Diff, DiffError> {
+    let mut first = super::open(first_osd_file)?;
+    let mut second = super::open(second_osd_file)?;
+    let header_differences = diff_headers(&first, &second);
+    let (first_frames, second_frames) = (first.frames()?, second.frames()?);
+    let (only_in_first, only_in_second, differing_frames) = diff_frames(&first_frames, &second_frames);
+    Ok(Diff {
+        header_differences,
+        first_frame_count: first_frames.len(),
+        second_frame_count: second_frames.len(),
+        only_in_first,
+        only_in_second,
+        differing_frames,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::osd::{FontVariant, Kind, TileIndices, tile_indices};
+
+    use super::*;
+
+    fn frame(index: VideoFrameIndex, tiles: &[(usize, TileIndex)]) -> Frame {
+        let mut indices = vec![0; tile_indices::COUNT];
+        for (position, tile_index) in tiles {
+            indices[*position] = *tile_index;
+        }
+        Frame::new(index, TileIndices::new(indices))
+    }
+
+    fn frames(frames: Vec<Frame>) -> SortedUniqFrames {
+        SortedUniqFrames::new(Kind::DJI_FakeHD, FontVariant::Betaflight, frames)
+    }
+
+    #[test]
+    fn frames_differences() {
+        let first = frames(vec![frame(0, &[(0, 1)]), frame(6, &[(0, 1), (23, 2)]), frame(12, &[])]);
+        let second = frames(vec![frame(0, &[(0, 1)]), frame(6, &[(0, 3), (45, 4)]), frame(18, &[])]);
+        let (only_in_first, only_in_second, differing_frames) = diff_frames(&first, &second);
+        assert_eq!(only_in_first, vec![12]);
+        assert_eq!(only_in_second, vec![18]);
+        assert_eq!(differing_frames, vec![FrameDifference { index: 6, tiles: vec![
+            TileDifference { x: 0, y: 0, first: 1, second: 3 },
+            TileDifference { x: 1, y: 1, first: 2, second: 0 },
+            TileDifference { x: 2, y: 1, first: 0, second: 4 },
+        ] }]);
+    }
+
+}
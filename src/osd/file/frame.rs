@@ -1,12 +1,13 @@
 use derive_more::Deref;
 use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    osd::{Region, TileIndices, tile_indices::{TileIndicesEnumeratorIter, UnknownOSDItem}, FontVariant},
+    osd::{Dimensions, Region, TileIndices, tile_indices::{TileIndicesEnumeratorIter, UnknownOSDItem}, FontVariant},
     video
 };
 
-#[derive(Debug, CopyGetters, Getters, Deref, Clone, PartialEq, Eq)]
+#[derive(Debug, CopyGetters, Getters, Deref, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Frame {
     #[getset(get_copy = "pub")]
     index: u32,
@@ -25,6 +26,19 @@ impl Frame {
         self.tile_indices().enumerate()
     }
 
+    /// renders the frame as lines of characters, tiles without a corresponding character are rendered with `symbol_char`
+    pub fn to_text(&self, font_variant: FontVariant, dimensions: Dimensions, symbol_char: char) -> String {
+        let mut lines = vec![vec![' '; dimensions.width as usize]; dimensions.height as usize];
+        for (coordinates, tile_index) in self.enumerate_tile_indices() {
+            if let Some(line) = lines.get_mut(coordinates.y as usize) {
+                if let Some(c) = line.get_mut(coordinates.x as usize) {
+                    *c = font_variant.glyph_char(tile_index).unwrap_or(symbol_char);
+                }
+            }
+        }
+        lines.iter().map(|line| line.iter().collect::<String>().trim_end().to_owned()).collect::<Vec<_>>().join("\n")
+    }
+
     pub fn with_erased_regions(&self, regions: &[Region]) -> Self {
         let mut tile_indices = self.tile_indices.clone();
         tile_indices.erase_regions(regions);
@@ -37,4 +51,21 @@ impl Frame {
         Ok(Self::new(self.index, tile_indices))
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn text_rendered_line_by_line_with_symbols_replaced() {
+        // 3x2 grid stored column by column
+        let tile_indices = TileIndices::with_grid_dimensions(vec![0x41, 0x43, 0x01, 0, 0x42, 0], Dimensions::new(3, 2));
+        let frame = Frame::new(0, tile_indices);
+        assert_eq!(frame.to_text(FontVariant::Betaflight, Dimensions::new(3, 2), '#'), "A#B\nC");
+        assert_eq!(FontVariant::INAV.glyph_char(0x5F), Some('_'));
+        assert_eq!(FontVariant::INAV.glyph_char(0x60), None);
+    }
+
+}
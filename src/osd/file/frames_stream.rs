@@ -0,0 +1,225 @@
+
+use getset::CopyGetters;
+
+use super::{Frame, GenericReader, ReadError, Reader};
+
+use crate::{osd::tile_indices::TileIndex, video::FrameIndex as VideoFrameIndex};
+
+
+/// summary of the frames of a stream, computed by reading the whole stream once
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct FramesStreamSummary {
+    last_frame_index: VideoFrameIndex,
+    highest_used_tile_index: TileIndex,
+    /// number of frames skipped by the stream because they are not stored in order
+    out_of_order_frame_count: usize,
+}
+
+/// Frames read lazily from an OSD file so that the memory used does not depend on the length of the recording
+///
+/// The frames are expected to be stored in increasing index order like in the files recorded by the goggles.
+/// Contrary to [`GenericReader::frames`] which sorts all the frames, frames with an index not greater than
+/// the index of the previous frame are skipped, they are counted in the [`FramesStreamSummary`] so that the frames
+/// of such files can be loaded and sorted instead.
+pub struct FramesStream {
+    reader: Reader,
+    last_index: Option<VideoFrameIndex>,
+    out_of_order_frame_count: usize,
+}
+
+impl FramesStream {
+
+    /// the frames are read from the current position of the reader
+    pub fn new(reader: Reader) -> Self {
+        Self { reader, last_index: None, out_of_order_frame_count: 0 }
+    }
+
+    /// reads the whole stream, returns `None` if there is no frame
+    pub fn summary(mut self) -> Result<Option<FramesStreamSummary>, ReadError> {
+        let mut summary: Option<FramesStreamSummary> = None;
+        for frame in self.by_ref() {
+            let frame = frame?;
+            let highest_used_tile_index = frame.tile_indices().as_slice().iter().max().copied().unwrap_or_default();
+            summary = Some(FramesStreamSummary {
+                last_frame_index: frame.index(),
+                highest_used_tile_index: summary.map_or(highest_used_tile_index, |summary| summary.highest_used_tile_index.max(highest_used_tile_index)),
+                out_of_order_frame_count: 0,
+            });
+        }
+        Ok(summary.map(|summary| FramesStreamSummary { out_of_order_frame_count: self.out_of_order_frame_count, ..summary }))
+    }
+
+}
+
+impl Iterator for FramesStream {
+    type Item = Result<Frame, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = match self.reader.read_frame().transpose()? {
+                Ok(frame) => frame,
+                Err(error) => return Some(Err(error)),
+            };
+            match self.last_index {
+                Some(last_index) if frame.index() <= last_index => {
+                    log::debug!("skipping out of order OSD frame {} after frame {}", frame.index(), last_index);
+                    self.out_of_order_frame_count += 1;
+                    continue;
+                },
+                _ => {},
+            }
+            self.last_index = Some(frame.index());
+            return Some(Ok(frame));
+        }
+    }
+}
+
+/// Same as [`super::sorted_frames::VideoFramesIter`] with the frames read lazily from a [`FramesStream`]
+pub struct VideoFramesStreamIter {
+    frames: Option<FramesStream>,
+    /// error which occurred when opening the stream, returned by the first call to `next`
+    open_error: Option<ReadError>,
+    next_frame: Option<Frame>,
+    skipped_first_frames: bool,
+    video_frame_index: u32,
+    last_video_frame_index: Option<u32>,
+    video_frame_shift: i32,
+    /// index of the last frame of the stream
+    last_frame_index: VideoFrameIndex,
+}
+
+impl VideoFramesStreamIter {
+
+    pub fn new(frames: Result<FramesStream, ReadError>, first_frame: u32, last_frame: Option<u32>, frame_shift: i32,
+            last_frame_index: VideoFrameIndex) -> Self {
+        let (frames, open_error) = match frames {
+            Ok(frames) => (Some(frames), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            frames,
+            open_error,
+            next_frame: None,
+            skipped_first_frames: false,
+            video_frame_index: first_frame,
+            last_video_frame_index: last_frame,
+            video_frame_shift: frame_shift,
+            last_frame_index,
+        }
+    }
+
+    fn read_next_frame(&mut self) -> Result<Option<Frame>, ReadError> {
+        match self.next_frame.take() {
+            Some(frame) => Ok(Some(frame)),
+            None => self.frames.as_mut().and_then(|frames| frames.next()).transpose(),
+        }
+    }
+
+    /// skips the frames before the first video frame
+    fn skip_first_frames(&mut self) -> Result<(), ReadError> {
+        let first_video_frame_index = self.video_frame_index as i32 - self.video_frame_shift;
+        while let Some(frame) = self.read_next_frame()? {
+            if frame.index() as i32 >= first_video_frame_index {
+                self.next_frame = Some(frame);
+                break;
+            }
+        }
+        self.skipped_first_frames = true;
+        Ok(())
+    }
+
+}
+
+impl Iterator for VideoFramesStreamIter {
+    type Item = Result<Option<Frame>, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.open_error.take() { return Some(Err(error)) }
+        if ! self.skipped_first_frames {
+            if let Err(error) = self.skip_first_frames() { return Some(Err(error)) }
+        }
+
+        if matches!(self.last_video_frame_index, Some(last_frame) if self.video_frame_index > last_frame) { return None }
+
+        let current_frame = match self.read_next_frame() {
+            Ok(Some(frame)) => frame,
+            // end of the frames, continue up to the last video frame if any
+            Ok(None) if self.last_video_frame_index.is_some() => {
+                self.video_frame_index += 1;
+                return Some(Ok(None));
+            },
+            Ok(None) => return None,
+            Err(error) => return Some(Err(error)),
+        };
+
+        let actual_frame_video_frame_index = current_frame.index() as i32 + self.video_frame_shift;
+
+        let frame =
+            if (self.video_frame_index as i32) < actual_frame_video_frame_index {
+                self.next_frame = Some(current_frame);
+                None
+            } else {
+                Some(current_frame)
+            };
+
+        self.video_frame_index += 1;
+
+        Some(Ok(frame))
+    }
+}
+
+impl ExactSizeIterator for VideoFramesStreamIter {
+    fn len(&self) -> usize {
+        match self.last_video_frame_index {
+            Some(last_video_frame_index) => last_video_frame_index as usize + 1,
+            None => self.last_frame_index as usize + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+
+    use super::*;
+    use crate::osd::file::{sorted_frames::GetFramesExt, test_files::write_dji_file};
+
+    fn frames_stream(path: &Path) -> FramesStream {
+        Reader::open(path).unwrap().frames_stream().unwrap()
+    }
+
+    #[test]
+    fn same_video_frames_as_sorted_frames() {
+        let path = write_dji_file("frames_stream_test", &[2, 8, 14, 20, 26]);
+        let sorted_frames = Reader::open(&path).unwrap().frames().unwrap();
+        let last_frame_index = frames_stream(&path).summary().unwrap().unwrap().last_frame_index();
+        assert_eq!(last_frame_index, 26);
+
+        for frame_shift in [-5, 0, 3] {
+            for first_frame in [0, 9] {
+                for last_frame in [None, Some(15), Some(40)] {
+                    let expected = sorted_frames.video_frames_iter(first_frame, last_frame, frame_shift);
+                    let actual = VideoFramesStreamIter::new(Ok(frames_stream(&path)), first_frame, last_frame, frame_shift, last_frame_index);
+                    assert_eq!(actual.len(), expected.len(), "shift {frame_shift}, first {first_frame}, last {last_frame:?}");
+                    let expected = expected.map(|frame| frame.map(Frame::index)).collect::<Vec<_>>();
+                    let actual = actual.map(|frame| frame.unwrap().map(|frame| frame.index())).collect::<Vec<_>>();
+                    assert_eq!(actual, expected, "shift {frame_shift}, first {first_frame}, last {last_frame:?}");
+                }
+            }
+        }
+
+        fs_err::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn out_of_order_frames_skipped() {
+        let path = write_dji_file("frames_stream_out_of_order_test", &[0, 6, 3, 6, 12]);
+        let frame_indices = frames_stream(&path).map(|frame| frame.unwrap().index()).collect::<Vec<_>>();
+        assert_eq!(frame_indices, [0, 6, 12]);
+        assert_eq!(frames_stream(&path).summary().unwrap().unwrap().out_of_order_frame_count(), 2);
+        fs_err::remove_file(&path).unwrap();
+    }
+
+}
@@ -0,0 +1,246 @@
+
+//! Integrity check and repair of the OSD files
+//!
+//! The OSD files are written frame by frame while recording so they end with a truncated frame when the goggles lose
+//! power, and the readers fail on them. The check walks the frames without decoding them, the repair writes a new
+//! OSD file made of the header and of all the complete frames, skipping the corrupted data.
+
+use std::{
+    fmt::Display,
+    io::{BufWriter, Error as IOError, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters};
+use thiserror::Error;
+
+use crate::osd::{Dimensions, dji, wsa};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    TruncatedHeader,
+    UnsupportedFormatVersion(u16),
+    InvalidOSDDimensions(Dimensions),
+    UnknownFontVariant(String),
+    /// incomplete frame at the end of the file
+    TruncatedFrame { offset: u64, len: u64 },
+    /// bytes which are not part of a frame, skipped until the next valid frame
+    CorruptedData { offset: u64, len: u64 },
+}
+
+impl Issue {
+
+    /// the frames cannot be read when the header is invalid so nothing can be recovered
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::TruncatedHeader | Self::UnsupportedFormatVersion(_) | Self::InvalidOSDDimensions(_))
+    }
+
+    /// the file can still be read, e.g. with the generic font variant
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Self::UnknownFontVariant(_))
+    }
+
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TruncatedHeader => write!(f, "the header is truncated"),
+            Self::UnsupportedFormatVersion(version) => dji::file::UnsupportedFormatVersionError(*version).fmt(f),
+            Self::InvalidOSDDimensions(dimensions) => write!(f, "invalid OSD dimensions: {dimensions}"),
+            Self::UnknownFontVariant(font_variant_id) => write!(f, "unknown font variant: {font_variant_id}"),
+            Self::TruncatedFrame { offset, len } =>
+                write!(f, "incomplete last frame of {len} bytes at offset {offset}, the recording was probably interrupted by a power loss"),
+            Self::CorruptedData { offset, len } => write!(f, "{len} bytes of corrupted data at offset {offset}"),
+        }
+    }
+}
+
+/// Result of the check of the frames of an OSD file made by the format readers: the byte ranges of the header and of
+/// the complete frames and the issues found
+pub(crate) struct Scan {
+    pub(crate) format: &'static str,
+    pub(crate) header_len: usize,
+    pub(crate) frames: Vec<Range<usize>>,
+    pub(crate) issues: Vec<Issue>,
+}
+
+impl Scan {
+
+    pub(crate) fn new(format: &'static str, header_len: usize) -> Self {
+        Self { format, header_len, frames: vec![], issues: vec![] }
+    }
+
+    fn report(&self, file_len: usize) -> Report {
+        Report {
+            format: self.format,
+            file_len: file_len as u64,
+            frame_count: self.frames.len(),
+            recoverable_len: (self.header_len + self.frames.iter().map(|frame| frame.len()).sum::<usize>()) as u64,
+            issues: self.issues.clone(),
+        }
+    }
+
+}
+
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct Report {
+    /// OSD file format, `DJI FPV` or `Walksnail Avatar`
+    #[getset(get_copy = "pub")]
+    format: &'static str,
+    #[getset(get_copy = "pub")]
+    file_len: u64,
+    /// number of complete frames
+    #[getset(get_copy = "pub")]
+    frame_count: usize,
+    /// size of the header and of the complete frames, the size of the repaired file
+    #[getset(get_copy = "pub")]
+    recoverable_len: u64,
+    #[getset(get = "pub")]
+    issues: Vec<Issue>,
+}
+
+impl Report {
+
+    pub fn is_valid(&self) -> bool {
+        self.issues.iter().all(Issue::is_warning)
+    }
+
+    pub fn is_repairable(&self) -> bool {
+        ! self.is_valid() && ! self.issues.iter().any(Issue::is_fatal)
+    }
+
+}
+
+#[derive(Debug, Error, From)]
+pub enum VerifyError {
+    #[error(transparent)]
+    FileError(IOError),
+    #[error("unrecognized OSD file: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    UnrecognizedFormat(PathBuf),
+}
+
+#[derive(Debug, Error, From)]
+pub enum RepairError {
+    #[error(transparent)]
+    VerifyError(VerifyError),
+    #[error("input file and output file are the same file")]
+    InputAndOutputFileIsTheSame,
+    #[error("output OSD file exists: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    OutputFileExists(PathBuf),
+    #[error("the OSD file cannot be repaired: {0}")]
+    #[from(ignore)]
+    Unrepairable(Issue),
+    #[error("failed writing repaired OSD file: {0}")]
+    WriteError(IOError),
+}
+
+fn scan_bytes(bytes: &[u8]) -> Option<Scan> {
+    dji::file::scan(bytes).or_else(|| wsa::file::scan(bytes))
+}
+
+fn scan_file(path: &Path) -> Result<(Vec<u8>, Scan), VerifyError> {
+    let bytes = fs_err::read(path)?;
+    let scan = scan_bytes(&bytes).ok_or_else(|| VerifyError::UnrecognizedFormat(path.to_path_buf()))?;
+    Ok((bytes, scan))
+}
+
+/// checks the header and the frames of a DJI or Walksnail Avatar OSD file
+pub fn verify<P: AsRef<Path>>(osd_file: P) -> Result<Report, VerifyError> {
+    let (bytes, scan) = scan_file(osd_file.as_ref())?;
+    Ok(scan.report(bytes.len()))
+}
+
+/// writes the header and the complete frames of the OSD file into a new OSD file, the report is the one of the input
+/// OSD file
+pub fn repair<P: AsRef<Path>, Q: AsRef<Path>>(osd_file: P, output_osd_file: Q, overwrite: bool) -> Result<Report, RepairError> {
+    let (osd_file, output_osd_file) = (osd_file.as_ref(), output_osd_file.as_ref());
+    if osd_file == output_osd_file { return Err(RepairError::InputAndOutputFileIsTheSame) }
+    if ! overwrite && output_osd_file.exists() { return Err(RepairError::OutputFileExists(output_osd_file.to_path_buf())) }
+
+    let (bytes, scan) = scan_file(osd_file)?;
+    let report = scan.report(bytes.len());
+    if let Some(issue) = report.issues.iter().find(|issue| issue.is_fatal()) {
+        return Err(RepairError::Unrepairable(issue.clone()));
+    }
+    if ! crate::process::plan_file_write(output_osd_file, "repaired OSD file") { return Ok(report) }
+
+    let _partial_file = crate::process::PartialFile::new(output_osd_file);
+    let mut output = BufWriter::new(fs_err::File::create(output_osd_file)?);
+    output.write_all(&bytes[..scan.header_len])?;
+    for frame in &scan.frames {
+        output.write_all(&bytes[frame.clone()])?;
+    }
+    output.flush()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::osd::file::test_files::{dji_frame, dji_header};
+
+    #[test]
+    fn dji_valid() {
+        let bytes = [dji_header(), dji_frame(0), dji_frame(6)].concat();
+        let report = scan_bytes(&bytes).unwrap().report(bytes.len());
+        assert_eq!(report.frame_count(), 2);
+        assert!(report.is_valid());
+        assert_eq!(report.recoverable_len(), bytes.len() as u64);
+    }
+
+    #[test]
+    fn dji_truncated_last_frame() {
+        let bytes = [dji_header(), dji_frame(0), dji_frame(6), dji_frame(12)[..100].to_vec()].concat();
+        let report = scan_bytes(&bytes).unwrap().report(bytes.len());
+        assert_eq!(report.frame_count(), 2);
+        assert!(report.is_repairable());
+        assert_eq!(report.issues(), &[Issue::TruncatedFrame { offset: report.recoverable_len(), len: 100 }]);
+    }
+
+    #[test]
+    fn dji_corrupted_data_skipped() {
+        let header_len = dji_header().len() as u64;
+        let frame_len = dji_frame(0).len() as u64;
+        let bytes = [dji_header(), dji_frame(0), vec![0xff; 50], dji_frame(6)].concat();
+        let report = scan_bytes(&bytes).unwrap().report(bytes.len());
+        assert_eq!(report.frame_count(), 2);
+        assert_eq!(report.issues(), &[Issue::CorruptedData { offset: header_len + frame_len, len: 50 }]);
+    }
+
+    #[test]
+    fn dji_unsupported_format_version() {
+        let mut bytes = [dji_header(), dji_frame(0)].concat();
+        bytes[7..9].copy_from_slice(&2u16.to_le_bytes());
+        let report = scan_bytes(&bytes).unwrap().report(bytes.len());
+        assert_eq!(report.issues(), &[Issue::UnsupportedFormatVersion(2)]);
+        assert!(! report.is_repairable());
+        assert_eq!(report.issues()[0].to_string(), "unsupported OSD file format version 2, supported: 1");
+    }
+
+    #[test]
+    fn wsa_truncated_last_frame() {
+        let mut bytes = b"INAV".to_vec();
+        bytes.extend_from_slice(&[0; 32]);
+        bytes.extend_from_slice(&53u16.to_le_bytes());
+        bytes.extend_from_slice(&20u16.to_le_bytes());
+        let frame_len = 4 + 53 * 20 * 2;
+        bytes.extend(std::iter::repeat(0).take(frame_len * 3 + 10));
+        let report = scan_bytes(&bytes).unwrap().report(bytes.len());
+        assert_eq!(report.format(), "Walksnail Avatar");
+        assert_eq!(report.frame_count(), 3);
+        assert_eq!(report.issues(), &[Issue::TruncatedFrame { offset: bytes.len() as u64 - 10, len: 10 }]);
+    }
+
+    #[test]
+    fn unrecognized() {
+        assert!(scan_bytes(b"not an OSD file").is_none());
+    }
+
+}
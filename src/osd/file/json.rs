@@ -0,0 +1,165 @@
+
+//! JSON representation of the OSD files allowing external tools to generate or transform OSD data
+//!
+//! The document is made of the header, tagged with the file type, and of the frames sorted by video frame index. The
+//! tile indices of a frame are stored column by column in a grid which is either the full 60x22 grid or the grid of
+//! the OSD dimensions of the header, a tile index of 0 meaning that there is no tile.
+//!
+//! ```json
+//! {
+//!   "header": { "file_type": "DJI", "format_version": 1, "osd_dimensions": { "width": 60, "height": 22 }, ... },
+//!   "frames": [ { "index": 0, "tile_indices": { "indices": [0, 0, ...], "grid_dimensions": { "width": 60, "height": 22 } } } ]
+//! }
+//! ```
+//!
+//! Only DJI OSD files can be written back from JSON documents.
+
+use std::{io::{Error as IOError, Read, Write}, path::Path};
+
+use derive_more::From;
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::osd::{Dimensions, Kind, dji, kind::InvalidDimensionsError, tile_indices, wsa};
+
+use super::{Frame, GenericReader, ReadError, Reader, UnrecognizedOSDFile};
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "file_type")]
+pub enum Header {
+    DJI(dji::file::FileHeader),
+    WSA(wsa::file::FileHeader),
+}
+
+#[derive(Debug, Clone, Getters, Serialize, Deserialize)]
+#[getset(get = "pub")]
+pub struct Document {
+    header: Header,
+    frames: Vec<Frame>,
+}
+
+#[derive(Debug, Error, From)]
+pub enum ExportError {
+    #[error(transparent)]
+    UnrecognizedOSDFile(UnrecognizedOSDFile),
+    #[error(transparent)]
+    ReadError(ReadError),
+    #[error("failed writing JSON: {0}")]
+    WriteError(serde_json::Error),
+}
+
+#[derive(Debug, Error, From)]
+pub enum ImportError {
+    #[error("invalid JSON OSD document: {0}")]
+    InvalidDocument(serde_json::Error),
+    #[error("only DJI OSD files can be written")]
+    UnsupportedFileType,
+    #[error(transparent)]
+    InvalidOSDDimensions(InvalidDimensionsError),
+    #[error("frame {index}: {tile_count} tile indices for a {grid_dimensions} grid")]
+    #[from(ignore)]
+    InvalidTileCount { index: u32, tile_count: usize, grid_dimensions: Dimensions },
+    #[error("frame {index}: invalid grid dimensions {grid_dimensions}, it must be the full {} grid or the OSD dimensions {osd_dimensions}",
+        tile_indices::DIMENSIONS)]
+    #[from(ignore)]
+    InvalidGridDimensions { index: u32, grid_dimensions: Dimensions, osd_dimensions: Dimensions },
+    #[error("duplicate frame index: {0}")]
+    #[from(ignore)]
+    DuplicateFrameIndex(u32),
+    #[error(transparent)]
+    CreateError(dji::file::CreateError),
+    #[error("failed writing OSD file: {0}")]
+    WriteError(IOError),
+}
+
+/// reads the header and the frames of a DJI or Walksnail Avatar OSD file
+pub fn read_document<P: AsRef<Path>>(osd_file: P) -> Result<Document, ExportError> {
+    let mut reader = super::open(osd_file)?;
+    let header = match &reader {
+        Reader::DJI(reader) => Header::DJI(reader.header().clone()),
+        Reader::WSA(reader) => Header::WSA(reader.header().clone()),
+    };
+    let frames = reader.frames()?.to_vec();
+    Ok(Document { header, frames })
+}
+
+/// writes the OSD file as a JSON document
+pub fn export<P: AsRef<Path>>(osd_file: P, output: impl Write) -> Result<(), ExportError> {
+    let document = read_document(osd_file)?;
+    serde_json::to_writer(output, &document)?;
+    Ok(())
+}
+
+/// checks that the frames can be written with the header: the tile indices fill their grid and the grid is one of
+/// the grids the readers accept, the frames are sorted by video frame index
+fn validate_frames(header: &dji::file::FileHeader, frames: &mut [Frame]) -> Result<(), ImportError> {
+    Kind::try_from(header.osd_dimensions())?;
+    frames.sort_by_key(Frame::index);
+    for frame in frames.iter() {
+        let grid_dimensions = frame.tile_indices().grid_dimensions();
+        if grid_dimensions != tile_indices::DIMENSIONS && grid_dimensions != *header.osd_dimensions() {
+            return Err(ImportError::InvalidGridDimensions { index: frame.index(), grid_dimensions, osd_dimensions: *header.osd_dimensions() });
+        }
+        let tile_count = frame.tile_indices().len();
+        if tile_count != (grid_dimensions.width * grid_dimensions.height) as usize {
+            return Err(ImportError::InvalidTileCount { index: frame.index(), tile_count, grid_dimensions });
+        }
+    }
+    if let Some(frames) = frames.windows(2).find(|frames| frames[0].index() == frames[1].index()) {
+        return Err(ImportError::DuplicateFrameIndex(frames[0].index()));
+    }
+    Ok(())
+}
+
+/// writes the OSD file described by a JSON document, returns the number of frames written
+pub fn import<P: AsRef<Path>>(input: impl Read, output_osd_file: P, overwrite: bool) -> Result<usize, ImportError> {
+    let Document { header, mut frames } = serde_json::from_reader(input)?;
+    let header = match header {
+        Header::DJI(header) => header,
+        Header::WSA(_) => return Err(ImportError::UnsupportedFileType),
+    };
+    validate_frames(&header, &mut frames)?;
+    if ! crate::process::plan_file_write(output_osd_file.as_ref(), "imported OSD file") { return Ok(frames.len()) }
+    let mut writer = dji::file::Writer::create(output_osd_file, &header, overwrite)?;
+    writer.write_frames(&frames)?;
+    writer.finish()?;
+    Ok(frames.len())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::osd::TileIndices;
+
+    use super::*;
+
+    const DJI_HEADER: &str = r#"{ "file_type": "DJI", "format_version": 1, "osd_dimensions": { "width": 60, "height": 22 },
+        "tile_dimensions": { "width": 24, "height": 36 }, "offset": { "x": 0, "y": 0 }, "font_variant_id": 1 }"#;
+
+    #[test]
+    fn frame_round_trip() {
+        let frame = Frame::new(6, TileIndices::new(vec![3; tile_indices::COUNT]));
+        let json = serde_json::to_string(&frame).unwrap();
+        assert_eq!(serde_json::from_str::<Frame>(&json).unwrap(), frame);
+    }
+
+    #[test]
+    fn unsupported_format_version() {
+        let header = DJI_HEADER.replace(r#""format_version": 1"#, r#""format_version": 2"#);
+        let error = serde_json::from_str::<Header>(&header).unwrap_err();
+        assert!(error.to_string().contains("unsupported OSD file format version 2"));
+    }
+
+    #[test]
+    fn invalid_tile_count() {
+        let header = match serde_json::from_str::<Header>(DJI_HEADER).unwrap() {
+            Header::DJI(header) => header,
+            Header::WSA(_) => unreachable!(),
+        };
+        let mut frames = vec![Frame::new(0, TileIndices::new(vec![0; 10]))];
+        assert!(matches!(validate_frames(&header, &mut frames), Err(ImportError::InvalidTileCount { index: 0, tile_count: 10, .. })));
+    }
+
+}
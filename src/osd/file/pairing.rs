@@ -0,0 +1,155 @@
+
+//! Pairing of the video files with their OSD file when their names do not allow finding it
+
+use std::{fmt::Display, path::{Path, PathBuf}, time::SystemTime};
+
+use getset::Getters;
+
+use crate::{osd, video::probe};
+
+use super::{GenericReader, find_associated_to_video_file, open};
+
+
+/// maximum difference between the durations of the video and the OSD, the OSD recording does not start and stop
+/// exactly at the same time as the video recording
+const MAX_DURATION_DIFFERENCE_SECONDS: f64 = 3.0;
+const MAX_DURATION_DIFFERENCE_RATIO: f64 = 0.05;
+
+/// how the OSD file associated to a video file is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum OSDFileDiscovery {
+    /// from the name of the video file, see [`find_associated_to_video_file`]
+    Name,
+    /// from the name of the video file then by looking for the OSD file of the directory with the duration and
+    /// modification time closest to the ones of the video file
+    Auto,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchReason {
+    Name,
+    Duration {
+        video_duration: f64,
+        osd_duration: f64,
+        /// difference between the modification times of the files in seconds if known
+        modification_time_difference: Option<f64>,
+    },
+}
+
+impl Display for MatchReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchReason::Name => f.write_str("matching name"),
+            MatchReason::Duration { video_duration, osd_duration, modification_time_difference } => {
+                write!(f, "duration {osd_duration:.1}s for a {video_duration:.1}s video")?;
+                if let Some(modification_time_difference) = modification_time_difference {
+                    write!(f, ", modified {modification_time_difference:.0}s apart")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct OSDFileMatch {
+    osd_file: PathBuf,
+    reason: MatchReason,
+}
+
+impl OSDFileMatch {
+    pub fn into_osd_file(self) -> PathBuf {
+        self.osd_file
+    }
+}
+
+impl Display for OSDFileMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.osd_file.to_string_lossy(), self.reason)
+    }
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|metadata| metadata.modified()).ok()
+}
+
+fn is_osd_file(path: &Path) -> bool {
+    path.is_file() && matches!(path.extension(), Some(extension) if extension.eq_ignore_ascii_case("osd"))
+}
+
+/// duration of the OSD recording in seconds
+fn osd_file_duration(path: &Path) -> Option<f64> {
+    let mut reader = open(path).ok()?;
+    let last_frame_index = reader.last_frame_frame_index().ok()?;
+    Some((last_frame_index + 1) as f64 / osd::FRAME_RATE as f64)
+}
+
+/// Looks for the OSD file in the directory of the video file whose recording matches the video recording
+///
+/// The candidates are the OSD files whose duration is close to the duration of the video, the one with the closest
+/// duration and modification time is returned.
+pub fn find_by_duration<P: AsRef<Path>>(video_file_path: P) -> Option<OSDFileMatch> {
+    let video_file_path = video_file_path.as_ref();
+    let video_duration = probe(video_file_path).ok()?.duration();
+    let local_video_file_path = crate::file::local_path(video_file_path);
+    let video_modification_time = modification_time(&local_video_file_path);
+    let dir = match local_video_file_path.parent() {
+        Some(dir) if ! dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let max_duration_difference = MAX_DURATION_DIFFERENCE_SECONDS.max(video_duration * MAX_DURATION_DIFFERENCE_RATIO);
+
+    let mut best_match: Option<(f64, OSDFileMatch)> = None;
+    for entry in fs_err::read_dir(dir).ok()? {
+        let osd_file = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        if ! is_osd_file(&osd_file) { continue }
+        let osd_duration = match osd_file_duration(&osd_file) {
+            Some(osd_duration) => osd_duration,
+            None => {
+                log::debug!("skipping unreadable OSD file: {}", osd_file.to_string_lossy());
+                continue;
+            },
+        };
+        let duration_difference = (osd_duration - video_duration).abs();
+        if duration_difference > max_duration_difference {
+            log::debug!("duration of {} does not match: {osd_duration:.1}s", osd_file.to_string_lossy());
+            continue;
+        }
+        let modification_time_difference = match (video_modification_time, modification_time(&osd_file)) {
+            (Some(video_time), Some(osd_time)) => {
+                let difference = video_time.duration_since(osd_time).or_else(|_| osd_time.duration_since(video_time)).unwrap();
+                Some(difference.as_secs_f64())
+            },
+            _ => None,
+        };
+        // a minute between the modification times weighs as much as a second between the durations
+        let score = duration_difference + modification_time_difference.unwrap_or(0.0) / 60.0;
+        if matches!(&best_match, Some((best_score, _)) if *best_score <= score) { continue }
+        let reason = MatchReason::Duration { video_duration, osd_duration, modification_time_difference };
+        best_match = Some((score, OSDFileMatch { osd_file, reason }));
+    }
+
+    best_match.map(|(_, osd_file_match)| osd_file_match)
+}
+
+/// Finds the OSD file associated to the video file and reports how it was matched
+pub fn find_osd_file<P: AsRef<Path>>(video_file_path: P, discovery: OSDFileDiscovery) -> Option<OSDFileMatch> {
+    let video_file_path = video_file_path.as_ref();
+    let osd_file_match = match find_associated_to_video_file(video_file_path) {
+        Some(osd_file) => Some(OSDFileMatch { osd_file, reason: MatchReason::Name }),
+        None if discovery == OSDFileDiscovery::Auto => {
+            log::info!("looking for an OSD file matching the video recording");
+            find_by_duration(video_file_path)
+        },
+        None => None,
+    };
+    if let Some(osd_file_match) = &osd_file_match {
+        log::info!("matched OSD file: {osd_file_match}");
+    }
+    osd_file_match
+}
@@ -1,5 +1,5 @@
 
-// use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use derive_more::Deref;
 use getset::CopyGetters;
@@ -12,7 +12,7 @@ use strum::EnumIter;
 
 use super::Frame;
 
-use crate::{video::FrameIndex as VideoFrameIndex, osd::{Kind, FontVariant, tile_indices::TileIndex}};
+use crate::{video::FrameIndex as VideoFrameIndex, osd::{Kind, FontVariant, Region, tile_indices::{TileIndex, UnknownOSDItem}}};
 
 
 #[derive(Deref, Clone, CopyGetters)]
@@ -34,6 +34,43 @@ impl SortedUniqFrames {
         Self { frames, kind, font_variant }
     }
 
+    fn with_frames(&self, frames: Vec<Frame>) -> Self {
+        Self::new(self.kind, self.font_variant, frames)
+    }
+
+    pub fn with_erased_regions(&self, regions: &[Region]) -> Self {
+        self.with_frames(self.frames.iter().map(|frame| frame.with_erased_regions(regions)).collect())
+    }
+
+    pub fn with_erased_osd_items(&self, item_names: &[String]) -> Result<Self, UnknownOSDItem> {
+        let frames = self.frames.iter().map(|frame|
+            frame.with_erased_osd_items(self.font_variant, item_names)
+        ).collect::<Result<Vec<_>, _>>()?;
+        Ok(self.with_frames(frames))
+    }
+
+    /// shifts the index of all the frames, frames ending up with a negative index are dropped
+    pub fn shifted(&self, frame_shift: i32) -> Self {
+        let frames = self.frames.iter().filter_map(|frame| {
+            let shifted_index = u32::try_from(frame.index() as i32 + frame_shift).ok()?;
+            Some(Frame::new(shifted_index, frame.tile_indices().clone()))
+        }).collect();
+        self.with_frames(frames)
+    }
+
+    /// keeps the frames displayed from `first_video_frame` to `last_video_frame` and re-indexes them so that
+    /// `first_video_frame` becomes frame 0. The frame being displayed at `first_video_frame` is kept as frame 0.
+    pub fn trimmed(&self, first_video_frame: u32, last_video_frame: Option<u32>) -> Self {
+        let displayed_frame_position = self.frames.iter().rposition(|frame| frame.index() <= first_video_frame);
+        let frames = self.frames.iter().enumerate().filter(|(position, frame)|
+            Some(*position) == displayed_frame_position ||
+                (frame.index() > first_video_frame && last_video_frame.map_or(true, |last| frame.index() <= last))
+        ).map(|(_, frame)|
+            Frame::new(frame.index().saturating_sub(first_video_frame), frame.tile_indices().clone())
+        ).collect();
+        self.with_frames(frames)
+    }
+
 }
 
 #[derive(Deref, Clone, CopyGetters)]
@@ -117,9 +154,20 @@ impl<'a> GetFrames for SortedUniqFramesForVideoSlice<'a> {
 #[derive(Deref)]
 pub struct SortedUniqFrameIndices(Vec<VideoFrameIndex>);
 
+/// how often a tile index is used in the frames
+#[derive(Debug, Clone, Copy, Default, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct TileUsage {
+    /// total number of tiles with this index in all the frames
+    occurrences: usize,
+    /// number of frames in which this index is used
+    frames: usize,
+}
+
 pub trait GetFramesExt {
     fn highest_video_frame_index(&self) -> Option<VideoFrameIndex>;
     fn highest_used_tile_index(&self) -> Option<TileIndex>;
+    fn tile_usage(&self) -> BTreeMap<TileIndex, TileUsage>;
     fn first_video_frame_index(&self, first_video_frame: u32, video_frame_shift: i32) -> Option<u32>;
     fn video_frame_indices(&self, video_frame_shift: i32) -> SortedUniqFrameIndices;
     fn shift_iter(&self, video_frame_shift: i32) -> ShiftIter;
@@ -137,6 +185,22 @@ impl<T> GetFramesExt for T where T: GetFrames {
         self.frames().iter().flat_map(|frame| frame.tile_indices().as_slice()).max().cloned()
     }
 
+    /// usage of each tile index, the empty tile index 0 is not included
+    fn tile_usage(&self) -> BTreeMap<TileIndex, TileUsage> {
+        let mut usage = BTreeMap::<TileIndex, TileUsage>::new();
+        for frame in self.frames() {
+            let mut frame_tile_indices = BTreeSet::new();
+            for (_, tile_index) in frame.enumerate_tile_indices() {
+                usage.entry(tile_index).or_default().occurrences += 1;
+                frame_tile_indices.insert(tile_index);
+            }
+            for tile_index in frame_tile_indices {
+                usage.entry(tile_index).or_default().frames += 1;
+            }
+        }
+        usage
+    }
+
     /// returns the video frame shifted index of the first frame which has a video frame shifted index greater than the specified first video frame
     fn first_video_frame_index(&self, first_video_frame: u32, video_frame_shift: i32) -> Option<u32> {
         let first_video_frame_index = first_video_frame as i32 - video_frame_shift;
@@ -609,7 +673,7 @@ mod tests {
 
     use crate::osd::{TileIndices, FontVariant, Kind};
 
-    use super::{SortedUniqFrames, EndOfFramesAction, VideoFramesRelIndexIterItem, VideoFramesRelIndexIter, ParallelVideoFramesRelIndexIter};
+    use super::{SortedUniqFrames, EndOfFramesAction, GetFramesExt, VideoFramesRelIndexIterItem, VideoFramesRelIndexIter, ParallelVideoFramesRelIndexIter};
 
 
     #[derive(PartialEq, Eq, Deref)]
@@ -691,4 +755,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tile_usage_counts_occurrences_and_frames() {
+        let frames = [
+            super::Frame::new(0, TileIndices::new(vec![0x41, 0x41, 0, 0x142])),
+            super::Frame::new(1, TileIndices::new(vec![0, 0x41])),
+            super::Frame::new(2, TileIndices::new(vec![0, 0])),
+        ];
+        let frames = SortedUniqFrames::new(Kind::DJI_HD, FontVariant::Betaflight, frames.to_vec());
+        let usage = frames.tile_usage().into_iter()
+            .map(|(tile_index, usage)| (tile_index, usage.occurrences(), usage.frames()))
+            .collect::<Vec<_>>();
+        assert_eq!(usage, [(0x41, 3, 2), (0x142, 1, 1)]);
+        assert_eq!(frames.highest_used_tile_index(), Some(0x142));
+    }
+
 }
\ No newline at end of file
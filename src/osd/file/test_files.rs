@@ -0,0 +1,35 @@
+//! DJI OSD file contents shared by the tests reading OSD files
+
+use std::path::PathBuf;
+
+/// number of tiles of the frames, the 60x22 FakeHD grid
+pub const DJI_FRAME_DATA_LEN: u32 = 60 * 22;
+
+/// header of a DJI OSD file with the FakeHD grid and the Betaflight font variant
+pub fn dji_header() -> Vec<u8> {
+    let mut bytes = b"MSPOSD\x00".to_vec();
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&[60, 22, 24, 36]);
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.push(1);
+    bytes
+}
+
+/// DJI OSD file frame with the index and all its tiles set to spaces
+pub fn dji_frame(index: u32) -> Vec<u8> {
+    let mut bytes = index.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&DJI_FRAME_DATA_LEN.to_le_bytes());
+    bytes.extend(std::iter::repeat(0x20).take(DJI_FRAME_DATA_LEN as usize * 2));
+    bytes
+}
+
+/// writes a DJI OSD file with frames of the indices in the temporary directory, the name is made unique per process
+pub fn write_dji_file(name: &str, frame_indices: &[u32]) -> PathBuf {
+    let mut bytes = dji_header();
+    for frame_index in frame_indices {
+        bytes.extend(dji_frame(*frame_index));
+    }
+    let path = std::env::temp_dir().join(format!("DJIG_{}_{}.osd", name, std::process::id()));
+    fs_err::write(&path, bytes).unwrap();
+    path
+}
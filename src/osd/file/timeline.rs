@@ -0,0 +1,257 @@
+
+//! Timeline of the OSD frames of a file: gaps between the frames, changes of the update rate and interruptions of the
+//! recording
+//!
+//! The frames are walked in the order they are stored, each one being tagged with the index of the 60 FPS video frame
+//! it is displayed from. The usual interval between two frames is the median interval, a gap is an interval of more
+//! than [`GAP_FACTOR`] times the usual one and an interruption is a gap of at least [`INTERRUPTION_MIN_FRAMES`] or a
+//! frame index going backwards, e.g. after the recording was restarted while the goggles kept writing the same file.
+//! Such interruptions are why an overlay starts drifting from the video mid-flight. The update rate is measured over
+//! windows of [`RATE_WINDOW_FRAMES`] and a change is reported when it differs by more than [`RATE_CHANGE_RATIO`].
+
+use std::{fmt::Display, io::{Error as IOError, Write}};
+
+use getset::{CopyGetters, Getters};
+
+use crate::{osd, video::{FrameIndex as VideoFrameIndex, Timestamp}};
+
+use super::{GenericReader, ReadError};
+
+
+/// an interval between two frames is a gap when it is this many times longer than the usual interval
+pub const GAP_FACTOR: u32 = 4;
+
+/// gaps of at least this number of video frames are interruptions of the recording
+pub const INTERRUPTION_MIN_FRAMES: u32 = 2 * osd::FRAME_RATE;
+
+/// number of video frames over which the update rate is measured
+pub const RATE_WINDOW_FRAMES: u32 = 10 * osd::FRAME_RATE;
+
+/// relative difference of update rate reported as a change
+pub const RATE_CHANGE_RATIO: f64 = 0.2;
+
+fn timestamp(frame_index: VideoFrameIndex) -> Timestamp {
+    Timestamp::from_milliseconds(frame_index as u64 * 1000 / osd::FRAME_RATE as u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    /// no OSD frame for `frame_count` video frames after the frame at `frame_index`
+    Gap { frame_index: VideoFrameIndex, frame_count: u32 },
+    /// gap long enough for the recording to have been interrupted
+    Interruption { frame_index: VideoFrameIndex, frame_count: u32 },
+    /// the frame stored at `position` in the file has a lower index than the previous frame
+    IndexReset { position: usize, from_frame_index: VideoFrameIndex, to_frame_index: VideoFrameIndex },
+    /// the update rate in Hz changed at the frame at `frame_index`
+    RateChange { frame_index: VideoFrameIndex, from_rate: f64, to_rate: f64 },
+}
+
+impl Event {
+
+    /// index of the video frame after which the event happens, used to sort the events
+    pub fn frame_index(&self) -> VideoFrameIndex {
+        match self {
+            Self::Gap { frame_index, .. } | Self::Interruption { frame_index, .. } | Self::RateChange { frame_index, .. } => *frame_index,
+            Self::IndexReset { from_frame_index, .. } => *from_frame_index,
+        }
+    }
+
+    pub fn is_interruption(&self) -> bool {
+        matches!(self, Self::Interruption { .. } | Self::IndexReset { .. })
+    }
+
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gap { frame_index, frame_count } =>
+                write!(f, "{} (frame {frame_index}): gap of {frame_count} frames ({:.2}s) without OSD update", timestamp(*frame_index),
+                    *frame_count as f64 / osd::FRAME_RATE as f64),
+            Self::Interruption { frame_index, frame_count } =>
+                write!(f, "{} (frame {frame_index}): recording interrupted for {frame_count} frames ({:.1}s)", timestamp(*frame_index),
+                    *frame_count as f64 / osd::FRAME_RATE as f64),
+            Self::IndexReset { position, from_frame_index, to_frame_index } =>
+                write!(f, "{} (frame {from_frame_index}): recording restarted, the OSD frame #{} of the file goes back to frame {to_frame_index}",
+                    timestamp(*from_frame_index), position + 1),
+            Self::RateChange { frame_index, from_rate, to_rate } =>
+                write!(f, "{} (frame {frame_index}): update rate changed from {from_rate:.1}Hz to {to_rate:.1}Hz", timestamp(*frame_index)),
+        }
+    }
+}
+
+/// period during which the OSD was updated at about the same rate
+#[derive(Debug, Clone, Copy, PartialEq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct RateSegment {
+    start_frame_index: VideoFrameIndex,
+    end_frame_index: VideoFrameIndex,
+    /// update rate in Hz
+    rate: f64,
+}
+
+impl Display for RateSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} - {} (frames {} - {}): {:.1}Hz", timestamp(self.start_frame_index), timestamp(self.end_frame_index),
+            self.start_frame_index, self.end_frame_index, self.rate)
+    }
+}
+
+#[derive(Debug, Clone, CopyGetters, Getters)]
+pub struct Timeline {
+    #[getset(get_copy = "pub")]
+    frame_count: usize,
+    /// usual number of video frames between two OSD frames
+    #[getset(get_copy = "pub")]
+    median_interval: u32,
+    #[getset(get = "pub")]
+    rate_segments: Vec<RateSegment>,
+    /// sorted by video frame index
+    #[getset(get = "pub")]
+    events: Vec<Event>,
+}
+
+impl Timeline {
+
+    /// timeline of the frames with the video frame indices in the order they are stored in the file
+    pub fn new(frame_indices: &[VideoFrameIndex]) -> Self {
+        let mut intervals = frame_indices.windows(2)
+            .filter(|indices| indices[1] > indices[0])
+            .map(|indices| indices[1] - indices[0])
+            .collect::<Vec<_>>();
+        intervals.sort_unstable();
+        let median_interval = intervals.get(intervals.len() / 2).copied().unwrap_or(1);
+
+        let mut events = vec![];
+        for (position, indices) in frame_indices.windows(2).enumerate() {
+            let (frame_index, next_frame_index) = (indices[0], indices[1]);
+            if next_frame_index < frame_index {
+                events.push(Event::IndexReset { position: position + 1, from_frame_index: frame_index, to_frame_index: next_frame_index });
+                continue;
+            }
+            let frame_count = next_frame_index - frame_index;
+            if frame_count >= INTERRUPTION_MIN_FRAMES {
+                events.push(Event::Interruption { frame_index, frame_count });
+            } else if frame_count > GAP_FACTOR * median_interval {
+                events.push(Event::Gap { frame_index, frame_count });
+            }
+        }
+
+        let rate_segments = Self::rate_segments(frame_indices);
+        events.extend(rate_segments.windows(2).map(|segments| Event::RateChange {
+            frame_index: segments[1].start_frame_index, from_rate: segments[0].rate, to_rate: segments[1].rate,
+        }));
+        events.sort_by_key(Event::frame_index);
+
+        Self { frame_count: frame_indices.len(), median_interval, rate_segments, events }
+    }
+
+    /// reads the frames of the file in the order they are stored
+    pub fn read<R: GenericReader>(reader: &mut R) -> Result<Self, ReadError> {
+        let mut frame_indices = vec![];
+        while let Some(frame) = reader.read_frame()? {
+            frame_indices.push(frame.index());
+        }
+        Ok(Self::new(&frame_indices))
+    }
+
+    /// update rate measured over consecutive windows, the windows with about the same rate being merged
+    fn rate_segments(frame_indices: &[VideoFrameIndex]) -> Vec<RateSegment> {
+        let mut sorted_indices = frame_indices.to_vec();
+        sorted_indices.sort_unstable();
+        sorted_indices.dedup();
+        let (first_frame_index, last_frame_index) = match (sorted_indices.first(), sorted_indices.last()) {
+            (Some(first), Some(last)) if last > first => (*first, *last),
+            _ => return vec![],
+        };
+
+        let mut segments: Vec<RateSegment> = vec![];
+        let mut window_indices = sorted_indices.as_slice();
+        let mut window_start = first_frame_index;
+        while window_start < last_frame_index {
+            let window_end = (window_start + RATE_WINDOW_FRAMES).min(last_frame_index);
+            let window_frame_count = window_indices.iter().take_while(|index| **index < window_end).count();
+            window_indices = &window_indices[window_frame_count..];
+            let rate = window_frame_count as f64 * osd::FRAME_RATE as f64 / (window_end - window_start) as f64;
+            match segments.last_mut() {
+                Some(segment) if (rate - segment.rate).abs() <= segment.rate * RATE_CHANGE_RATIO => {
+                    // the rate of the segment is the mean rate of its windows
+                    let segment_frames = (segment.end_frame_index - segment.start_frame_index) as f64;
+                    let window_frames = (window_end - window_start) as f64;
+                    segment.rate = (segment.rate * segment_frames + rate * window_frames) / (segment_frames + window_frames);
+                    segment.end_frame_index = window_end;
+                },
+                _ => segments.push(RateSegment { start_frame_index: window_start, end_frame_index: window_end, rate }),
+            }
+            window_start = window_end;
+        }
+        segments
+    }
+
+    pub fn interruption_count(&self) -> usize {
+        self.events.iter().filter(|event| event.is_interruption()).count()
+    }
+
+}
+
+/// Writes one CSV row per video frame from the frame 0 to the last OSD frame telling whether an OSD frame is
+/// displayed from it, to plot where the updates stalled
+///
+/// The columns are the video frame index, the time in seconds, 1 if the OSD is updated at this video frame else 0 and
+/// the number of video frames since the last update. The frame indices must be sorted.
+pub fn write_updates_csv<W: Write>(frame_indices: impl IntoIterator<Item = VideoFrameIndex>, output: &mut W) -> Result<(), IOError> {
+    writeln!(output, "frame,time,osd_frame,frames_since_update")?;
+    let mut next_frame_index = 0;
+    let mut last_update_frame_index = None;
+    for frame_index in frame_indices {
+        for video_frame_index in next_frame_index..frame_index {
+            let frames_since_update = last_update_frame_index.map(|last| (video_frame_index - last).to_string()).unwrap_or_default();
+            writeln!(output, "{video_frame_index},{:.3},0,{frames_since_update}", video_frame_index as f64 / osd::FRAME_RATE as f64)?;
+        }
+        if frame_index >= next_frame_index {
+            writeln!(output, "{frame_index},{:.3},1,0", frame_index as f64 / osd::FRAME_RATE as f64)?;
+            last_update_frame_index = Some(frame_index);
+            next_frame_index = frame_index + 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn gaps_and_interruptions() {
+        let mut frame_indices = (0..600).step_by(6).collect::<Vec<_>>();
+        frame_indices.extend((660..1200).step_by(6));
+        frame_indices.extend((1500..2400).step_by(6));
+        frame_indices.extend((100..200).step_by(6));
+        let timeline = Timeline::new(&frame_indices);
+        assert_eq!(timeline.median_interval(), 6);
+        assert!(timeline.events().contains(&Event::Gap { frame_index: 594, frame_count: 66 }));
+        assert!(timeline.events().contains(&Event::Interruption { frame_index: 1194, frame_count: 306 }));
+        assert!(timeline.events().contains(&Event::IndexReset { position: 340, from_frame_index: 2394, to_frame_index: 100 }));
+        assert_eq!(timeline.interruption_count(), 2);
+    }
+
+    #[test]
+    fn updates_csv() {
+        let mut csv = vec![];
+        write_updates_csv([1, 3], &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(),
+            "frame,time,osd_frame,frames_since_update\n0,0.000,0,\n1,0.017,1,0\n2,0.033,0,1\n3,0.050,1,0\n");
+    }
+
+    #[test]
+    fn rate_change() {
+        let mut frame_indices = (0..1200).step_by(6).collect::<Vec<_>>();
+        frame_indices.extend((1200..2400).step_by(2));
+        let timeline = Timeline::new(&frame_indices);
+        assert_eq!(timeline.rate_segments().len(), 2);
+        assert_eq!(timeline.rate_segments()[1].start_frame_index(), 1200);
+        assert!((timeline.rate_segments()[1].rate() - 30.0).abs() < 0.1);
+    }
+
+}
@@ -0,0 +1,138 @@
+
+//! Summary of a flight read from the OSD: flight time, maximum speed and altitude and battery capacity used
+//!
+//! The flight time is the time during which the quad was armed, see [`super::flights`]. The other values are read from
+//! the numeric OSD items displayed during the flights, in the units configured in the flight controller, and are
+//! missing when the item is not displayed or not known for the font variant. The capacity used is the increase of the
+//! mAh drawn counter, which is not reset when the quad is disarmed.
+
+use getset::CopyGetters;
+
+use super::{self as osd, FontVariant, Kind, file::Frame, flights, item_series::ItemSeries, tile_indices::UnknownOSDItem};
+
+
+pub const SPEED_OSD_ITEM: &str = "speed";
+pub const ALTITUDE_OSD_ITEM: &str = "alt";
+pub const MAH_DRAWN_OSD_ITEM: &str = "mah";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct FlightStats {
+    /// armed time in seconds, `None` when no flight was detected
+    flight_time: Option<f64>,
+    max_speed: Option<f64>,
+    max_altitude: Option<f64>,
+    /// battery capacity used in mAh
+    used_mah: Option<f64>,
+}
+
+/// lowest and highest values of the item displayed in the frames, `None` if the item is unknown for the font variant
+/// or not displayed
+fn item_range<'a>(frames: impl IntoIterator<Item = &'a Frame>, font_variant: FontVariant, item_name: &'static str)
+        -> Result<Option<(f64, f64)>, UnknownOSDItem> {
+    if font_variant.find_osd_item_location_data(item_name).is_none() { return Ok(None) }
+    Ok(ItemSeries::extract(frames, font_variant, item_name)?.range())
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl FlightStats {
+
+    /// Stats of the flights between the two OSD frames included
+    ///
+    /// The values are read from the first arming to the last disarming within these frames, or from all of them when
+    /// no flight is detected, e.g. when the video starts after the arming.
+    pub fn extract(frames: &[Frame], kind: Kind, font_variant: FontVariant, first_frame_index: u32, last_frame_index: u32)
+            -> Result<Self, UnknownOSDItem> {
+        let flights = flights::detect_flights(frames, kind, font_variant)?.into_iter()
+            .filter(|flight| flight.end_frame() >= first_frame_index && flight.start_frame() <= last_frame_index)
+            .collect::<Vec<_>>();
+        let (first_frame_index, last_frame_index) = match (flights.first(), flights.last()) {
+            (Some(first_flight), Some(last_flight)) =>
+                (first_flight.start_frame().max(first_frame_index), last_flight.end_frame().min(last_frame_index)),
+            _ => (first_frame_index, last_frame_index),
+        };
+        let flight_time = Some(flights.iter().map(|flight| {
+            let frame_count = flight.end_frame().min(last_frame_index) - flight.start_frame().max(first_frame_index);
+            frame_count as f64 / osd::FRAME_RATE as f64
+        }).sum::<f64>()).filter(|_| ! flights.is_empty());
+
+        let flight_frames = || frames.iter().filter(|frame| (first_frame_index..=last_frame_index).contains(&frame.index()));
+        let max = |range: Option<(f64, f64)>| range.map(|(_, max)| max);
+        Ok(Self {
+            flight_time,
+            max_speed: max(item_range(flight_frames(), font_variant, SPEED_OSD_ITEM)?),
+            max_altitude: max(item_range(flight_frames(), font_variant, ALTITUDE_OSD_ITEM)?),
+            used_mah: item_range(flight_frames(), font_variant, MAH_DRAWN_OSD_ITEM)?.map(|(min, max)| max - min),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// one line per known value, e.g. `Max speed 94`, the speed and altitude being in the units displayed by the OSD
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some(flight_time) = self.flight_time { lines.push(format!("Flight time {}", format_duration(flight_time))); }
+        if let Some(max_speed) = self.max_speed { lines.push(format!("Max speed {max_speed:.0}")); }
+        if let Some(max_altitude) = self.max_altitude { lines.push(format!("Max altitude {max_altitude:.0}")); }
+        if let Some(used_mah) = self.used_mah { lines.push(format!("Used {used_mah:.0} mAh")); }
+        lines
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::osd::{Dimensions, tile_indices::{TileIndex, TileIndices}};
+
+    /// frames every half second of two flights, armed from 10s to 50s and from 70s to 110s, with the Betaflight
+    /// altitude climbing during the flights and the mAh drawn counter increasing all along
+    fn flights_frames() -> Vec<Frame> {
+        (0..240).map(|sample: u32| {
+            let armed = (20..100).contains(&sample) || (140..220).contains(&sample);
+            let text = format!("{:8} \x7F{:<3}    {:>5}\x07", if armed { "" } else { "DISARMED" }, if armed { sample } else { 0 }, sample * 2);
+            let mut tiles = text.bytes().map(TileIndex::from).collect::<Vec<_>>();
+            tiles.resize(24, 0);
+            Frame::new(sample * 30, TileIndices::with_grid_dimensions(tiles, Dimensions::new(24, 1)))
+        }).collect()
+    }
+
+    fn extract(first_frame_index: u32, last_frame_index: u32) -> FlightStats {
+        FlightStats::extract(&flights_frames(), Kind::DJI_FakeHD, FontVariant::Betaflight, first_frame_index, last_frame_index).unwrap()
+    }
+
+    #[test]
+    fn stats_of_the_flights() {
+        assert_eq!(extract(0, 7200),
+            FlightStats { flight_time: Some(80.0), max_speed: None, max_altitude: Some(219.0), used_mah: Some(400.0) });
+    }
+
+    #[test]
+    fn stats_of_the_flights_clipped_to_the_section() {
+        assert_eq!(extract(1200, 3600),
+            FlightStats { flight_time: Some(30.0), max_speed: None, max_altitude: Some(99.0), used_mah: Some(120.0) });
+        assert_eq!(extract(1200, 5400),
+            FlightStats { flight_time: Some(50.0), max_speed: None, max_altitude: Some(180.0), used_mah: Some(280.0) });
+    }
+
+    #[test]
+    fn stats_of_the_section_without_flight() {
+        assert_eq!(extract(3300, 3900),
+            FlightStats { flight_time: None, max_speed: None, max_altitude: Some(0.0), used_mah: Some(40.0) });
+    }
+
+    #[test]
+    fn summary() {
+        let stats = FlightStats { flight_time: Some(204.4), max_speed: Some(94.0), max_altitude: None, used_mah: Some(1312.0) };
+        assert_eq!(stats.summary_lines(), ["Flight time 3:24", "Max speed 94", "Used 1312 mAh"]);
+        assert!(FlightStats::default().is_empty());
+    }
+
+}
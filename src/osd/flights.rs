@@ -0,0 +1,215 @@
+
+//! Detection of the flights recorded by a DVR from the OSD
+//!
+//! The quad is considered armed while the fly time displayed by the OSD is running: a flight starts at the first
+//! tick of the timer and ends when the timer stops or is reset or when the `DISARMED` warning appears. With the font
+//! variants whose fly time item is unknown, or when the fly time is not displayed by the OSD, the quad is considered
+//! armed whenever the `DISARMED` warning is not displayed.
+
+use std::path::Path;
+
+use derive_more::{Constructor, From};
+use getset::CopyGetters;
+use thiserror::Error;
+
+use crate::telemetry::{FLY_TIME_OSD_ITEM, parse_timer};
+use crate::video::{Timestamp, TimestampRange};
+
+use super::{self as osd, FontVariant, Kind, file::{self, Frame, GenericReader, ReadError, UnrecognizedOSDFile}, tile_indices::UnknownOSDItem};
+
+
+/// the fly time ticking every second, it is considered stopped when it did not change for this number of OSD frames
+const FLY_TIME_STOPPED_FRAMES: u32 = 3 * osd::FRAME_RATE;
+
+/// armed periods shorter than this number of OSD frames are not flights, e.g. arming on the bench to check the quad
+const MIN_FLIGHT_FRAMES: u32 = 5 * osd::FRAME_RATE;
+
+const DISARMED_WARNING: &str = "DISARMED";
+
+#[derive(Debug, Error, From)]
+pub enum FlightDetectionError {
+    #[error("no OSD file associated to the video was found")]
+    OSDFileNotFound,
+    #[error(transparent)]
+    UnrecognizedOSDFile(UnrecognizedOSDFile),
+    #[error("OSD file read error: {0}")]
+    OSDFileReadError(ReadError),
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+    #[error("no flight detected in the OSD file")]
+    NoFlightDetected,
+}
+
+/// Period during which the quad was armed, in OSD frame indices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters, Constructor)]
+#[getset(get_copy = "pub")]
+pub struct Flight {
+    start_frame: u32,
+    end_frame: u32,
+}
+
+fn frame_timestamp(frame_index: u32) -> Timestamp {
+    Timestamp::from_milliseconds(frame_index as u64 * 1000 / osd::FRAME_RATE as u64)
+}
+
+impl Flight {
+
+    pub fn start(&self) -> Timestamp {
+        frame_timestamp(self.start_frame)
+    }
+
+    pub fn end(&self) -> Timestamp {
+        frame_timestamp(self.end_frame)
+    }
+
+    /// duration in seconds
+    pub fn duration(&self) -> f64 {
+        (self.end_frame - self.start_frame) as f64 / osd::FRAME_RATE as f64
+    }
+
+    /// time range of the flight extended by `padding` seconds on both sides, the start being clamped to the start
+    /// of the video
+    pub fn padded_range(&self, padding: f64) -> TimestampRange {
+        let padding_frames = (padding.max(0.0) * osd::FRAME_RATE as f64).round() as u32;
+        TimestampRange::new(
+            Some(frame_timestamp(self.start_frame.saturating_sub(padding_frames))),
+            Some(frame_timestamp(self.end_frame + padding_frames)),
+        )
+    }
+
+}
+
+/// flight ended by the fly time stopping or being reset, the quad staying armed until the next tick of the timer
+fn timer_stopped(mut flight: Flight) -> Flight {
+    flight.end_frame += osd::FRAME_RATE;
+    flight
+}
+
+/// flights detected from the OSD frames, in chronological order
+pub fn detect_flights(frames: &[Frame], kind: Kind, font_variant: FontVariant) -> Result<Vec<Flight>, UnknownOSDItem> {
+    let has_fly_time_item = font_variant.find_osd_item_location_data(FLY_TIME_OSD_ITEM).is_some();
+    let fly_times = if has_fly_time_item {
+        frames.iter()
+            .map(|frame| Ok(frame.osd_item_text(font_variant, FLY_TIME_OSD_ITEM)?.as_deref().and_then(parse_timer)))
+            .collect::<Result<Vec<_>, UnknownOSDItem>>()?
+    } else {
+        vec![None; frames.len()]
+    };
+    let has_fly_time = fly_times.iter().any(Option::is_some);
+    if has_fly_time_item && ! has_fly_time {
+        log::info!("fly time not displayed by the OSD, detecting the flights from the {DISARMED_WARNING} warning");
+    }
+    let dimensions = kind.dimensions_tiles();
+    let mut flights = vec![];
+    let mut current_flight: Option<Flight> = None;
+    let mut previous_fly_time = None;
+
+    for (frame, fly_time) in frames.iter().zip(fly_times) {
+        let disarmed = frame.to_text(font_variant, dimensions, ' ').contains(DISARMED_WARNING);
+
+        if disarmed {
+            if let Some(mut flight) = current_flight.take() {
+                flight.end_frame = frame.index().clamp(flight.end_frame, flight.end_frame + FLY_TIME_STOPPED_FRAMES);
+                flights.push(flight);
+            }
+            previous_fly_time = fly_time.or(previous_fly_time);
+            continue;
+        }
+
+        let armed = if has_fly_time {
+            let ticked = matches!((previous_fly_time, fly_time), (Some(previous_fly_time), Some(fly_time)) if fly_time > previous_fly_time);
+            // the timer is reset when the quad is re-armed after a battery change with some firmwares
+            if matches!((previous_fly_time, fly_time), (Some(previous_fly_time), Some(fly_time)) if fly_time < previous_fly_time) {
+                flights.extend(current_flight.take().map(timer_stopped));
+            }
+            ticked
+        } else {
+            true
+        };
+        previous_fly_time = fly_time.or(previous_fly_time);
+
+        if armed {
+            match &mut current_flight {
+                // the frame indices go back when the OSD files of several recordings are read one after the other
+                Some(flight) if frame.index().checked_sub(flight.end_frame).map_or(false, |gap| gap <= FLY_TIME_STOPPED_FRAMES) =>
+                    flight.end_frame = frame.index(),
+                _ => {
+                    flights.extend(current_flight.take().map(|flight| if has_fly_time { timer_stopped(flight) } else { flight }));
+                    // the timer ticks one second after arming
+                    let start_frame = if has_fly_time { frame.index().saturating_sub(osd::FRAME_RATE) } else { frame.index() };
+                    current_flight = Some(Flight { start_frame, end_frame: frame.index() });
+                },
+            }
+        }
+    }
+    flights.extend(current_flight.map(|flight| if has_fly_time { timer_stopped(flight) } else { flight }));
+
+    Ok(flights.into_iter()
+        .filter(|flight| flight.end_frame - flight.start_frame >= MIN_FLIGHT_FRAMES)
+        .collect())
+}
+
+/// flights detected from the OSD file, fails if no flight is detected
+pub fn detect_osd_file_flights<P: AsRef<Path>>(osd_file: P) -> Result<Vec<Flight>, FlightDetectionError> {
+    let frames = file::open(osd_file)?.frames()?;
+    let flights = detect_flights(&frames, frames.kind(), frames.font_variant())?;
+    if flights.is_empty() { return Err(FlightDetectionError::NoFlightDetected) }
+    Ok(flights)
+}
+
+/// time range from the first arming to the last disarming extended by `padding` seconds on both sides
+pub fn armed_range(flights: &[Flight], padding: f64) -> Option<TimestampRange> {
+    let (first_flight, last_flight) = (flights.first()?, flights.last()?);
+    let armed_flight = Flight { start_frame: first_flight.start_frame, end_frame: last_flight.end_frame };
+    Some(armed_flight.padded_range(padding))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::osd::{Dimensions, tile_indices::{TileIndex, TileIndices}};
+
+    /// frame of a single line grid displaying the text, `~` standing for the Betaflight fly time symbol
+    fn text_frame(index: u32, text: &str) -> Frame {
+        let mut tiles = text.bytes().map(|byte| if byte == b'~' { 0x9C } else { TileIndex::from(byte) }).collect::<Vec<_>>();
+        tiles.resize(10, 0);
+        Frame::new(index, TileIndices::with_grid_dimensions(tiles, Dimensions::new(10, 1)))
+    }
+
+    /// frames every half second, disarmed for the first 10s and the last 10s of the minute
+    fn disarmed_frames(armed_text: &str) -> Vec<Frame> {
+        (0..120).map(|index| {
+            let text = if (20..100).contains(&index) { armed_text } else { DISARMED_WARNING };
+            text_frame(index * 30, text)
+        }).collect()
+    }
+
+    #[test]
+    fn flights_detected_from_the_disarmed_warning() {
+        let flights = detect_flights(&disarmed_frames(""), Kind::DJI_FakeHD, FontVariant::Generic).unwrap();
+        assert_eq!(flights, vec![Flight { start_frame: 600, end_frame: 3000 }]);
+    }
+
+    #[test]
+    fn flights_detected_from_the_disarmed_warning_when_the_timer_is_hidden() {
+        let flights = detect_flights(&disarmed_frames("12V"), Kind::DJI_FakeHD, FontVariant::Betaflight).unwrap();
+        assert_eq!(flights, vec![Flight { start_frame: 600, end_frame: 3000 }]);
+    }
+
+    #[test]
+    fn flight_ended_by_the_timer_reset() {
+        // frames every second, the timer being reset after 19s and ticking again for 19s
+        let frames = (0..40).map(|index| text_frame(index * 60, &format!("~0:{:02}", index % 20))).collect::<Vec<_>>();
+        let flights = detect_flights(&frames, Kind::DJI_FakeHD, FontVariant::Betaflight).unwrap();
+        assert_eq!(flights, vec![Flight { start_frame: 0, end_frame: 1200 }, Flight { start_frame: 1200, end_frame: 2400 }]);
+    }
+
+    #[test]
+    fn flight_ended_when_the_frame_indices_go_back() {
+        let frames = (0..40).map(|index| text_frame(index % 20 * 30, "")).collect::<Vec<_>>();
+        let flights = detect_flights(&frames, Kind::DJI_FakeHD, FontVariant::Generic).unwrap();
+        assert_eq!(flights, vec![Flight { start_frame: 0, end_frame: 570 }; 2]);
+    }
+
+}
@@ -1,45 +1,116 @@
 
 use std::path::{PathBuf, Path};
 
+use getset::{CopyGetters, Setters};
 use hd_fpv_osd_font_tool::prelude::*;
+use image::Rgba;
 
 use crate::osd::{font_variant::FontVariant, tile_indices::TileIndex};
 
 
-pub struct FontDir(PathBuf);
+/// number of tiles of a font page, the tile index of a glyph is its page index times this count plus its index in the page
+pub const PAGE_TILE_COUNT: usize = 256;
+
+/// the font files hold at most 2 pages: the base and extended font files
+pub const MAX_PAGE_COUNT: usize = 2;
+
+/// number of font pages to load to draw the tiles up to the tile index
+pub fn page_count(max_used_tile_index: TileIndex) -> usize {
+    (max_used_tile_index as usize / PAGE_TILE_COUNT + 1).min(MAX_PAGE_COUNT)
+}
+
+/// converts the pixel from premultiplied alpha to the straight alpha expected by the compositor
+fn unpremultiply(pixel: &mut Rgba<u8>) {
+    let alpha = pixel.0[3] as u32;
+    if alpha == 0 {
+        *pixel = Rgba([0, 0, 0, 0]);
+        return;
+    }
+    for channel in &mut pixel.0[0..3] {
+        *channel = (*channel as u32 * 255 / alpha).min(255) as u8;
+    }
+}
+
+/// Directory containing font sets
+///
+/// The directory can also be a font library holding the fonts of several firmwares in subdirectories named after the
+/// font variant, e.g. `betaflight/font.bin` and `inav/font_hd.bin`, in which case the font of each OSD file is taken
+/// from the subdirectory of the font variant of its header when it exists. This allows processing the recordings of
+/// quads running different firmwares with the same font directory.
+#[derive(Debug, Clone, Setters, CopyGetters)]
+pub struct FontDir {
+    dir: PathBuf,
+    /// the color channels of the font tiles are premultiplied by their alpha
+    #[getset(get_copy = "pub", set = "pub")]
+    premultiplied_alpha: bool,
+}
 
 impl FontDir {
 
     pub fn new<P: AsRef<Path>>(dir_path: P) -> Self {
-        Self(dir_path.as_ref().to_path_buf())
+        Self { dir: dir_path.as_ref().to_path_buf(), premultiplied_alpha: false }
     }
 
-    pub fn load(&self, tile_kind: tile::Kind, ident: &Option<&str>, max_used_tile_index: TileIndex) -> Result<Vec<Tile>, bin_file::LoadError> {
-        match max_used_tile_index {
-            max_index if max_index <= bin_file::TILE_COUNT as u16 => bin_file::load_base_norm(&self.0, tile_kind, ident),
-            _ => bin_file::load_extended_norm(&self.0, tile_kind, ident)
+    /// Loads the font pages needed to draw the tiles up to `max_used_tile_index` from the base and extended font
+    /// files, each page holding 256 tiles
+    pub fn load(&self, tile_kind: tile::Kind, ident: &Option<&str>, max_used_tile_index: TileIndex) -> Result<Vec<tile::Image>, bin_file::LoadError> {
+        let tiles = match page_count(max_used_tile_index) {
+            1 => bin_file::load_base_norm(&self.dir, tile_kind, ident)?,
+            _ => bin_file::load_extended_norm(&self.dir, tile_kind, ident)?,
+        };
+        let mut tile_images = tiles.into_iter().map(|tile| tile.image().clone()).collect::<Vec<_>>();
+        if self.premultiplied_alpha {
+            for tile_image in &mut tile_images {
+                tile_image.pixels_mut().for_each(unpremultiply);
+            }
         }
+        Ok(tile_images)
+    }
+
+    /// font directory of the variant in a font library, `None` if the directory has no subdirectory for the variant
+    fn library_variant_dir(&self, variant: &FontVariant) -> Option<Self> {
+        let dir = self.dir.join(variant.library_dir_name());
+        dir.is_dir().then(|| Self { dir, premultiplied_alpha: self.premultiplied_alpha })
     }
 
-    pub fn load_variant_with_fallback(&self, tile_kind: tile::Kind, variant: &FontVariant, max_used_tile_index: TileIndex) -> Result<Vec<Tile>, bin_file::LoadError> {
+    /// font directories and identifiers tried in order to load the font of the variant: the generic then the variant
+    /// font files of the subdirectory of the variant when the directory is a font library, then the variant font files
+    /// and finally the generic font files of the directory
+    fn variant_fallbacks<'a>(&self, variant: &'a FontVariant) -> Vec<(Self, Option<&'a str>)> {
         let ident = variant.font_set_ident();
-        let ident_load_result = self.load(tile_kind, &ident, max_used_tile_index);
-        let tiles = match (ident, ident_load_result) {
-            (None, Ok(tiles)) | (Some(_), Ok(tiles)) => tiles,
-            (None, error @ Err(_)) => return error,
-            (Some(ident), Err(error)) => {
-                if error.because_file_is_missing() {
-                    log::warn!("font for {variant} ({ident} ident) not found, falling back to generic font");
-                    self.load(tile_kind, &None, max_used_tile_index)?
-                } else {
-                    return Err(error);
-                }
-            },
-        };
-        Ok(tiles)
+        let mut fallbacks = vec![];
+        if let Some(variant_dir) = self.library_variant_dir(variant) {
+            fallbacks.push((variant_dir.clone(), None));
+            if ident.is_some() { fallbacks.push((variant_dir, ident)) }
+        }
+        if ident.is_some() { fallbacks.push((self.clone(), ident)) }
+        fallbacks.push((self.clone(), None));
+        fallbacks
     }
 
-    pub fn load_with_fallback(&self, tile_kind: tile::Kind, ident: &Option<&str>, highest_used_tile_index: TileIndex) -> Result<Vec<Tile>, bin_file::LoadError> {
+    /// Loads the font of the variant from the first of its fallbacks whose font files exist, see
+    /// [`Self::variant_fallbacks`]
+    pub fn load_variant_with_fallback(&self, tile_kind: tile::Kind, variant: &FontVariant, max_used_tile_index: TileIndex) -> Result<Vec<tile::Image>, bin_file::LoadError> {
+        let mut missing_error = None;
+        for (index, (font_dir, ident)) in self.variant_fallbacks(variant).into_iter().enumerate() {
+            match font_dir.load(tile_kind, &ident, max_used_tile_index) {
+                Ok(tiles) => {
+                    if font_dir.dir != self.dir {
+                        log::info!("using {variant} {tile_kind} font from font library: {}", font_dir.dir.to_string_lossy());
+                    } else if index > 0 && ident.is_none() {
+                        log::warn!("{variant} {tile_kind} font not found, falling back to the generic font");
+                    }
+                    return Ok(tiles);
+                },
+                Err(error) if error.because_file_is_missing() => missing_error = Some(error),
+                Err(error) => return Err(error),
+            }
+        }
+        // there is always at least the generic font fallback
+        Err(missing_error.unwrap())
+    }
+
+    pub fn load_with_fallback(&self, tile_kind: tile::Kind, ident: &Option<&str>, highest_used_tile_index: TileIndex) -> Result<Vec<tile::Image>, bin_file::LoadError> {
         let ident_load_result = self.load(tile_kind, ident, highest_used_tile_index);
         let tiles = match (ident, ident_load_result) {
             (None, Ok(tiles)) | (Some(_), Ok(tiles)) => tiles,
@@ -56,4 +127,34 @@ impl FontDir {
         Ok(tiles)
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn fallbacks(font_dir: &FontDir, variant: FontVariant) -> Vec<(PathBuf, Option<String>)> {
+        font_dir.variant_fallbacks(&variant).into_iter()
+            .map(|(font_dir, ident)| (font_dir.dir, ident.map(str::to_owned)))
+            .collect()
+    }
+
+    #[test]
+    fn variant_fonts_fall_back_to_the_generic_font() {
+        let dir = std::env::temp_dir().join(format!("hd_fpv_video_tool_font_library_test_{}", std::process::id()));
+        let betaflight_dir = dir.join(FontVariant::Betaflight.library_dir_name());
+        fs_err::create_dir_all(&betaflight_dir).unwrap();
+        let font_dir = FontDir::new(&dir);
+        let bf = || Some("bf".to_owned());
+
+        assert_eq!(fallbacks(&font_dir, FontVariant::Betaflight),
+            vec![(betaflight_dir.clone(), None), (betaflight_dir, bf()), (dir.clone(), bf()), (dir.clone(), None)]);
+        // no library subdirectory for INAV
+        assert_eq!(fallbacks(&font_dir, FontVariant::INAV), vec![(dir.clone(), Some("inav".to_owned())), (dir.clone(), None)]);
+        assert_eq!(fallbacks(&font_dir, FontVariant::Generic), vec![(dir.clone(), None)]);
+
+        fs_err::remove_dir_all(&dir).unwrap();
+    }
+
+}
@@ -0,0 +1,128 @@
+
+//! Font sheet previews: the tiles of a font drawn in a grid labelled with their tile index
+//!
+//! The tiles are laid out 16 per row so that the tile at row R and column C of a page has the index R * 16 + C in the
+//! page, which matches the hexadecimal numbering used by the firmware documentations. The pages are separated by a
+//! gap. The tiles are drawn over a light square so that the empty tiles can be told apart from the missing ones.
+
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use hd_fpv_osd_font_tool::prelude::*;
+use image::{Rgba, RgbaImage};
+use thiserror::Error;
+
+use crate::image::{WriteError, WriteImageFile, draw_number, number_dimensions};
+
+use super::font_dir::{MAX_PAGE_COUNT, PAGE_TILE_COUNT};
+
+
+/// number of tiles per row of the sheet
+const COLUMN_COUNT: usize = 16;
+
+const BACKGROUND_COLOR: Rgba<u8> = Rgba([48, 48, 48, 255]);
+const TILE_BACKGROUND_COLOR: Rgba<u8> = Rgba([96, 96, 96, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([255, 220, 0, 255]);
+
+#[derive(Debug, Error, From)]
+pub enum FontPreviewError {
+    #[error("output file exists: {0}")]
+    #[from(ignore)]
+    OutputFileExists(PathBuf),
+    #[error("the font has no tile")]
+    EmptyFont,
+    #[error(transparent)]
+    WriteError(WriteError),
+}
+
+/// Layout of the sheet in pixels
+struct SheetLayout {
+    label_scale: u32,
+    padding: u32,
+    cell_width: u32,
+    cell_height: u32,
+    page_gap: u32,
+}
+
+impl SheetLayout {
+
+    fn new(tile: &tile::Image) -> Self {
+        // the labels of the HD tiles are drawn twice as big as the labels of the SD tiles
+        let label_scale = (tile.height() / 18).max(1);
+        let padding = 2 * label_scale;
+        let (label_width, label_height) = number_dimensions((PAGE_TILE_COUNT * MAX_PAGE_COUNT - 1) as u32, label_scale);
+        Self {
+            label_scale,
+            padding,
+            cell_width: tile.width().max(label_width) + 2 * padding,
+            cell_height: label_height + tile.height() + 3 * padding,
+            page_gap: 4 * padding,
+        }
+    }
+
+    fn row_count(tile_count: usize) -> usize {
+        (tile_count + COLUMN_COUNT - 1) / COLUMN_COUNT
+    }
+
+    fn page_height(&self) -> u32 {
+        Self::row_count(PAGE_TILE_COUNT) as u32 * self.cell_height
+    }
+
+    /// position of the top left corner of the cell of the tile
+    fn cell_position(&self, tile_index: usize) -> (u32, u32) {
+        let (page, index_in_page) = (tile_index / PAGE_TILE_COUNT, tile_index % PAGE_TILE_COUNT);
+        let x = (index_in_page % COLUMN_COUNT) as u32 * self.cell_width;
+        let y = page as u32 * (self.page_height() + self.page_gap) + (index_in_page / COLUMN_COUNT) as u32 * self.cell_height;
+        (x + self.padding, y + self.padding)
+    }
+
+    fn dimensions(&self, tile_count: usize) -> (u32, u32) {
+        let page_count = (tile_count + PAGE_TILE_COUNT - 1) / PAGE_TILE_COUNT;
+        let last_page_row_count = Self::row_count(tile_count - (page_count - 1) * PAGE_TILE_COUNT) as u32;
+        let height = (page_count as u32 - 1) * (self.page_height() + self.page_gap) + last_page_row_count * self.cell_height;
+        (COLUMN_COUNT as u32 * self.cell_width + 2 * self.padding, height + 2 * self.padding)
+    }
+
+}
+
+/// draws the tiles of the font in a grid, each tile below its index
+pub fn render(tiles: &[tile::Image]) -> Result<RgbaImage, FontPreviewError> {
+    let first_tile = tiles.first().ok_or(FontPreviewError::EmptyFont)?;
+    let layout = SheetLayout::new(first_tile);
+    let (width, height) = layout.dimensions(tiles.len());
+    let mut sheet = RgbaImage::from_pixel(width, height, BACKGROUND_COLOR);
+    for (tile_index, tile) in tiles.iter().enumerate() {
+        let (x, y) = layout.cell_position(tile_index);
+        draw_number(&mut sheet, (x, y), tile_index as u32, layout.label_scale, LABEL_COLOR);
+        let tile_y = y + number_dimensions(0, layout.label_scale).1 + layout.padding;
+        let tile_background = RgbaImage::from_pixel(tile.width(), tile.height(), TILE_BACKGROUND_COLOR);
+        image::imageops::replace(&mut sheet, &tile_background, x as i64, tile_y as i64);
+        image::imageops::overlay(&mut sheet, tile, x as i64, tile_y as i64);
+    }
+    Ok(sheet)
+}
+
+/// writes the font sheet preview of the tiles as a PNG file
+pub fn write<P: AsRef<Path>>(tiles: &[tile::Image], output_file: P, overwrite: bool) -> Result<(), FontPreviewError> {
+    let output_file = output_file.as_ref();
+    if ! overwrite && output_file.exists() { return Err(FontPreviewError::OutputFileExists(output_file.to_path_buf())) }
+    render(tiles)?.write_image_file(output_file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn sheet_layout() {
+        let tiles = vec![tile::Image::new(12, 18); PAGE_TILE_COUNT + 1];
+        let sheet = render(&tiles).unwrap();
+        let layout = SheetLayout::new(&tiles[0]);
+        assert_eq!(layout.cell_position(17), (layout.cell_width + layout.padding, layout.cell_height + layout.padding));
+        assert_eq!(layout.cell_position(PAGE_TILE_COUNT).1, layout.page_height() + layout.page_gap + layout.padding);
+        assert_eq!(sheet.height(), layout.page_height() + layout.page_gap + layout.cell_height + 2 * layout.padding);
+    }
+
+}
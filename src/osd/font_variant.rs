@@ -1,7 +1,10 @@
 
+use serde::{Deserialize, Serialize};
 use strum::{Display, EnumIter};
 
-#[derive(Debug, Display, Clone, Copy, EnumIter, PartialEq, Eq, Hash)]
+use super::tile_indices::TileIndex;
+
+#[derive(Debug, Display, Clone, Copy, EnumIter, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FontVariant {
     Generic,
     Ardupilot,
@@ -22,5 +25,28 @@ impl FontVariant {
             Generic | Unknown => None,
         }
     }
+
+    /// name of the subdirectory of a font library holding the fonts of this variant, see [`crate::osd::FontDir`]
+    pub fn library_dir_name(&self) -> &'static str {
+        use FontVariant::*;
+        match self {
+            Generic => "generic",
+            Ardupilot => "ardupilot",
+            Betaflight => "betaflight",
+            INAV => "inav",
+            KISSUltra => "kiss-ultra",
+            Unknown => "unknown",
+        }
+    }
+
+    /// character drawn by the tile in the fonts of this variant, `None` for the symbols
+    ///
+    /// All the font variants draw the printable ASCII characters from space to underscore at their ASCII code.
+    pub fn glyph_char(&self, tile_index: TileIndex) -> Option<char> {
+        match tile_index {
+            0x20..=0x5F => Some(tile_index as u8 as char),
+            _ => None,
+        }
+    }
 }
 
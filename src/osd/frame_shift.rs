@@ -0,0 +1,98 @@
+
+//! Shift of the OSD frames used to sync the OSD with the video, given as a number of frames or as a duration
+//!
+//! A duration is easier to reason about when mixing DVR videos recorded at different frame rates, it is converted to
+//! a number of frames with the frame rate the frames are counted at.
+
+use std::{fmt::Display, str::FromStr};
+
+use ffmpeg_next::Rational;
+use thiserror::Error;
+
+use crate::osd;
+
+
+#[derive(Debug, Error)]
+#[error("invalid frame shift: {0}, expected a number of frames or a number of milliseconds with the ms suffix, e.g. -3 or 120ms")]
+pub struct FrameShiftFormatError(String);
+
+/// OSD frame shift parsed from `<frames>` or `<milliseconds>ms`, e.g. `-3` or `120ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameShift {
+    Frames(i32),
+    Milliseconds(i32),
+}
+
+impl FrameShift {
+
+    /// number of frames at the frame rate, the durations being rounded to the closest frame
+    pub fn frames(&self, frame_rate: Rational) -> i32 {
+        match *self {
+            Self::Frames(frames) => frames,
+            Self::Milliseconds(milliseconds) =>
+                (milliseconds as f64 * frame_rate.numerator() as f64 / frame_rate.denominator() as f64 / 1000.0).round() as i32,
+        }
+    }
+
+    /// number of OSD frames
+    pub fn osd_frames(&self) -> i32 {
+        self.frames(Rational::new(osd::FRAME_RATE as i32, 1))
+    }
+
+}
+
+impl From<i32> for FrameShift {
+    fn from(frames: i32) -> Self {
+        Self::Frames(frames)
+    }
+}
+
+impl FromStr for FrameShift {
+    type Err = FrameShiftFormatError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || FrameShiftFormatError(value.to_owned());
+        let value = value.trim();
+        match value.strip_suffix("ms") {
+            Some(milliseconds) => Ok(Self::Milliseconds(milliseconds.trim().parse().map_err(|_| error())?)),
+            None => Ok(Self::Frames(value.parse().map_err(|_| error())?)),
+        }
+    }
+}
+
+impl Display for FrameShift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Frames(frames) => write!(f, "{frames} frames"),
+            Self::Milliseconds(milliseconds) => write!(f, "{milliseconds}ms"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_frames() {
+        assert_eq!("-3".parse::<FrameShift>().unwrap(), FrameShift::Frames(-3));
+    }
+
+    #[test]
+    fn parse_milliseconds() {
+        assert_eq!("120ms".parse::<FrameShift>().unwrap(), FrameShift::Milliseconds(120));
+        assert_eq!("-50ms".parse::<FrameShift>().unwrap(), FrameShift::Milliseconds(-50));
+        assert!("120s".parse::<FrameShift>().is_err());
+    }
+
+    #[test]
+    fn milliseconds_to_frames() {
+        let shift = FrameShift::Milliseconds(120);
+        assert_eq!(shift.frames(Rational::new(30, 1)), 4);
+        assert_eq!(shift.frames(Rational::new(120, 1)), 14);
+        assert_eq!(shift.osd_frames(), 7);
+        assert_eq!(FrameShift::Milliseconds(-100).frames(Rational::new(30000, 1001)), -3);
+    }
+
+}
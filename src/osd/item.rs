@@ -64,18 +64,37 @@ const fn lde(name: &'static str, marker_tile_indices: &'static [TileIndex], top_
 }
 
 mod location_data {
-    use super::{LocationData, ld, ldo};
+    use super::{LocationData, TileIndex, ld, ldo};
+
+    const BATTERY_SYMBOLS: [TileIndex; 7] = [0x90, 0x91, 0x92, 0x93, 0x94, 0x95, 0x96];
+
+    pub const BETAFLIGHT: [LocationData; 8] = [
+        ld("alt", &[0x7F], 7),
+        ld("speed", &[0x70], 5),
+        ld("rssi", &[0x01], 4),
+        ld("bat", &BATTERY_SYMBOLS, 6),
+        ldo("curr", &[0x9A], -5, 6),
+        ldo("mah", &[0x07], -5, 6),
+        ld("sats", &[0x1E], 4),
+        ld("flytime", &[0x9C], 6),
+    ];
 
-    pub const INAV: [LocationData; 3] = [
+    pub const INAV: [LocationData; 5] = [
         ld("gpslat", &[3], 10),
         ld("gpslon", &[4], 10),
         ldo("alt", &[0x76, 0x77, 0x78, 0x79], -4, 5),
+        ld("rssi", &[0x01], 4),
+        ld("bat", &BATTERY_SYMBOLS, 6),
     ];
 
-    pub const ARDUPILOT: [LocationData; 5] = [
+    pub const ARDUPILOT: [LocationData; 9] = [
         ld("gpslat", &[0xA6], 10),
         ld("gpslon", &[0xA7], 11),
         ldo("alt", &[0xB1, 0xB3], -4, 5),
+        ldo("teralt", &[0xEF], -5, 6),
+        ld("home", &[0xBF], 7),
+        ld("dist", &[0x22], 6),
+        ld("sats", &[0x1E], 4),
         ldo("short+code", &[0x2B], -4, 8),
         ldo("long+code", &[0x2B], -8, 12),
     ];
@@ -87,7 +106,7 @@ impl FontVariant {
         match self {
             FontVariant::Generic => &[],
             FontVariant::Ardupilot => &location_data::ARDUPILOT,
-            FontVariant::Betaflight => &[],
+            FontVariant::Betaflight => &location_data::BETAFLIGHT,
             FontVariant::INAV => &location_data::INAV,
             FontVariant::KISSUltra => &[],
             FontVariant::Unknown => &[],
@@ -0,0 +1,155 @@
+
+//! Values of the numeric OSD items over time
+
+use std::io::{Error as IOError, Write};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters};
+use thiserror::Error;
+
+use crate::{osd, video::FrameIndex as VideoFrameIndex};
+
+use super::{FontVariant, file::Frame, tile_indices::UnknownOSDItem};
+
+
+/// located OSD items which do not display a number
+const NON_NUMERIC_ITEMS: &[&str] = &["short+code", "long+code", "flytime"];
+
+#[derive(Debug, Error, From)]
+pub enum ExportCSVError {
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+    #[error("failed writing CSV: {0}")]
+    IOError(IOError),
+}
+
+/// names of the located OSD items displaying a number for the font variant
+pub fn numeric_item_names(font_variant: FontVariant) -> Vec<&'static str> {
+    font_variant.osd_items_location_data().iter()
+        .map(|location_data| location_data.name())
+        .filter(|name| ! NON_NUMERIC_ITEMS.contains(name))
+        .collect()
+}
+
+/// Writes the values of the items displayed in each OSD frame as CSV
+///
+/// The columns are the video frame index, the time in seconds then one column per item which is empty when the item
+/// is not displayed in the frame.
+pub fn write_csv<'a, W: Write>(frames: impl IntoIterator<Item = &'a Frame>, font_variant: FontVariant, item_names: &[&str],
+        output: &mut W) -> Result<(), ExportCSVError> {
+    writeln!(output, "frame,time,{}", item_names.join(","))?;
+    for frame in frames {
+        write!(output, "{},{:.3}", frame.index(), frame.index() as f64 / osd::FRAME_RATE as f64)?;
+        for item_name in item_names {
+            match frame.osd_item_value(font_variant, item_name)? {
+                Some(value) => write!(output, ",{value}")?,
+                None => write!(output, ",")?,
+            }
+        }
+        writeln!(output)?;
+    }
+    Ok(())
+}
+
+/// values of an OSD item read from the OSD frames, a value is only recorded when it changes
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct ItemSeries {
+    #[getset(get_copy = "pub")]
+    item_name: &'static str,
+    /// video frame index and value
+    #[getset(get = "pub")]
+    points: Vec<(VideoFrameIndex, f64)>,
+}
+
+impl ItemSeries {
+
+    /// series of values coming from another source than the OSD frames, e.g. the flight telemetry
+    pub fn new(item_name: &'static str, points: Vec<(VideoFrameIndex, f64)>) -> Self {
+        Self { item_name, points }
+    }
+
+    /// extracts the values of the item from the frames, the frames in which the item is not displayed are skipped
+    pub fn extract<'a>(frames: impl IntoIterator<Item = &'a Frame>, font_variant: FontVariant, item_name: &'static str)
+            -> Result<Self, UnknownOSDItem> {
+        let mut points: Vec<(VideoFrameIndex, f64)> = vec![];
+        for frame in frames {
+            if let Some(value) = frame.osd_item_value(font_variant, item_name)? {
+                if ! matches!(points.last(), Some((_, last_value)) if *last_value == value) {
+                    points.push((frame.index(), value));
+                }
+            }
+        }
+        Ok(Self { item_name, points })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// lowest and highest values
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.points.iter().map(|(_, value)| *value).fold(None, |range, value| match range {
+            None => Some((value, value)),
+            Some((min, max)) => Some((min.min(value), max.max(value))),
+        })
+    }
+
+    /// value displayed at the video frame
+    pub fn value_at(&self, frame_index: VideoFrameIndex) -> Option<f64> {
+        let count = self.points.partition_point(|(index, _)| *index <= frame_index);
+        count.checked_sub(1).map(|last| self.points[last].1)
+    }
+
+    /// points between the two video frames included, preceded by the last point before `first_frame_index` if any
+    pub fn points_between(&self, first_frame_index: VideoFrameIndex, last_frame_index: VideoFrameIndex) -> &[(VideoFrameIndex, f64)] {
+        let start = self.points.partition_point(|(index, _)| *index < first_frame_index).saturating_sub(1);
+        let end = self.points.partition_point(|(index, _)| *index <= last_frame_index);
+        &self.points[start..end.max(start)]
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::osd::{Dimensions, tile_indices::{TileIndex, TileIndices}};
+
+    /// frame of a single line grid displaying the Betaflight altitude item
+    fn altitude_frame(index: VideoFrameIndex, altitude: &str) -> Frame {
+        let mut tiles = vec![0x7F];
+        tiles.extend(altitude.bytes().map(TileIndex::from));
+        tiles.resize(8, 0);
+        Frame::new(index, TileIndices::with_grid_dimensions(tiles, Dimensions::new(8, 1)))
+    }
+
+    fn series() -> ItemSeries {
+        ItemSeries::new("alt", vec![(0, 10.0), (60, 25.5), (120, 5.0)])
+    }
+
+    #[test]
+    fn values_looked_up_by_video_frame() {
+        let series = series();
+        assert_eq!(series.range(), Some((5.0, 25.5)));
+        assert_eq!(series.value_at(59), Some(10.0));
+        assert_eq!(series.value_at(60), Some(25.5));
+        assert_eq!(ItemSeries::new("alt", vec![(10, 1.0)]).value_at(9), None);
+        assert_eq!(series.points_between(30, 60), &[(0, 10.0), (60, 25.5)]);
+        assert_eq!(series.points_between(200, 300), &[(120, 5.0)]);
+        assert!(ItemSeries::new("alt", vec![]).range().is_none());
+    }
+
+    #[test]
+    fn values_extracted_and_written_as_csv() {
+        let frames = [altitude_frame(0, "12.5"), altitude_frame(30, "12.5"), altitude_frame(60, "-3")];
+        let series = ItemSeries::extract(&frames, FontVariant::Betaflight, "alt").unwrap();
+        assert_eq!(series.points(), &[(0, 12.5), (60, -3.0)]);
+
+        let mut csv = vec![];
+        write_csv(&frames[..2], FontVariant::Betaflight, &["alt", "sats"], &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "frame,time,alt,sats\n0,0.000,12.5,\n30,0.500,12.5,\n");
+        assert!(numeric_item_names(FontVariant::Betaflight).contains(&"alt"));
+        assert!(! numeric_item_names(FontVariant::Betaflight).contains(&"flytime"));
+    }
+
+}
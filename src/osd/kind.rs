@@ -12,6 +12,7 @@ pub enum Kind {
     DJI_SD,
     DJI_FakeHD,
     DJI_HD,
+    DJI_HDCanvas,
     WSA,
 }
 
@@ -23,6 +24,7 @@ impl Kind {
             DJI_SD => dji::dimensions::SD,
             DJI_FakeHD => dji::dimensions::FAKE_HD,
             DJI_HD => dji::dimensions::HD,
+            DJI_HDCanvas => dji::dimensions::HD_CANVAS,
             WSA => wsa::DIMENSIONS,
         }
     }
@@ -33,6 +35,7 @@ impl Kind {
             DJI_SD => tile::Kind::SD,
             DJI_FakeHD => tile::Kind::HD,
             DJI_HD => tile::Kind::HD,
+            DJI_HDCanvas => tile::Kind::HD,
             WSA => tile::Kind::SD,
         }
     }
@@ -51,6 +54,7 @@ impl TryFrom<&Dimensions> for Kind {
             dji::dimensions::SD => Ok(Self::DJI_SD),
             dji::dimensions::FAKE_HD => Ok(Self::DJI_FakeHD),
             dji::dimensions::HD => Ok(Self::DJI_HD),
+            dji::dimensions::HD_CANVAS => Ok(Self::DJI_HDCanvas),
             _ => Err(InvalidDimensionsError(*dimensions_tiles))
         }
     }
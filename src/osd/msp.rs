@@ -0,0 +1,197 @@
+
+//! MSP DisplayPort OSD feed sent by the flight controllers to the HD video systems
+
+use getset::{CopyGetters, Getters};
+
+use super::{Dimensions, FontVariant, tile_indices::{TileIndex, TileIndices}};
+
+pub mod recorder;
+
+
+pub const MSP_DISPLAYPORT: u8 = 182;
+
+/// speed of the serial port configured for MSP DisplayPort on the flight controller
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+/// the font page is stored in the low bits of the attribute byte of the write string command
+const FONT_PAGE_ATTRIBUTE_MASK: u8 = 0x03;
+
+/// firmware of the flight controller sending the OSD, determines the font used to draw it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlightControllerFirmware {
+    Betaflight,
+    INAV,
+    Ardupilot,
+    KISSUltra,
+}
+
+impl FlightControllerFirmware {
+
+    pub fn font_variant(&self) -> FontVariant {
+        use FlightControllerFirmware::*;
+        match self {
+            Betaflight => FontVariant::Betaflight,
+            INAV => FontVariant::INAV,
+            Ardupilot => FontVariant::Ardupilot,
+            KISSUltra => FontVariant::KISSUltra,
+        }
+    }
+
+    /// highest tile index used by the OSD, INAV and Ardupilot use the second font page
+    pub fn highest_tile_index(&self) -> TileIndex {
+        use FlightControllerFirmware::*;
+        match self {
+            INAV | Ardupilot => 511,
+            Betaflight | KISSUltra => 255,
+        }
+    }
+
+}
+
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct Message {
+    #[getset(get_copy = "pub")]
+    command: u8,
+    #[getset(get = "pub")]
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ParserState {
+    Idle,
+    Header,
+    Direction,
+    Size,
+    Command { size: u8 },
+    Payload { size: u8, command: u8 },
+    Checksum { command: u8 },
+}
+
+/// MSP v1 frames parser, the bytes which are not part of a valid frame are skipped
+pub struct Parser {
+    state: ParserState,
+    payload: Vec<u8>,
+    checksum: u8,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self { state: ParserState::Idle, payload: vec![], checksum: 0 }
+    }
+}
+
+impl Parser {
+
+    /// pushes the next byte, returns the message when the byte completes a valid frame
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        use ParserState::*;
+        self.state = match (self.state, byte) {
+            (Idle, b'$') => Header,
+            (Header, b'M') => Direction,
+            (Direction, b'>' | b'<') => Size,
+            (Size, size) => {
+                self.checksum = size;
+                self.payload.clear();
+                Command { size }
+            },
+            (Command { size }, command) => {
+                self.checksum ^= command;
+                if size == 0 { Checksum { command } } else { Payload { size, command } }
+            },
+            (Payload { size, command }, byte) => {
+                self.checksum ^= byte;
+                self.payload.push(byte);
+                if self.payload.len() == size as usize { Checksum { command } } else { Payload { size, command } }
+            },
+            (Checksum { command }, checksum) => {
+                self.state = Idle;
+                if checksum != self.checksum {
+                    log::debug!("MSP frame with invalid checksum for command {command}");
+                    return None;
+                }
+                return Some(Message { command, payload: std::mem::take(&mut self.payload) });
+            },
+            _ => Idle,
+        };
+        None
+    }
+
+}
+
+/// OSD screen drawn with the MSP DisplayPort commands
+pub struct DisplayPortScreen {
+    grid_dimensions: Dimensions,
+    max_tile_index: TileIndex,
+    /// tiles being drawn, displayed when the draw command is received
+    pending: Vec<TileIndex>,
+}
+
+impl DisplayPortScreen {
+
+    /// tiles with an index greater than `max_tile_index` are not drawn since they are not in the loaded font
+    pub fn new(grid_dimensions: Dimensions, max_tile_index: TileIndex) -> Self {
+        Self { grid_dimensions, max_tile_index, pending: vec![0; grid_dimensions.width as usize * grid_dimensions.height as usize] }
+    }
+
+    fn tile_indices(&self) -> TileIndices {
+        TileIndices::with_grid_dimensions(self.pending.clone(), self.grid_dimensions)
+    }
+
+    fn write_string(&mut self, row: u8, column: u8, attribute: u8, string: &[u8]) {
+        let font_page = (attribute & FONT_PAGE_ATTRIBUTE_MASK) as TileIndex;
+        for (x, character) in (column as u32..).zip(string) {
+            let tile_index = font_page * 256 + *character as TileIndex;
+            if x >= self.grid_dimensions.width || row as u32 >= self.grid_dimensions.height || tile_index > self.max_tile_index { continue }
+            // tile indices are stored column by column
+            self.pending[x as usize * self.grid_dimensions.height as usize + row as usize] = tile_index;
+        }
+    }
+
+    /// applies the message if it is a DisplayPort command, returns the tiles to display when the screen is drawn or released
+    pub fn apply(&mut self, message: &Message) -> Option<TileIndices> {
+        if message.command() != MSP_DISPLAYPORT { return None }
+        match message.payload().as_slice() {
+            // release: the flight controller stops drawing the OSD
+            [1, ..] => {
+                self.pending.fill(0);
+                Some(self.tile_indices())
+            },
+            [2, ..] => {
+                self.pending.fill(0);
+                None
+            },
+            [3, row, column, attribute, string @ ..] => {
+                self.write_string(*row, *column, *attribute, string);
+                None
+            },
+            [4, ..] => Some(self.tile_indices()),
+            // heartbeat and options
+            _ => None,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(command: u8, payload: &[u8]) -> Vec<u8> {
+        let checksum = payload.iter().fold(payload.len() as u8 ^ command, |checksum, byte| checksum ^ byte);
+        [&[b'$', b'M', b'>', payload.len() as u8, command][..], payload, &[checksum]].concat()
+    }
+
+    #[test]
+    fn parse_and_draw_displayport_string() {
+        let mut parser = Parser::default();
+        let mut screen = DisplayPortScreen::new(Dimensions::new(4, 2), 511);
+        let bytes = [frame(MSP_DISPLAYPORT, &[3, 1, 2, 1, b'A', b'B', b'C']), frame(MSP_DISPLAYPORT, &[4])].concat();
+        let drawn = bytes.into_iter()
+            .filter_map(|byte| parser.push(byte))
+            .filter_map(|message| screen.apply(&message))
+            .last().unwrap();
+        // the last character does not fit in the grid
+        assert_eq!(drawn.as_slice(), &[0, 0, 0, 0, 0, 0x141, 0, 0x142]);
+    }
+
+}
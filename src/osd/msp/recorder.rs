@@ -0,0 +1,203 @@
+
+//! Recording of the MSP DisplayPort OSD feed into DJI OSD files
+//!
+//! The feed is read from a serial port connected to a flight controller UART configured for MSP DisplayPort or from
+//! a TCP connection, e.g. to the MSP port of a Betaflight SITL. An OSD frame is recorded each time the flight
+//! controller draws the screen, its index being the index of the 60 FPS video frame at that time since the start of
+//! the recording. This allows the HDZero and analog users to get OSD files compatible with the rest of the tool.
+//!
+//! The frames are flushed to the file as they are recorded so that the file is complete when the recording is
+//! stopped with Ctrl-C.
+
+use std::{
+    fmt::Display,
+    io::{Error as IOError, ErrorKind, Read},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+
+use crate::{osd::{self, dji, file::Frame}, video::FrameIndex as VideoFrameIndex};
+
+use super::{DisplayPortScreen, FlightControllerFirmware, Parser};
+
+
+/// the OSD is recorded on the 60x22 grid which contains the grids of all the HD OSD layouts
+const OSD_KIND: osd::Kind = osd::Kind::DJI_FakeHD;
+
+/// timeout of the reads after which the recording duration is checked
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+const TCP_SOURCE_PREFIX: &str = "tcp://";
+
+/// where the MSP DisplayPort feed is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    SerialPort(PathBuf),
+    /// `host:port` address
+    Tcp(String),
+}
+
+impl FromStr for Source {
+    type Err = std::convert::Infallible;
+
+    /// `tcp://<host>:<port>` for a TCP connection, a serial port path otherwise
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Ok(match source.strip_prefix(TCP_SOURCE_PREFIX) {
+            Some(address) => Self::Tcp(address.to_owned()),
+            None => Self::SerialPort(PathBuf::from(source)),
+        })
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SerialPort(path) => write!(f, "{}", path.to_string_lossy()),
+            Self::Tcp(address) => write!(f, "{TCP_SOURCE_PREFIX}{address}"),
+        }
+    }
+}
+
+#[derive(Debug, Error, From)]
+pub enum RecordError {
+    #[error("failed opening serial port {path}: {error}")]
+    FailedOpeningSerialPort {
+        path: PathBuf,
+        error: serialport::Error,
+    },
+    #[error("failed connecting to {address}: {error}")]
+    FailedConnecting {
+        address: String,
+        error: IOError,
+    },
+    #[error("failed reading OSD feed: {0}")]
+    #[from(ignore)]
+    ReadError(IOError),
+    #[error(transparent)]
+    CreateError(dji::file::CreateError),
+    #[error("failed writing OSD file: {0}")]
+    WriteError(IOError),
+}
+
+/// Records the MSP DisplayPort OSD feed into a DJI OSD file
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct Recorder {
+    source: Source,
+    /// baud rate of the serial port source
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    baud_rate: u32,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    firmware: FlightControllerFirmware,
+    /// the recording stops after this duration if set, else when the feed ends or when interrupted
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    duration: Option<Duration>,
+}
+
+impl Recorder {
+
+    pub fn new(source: Source) -> Self {
+        Self { source, baud_rate: super::DEFAULT_BAUD_RATE, firmware: FlightControllerFirmware::Betaflight, duration: None }
+    }
+
+    fn open_source(&self) -> Result<Box<dyn Read + Send>, RecordError> {
+        match &self.source {
+            Source::SerialPort(path) => {
+                let serial_port = serialport::new(path.to_string_lossy(), self.baud_rate)
+                    .timeout(READ_TIMEOUT)
+                    .open()
+                    .map_err(|error| RecordError::FailedOpeningSerialPort { path: path.clone(), error })?;
+                Ok(Box::new(serial_port))
+            },
+            Source::Tcp(address) => {
+                let failed_connecting = |error| RecordError::FailedConnecting { address: address.clone(), error };
+                let stream = TcpStream::connect(address).map_err(failed_connecting)?;
+                stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(failed_connecting)?;
+                Ok(Box::new(stream))
+            },
+        }
+    }
+
+    /// records the OSD frames into the OSD file until the duration is reached or the feed ends, returns the number of
+    /// frames recorded
+    ///
+    /// The feed is read with blocking reads so the recording runs on the blocking threads.
+    pub async fn record<P: AsRef<Path>>(&self, output_osd_file: P, overwrite: bool) -> Result<usize, RecordError> {
+        let recorder = self.clone();
+        let output_osd_file = output_osd_file.as_ref().to_path_buf();
+        tokio::task::spawn_blocking(move || recorder.record_blocking(&output_osd_file, overwrite)).await.unwrap()
+    }
+
+    fn record_blocking(&self, output_osd_file: &Path, overwrite: bool) -> Result<usize, RecordError> {
+        let mut source = self.open_source()?;
+        let header = dji::file::FileHeader::new(OSD_KIND.dimensions_tiles(), OSD_KIND.tile_kind().dimensions(), self.firmware.font_variant());
+        let mut writer = dji::file::Writer::create(output_osd_file, &header, overwrite)?;
+        writer.keep_when_interrupted();
+        let mut parser = Parser::default();
+        let mut screen = DisplayPortScreen::new(OSD_KIND.dimensions_tiles(), self.firmware.highest_tile_index());
+        let mut buffer = [0; 256];
+        // the last frame drawn, only written once the next video frame starts so that it is the last one drawn
+        // during its video frame
+        let mut pending_frame: Option<Frame> = None;
+        let mut frame_count = 0;
+
+        log::info!("recording OSD from {}, press Ctrl-C to stop", self.source);
+        let start = Instant::now();
+        while self.duration.map_or(true, |duration| start.elapsed() < duration) {
+            let read_count = match source.read(&mut buffer) {
+                Ok(0) => {
+                    log::info!("the OSD feed ended");
+                    break;
+                },
+                Ok(read_count) => read_count,
+                Err(error) if matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::Interrupted) => continue,
+                Err(error) => return Err(RecordError::ReadError(error)),
+            };
+            for byte in &buffer[..read_count] {
+                let tile_indices = match parser.push(*byte).and_then(|message| screen.apply(&message)) {
+                    Some(tile_indices) => tile_indices,
+                    None => continue,
+                };
+                let frame_index = (start.elapsed().as_secs_f64() * osd::FRAME_RATE as f64).round() as VideoFrameIndex;
+                if let Some(frame) = pending_frame.take() {
+                    if frame.index() != frame_index {
+                        writer.write_frame(&frame)?;
+                        writer.flush()?;
+                        frame_count += 1;
+                    }
+                }
+                pending_frame = Some(Frame::new(frame_index, tile_indices));
+            }
+        }
+
+        if let Some(frame) = pending_frame {
+            writer.write_frame(&frame)?;
+            frame_count += 1;
+        }
+        writer.finish()?;
+        Ok(frame_count)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_source() {
+        assert_eq!("tcp://localhost:5761".parse::<Source>().unwrap(), Source::Tcp("localhost:5761".to_owned()));
+        assert_eq!("/dev/ttyUSB0".parse::<Source>().unwrap(), Source::SerialPort(PathBuf::from("/dev/ttyUSB0")));
+    }
+
+}
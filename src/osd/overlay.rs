@@ -1,9 +1,14 @@
 
 use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ops::Range,
     path::{
         Path,
         PathBuf
     },
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
     io::{
         Error as IOError,
         Write, self
@@ -11,7 +16,7 @@ use std::{
 };
 
 use derive_more::{From, Deref};
-use getset::{CopyGetters, Getters};
+use getset::{CopyGetters, Getters, Setters};
 use path_absolutize::Absolutize;
 use thiserror::Error;
 use image::{ImageBuffer, Rgba, GenericImage, ImageResult};
@@ -21,6 +26,17 @@ use rayon::prelude::{ParallelIterator, IndexedParallelIterator};
 pub mod scaling;
 pub mod margins;
 pub mod osd_kind_ext;
+pub mod style;
+pub mod pgs;
+pub mod graphs;
+pub mod sticks;
+pub mod interpolation;
+pub mod render_cache;
+pub mod convert;
+pub mod script;
+pub mod image_sequence;
+pub mod composition;
+pub mod layers;
 
 use hd_fpv_osd_font_tool::{
     dimensions::Dimensions as GenericDimensions,
@@ -40,23 +56,33 @@ use crate::{
     image::{
         WriteImageFile,
         WriteError as ImageWriteError,
+        PngCompression,
     },
+    telemetry::{self, AlignedTelemetry, TelemetryError, TelemetrySettings, TelemetrySync},
     video::{
         FrameIndex as VideoFrameIndex,
-        resolution::Resolution as VideoResolution, timestamp::{Timestamp, StartEndOverlayFrameIndex},
-    }, osd::file::sorted_frames::EndOfFramesAction,
+        resolution::Resolution as VideoResolution,
+        container::{Container, ContainerError},
+        cut::{self, TempFiles},
+        estimate::Estimate,
+        layers::{LayerConfig, LayerKind},
+    }, osd::{self, file::sorted_frames::EndOfFramesAction},
 };
 
 use super::{
     file::{
         Frame as OSDFileFrame,
         SortedUniqFrames as OSDFileSortedFrames,
+        Reader as OSDFileReader,
+        GenericReader,
+        frames_stream::VideoFramesStreamIter,
     },
+    Coordinate,
     Region,
-    tile_resize::ResizeTiles, font_variant::FontVariant, file::{ReadError, sorted_frames::{GetFramesExt, VideoFramesIter}}, tile_indices::UnknownOSDItem, FontDir,
+    tile_resize::ResizeTiles, font_variant::FontVariant, file::{ReadError, sorted_frames::{GetFrames, GetFramesExt, VideoFramesIter}}, tile_indices::{TileIndex, TileIndices, UnknownOSDItem}, FontDir,
 };
 
-use self::scaling::Scaling;
+use self::{graphs::TelemetryGraphs, interpolation::InterpolatedFramesIter, layers::OverlayLayers, render_cache::RenderCache, scaling::Scaling, script::ScriptLayer, sticks::StickOverlay};
 
 pub type Dimensions = GenericDimensions<u32>;
 #[derive(Deref, Clone, CopyGetters)]
@@ -75,32 +101,101 @@ pub struct VideoResolutionTooSmallError {
     pub video_resolution: VideoResolution
 }
 
+/// method used to copy the tile images onto the overlay frames, this is the hot loop of the overlay generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum TileBlitMethod {
+    /// pixel by pixel copy with the bounds checked for each pixel
+    Pixels,
+    /// row by row copy of slices of the raw pixel data, see [`Frame::copy_from`]
+    Rows,
+}
+
+impl Default for TileBlitMethod {
+    fn default() -> Self {
+        Self::Rows
+    }
+}
+
 impl Frame {
     pub fn new(dimensions: Dimensions) -> Self {
         Self { dimensions, image: ImageBuffer::new(dimensions.width, dimensions.height) }
     }
 
+    pub fn image_mut(&mut self) -> &mut ImageBuffer<Rgba<u8>, Vec<u8>> {
+        &mut self.image
+    }
+
+    /// copies the image at the position row by row as slices of the raw pixel data, much faster than the pixel by pixel
+    /// copy in this hot loop of the overlay generation
     pub fn copy_from(&mut self, image: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32) -> ImageResult<()> {
-        self.image.copy_from(image, x, y)
+        let (width, height) = image.dimensions();
+        let fits = matches!(x.checked_add(width), Some(right) if right <= self.image.width())
+            && matches!(y.checked_add(height), Some(bottom) if bottom <= self.image.height());
+        // the generic copy reports the out of bounds error
+        if ! fits { return self.image.copy_from(image, x, y) }
+        let frame_width = self.image.width() as usize;
+        let row_size = width as usize * 4;
+        let frame_raw: &mut [u8] = &mut self.image;
+        for (row, image_row) in image.as_raw().chunks_exact(row_size).enumerate() {
+            let start = ((y as usize + row) * frame_width + x as usize) * 4;
+            frame_raw[start..start + row_size].copy_from_slice(image_row);
+        }
+        Ok(())
+    }
+
+    /// copies the image at the position with the method
+    pub fn blit(&mut self, image: &ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, method: TileBlitMethod) -> ImageResult<()> {
+        match method {
+            TileBlitMethod::Pixels => self.image.copy_from(image, x, y),
+            TileBlitMethod::Rows => self.copy_from(image, x, y),
+        }
     }
 }
 
+/// how the tiles of the OSD file frames are drawn onto the overlay frames
+#[derive(Clone, Copy)]
+struct TilesDrawing<'a> {
+    frame_dimensions: Dimensions,
+    font_variant: FontVariant,
+    tile_images: &'a [tile::Image],
+    hidden_regions: &'a [Region],
+    hidden_items: &'a [&'a str],
+    /// columns of the OSD grid the OSD items are moved into
+    visible_columns: Option<&'a Range<Coordinate>>,
+    /// styles applied to the composed frames
+    styles: &'a [style::Style],
+    blit_method: TileBlitMethod,
+}
+
 
 impl super::file::Frame {
 
-    fn draw_overlay_frame(&self, dimensions: Dimensions, font_variant: FontVariant, tile_images: &[tile::Image], hidden_regions: &[Region], hidden_items: &[impl AsRef<str>]) -> Result<Frame, UnknownOSDItem> {
-        let (tiles_width, tiles_height) = tile_images.first().unwrap().dimensions();
-        let mut frame = Frame::new(dimensions);
+    fn draw_overlay_frame(&self, drawing: &TilesDrawing) -> Result<Frame, UnknownOSDItem> {
+        let (tiles_width, tiles_height) = drawing.tile_images.first().unwrap().dimensions();
+        let mut frame = Frame::new(drawing.frame_dimensions);
         let mut tile_indices = self.tile_indices().clone();
-        tile_indices.erase_regions(hidden_regions);
-        tile_indices.erase_osd_items(font_variant, hidden_items)?;
+        tile_indices.erase_regions(drawing.hidden_regions);
+        tile_indices.erase_osd_items(drawing.font_variant, drawing.hidden_items)?;
+        if let Some(visible_columns) = drawing.visible_columns {
+            tile_indices.fit_columns(visible_columns.clone());
+        }
         for (osd_coordinates, tile_index) in tile_indices.enumerate() {
-            frame.copy_from(
-                &tile_images[tile_index as usize],
+            // tiles past the last loaded font page are not drawn
+            let tile_image = match drawing.tile_images.get(tile_index as usize) {
+                Some(tile_image) => tile_image,
+                None => continue,
+            };
+            frame.blit(
+                tile_image,
                 osd_coordinates.x as u32 * tiles_width,
-                osd_coordinates.y as u32 * tiles_height
+                osd_coordinates.y as u32 * tiles_height,
+                drawing.blit_method
             ).unwrap();
         }
+        for style in drawing.styles {
+            style.apply_to_frame(frame.image_mut(), tiles_width, tiles_height);
+        }
         Ok(frame)
     }
 
@@ -128,7 +223,7 @@ pub fn make_overlay_frame_file_path<P: AsRef<Path>>(dir_path: P, frame_index: Vi
 }
 
 
-#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+#[derive(Debug, Clone, Copy)]
 pub enum OverlayVideoCodec {
     Vp8,
     Vp9
@@ -167,6 +262,52 @@ impl OverlayVideoCodec {
     }
 }
 
+/// default speed of the overlay video encoders, good quality for the OSD which has few details while being much
+/// faster than the libvpx default
+pub const DEFAULT_OVERLAY_VIDEO_ENCODER_SPEED: u8 = 4;
+
+/// speed related settings of the VP8/VP9 overlay video encoders
+#[derive(Debug, Clone, Copy, CopyGetters, Setters)]
+#[getset(get_copy = "pub", set = "pub")]
+pub struct OverlayVideoEncoderSpeed {
+    /// number of threads used by the encoder, defaults to the number of CPUs
+    encoder_threads: Option<u32>,
+    /// speed of the encoder (libvpx -cpu-used) from 0 (slowest, best quality) to 8 (fastest)
+    encoder_speed: u8,
+    /// disable the row based multithreading of the VP9 encoder
+    no_row_mt: bool,
+    /// log2 of the number of tile columns used by the VP9 encoder, defaults to the libvpx default
+    tile_columns: Option<u8>,
+}
+
+impl Default for OverlayVideoEncoderSpeed {
+    fn default() -> Self {
+        Self { encoder_threads: None, encoder_speed: DEFAULT_OVERLAY_VIDEO_ENCODER_SPEED, no_row_mt: false, tile_columns: None }
+    }
+}
+
+impl OverlayVideoEncoderSpeed {
+
+    /// encoder arguments for the codec
+    pub fn encoder_args(&self, codec: OverlayVideoCodec) -> Vec<String> {
+        let threads = self.encoder_threads
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|count| count.get() as u32).unwrap_or(1));
+        let mut args = vec![
+            "-threads".to_owned(), threads.to_string(),
+            "-deadline".to_owned(), "good".to_owned(),
+            "-cpu-used".to_owned(), self.encoder_speed.to_string(),
+        ];
+        if let OverlayVideoCodec::Vp9 = codec {
+            args.extend(["-row-mt".to_owned(), if self.no_row_mt { "0" } else { "1" }.to_owned()]);
+            if let Some(tile_columns) = self.tile_columns {
+                args.extend(["-tile-columns".to_owned(), tile_columns.to_string()]);
+            }
+        }
+        args
+    }
+
+}
+
 #[derive(Debug, Error, From)]
 pub enum SaveFramesToDirError {
     #[error(transparent)]
@@ -193,8 +334,14 @@ pub enum GenerateOverlayVideoError {
     FrameReadError(ReadError),
     #[error("target video file exists: {0}")]
     TargetVideoFileExists(PathBuf),
-    #[error("output video file extension needs to be .webm")]
-    OutputFileExtensionNotWebm,
+    #[error("cannot infer the container from the output video file extension, use a .webm or .mkv extension or set the container")]
+    UnknownOutputContainer,
+    #[error("the {0} container cannot store the transparency of the overlay video, use webm or mkv")]
+    ContainerWithoutTransparency(Container),
+    #[error(transparent)]
+    ContainerError(ContainerError),
+    #[error("preview file extension needs to be .gif or .mp4")]
+    PreviewFileExtensionNotGifOrMp4,
     #[error(transparent)]
     FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
     #[error("failed sending OSD frames to ffmpeg process: {0}")]
@@ -213,12 +360,19 @@ impl From<SendFramesToFFMpegError> for GenerateOverlayVideoError {
         match error {
             PipeError(error) => Self::FailedSendingOSDFramesToFFMpeg(error),
             UnknownOSDItem(error) => Self::UnknownOSDItem(error),
+            SendFramesToFFMpegError::ReadError(error) => Self::FrameReadError(error),
             FFMpegExitedWithError(error) => Self::FFMpegExitedWithError(error),
         }
     }
 }
 
-fn best_settings_for_requested_scaling(osd_kind: super::Kind, scaling: &Scaling) -> Result<(Dimensions, tile::Kind, Option<TileDimensions>), DrawFrameOverlayError> {
+/// minimum ratio between the area of the OSD scaled with an integer factor and the area of the biggest possible OSD
+/// for the integer factor to be preferred, integer factors keep the tiles sharp
+const INTEGER_SCALING_MIN_AREA_RATIO: f64 = 0.85;
+
+/// Returns the overlay dimensions, the kind of tiles to use and the tile dimensions if they need to be scaled.
+/// The steps leading to the decision are added to `explanation`.
+fn best_settings_for_requested_scaling(osd_kind: super::Kind, scaling: &Scaling, explanation: &mut Vec<String>) -> Result<(Dimensions, tile::Kind, Option<TileDimensions>), DrawFrameOverlayError> {
     Ok(match *scaling {
 
         Scaling::No { target_resolution } => {
@@ -228,13 +382,20 @@ fn best_settings_for_requested_scaling(osd_kind: super::Kind, scaling: &Scaling)
                 Some(target_resolution) => {
                     let tile_kind = osd_kind.best_kind_of_tiles_to_use_without_scaling(target_resolution.dimensions()).map_err(|error| {
                         let VideoResolutionTooSmallError { osd_kind, video_resolution } = error;
+                        explanation.push(format!("without scaling no tile kind fits in the target resolution {video_resolution}"));
                         DrawFrameOverlayError::VideoResolutionTooSmallError { osd_kind, video_resolution }
                     })?;
-                    (osd_kind.dimensions_pixels_for_tile_kind(tile_kind), tile_kind, None)
+                    let overlay_dimensions = osd_kind.dimensions_pixels_for_tile_kind(tile_kind);
+                    explanation.push(format!("without scaling: {tile_kind} tiles best fill the target resolution {}, overlay resolution: {overlay_dimensions}",
+                        target_resolution.dimensions()));
+                    (overlay_dimensions, tile_kind, None)
                 },
 
                 // no target resolution specified so use the native tile kind for the OSD kind
-                None => (osd_kind.dimensions_pixels(), osd_kind.tile_kind(), None)
+                None => {
+                    explanation.push(format!("no target resolution: using the native {} tiles of the OSD", osd_kind.tile_kind()));
+                    (osd_kind.dimensions_pixels(), osd_kind.tile_kind(), None)
+                },
 
             }
         },
@@ -245,14 +406,38 @@ fn best_settings_for_requested_scaling(osd_kind: super::Kind, scaling: &Scaling)
                 target_resolution.dimensions().height - 2 * min_margins.vertical(),
             );
             let (tile_kind, tile_dimensions, overlay_dimensions) = osd_kind.best_kind_of_tiles_to_use_with_scaling(max_resolution);
-            (overlay_dimensions, tile_kind, Some(tile_dimensions))
+            explanation.push(format!("with scaling: maximum overlay resolution with the minimum margins: {max_resolution}, \
+                best fit: {tile_kind} tiles scaled to {tile_dimensions}, overlay resolution: {overlay_dimensions}"));
+
+            match osd_kind.best_kind_of_tiles_to_use_with_integer_scaling(max_resolution) {
+                Some((int_tile_kind, factor, int_tile_dimensions, int_overlay_dimensions)) => {
+                    let area_ratio = (int_overlay_dimensions.width * int_overlay_dimensions.height) as f64 /
+                        (overlay_dimensions.width * overlay_dimensions.height) as f64;
+                    explanation.push(format!("with integer scaling: {int_tile_kind} tiles scaled x{factor} to {int_tile_dimensions}, \
+                        overlay resolution: {int_overlay_dimensions} ({:.0}% of the best fit area)", area_ratio * 100.0));
+                    if area_ratio >= INTEGER_SCALING_MIN_AREA_RATIO {
+                        explanation.push(format!("using integer scaling since it covers at least {:.0}% of the best fit area",
+                            INTEGER_SCALING_MIN_AREA_RATIO * 100.0));
+                        (int_overlay_dimensions, int_tile_kind, Some(int_tile_dimensions))
+                    } else {
+                        (overlay_dimensions, tile_kind, Some(tile_dimensions))
+                    }
+                },
+                None => {
+                    explanation.push("integer scaling: no tile kind can be scaled by at least 2".to_owned());
+                    (overlay_dimensions, tile_kind, Some(tile_dimensions))
+                },
+            }
         },
 
         Scaling::Auto { min_margins, min_resolution, target_resolution } => {
+            explanation.push(format!("auto scaling: target resolution {}, minimum margins {}:{}, minimum overlay resolution {min_resolution}",
+                target_resolution.dimensions(), min_margins.horizontal(), min_margins.vertical()));
+
             let (overlay_resolution, tile_kind, tile_scaling) =
 
                 // check results without scaling
-                match best_settings_for_requested_scaling(osd_kind, &Scaling::No { target_resolution: Some(target_resolution) }) {
+                match best_settings_for_requested_scaling(osd_kind, &Scaling::No { target_resolution: Some(target_resolution) }, explanation) {
 
                     // no scaling is possible
                     Ok(values) => {
@@ -263,16 +448,20 @@ fn best_settings_for_requested_scaling(osd_kind: super::Kind, scaling: &Scaling)
 
                         // check whether the result would match the user specified conditions
                         if min_margins_condition_met && min_dimensions_condition_met {
+                            explanation.push(format!("not scaling: margins {margin_width}:{margin_height} and overlay resolution meet the requirements"));
                             values
                         } else {
                             // else return parameters with scaling enabled
-                            best_settings_for_requested_scaling(osd_kind, &Scaling::Yes { target_resolution, min_margins })?
+                            explanation.push(format!("scaling: without scaling the margins would be {margin_width}:{margin_height}{}{}",
+                                if min_margins_condition_met { "" } else { ", smaller than the minimum margins" },
+                                if min_dimensions_condition_met { "" } else { ", the overlay would be smaller than the minimum resolution" }));
+                            best_settings_for_requested_scaling(osd_kind, &Scaling::Yes { target_resolution, min_margins }, explanation)?
                         }
 
                     },
 
                     // no scaling does not work, return parameters with scaling enabled
-                    Err(_) => best_settings_for_requested_scaling(osd_kind, &Scaling::Yes { target_resolution, min_margins })?,
+                    Err(_) => best_settings_for_requested_scaling(osd_kind, &Scaling::Yes { target_resolution, min_margins }, explanation)?,
                 };
 
             let tile_scaling_yes_no = match tile_scaling { Some(_) => "yes", None => "no" };
@@ -283,37 +472,140 @@ fn best_settings_for_requested_scaling(osd_kind: super::Kind, scaling: &Scaling)
     })
 }
 
-#[derive(CopyGetters)]
+/// frames of the OSD file drawn by the [`Generator`]
+#[derive(From)]
+pub enum OSDFileFrames {
+    /// all the frames loaded in memory
+    Loaded(OSDFileSortedFrames),
+    /// frames read lazily from the file each time they are iterated so that the memory used does not depend
+    /// on the length of the recording, see [`super::file::FramesStream`]
+    Streamed(OSDFileReader),
+}
+
+enum FramesSource {
+    Loaded(OSDFileSortedFrames),
+    Streamed { reader: OSDFileReader, last_frame_index: VideoFrameIndex },
+}
+
+/// position of [`Generator::frame_at`] in the streamed frames so that the file is only read again from the start
+/// when an earlier frame is requested
+struct StreamCursor {
+    frames: std::iter::Peekable<super::file::FramesStream>,
+    /// OSD frame index requested last
+    osd_frame_index: i32,
+    /// last frame read with an index not greater than the requested one
+    displayed_frame: Option<OSDFileFrame>,
+}
+
+impl FramesSource {
+
+    /// all the frames, read from the file if they are streamed
+    fn load(&self, font_variant: FontVariant) -> Result<Cow<OSDFileSortedFrames>, ReadError> {
+        Ok(match self {
+            Self::Loaded(frames) => Cow::Borrowed(frames),
+            Self::Streamed { reader, .. } => {
+                let frames = reader.frames_stream()?.collect::<Result<Vec<_>, _>>()?;
+                Cow::Owned(OSDFileSortedFrames::new(reader.kind(), font_variant, frames))
+            },
+        })
+    }
+
+}
+
+#[derive(CopyGetters, Getters)]
 pub struct Generator<'a> {
-    osd_file_frames: OSDFileSortedFrames,
+    frames_source: FramesSource,
     font_variant: FontVariant,
     tile_images: Vec<tile::Image>,
     hidden_regions: &'a [Region],
     hidden_items: Vec<&'a str>,
+    telemetry: Option<AlignedTelemetry>,
+    /// telemetry graphs, sticks and script widgets drawn with the OSD tiles
+    layers: OverlayLayers,
+    /// columns of the OSD grid visible once the video is cropped, the OSD items outside are moved into them
+    visible_columns: Option<Range<Coordinate>>,
+    /// styles with effects applied to the composed frames, in the order they were applied
+    frame_styles: Vec<style::Style>,
+    tile_blit_method: TileBlitMethod,
+    stream_cursor: Mutex<Option<StreamCursor>>,
+    /// frames already drawn by [`Self::draw_frame`], shared by the threads drawing the frames in parallel
+    render_cache: Mutex<RenderCache>,
 
     #[getset(get_copy = "pub")]
     frame_dimensions: Dimensions,
+
+    /// steps which led to the choice of tile kind and scaling
+    #[getset(get = "pub")]
+    scaling_explanation: Vec<String>,
 }
 
 impl<'a> Generator<'a> {
 
-    pub fn new(osd_file_frames: OSDFileSortedFrames, font_variant: FontVariant, font_dir: &FontDir, font_ident: &Option<Option<&str>>,
+    pub fn new(osd_file_frames: impl Into<OSDFileFrames>, font_variant: FontVariant, font_dir: &FontDir, font_ident: &Option<Option<&str>>,
                     scaling: Scaling, hidden_regions: &'a [Region], hidden_items: &'a [String]) -> Result<Self, DrawFrameOverlayError> {
 
-        if osd_file_frames.is_empty() { return Err(DrawFrameOverlayError::OSDFileIsEmpty) }
+        let (frames_source, osd_kind, highest_used_tile_index) = match osd_file_frames.into() {
+            OSDFileFrames::Loaded(frames) => {
+                let highest_used_tile_index = frames.highest_used_tile_index().ok_or(DrawFrameOverlayError::OSDFileIsEmpty)?;
+                let osd_kind = frames.kind();
+                (FramesSource::Loaded(frames), osd_kind, highest_used_tile_index)
+            },
+            OSDFileFrames::Streamed(mut reader) => {
+                // reading the whole file once is needed to know which tiles to load and how many frames there are
+                let summary = reader.frames_stream()?.summary()?.ok_or(DrawFrameOverlayError::OSDFileIsEmpty)?;
+                let osd_kind = reader.kind();
+                if summary.out_of_order_frame_count() > 0 {
+                    // the stream would skip these frames, the sorted frames include them
+                    log::warn!("{} OSD frames are not stored in order, loading all the frames to sort them", summary.out_of_order_frame_count());
+                    (FramesSource::Loaded(reader.frames()?), osd_kind, summary.highest_used_tile_index())
+                } else {
+                    (FramesSource::Streamed { reader, last_frame_index: summary.last_frame_index() }, osd_kind, summary.highest_used_tile_index())
+                }
+            },
+        };
+
+        Self::with_frames_source(frames_source, osd_kind, highest_used_tile_index, font_variant, font_dir, font_ident, scaling,
+            hidden_regions, hidden_items)
+    }
+
+    /// Generator without OSD file frames drawing the tile indices received live with [`Self::draw_tile_indices`],
+    /// the tiles of the font up to `highest_tile_index` are loaded
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_live(osd_kind: super::Kind, highest_tile_index: TileIndex, font_variant: FontVariant, font_dir: &FontDir,
+                    font_ident: &Option<Option<&str>>, scaling: Scaling, hidden_regions: &'a [Region], hidden_items: &'a [String])
+                    -> Result<Self, DrawFrameOverlayError> {
+        let frames_source = FramesSource::Loaded(OSDFileSortedFrames::new(osd_kind, font_variant, vec![]));
+        Self::with_frames_source(frames_source, osd_kind, highest_tile_index, font_variant, font_dir, font_ident, scaling,
+            hidden_regions, hidden_items)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn with_frames_source(frames_source: FramesSource, osd_kind: super::Kind, highest_used_tile_index: TileIndex, font_variant: FontVariant,
+                    font_dir: &FontDir, font_ident: &Option<Option<&str>>, scaling: Scaling, hidden_regions: &'a [Region],
+                    hidden_items: &'a [String]) -> Result<Self, DrawFrameOverlayError> {
 
+        let mut scaling_explanation = vec![];
         let (overlay_resolution, tile_kind, tile_scaling) =
-            best_settings_for_requested_scaling(osd_file_frames.kind(), &scaling)?;
+            best_settings_for_requested_scaling(osd_kind, &scaling, &mut scaling_explanation)?;
+        for line in &scaling_explanation {
+            log::debug!("scaling decision: {line}");
+        }
+
+        let font_page_count = super::font_dir::page_count(highest_used_tile_index);
+        if highest_used_tile_index as usize >= font_page_count * super::font_dir::PAGE_TILE_COUNT {
+            log::warn!("tiles from index {} are not in the {font_page_count} font pages and will not be drawn",
+                font_page_count * super::font_dir::PAGE_TILE_COUNT);
+        }
+        log::debug!("loading {font_page_count} font page(s) for tile indices up to {highest_used_tile_index}");
 
-        let highest_used_tile_index = osd_file_frames.highest_used_tile_index().unwrap();
-        let tiles = match font_ident {
+        let tile_images = match font_ident {
             Some(font_ident) => font_dir.load_with_fallback(tile_kind, font_ident, highest_used_tile_index)?,
-            None => font_dir.load_variant_with_fallback(tile_kind, &osd_file_frames.font_variant(), highest_used_tile_index)?,
+            None => font_dir.load_variant_with_fallback(tile_kind, &font_variant, highest_used_tile_index)?,
         };
 
         let tile_images = match tile_scaling {
-            Some(tile_dimensions) => tiles.as_slice().resized_tiles_par_with_progress(tile_dimensions),
-            None => tiles.into_iter().map(|tile| tile.image().clone()).collect(),
+            Some(tile_dimensions) => tile_images.as_slice().resized_tiles_par_with_progress(tile_dimensions),
+            None => tile_images,
         };
 
         if let Scaling::No { target_resolution: Some(target_resolution) } = scaling {
@@ -330,15 +622,193 @@ impl<'a> Generator<'a> {
 
         let hidden_items = hidden_items.iter().map(String::as_str).collect();
 
-        Ok(Self { osd_file_frames, tile_images, frame_dimensions: overlay_resolution, hidden_regions, hidden_items, font_variant })
+        Ok(Self { frames_source, tile_images, frame_dimensions: overlay_resolution, hidden_regions, hidden_items, font_variant,
+            telemetry: None, layers: OverlayLayers::default(), visible_columns: None, frame_styles: vec![],
+            tile_blit_method: TileBlitMethod::default(), stream_cursor: Mutex::new(None),
+            render_cache: Mutex::new(RenderCache::new(render_cache::DEFAULT_CAPACITY)), scaling_explanation })
+    }
+
+    /// logs the steps which led to the choice of the kind of tiles and of the scaling
+    pub fn log_scaling_explanation(&self) {
+        for line in &self.scaling_explanation {
+            log::info!("scaling decision: {line}");
+        }
+    }
+
+    /// selects the method used to copy the tiles onto the frames, the row by row copy is used by default
+    pub fn set_tile_blit_method(&mut self, method: TileBlitMethod) {
+        self.tile_blit_method = method;
+    }
+
+    fn tiles_drawing(&self) -> TilesDrawing {
+        TilesDrawing {
+            frame_dimensions: self.frame_dimensions,
+            font_variant: self.font_variant,
+            tile_images: &self.tile_images,
+            hidden_regions: self.hidden_regions,
+            hidden_items: &self.hidden_items,
+            visible_columns: self.visible_columns.as_ref(),
+            styles: &self.frame_styles,
+            blit_method: self.tile_blit_method,
+        }
+    }
+
+    /// applies the style to the OSD tiles and to the frames drawn with them, the style is applied on top of any
+    /// previously applied style
+    pub fn apply_style(&mut self, style: &style::Style) {
+        style.apply_to_tiles(&mut self.tile_images);
+        if style.has_frame_effects() {
+            self.frame_styles.push(style.clone());
+        }
+    }
+
+    /// reads the flight telemetry and aligns it with the OSD frames, see [`crate::telemetry`]
+    ///
+    /// The telemetry is used by the graphs and the sticks which must be set afterwards.
+    pub fn set_telemetry(&mut self, settings: Option<&TelemetrySettings>) -> Result<(), TelemetryError> {
+        let settings = match settings {
+            Some(settings) => settings,
+            None => {
+                self.telemetry = None;
+                return Ok(());
+            },
+        };
+        let log = telemetry::read(settings.telemetry_file(), settings.log_index())?;
+        let osd_arm_frame_index = match settings.sync() {
+            TelemetrySync::OsdArm => {
+                let osd_arm_frame_index = match &self.frames_source {
+                    FramesSource::Loaded(frames) => telemetry::osd_arm_frame_index(frames.frames().iter().map(Ok), self.font_variant)?,
+                    FramesSource::Streamed { reader, .. } => telemetry::osd_arm_frame_index(reader.frames_stream()?, self.font_variant)?,
+                };
+                if osd_arm_frame_index.is_none() {
+                    log::warn!("the OSD fly time was not found, aligning the telemetry with the start of the OSD");
+                }
+                osd_arm_frame_index
+            },
+            TelemetrySync::ArmBeep => {
+                if settings.arm_beep_frame_index().is_none() {
+                    log::warn!("no arm beep detected in the video, aligning the telemetry with the start of the OSD");
+                }
+                settings.arm_beep_frame_index()
+            },
+            TelemetrySync::Start => None,
+        };
+        if let Some(osd_arm_frame_index) = osd_arm_frame_index {
+            log::info!("aligning the telemetry with the OSD: armed at OSD frame {osd_arm_frame_index}");
+        }
+        self.telemetry = Some(AlignedTelemetry::new(log, osd_arm_frame_index, settings.offset()));
+        Ok(())
+    }
+
+    /// draws graphs of the OSD item values next to the OSD, see [`graphs::TelemetryGraphs`]
+    pub fn set_telemetry_graphs(&mut self, settings: &graphs::GraphsSettings) -> Result<(), FramesIterError> {
+        self.layers.remove(LayerKind::Graphs);
+        if settings.items().is_empty() { return Ok(()) }
+        let telemetry_graphs = {
+            let frames = self.frames_source.load(self.font_variant)?;
+            TelemetryGraphs::new(frames.frames(), self.font_variant, settings, self.telemetry.as_ref())?
+        };
+        if ! telemetry_graphs.is_empty() {
+            self.layers.add(Box::new(telemetry_graphs));
+        }
+        Ok(())
+    }
+
+    /// Restricts the OSD to the horizontal area of the overlay frames which stays visible once the video is cropped,
+    /// given as its left edge and width in pixels, the OSD items outside of it are moved into it
+    pub fn set_visible_area(&mut self, left: i64, width: u32) {
+        let tile_width = self.tile_images.first().map(|tile_image| tile_image.width()).unwrap_or(1) as i64;
+        let first_column = (left.max(0) + tile_width - 1) / tile_width;
+        let end_column = ((left + width as i64) / tile_width).clamp(0, Coordinate::MAX as i64);
+        log::info!("moving the OSD items out of the visible area into OSD columns {first_column} to {}", end_column - 1);
+        self.visible_columns = Some(first_column.min(end_column) as Coordinate..end_column as Coordinate);
+    }
+
+    /// draws the stick positions read from the flight telemetry next to the OSD, see [`sticks::StickOverlay`]
+    pub fn set_stick_overlay(&mut self, settings: &sticks::SticksSettings) {
+        self.layers.remove(LayerKind::Sticks);
+        if ! settings.enabled() { return }
+        match &self.telemetry {
+            Some(telemetry) if sticks::TELEMETRY_SERIES.iter().all(|name| telemetry.has_series(name)) =>
+                self.layers.add(Box::new(StickOverlay::new(settings))),
+            _ => log::warn!("the flight telemetry has no stick positions, not drawing the sticks"),
+        }
+    }
+
+    /// draws the widgets defined by the script on top of the OSD, see [`script::ScriptLayer`]
+    ///
+    /// The script is given the flight telemetry which must be set before.
+    pub fn set_script_layer(&mut self, script_file: Option<&Path>) -> Result<(), script::ScriptError> {
+        self.layers.remove(LayerKind::Script);
+        let script_file = match script_file {
+            Some(script_file) => script_file,
+            None => return Ok(()),
+        };
+        let script_layer = {
+            let frames = self.frames_source.load(self.font_variant)?;
+            ScriptLayer::load(script_file, frames.frames(), self.font_variant)?
+        };
+        script_layer.check(self.frame_dimensions, self.telemetry.as_ref())?;
+        self.layers.add(Box::new(script_layer));
+        Ok(())
+    }
+
+    /// z-order, opacity and blend mode of the graphs, sticks and script layers, see [`layers`]
+    pub fn set_layer_configs(&mut self, configs: &[LayerConfig]) {
+        self.layers.set_configs(configs);
+    }
+
+    /// frame with the tiles of the OSD frame, copied from the render cache when the same tiles were already drawn
+    fn draw_tiles(&self, osd_file_frame: &OSDFileFrame) -> Result<Frame, UnknownOSDItem> {
+        // the cache is only locked while looking up the frame, the frame being copied afterwards
+        let cached_frame = self.render_cache.lock().unwrap().get(osd_file_frame.tile_indices());
+        if let Some(frame) = cached_frame {
+            return Ok(Frame::clone(&frame));
+        }
+        let frame = osd_file_frame.draw_overlay_frame(&self.tiles_drawing())?;
+        self.render_cache.lock().unwrap().insert(osd_file_frame.tile_indices(), Arc::new(frame.clone()));
+        Ok(frame)
     }
 
     fn draw_frame(&self, osd_file_frame: &OSDFileFrame) -> Result<Frame, UnknownOSDItem> {
-        osd_file_frame.draw_overlay_frame(self.frame_dimensions, self.font_variant, &self.tile_images, self.hidden_regions, &self.hidden_items)
+        let tiles = self.draw_tiles(osd_file_frame)?;
+        Ok(self.layers.draw(tiles, osd_file_frame.index() as i32, self.telemetry.as_ref()))
     }
 
-    pub fn save_frames_to_dir<P: AsRef<Path> + std::marker::Sync>(&mut self, start: Option<Timestamp>, end: Option<Timestamp>,
-                                                                    path: P, frame_shift: i32) -> Result<(), SaveFramesToDirError> {
+    /// draws an OSD frame from tile indices not coming from the OSD file, e.g. received live from the flight controller
+    pub fn draw_tile_indices(&self, tile_indices: &TileIndices) -> Result<Frame, UnknownOSDItem> {
+        self.draw_frame(&OSDFileFrame::new(0, tile_indices.clone()))
+    }
+
+    /// draws the OSD frame being displayed at the specified video frame, empty if no OSD frame was displayed yet
+    pub fn frame_at(&self, video_frame: u32, frame_shift: i32) -> Result<Frame, FramesIterError> {
+        let osd_frame_index = video_frame as i32 - frame_shift;
+        let displayed_frame = match &self.frames_source {
+            FramesSource::Loaded(frames) =>
+                frames.frames().iter().rev().find(|frame| frame.index() as i32 <= osd_frame_index).map(Cow::Borrowed),
+            FramesSource::Streamed { reader, .. } => {
+                let mut stream_cursor = self.stream_cursor.lock().unwrap();
+                if ! matches!(&*stream_cursor, Some(stream_cursor) if stream_cursor.osd_frame_index <= osd_frame_index) {
+                    *stream_cursor = Some(StreamCursor { frames: reader.frames_stream()?.peekable(), osd_frame_index, displayed_frame: None });
+                }
+                let stream_cursor = stream_cursor.as_mut().unwrap();
+                let is_displayed = |frame: &Result<OSDFileFrame, ReadError>|
+                    frame.as_ref().map_or(true, |frame| frame.index() as i32 <= osd_frame_index);
+                while let Some(frame) = stream_cursor.frames.next_if(is_displayed) {
+                    stream_cursor.displayed_frame = Some(frame?);
+                }
+                stream_cursor.osd_frame_index = osd_frame_index;
+                stream_cursor.displayed_frame.clone().map(Cow::Owned)
+            },
+        };
+        match displayed_frame {
+            Some(osd_file_frame) => Ok(self.draw_frame(&osd_file_frame)?),
+            None => Ok(Frame::new(self.frame_dimensions)),
+        }
+    }
+
+    pub fn save_frames_to_dir<P: AsRef<Path> + std::marker::Sync>(&mut self, first_video_frame: u32, last_video_frame: Option<u32>,
+                                                                    path: P, frame_shift: i32, png_compression: PngCompression) -> Result<(), SaveFramesToDirError> {
 
         if path.as_ref().exists() {
             return Err(SaveFramesToDirError::TargetDirectoryExists(path.as_ref().to_path_buf()));
@@ -347,11 +817,10 @@ impl<'a> Generator<'a> {
         create_path(&path)?;
         log::info!("generating overlay frames and saving into directory: {}", path.as_ref().to_string_lossy());
 
-        let first_video_frame = start.start_overlay_frame_count();
-        let last_video_frame = end.end_overlay_frame_index();
-
+        // the frames slices are processed in parallel so they are all needed in memory
+        let osd_file_frames = self.frames_source.load(self.font_variant)?;
         let osd_file_frames_slice =
-            self.osd_file_frames.select_slice(first_video_frame, last_video_frame, frame_shift);
+            osd_file_frames.select_slice(first_video_frame, last_video_frame, frame_shift);
         if osd_file_frames_slice.is_empty() { return Err(SaveFramesToDirError::NoFrameToWrite); }
 
         let iter =
@@ -364,18 +833,44 @@ impl<'a> Generator<'a> {
 
         let abs_output_dir_path = path.as_ref().absolutize().unwrap();
 
+        use crate::osd::file::sorted_frames::VideoFramesRelIndexIterItem::*;
+
+        // without layers the overlay frames only depend on their tiles, so the frames identical to an earlier one are
+        // linked to the file of the earlier one instead of being drawn and encoded again
+        let identical_frames: HashMap<u32, u32> = if self.layers.is_empty() {
+            let existing_frames = osd_file_frames_slice.video_frames_rel_index_iter(EndOfFramesAction::ContinueToLastVideoFrame)
+                .filter_map(|item| match item { Existing { rel_index, frame } => Some((rel_index, frame)), _ => None })
+                .collect::<Vec<_>>();
+            render_cache::identical_tile_grids(existing_frames.iter().map(|(_, frame)| frame.tile_indices()))
+                .into_iter()
+                .map(|(position, first_position)| (existing_frames[position].0, existing_frames[first_position].0))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
         iter.progress_with(progress_bar).try_for_each(|item| {
-            use crate::osd::file::sorted_frames::VideoFramesRelIndexIterItem::*;
             match item {
+                Existing { rel_index, .. } if identical_frames.contains_key(&rel_index) => {
+                    let first_rel_index = identical_frames[&rel_index];
+                    log::debug!("identical {} -> {}", rel_index, first_rel_index);
+                    let first_path = make_overlay_frame_file_path(&abs_output_dir_path, first_rel_index);
+                    let link_path = make_overlay_frame_file_path(&path, rel_index);
+                    fs_err::os::unix::fs::symlink(first_path, link_path)
+                        .map_err(SaveFramesToDirError::SymlinkError)?;
+                },
                 Existing { rel_index, frame } => {
                     log::debug!("existing {}", &rel_index);
                     let frame_image = self.draw_frame(frame)?;
-                    frame_image.write_image_file(make_overlay_frame_file_path(&path, rel_index))?;
+                    let frame_path = make_overlay_frame_file_path(&path, rel_index);
+                    let _partial_file = crate::process::PartialFile::new(&frame_path);
+                    frame_image.write_png_file(frame_path, png_compression)?;
                 },
                 FirstNonExisting => {
                     log::debug!("first non existing");
                     let frame_0_path = make_overlay_frame_file_path(&path, 0);
-                    Frame::new(self.frame_dimensions).write_image_file(frame_0_path)?;
+                    let _partial_file = crate::process::PartialFile::new(&frame_0_path);
+                    Frame::new(self.frame_dimensions).write_png_file(frame_0_path, png_compression)?;
                 },
                 NonExisting { prev_rel_index, rel_index } => {
                     log::debug!("non existing {} -> {}", rel_index, prev_rel_index);
@@ -388,39 +883,98 @@ impl<'a> Generator<'a> {
             Ok::<(), SaveFramesToDirError>(())
         })?;
 
+        let cache = self.render_cache.lock().unwrap();
+        log::debug!("render cache: {} hits, {} misses", cache.hit_count(), cache.miss_count());
         log::info!("overlay frames generation completed: {} frame files written", frame_count);
         Ok(())
     }
 
-    pub async fn generate_overlay_video<P: AsRef<Path>>(&mut self, codec: OverlayVideoCodec, start: Option<Timestamp>, end: Option<Timestamp>,
+    /// estimates the encoding time and output size of the overlay video by generating `sample_duration` seconds from
+    /// the middle of the frames with the same settings, the sample is written to a temporary file removed afterwards
+    pub async fn estimate_overlay_video<P: AsRef<Path>>(&mut self, codec: OverlayVideoCodec, encoder_speed: &OverlayVideoEncoderSpeed,
+                                    first_video_frame: u32, last_video_frame: Option<u32>, frame_rate: u16, container: Option<Container>,
+                                    output_video_path: P, frame_shift: i32, sample_duration: f64) -> Result<Estimate, GenerateOverlayVideoError> {
+
+        let output_video_path = output_video_path.as_ref();
+        let output_container = match container.or_else(|| Container::from_extension(output_video_path)) {
+            Some(output_container) => output_container,
+            None if file::is_stdout(output_video_path) => Container::Mkv,
+            None => return Err(GenerateOverlayVideoError::UnknownOutputContainer),
+        };
+
+        // the frame indices are OSD frame indices whatever the overlay video frame rate
+        let frame_count = self.iter_advanced(first_video_frame, last_video_frame, frame_shift).len() as u32;
+        let sample_frame_count = ((sample_duration * osd::FRAME_RATE as f64) as u32).min(frame_count);
+        let sample_first_frame = first_video_frame + (frame_count - sample_frame_count) / 2;
+
+        let temp_files = TempFiles(vec![cut::temp_file_path(output_video_path, &format!(".estimate.{}", output_container.extension()))]);
+        let sample_file = &temp_files.0[0];
+
+        log::info!("generating a {:.1}s sample to estimate the encoding time and output size", sample_frame_count as f64 / osd::FRAME_RATE as f64);
+        let sample_start_time = Instant::now();
+        self.generate_overlay_video(codec, encoder_speed, sample_first_frame, Some(sample_first_frame + sample_frame_count), frame_rate,
+            Some(output_container), sample_file, frame_shift, true).await?;
+        Ok(Estimate::from_sample(sample_file, sample_frame_count as f64 / osd::FRAME_RATE as f64, sample_start_time.elapsed(),
+            frame_count as f64 / osd::FRAME_RATE as f64))
+    }
+
+    /// generates the overlay video, `frame_rate` can be lowered down to the OSD update rate to reduce the encoding
+    /// time and file size, the OSD frames are then sampled at this constant rate
+    ///
+    /// The container is inferred from the extension of the output file when not set, the standard output is written
+    /// in the matroska format by default.
+    pub async fn generate_overlay_video<P: AsRef<Path>>(&mut self, codec: OverlayVideoCodec, encoder_speed: &OverlayVideoEncoderSpeed,
+                                    first_video_frame: u32, last_video_frame: Option<u32>, frame_rate: u16, container: Option<Container>,
                                     output_video_path: P, frame_shift: i32, overwrite_output: bool) -> Result<(), GenerateOverlayVideoError> {
 
         let output_video_path = output_video_path.as_ref();
+        let output_to_stdout = file::is_stdout(output_video_path);
 
-        if ! matches!(output_video_path.extension(), Some(extension) if extension == "webm") {
-            return Err(GenerateOverlayVideoError::OutputFileExtensionNotWebm)
+        if let Some(container) = container {
+            container.check_output_file(output_video_path)?;
         }
-
-        if ! overwrite_output &&  output_video_path.exists() {
-            return Err(GenerateOverlayVideoError::TargetVideoFileExists(output_video_path.to_path_buf()));
+        let output_container = match container.or_else(|| Container::from_extension(output_video_path)) {
+            Some(output_container) => output_container,
+            None if output_to_stdout => Container::Mkv,
+            None => return Err(GenerateOverlayVideoError::UnknownOutputContainer),
+        };
+        if ! output_container.supports_transparency() {
+            return Err(GenerateOverlayVideoError::ContainerWithoutTransparency(output_container));
         }
+        output_container.check_encoders(codec.params().encoder(), None)?;
+
+        if ! output_to_stdout {
 
-        file::touch(output_video_path)?;
+            if ! overwrite_output &&  output_video_path.exists() {
+                return Err(GenerateOverlayVideoError::TargetVideoFileExists(output_video_path.to_path_buf()));
+            }
+
+            file::touch(output_video_path)?;
+        }
 
         log::info!("generating overlay video: {}", output_video_path.to_string_lossy());
 
         let frames_iter =
-            self.iter_advanced(start.start_overlay_frame_count(), end.end_overlay_frame_index(), frame_shift);
-        let frame_count = frames_iter.len();
+            self.iter_advanced(first_video_frame, last_video_frame, frame_shift);
+        let frame_count = frames_iter.len() * frame_rate as usize / osd::FRAME_RATE as usize;
+        let frames_iter = InterpolatedFramesIter::new(frames_iter, frame_rate, false);
 
         let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
 
         ffmpeg_command
-            .add_stdin_input(self.frame_dimensions, 60).unwrap()
+            .add_stdin_input(self.frame_dimensions, frame_rate).unwrap()
             .set_output_video_settings(Some(codec.params().encoder()), codec.params().bitrate(), codec.params().crf())
             .add_args(codec.params().additional_args())
-            .set_output_file(output_video_path)
-            .set_overwrite_output_file(true);
+            .add_args(&encoder_speed.encoder_args(codec).iter().map(String::as_str).collect::<Vec<_>>());
+
+        if output_to_stdout {
+            ffmpeg_command.set_output_stdout(output_container.ffmpeg_format());
+        } else {
+            ffmpeg_command
+                .add_args(&["-f", output_container.ffmpeg_format()])
+                .set_output_file(output_video_path)
+                .set_overwrite_output_file(true);
+        }
 
         let ffmpeg_process = ffmpeg_command.build().unwrap().spawn_with_progress(frame_count as u64)?;
 
@@ -430,6 +984,59 @@ impl<'a> Generator<'a> {
         Ok(())
     }
 
+    /// generates a downscaled preview of the OSD over a checkerboard as an animated GIF or MP4 video depending on the file extension
+    pub async fn generate_preview<P: AsRef<Path>>(&self, first_video_frame: u32, last_video_frame: Option<u32>, output_path: P,
+                                    frame_shift: i32, width: u32, fps: u32, overwrite_output: bool) -> Result<(), GenerateOverlayVideoError> {
+
+        let output_path = output_path.as_ref();
+
+        let is_gif = match output_path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("gif") => true,
+            Some(extension) if extension.eq_ignore_ascii_case("mp4") => false,
+            _ => return Err(GenerateOverlayVideoError::PreviewFileExtensionNotGifOrMp4),
+        };
+
+        if ! overwrite_output && output_path.exists() {
+            return Err(GenerateOverlayVideoError::TargetVideoFileExists(output_path.to_path_buf()));
+        }
+
+        file::touch(output_path)?;
+
+        log::info!("generating OSD preview: {}", output_path.to_string_lossy());
+
+        let frames_iter = self.iter_advanced(first_video_frame, last_video_frame, frame_shift);
+        let frame_count = frames_iter.len();
+
+        // even dimensions are required by the MP4 encoder
+        let height = ((width as f64 * self.frame_dimensions.height as f64 / self.frame_dimensions.width as f64 / 2.0).round() as u32 * 2).max(2);
+        let width = width / 2 * 2;
+        let checkerboard = format!("nullsrc=s={width}x{height}:r={fps},geq=lum='if(mod(floor(X/16)+floor(Y/16),2),160,96)':cb=128:cr=128");
+        let osd = format!("fps={fps},scale={width}:{height}:flags=lanczos");
+        let output_filter = if is_gif { "split[v1][v2];[v1]palettegen[p];[v2][p]paletteuse" } else { "format=yuv420p" };
+        let filter = format!("{checkerboard}[bg];[0]{osd}[osd];[bg][osd]overlay=shortest=1,{output_filter}[vo]");
+
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+
+        ffmpeg_command
+            .add_stdin_input(self.frame_dimensions, osd::FRAME_RATE as u16).unwrap()
+            .add_complex_filter(&filter)
+            .add_mapping("[vo]")
+            .set_output_file(output_path)
+            .set_overwrite_output_file(true);
+
+        if ! is_gif {
+            ffmpeg_command.set_output_video_settings(Some("libx264"), None, Some(23));
+        }
+
+        let output_frame_count = frame_count as u64 * fps as u64 / osd::FRAME_RATE as u64;
+        let ffmpeg_process = ffmpeg_command.build().unwrap().spawn_with_progress(output_frame_count)?;
+
+        frames_iter.send_frames_to_ffmpeg_and_wait(ffmpeg_process).await?;
+
+        log::info!("OSD preview generation completed");
+        Ok(())
+    }
+
     pub fn iter(&self) -> FramesIter {
         self.into_iter()
     }
@@ -437,19 +1044,24 @@ impl<'a> Generator<'a> {
     pub fn iter_advanced(&self, first_frame: u32, last_frame: Option<u32>, frame_shift: i32) -> FramesIter {
         FramesIter {
             frame_dimensions: self.frame_dimensions,
-            font_variant: self.font_variant,
-            tile_images: &self.tile_images,
-            vframes_iter: self.osd_file_frames.video_frames_iter(first_frame, last_frame, frame_shift),
-            hidden_regions: self.hidden_regions,
-            hidden_items: &self.hidden_items,
-            prev_frame: Frame::new(self.frame_dimensions)
+            tiles_drawing: self.tiles_drawing(),
+            vframes_iter: match &self.frames_source {
+                FramesSource::Loaded(frames) => VideoFrames::Loaded(frames.video_frames_iter(first_frame, last_frame, frame_shift)),
+                FramesSource::Streamed { reader, last_frame_index } =>
+                    VideoFrames::Streamed(VideoFramesStreamIter::new(reader.frames_stream(), first_frame, last_frame, frame_shift, *last_frame_index)),
+            },
+            layers: &self.layers,
+            telemetry: self.telemetry.as_ref(),
+            osd_frame_index: first_frame as i32 - frame_shift,
+            prev_frame: Frame::new(self.frame_dimensions),
+            render_cache: RenderCache::new(render_cache::DEFAULT_CAPACITY),
         }
     }
 
 }
 
 impl<'a> IntoIterator for &'a Generator<'a> {
-    type Item = Result<Frame, UnknownOSDItem>;
+    type Item = Result<Frame, FramesIterError>;
 
     type IntoIter = FramesIter<'a>;
 
@@ -458,37 +1070,60 @@ impl<'a> IntoIterator for &'a Generator<'a> {
     }
 }
 
+#[derive(Debug, Error, From)]
+pub enum FramesIterError {
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+    #[error("OSD file read error: {0}")]
+    ReadError(ReadError),
+}
+
 #[derive(Debug, Error, From)]
 pub enum SendFramesToFFMpegError {
     #[error("error sending overlay frames to FFMpeg: pipe error: {0}")]
     PipeError(io::Error),
     #[error(transparent)]
     UnknownOSDItem(UnknownOSDItem),
+    #[error("OSD file read error: {0}")]
+    ReadError(ReadError),
     #[error(transparent)]
     FFMpegExitedWithError(ffmpeg::ProcessError),
 }
 
+impl From<FramesIterError> for SendFramesToFFMpegError {
+    fn from(error: FramesIterError) -> Self {
+        match error {
+            FramesIterError::UnknownOSDItem(error) => Self::UnknownOSDItem(error),
+            FramesIterError::ReadError(error) => Self::ReadError(error),
+        }
+    }
+}
+
+/// video frames iterator over the loaded or streamed OSD file frames
+enum VideoFrames<'a> {
+    Loaded(VideoFramesIter<'a>),
+    Streamed(VideoFramesStreamIter),
+}
+
 #[derive(CopyGetters)]
 pub struct FramesIter<'a> {
     #[getset(get_copy = "pub")]
     frame_dimensions: Dimensions,
-    font_variant: FontVariant,
-    tile_images: &'a [tile::Image],
-    vframes_iter: VideoFramesIter<'a>,
-    hidden_regions: &'a [Region],
-    hidden_items: &'a [&'a str],
-    prev_frame: Frame
+    tiles_drawing: TilesDrawing<'a>,
+    vframes_iter: VideoFrames<'a>,
+    layers: &'a OverlayLayers,
+    telemetry: Option<&'a AlignedTelemetry>,
+    /// index of the OSD frame displayed at the next video frame
+    osd_frame_index: i32,
+    /// last OSD update, without the layers when they are animated
+    prev_frame: Frame,
+    render_cache: RenderCache,
 }
 
 impl<'a> FramesIter<'a> {
 
     pub fn send_frames_to_ffmpeg(&mut self, ffmpeg_process: &mut ffmpeg::Process) -> Result<(), SendFramesToFFMpegError> {
-        let mut ffmpeg_stdin = ffmpeg_process.take_stdin().unwrap();
-        for osd_frame_image in self {
-            ffmpeg_stdin.write_all(osd_frame_image?.as_raw())?;
-        }
-        drop(ffmpeg_stdin);
-        Ok(())
+        send_frames_to_ffmpeg(self, ffmpeg_process)
     }
 
     pub async fn send_frames_to_ffmpeg_and_wait(mut self, mut ffmpeg_process: ffmpeg::Process) -> Result<(), SendFramesToFFMpegError> {
@@ -500,29 +1135,135 @@ impl<'a> FramesIter<'a> {
         Ok(())
     }
 
+    /// sends the frames at the OSD frame rate, for live outputs where FFMpeg reads the other inputs in real time
+    pub async fn send_frames_to_ffmpeg_in_real_time_and_wait(mut self, mut ffmpeg_process: ffmpeg::Process) -> Result<(), SendFramesToFFMpegError> {
+        let send_result = send_frames_to_ffmpeg_in_real_time(&mut self, &mut ffmpeg_process).await;
+
+        ffmpeg_process.wait().await?;
+        send_result?;
+
+        Ok(())
+    }
+
 }
 
-impl<'a> Iterator for FramesIter<'a> {
-    type Item = Result<Frame, UnknownOSDItem>;
+/// sends the frames to the standard input of the FFMpeg process, each frame when it should be displayed
+///
+/// Frames sent ahead of the live input would be buffered by FFMpeg and displayed late.
+async fn send_frames_to_ffmpeg_in_real_time(frames: impl Iterator<Item = Result<Frame, FramesIterError>>, ffmpeg_process: &mut ffmpeg::Process)
+        -> Result<(), SendFramesToFFMpegError> {
+    // no process in dry run mode
+    let mut ffmpeg_stdin = match ffmpeg_process.take_stdin() {
+        Some(ffmpeg_stdin) => ffmpeg_stdin,
+        None => return Ok(()),
+    };
+    let frame_duration = Duration::from_secs_f64(1.0 / osd::FRAME_RATE as f64);
+    let start = tokio::time::Instant::now();
+    for (frame_index, osd_frame_image) in frames.enumerate() {
+        tokio::time::sleep_until(start + frame_duration * frame_index as u32).await;
+        ffmpeg_stdin.write_all(osd_frame_image?.as_raw())?;
+    }
+    drop(ffmpeg_stdin);
+    Ok(())
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.vframes_iter.next()? {
+/// sends the frames to the standard input of the FFMpeg process
+fn send_frames_to_ffmpeg(frames: impl Iterator<Item = Result<Frame, FramesIterError>>, ffmpeg_process: &mut ffmpeg::Process)
+        -> Result<(), SendFramesToFFMpegError> {
+    // no process in dry run mode
+    let mut ffmpeg_stdin = match ffmpeg_process.take_stdin() {
+        Some(ffmpeg_stdin) => ffmpeg_stdin,
+        None => return Ok(()),
+    };
+    for osd_frame_image in frames {
+        ffmpeg_stdin.write_all(osd_frame_image?.as_raw())?;
+    }
+    drop(ffmpeg_stdin);
+    Ok(())
+}
+
+impl<'a> FramesIter<'a> {
+
+    /// next frame and whether it is an OSD update, the frame is a copy of the previous frame when it is not
+    ///
+    /// With animated layers like the sticks or the script widgets every frame is an update since they move between the
+    /// OSD updates.
+    fn next_with_update_flag(&mut self) -> Option<Result<(Frame, bool), FramesIterError>> {
+        let osd_file_frame = match &mut self.vframes_iter {
+            VideoFrames::Loaded(iter) => iter.next()?.map(Cow::Borrowed),
+            VideoFrames::Streamed(iter) => match iter.next()? {
+                Ok(osd_file_frame) => osd_file_frame.map(Cow::Owned),
+                Err(error) => return Some(Err(error.into())),
+            },
+        };
+        let osd_frame_index = self.osd_frame_index;
+        self.osd_frame_index += 1;
+        Some(self.osd_frame(osd_file_frame, osd_frame_index))
+    }
+
+    /// OSD frame with the layers and whether it is an OSD update
+    fn osd_frame(&mut self, osd_file_frame: Option<Cow<OSDFileFrame>>, osd_frame_index: i32) -> Result<(Frame, bool), FramesIterError> {
+        let animated = self.layers.is_animated();
+        match osd_file_frame {
             Some(osd_file_frame) => {
-                let frame = match osd_file_frame.draw_overlay_frame(self.frame_dimensions, self.font_variant,
-                                                                           self.tile_images, self.hidden_regions, self.hidden_items) {
-                    Ok(frame) => frame,
-                    Err(error) => return Some(Err(error)),
+                let tiles = match self.render_cache.get(osd_file_frame.tile_indices()) {
+                    Some(frame) => Frame::clone(&frame),
+                    None => {
+                        let frame = osd_file_frame.draw_overlay_frame(&self.tiles_drawing)?;
+                        self.render_cache.insert(osd_file_frame.tile_indices(), Arc::new(frame.clone()));
+                        frame
+                    },
                 };
-                self.prev_frame = frame.clone();
-                Some(Ok(frame))
+                if animated {
+                    self.prev_frame = tiles.clone();
+                    Ok((self.layers.draw(tiles, osd_frame_index, self.telemetry), true))
+                } else {
+                    let frame = self.layers.draw(tiles, osd_file_frame.index() as i32, self.telemetry);
+                    self.prev_frame = frame.clone();
+                    Ok((frame, true))
+                }
             },
-            None => Some(Ok(self.prev_frame.clone())),
+            None if animated => Ok((self.layers.draw(self.prev_frame.clone(), osd_frame_index, self.telemetry), true)),
+            None => Ok((self.prev_frame.clone(), false)),
         }
     }
+
+}
+
+impl<'a> Iterator for FramesIter<'a> {
+    type Item = Result<Frame, FramesIterError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_update_flag().map(|result| result.map(|(frame, _)| frame))
+    }
 }
 
 impl<'a> ExactSizeIterator for FramesIter<'a> {
     fn len(&self) -> usize {
-        self.vframes_iter.len()
+        match &self.vframes_iter {
+            VideoFrames::Loaded(iter) => iter.len(),
+            VideoFrames::Streamed(iter) => iter.len(),
+        }
     }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn blit_methods_draw_identical_frames() {
+        let tile = ImageBuffer::from_fn(3, 2, |x, y| Rgba([x as u8, y as u8, (x * y) as u8, 255]));
+        let mut rows_frame = Frame::new(Dimensions::new(8, 6));
+        let mut pixels_frame = Frame::new(Dimensions::new(8, 6));
+        for (x, y) in [(0, 0), (5, 4), (2, 3)] {
+            rows_frame.blit(&tile, x, y, TileBlitMethod::Rows).unwrap();
+            pixels_frame.blit(&tile, x, y, TileBlitMethod::Pixels).unwrap();
+        }
+        assert_eq!(rows_frame.image, pixels_frame.image);
+        assert!(rows_frame.blit(&tile, 6, 0, TileBlitMethod::Rows).is_err());
+        assert!(rows_frame.blit(&tile, u32::MAX, 0, TileBlitMethod::Rows).is_err());
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,172 @@
+
+//! Several OSD files burnt onto one video, e.g. the OSD of two pilots onto a split screen video composed of their
+//! two DVR videos
+//!
+//! Each OSD file is drawn into an area of the video, the OSD being scaled to fit the area as if the area was the
+//! whole video then centered in it. The frames of the OSD files are composed into frames of the video size sent to
+//! FFMpeg as a single OSD input.
+
+use std::{path::PathBuf, str::FromStr};
+
+use getset::{CopyGetters, Getters};
+use thiserror::Error;
+
+use crate::{ffmpeg, osd::frame_shift::FrameShift};
+
+use super::{Dimensions, Frame, FramesIterError, SendFramesToFFMpegError, interpolation::InterpolatedFramesIter, send_frames_to_ffmpeg};
+
+
+#[derive(Debug, Error)]
+#[error("invalid OSD area: {0}, expected left, right, top, bottom, top-left, top-right, bottom-left, bottom-right or <x>,<y>,<width>,<height> in percent of the video")]
+pub struct InvalidAreaError(String);
+
+/// rectangle of the video in percent of the video dimensions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Area {
+
+    /// position and dimensions in pixels of the area of a video of the dimensions
+    pub fn region(&self, video_dimensions: Dimensions) -> (u32, u32, Dimensions) {
+        let pixels = |percent: f64, size: u32| (percent * size as f64 / 100.0).round() as u32;
+        let (left, top) = (pixels(self.left, video_dimensions.width), pixels(self.top, video_dimensions.height));
+        let width = pixels(self.width, video_dimensions.width).min(video_dimensions.width - left);
+        let height = pixels(self.height, video_dimensions.height).min(video_dimensions.height - top);
+        (left, top, Dimensions::new(width, height))
+    }
+
+}
+
+impl FromStr for Area {
+    type Err = InvalidAreaError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let [left, top, width, height] = match value.trim() {
+            "left" => [0.0, 0.0, 50.0, 100.0],
+            "right" => [50.0, 0.0, 50.0, 100.0],
+            "top" => [0.0, 0.0, 100.0, 50.0],
+            "bottom" => [0.0, 50.0, 100.0, 50.0],
+            "top-left" => [0.0, 0.0, 50.0, 50.0],
+            "top-right" => [50.0, 0.0, 50.0, 50.0],
+            "bottom-left" => [0.0, 50.0, 50.0, 50.0],
+            "bottom-right" => [50.0, 50.0, 50.0, 50.0],
+            rectangle => {
+                let values = rectangle.split(',').map(|value| value.trim().parse::<f64>()).collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| InvalidAreaError(value.to_owned()))?;
+                <[f64; 4]>::try_from(values).map_err(|_| InvalidAreaError(value.to_owned()))?
+            },
+        };
+        let valid = [left, top].iter().all(|value| (0.0..100.0).contains(value))
+            && width > 0.0 && height > 0.0 && left + width <= 100.0 && top + height <= 100.0;
+        if ! valid { return Err(InvalidAreaError(value.to_owned())) }
+        Ok(Self { left, top, width, height })
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid OSD file: {0}, expected <OSD file>[|area=<area>][|shift=<frames|<ms>ms>]")]
+pub struct InvalidOSDSourceError(String);
+
+/// OSD file drawn into an area of the video with its own frame shift, parsed from
+/// `<OSD file>[|area=<area>][|shift=<frames|<ms>ms>]`, e.g. `DJIG0001.osd|area=right|shift=-3`
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct OSDSource {
+    #[getset(get = "pub")]
+    file: PathBuf,
+    /// the whole video when not set
+    #[getset(get_copy = "pub")]
+    area: Option<Area>,
+    #[getset(get_copy = "pub")]
+    frame_shift: Option<FrameShift>,
+}
+
+impl FromStr for OSDSource {
+    type Err = InvalidOSDSourceError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidOSDSourceError(value.to_owned());
+        let mut parts = value.split('|');
+        let file = parts.next().filter(|file| ! file.is_empty()).ok_or_else(error)?;
+        let mut source = Self { file: PathBuf::from(file), area: None, frame_shift: None };
+        for part in parts {
+            let (key, option_value) = part.split_once('=').ok_or_else(error)?;
+            match key.trim() {
+                "area" => source.area = Some(option_value.parse().map_err(|_| error())?),
+                "shift" => source.frame_shift = Some(option_value.parse().map_err(|_| error())?),
+                _ => return Err(error()),
+            }
+        }
+        Ok(source)
+    }
+}
+
+/// Iterator over the frames of several OSD sources composed into frames of the video dimensions
+///
+/// Each source is drawn at its position, the frames end when all the sources ended.
+pub struct ComposedFramesIter<'a> {
+    dimensions: Dimensions,
+    /// frames of the source, position of the frames and whether the source ended
+    sources: Vec<(InterpolatedFramesIter<'a>, (u32, u32), bool)>,
+}
+
+impl<'a> ComposedFramesIter<'a> {
+
+    pub fn new(dimensions: Dimensions, sources: Vec<(InterpolatedFramesIter<'a>, (u32, u32))>) -> Self {
+        Self { dimensions, sources: sources.into_iter().map(|(frames, position)| (frames, position, false)).collect() }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, FramesIterError> {
+        let mut frame = Frame::new(self.dimensions);
+        let mut drawn = false;
+        for (frames, (x, y), ended) in &mut self.sources {
+            if *ended { continue }
+            match frames.next() {
+                Some(source_frame) => {
+                    image::imageops::overlay(frame.image_mut(), &*source_frame?, *x as i64, *y as i64);
+                    drawn = true;
+                },
+                None => *ended = true,
+            }
+        }
+        Ok(Some(frame).filter(|_| drawn))
+    }
+
+    pub async fn send_frames_to_ffmpeg_and_wait(self, mut ffmpeg_process: ffmpeg::Process) -> Result<(), SendFramesToFFMpegError> {
+        let send_result = send_frames_to_ffmpeg(self, &mut ffmpeg_process);
+
+        ffmpeg_process.wait().await?;
+        send_result?;
+
+        Ok(())
+    }
+
+}
+
+impl<'a> Iterator for ComposedFramesIter<'a> {
+    type Item = Result<Frame, FramesIterError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_osd_source() {
+        let source = "DJIG0001.osd|area=right|shift=-3".parse::<OSDSource>().unwrap();
+        assert_eq!(source.file(), &PathBuf::from("DJIG0001.osd"));
+        assert_eq!(source.area().unwrap().region(Dimensions::new(1920, 1080)), (960, 0, Dimensions::new(960, 1080)));
+        assert_eq!(source.frame_shift(), Some(FrameShift::Frames(-3)));
+        assert!("DJIG0001.osd|area=60,0,50,100".parse::<OSDSource>().is_err());
+    }
+
+}
@@ -0,0 +1,170 @@
+
+//! Conversion of existing overlay videos to another codec keeping their transparency, without the OSD file
+//!
+//! FFMpeg decodes the VP8 and VP9 videos with its own decoders by default which ignore the alpha channel stored next
+//! to the frames in the WebM/Matroska files, these videos are then decoded with libvpx. The frames are converted to a
+//! pixel format with alpha supported by the target encoder: `yuva420p` for VP8/VP9 and `yuva444p10le` for ProRes 4444,
+//! the codec expected by most video editors.
+
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use thiserror::Error;
+
+use crate::{
+    ffmpeg,
+    file::{self, TouchError},
+    video::{container::{Container, ContainerError}, probe},
+};
+
+use super::{OverlayVideoCodec, OverlayVideoEncoderSpeed};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum OverlayConversionCodec {
+    Vp8,
+    Vp9,
+    /// ProRes 4444, for video editors
+    Prores,
+}
+
+impl OverlayConversionCodec {
+
+    fn overlay_video_codec(&self) -> Option<OverlayVideoCodec> {
+        match self {
+            Self::Vp8 => Some(OverlayVideoCodec::Vp8),
+            Self::Vp9 => Some(OverlayVideoCodec::Vp9),
+            Self::Prores => None,
+        }
+    }
+
+    /// container used when it is not set nor inferred from the extension of the output file
+    pub fn default_container(&self) -> Container {
+        match self {
+            Self::Vp8 | Self::Vp9 => Container::Webm,
+            Self::Prores => Container::Mov,
+        }
+    }
+
+    /// whether the container can store the codec with the alpha channel
+    pub(super) fn supports_container(&self, container: Container) -> bool {
+        match self {
+            Self::Vp8 | Self::Vp9 => matches!(container, Container::Webm | Container::Mkv),
+            Self::Prores => matches!(container, Container::Mov | Container::Mkv),
+        }
+    }
+
+    /// sets the encoder of the codec with a pixel format keeping the alpha channel
+    pub(super) fn set_encoder_settings(&self, ffmpeg_command: &mut ffmpeg::CommandBuilder, encoder_speed: &OverlayVideoEncoderSpeed) {
+        match self.overlay_video_codec() {
+            Some(codec) => {
+                let params = codec.params();
+                ffmpeg_command
+                    .set_output_video_settings(Some(params.encoder()), params.bitrate(), params.crf())
+                    .add_args(params.additional_args())
+                    .add_args(&encoder_speed.encoder_args(codec).iter().map(String::as_str).collect::<Vec<_>>())
+                    .add_args(&["-pix_fmt", "yuva420p"]);
+            },
+            None => {
+                ffmpeg_command
+                    .set_output_video_settings(Some("prores_ks"), None, None)
+                    .add_args(&["-profile:v", "4444", "-pix_fmt", "yuva444p10le", "-alpha_bits", "16", "-vendor", "apl0"]);
+            },
+        }
+    }
+
+}
+
+/// decoder of the input video codec which decodes the alpha channel, `None` when the FFMpeg default decoder does
+fn alpha_decoder(codec: &str) -> Option<&'static str> {
+    match codec {
+        "vp8" => Some("libvpx"),
+        "vp9" => Some("libvpx-vp9"),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Error, From)]
+pub enum ConvertOverlayError {
+    #[error("input video file does not exist: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    InputVideoFileDoesNotExist(PathBuf),
+    #[error("output video file exists: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    OutputVideoFileExists(PathBuf),
+    #[error("input file and output file are the same file")]
+    InputAndOutputFileIsTheSame,
+    #[error("the {container} container cannot store {codec} video with transparency")]
+    #[from(ignore)]
+    UnsupportedContainer { container: Container, codec: OverlayConversionCodec },
+    #[error(transparent)]
+    ContainerError(ContainerError),
+    #[error("failed to get input video details: {0}")]
+    FailedToGetInputVideoDetails(probe::Error),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error(transparent)]
+    WriteToFileError(TouchError),
+}
+
+/// output file used when not provided: the input file with the codec appended to its name and the extension of the
+/// container
+pub fn default_output_video_file(input_video_file: &Path, codec: OverlayConversionCodec, container: Option<Container>) -> PathBuf {
+    let mut file_name = input_video_file.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("_{codec}"));
+    input_video_file.with_file_name(file_name).with_extension(container.unwrap_or_else(|| codec.default_container()).extension())
+}
+
+/// re-encodes the overlay video with the codec keeping its alpha channel, the container is inferred from the extension
+/// of the output file when not set
+pub async fn convert(input_video_file: &Path, output_video_file: &Path, codec: OverlayConversionCodec, encoder_speed: &OverlayVideoEncoderSpeed,
+        container: Option<Container>, overwrite: bool) -> Result<(), ConvertOverlayError> {
+
+    if ! input_video_file.is_file() { return Err(ConvertOverlayError::InputVideoFileDoesNotExist(input_video_file.to_path_buf())) }
+    if input_video_file == output_video_file { return Err(ConvertOverlayError::InputAndOutputFileIsTheSame) }
+    if ! overwrite && output_video_file.exists() { return Err(ConvertOverlayError::OutputVideoFileExists(output_video_file.to_path_buf())) }
+    if let Some(container) = container {
+        container.check_output_file(output_video_file)?;
+    }
+    let container = container.or_else(|| Container::from_extension(output_video_file)).unwrap_or_else(|| codec.default_container());
+    if ! codec.supports_container(container) { return Err(ConvertOverlayError::UnsupportedContainer { container, codec }) }
+
+    let video_info = probe(input_video_file)?;
+    let input_codec = video_info.video_codec().as_deref().unwrap_or_default();
+
+    file::touch(output_video_file)?;
+
+    log::info!("converting overlay video from {input_codec} to {codec}: {} -> {}", input_video_file.to_string_lossy(), output_video_file.to_string_lossy());
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    match alpha_decoder(input_codec) {
+        Some(decoder) => ffmpeg_command.add_input_file_with_decoder(input_video_file, decoder),
+        None => ffmpeg_command.add_input_file(input_video_file),
+    };
+    ffmpeg_command.add_mapping("0:v:0");
+    codec.set_encoder_settings(&mut ffmpeg_command, encoder_speed);
+    ffmpeg_command
+        .add_args(&["-f", container.ffmpeg_format()])
+        .set_output_file(output_video_file)
+        .set_overwrite_output_file(true);
+    ffmpeg_command.build().unwrap().spawn_with_progress(video_info.frame_count())?.wait().await?;
+
+    log::info!("overlay video converted successfully");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn default_output_file() {
+        let output_video_file = default_output_video_file(Path::new("dir/DJIG0000_osd.webm"), OverlayConversionCodec::Prores, None);
+        assert_eq!(output_video_file, Path::new("dir/DJIG0000_osd_prores.mov"));
+    }
+
+}
@@ -0,0 +1,189 @@
+
+//! Small graphs of the OSD item values over time drawn next to the OSD
+
+use getset::{CopyGetters, Getters, Setters};
+use image::Rgba;
+
+use crate::image::draw_line;
+use crate::osd::{self, FontVariant, file::Frame as OSDFileFrame, item_series::ItemSeries, tile_indices::UnknownOSDItem};
+use crate::telemetry::{self, AlignedTelemetry};
+use crate::video::FrameIndex as VideoFrameIndex;
+
+use super::{Frame, style::blend_over};
+
+
+/// duration of the history displayed by the graphs in OSD frames: 30s
+const HISTORY_FRAMES: VideoFrameIndex = 30 * osd::FRAME_RATE;
+
+const BACKGROUND_COLOR: Rgba<u8> = Rgba([0, 0, 0, 96]);
+
+/// graph dimensions relative to the overlay frame dimensions
+const WIDTH_RATIO: u32 = 5;
+const HEIGHT_RATIO: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphedItem {
+    Altitude,
+    Speed,
+    RSSI,
+    BatteryVoltage,
+}
+
+impl GraphedItem {
+
+    pub fn osd_item_name(&self) -> &'static str {
+        use GraphedItem::*;
+        match self {
+            Altitude => "alt",
+            Speed => "speed",
+            RSSI => "rssi",
+            BatteryVoltage => "bat",
+        }
+    }
+
+    /// name of the flight telemetry series with the values of the item
+    pub fn telemetry_series_name(&self) -> &'static str {
+        use GraphedItem::*;
+        match self {
+            Altitude => telemetry::ALTITUDE,
+            Speed => telemetry::SPEED,
+            RSSI => telemetry::RSSI,
+            BatteryVoltage => telemetry::BATTERY_VOLTAGE,
+        }
+    }
+
+    fn color(&self) -> Rgba<u8> {
+        use GraphedItem::*;
+        match self {
+            Altitude => Rgba([80, 170, 255, 255]),
+            Speed => Rgba([255, 200, 0, 255]),
+            RSSI => Rgba([120, 220, 120, 255]),
+            BatteryVoltage => Rgba([255, 110, 110, 255]),
+        }
+    }
+
+}
+
+/// corner of the overlay frame in which the graphs are stacked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphsPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Default for GraphsPosition {
+    fn default() -> Self {
+        Self::BottomRight
+    }
+}
+
+#[derive(Debug, Clone, Default, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct GraphsSettings {
+    items: Vec<GraphedItem>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    position: GraphsPosition,
+}
+
+/// Graphs of the OSD item values over the last 30 seconds, the values themselves are displayed by the OSD
+pub struct TelemetryGraphs {
+    graphs: Vec<(GraphedItem, ItemSeries)>,
+    position: GraphsPosition,
+}
+
+impl TelemetryGraphs {
+
+    /// extracts the values of the graphed items from the OSD file frames, the values of the items which are not
+    /// located for the font variant or which are never displayed are taken from the flight telemetry when available,
+    /// otherwise the items are skipped with a warning
+    pub fn new(frames: &[OSDFileFrame], font_variant: FontVariant, settings: &GraphsSettings, telemetry: Option<&AlignedTelemetry>)
+            -> Result<Self, UnknownOSDItem> {
+        let mut graphs = vec![];
+        for item in &settings.items {
+            let series = match font_variant.find_osd_item_location_data(item.osd_item_name()) {
+                Some(_) => ItemSeries::extract(frames, font_variant, item.osd_item_name())?,
+                None => ItemSeries::new(item.osd_item_name(), vec![]),
+            };
+            let series = match telemetry {
+                Some(telemetry) if series.is_empty() && telemetry.has_series(item.telemetry_series_name()) => {
+                    log::info!("graphing the `{}` OSD item from the flight telemetry", item.osd_item_name());
+                    ItemSeries::new(item.osd_item_name(), telemetry.frame_points(item.telemetry_series_name()))
+                },
+                _ => series,
+            };
+            if series.is_empty() {
+                log::warn!("the `{}` OSD item cannot be located with the `{font_variant}` font variant or is never displayed, not graphing it",
+                    item.osd_item_name());
+                continue;
+            }
+            graphs.push((*item, series));
+        }
+        Ok(Self { graphs, position: settings.position })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.graphs.is_empty()
+    }
+
+    /// draws the graph in the area given as (left, top, width, height)
+    fn draw_graph(frame: &mut Frame, area: (u32, u32, u32, u32), item: GraphedItem, series: &ItemSeries, frame_index: VideoFrameIndex) {
+        let (left, top, width, height) = area;
+        let image = frame.image_mut();
+        for y in top..top + height {
+            for x in left..left + width {
+                let pixel = image.get_pixel_mut(x, y);
+                *pixel = blend_over(BACKGROUND_COLOR, *pixel);
+            }
+        }
+
+        let (min, max) = series.range().unwrap();
+        let first_frame_index = frame_index.saturating_sub(HISTORY_FRAMES);
+        let to_image = |point_frame_index: VideoFrameIndex, value: f64| {
+            let x = left as f64 + (point_frame_index.max(first_frame_index) - first_frame_index) as f64 / HISTORY_FRAMES as f64 * (width - 1) as f64;
+            let y_ratio = if max > min { (value - min) / (max - min) } else { 0.5 };
+            let y = top as f64 + (1.0 - y_ratio) * (height - 1) as f64;
+            (x, y)
+        };
+
+        let points = series.points_between(first_frame_index, frame_index);
+        // the values are held until the next change so the graph is drawn as steps up to the current frame
+        let mut previous: Option<(f64, f64)> = None;
+        for (point_index, (point_frame_index, value)) in points.iter().enumerate() {
+            let point = to_image(*point_frame_index, *value);
+            if let Some(previous) = previous {
+                draw_line(image, previous, (point.0, previous.1), 1, item.color());
+                draw_line(image, (point.0, previous.1), point, 1, item.color());
+            }
+            if point_index == points.len() - 1 {
+                draw_line(image, point, to_image(frame_index, *value), 1, item.color());
+            }
+            previous = Some(point);
+        }
+    }
+
+    /// draws the graphs onto the overlay frame displayed at the video frame
+    pub fn draw(&self, frame: &mut Frame, frame_index: VideoFrameIndex) {
+        let dimensions = frame.dimensions();
+        let (width, height) = (dimensions.width / WIDTH_RATIO, dimensions.height / HEIGHT_RATIO);
+        let gap = height / 4;
+        if width < 2 || height < 2 { return }
+        let stack_height = self.graphs.len() as u32 * (height + gap);
+        for (graph_index, (item, series)) in self.graphs.iter().enumerate() {
+            let offset = gap + graph_index as u32 * (height + gap);
+            let left = match self.position {
+                GraphsPosition::TopLeft | GraphsPosition::BottomLeft => gap,
+                GraphsPosition::TopRight | GraphsPosition::BottomRight => dimensions.width - width - gap,
+            };
+            let top = match self.position {
+                GraphsPosition::TopLeft | GraphsPosition::TopRight => offset,
+                GraphsPosition::BottomLeft | GraphsPosition::BottomRight => (dimensions.height + offset).saturating_sub(stack_height + gap),
+            };
+            if top + height > dimensions.height { break }
+            Self::draw_graph(frame, (left, top, width, height), *item, series, frame_index);
+        }
+    }
+
+}
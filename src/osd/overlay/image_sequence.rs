@@ -0,0 +1,176 @@
+
+//! Overlay videos encoded from numbered sequences of transparent PNG images
+//!
+//! The images are the frames of the overlay video, the number at the end of their file name being the index of the
+//! video frame they are displayed from, e.g. the frames written by `generate-overlay-frames` or by other tools. The
+//! sequence does not need to be continuous: each image is displayed until the frame of the next one so the tools
+//! writing only the frames which changed are supported. The images are read by the FFMpeg concat demuxer from a list
+//! giving the duration of each image, the numbers may then start anywhere and have any number of digits.
+
+use std::{io::Error as IOError, path::{Path, PathBuf}};
+
+use derive_more::From;
+use path_absolutize::Absolutize;
+use thiserror::Error;
+
+use crate::{
+    ffmpeg,
+    file::{self, TouchError},
+    video::{container::{Container, ContainerError}, cut::{self, TempFiles}},
+};
+
+use super::{OverlayVideoEncoderSpeed, convert::OverlayConversionCodec};
+
+
+pub const DEFAULT_FRAME_RATE: u16 = 60;
+
+#[derive(Debug, Error, From)]
+pub enum ImageSequenceError {
+    #[error("input directory does not exist: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    InputDirDoesNotExist(PathBuf),
+    #[error("failed reading input directory: {0}")]
+    FailedReadingInputDir(IOError),
+    #[error("no numbered PNG image in directory: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    NoImage(PathBuf),
+    #[error("several images are numbered {number}: {} and {}", .first.to_string_lossy(), .second.to_string_lossy())]
+    #[from(ignore)]
+    DuplicateNumber { number: u64, first: PathBuf, second: PathBuf },
+    #[error("invalid frame rate: {0}")]
+    #[from(ignore)]
+    InvalidFrameRate(u16),
+    #[error("output video file exists: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    OutputVideoFileExists(PathBuf),
+    #[error("the {container} container cannot store {codec} video with transparency")]
+    #[from(ignore)]
+    UnsupportedContainer { container: Container, codec: OverlayConversionCodec },
+    #[error(transparent)]
+    ContainerError(ContainerError),
+    #[error("failed writing the images list: {0}")]
+    #[from(ignore)]
+    FailedWritingImagesList(IOError),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error(transparent)]
+    WriteToFileError(TouchError),
+}
+
+/// number at the end of the name of a PNG image, e.g. 42 for `frame_0042.png`
+fn image_number(file_name: &str) -> Option<u64> {
+    let stem = file_name.len().checked_sub(4)
+        .filter(|stem_len| file_name.is_char_boundary(*stem_len) && file_name[*stem_len..].eq_ignore_ascii_case(".png"))
+        .map(|stem_len| &file_name[..stem_len])?;
+    let digits = &stem[stem.trim_end_matches(|c: char| c.is_ascii_digit()).len()..];
+    digits.parse().ok()
+}
+
+/// numbered PNG images of the directory sorted by number, the other files are ignored
+pub fn find_images(dir: &Path) -> Result<Vec<(u64, PathBuf)>, ImageSequenceError> {
+    if ! dir.is_dir() { return Err(ImageSequenceError::InputDirDoesNotExist(dir.to_path_buf())) }
+    let mut images = vec![];
+    for entry in fs_err::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(number) = path.file_name().and_then(|file_name| file_name.to_str()).and_then(image_number) {
+            images.push((number, path));
+        }
+    }
+    images.sort_unstable();
+    if let Some(duplicates) = images.windows(2).find(|images| images[0].0 == images[1].0) {
+        return Err(ImageSequenceError::DuplicateNumber { number: duplicates[0].0, first: duplicates[0].1.clone(), second: duplicates[1].1.clone() })
+    }
+    if images.is_empty() { return Err(ImageSequenceError::NoImage(dir.to_path_buf())) }
+    Ok(images)
+}
+
+/// Concat demuxer list of the images, each image lasting until the number of the next one
+///
+/// The last image is listed twice since the demuxer ignores the duration of the last entry.
+fn images_list(images: &[(u64, PathBuf)], frame_rate: u16) -> String {
+    let entry = |path: &Path| format!("file '{}'\n", path.absolutize().unwrap().to_string_lossy().replace('\'', r"'\''"));
+    let mut list = "ffconcat version 1.0\n".to_owned();
+    for (index, (number, path)) in images.iter().enumerate() {
+        let frame_count = images.get(index + 1).map(|(next_number, _)| next_number - number).unwrap_or(1);
+        list.push_str(&entry(path));
+        list.push_str(&format!("duration {:.6}\n", frame_count as f64 / frame_rate as f64));
+    }
+    if let Some((_, last_path)) = images.last() {
+        list.push_str(&entry(last_path));
+    }
+    list
+}
+
+/// output file used when not provided: the directory with the extension of the container
+pub fn default_output_video_file(input_dir: &Path, codec: OverlayConversionCodec, container: Option<Container>) -> PathBuf {
+    let input_dir = input_dir.absolutize().map(|dir| dir.to_path_buf()).unwrap_or_else(|_| input_dir.to_path_buf());
+    input_dir.with_extension(container.unwrap_or_else(|| codec.default_container()).extension())
+}
+
+/// Encodes the numbered PNG images of the directory into an overlay video with the codec keeping the alpha channel,
+/// the first frame of the video being the image with the lowest number
+pub async fn encode(input_dir: &Path, output_video_file: &Path, codec: OverlayConversionCodec, encoder_speed: &OverlayVideoEncoderSpeed,
+        frame_rate: u16, container: Option<Container>, overwrite: bool) -> Result<(), ImageSequenceError> {
+
+    if frame_rate == 0 { return Err(ImageSequenceError::InvalidFrameRate(frame_rate)) }
+    if ! overwrite && output_video_file.exists() { return Err(ImageSequenceError::OutputVideoFileExists(output_video_file.to_path_buf())) }
+    if let Some(container) = container {
+        container.check_output_file(output_video_file)?;
+    }
+    let container = container.or_else(|| Container::from_extension(output_video_file)).unwrap_or_else(|| codec.default_container());
+    if ! codec.supports_container(container) { return Err(ImageSequenceError::UnsupportedContainer { container, codec }) }
+
+    let images = find_images(input_dir)?;
+    let (first_number, last_number) = (images[0].0, images[images.len() - 1].0);
+    let frame_count = last_number - first_number + 1;
+
+    file::touch(output_video_file)?;
+
+    log::info!("encoding {} images numbered {first_number} to {last_number} into {frame_count} frames of {codec} overlay video: {} -> {}",
+        images.len(), input_dir.to_string_lossy(), output_video_file.to_string_lossy());
+
+    let temp_files = TempFiles(vec![cut::temp_file_path(output_video_file, ".images.ffconcat")]);
+    let list_file = &temp_files.0[0];
+    if ! crate::process::dry_run() {
+        fs_err::write(list_file, images_list(&images, frame_rate)).map_err(ImageSequenceError::FailedWritingImagesList)?;
+    }
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    ffmpeg_command
+        .add_concat_list_input(list_file)
+        .add_mapping("0:v:0")
+        .add_args(&["-r", &frame_rate.to_string()]);
+    codec.set_encoder_settings(&mut ffmpeg_command, encoder_speed);
+    ffmpeg_command
+        .add_args(&["-frames:v", &frame_count.to_string(), "-f", container.ffmpeg_format()])
+        .set_output_file(output_video_file)
+        .set_overwrite_output_file(true);
+    ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
+
+    log::info!("overlay video encoded successfully");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn numbered_images() {
+        assert_eq!(image_number("0000000042.png"), Some(42));
+        assert_eq!(image_number("frame_7.PNG"), Some(7));
+        assert_eq!(image_number("frame.png"), None);
+        assert_eq!(image_number("0001.jpg"), None);
+    }
+
+    #[test]
+    fn sparse_images_list() {
+        let images = [(10, PathBuf::from("/frames/a10.png")), (13, PathBuf::from("/frames/a13.png"))];
+        assert_eq!(images_list(&images, 60),
+            "ffconcat version 1.0\nfile '/frames/a10.png'\nduration 0.050000\nfile '/frames/a13.png'\nduration 0.016667\nfile '/frames/a13.png'\n");
+    }
+
+}
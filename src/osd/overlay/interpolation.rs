@@ -0,0 +1,232 @@
+
+//! OSD frames mapped onto the video frame rate, optionally blended between the OSD updates
+
+use std::collections::VecDeque;
+
+use image::Rgba;
+
+use crate::{ffmpeg, osd};
+
+use super::{Frame, FramesIter, FramesIterError, SendFramesToFFMpegError, send_frames_to_ffmpeg};
+
+
+/// OSD updates further apart are not blended, the OSD is considered static in between
+const MAX_BLENDED_UPDATE_INTERVAL: u32 = 15;
+
+/// position in OSD frames of the video frame with the index in a video with the frame rate, the OSD frame indices
+/// being the indices of the frames of a video with the OSD frame rate
+pub fn osd_position(video_frame_index: u64, video_frame_rate: f64) -> f64 {
+    video_frame_index as f64 * osd::FRAME_RATE as f64 / video_frame_rate
+}
+
+/// index of the OSD frame displayed with the video frame with the index in a video with the frame rate
+pub fn osd_frame_index(video_frame_index: u64, video_frame_rate: f64) -> u32 {
+    osd_position(video_frame_index, video_frame_rate).floor() as u32
+}
+
+/// index of the first video frame displaying the OSD frame with the index, in a video with the frame rate
+pub fn first_video_frame_index(osd_frame_index: u32, video_frame_rate: f64) -> u64 {
+    let mut video_frame_index = (osd_frame_index as f64 * video_frame_rate / osd::FRAME_RATE as f64).ceil() as u64;
+    // corrects the floating point rounding errors
+    while video_frame_index > 0 && self::osd_frame_index(video_frame_index - 1, video_frame_rate) >= osd_frame_index {
+        video_frame_index -= 1;
+    }
+    while self::osd_frame_index(video_frame_index, video_frame_rate) < osd_frame_index {
+        video_frame_index += 1;
+    }
+    video_frame_index
+}
+
+fn blend_pixel(from: Rgba<u8>, to: Rgba<u8>, ratio: f32) -> Rgba<u8> {
+    let (from_alpha, to_alpha) = (from[3] as f32 / 255.0, to[3] as f32 / 255.0);
+    let alpha = from_alpha * (1.0 - ratio) + to_alpha * ratio;
+    if alpha == 0.0 { return Rgba([0, 0, 0, 0]) }
+    let channel = |index: usize| {
+        let value = (from[index] as f32 * from_alpha * (1.0 - ratio) + to[index] as f32 * to_alpha * ratio) / alpha;
+        value.round().clamp(0.0, 255.0) as u8
+    };
+    Rgba([channel(0), channel(1), channel(2), (alpha * 255.0).round() as u8])
+}
+
+impl Frame {
+
+    /// frame blended between this frame and the other frame, `ratio` being the weight of the other frame
+    pub fn blend(&self, other: &Frame, ratio: f32) -> Frame {
+        let mut frame = self.clone();
+        for (pixel, other_pixel) in frame.image_mut().pixels_mut().zip(other.pixels()) {
+            *pixel = blend_pixel(*pixel, *other_pixel, ratio);
+        }
+        frame
+    }
+
+}
+
+/// Iterator over the OSD frames to overlay onto each frame of a video with the given frame rate
+///
+/// Without blending each OSD frame is held until the next OSD update. With blending the frames between two OSD
+/// updates progressively transition from the first update to the next one which smoothes the moving elements like
+/// the crosshair or the artificial horizon.
+pub struct InterpolatedFramesIter<'a> {
+    frames_iter: FramesIter<'a>,
+    output_frame_rate: u16,
+    blend: bool,
+    output_index: u64,
+    /// OSD frames read ahead: relative index, frame and whether the frame is an OSD update
+    read_ahead: VecDeque<(u32, Frame, bool)>,
+    next_read_index: u32,
+    frames_iter_ended: bool,
+    /// last OSD update before the current frame with its relative index
+    current_update: Option<(u32, Frame)>,
+}
+
+impl<'a> InterpolatedFramesIter<'a> {
+
+    pub fn new(frames_iter: FramesIter<'a>, output_frame_rate: u16, blend: bool) -> Self {
+        Self {
+            frames_iter,
+            output_frame_rate: output_frame_rate.max(1),
+            blend,
+            output_index: 0,
+            read_ahead: VecDeque::new(),
+            next_read_index: 0,
+            frames_iter_ended: false,
+            current_update: None,
+        }
+    }
+
+    /// frame rate of the OSD frames to send to FFMpeg: the video frame rate when blending so that there is a
+    /// frame for each video frame, otherwise the OSD frame rate since the held frames do not need to be repeated
+    pub fn frame_rate_for_video(video_frame_rate: f64, blend: bool) -> u16 {
+        if blend { (video_frame_rate.round() as u16).max(osd::FRAME_RATE as u16) } else { osd::FRAME_RATE as u16 }
+    }
+
+    pub fn output_frame_rate(&self) -> u16 {
+        self.output_frame_rate
+    }
+
+    /// reads the OSD frames until the frame with the relative index is read ahead, returns false when there are
+    /// not enough frames
+    fn read_until(&mut self, index: u32) -> Result<bool, FramesIterError> {
+        while self.next_read_index <= index {
+            if self.frames_iter_ended { return Ok(false) }
+            match self.frames_iter.next_with_update_flag() {
+                Some(result) => {
+                    let (frame, is_update) = result?;
+                    self.read_ahead.push_back((self.next_read_index, frame, is_update));
+                    self.next_read_index += 1;
+                },
+                None => {
+                    self.frames_iter_ended = true;
+                    return Ok(false);
+                },
+            }
+        }
+        Ok(true)
+    }
+
+    /// relative index and position in the read ahead frames of the next OSD update after the relative index
+    /// up to the last index
+    fn next_update_between(&mut self, index: u32, last_index: u32) -> Result<Option<(u32, usize)>, FramesIterError> {
+        for update_index in index + 1..=last_index {
+            if ! self.read_until(update_index)? { return Ok(None) }
+            let position = self.read_ahead.iter().position(|(frame_index, _, _)| *frame_index == update_index).unwrap();
+            if self.read_ahead[position].2 {
+                return Ok(Some((update_index, position)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, FramesIterError> {
+        // position of the video frame in OSD frames
+        let osd_position = osd_position(self.output_index, self.output_frame_rate as f64);
+        let index = osd_position.floor() as u32;
+        if ! self.read_until(index)? { return Ok(None) }
+
+        while let Some((frame_index, _, _)) = self.read_ahead.front() {
+            if *frame_index > index { break }
+            let (frame_index, frame, is_update) = self.read_ahead.pop_front().unwrap();
+            if is_update || self.current_update.is_none() {
+                self.current_update = Some((frame_index, frame));
+            }
+        }
+        self.output_index += 1;
+
+        let (update_index, update_frame) = self.current_update.clone().unwrap();
+        if ! self.blend { return Ok(Some(update_frame)) }
+        match self.next_update_between(index, update_index + MAX_BLENDED_UPDATE_INTERVAL)? {
+            Some((next_update_index, position)) => {
+                let ratio = (osd_position - update_index as f64) / (next_update_index - update_index) as f64;
+                Ok(Some(update_frame.blend(&self.read_ahead[position].1, ratio as f32)))
+            },
+            None => Ok(Some(update_frame)),
+        }
+    }
+
+    pub fn send_frames_to_ffmpeg(self, ffmpeg_process: &mut ffmpeg::Process) -> Result<(), SendFramesToFFMpegError> {
+        send_frames_to_ffmpeg(self, ffmpeg_process)
+    }
+
+    pub async fn send_frames_to_ffmpeg_and_wait(self, mut ffmpeg_process: ffmpeg::Process) -> Result<(), SendFramesToFFMpegError> {
+        let send_result = send_frames_to_ffmpeg(self, &mut ffmpeg_process);
+
+        ffmpeg_process.wait().await?;
+        send_result?;
+
+        Ok(())
+    }
+
+}
+
+impl<'a> Iterator for InterpolatedFramesIter<'a> {
+    type Item = Result<Frame, FramesIterError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_frame().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn osd_frame_index_for_video_frame_rates() {
+        assert_eq!((0..4).map(|index| osd_frame_index(index, 60.0)).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!((0..4).map(|index| osd_frame_index(index, 30.0)).collect::<Vec<_>>(), [0, 2, 4, 6]);
+        assert_eq!((0..4).map(|index| osd_frame_index(index, 120.0)).collect::<Vec<_>>(), [0, 0, 1, 1]);
+        // 29.97 FPS video frames drift behind the 30 FPS ones
+        assert!((osd_position(30_000, 30_000.0 / 1001.0) - 60_060.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn first_video_frame_of_osd_frames() {
+        assert_eq!((0..5).map(|index| first_video_frame_index(index, 30.0)).collect::<Vec<_>>(), [0, 1, 1, 2, 2]);
+        assert_eq!((0..3).map(|index| first_video_frame_index(index, 120.0)).collect::<Vec<_>>(), [0, 2, 4]);
+        let frame_rate = 30_000.0 / 1001.0;
+        for index in 0..10_000 {
+            let video_frame_index = first_video_frame_index(index, frame_rate);
+            assert!(osd_frame_index(video_frame_index, frame_rate) >= index);
+            assert!(video_frame_index == 0 || osd_frame_index(video_frame_index - 1, frame_rate) < index);
+        }
+    }
+
+    #[test]
+    fn osd_position_between_osd_frames() {
+        assert_eq!(osd_position(1, 120.0), 0.5);
+        assert_eq!(osd_position(5, 50.0), 6.0);
+    }
+
+    #[test]
+    fn blend_pixels() {
+        let (from, to) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        assert_eq!(blend_pixel(from, to, 0.0), from);
+        assert_eq!(blend_pixel(from, to, 1.0), to);
+        assert_eq!(blend_pixel(from, to, 0.5), Rgba([128, 0, 128, 255]));
+        // the color of a transparent pixel does not bleed into the blended pixel
+        assert_eq!(blend_pixel(Rgba([0, 0, 0, 0]), to, 0.5), Rgba([0, 0, 255, 128]));
+        assert_eq!(blend_pixel(Rgba([0, 0, 0, 0]), Rgba([0, 0, 0, 0]), 0.5), Rgba([0, 0, 0, 0]));
+    }
+
+}
@@ -0,0 +1,211 @@
+
+//! Layers drawn with the OSD tiles onto the overlay frames: telemetry graphs, sticks and script widgets
+//!
+//! The layers are drawn in z-order with the OSD tiles, whose z-order is the one of the `osd` layer, each with an
+//! opacity and a blend mode, see [`crate::video::layers`]. A layer which is not opaque or not blended with the normal
+//! mode is drawn onto a transparent frame which is then composited onto the frame below it.
+
+use image::Rgba;
+
+use crate::telemetry::AlignedTelemetry;
+use crate::video::{FrameIndex as VideoFrameIndex, layers::{BlendMode, LayerConfig, LayerKind, LayerStyle, layer_style}};
+
+use super::{Frame, graphs::TelemetryGraphs, script::ScriptLayer, sticks::StickOverlay, style::blend_over};
+
+
+pub trait OverlayLayer: Send + Sync {
+    fn kind(&self) -> LayerKind;
+
+    /// whether the layer changes between the OSD updates, the overlay frames are then drawn for every video frame
+    fn is_animated(&self) -> bool;
+
+    /// draws the layer onto the overlay frame displayed at the OSD frame index
+    fn draw(&self, frame: &mut Frame, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>);
+}
+
+impl OverlayLayer for TelemetryGraphs {
+    fn kind(&self) -> LayerKind { LayerKind::Graphs }
+
+    fn is_animated(&self) -> bool { false }
+
+    fn draw(&self, frame: &mut Frame, osd_frame_index: i32, _telemetry: Option<&AlignedTelemetry>) {
+        TelemetryGraphs::draw(self, frame, osd_frame_index.max(0) as VideoFrameIndex);
+    }
+}
+
+impl OverlayLayer for StickOverlay {
+    fn kind(&self) -> LayerKind { LayerKind::Sticks }
+
+    fn is_animated(&self) -> bool { true }
+
+    fn draw(&self, frame: &mut Frame, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>) {
+        if let Some(telemetry) = telemetry {
+            StickOverlay::draw(self, frame, telemetry, osd_frame_index);
+        }
+    }
+}
+
+impl OverlayLayer for ScriptLayer {
+    fn kind(&self) -> LayerKind { LayerKind::Script }
+
+    fn is_animated(&self) -> bool { true }
+
+    fn draw(&self, frame: &mut Frame, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>) {
+        ScriptLayer::draw(self, frame, osd_frame_index, telemetry);
+    }
+}
+
+/// Layers of the overlay frames sorted by z-order, the layers with the same z-order are drawn in the order they were
+/// added
+#[derive(Default)]
+pub struct OverlayLayers {
+    layers: Vec<(Box<dyn OverlayLayer>, LayerStyle)>,
+    configs: Vec<LayerConfig>,
+}
+
+impl OverlayLayers {
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.layers.iter().any(|(layer, _)| layer.is_animated())
+    }
+
+    /// z-order of the OSD tiles
+    fn tiles_z_order(&self) -> i32 {
+        layer_style(LayerKind::Osd, &self.configs).z_order()
+    }
+
+    /// styles the layers with the configurations, including the layers added afterwards
+    pub fn set_configs(&mut self, configs: &[LayerConfig]) {
+        self.configs = configs.to_vec();
+        for (layer, style) in &mut self.layers {
+            *style = layer_style(layer.kind(), &self.configs);
+        }
+        self.layers.sort_by_key(|(_, style)| style.z_order());
+    }
+
+    /// adds the layer with the style of its kind resulting from the configurations
+    pub fn add(&mut self, layer: Box<dyn OverlayLayer>) {
+        let style = layer_style(layer.kind(), &self.configs);
+        let index = self.layers.partition_point(|(_, other_style)| other_style.z_order() <= style.z_order());
+        self.layers.insert(index, (layer, style));
+    }
+
+    pub fn remove(&mut self, kind: LayerKind) {
+        self.layers.retain(|(layer, _)| layer.kind() != kind);
+    }
+
+    /// overlay frame with the layers drawn in z-order with the frame of the OSD tiles
+    pub fn draw(&self, tiles: Frame, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>) -> Frame {
+        let tiles_z_order = self.tiles_z_order();
+        let below_count = self.layers.partition_point(|(_, style)| style.z_order() < tiles_z_order);
+        let (below, above) = self.layers.split_at(below_count);
+        let mut frame = if below.is_empty() {
+            tiles
+        } else {
+            let mut frame = Frame::new(tiles.dimensions());
+            for (layer, style) in below {
+                Self::draw_layer(&mut frame, layer.as_ref(), *style, osd_frame_index, telemetry);
+            }
+            composite(&mut frame, &tiles, BlendMode::Normal, 1.0);
+            frame
+        };
+        for (layer, style) in above {
+            Self::draw_layer(&mut frame, layer.as_ref(), *style, osd_frame_index, telemetry);
+        }
+        frame
+    }
+
+    fn draw_layer(frame: &mut Frame, layer: &dyn OverlayLayer, style: LayerStyle, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>) {
+        if style.opacity() <= 0.0 { return }
+        if style.opacity() >= 1.0 && style.blend_mode() == BlendMode::Normal {
+            layer.draw(frame, osd_frame_index, telemetry);
+        } else {
+            let mut layer_frame = Frame::new(frame.dimensions());
+            layer.draw(&mut layer_frame, osd_frame_index, telemetry);
+            composite(frame, &layer_frame, style.blend_mode(), style.opacity());
+        }
+    }
+
+}
+
+/// composites the layer frame onto the frame with the blend mode and opacity
+fn composite(frame: &mut Frame, layer_frame: &Frame, blend_mode: BlendMode, opacity: f64) {
+    for (pixel, layer_pixel) in frame.image_mut().pixels_mut().zip(layer_frame.pixels()) {
+        if layer_pixel.0[3] == 0 { continue }
+        *pixel = blend_pixel(*layer_pixel, *pixel, blend_mode, opacity);
+    }
+}
+
+/// pixel of a layer blended onto the backdrop pixel, the blended color only applies where the backdrop is opaque and
+/// the result is drawn over the backdrop like the W3C compositing does
+fn blend_pixel(pixel: Rgba<u8>, backdrop: Rgba<u8>, blend_mode: BlendMode, opacity: f64) -> Rgba<u8> {
+    let backdrop_alpha = backdrop.0[3] as f64 / 255.0;
+    let component = |index: usize| {
+        let (source, backdrop) = (pixel.0[index] as f64 / 255.0, backdrop.0[index] as f64 / 255.0);
+        (((1.0 - backdrop_alpha) * source + backdrop_alpha * blend_mode.blend(backdrop, source)) * 255.0).round() as u8
+    };
+    let alpha = (pixel.0[3] as f64 * opacity).round() as u8;
+    blend_over(Rgba([component(0), component(1), component(2), alpha]), backdrop)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use super::super::Dimensions;
+
+    /// layer filling the frame with a color
+    struct FillLayer(LayerKind, Rgba<u8>);
+
+    impl OverlayLayer for FillLayer {
+        fn kind(&self) -> LayerKind { self.0 }
+        fn is_animated(&self) -> bool { false }
+        fn draw(&self, frame: &mut Frame, _osd_frame_index: i32, _telemetry: Option<&AlignedTelemetry>) {
+            for pixel in frame.image_mut().pixels_mut() {
+                *pixel = blend_over(self.1, *pixel);
+            }
+        }
+    }
+
+    fn tiles() -> Frame {
+        let mut tiles = Frame::new(Dimensions::new(2, 1));
+        tiles.image_mut().put_pixel(0, 0, Rgba([200, 100, 0, 255]));
+        tiles
+    }
+
+    #[test]
+    fn pixels_blended_onto_the_backdrop() {
+        let backdrop = Rgba([200, 100, 0, 255]);
+        assert_eq!(blend_pixel(Rgba([100, 100, 100, 255]), backdrop, BlendMode::Normal, 1.0), Rgba([100, 100, 100, 255]));
+        assert_eq!(blend_pixel(Rgba([100, 100, 100, 255]), backdrop, BlendMode::Darken, 1.0), Rgba([100, 100, 0, 255]));
+        assert_eq!(blend_pixel(Rgba([255, 255, 255, 255]), backdrop, BlendMode::Difference, 1.0), Rgba([55, 155, 255, 255]));
+        assert_eq!(blend_pixel(Rgba([0, 0, 0, 255]), backdrop, BlendMode::Normal, 0.5), Rgba([100, 50, 0, 255]));
+        // nothing to blend with on a transparent backdrop
+        assert_eq!(blend_pixel(Rgba([100, 100, 100, 255]), Rgba([0, 0, 0, 0]), BlendMode::Darken, 1.0), Rgba([100, 100, 100, 255]));
+    }
+
+    #[test]
+    fn layers_drawn_in_z_order_with_the_tiles() {
+        let mut layers = OverlayLayers::default();
+        layers.add(Box::new(FillLayer(LayerKind::Sticks, Rgba([0, 0, 255, 255]))));
+        assert_eq!(*layers.draw(tiles(), 0, None).get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+
+        // below the tiles the layer only shows where the tiles are transparent
+        layers.set_configs(&["sticks|z=-1".parse().unwrap()]);
+        let frame = layers.draw(tiles(), 0, None);
+        assert_eq!(*frame.get_pixel(0, 0), Rgba([200, 100, 0, 255]));
+        assert_eq!(*frame.get_pixel(1, 0), Rgba([0, 0, 255, 255]));
+
+        layers.set_configs(&["sticks|blend=lighten".parse().unwrap()]);
+        assert_eq!(*layers.draw(tiles(), 0, None).get_pixel(0, 0), Rgba([200, 100, 255, 255]));
+
+        layers.remove(LayerKind::Sticks);
+        assert!(layers.is_empty());
+        assert_eq!(*layers.draw(tiles(), 0, None).get_pixel(1, 0), Rgba([0, 0, 0, 0]));
+    }
+
+}
@@ -18,6 +18,12 @@ pub struct Margins {
     vertical: u32,
 }
 
+impl Margins {
+    pub fn new(horizontal: u32, vertical: u32) -> Self {
+        Self { horizontal, vertical }
+    }
+}
+
 impl FromStr for Margins {
     type Err = InvalidMarginsFormatError;
 
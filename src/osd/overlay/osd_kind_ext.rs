@@ -40,6 +40,20 @@ impl osd::Kind {
         }
     }
 
+    /// Returns the kind of tiles which scaled by the highest integer factor (at least 2) gives the biggest OSD fitting
+    /// in the maximum resolution, integer factors keep the tiles sharp
+    pub fn best_kind_of_tiles_to_use_with_integer_scaling(&self, max_resolution: OverlayFrameDimensions) -> Option<(tile::Kind, u32, tile::Dimensions, OverlayFrameDimensions)> {
+        tile::Kind::iter().filter_map(|tile_kind| {
+            let osd_dimensions = self.dimensions_pixels_for_tile_kind(tile_kind);
+            let factor = std::cmp::min(max_resolution.width / osd_dimensions.width, max_resolution.height / osd_dimensions.height);
+            if factor < 2 { return None }
+            let mut tile_dimensions = tile_kind.dimensions();
+            tile_dimensions.width *= factor;
+            tile_dimensions.height *= factor;
+            Some((tile_kind, factor, tile_dimensions, self.dimensions_pixels_for_tile_dimensions(tile_dimensions)))
+        }).max_by_key(|(_, _, _, overlay_dimensions)| overlay_dimensions.width * overlay_dimensions.height)
+    }
+
     pub fn best_kind_of_tiles_to_use_with_scaling(&self, max_resolution: OverlayFrameDimensions) -> (tile::Kind, tile::Dimensions, OverlayFrameDimensions) {
         let max_tile_width = max_resolution.width / self.dimensions_tiles().width;
         let max_tile_height = max_resolution.height / self.dimensions_tiles().height;
@@ -0,0 +1,321 @@
+
+use std::{
+    collections::HashMap,
+    io::{Error as IOError, Write},
+};
+
+use derive_more::From;
+use thiserror::Error;
+
+use crate::video::resolution::Resolution as VideoResolution;
+
+use super::{Frame, FramesIter, FramesIterError, interpolation::first_video_frame_index};
+use crate::osd::{file::ReadError, tile_indices::UnknownOSDItem};
+
+
+/// PGS timestamps are expressed in 90kHz clock ticks
+const PTS_CLOCK_FREQUENCY: f64 = 90_000.0;
+
+/// maximum payload size of a PGS segment
+const MAX_SEGMENT_PAYLOAD_SIZE: usize = u16::MAX as usize;
+
+/// palette entry used for the transparent pixels, the RLE encoding has a compact form for runs of this entry
+const TRANSPARENT_PALETTE_ENTRY: u8 = 0;
+
+const SEGMENT_TYPE_PDS: u8 = 0x14;
+const SEGMENT_TYPE_ODS: u8 = 0x15;
+const SEGMENT_TYPE_PCS: u8 = 0x16;
+const SEGMENT_TYPE_WDS: u8 = 0x17;
+const SEGMENT_TYPE_END: u8 = 0x80;
+
+#[derive(Debug, Error, From)]
+pub enum WritePGSError {
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+    #[error("OSD file read error: {0}")]
+    ReadError(ReadError),
+    #[error("failed writing PGS subtitles: {0}")]
+    WriteError(IOError),
+}
+
+impl From<FramesIterError> for WritePGSError {
+    fn from(error: FramesIterError) -> Self {
+        match error {
+            FramesIterError::UnknownOSDItem(error) => Self::UnknownOSDItem(error),
+            FramesIterError::ReadError(error) => Self::ReadError(error),
+        }
+    }
+}
+
+/// region of the frame containing the visible pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BoundingBox {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+fn visible_bounding_box(frame: &Frame) -> Option<BoundingBox> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in frame.enumerate_pixels() {
+        if pixel.0[3] == 0 { continue }
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    if min_x > max_x { return None }
+    Some(BoundingBox { x: min_x, y: min_y, width: max_x - min_x + 1, height: max_y - min_y + 1 })
+}
+
+/// RGBA palette of at most 255 colors and the palette indices of the pixels of the bounding box
+///
+/// Colors are quantized with less and less bits until they fit in the palette, OSD frames usually contain
+/// few colors so the quantization is rarely needed.
+fn indexed_image(frame: &Frame, bounding_box: BoundingBox) -> (Vec<[u8; 4]>, Vec<u8>) {
+    'quantization: for dropped_bits in 0..8 {
+        let mut palette = vec![[0, 0, 0, 0]];
+        let mut palette_indices = HashMap::new();
+        let mut indices = Vec::with_capacity((bounding_box.width * bounding_box.height) as usize);
+        for y in bounding_box.y..bounding_box.y + bounding_box.height {
+            for x in bounding_box.x..bounding_box.x + bounding_box.width {
+                let pixel = frame.get_pixel(x, y).0;
+                if pixel[3] == 0 {
+                    indices.push(TRANSPARENT_PALETTE_ENTRY);
+                    continue;
+                }
+                let key = pixel.map(|component| component >> dropped_bits);
+                let index = match palette_indices.get(&key) {
+                    Some(&index) => index,
+                    None => {
+                        if palette.len() > u8::MAX as usize { continue 'quantization }
+                        let index = palette.len() as u8;
+                        let half_step = (1u8 << dropped_bits) >> 1;
+                        palette.push(key.map(|component| (component << dropped_bits) | half_step));
+                        palette_indices.insert(key, index);
+                        index
+                    },
+                };
+                indices.push(index);
+            }
+        }
+        return (palette, indices);
+    }
+    unreachable!("a palette with one bit per component always fits")
+}
+
+/// converts the RGB color to the BT.709 Y, Cr, Cb components used by the PGS palettes
+fn rgb_to_ycrcb([red, green, blue]: [u8; 3]) -> [u8; 3] {
+    let (red, green, blue) = (red as f64, green as f64, blue as f64);
+    let y = 16.0 + 0.1826 * red + 0.6142 * green + 0.0620 * blue;
+    let cr = 128.0 + 0.4392 * red - 0.3989 * green - 0.0403 * blue;
+    let cb = 128.0 - 0.1006 * red - 0.3386 * green + 0.4392 * blue;
+    [y, cr, cb].map(|component| component.round().clamp(0.0, 255.0) as u8)
+}
+
+/// timestamp of the video frame with the index in a video with the frame rate
+fn video_frame_pts(video_frame_index: u32, video_frame_rate: f64) -> u32 {
+    (video_frame_index as f64 * PTS_CLOCK_FREQUENCY / video_frame_rate).round() as u32
+}
+
+/// run length encodes the palette indices line by line as specified for the PGS object data
+fn rle_encode(indices: &[u8], width: usize) -> Vec<u8> {
+    let mut data = vec![];
+    for line in indices.chunks(width) {
+        let mut position = 0;
+        while position < line.len() {
+            let color = line[position];
+            let run_length = line[position..].iter().take(0x3fff).take_while(|&&index| index == color).count();
+            match (color, run_length) {
+                (TRANSPARENT_PALETTE_ENTRY, 1..=0x3f) => data.extend([0, run_length as u8]),
+                (TRANSPARENT_PALETTE_ENTRY, _) => data.extend([0, 0x40 | (run_length >> 8) as u8, run_length as u8]),
+                (_, 1..=2) => data.extend(std::iter::repeat(color).take(run_length)),
+                (_, 3..=0x3f) => data.extend([0, 0x80 | run_length as u8, color]),
+                (_, _) => data.extend([0, 0xc0 | (run_length >> 8) as u8, run_length as u8, color]),
+            }
+            position += run_length;
+        }
+        data.extend([0, 0]);
+    }
+    data
+}
+
+/// Writes the OSD frames as a PGS (Blu-ray) image subtitle stream
+///
+/// A display set is written each time the OSD frame changes. Each display set starts a new epoch so that players
+/// can start displaying the subtitles from any display set when seeking.
+pub struct PGSWriter<W: Write> {
+    output: W,
+    video_resolution: VideoResolution,
+    video_frame_rate: f64,
+    /// position of the OSD frame in the video
+    frame_offset: (u32, u32),
+    composition_number: u16,
+}
+
+impl<W: Write> PGSWriter<W> {
+
+    /// the OSD frames are centered in a video of the specified resolution, the timestamps are the ones of the frames
+    /// of a video with the specified frame rate
+    pub fn new(output: W, video_resolution: VideoResolution, video_frame_rate: f64, frame_dimensions: super::Dimensions) -> Self {
+        let frame_offset = (
+            video_resolution.width.saturating_sub(frame_dimensions.width) / 2,
+            video_resolution.height.saturating_sub(frame_dimensions.height) / 2,
+        );
+        Self { output, video_resolution, video_frame_rate, frame_offset, composition_number: 0 }
+    }
+
+    fn write_segment(&mut self, pts: u32, segment_type: u8, payload: &[u8]) -> Result<(), IOError> {
+        self.output.write_all(b"PG")?;
+        self.output.write_all(&pts.to_be_bytes())?;
+        self.output.write_all(&0u32.to_be_bytes())?;
+        self.output.write_all(&[segment_type])?;
+        self.output.write_all(&(payload.len() as u16).to_be_bytes())?;
+        self.output.write_all(payload)
+    }
+
+    /// writes the presentation composition segment with the position of the object if any
+    fn write_pcs(&mut self, pts: u32, object_position: Option<(u32, u32)>) -> Result<(), IOError> {
+        let mut payload = vec![];
+        payload.extend((self.video_resolution.width as u16).to_be_bytes());
+        payload.extend((self.video_resolution.height as u16).to_be_bytes());
+        payload.push(0x10);
+        payload.extend(self.composition_number.to_be_bytes());
+        // epoch start, no palette update, palette 0
+        payload.extend([0x80, 0, 0]);
+        match object_position {
+            Some((x, y)) => {
+                // object 0 in window 0 without cropping
+                payload.extend([1, 0, 0, 0, 0]);
+                payload.extend((x as u16).to_be_bytes());
+                payload.extend((y as u16).to_be_bytes());
+            },
+            None => payload.push(0),
+        }
+        self.composition_number = self.composition_number.wrapping_add(1);
+        self.write_segment(pts, SEGMENT_TYPE_PCS, &payload)
+    }
+
+    fn write_wds(&mut self, pts: u32, x: u32, y: u32, width: u32, height: u32) -> Result<(), IOError> {
+        let mut payload = vec![1, 0];
+        for value in [x, y, width, height] {
+            payload.extend((value as u16).to_be_bytes());
+        }
+        self.write_segment(pts, SEGMENT_TYPE_WDS, &payload)
+    }
+
+    fn write_pds(&mut self, pts: u32, palette: &[[u8; 4]]) -> Result<(), IOError> {
+        let mut payload = vec![0, 0];
+        for (index, [red, green, blue, alpha]) in palette.iter().copied().enumerate() {
+            payload.push(index as u8);
+            payload.extend(rgb_to_ycrcb([red, green, blue]));
+            payload.push(alpha);
+        }
+        self.write_segment(pts, SEGMENT_TYPE_PDS, &payload)
+    }
+
+    /// writes the object definition segments, the object data is split into several segments if it is too large
+    fn write_ods(&mut self, pts: u32, width: u32, height: u32, rle_data: &[u8]) -> Result<(), IOError> {
+        let mut object_data = vec![];
+        object_data.extend(((rle_data.len() + 4) as u32).to_be_bytes()[1..].iter());
+        object_data.extend((width as u16).to_be_bytes());
+        object_data.extend((height as u16).to_be_bytes());
+        object_data.extend(rle_data);
+
+        // object id, version and sequence flag
+        const HEADER_SIZE: usize = 4;
+        let fragments = object_data.chunks(MAX_SEGMENT_PAYLOAD_SIZE - HEADER_SIZE).collect::<Vec<_>>();
+        for (index, fragment) in fragments.iter().enumerate() {
+            let mut sequence_flag = 0;
+            if index == 0 { sequence_flag |= 0x80 }
+            if index == fragments.len() - 1 { sequence_flag |= 0x40 }
+            let mut payload = vec![0, 0, 0, sequence_flag];
+            payload.extend(*fragment);
+            self.write_segment(pts, SEGMENT_TYPE_ODS, &payload)?;
+        }
+        Ok(())
+    }
+
+    /// writes a display set showing the frame at the specified video frame index, an empty frame clears the screen
+    pub fn write_frame(&mut self, video_frame_index: u32, frame: &Frame) -> Result<(), IOError> {
+        let pts = video_frame_pts(video_frame_index, self.video_frame_rate);
+        let bounding_box = match visible_bounding_box(frame) {
+            Some(bounding_box) => bounding_box,
+            None => return self.write_clear(video_frame_index),
+        };
+        let (x, y) = (self.frame_offset.0 + bounding_box.x, self.frame_offset.1 + bounding_box.y);
+        let (palette, indices) = indexed_image(frame, bounding_box);
+        let rle_data = rle_encode(&indices, bounding_box.width as usize);
+        self.write_pcs(pts, Some((x, y)))?;
+        self.write_wds(pts, x, y, bounding_box.width, bounding_box.height)?;
+        self.write_pds(pts, &palette)?;
+        self.write_ods(pts, bounding_box.width, bounding_box.height, &rle_data)?;
+        self.write_segment(pts, SEGMENT_TYPE_END, &[])
+    }
+
+    /// writes a display set clearing the screen at the specified video frame index
+    pub fn write_clear(&mut self, video_frame_index: u32) -> Result<(), IOError> {
+        let pts = video_frame_pts(video_frame_index, self.video_frame_rate);
+        self.write_pcs(pts, None)?;
+        self.write_wds(pts, 0, 0, self.video_resolution.width, self.video_resolution.height)?;
+        self.write_segment(pts, SEGMENT_TYPE_END, &[])
+    }
+
+    /// writes the OSD frames of the iterator displayed during the video frames, only the frames which differ from the
+    /// previously written one are written
+    ///
+    /// Each OSD frame is written at the first video frame displaying it. When several OSD frames start at the same
+    /// video frame, which happens with videos having a lower frame rate than the OSD, only the last one is displayed.
+    pub fn write_frames(mut self, frames_iter: FramesIter, video_frame_count: u32) -> Result<(), WritePGSError> {
+        let mut written_frame: Option<Frame> = None;
+        let mut pending_frame: Option<(u32, Frame)> = None;
+        for (osd_frame_index, frame) in frames_iter.enumerate() {
+            let frame = frame?;
+            let video_frame_index = first_video_frame_index(osd_frame_index as u32, self.video_frame_rate);
+            if video_frame_index >= video_frame_count as u64 { break }
+            let video_frame_index = video_frame_index as u32;
+            if let Some((pending_video_frame_index, pending)) = pending_frame.take() {
+                if pending_video_frame_index != video_frame_index {
+                    self.write_changed_frame(pending_video_frame_index, pending, &mut written_frame)?;
+                }
+            }
+            pending_frame = Some((video_frame_index, frame));
+        }
+        if let Some((pending_video_frame_index, pending)) = pending_frame {
+            self.write_changed_frame(pending_video_frame_index, pending, &mut written_frame)?;
+        }
+        self.write_clear(video_frame_count)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn write_changed_frame(&mut self, video_frame_index: u32, frame: Frame, written_frame: &mut Option<Frame>) -> Result<(), IOError> {
+        if matches!(written_frame, Some(written_frame) if written_frame.as_raw() == frame.as_raw()) { return Ok(()) }
+        self.write_frame(video_frame_index, &frame)?;
+        *written_frame = Some(frame);
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle_encode_runs() {
+        let line = [0, 0, 0, 5, 5, 7, 7, 7, 7, 0];
+        assert_eq!(rle_encode(&line, line.len()), vec![0, 3, 5, 5, 0, 0x84, 7, 0, 1, 0, 0]);
+        let line = [3; 100];
+        assert_eq!(rle_encode(&line, line.len()), vec![0, 0xc0, 100, 3, 0, 0]);
+    }
+
+    #[test]
+    fn pts_of_video_frames() {
+        assert_eq!(video_frame_pts(60, 60.0), 90_000);
+        assert_eq!(video_frame_pts(25, 25.0), 90_000);
+        assert_eq!(video_frame_pts(30, 30_000.0 / 1001.0), 90_090);
+    }
+
+}
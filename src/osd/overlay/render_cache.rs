@@ -0,0 +1,135 @@
+
+//! Cache of the rendered overlay frames keyed by the content of the OSD frames
+//!
+//! Most OSD updates redraw the same tiles, e.g. while the quad sits on the ground or when only a blinking warning
+//! toggles, so the frames drawn from tile grids already seen are copied from the cache instead of being composed
+//! again. The cached frames are the tiles only, the graphs and sticks drawn for each video frame are added afterwards.
+//! The frames are shared with the callers so that a cache shared by several threads is only locked while looking up
+//! the frames and not while they are copied.
+
+use std::{
+    collections::{HashMap, VecDeque, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use getset::CopyGetters;
+
+use crate::osd::tile_indices::TileIndices;
+
+use super::Frame;
+
+
+/// number of frames kept by default, a full HD overlay frame uses 8 MB
+pub const DEFAULT_CAPACITY: usize = 8;
+
+/// hash of the tile grid of an OSD frame
+pub fn tile_indices_hash(tile_indices: &TileIndices) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let grid_dimensions = tile_indices.grid_dimensions();
+    (grid_dimensions.width, grid_dimensions.height).hash(&mut hasher);
+    tile_indices.as_slice().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// maps the position of each tile grid identical to an earlier one to the position of the first of them
+pub fn identical_tile_grids<'a>(tile_grids: impl IntoIterator<Item = &'a TileIndices>) -> HashMap<usize, usize> {
+    let mut first_positions: HashMap<u64, Vec<(usize, &TileIndices)>> = HashMap::new();
+    let mut identical_positions = HashMap::new();
+    for (position, tile_indices) in tile_grids.into_iter().enumerate() {
+        let first_grids = first_positions.entry(tile_indices_hash(tile_indices)).or_default();
+        match first_grids.iter().find(|(_, first_tile_indices)| *first_tile_indices == tile_indices) {
+            Some((first_position, _)) => { identical_positions.insert(position, *first_position); },
+            None => first_grids.push((position, tile_indices)),
+        }
+    }
+    identical_positions
+}
+
+struct Entry {
+    hash: u64,
+    tile_indices: TileIndices,
+    frame: Arc<Frame>,
+}
+
+/// Least recently used rendered frames, the tile grids are compared on hash match so that a hash collision cannot
+/// return the wrong frame
+#[derive(CopyGetters)]
+pub struct RenderCache {
+    capacity: usize,
+    /// most recently used first
+    entries: VecDeque<Entry>,
+    #[getset(get_copy = "pub")]
+    hit_count: u64,
+    #[getset(get_copy = "pub")]
+    miss_count: u64,
+}
+
+impl RenderCache {
+
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: VecDeque::with_capacity(capacity), hit_count: 0, miss_count: 0 }
+    }
+
+    /// frame drawn from the tile grid if it is cached
+    pub fn get(&mut self, tile_indices: &TileIndices) -> Option<Arc<Frame>> {
+        let hash = tile_indices_hash(tile_indices);
+        match self.entries.iter().position(|entry| entry.hash == hash && entry.tile_indices == *tile_indices) {
+            Some(position) => {
+                let entry = self.entries.remove(position).unwrap();
+                let frame = entry.frame.clone();
+                self.entries.push_front(entry);
+                self.hit_count += 1;
+                Some(frame)
+            },
+            None => {
+                self.miss_count += 1;
+                None
+            },
+        }
+    }
+
+    /// caches the frame drawn from the tile grid, evicting the least recently used frame when full
+    pub fn insert(&mut self, tile_indices: &TileIndices, frame: Arc<Frame>) {
+        if self.capacity == 0 { return }
+        let hash = tile_indices_hash(tile_indices);
+        if self.entries.iter().any(|entry| entry.hash == hash && entry.tile_indices == *tile_indices) { return }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(Entry { hash, tile_indices: tile_indices.clone(), frame });
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::osd::tile_indices;
+
+    use super::*;
+
+    fn tile_indices(tile_index: u16) -> TileIndices {
+        TileIndices::new(vec![tile_index; tile_indices::COUNT])
+    }
+
+    #[test]
+    fn least_recently_used_eviction() {
+        let frame = Arc::new(Frame::new(super::super::Dimensions::new(4, 4)));
+        let mut cache = RenderCache::new(2);
+        cache.insert(&tile_indices(1), frame.clone());
+        cache.insert(&tile_indices(2), frame.clone());
+        assert!(cache.get(&tile_indices(1)).is_some());
+        cache.insert(&tile_indices(3), frame);
+        assert!(cache.get(&tile_indices(2)).is_none());
+        assert!(cache.get(&tile_indices(1)).is_some());
+        assert_eq!((cache.hit_count(), cache.miss_count()), (2, 1));
+    }
+
+    #[test]
+    fn identical_tile_grids_mapped_to_the_first_one() {
+        let tile_grids = [tile_indices(1), tile_indices(2), tile_indices(1), tile_indices(3), tile_indices(2), tile_indices(1)];
+        assert_eq!(identical_tile_grids(&tile_grids), HashMap::from([(2, 0), (4, 1), (5, 0)]));
+    }
+
+}
@@ -76,6 +76,10 @@ pub struct ScalingArgs {
     /// minimum percentage of OSD coverage under which scaling will be used if --scaling/--no-scaling options are not provided
     #[clap(long, value_parser = clap::value_parser!(u8).range(1..=100), value_name = "percent", default_value = "90")]
     min_coverage: u8,
+
+    /// print the steps which led to the choice of the kind of tiles and of the scaling
+    #[clap(long, value_parser)]
+    explain_scaling: bool,
 }
 
 #[derive(Args, CopyGetters)]
@@ -97,6 +101,58 @@ pub struct OSDScalingArgs {
     /// minimum percentage of OSD coverage under which scaling will be used if --scaling/--no-scaling options are not provided
     #[clap(long, value_parser = clap::value_parser!(u8).range(1..=100), value_name = "percent", default_value = "90")]
     min_osd_coverage: u8,
+
+    /// print the steps which led to the choice of the kind of tiles and of the scaling
+    #[clap(long, value_parser)]
+    explain_scaling: bool,
+}
+
+/// OSD scaling settings used when burning the OSD onto a video, the target resolution is the video resolution
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct OSDScalingSettings {
+    /// force scaling (`Some(true)`) or no scaling (`Some(false)`), automatic when `None`
+    scaling: Option<bool>,
+    min_margins: Margins,
+    min_coverage: u8,
+    /// log the steps which led to the choice of the kind of tiles and of the scaling
+    explain: bool,
+}
+
+impl OSDScalingSettings {
+
+    pub fn new(scaling: Option<bool>, min_margins: Margins, min_coverage: u8) -> Result<Self, ScalingArgsError> {
+        if ! (1..=100).contains(&min_coverage) { return Err(ScalingArgsError::InvalidMinCoveragePercent(min_coverage)) }
+        Ok(Self { scaling, min_margins, min_coverage, explain: false })
+    }
+
+    pub fn set_explain(&mut self, explain: bool) -> &mut Self {
+        self.explain = explain;
+        self
+    }
+
+}
+
+impl Default for OSDScalingSettings {
+    fn default() -> Self {
+        Self { scaling: None, min_margins: Margins::new(20, 20), min_coverage: 90, explain: false }
+    }
+}
+
+impl OSDScalingArgs {
+
+    pub fn settings(&self) -> Result<OSDScalingSettings, ScalingArgsError> {
+        let scaling = match (self.osd_scaling, self.no_osd_scaling) {
+            (true, true) => return Err(ScalingArgsError::IncompatibleArguments),
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
+        };
+        let mut settings = OSDScalingSettings::new(scaling, self.min_osd_margins, self.min_osd_coverage)?;
+        settings.set_explain(self.explain_scaling);
+        Ok(settings)
+    }
+
 }
 
 impl Scaling {
@@ -136,20 +192,23 @@ impl Scaling {
     }
 
     pub fn try_from_osd_args(args: &OSDScalingArgs, video_resolution: VideoResolution) -> Result<Self, ScalingArgsError> {
-        Ok(match (args.osd_scaling, args.no_osd_scaling) {
-            (true, true) => return Err(ScalingArgsError::IncompatibleArguments),
-            (true, false) => Scaling::Yes { target_resolution: TargetResolution::Custom(video_resolution), min_margins: args.min_osd_margins },
-            (false, true) => Scaling::No { target_resolution: Some(TargetResolution::Custom(video_resolution)) },
-            (false, false) => {
-                let target_resolution = TargetResolution::Custom(video_resolution);
-                let min_coverage = args.min_osd_coverage as f64 / 100.0;
+        Ok(Self::from_osd_settings(&args.settings()?, video_resolution))
+    }
+
+    pub fn from_osd_settings(settings: &OSDScalingSettings, video_resolution: VideoResolution) -> Self {
+        let target_resolution = TargetResolution::Custom(video_resolution);
+        match settings.scaling {
+            Some(true) => Scaling::Yes { target_resolution, min_margins: settings.min_margins },
+            Some(false) => Scaling::No { target_resolution: Some(target_resolution) },
+            None => {
+                let min_coverage = settings.min_coverage as f64 / 100.0;
                 let min_resolution = VideoResolution::new(
                     (target_resolution.dimensions().width as f64 * min_coverage) as u32,
                     (target_resolution.dimensions().height as f64 * min_coverage) as u32
                 );
-                Scaling::Auto { target_resolution, min_margins: args.min_osd_margins, min_resolution }
+                Scaling::Auto { target_resolution, min_margins: settings.min_margins, min_resolution }
             },
-        })
+        }
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,330 @@
+
+//! Custom widgets drawn onto the overlay frames by a Rhai script
+//!
+//! The script defines a `draw(canvas, frame)` function called for every overlay frame, `frame` being a map with:
+//! - `index`: index of the OSD frame, counted at 60 FPS from the start of the OSD recording
+//! - `time`: time of the frame in seconds from the start of the OSD recording
+//! - `osd`: values of the numeric OSD items displayed at this frame, e.g. `frame.osd.speed`
+//! - `telemetry`: values of the flight telemetry series at this frame, e.g. `frame.telemetry.throttle`
+//!
+//! The values which are not available are missing from the maps, reading them returns `()`. The `canvas` has `width`
+//! and `height` properties and the `line(x1, y1, x2, y2, width, color)`, `disc(x, y, radius, color)`,
+//! `rect(x, y, width, height, color)`, `arc(x, y, radius, start_angle, end_angle, width, color)` and
+//! `number(x, y, value, scale, color)` drawing methods, the angles are in degrees clockwise from the top and the colors
+//! are strings like `white`, `#ff8000` or `black@0.5`. The rectangles are blended over the frame while the other
+//! shapes replace its pixels.
+//!
+//! Each call is limited to [`MAX_OPERATIONS`] so that an infinite loop cannot hang the generation, and to [`MAX_SHAPES`]
+//! shapes since a single operation can draw many of them. The arcs are limited to one turn and the line widths and
+//! number scales to the size of the frame. The script is run
+//! once when loaded to report its errors early, the errors happening afterwards are logged once and the widgets are
+//! not drawn for the failing frames.
+
+use std::{
+    f64::consts::PI,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+};
+
+use derive_more::From;
+use image::Rgba;
+use rhai::{Dynamic, Engine, EvalAltResult, ImmutableString, Map, AST};
+use thiserror::Error;
+
+use crate::{
+    image::{draw_disc, draw_line, draw_number},
+    osd::{self, FontVariant, file::{Frame as OSDFileFrame, ReadError}, item_series::{self, ItemSeries}, tile_indices::UnknownOSDItem},
+    telemetry::{self, AlignedTelemetry},
+};
+
+use super::{Frame, style::{Color, blend_over}};
+
+
+/// maximum number of operations of a call of the script `draw` function
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// maximum number of shapes drawn by a call of the script `draw` function
+pub const MAX_SHAPES: usize = 100_000;
+
+/// number of segments of a full circle drawn with `arc`
+const ARC_SEGMENTS: f64 = 90.0;
+
+/// result of the functions called by the script
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Debug, Error, From)]
+pub enum ScriptError {
+    #[error("failed loading script {}: {error}", .path.to_string_lossy())]
+    #[from(ignore)]
+    CompileError { path: PathBuf, error: Box<EvalAltResult> },
+    #[error("script {} does not define a `draw(canvas, frame)` function", .0.to_string_lossy())]
+    #[from(ignore)]
+    MissingDrawFunction(PathBuf),
+    #[error("script {} failed: {error}", .path.to_string_lossy())]
+    #[from(ignore)]
+    RuntimeError { path: PathBuf, error: Box<EvalAltResult> },
+    #[error("OSD file read error: {0}")]
+    ReadError(ReadError),
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    Line { from: (f64, f64), to: (f64, f64), width: u32, color: Rgba<u8> },
+    Disc { center: (f64, f64), radius: f64, color: Rgba<u8> },
+    Rect { left: f64, top: f64, width: f64, height: f64, color: Rgba<u8> },
+    Number { position: (f64, f64), value: u32, scale: u32, color: Rgba<u8> },
+}
+
+impl Shape {
+
+    fn draw(&self, frame: &mut Frame) {
+        let image = frame.image_mut();
+        match *self {
+            Self::Line { from, to, width, color } => draw_line(image, from, to, width, color),
+            Self::Disc { center, radius, color } => draw_disc(image, center, radius, color),
+            Self::Rect { left, top, width, height, color } => {
+                let x_range = left.max(0.0) as u32..((left + width).max(0.0) as u32).min(image.width());
+                for y in top.max(0.0) as u32..((top + height).max(0.0) as u32).min(image.height()) {
+                    for x in x_range.clone() {
+                        let pixel = image.get_pixel_mut(x, y);
+                        *pixel = blend_over(color, *pixel);
+                    }
+                }
+            },
+            Self::Number { position, value, scale, color } =>
+                draw_number(image, (position.0.max(0.0) as u32, position.1.max(0.0) as u32), value, scale, color),
+        }
+    }
+
+}
+
+/// Drawing surface handed to the script, the shapes are recorded and drawn onto the frame once the script returns
+#[derive(Debug, Clone)]
+struct Canvas {
+    width: i64,
+    height: i64,
+    shapes: Arc<Mutex<Vec<Shape>>>,
+}
+
+impl Canvas {
+
+    fn new(width: u32, height: u32) -> Self {
+        Self { width: width as i64, height: height as i64, shapes: Arc::new(Mutex::new(vec![])) }
+    }
+
+    /// largest size of the frame, the largest visible line width and number scale
+    fn max_size(&self) -> f64 {
+        self.width.max(self.height) as f64
+    }
+
+    fn push(&mut self, shape: Shape) -> ScriptResult<()> {
+        let mut shapes = self.shapes.lock().unwrap();
+        if shapes.len() >= MAX_SHAPES { return Err(format!("too many shapes drawn, the maximum is {MAX_SHAPES}").into()) }
+        shapes.push(shape);
+        Ok(())
+    }
+
+}
+
+/// number passed by the script, integers being accepted where decimals are expected
+fn number(value: Dynamic) -> ScriptResult<f64> {
+    value.as_float().or_else(|_| value.as_int().map(|value| value as f64))
+        .map_err(|type_name| format!("expected a number, got {type_name}").into())
+}
+
+fn color(value: ImmutableString) -> ScriptResult<Rgba<u8>> {
+    Color::from_str(&value).map(|color| color.rgba()).map_err(|error| error.to_string().into())
+}
+
+/// point on the circle at the angle in degrees clockwise from the top
+fn circle_point(center: (f64, f64), radius: f64, angle: f64) -> (f64, f64) {
+    let radians = angle * PI / 180.0;
+    (center.0 + radius * radians.sin(), center.1 - radius * radians.cos())
+}
+
+fn register_canvas(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Canvas>("Canvas")
+        .register_get("width", |canvas: &mut Canvas| canvas.width)
+        .register_get("height", |canvas: &mut Canvas| canvas.height)
+        .register_fn("line", |canvas: &mut Canvas, x1: Dynamic, y1: Dynamic, x2: Dynamic, y2: Dynamic, width: Dynamic,
+                line_color: ImmutableString| -> ScriptResult<()> {
+            let shape = Shape::Line { from: (number(x1)?, number(y1)?), to: (number(x2)?, number(y2)?),
+                width: number(width)?.max(1.0).min(canvas.max_size()) as u32, color: color(line_color)? };
+            canvas.push(shape)
+        })
+        .register_fn("disc", |canvas: &mut Canvas, x: Dynamic, y: Dynamic, radius: Dynamic, disc_color: ImmutableString| -> ScriptResult<()> {
+            let shape = Shape::Disc { center: (number(x)?, number(y)?), radius: number(radius)?, color: color(disc_color)? };
+            canvas.push(shape)
+        })
+        .register_fn("rect", |canvas: &mut Canvas, x: Dynamic, y: Dynamic, width: Dynamic, height: Dynamic,
+                rect_color: ImmutableString| -> ScriptResult<()> {
+            let shape = Shape::Rect { left: number(x)?, top: number(y)?, width: number(width)?, height: number(height)?, color: color(rect_color)? };
+            canvas.push(shape)
+        })
+        .register_fn("arc", |canvas: &mut Canvas, x: Dynamic, y: Dynamic, radius: Dynamic, start_angle: Dynamic, end_angle: Dynamic,
+                width: Dynamic, arc_color: ImmutableString| -> ScriptResult<()> {
+            let (center, radius, start_angle, end_angle) = ((number(x)?, number(y)?), number(radius)?, number(start_angle)?, number(end_angle)?);
+            let (width, color) = (number(width)?.max(1.0).min(canvas.max_size()) as u32, color(arc_color)?);
+            // more than a turn draws the same circle
+            let sweep = (end_angle - start_angle).clamp(-360.0, 360.0);
+            let segment_count = (sweep.abs() / 360.0 * ARC_SEGMENTS).ceil().max(1.0) as u32;
+            for segment in 0..segment_count {
+                let angle = |segment: u32| start_angle + sweep * segment as f64 / segment_count as f64;
+                canvas.push(Shape::Line { from: circle_point(center, radius, angle(segment)), to: circle_point(center, radius, angle(segment + 1)),
+                    width, color })?;
+            }
+            Ok(())
+        })
+        .register_fn("number", |canvas: &mut Canvas, x: Dynamic, y: Dynamic, value: Dynamic, scale: Dynamic,
+                number_color: ImmutableString| -> ScriptResult<()> {
+            let shape = Shape::Number { position: (number(x)?, number(y)?), value: number(value)?.round().max(0.0) as u32,
+                scale: number(scale)?.max(1.0).min(canvas.max_size()) as u32, color: color(number_color)? };
+            canvas.push(shape)
+        });
+}
+
+/// Widgets drawn by a script as an extra layer on top of the OSD, see the [module documentation](self)
+pub struct ScriptLayer {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    osd_items: Vec<ItemSeries>,
+    runtime_error_logged: AtomicBool,
+}
+
+impl ScriptLayer {
+
+    /// compiles the script and extracts the values of the numeric OSD items from the OSD file frames
+    pub fn load(path: &Path, frames: &[OSDFileFrame], font_variant: FontVariant) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| log::info!("OSD script: {text}"));
+        engine.on_debug(|text, _, _| log::debug!("OSD script: {text}"));
+        register_canvas(&mut engine);
+
+        let ast = engine.compile_file(path.to_path_buf()).map_err(|error| ScriptError::CompileError { path: path.to_path_buf(), error })?;
+        if ! ast.iter_functions().any(|function| function.name == "draw" && function.params.len() == 2) {
+            return Err(ScriptError::MissingDrawFunction(path.to_path_buf()));
+        }
+
+        let mut osd_items = vec![];
+        for item_name in item_series::numeric_item_names(font_variant) {
+            let series = ItemSeries::extract(frames, font_variant, item_name)?;
+            if ! series.is_empty() {
+                osd_items.push(series);
+            }
+        }
+
+        log::info!("loaded OSD script {} with {} OSD items", path.to_string_lossy(), osd_items.len());
+        Ok(Self { path: path.to_path_buf(), engine, ast, osd_items, runtime_error_logged: AtomicBool::new(false) })
+    }
+
+    /// `frame` argument of the script `draw` function
+    fn frame_map(&self, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>) -> Map {
+        let mut osd_values = Map::new();
+        if osd_frame_index >= 0 {
+            for series in &self.osd_items {
+                if let Some(value) = series.value_at(osd_frame_index as u32) {
+                    osd_values.insert(series.item_name().into(), value.into());
+                }
+            }
+        }
+        let mut telemetry_values = Map::new();
+        if let Some(telemetry) = telemetry {
+            for name in telemetry::SERIES_NAMES {
+                if let Some(value) = telemetry.value_at(name, osd_frame_index as i64) {
+                    telemetry_values.insert(name.into(), value.into());
+                }
+            }
+        }
+        let mut frame = Map::new();
+        frame.insert("index".into(), (osd_frame_index as i64).into());
+        frame.insert("time".into(), (osd_frame_index as f64 / osd::FRAME_RATE as f64).into());
+        frame.insert("osd".into(), osd_values.into());
+        frame.insert("telemetry".into(), telemetry_values.into());
+        frame
+    }
+
+    /// runs the script `draw` function for the OSD frame index and draws the recorded shapes onto the frame
+    fn try_draw(&self, frame: &mut Frame, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>) -> Result<(), ScriptError> {
+        let dimensions = frame.dimensions();
+        let canvas = Canvas::new(dimensions.width, dimensions.height);
+        let frame_map = self.frame_map(osd_frame_index, telemetry);
+        self.engine.call_fn::<Dynamic>(&mut rhai::Scope::new(), &self.ast, "draw", (canvas.clone(), frame_map))
+            .map_err(|error| ScriptError::RuntimeError { path: self.path.clone(), error })?;
+        for shape in canvas.shapes.lock().unwrap().iter() {
+            shape.draw(frame);
+        }
+        Ok(())
+    }
+
+    /// runs the script on an empty frame to report the errors before the generation starts
+    pub fn check(&self, dimensions: super::Dimensions, telemetry: Option<&AlignedTelemetry>) -> Result<(), ScriptError> {
+        self.try_draw(&mut Frame::new(dimensions), 0, telemetry)
+    }
+
+    /// draws the widgets onto the overlay frame of the OSD frame index, the first error is logged and the widgets are
+    /// not drawn when the script fails
+    pub fn draw(&self, frame: &mut Frame, osd_frame_index: i32, telemetry: Option<&AlignedTelemetry>) {
+        if let Err(error) = self.try_draw(frame, osd_frame_index, telemetry) {
+            if ! self.runtime_error_logged.swap(true, Ordering::Relaxed) {
+                log::warn!("{error}, the widgets are not drawn for the frames where it fails");
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn arc_points() {
+        let (x, y) = circle_point((10.0, 10.0), 5.0, 90.0);
+        assert!((x - 15.0).abs() < 1e-9 && (y - 10.0).abs() < 1e-9);
+        let (x, y) = circle_point((10.0, 10.0), 5.0, 0.0);
+        assert!((x - 10.0).abs() < 1e-9 && (y - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn script_shapes() {
+        let mut engine = Engine::new();
+        register_canvas(&mut engine);
+        let script = r#"fn draw(canvas, frame) { canvas.rect(0, 0, canvas.width / 2, 1, "white"); canvas.disc(3.5, 0.5, 0.5, "red"); }"#;
+        let ast = engine.compile(script).unwrap();
+        let canvas = Canvas::new(4, 1);
+        engine.call_fn::<Dynamic>(&mut rhai::Scope::new(), &ast, "draw", (canvas.clone(), Map::new())).unwrap();
+        let mut frame = Frame::new(super::super::Dimensions::new(4, 1));
+        for shape in canvas.shapes.lock().unwrap().iter() {
+            shape.draw(&mut frame);
+        }
+        let pixels = frame.image_mut().pixels().map(|pixel| pixel.0).collect::<Vec<_>>();
+        assert_eq!(pixels, vec![[255, 255, 255, 255], [255, 255, 255, 255], [0, 0, 0, 0], [255, 0, 0, 255]]);
+    }
+
+    #[test]
+    fn script_shapes_bounded() {
+        let mut engine = Engine::new();
+        register_canvas(&mut engine);
+        let script = r#"fn draw(canvas, frame) { canvas.arc(0, 0, 10, 0, 1e12, 1, "white"); canvas.line(0, 0, 1, 1, 1e12, "white");
+            canvas.number(0, 0, 1, 1e12, "white"); }"#;
+        let ast = engine.compile(script).unwrap();
+        let canvas = Canvas::new(4, 1);
+        engine.call_fn::<Dynamic>(&mut rhai::Scope::new(), &ast, "draw", (canvas.clone(), Map::new())).unwrap();
+        let shapes = canvas.shapes.lock().unwrap();
+        assert_eq!(shapes.len(), ARC_SEGMENTS as usize + 2);
+        assert!(matches!(shapes[shapes.len() - 2], Shape::Line { width: 4, .. }));
+        assert!(matches!(shapes[shapes.len() - 1], Shape::Number { scale: 4, .. }));
+
+        let script = format!(r#"fn draw(canvas, frame) {{ for i in 0..{} {{ canvas.rect(0, 0, 1, 1, "white"); }} }}"#, MAX_SHAPES + 1);
+        let ast = engine.compile(script).unwrap();
+        assert!(engine.call_fn::<Dynamic>(&mut rhai::Scope::new(), &ast, "draw", (Canvas::new(4, 1), Map::new())).is_err());
+    }
+
+}
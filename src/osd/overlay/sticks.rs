@@ -0,0 +1,132 @@
+
+//! Stick positions read from the flight telemetry drawn next to the OSD as two gimbals
+
+use getset::{CopyGetters, Setters};
+use image::Rgba;
+
+use crate::image::{draw_disc, draw_line};
+use crate::telemetry::{AlignedTelemetry, PITCH, ROLL, THROTTLE, YAW};
+
+use super::{Frame, style::blend_over};
+
+
+const BACKGROUND_COLOR: Rgba<u8> = Rgba([0, 0, 0, 96]);
+const GUIDE_COLOR: Rgba<u8> = Rgba([255, 255, 255, 96]);
+const STICK_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// gimbal size relative to the overlay frame height
+const SIZE_RATIO: u32 = 7;
+
+/// telemetry series needed to draw the sticks
+pub const TELEMETRY_SERIES: [&str; 4] = [ROLL, PITCH, YAW, THROTTLE];
+
+/// position of the gimbals on the overlay frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SticksPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Default for SticksPosition {
+    fn default() -> Self {
+        Self::Bottom
+    }
+}
+
+#[derive(Debug, Clone, Default, CopyGetters, Setters)]
+#[getset(get_copy = "pub", set = "pub")]
+pub struct SticksSettings {
+    /// the sticks are drawn when the telemetry is available
+    enabled: bool,
+    position: SticksPosition,
+}
+
+/// Stick positions drawn as the two gimbals of a mode 2 radio: throttle and yaw on the left, pitch and roll on the right
+pub struct StickOverlay {
+    position: SticksPosition,
+}
+
+impl StickOverlay {
+
+    pub fn new(settings: &SticksSettings) -> Self {
+        Self { position: settings.position }
+    }
+
+    /// draws a gimbal in the square area given as (left, top, size), `x` and `y` from -1 to 1
+    fn draw_gimbal(frame: &mut Frame, area: (u32, u32, u32), x: f64, y: f64) {
+        let (left, top, size) = area;
+        let image = frame.image_mut();
+        for pixel_y in top..top + size {
+            for pixel_x in left..left + size {
+                let pixel = image.get_pixel_mut(pixel_x, pixel_y);
+                *pixel = blend_over(BACKGROUND_COLOR, *pixel);
+            }
+        }
+        let (left, top, last) = (left as f64, top as f64, (size - 1) as f64);
+        draw_line(image, (left + last / 2.0, top), (left + last / 2.0, top + last), 1, GUIDE_COLOR);
+        draw_line(image, (left, top + last / 2.0), (left + last, top + last / 2.0), 1, GUIDE_COLOR);
+        let stick = (left + (x + 1.0) / 2.0 * last, top + (1.0 - y) / 2.0 * last);
+        draw_line(image, (left + last / 2.0, top + last / 2.0), stick, 2, STICK_COLOR);
+        draw_disc(image, stick, size as f64 / 12.0, STICK_COLOR);
+    }
+
+    /// draws the gimbals onto the overlay frame of the OSD frame index, nothing is drawn outside of the telemetry
+    pub fn draw(&self, frame: &mut Frame, telemetry: &AlignedTelemetry, osd_frame_index: i32) {
+        let value = |name| telemetry.value_at(name, osd_frame_index as i64);
+        let (roll, pitch, yaw, throttle) = match (value(ROLL), value(PITCH), value(YAW), value(THROTTLE)) {
+            (Some(roll), Some(pitch), Some(yaw), Some(throttle)) => (roll, pitch, yaw, throttle),
+            _ => return,
+        };
+        let dimensions = frame.dimensions();
+        let size = dimensions.height / SIZE_RATIO;
+        let gap = size / 4;
+        let width = 2 * size + gap;
+        if size < 2 || width + 2 * gap > dimensions.width { return }
+        let left = match self.position {
+            SticksPosition::TopLeft | SticksPosition::BottomLeft => gap,
+            SticksPosition::Bottom => (dimensions.width - width) / 2,
+            SticksPosition::TopRight | SticksPosition::BottomRight => dimensions.width - width - gap,
+        };
+        let top = match self.position {
+            SticksPosition::TopLeft | SticksPosition::TopRight => gap,
+            _ => dimensions.height - size - gap,
+        };
+        Self::draw_gimbal(frame, (left, top, size), yaw, throttle * 2.0 - 1.0);
+        Self::draw_gimbal(frame, (left + size + gap, top, size), roll, pitch);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::telemetry::{Series, TelemetryLog};
+    use super::super::Dimensions;
+
+    fn telemetry() -> AlignedTelemetry {
+        let series = |name, value| Series::new(name, vec![(0.0, value), (1.0, value)]);
+        let log = TelemetryLog::new(vec![series(ROLL, 1.0), series(PITCH, 1.0), series(YAW, 0.0), series(THROTTLE, 0.5)], None);
+        AlignedTelemetry::new(log, None, 0.0)
+    }
+
+    #[test]
+    fn sticks_drawn_at_the_telemetry_positions() {
+        let overlay = StickOverlay::new(&SticksSettings::default());
+        let telemetry = telemetry();
+        let mut frame = Frame::new(Dimensions::new(700, 350));
+        overlay.draw(&mut frame, &telemetry, 30);
+        // 50 pixels gimbals with a 12 pixels gap centered at the bottom, the right stick is at the top right corner
+        assert_eq!(*frame.get_pixel(405, 288), STICK_COLOR);
+        assert_eq!(*frame.get_pixel(356 + 24, 288 + 24), STICK_COLOR);
+        assert_eq!(*frame.get_pixel(10, 10), Rgba([0, 0, 0, 0]));
+
+        let mut frame = Frame::new(Dimensions::new(700, 350));
+        overlay.draw(&mut frame, &telemetry, 200);
+        assert!(frame.pixels().all(|pixel| *pixel == Rgba([0, 0, 0, 0])));
+    }
+
+}
@@ -0,0 +1,323 @@
+
+use std::str::FromStr;
+
+use getset::{CopyGetters, Getters};
+use hd_fpv_osd_font_tool::prelude::*;
+use image::{Rgba, RgbaImage};
+use thiserror::Error;
+
+
+#[derive(Debug, Error)]
+#[error("invalid color: {0}, expected <name|RRGGBB>[@<alpha 0-1>]")]
+pub struct InvalidColorError(String);
+
+/// RGBA color parsed from `<name|[#]RRGGBB>[@<alpha>]` with alpha between 0 and 1 like FFMpeg colors, e.g. `black@0.5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(Rgba<u8>);
+
+impl Color {
+
+    pub fn rgba(&self) -> Rgba<u8> {
+        self.0
+    }
+
+    fn from_name(name: &str) -> Option<[u8; 3]> {
+        Some(match name.to_lowercase().as_str() {
+            "black" => [0, 0, 0],
+            "white" => [255, 255, 255],
+            "gray" | "grey" => [128, 128, 128],
+            "red" => [255, 0, 0],
+            "green" => [0, 128, 0],
+            "blue" => [0, 0, 255],
+            "yellow" => [255, 255, 0],
+            "orange" => [255, 165, 0],
+            "cyan" => [0, 255, 255],
+            "magenta" => [255, 0, 255],
+            _ => return None,
+        })
+    }
+
+    fn from_hex(hex: &str) -> Option<[u8; 3]> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if hex.len() != 6 || ! hex.is_ascii() { return None }
+        let component = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok();
+        Some([component(0)?, component(1)?, component(2)?])
+    }
+
+}
+
+impl FromStr for Color {
+    type Err = InvalidColorError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidColorError(value.to_owned());
+        let (color, alpha) = match value.split_once('@') {
+            Some((color, alpha)) => {
+                let alpha = alpha.parse::<f64>().ok().filter(|alpha| (0.0..=1.0).contains(alpha)).ok_or_else(error)?;
+                (color, alpha)
+            },
+            None => (value, 1.0),
+        };
+        let [red, green, blue] = Self::from_name(color).or_else(|| Self::from_hex(color)).ok_or_else(error)?;
+        Ok(Self(Rgba([red, green, blue, (alpha * 255.0).round() as u8])))
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid OSD color remap: {0}, expected <color>=<color[@alpha]>")]
+pub struct InvalidColorRemapError(String);
+
+/// maximum difference of each color component for a pixel to be considered of the color to remap, so that the
+/// slightly different pixels at the edges of the glyphs are remapped as well
+const COLOR_REMAP_TOLERANCE: u8 = 48;
+
+/// replaces the glyph pixels of a color with another color, e.g. `white=yellow`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct ColorRemap {
+    from: Color,
+    to: Color,
+}
+
+impl ColorRemap {
+
+    pub fn new(from: Color, to: Color) -> Self {
+        Self { from, to }
+    }
+
+    /// remaps the pixel if it matches, the alpha of the pixel is multiplied by the alpha of the new color
+    fn apply(&self, pixel: &mut Rgba<u8>) -> bool {
+        let from = self.from.rgba();
+        if pixel.0[3] == 0 || (0..3).any(|index| pixel.0[index].abs_diff(from.0[index]) > COLOR_REMAP_TOLERANCE) { return false }
+        let to = self.to.rgba();
+        *pixel = Rgba([to.0[0], to.0[1], to.0[2], (pixel.0[3] as u32 * to.0[3] as u32 / 255) as u8]);
+        true
+    }
+
+}
+
+impl FromStr for ColorRemap {
+    type Err = InvalidColorRemapError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidColorRemapError(value.to_owned());
+        let (from, to) = value.split_once('=').ok_or_else(error)?;
+        Ok(Self { from: from.parse().map_err(|_| error())?, to: to.parse().map_err(|_| error())? })
+    }
+}
+
+/// multiplies the color of the pixel by the tint color so that white becomes the tint color and black stays black
+fn tint_pixel(pixel: &mut Rgba<u8>, tint: Color) {
+    let tint = tint.rgba();
+    for index in 0..3 {
+        pixel.0[index] = (pixel.0[index] as u32 * tint.0[index] as u32 / 255) as u8;
+    }
+}
+
+/// outline drawn around the OSD characters
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct Outline {
+    /// width in pixels
+    width: u32,
+    color: Color,
+}
+
+impl Outline {
+
+    pub fn new(width: u32, color: Color) -> Self {
+        Self { width, color }
+    }
+
+    /// draws the outline behind the characters drawn on the composed OSD frame image
+    ///
+    /// The outline alpha of a pixel is the highest alpha of the character pixels within the outline width. It is
+    /// computed by spreading the alpha of the visible pixels only, most of the frame being transparent.
+    fn draw(&self, image: &mut RgbaImage) {
+        let (width, height) = (image.width() as i64, image.height() as i64);
+        let radius = self.width as i64;
+        let mut outline_alphas = vec![0u8; (width * height) as usize];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let alpha = pixel.0[3];
+            if alpha == 0 { continue }
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if dx * dx + dy * dy > radius * radius { continue }
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if nx < 0 || ny < 0 || nx >= width || ny >= height { continue }
+                    let outline_alpha = &mut outline_alphas[(ny * width + nx) as usize];
+                    *outline_alpha = (*outline_alpha).max(alpha);
+                }
+            }
+        }
+        for (pixel, outline_alpha) in image.pixels_mut().zip(outline_alphas) {
+            if outline_alpha == 0 { continue }
+            let mut outline_pixel = self.color.rgba();
+            outline_pixel.0[3] = (outline_pixel.0[3] as u32 * outline_alpha as u32 / 255) as u8;
+            *pixel = blend_over(*pixel, outline_pixel);
+        }
+    }
+
+}
+
+/// how the OSD tiles are drawn: color of the characters, outline around them, opacity of the OSD and background box
+/// drawn behind the non empty tiles
+#[derive(Debug, Clone, Getters, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct Style {
+    /// applied before the tint
+    #[getset(skip)]
+    #[getset(get = "pub")]
+    color_remaps: Vec<ColorRemap>,
+    tint: Option<Color>,
+    outline: Option<Outline>,
+    /// opacity in percent
+    opacity: u8,
+    background: Option<Color>,
+}
+
+#[derive(Debug, Error)]
+#[error("invalid OSD opacity: {0}, must be between 0 and 100")]
+pub struct InvalidOpacityError(u8);
+
+impl Style {
+
+    pub fn new(outline: Option<Outline>, opacity: u8, background: Option<Color>) -> Result<Self, InvalidOpacityError> {
+        if opacity > 100 { return Err(InvalidOpacityError(opacity)) }
+        Ok(Self { color_remaps: vec![], tint: None, outline, opacity, background })
+    }
+
+    /// style changing the color of the characters with the remaps then the tint
+    pub fn with_colors(mut self, color_remaps: Vec<ColorRemap>, tint: Option<Color>) -> Self {
+        self.color_remaps = color_remaps;
+        self.tint = tint;
+        self
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.color_remaps.is_empty() && self.tint.is_none() && self.outline.is_none() && self.opacity == 100 && self.background.is_none()
+    }
+
+    /// whether the style has effects which must be applied to the composed OSD frames with [`Self::apply_to_frame`]
+    pub fn has_frame_effects(&self) -> bool {
+        self.outline.is_some() || self.opacity != 100 || self.background.is_some()
+    }
+
+    /// changes the color of the characters of the tile images
+    pub fn apply_to_tiles(&self, tile_images: &mut [tile::Image]) {
+        if self.color_remaps.is_empty() && self.tint.is_none() { return }
+        for pixel in tile_images.iter_mut().flat_map(|tile_image| tile_image.pixels_mut()) {
+            // only the first matching remap applies so that remaps can swap colors
+            for color_remap in &self.color_remaps {
+                if color_remap.apply(pixel) { break }
+            }
+            if let Some(tint) = self.tint {
+                tint_pixel(pixel, tint);
+            }
+        }
+    }
+
+    /// draws the outline, applies the opacity and draws the background box behind the non empty tiles of the
+    /// composed OSD frame image, the tiles being drawn on a grid of tiles with the dimensions from the top left corner
+    ///
+    /// The outline is drawn once the tiles are composed so that it is not cut at the edges of the tiles.
+    pub fn apply_to_frame(&self, image: &mut RgbaImage, tile_width: u32, tile_height: u32) {
+        if ! self.has_frame_effects() { return }
+        let non_empty_tiles = self.background.map(|_| non_empty_tiles(image, tile_width, tile_height));
+        if let Some(outline) = &self.outline {
+            outline.draw(image);
+        }
+        let opacity = self.opacity as f64 / 100.0;
+        let columns = (image.width() + tile_width - 1) / tile_width;
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            pixel.0[3] = (pixel.0[3] as f64 * opacity).round() as u8;
+            if let (Some(background), Some(non_empty_tiles)) = (self.background, &non_empty_tiles) {
+                if non_empty_tiles[(y / tile_height * columns + x / tile_width) as usize] {
+                    *pixel = blend_over(*pixel, background.rgba());
+                }
+            }
+        }
+    }
+
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self { color_remaps: vec![], tint: None, outline: None, opacity: 100, background: None }
+    }
+}
+
+/// whether each tile of the grid of tiles covering the image, row by row, has visible pixels
+fn non_empty_tiles(image: &RgbaImage, tile_width: u32, tile_height: u32) -> Vec<bool> {
+    let columns = (image.width() + tile_width - 1) / tile_width;
+    let rows = (image.height() + tile_height - 1) / tile_height;
+    let mut non_empty_tiles = vec![false; (columns * rows) as usize];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel.0[3] != 0 {
+            non_empty_tiles[(y / tile_height * columns + x / tile_width) as usize] = true;
+        }
+    }
+    non_empty_tiles
+}
+
+/// alpha composites the foreground pixel over the background pixel
+pub(crate) fn blend_over(foreground: Rgba<u8>, background: Rgba<u8>) -> Rgba<u8> {
+    let foreground_alpha = foreground.0[3] as f64 / 255.0;
+    let background_alpha = background.0[3] as f64 / 255.0 * (1.0 - foreground_alpha);
+    let alpha = foreground_alpha + background_alpha;
+    if alpha == 0.0 { return Rgba([0, 0, 0, 0]) }
+    let component = |index: usize|
+        ((foreground.0[index] as f64 * foreground_alpha + background.0[index] as f64 * background_alpha) / alpha).round() as u8;
+    Rgba([component(0), component(1), component(2), (alpha * 255.0).round() as u8])
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn style(outline: Option<Outline>, opacity: u8, background: Option<&str>) -> Style {
+        Style::new(outline, opacity, background.map(|color| color.parse().unwrap())).unwrap()
+    }
+
+    #[test]
+    fn colors_parsed_from_names_and_hex_with_alpha() {
+        assert_eq!("white".parse::<Color>().unwrap().rgba(), Rgba([255, 255, 255, 255]));
+        assert_eq!("#FF8000@0.5".parse::<Color>().unwrap().rgba(), Rgba([255, 128, 0, 128]));
+        assert!("white@2".parse::<Color>().is_err());
+        assert!("nocolor".parse::<Color>().is_err());
+        assert!("white".parse::<ColorRemap>().is_err());
+        assert!(Style::new(None, 101, None).is_err());
+    }
+
+    #[test]
+    fn color_remapped_before_tint() {
+        let mut tile_images = vec![RgbaImage::from_pixel(2, 2, Rgba([250, 250, 250, 255]))];
+        let style = Style::default().with_colors(vec!["white=yellow".parse().unwrap()], Some("#808080".parse().unwrap()));
+        style.apply_to_tiles(&mut tile_images);
+        assert_eq!(*tile_images[0].get_pixel(0, 0), Rgba([128, 128, 0, 255]));
+    }
+
+    #[test]
+    fn outline_drawn_across_tile_edges() {
+        // character pixel on the right edge of the first tile of 4x4 pixel tiles
+        let mut image = RgbaImage::new(8, 4);
+        image.put_pixel(3, 1, Rgba([255, 255, 255, 255]));
+        style(Some(Outline::new(1, "black".parse().unwrap())), 100, None).apply_to_frame(&mut image, 4, 4);
+        assert_eq!(*image.get_pixel(3, 1), Rgba([255, 255, 255, 255]));
+        assert_eq!(*image.get_pixel(4, 1), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(3, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(image.get_pixel(4, 0).0[3], 0);
+    }
+
+    #[test]
+    fn background_drawn_behind_non_empty_tiles_with_opacity() {
+        let mut image = RgbaImage::new(8, 4);
+        image.put_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        style(None, 50, Some("black")).apply_to_frame(&mut image, 4, 4);
+        assert_eq!(*image.get_pixel(1, 1), Rgba([128, 128, 128, 255]));
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(image.get_pixel(4, 0).0[3], 0);
+    }
+
+}
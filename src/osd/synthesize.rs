@@ -0,0 +1,268 @@
+
+//! OSD synthesized from flight telemetry, for the flights whose OSD was not recorded by the goggles, e.g. to burn the
+//! telemetry onto the footage of an action camera
+//!
+//! The OSD frames are generated at a fixed rate from the telemetry values placed on the 60x22 grid of the DJI FakeHD
+//! OSD by a layout template. The template has one item per line: `<x>,<y> <item> [<format>]`, the item being the name
+//! of a telemetry series or `flytime` for the time since arming. In the format `{}` is replaced by the value with the
+//! default number of decimals of the item and `{N}` by the value with N decimals, `\xNN` inserts the tile with the
+//! hexadecimal index NN, e.g. a symbol of the font. The text is displayed in upper case since the fonts only draw the
+//! upper case letters. Empty lines and lines starting with `#` are ignored. The item is not displayed when there is
+//! no telemetry value at the time of the frame.
+//!
+//! The OSD frame 0 is at the start of the telemetry shifted by the offset, the resulting DJI OSD file can then be used
+//! like a recorded one to render an overlay video or to burn the OSD onto a video.
+
+use std::{io::Error as IOError, path::Path, str::FromStr};
+
+use derive_more::From;
+use getset::Getters;
+use thiserror::Error;
+
+use crate::{
+    osd::{self, Coordinate, FontVariant, Kind, TileIndices, dji, file::Frame, tile_indices::TileIndex},
+    telemetry::{self, AlignedTelemetry, TelemetryError, TelemetryLog},
+    video::FrameIndex as VideoFrameIndex,
+};
+
+
+/// OSD frames per second generated by default, the rate at which Betaflight refreshes the OSD
+pub const DEFAULT_UPDATE_RATE: u32 = 10;
+
+/// layout used when no layout template is provided
+pub const DEFAULT_LAYOUT: &str = "\
+# <x>,<y> <item> [<format>]
+2,1 rssi {0}%
+50,1 altitude {0}M
+2,20 battery_voltage {1}V
+26,20 speed {0}KM/H
+52,20 flytime
+";
+
+/// grid of the synthesized OSD
+const KIND: Kind = Kind::DJI_FakeHD;
+
+/// name of the fly time item
+pub const FLY_TIME: &str = "flytime";
+
+#[derive(Debug, Error)]
+#[error("invalid OSD layout, line {line}: {reason}")]
+pub struct InvalidLayoutError {
+    line: usize,
+    reason: String,
+}
+
+#[derive(Debug, Error, From)]
+pub enum SynthesizeError {
+    #[error("failed reading layout file: {0}")]
+    #[from(ignore)]
+    LayoutFileError(IOError),
+    #[error(transparent)]
+    InvalidLayout(InvalidLayoutError),
+    #[error(transparent)]
+    TelemetryError(TelemetryError),
+    #[error("the telemetry has no sample")]
+    EmptyTelemetry,
+    #[error(transparent)]
+    CreateError(dji::file::CreateError),
+    #[error("failed writing OSD file: {0}")]
+    WriteError(IOError),
+}
+
+/// number of decimals of the values when not specified in the format
+fn default_decimals(item: &str) -> usize {
+    match item {
+        telemetry::BATTERY_VOLTAGE | telemetry::ROLL | telemetry::PITCH | telemetry::YAW | telemetry::THROTTLE => 1,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub")]
+pub struct LayoutItem {
+    x: Coordinate,
+    y: Coordinate,
+    /// telemetry series name or `flytime`
+    item: String,
+    format: String,
+}
+
+impl LayoutItem {
+
+    /// text displayed for the value, see the module documentation for the format
+    fn text(&self, value: &str, number: Option<f64>) -> String {
+        let mut text = String::new();
+        let mut rest = self.format.as_str();
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let decimals = rest[start + 1..end].parse::<usize>().ok();
+            match (number, decimals) {
+                (Some(number), Some(decimals)) => text.push_str(&format!("{:.*}", decimals, number)),
+                _ => text.push_str(value),
+            }
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        text
+    }
+
+}
+
+/// tile indices of the text: the characters from space to underscore are drawn by the tile at their ASCII code and
+/// `\xNN` by the tile NN, the other characters are displayed as spaces
+fn text_tile_indices(text: &str) -> Vec<TileIndex> {
+    let mut tile_indices = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&'x') {
+            let digits = chars.clone().skip(1).take(2).collect::<String>();
+            if let Ok(tile_index) = TileIndex::from_str_radix(&digits, 16) {
+                tile_indices.push(tile_index);
+                chars.nth(2);
+                continue;
+            }
+        }
+        match c.to_ascii_uppercase() {
+            c @ ' '..='_' => tile_indices.push(c as TileIndex),
+            _ => tile_indices.push(' ' as TileIndex),
+        }
+    }
+    tile_indices
+}
+
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct Layout {
+    items: Vec<LayoutItem>,
+}
+
+impl Layout {
+
+    pub fn read<P: AsRef<Path>>(layout_file: P) -> Result<Self, SynthesizeError> {
+        Ok(fs_err::read_to_string(layout_file).map_err(SynthesizeError::LayoutFileError)?.parse()?)
+    }
+
+    /// tile indices of the OSD frame displaying the values of the telemetry at the OSD frame index
+    fn render(&self, telemetry: &AlignedTelemetry, frame_index: VideoFrameIndex) -> TileIndices {
+        let dimensions = KIND.dimensions_tiles();
+        let mut tile_indices = vec![0; (dimensions.width * dimensions.height) as usize];
+        let time = telemetry.time(frame_index as i64);
+        for item in &self.items {
+            let text = if item.item == FLY_TIME {
+                let arm_time = telemetry.log().arm_time().or_else(|| telemetry.log().start_time());
+                match arm_time {
+                    Some(arm_time) if time >= arm_time => {
+                        let fly_time = (time - arm_time) as u32;
+                        item.text(&format!("{:02}:{:02}", fly_time / 60, fly_time % 60), None)
+                    },
+                    _ => continue,
+                }
+            } else {
+                match telemetry.value_at(&item.item, frame_index as i64) {
+                    Some(value) => item.text(&format!("{:.*}", default_decimals(&item.item), value), Some(value)),
+                    None => continue,
+                }
+            };
+            for (offset, tile_index) in text_tile_indices(&text).into_iter().enumerate() {
+                let x = item.x as usize + offset;
+                if x >= dimensions.width as usize { break }
+                tile_indices[item.y as usize + x * dimensions.height as usize] = tile_index;
+            }
+        }
+        TileIndices::new(tile_indices)
+    }
+
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        DEFAULT_LAYOUT.parse().unwrap()
+    }
+}
+
+impl FromStr for Layout {
+    type Err = InvalidLayoutError;
+
+    fn from_str(layout: &str) -> Result<Self, Self::Err> {
+        let dimensions = KIND.dimensions_tiles();
+        let mut items = vec![];
+        for (line_index, line) in layout.lines().enumerate() {
+            let invalid = |reason: String| InvalidLayoutError { line: line_index + 1, reason };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+            let mut fields = line.splitn(3, char::is_whitespace);
+            let coordinates = fields.next().unwrap_or_default();
+            let item = fields.next().ok_or_else(|| invalid("no item".to_owned()))?;
+            let (x, y) = coordinates.split_once(',')
+                .and_then(|(x, y)| Some((x.parse::<Coordinate>().ok()?, y.parse::<Coordinate>().ok()?)))
+                .ok_or_else(|| invalid(format!("invalid coordinates: {coordinates}, expected <x>,<y>")))?;
+            if x as u32 >= dimensions.width || y as u32 >= dimensions.height {
+                return Err(invalid(format!("coordinates {x},{y} outside of the {dimensions} grid")));
+            }
+            if item != FLY_TIME && ! telemetry::SERIES_NAMES.contains(&item) {
+                return Err(invalid(format!("unknown item: {item}, expected {FLY_TIME} or one of {}", telemetry::SERIES_NAMES.join(", "))));
+            }
+            let format = fields.next().map(str::trim).filter(|format| ! format.is_empty()).unwrap_or("{}").to_owned();
+            items.push(LayoutItem { x, y, item: item.to_owned(), format });
+        }
+        Ok(Self { items })
+    }
+}
+
+/// OSD frames displaying the telemetry from the OSD frame 0 to the end of the telemetry, generated `update_rate`
+/// times per second
+pub fn synthesize(telemetry: &AlignedTelemetry, layout: &Layout, update_rate: u32) -> Result<Vec<Frame>, SynthesizeError> {
+    let end_time = telemetry.log().end_time().ok_or(SynthesizeError::EmptyTelemetry)?;
+    for item in layout.items() {
+        if item.item != FLY_TIME && ! telemetry.has_series(&item.item) {
+            log::warn!("the telemetry has no {} values, the item at {},{} will not be displayed", item.item, item.x, item.y);
+        }
+    }
+    let frame_step = (osd::FRAME_RATE / update_rate.clamp(1, osd::FRAME_RATE)) as usize;
+    let last_frame_index = ((end_time - telemetry.origin()).max(0.0) * osd::FRAME_RATE as f64) as VideoFrameIndex;
+    Ok((0..=last_frame_index).step_by(frame_step).map(|frame_index| Frame::new(frame_index, layout.render(telemetry, frame_index))).collect())
+}
+
+/// writes a DJI OSD file displaying the telemetry with the layout, returns the number of frames written
+pub fn write_osd_file<P: AsRef<Path>>(telemetry_log: TelemetryLog, telemetry_offset: f64, layout: &Layout, font_variant: FontVariant,
+        update_rate: u32, output_osd_file: P, overwrite: bool) -> Result<usize, SynthesizeError> {
+    let telemetry = AlignedTelemetry::new(telemetry_log, None, telemetry_offset);
+    let frames = synthesize(&telemetry, layout, update_rate)?;
+    let header = dji::file::FileHeader::new(KIND.dimensions_tiles(), KIND.tile_kind().dimensions(), font_variant);
+    let mut writer = dji::file::Writer::create(output_osd_file, &header, overwrite)?;
+    writer.write_frames(&frames)?;
+    writer.finish()?;
+    Ok(frames.len())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn default_layout() {
+        assert_eq!(Layout::default().items().len(), 5);
+    }
+
+    #[test]
+    fn item_text() {
+        let item = LayoutItem { x: 0, y: 0, item: telemetry::BATTERY_VOLTAGE.to_owned(), format: r"\x97{2}v {}".to_owned() };
+        let text = item.text("16.4", Some(16.376));
+        assert_eq!(text, r"\x9716.38v 16.4");
+        let mut tile_indices = vec![0x97];
+        tile_indices.extend("16.38V 16.4".bytes().map(TileIndex::from));
+        assert_eq!(text_tile_indices(&text), tile_indices);
+    }
+
+    #[test]
+    fn invalid_layout() {
+        assert!("2,1 unknown".parse::<Layout>().is_err());
+        assert!("60,1 altitude".parse::<Layout>().is_err());
+    }
+
+}
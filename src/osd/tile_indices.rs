@@ -1,7 +1,9 @@
 
-use std::{iter::Enumerate, ops::Index};
+use std::{iter::Enumerate, ops::{Index, Range}};
 
 use derive_more::Deref;
+use getset::CopyGetters;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::osd;
@@ -10,7 +12,8 @@ use super::{FontVariant, Dimensions, Kind};
 
 pub type TileIndex = u16;
 
-// frame payloads are always 1320*2=2640 bytes representing a 60x22 grid which corresponds to the FakeHD OSD format
+// default grid: DJI frame payloads are usually 1320*2=2640 bytes representing a 60x22 grid which corresponds to the FakeHD OSD format,
+// files recorded with the Betaflight 4.4+/INAV HD canvas layouts can instead store only the tiles of the canvas
 pub const DIMENSIONS: Dimensions = Kind::DJI_FakeHD.dimensions_tiles();
 pub const COUNT: usize = DIMENSIONS.width as usize * DIMENSIONS.height as usize;
 
@@ -25,32 +28,50 @@ impl UnknownOSDItem {
     pub fn new(font_variant: FontVariant, item_name: &str) -> Self { Self { font_variant, item_name: item_name.to_owned() } }
 }
 
-#[derive(Debug, Deref, Clone, PartialEq, Eq)]
-pub struct TileIndices(Vec<TileIndex>);
+/// parses the number in an OSD item text, e.g. `16.4` for the battery voltage or `-45.1234567` for a coordinate
+fn parse_osd_number(text: &str) -> Option<f64> {
+    let number = text.chars().filter(|c| c.is_ascii_digit() || *c == '.' || *c == '-').collect::<String>();
+    number.parse().ok()
+}
+
+/// tile indices of an OSD frame stored column by column in a grid
+#[derive(Debug, Deref, Clone, PartialEq, Eq, CopyGetters, Serialize, Deserialize)]
+pub struct TileIndices {
+    #[deref]
+    indices: Vec<TileIndex>,
+    #[getset(get_copy = "pub")]
+    #[serde(with = "crate::osd::dimensions_serde")]
+    grid_dimensions: Dimensions,
+}
 
 impl TileIndices {
 
+    /// tile indices stored in the default 60x22 grid
     pub fn new(inner: Vec<TileIndex>) -> Self {
-        Self(inner)
+        Self::with_grid_dimensions(inner, DIMENSIONS)
+    }
+
+    pub fn with_grid_dimensions(inner: Vec<TileIndex>, grid_dimensions: Dimensions) -> Self {
+        Self { indices: inner, grid_dimensions }
     }
 
-    fn screen_coordinates_to_index(x: osd::Coordinate, y: osd::Coordinate) -> usize {
-        y as usize + x as usize * DIMENSIONS.height as usize
+    fn screen_coordinates_to_index(&self, x: osd::Coordinate, y: osd::Coordinate) -> usize {
+        y as usize + x as usize * self.grid_dimensions.height as usize
     }
 
-    fn index_to_screen_coordinates(index: usize) -> osd::Coordinates {
+    fn index_to_screen_coordinates(grid_height: u32, index: usize) -> osd::Coordinates {
         osd::Coordinates::new(
-            (index / DIMENSIONS.height as usize) as osd::Coordinate,
-            (index % DIMENSIONS.height as usize) as osd::Coordinate
+            (index / grid_height as usize) as osd::Coordinate,
+            (index % grid_height as usize) as osd::Coordinate
         )
     }
 
     pub fn enumerate(&self) -> TileIndicesEnumeratorIter {
-        TileIndicesEnumeratorIter(self.iter().enumerate())
+        TileIndicesEnumeratorIter { iter: self.indices.iter().enumerate(), grid_height: self.grid_dimensions.height }
     }
 
     fn enumerate_mut(&mut self) -> TileIndicesEnumeratorIterMut {
-        TileIndicesEnumeratorIterMut(self.0.iter_mut().enumerate())
+        TileIndicesEnumeratorIterMut { iter: self.indices.iter_mut().enumerate(), grid_height: self.grid_dimensions.height }
     }
 
     pub fn erase_region(&mut self, region: &osd::Region) {
@@ -82,6 +103,35 @@ impl TileIndices {
         Ok(())
     }
 
+    /// text displayed by the first occurrence of the OSD item, `None` if the item is not displayed
+    ///
+    /// The region of the item is read line by line, empty tiles are skipped and the tiles without a corresponding
+    /// character are read as spaces.
+    pub fn osd_item_text(&self, font_variant: FontVariant, item_name: impl AsRef<str>) -> Result<Option<String>, UnknownOSDItem> {
+        let oild = font_variant.find_osd_item_location_data(item_name.as_ref())
+            .ok_or_else(|| UnknownOSDItem::new(font_variant, item_name.as_ref()))?;
+
+        let marker_coordinates = self.enumerate()
+            .find(|(_, tile_index)| oild.marker_tile_indices().contains(tile_index))
+            .map(|(coordinates, _)| coordinates);
+        let coordinates_range = match marker_coordinates {
+            Some(marker_coordinates) => oild.region(marker_coordinates).to_coordinates_range(),
+            None => return Ok(None),
+        };
+
+        let mut tiles = self.enumerate().filter(|(coordinates, _)| coordinates_range.contains(coordinates.clone())).collect::<Vec<_>>();
+        tiles.sort_by_key(|(coordinates, _)| (coordinates.y, coordinates.x));
+        let text = tiles.iter()
+            .map(|(_, tile_index)| font_variant.glyph_char(*tile_index).unwrap_or(' '))
+            .collect();
+        Ok(Some(text))
+    }
+
+    /// number displayed by the first occurrence of the OSD item, the symbols around the number are ignored
+    pub fn osd_item_value(&self, font_variant: FontVariant, item_name: impl AsRef<str>) -> Result<Option<f64>, UnknownOSDItem> {
+        Ok(self.osd_item_text(font_variant, item_name)?.as_deref().and_then(parse_osd_number))
+    }
+
     pub fn erase_osd_items(&mut self, font_variant: FontVariant, item_names: &[impl AsRef<str>]) -> Result<(), UnknownOSDItem> {
         for item_name in item_names {
             self.erase_osd_item(font_variant, item_name)?;
@@ -89,25 +139,90 @@ impl TileIndices {
         Ok(())
     }
 
+    /// Moves horizontally the runs of tiles of each line which are not within the range of columns into it
+    ///
+    /// A run is a sequence of tiles with gaps of at most one empty tile, e.g. an OSD item like `16.4V` or `12:34`.
+    /// The runs crossing the left edge of the range are aligned to its first column and the runs crossing its right
+    /// edge to its last column, the runs wider than the range are aligned to its first column. A run which would
+    /// overlap the run before it is moved right after it, separated by an empty tile, then the runs going past the
+    /// last column push the runs before them to the left as far as the range allows.
+    pub fn fit_columns(&mut self, columns: Range<osd::Coordinate>) {
+        let (width, height) = (self.grid_dimensions.width as usize, self.grid_dimensions.height as usize);
+        let (first, end) = (columns.start as usize, (columns.end as usize).min(width));
+        if first >= end { return }
+        for y in 0..height {
+            let line = (0..width).map(|x| self.indices.get(y + x * height).copied().unwrap_or(0)).collect::<Vec<_>>();
+            // runs with the column they are moved to
+            let mut runs = vec![];
+            let mut x = 0;
+            while x < width {
+                if line[x] == 0 { x += 1; continue }
+                let start = x;
+                while x < width && (line[x] != 0 || matches!(line.get(x + 1), Some(next) if *next != 0)) { x += 1 }
+                let new_start = if start < first {
+                    first
+                } else if x > end {
+                    end.saturating_sub(x - start).max(first)
+                } else {
+                    start
+                };
+                runs.push((start..x, new_start));
+            }
+            if runs.iter().all(|(run, new_start)| run.start == *new_start) { continue }
+
+            // the runs are laid out into an empty line so that the moved runs do not overwrite the other runs
+            runs.sort_by_key(|(run, new_start)| (*new_start, run.start));
+            let mut free_x = 0;
+            for (run, new_start) in &mut runs {
+                *new_start = (*new_start).max(free_x);
+                free_x = *new_start + run.len() + 1;
+            }
+            // the columns of the runs packed from the first column are the leftmost columns they can be moved to
+            let mut min_starts = Vec::with_capacity(runs.len());
+            let mut min_start = first;
+            for (run, _) in &runs {
+                min_starts.push(min_start);
+                min_start += run.len() + 1;
+            }
+            let mut limit = end;
+            for ((run, new_start), min_start) in runs.iter_mut().zip(min_starts).rev() {
+                *new_start = (*new_start).min(limit.saturating_sub(run.len())).max(min_start);
+                limit = new_start.saturating_sub(1);
+            }
+            let mut new_line = vec![0; width];
+            for (run, new_start) in runs {
+                for (offset, x) in run.enumerate() {
+                    if let Some(tile_index) = new_line.get_mut(new_start + offset) { *tile_index = line[x] }
+                }
+            }
+            for (x, new_tile_index) in new_line.into_iter().enumerate() {
+                if let Some(tile_index) = self.indices.get_mut(y + x * height) { *tile_index = new_tile_index }
+            }
+        }
+    }
+
 }
 
 impl Index<(osd::Coordinate, osd::Coordinate)> for TileIndices {
     type Output = TileIndex;
 
     fn index(&self, index: (osd::Coordinate, osd::Coordinate)) -> &Self::Output {
-        &self.0[Self::screen_coordinates_to_index(index.0, index.1)]
+        &self.indices[self.screen_coordinates_to_index(index.0, index.1)]
     }
 }
 
-pub struct TileIndicesEnumeratorIter<'a>(Enumerate<std::slice::Iter<'a, u16>>);
+pub struct TileIndicesEnumeratorIter<'a> {
+    iter: Enumerate<std::slice::Iter<'a, u16>>,
+    grid_height: u32,
+}
 
 impl<'a> Iterator for TileIndicesEnumeratorIter<'a> {
     type Item = (osd::Coordinates, TileIndex);
 
     fn next(&mut self) -> Option<Self::Item> {
-        for (tile_index_index, tile_index) in self.0.by_ref() {
+        for (tile_index_index, tile_index) in self.iter.by_ref() {
             if *tile_index > 0 {
-                let coordinates = TileIndices::index_to_screen_coordinates(tile_index_index);
+                let coordinates = TileIndices::index_to_screen_coordinates(self.grid_height, tile_index_index);
                 return Some((coordinates, *tile_index))
             }
         }
@@ -115,18 +230,53 @@ impl<'a> Iterator for TileIndicesEnumeratorIter<'a> {
     }
 }
 
-struct TileIndicesEnumeratorIterMut<'a>(Enumerate<std::slice::IterMut<'a, u16>>);
+struct TileIndicesEnumeratorIterMut<'a> {
+    iter: Enumerate<std::slice::IterMut<'a, u16>>,
+    grid_height: u32,
+}
 
 impl<'a> Iterator for TileIndicesEnumeratorIterMut<'a> {
     type Item = (osd::Coordinates, &'a mut TileIndex);
 
     fn next(&mut self) -> Option<Self::Item> {
-        for (tile_index_index, tile_index) in self.0.by_ref() {
+        for (tile_index_index, tile_index) in self.iter.by_ref() {
             if *tile_index > 0 {
-                let coordinates = TileIndices::index_to_screen_coordinates(tile_index_index);
+                let coordinates = TileIndices::index_to_screen_coordinates(self.grid_height, tile_index_index);
                 return Some((coordinates, tile_index))
             }
         }
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn fit_line_columns(tiles: &[TileIndex], columns: Range<osd::Coordinate>) -> Vec<TileIndex> {
+        let mut tile_indices = TileIndices::with_grid_dimensions(tiles.to_vec(), Dimensions::new(tiles.len() as u32, 1));
+        tile_indices.fit_columns(columns);
+        tile_indices.indices
+    }
+
+    #[test]
+    fn runs_in_the_columns_not_moved() {
+        let tiles = [0, 0, 1, 0, 2, 0, 0, 3, 4, 0, 0, 0];
+        assert_eq!(fit_line_columns(&tiles, 2..10), tiles);
+    }
+
+    #[test]
+    fn moved_runs_do_not_overwrite_the_adjacent_runs() {
+        assert_eq!(fit_line_columns(&[1, 2, 0, 0, 5, 6, 0, 0, 0, 0, 8, 9], 2..10), [0, 0, 1, 2, 0, 5, 6, 0, 8, 9, 0, 0]);
+        // the run going past the last column pushes the run before it to the left
+        assert_eq!(fit_line_columns(&[0, 0, 0, 0, 5, 6, 0, 0, 0, 8, 9, 0], 0..8), [0, 0, 0, 5, 6, 0, 8, 9, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn overlapping_moved_runs_laid_out_in_order() {
+        assert_eq!(fit_line_columns(&[1, 0, 0, 2, 3, 0, 0, 0, 0, 0, 0, 0], 6..12), [0, 0, 0, 0, 0, 0, 1, 0, 2, 3, 0, 0]);
+        assert_eq!(fit_line_columns(&[0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 2], 0..6), [0, 0, 0, 1, 0, 2, 0, 0, 0, 0, 0, 0]);
+    }
+
+}
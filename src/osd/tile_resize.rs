@@ -9,14 +9,18 @@ pub trait ResizeTiles {
     fn resized_tiles_par_with_progress(&self, new_dimensions: TileDimensions) -> Vec<tile::Image>;
 }
 
-impl ResizeTiles for &[Tile]
+impl ResizeTiles for &[tile::Image]
 {
     fn resized_tiles_par_with_progress(&self, new_dimensions: TileDimensions) -> Vec<tile::Image> {
         let tile_dimensions = self.first().unwrap().dimensions();
         log::info!("resizing {} tiles from {}x{} to {new_dimensions}", self.len(), tile_dimensions.0, tile_dimensions.1);
+        // integer upscaling factors keep the pixels sharp with the nearest filter
+        let integer_upscaling = new_dimensions.width % tile_dimensions.0 == 0 && new_dimensions.height % tile_dimensions.1 == 0 &&
+            new_dimensions.width / tile_dimensions.0 == new_dimensions.height / tile_dimensions.1;
+        let filter = if integer_upscaling { image::imageops::FilterType::Nearest } else { image::imageops::FilterType::Lanczos3 };
         let progress_style = ProgressStyle::with_template("{wide_bar} {pos:>6}/{len}").unwrap();
         self.par_iter().progress_with_style(progress_style).map(|tile|
-            image::imageops::resize(tile.image(), new_dimensions.width, new_dimensions.height, image::imageops::FilterType::Lanczos3)
+            image::imageops::resize(tile, new_dimensions.width, new_dimensions.height, filter)
         ).collect()
     }
 }
\ No newline at end of file
@@ -15,23 +15,26 @@ use byte_struct::*;
 use getset::{Getters, CopyGetters};
 use itertools::Itertools;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use lazy_static::lazy_static;
 use fs_err::File;
 
 use crate::{
     osd::{
+        self,
         Dimensions,
         FontVariant,
         file::{
             ReadError,
             Frame,
             sorted_frames::SortedUniqFrames,
-            GenericReader
+            GenericReader,
+            integrity::{Issue, Scan},
         },
         Kind,
         TileIndices,
-        TileIndex, tile_indices,
+        TileIndex,
     },
     video::FrameIndex as VideoFrameIndex,
 };
@@ -75,13 +78,14 @@ impl FileHeaderRaw {
 
 }
 
-#[derive(Debug, Getters, CopyGetters)]
+#[derive(Debug, Clone, Getters, CopyGetters, Serialize, Deserialize)]
 pub struct FileHeader {
     #[getset(get = "pub")]
     font_variant_id: String,
     #[getset(get_copy = "pub")]
     font_variant: FontVariant,
     #[getset(get_copy = "pub")]
+    #[serde(with = "crate::osd::dimensions_serde")]
     osd_dimensions: Dimensions,
 }
 
@@ -105,7 +109,7 @@ pub struct FrameRaw {
 
 impl FrameRaw {
     pub fn frame_index(&self) -> VideoFrameIndex {
-        (self.frame_timestamp as f64 * 60.0 / 1_000.0).round() as VideoFrameIndex
+        (self.frame_timestamp as f64 * osd::FRAME_RATE as f64 / 1_000.0).round() as VideoFrameIndex
     }
 }
 
@@ -139,6 +143,13 @@ impl Reader {
         Ok(Self { file, header })
     }
 
+    /// opens another reader of the same file positioned at the first frame
+    pub fn try_clone(&self) -> Result<Self, IOError> {
+        let mut file = File::open(self.file.path())?;
+        file.seek(SeekFrom::Start(FIRST_FRAME_FILE_POS))?;
+        Ok(Self { file, header: self.header.clone() })
+    }
+
     pub fn rewind(&mut self) -> Result<(), IOError> {
         self.file.seek(SeekFrom::Start(FIRST_FRAME_FILE_POS))?;
         Ok(())
@@ -167,18 +178,13 @@ impl GenericReader for Reader {
             FrameRaw::BYTE_LEN => FrameRaw::read_bytes(&frame_raw_bytes),
             _ => return Err(ReadError::unexpected_eof(self.file.path()))
         };
-        let mut tile_indices = Vec::with_capacity(tile_indices::COUNT);
-        let (x_range, y_range) = (0..DIMENSIONS.width as usize, 0..DIMENSIONS.height as usize);
-        for x in 0..tile_indices::DIMENSIONS.width as usize {
-            for y in 0..tile_indices::DIMENSIONS.height as usize {
-                if x_range.contains(&x) && y_range.contains(&y) {
-                    tile_indices.push(frame_raw.tile_indices[y][x]);
-                } else {
-                    tile_indices.push(0);
-                }
+        let mut tile_indices = Vec::with_capacity((DIMENSIONS.width * DIMENSIONS.height) as usize);
+        for x in 0..DIMENSIONS.width as usize {
+            for y in 0..DIMENSIONS.height as usize {
+                tile_indices.push(frame_raw.tile_indices[y][x]);
             }
         }
-        Ok(Some(Frame::new(frame_raw.frame_index(), TileIndices::new(tile_indices))))
+        Ok(Some(Frame::new(frame_raw.frame_index(), TileIndices::with_grid_dimensions(tile_indices, DIMENSIONS))))
     }
 
     fn frames(&mut self) -> Result<SortedUniqFrames, ReadError> {
@@ -258,20 +264,37 @@ impl<'a> IntoIterator for &'a mut Reader {
     }
 }
 
+/// checks the header and the frames of the bytes of a Walksnail Avatar OSD file, `None` if the header is not the one
+/// of a Walksnail Avatar OSD file
+///
+/// The frames have a fixed size so only the last one can be incomplete.
+pub(crate) fn scan(bytes: &[u8]) -> Option<Scan> {
+    let header_len = FileHeaderRaw::BYTE_LEN;
+    let header = FileHeader::from(FileHeaderRaw::read_bytes(bytes.get(..header_len)?));
+    if header.osd_dimensions != DIMENSIONS { return None }
+    let mut scan = Scan::new("Walksnail Avatar", header_len);
+    if header.font_variant == FontVariant::Unknown {
+        scan.issues.push(Issue::UnknownFontVariant(header.font_variant_id.trim_end_matches('\0').to_owned()));
+    }
+    let frame_count = (bytes.len() - header_len) / FrameRaw::BYTE_LEN;
+    scan.frames = (0..frame_count)
+        .map(|frame_index| header_len + frame_index * FrameRaw::BYTE_LEN..header_len + (frame_index + 1) * FrameRaw::BYTE_LEN)
+        .collect();
+    let frames_end = header_len + frame_count * FrameRaw::BYTE_LEN;
+    if frames_end < bytes.len() {
+        scan.issues.push(Issue::TruncatedFrame { offset: frames_end as u64, len: (bytes.len() - frames_end) as u64 });
+    }
+    Some(scan)
+}
+
 pub fn find_associated_to_video_file<P: AsRef<Path>>(video_file_path: P) -> Option<PathBuf> {
     let video_file_path = video_file_path.as_ref();
     let file_stem = video_file_path.file_stem()?.to_string_lossy();
-    lazy_static! { static ref DJI_VIDEO_FILE_RE: Regex = Regex::new(r"\A(?:Avatar(?:G|S)(\d{4}))").unwrap(); }
+    lazy_static! { static ref DJI_VIDEO_FILE_RE: Regex = Regex::new(r"(?i)\A(?:Avatar(?:G|S)(\d{4}))").unwrap(); }
 
     if let Some(captures) = DJI_VIDEO_FILE_RE.captures(&file_stem) {
         let dji_file_number = captures.get(1).unwrap().as_str();
-        let osd_file_path = video_file_path.with_file_name(format!("AvatarG{dji_file_number}")).with_extension("osd");
-        if osd_file_path.is_file() {
-            log::info!("found: {}", osd_file_path.to_string_lossy());
-            return Some(osd_file_path);
-        } else {
-            log::info!("not found: {}", osd_file_path.to_string_lossy());
-        }
+        return crate::osd::file::find_with_osd_extension(&video_file_path.with_file_name(format!("AvatarG{dji_file_number}")));
     }
 
     None
@@ -4,9 +4,13 @@ pub use crate::{
         transcode_video_args::TranscodeVideoArgs,
         generate_overlay_args::GenerateOverlayArgs,
         start_end_args::StartEndArgs,
+        audio_adjustment_args::AudioAdjustmentArgs,
+        trim_disarmed_args::TrimDisarmedArgs,
         transcode_video_args::TranscodeVideoOSDArgs,
+        overlay_video_encoder_args::OverlayVideoEncoderSpeedArgs,
     },
     file,
+    image::PngCompression,
     osd::{
         self,
         FontDir,
@@ -15,6 +19,8 @@ pub use crate::{
             file::{
                 OpenError as OSDFileOpenError,
                 Reader as OSDFileReader,
+                Writer as OSDFileWriter,
+                CreateError as OSDFileCreateError,
             },
         },
         overlay::{
@@ -26,6 +32,8 @@ pub use crate::{
                 ScalingArgs,
             },
             OverlayVideoCodec,
+            TileBlitMethod,
+            OverlayVideoEncoderSpeed,
         },
         region::{
             Region as OSDRegion,
@@ -39,6 +47,8 @@ pub use crate::{
     log_level::LogLevel,
     video::{
         self,
+        Transcoder,
+        transcoder::OSDSettings as TranscoderOSDSettings,
         AudioFixType as VideoAudioFixType,
         probe::Error as VideoProbingError,
     },
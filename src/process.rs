@@ -1,9 +1,168 @@
 
-use std::{ffi::OsStr, process, fmt::Display};
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+    process,
+    sync::{Mutex, atomic::{AtomicBool, Ordering}},
+};
 
 use derive_more::{Deref, DerefMut};
+use lazy_static::lazy_static;
 
 
+/// exit code of the process when interrupted with Ctrl-C, 128 + SIGINT like the shells
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// State of the whole process: the child processes and files being written which are cleaned up when the process is
+/// interrupted and the directory of the intermediate files, see [`crate::temp_dir`]
+#[derive(Default)]
+struct ProcessState {
+    child_process_ids: Vec<u32>,
+    partial_files: Vec<PathBuf>,
+    temp_dir: Option<PathBuf>,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<ProcessState> = Mutex::new(ProcessState::default());
+}
+
+/// in dry run mode the FFMpeg and MPV command lines are printed instead of being run, for the whole process
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_temp_dir(dir: PathBuf) {
+    STATE.lock().unwrap().temp_dir = Some(dir);
+}
+
+pub(crate) fn temp_dir() -> Option<PathBuf> {
+    STATE.lock().unwrap().temp_dir.clone()
+}
+
+/// prints a file which would be written in dry run mode, returns whether it should be written
+pub fn plan_file_write(path: &Path, description: &str) -> bool {
+    if dry_run() {
+        println!("# {description}: {}", path.to_string_lossy());
+    }
+    ! dry_run()
+}
+
+/// child process terminated when the process is interrupted, until unregistered
+pub fn register_child_process(process_id: u32) {
+    STATE.lock().unwrap().child_process_ids.push(process_id);
+}
+
+pub fn unregister_child_process(process_id: u32) {
+    STATE.lock().unwrap().child_process_ids.retain(|id| *id != process_id);
+}
+
+/// file being written, e.g. an output file or a temporary file, removed when the process is interrupted until
+/// unregistered once complete or removed, returns whether the file was not already registered
+pub fn register_partial_file<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    let mut cleanup = STATE.lock().unwrap();
+    let registered = cleanup.partial_files.iter().any(|partial_file| partial_file == path);
+    if ! registered {
+        cleanup.partial_files.push(path.to_path_buf());
+    }
+    ! registered
+}
+
+pub fn unregister_partial_file<P: AsRef<Path>>(path: P) {
+    STATE.lock().unwrap().partial_files.retain(|partial_file| partial_file != path.as_ref());
+}
+
+/// File written by this process, e.g. an OSD file or a CSV export, which is removed when the process is interrupted
+/// while the guard is alive
+pub struct PartialFile {
+    path: PathBuf,
+    registered: bool,
+}
+
+impl PartialFile {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let registered = register_partial_file(&path);
+        Self { path, registered }
+    }
+}
+
+impl Drop for PartialFile {
+    /// the file is complete or failed to be written, it is not removed anymore when interrupted
+    fn drop(&mut self) {
+        if self.registered {
+            unregister_partial_file(&self.path);
+        }
+    }
+}
+
+/// whether a process with the id is running, assumed to be running when it cannot be checked
+#[cfg(unix)]
+pub fn is_running(process_id: u32) -> bool {
+    let process_id = match libc::pid_t::try_from(process_id) {
+        Ok(process_id) if process_id > 0 => process_id,
+        _ => return false,
+    };
+    // SAFETY: the signal 0 is not sent, only the existence of the process is checked
+    let result = unsafe { libc::kill(process_id, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn is_running(_process_id: u32) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn terminate_process(process_id: u32) {
+    // SAFETY: only sends a signal, the process id is the one of a child process which has not been waited for
+    unsafe { libc::kill(process_id as libc::pid_t, libc::SIGTERM); }
+}
+
+/// the Ctrl-C event is sent to all the processes attached to the console, the child processes included
+#[cfg(not(unix))]
+fn terminate_process(_process_id: u32) {}
+
+/// terminates the child processes, removes the partial files then exits with [`INTERRUPTED_EXIT_CODE`]
+///
+/// Exiting does not run the `Drop` implementations, the files removed when dropped, like the temporary files and the
+/// watch lock file, must also be registered with [`register_partial_file`] to be removed when interrupted.
+fn interrupt() -> ! {
+    log::warn!("interrupted, cleaning up");
+    let cleanup = STATE.lock().unwrap();
+    for process_id in &cleanup.child_process_ids {
+        log::debug!("terminating child process {process_id}");
+        terminate_process(*process_id);
+    }
+    for partial_file in &cleanup.partial_files {
+        match std::fs::remove_file(partial_file) {
+            Ok(()) => log::info!("removed partial file: {}", partial_file.to_string_lossy()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {},
+            Err(error) => log::warn!("failed to remove partial file {}: {error}", partial_file.to_string_lossy()),
+        }
+    }
+    process::exit(INTERRUPTED_EXIT_CODE);
+}
+
+/// handles Ctrl-C for the whole process: the child processes are terminated and the partial files removed instead
+/// of being left behind, must be called from within the Tokio runtime
+pub fn handle_interrupt() {
+    tokio::spawn(async {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => interrupt(),
+            Err(error) => log::warn!("failed to handle Ctrl-C: {error}"),
+        }
+    });
+}
+
 #[derive(Deref, DerefMut)]
 pub struct Command(process::Command);
 
@@ -11,6 +170,15 @@ impl Command {
     pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
         Self(process::Command::new(program))
     }
+
+    /// spawns the process, in dry run mode only prints its command line and returns `None`
+    pub fn spawn_unless_dry_run(&mut self) -> io::Result<Option<process::Child>> {
+        if dry_run() {
+            println!("{self}");
+            return Ok(None);
+        }
+        self.spawn().map(Some)
+    }
 }
 
 impl Display for Command {
@@ -32,3 +200,26 @@ impl Display for Command {
         f.write_str(components.join(" ").as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn is_partial_file(path: &Path) -> bool {
+        STATE.lock().unwrap().partial_files.iter().any(|partial_file| partial_file == path)
+    }
+
+    #[test]
+    fn partial_file_registered_while_written() {
+        let path = Path::new("partial_file_registered_while_written.csv");
+        let partial_file = PartialFile::new(path);
+        assert!(is_partial_file(path));
+        // a file registered elsewhere stays registered until unregistered there
+        drop(PartialFile::new(path));
+        assert!(is_partial_file(path));
+        drop(partial_file);
+        assert!(! is_partial_file(path));
+    }
+
+}
@@ -0,0 +1,412 @@
+
+//! Flight telemetry recorded alongside the video: Betaflight blackbox logs, EdgeTX radio logs, CSV files or GPX tracks
+//!
+//! The telemetry is read into series of values over time named after the quantity they represent, e.g. [`ROLL`] or
+//! [`BATTERY_VOLTAGE`], whatever the source. The series are then time-aligned to the OSD frames by matching the
+//! arming of the quad in the telemetry with the arming seen in the OSD or heard in the audio of the video, and used
+//! by the overlay widgets drawn next to the OSD.
+
+use std::borrow::Borrow;
+use std::io::Error as IOError;
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+
+use crate::blackbox::{self, BlackboxError};
+use crate::ffmpeg;
+use crate::osd::{self, FontVariant, file::{Frame as OSDFileFrame, ReadError}, tile_indices::UnknownOSDItem};
+use crate::prelude::VideoProbingError;
+use crate::video::FrameIndex as VideoFrameIndex;
+
+pub mod csv;
+pub mod gpx;
+
+
+/// roll stick position from -1 (left) to 1 (right)
+pub const ROLL: &str = "roll";
+/// pitch stick position from -1 (back) to 1 (forward)
+pub const PITCH: &str = "pitch";
+/// yaw stick position from -1 (left) to 1 (right)
+pub const YAW: &str = "yaw";
+/// throttle stick position from 0 to 1
+pub const THROTTLE: &str = "throttle";
+/// altitude in meters
+pub const ALTITUDE: &str = "altitude";
+/// ground speed in km/h
+pub const SPEED: &str = "speed";
+/// link quality in percent
+pub const RSSI: &str = "rssi";
+/// battery voltage in volts
+pub const BATTERY_VOLTAGE: &str = "battery_voltage";
+
+/// names of the series which can be read from the telemetry
+pub const SERIES_NAMES: [&str; 8] = [ROLL, PITCH, YAW, THROTTLE, ALTITUDE, SPEED, RSSI, BATTERY_VOLTAGE];
+
+/// fly time OSD item, the timer starts when the quad is armed
+pub const FLY_TIME_OSD_ITEM: &str = "flytime";
+
+/// a value is not held further than this duration in seconds after its sample, e.g. after the end of the log
+const MAX_SAMPLE_HOLD: f64 = 1.0;
+
+/// number of OSD frames after which a fly time which did not change is considered stopped, it changes every second
+/// while armed
+const STOPPED_FLY_TIME_FRAMES: i64 = osd::FRAME_RATE as i64 * 3 / 2;
+
+/// sample rate of the audio analyzed to detect the arm beep
+const BEEP_DETECTION_SAMPLE_RATE: u32 = 8000;
+/// duration of the audio windows whose level is compared to detect the arm beep in seconds
+const BEEP_DETECTION_WINDOW: f64 = 0.02;
+
+#[derive(Debug, Error, From)]
+pub enum TelemetryError {
+    #[error("failed reading telemetry file: {0}")]
+    IOError(IOError),
+    #[error(transparent)]
+    BlackboxError(BlackboxError),
+    #[error("invalid telemetry CSV file, line {line}: {reason}")]
+    #[from(ignore)]
+    InvalidCsv { line: usize, reason: String },
+    #[error("invalid GPX file: {0}")]
+    #[from(ignore)]
+    InvalidGpx(String),
+    #[error("unsupported telemetry file: {}, expected a .bbl/.bfl blackbox log, a .csv file or a .gpx track", .0.to_string_lossy())]
+    #[from(ignore)]
+    UnsupportedFile(PathBuf),
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+    #[error("OSD file read error: {0}")]
+    OSDFileReadError(ReadError),
+    #[error("failed to get input video details")]
+    FailedToGetInputVideoDetails(VideoProbingError),
+    #[error(transparent)]
+    FailedReadingAudio(ffmpeg::ReadAudioError),
+}
+
+/// values of a quantity over time, the time is in seconds in the time base of the telemetry source
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct Series {
+    name: String,
+    /// time and value sorted by time
+    points: Vec<(f64, f64)>,
+}
+
+impl Series {
+
+    pub fn new<S: Into<String>>(name: S, mut points: Vec<(f64, f64)>) -> Self {
+        points.sort_by(|(time, _), (other_time, _)| time.partial_cmp(other_time).unwrap_or(std::cmp::Ordering::Equal));
+        Self { name: name.into(), points }
+    }
+
+    /// last value sampled before the time, `None` before the first sample or long after the last one
+    pub fn value_at(&self, time: f64) -> Option<f64> {
+        let index = self.points.partition_point(|(point_time, _)| *point_time <= time).checked_sub(1)?;
+        let (point_time, value) = self.points[index];
+        if index == self.points.len() - 1 && time - point_time > MAX_SAMPLE_HOLD { return None }
+        Some(value)
+    }
+
+}
+
+/// Telemetry of a flight read from a telemetry file
+#[derive(Debug, Clone, Default, Getters, CopyGetters)]
+pub struct TelemetryLog {
+    #[getset(get = "pub")]
+    series: Vec<Series>,
+    /// time the quad was armed if found in the telemetry
+    #[getset(get_copy = "pub")]
+    arm_time: Option<f64>,
+}
+
+impl TelemetryLog {
+
+    pub fn new(series: Vec<Series>, arm_time: Option<f64>) -> Self {
+        let series = series.into_iter().filter(|series| ! series.points.is_empty()).collect();
+        Self { series, arm_time }
+    }
+
+    pub fn find_series(&self, name: &str) -> Option<&Series> {
+        self.series.iter().find(|series| series.name == name)
+    }
+
+    /// time of the first sample
+    pub fn start_time(&self) -> Option<f64> {
+        self.series.iter().filter_map(|series| series.points.first().map(|(time, _)| *time)).reduce(f64::min)
+    }
+
+    /// time of the last sample
+    pub fn end_time(&self) -> Option<f64> {
+        self.series.iter().filter_map(|series| series.points.last().map(|(time, _)| *time)).reduce(f64::max)
+    }
+
+    /// telemetry of the blackbox log, the `time` field is in microseconds
+    pub fn from_blackbox(log: &blackbox::Log) -> Result<Self, TelemetryError> {
+        let times = log.field_values("time")?.map(|time| time as f64 / 1_000_000.0).collect::<Vec<_>>();
+        let series = |name: &str, field_name: &str, to_value: &dyn Fn(i64) -> f64| -> Option<Series> {
+            let values = log.field_values(field_name).ok()?;
+            Some(Series::new(name, times.iter().copied().zip(values.map(to_value)).collect()))
+        };
+        // the rcCommand are ±500 around the center for roll, pitch and yaw and from 1000 to 2000 for the throttle,
+        // the yaw command is negative when the stick is moved to the right
+        let series = [
+            series(ROLL, "rcCommand[0]", &|value| (value as f64 / 500.0).clamp(-1.0, 1.0)),
+            series(PITCH, "rcCommand[1]", &|value| (value as f64 / 500.0).clamp(-1.0, 1.0)),
+            series(YAW, "rcCommand[2]", &|value| (-value as f64 / 500.0).clamp(-1.0, 1.0)),
+            series(THROTTLE, "rcCommand[3]", &|value| ((value - 1000) as f64 / 1000.0).clamp(0.0, 1.0)),
+            series(BATTERY_VOLTAGE, "vbatLatest", &|value| value as f64 / 100.0),
+            series(ALTITUDE, "baroAlt", &|value| value as f64 / 100.0),
+            series(RSSI, "rssi", &|value| value as f64 * 100.0 / 1023.0),
+        ].into_iter().flatten().collect();
+        Ok(Self::new(series, log.arm_time().map(|arm_time| arm_time as f64 / 1_000_000.0)))
+    }
+
+}
+
+/// reads the telemetry file, the log index starting at 1 is used for the blackbox files containing several logs
+pub fn read<P: AsRef<Path>>(telemetry_file: P, log_index: usize) -> Result<TelemetryLog, TelemetryError> {
+    let telemetry_file = telemetry_file.as_ref();
+    let extension = telemetry_file.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("bbl" | "bfl") => TelemetryLog::from_blackbox(&blackbox::read_log(telemetry_file, log_index)?),
+        Some("csv") => csv::parse(&fs_err::read_to_string(telemetry_file)?),
+        Some("gpx") => gpx::parse(&fs_err::read_to_string(telemetry_file)?),
+        _ => Err(TelemetryError::UnsupportedFile(telemetry_file.to_path_buf())),
+    }
+}
+
+/// how the telemetry is time-aligned to the OSD
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetrySync {
+    /// arming seen in the OSD: start of the fly time displayed by the OSD
+    OsdArm,
+    /// arming heard in the audio of the video: first beep standing out of the background noise
+    ArmBeep,
+    /// start of the telemetry aligned with the start of the OSD
+    Start,
+}
+
+impl Default for TelemetrySync {
+    fn default() -> Self {
+        Self::OsdArm
+    }
+}
+
+/// Settings used to read the telemetry and time-align it to the OSD
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct TelemetrySettings {
+    telemetry_file: PathBuf,
+    /// 1 based index of the log in the blackbox file
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    log_index: usize,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    sync: TelemetrySync,
+    /// seconds added to the telemetry time matched with the OSD
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    offset: f64,
+    /// OSD frame index of the arm beep once detected in the audio of the video
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    arm_beep_frame_index: Option<i64>,
+}
+
+impl TelemetrySettings {
+
+    pub fn new<P: AsRef<Path>>(telemetry_file: P) -> Self {
+        Self {
+            telemetry_file: telemetry_file.as_ref().to_path_buf(),
+            log_index: 1,
+            sync: TelemetrySync::default(),
+            offset: 0.0,
+            arm_beep_frame_index: None,
+        }
+    }
+
+}
+
+/// parses a timer displayed by the OSD, e.g. `01:23`, into seconds
+pub(crate) fn parse_timer(text: &str) -> Option<u32> {
+    let (minutes, seconds) = text.trim().split_once(':')?;
+    let digits = |text: &str| text.chars().filter(char::is_ascii_digit).collect::<String>().parse::<u32>().ok();
+    Some(digits(minutes)? * 60 + digits(seconds)?)
+}
+
+/// OSD frame index at which the quad was armed for the flight of the recording, found from the first change of the
+/// fly time displayed by the OSD, `None` if the fly time is not displayed or does not change
+///
+/// The fly time adds up the time armed since the flight controller was powered so it does not start from zero after
+/// the first flight of a battery. When the fly time was stopped before changing the quad was armed when it started
+/// again, otherwise the quad was already armed when the recording started and the fly time is assumed to be the
+/// one of the current flight. The frames are read one by one so that they do not need to be loaded.
+pub fn osd_arm_frame_index<F: Borrow<OSDFileFrame>>(frames: impl IntoIterator<Item = Result<F, ReadError>>, font_variant: FontVariant)
+        -> Result<Option<i64>, TelemetryError> {
+    if font_variant.find_osd_item_location_data(FLY_TIME_OSD_ITEM).is_none() { return Ok(None) }
+    // fly time displayed and OSD frame index from which it is displayed
+    let mut displayed_fly_time: Option<(u32, i64)> = None;
+    for frame in frames {
+        let frame = frame?;
+        let frame = frame.borrow();
+        let fly_time = match frame.osd_item_text(font_variant, FLY_TIME_OSD_ITEM)?.as_deref().and_then(parse_timer) {
+            Some(fly_time) => fly_time,
+            None => continue,
+        };
+        let frame_index = frame.index() as i64;
+        match displayed_fly_time {
+            Some((previous_fly_time, since)) if previous_fly_time != fly_time => {
+                let arm_frame_index = if frame_index - since > STOPPED_FLY_TIME_FRAMES {
+                    frame_index - fly_time.saturating_sub(previous_fly_time) as i64 * osd::FRAME_RATE as i64
+                } else {
+                    log::info!("the OSD fly time is running from the start of the recording, assuming it is the fly time of the current flight");
+                    frame_index - fly_time as i64 * osd::FRAME_RATE as i64
+                };
+                return Ok(Some(arm_frame_index));
+            },
+            Some(_) => {},
+            None => displayed_fly_time = Some((fly_time, frame_index)),
+        }
+    }
+    Ok(None)
+}
+
+/// Time of the first beep in the audio of the video in seconds, `None` if no beep stands out
+///
+/// The audio is high-pass filtered to keep the beeper and motor beeps then the level of short windows is compared
+/// to the median level, the first window much louder than the median and followed by another loud window is the
+/// beep. Nothing is detected when the video has no audio stream or in dry run mode since FFMpeg is not run.
+pub async fn detect_arm_beep<P: AsRef<Path>>(video_file: P) -> Result<Option<f64>, TelemetryError> {
+    let video_file = video_file.as_ref();
+    if crate::video::probe(video_file)?.audio().is_none() {
+        log::warn!("the video has no audio stream to detect the arm beep in");
+        return Ok(None);
+    }
+    let samples = ffmpeg::read_audio_samples(video_file, BEEP_DETECTION_SAMPLE_RATE, Some("highpass=f=1500")).await?;
+    Ok(beep_time(&samples))
+}
+
+/// time of the first beep in the high-pass filtered samples in seconds
+fn beep_time(samples: &[f32]) -> Option<f64> {
+    let window_length = (BEEP_DETECTION_SAMPLE_RATE as f64 * BEEP_DETECTION_WINDOW) as usize;
+    let levels = samples.chunks_exact(window_length)
+        .map(|window| (window.iter().map(|sample| sample * sample).sum::<f32>() / window_length as f32).sqrt())
+        .collect::<Vec<_>>();
+    if levels.len() < 2 { return None }
+    let mut sorted_levels = levels.clone();
+    sorted_levels.sort_by(|level, other_level| level.partial_cmp(other_level).unwrap_or(std::cmp::Ordering::Equal));
+    // beeps are about 20dB above the background noise
+    let threshold = (sorted_levels[sorted_levels.len() / 2] * 8.0).max(0.01);
+    let beep_window = levels.windows(2).position(|windows| windows[0] > threshold && windows[1] > threshold / 2.0);
+    beep_window.map(|window_index| window_index as f64 * BEEP_DETECTION_WINDOW)
+}
+
+/// Telemetry time-aligned to the OSD frames
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct AlignedTelemetry {
+    #[getset(get = "pub")]
+    log: TelemetryLog,
+    /// telemetry time of the OSD frame 0
+    #[getset(get_copy = "pub")]
+    origin: f64,
+}
+
+impl AlignedTelemetry {
+
+    /// aligns the telemetry with the OSD by matching the arming time of the telemetry, or its start when the arming
+    /// is not found, with the arming OSD frame index, or with the start of the OSD when `None`
+    pub fn new(log: TelemetryLog, osd_arm_frame_index: Option<i64>, offset: f64) -> Self {
+        let (telemetry_reference, osd_reference) = match (osd_arm_frame_index, log.arm_time()) {
+            (Some(osd_arm_frame_index), Some(arm_time)) => (arm_time, osd_arm_frame_index),
+            (Some(osd_arm_frame_index), None) => {
+                log::info!("arming not found in the telemetry, assuming the telemetry started when armed");
+                (log.start_time().unwrap_or(0.0), osd_arm_frame_index)
+            },
+            (None, _) => (log.start_time().unwrap_or(0.0), 0),
+        };
+        let origin = telemetry_reference - osd_reference as f64 / osd::FRAME_RATE as f64 + offset;
+        Self { log, origin }
+    }
+
+    /// telemetry time at the OSD frame
+    pub fn time(&self, osd_frame_index: i64) -> f64 {
+        self.origin + osd_frame_index as f64 / osd::FRAME_RATE as f64
+    }
+
+    pub fn has_series(&self, name: &str) -> bool {
+        self.log.find_series(name).is_some()
+    }
+
+    /// value of the series at the OSD frame
+    pub fn value_at(&self, name: &str, osd_frame_index: i64) -> Option<f64> {
+        self.log.find_series(name)?.value_at(self.time(osd_frame_index))
+    }
+
+    /// values of the series at the OSD frame indices when they change, at most one per frame and the values before
+    /// the start of the OSD are skipped
+    pub fn frame_points(&self, name: &str) -> Vec<(VideoFrameIndex, f64)> {
+        let mut points: Vec<(VideoFrameIndex, f64)> = vec![];
+        let series = match self.log.find_series(name) {
+            Some(series) => series,
+            None => return points,
+        };
+        for (time, value) in &series.points {
+            let frame_index = ((time - self.origin) * osd::FRAME_RATE as f64).round();
+            if frame_index < 0.0 { continue }
+            let frame_index = frame_index as VideoFrameIndex;
+            match points.last_mut() {
+                Some(last) if last.0 == frame_index => last.1 = *value,
+                Some(last) if last.1 == *value => (),
+                _ => points.push((frame_index, *value)),
+            }
+        }
+        points
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::osd::{Dimensions, tile_indices::{TileIndex, TileIndices}};
+
+    /// frame of a single line grid displaying the Betaflight fly time item
+    fn fly_time_frame(index: u32, fly_time: &str) -> Result<OSDFileFrame, ReadError> {
+        let mut tiles = vec![0x9C];
+        tiles.extend(fly_time.bytes().map(TileIndex::from));
+        tiles.resize(8, 0);
+        Ok(OSDFileFrame::new(index, TileIndices::with_grid_dimensions(tiles, Dimensions::new(8, 1))))
+    }
+
+    #[test]
+    fn arm_found_when_the_fly_time_starts_again() {
+        // the fly time of a previous flight is displayed for 5s before the timer starts again
+        let frames = vec![fly_time_frame(0, "1:10"), fly_time_frame(150, "1:10"), fly_time_frame(300, "1:10"), fly_time_frame(360, "1:11")];
+        assert_eq!(osd_arm_frame_index(frames, FontVariant::Betaflight).unwrap(), Some(300));
+    }
+
+    #[test]
+    fn arm_found_from_the_fly_time_when_already_running() {
+        let frames = vec![fly_time_frame(0, "0:05"), fly_time_frame(40, "0:06")];
+        assert_eq!(osd_arm_frame_index(frames, FontVariant::Betaflight).unwrap(), Some(40 - 6 * 60));
+        let frames = vec![fly_time_frame(0, "0:05"), fly_time_frame(40, "0:05")];
+        assert_eq!(osd_arm_frame_index(frames, FontVariant::Betaflight).unwrap(), None);
+    }
+
+    #[test]
+    fn beep_found_above_the_background_noise() {
+        let sample_rate = BEEP_DETECTION_SAMPLE_RATE as usize;
+        // 1s of quiet noise with a 100ms beep from 0.5s
+        let samples = (0..sample_rate).map(|index| {
+            let level = if (sample_rate / 2..sample_rate * 6 / 10).contains(&index) { 0.5 } else { 0.001 };
+            if index % 2 == 0 { level } else { -level }
+        }).collect::<Vec<f32>>();
+        assert!(matches!(beep_time(&samples), Some(time) if (time - 0.5).abs() < 1e-9));
+        assert_eq!(beep_time(&samples[..sample_rate / 2]), None);
+        assert_eq!(beep_time(&[]), None);
+    }
+
+}
@@ -0,0 +1,171 @@
+
+//! Telemetry CSV files: EdgeTX/OpenTX radio logs or generic files with a `time` column in seconds
+//!
+//! EdgeTX logs are recognized from their `Date` and `Time` columns, the sticks are read from the `Ail`, `Ele`, `Rud`
+//! and `Thr` channels and the arming from the flight mode reported by Betaflight which is suffixed with `*` while
+//! disarmed. The other columns of generic files are named after the telemetry series, e.g. `altitude` or
+//! `battery_voltage`, or after the OSD items, e.g. `alt` or `bat`, and an optional `armed` column flags the rows
+//! recorded while armed. The values are not quoted.
+
+use super::{ALTITUDE, BATTERY_VOLTAGE, PITCH, ROLL, RSSI, SERIES_NAMES, SPEED, Series, TelemetryError, TelemetryLog, THROTTLE, YAW};
+
+
+/// EdgeTX channel values range from -1024 to 1024
+const EDGETX_CHANNEL_RANGE: f64 = 1024.0;
+
+/// EdgeTX columns and the series they are read into with the conversion of their value
+const EDGETX_COLUMNS: [(&str, &str, fn(f64) -> f64); 9] = [
+    ("Ail", ROLL, edgetx_stick),
+    ("Ele", PITCH, edgetx_stick),
+    ("Rud", YAW, edgetx_stick),
+    ("Thr", THROTTLE, edgetx_throttle),
+    ("RxBt(V)", BATTERY_VOLTAGE, identity),
+    ("Alt(m)", ALTITUDE, identity),
+    ("GSpd(kmh)", SPEED, identity),
+    ("GSpd(kts)", SPEED, knots_to_kmh),
+    ("RQly(%)", RSSI, identity),
+];
+
+/// OSD item names accepted as generic columns besides the series names
+const OSD_ITEM_COLUMNS: [(&str, &str); 2] = [("alt", ALTITUDE), ("bat", BATTERY_VOLTAGE)];
+
+fn identity(value: f64) -> f64 { value }
+fn edgetx_stick(value: f64) -> f64 { (value / EDGETX_CHANNEL_RANGE).clamp(-1.0, 1.0) }
+fn edgetx_throttle(value: f64) -> f64 { ((value / EDGETX_CHANNEL_RANGE + 1.0) / 2.0).clamp(0.0, 1.0) }
+fn knots_to_kmh(value: f64) -> f64 { value * 1.852 }
+
+/// parses a time of day like `14:23:45.120` into seconds
+fn parse_time_of_day(value: &str) -> Option<f64> {
+    let mut parts = value.trim().split(':');
+    let hours = parts.next()?.parse::<u32>().ok()?;
+    let minutes = parts.next()?.parse::<u32>().ok()?;
+    let seconds = parts.next()?.parse::<f64>().ok()?;
+    if parts.next().is_some() { return None }
+    Some((hours * 3600 + minutes * 60) as f64 + seconds)
+}
+
+struct Table<'a> {
+    header: Vec<&'a str>,
+    /// line number and values of the rows
+    rows: Vec<(usize, Vec<&'a str>)>,
+}
+
+impl<'a> Table<'a> {
+
+    fn parse(content: &'a str) -> Result<Self, TelemetryError> {
+        let mut lines = content.lines().enumerate().filter(|(_, line)| ! line.trim().is_empty());
+        let header = match lines.next() {
+            Some((_, header)) => header.split(',').map(str::trim).collect(),
+            None => return Err(TelemetryError::InvalidCsv { line: 1, reason: "no header".to_owned() }),
+        };
+        let rows = lines.map(|(line_index, line)| (line_index + 1, line.split(',').map(str::trim).collect())).collect();
+        Ok(Self { header, rows })
+    }
+
+    fn column_index(&self, name: &str) -> Option<usize> {
+        self.header.iter().position(|column_name| column_name.eq_ignore_ascii_case(name))
+    }
+
+    /// values of the column for the rows with a numeric value, paired with the time of the row
+    fn series(&self, name: &str, times: &[f64], column_index: usize, to_value: fn(f64) -> f64) -> Series {
+        let points = self.rows.iter().zip(times)
+            .filter_map(|((_, row), time)| Some((*time, to_value(row.get(column_index)?.parse::<f64>().ok()?))))
+            .collect();
+        Series::new(name, points)
+    }
+
+}
+
+fn parse_edgetx(table: &Table, time_column_index: usize) -> Result<TelemetryLog, TelemetryError> {
+    let mut previous_time = None;
+    let mut day_offset = 0.0;
+    let times = table.rows.iter().map(|(line, row)| {
+        let time = row.get(time_column_index).and_then(|value| parse_time_of_day(value))
+            .ok_or_else(|| TelemetryError::InvalidCsv { line: *line, reason: "invalid time".to_owned() })?;
+        // the time of day wraps around at midnight
+        if matches!(previous_time, Some(previous_time) if time + day_offset < previous_time) { day_offset += 86400.0 }
+        previous_time = Some(time + day_offset);
+        Ok(time + day_offset)
+    }).collect::<Result<Vec<_>, TelemetryError>>()?;
+
+    let mut series: Vec<Series> = vec![];
+    for (column_name, series_name, to_value) in EDGETX_COLUMNS {
+        if series.iter().any(|series| series.name() == series_name) { continue }
+        if let Some(column_index) = table.column_index(column_name) {
+            series.push(table.series(series_name, &times, column_index, to_value));
+        }
+    }
+
+    let arm_time = table.column_index("FM").and_then(|flight_mode_index| {
+        table.rows.iter().zip(&times).find_map(|((_, row), time)| {
+            let flight_mode = row.get(flight_mode_index)?;
+            if flight_mode.is_empty() || flight_mode.ends_with('*') { None } else { Some(*time) }
+        })
+    });
+
+    Ok(TelemetryLog::new(series, arm_time))
+}
+
+fn parse_generic(table: &Table) -> Result<TelemetryLog, TelemetryError> {
+    let time_column_index = table.column_index("time")
+        .ok_or_else(|| TelemetryError::InvalidCsv { line: 1, reason: "no time column".to_owned() })?;
+    let times = table.rows.iter().map(|(line, row)| {
+        let time = row.get(time_column_index).and_then(|value| value.parse::<f64>().ok())
+            .ok_or_else(|| TelemetryError::InvalidCsv { line: *line, reason: "invalid time".to_owned() })?;
+        Ok(time)
+    }).collect::<Result<Vec<_>, TelemetryError>>()?;
+
+    let columns = SERIES_NAMES.iter().map(|name| (*name, *name)).chain(OSD_ITEM_COLUMNS);
+    let mut series: Vec<Series> = vec![];
+    for (column_name, series_name) in columns {
+        if series.iter().any(|series| series.name() == series_name) { continue }
+        if let Some(column_index) = table.column_index(column_name) {
+            series.push(table.series(series_name, &times, column_index, identity));
+        }
+    }
+
+    let arm_time = table.column_index("armed").and_then(|armed_index| {
+        table.rows.iter().zip(&times).find_map(|((_, row), time)| {
+            let armed = row.get(armed_index)?.parse::<f64>().ok()?;
+            if armed != 0.0 { Some(*time) } else { None }
+        })
+    });
+
+    Ok(TelemetryLog::new(series, arm_time))
+}
+
+pub fn parse(content: &str) -> Result<TelemetryLog, TelemetryError> {
+    let table = Table::parse(content)?;
+    match (table.column_index("Date"), table.column_index("Time")) {
+        (Some(_), Some(time_column_index)) => parse_edgetx(&table, time_column_index),
+        _ => parse_generic(&table),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_edgetx_log() {
+        let content = "Date,Time,1RSS(dB),RQly(%),RxBt(V),FM,Rud,Ele,Thr,Ail\n\
+            2023-06-01,23:59:59.500,-60,100,16.8,ACRO*,0,0,-1024,0\n\
+            2023-06-01,23:59:59.750,-60,100,16.7,ACRO,512,0,0,-1024\n\
+            2023-06-02,00:00:00.250,-61,98,16.5,ACRO,0,1024,1024,0\n";
+        let log = parse(content).unwrap();
+        let start = 86399.5;
+        assert_eq!(log.arm_time(), Some(start + 0.25));
+        assert_eq!(log.find_series(THROTTLE).unwrap().value_at(start), Some(0.0));
+        assert_eq!(log.find_series(YAW).unwrap().value_at(start + 0.5), Some(0.5));
+        assert_eq!(log.find_series(ROLL).unwrap().value_at(start + 0.5), Some(-1.0));
+        assert_eq!(log.find_series(PITCH).unwrap().value_at(start + 0.8), Some(1.0));
+        assert_eq!(log.find_series(RSSI).unwrap().value_at(start + 0.8), Some(98.0));
+        assert_eq!(log.find_series(BATTERY_VOLTAGE).unwrap().value_at(start - 0.1), None);
+
+        // the RSSI in dB is not a link quality in percent
+        let log = parse("Date,Time,1RSS(dB)\n2023-06-01,12:00:00.000,-60\n").unwrap();
+        assert!(log.find_series(RSSI).is_none());
+    }
+
+}
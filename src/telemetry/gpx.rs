@@ -0,0 +1,98 @@
+
+//! GPX tracks recorded by GPS loggers, phones or action cameras
+//!
+//! The track points are read with their elevation into the altitude series and the ground speed is calculated from
+//! the distance between consecutive points. The time is in seconds since the Unix epoch. GPX tracks do not record the
+//! arming so the telemetry is aligned on the start of the track.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::date_time;
+
+use super::{ALTITUDE, SPEED, Series, TelemetryError, TelemetryLog};
+
+
+/// mean radius of the Earth in meters
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+lazy_static! {
+    static ref TRACK_POINT_REGEX: Regex = Regex::new(r"(?s)<trkpt\b([^>]*)>(.*?)</trkpt>").unwrap();
+    static ref LATITUDE_REGEX: Regex = Regex::new(r#"\blat\s*=\s*["']([^"']+)["']"#).unwrap();
+    static ref LONGITUDE_REGEX: Regex = Regex::new(r#"\blon\s*=\s*["']([^"']+)["']"#).unwrap();
+    static ref ELEVATION_REGEX: Regex = Regex::new(r"<ele>\s*([^<]+?)\s*</ele>").unwrap();
+    static ref TIME_REGEX: Regex = Regex::new(r"<time>\s*([^<]+?)\s*</time>").unwrap();
+}
+
+struct TrackPoint {
+    time: f64,
+    latitude: f64,
+    longitude: f64,
+    elevation: Option<f64>,
+}
+
+/// great-circle distance in meters between two points
+fn distance(point: &TrackPoint, other_point: &TrackPoint) -> f64 {
+    let (latitude, other_latitude) = (point.latitude.to_radians(), other_point.latitude.to_radians());
+    let half_chord = ((other_latitude - latitude) / 2.0).sin().powi(2) +
+        latitude.cos() * other_latitude.cos() * ((other_point.longitude - point.longitude).to_radians() / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * half_chord.sqrt().asin()
+}
+
+fn parse_track_points(content: &str) -> Result<Vec<TrackPoint>, TelemetryError> {
+    let mut track_points = vec![];
+    for (index, captures) in TRACK_POINT_REGEX.captures_iter(content).enumerate() {
+        let invalid = |reason: &str| TelemetryError::InvalidGpx(format!("track point {}: {reason}", index + 1));
+        let coordinate = |regex: &Regex| regex.captures(&captures[1]).and_then(|captures| captures[1].trim().parse::<f64>().ok());
+        // the track points without time cannot be placed in the telemetry
+        let time = match TIME_REGEX.captures(&captures[2]) {
+            Some(time) => date_time::parse_iso8601(&time[1]).ok_or_else(|| invalid("invalid time"))?,
+            None => continue,
+        };
+        track_points.push(TrackPoint {
+            time,
+            latitude: coordinate(&LATITUDE_REGEX).ok_or_else(|| invalid("invalid latitude"))?,
+            longitude: coordinate(&LONGITUDE_REGEX).ok_or_else(|| invalid("invalid longitude"))?,
+            elevation: ELEVATION_REGEX.captures(&captures[2]).and_then(|elevation| elevation[1].parse::<f64>().ok()),
+        });
+    }
+    Ok(track_points)
+}
+
+pub fn parse(content: &str) -> Result<TelemetryLog, TelemetryError> {
+    let mut track_points = parse_track_points(content)?;
+    if track_points.is_empty() { return Err(TelemetryError::InvalidGpx("no track point with a time".to_owned())) }
+    track_points.sort_by(|point, other_point| point.time.partial_cmp(&other_point.time).unwrap_or(std::cmp::Ordering::Equal));
+
+    let altitude = track_points.iter().filter_map(|point| Some((point.time, point.elevation?))).collect();
+    let speed = track_points.windows(2).filter_map(|points| {
+        let duration = points[1].time - points[0].time;
+        if duration <= 0.0 { return None }
+        Some((points[1].time, distance(&points[0], &points[1]) / duration * 3.6))
+    }).collect();
+
+    Ok(TelemetryLog::new(vec![Series::new(ALTITUDE, altitude), Series::new(SPEED, speed)], None))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_track() {
+        let content = r#"<?xml version="1.0"?>
+            <gpx><trk><trkseg>
+                <trkpt lat="46.0" lon="7.0"><ele>1000.5</ele><time>2023-06-01T14:23:45Z</time></trkpt>
+                <trkpt lon="7.0" lat="46.001"><ele>1002</ele><time>2023-06-01T16:23:46+02:00</time></trkpt>
+            </trkseg></trk></gpx>"#;
+        let log = parse(content).unwrap();
+        let start = 1_685_629_425.0;
+        assert_eq!(log.start_time(), Some(start));
+        assert_eq!(log.find_series(ALTITUDE).unwrap().value_at(start + 1.0), Some(1002.0));
+        // 0.001° of latitude is about 111 m
+        let speed = log.find_series(SPEED).unwrap().value_at(start + 1.0).unwrap();
+        assert!((speed - 111.2 * 3.6).abs() < 1.0, "{speed}");
+    }
+
+}
@@ -0,0 +1,134 @@
+
+//! Workspace of the intermediate files written while processing a video, e.g. the segments cut before being joined
+//!
+//! The intermediate files are written next to the output file unless a temporary directory is set for the whole
+//! process, e.g. on a larger or faster disk. The space they need is estimated before starting so that the commands
+//! fail early instead of after having processed part of the video. The intermediate files are removed once the output
+//! is written, when a command fails and when the process is interrupted.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+
+/// margin added to the estimated space needed by the intermediate files, the estimations being rough
+const REQUIRED_SPACE_MARGIN: f64 = 1.1;
+
+#[derive(Debug, Error)]
+pub enum InsufficientSpaceError {
+    #[error("not enough space for the intermediate files in {}: {} MB needed, {} MB available, use --temp-dir to write them on another disk",
+        .dir.to_string_lossy(), .required / 1_000_000, .available / 1_000_000)]
+    IntermediateFiles { dir: PathBuf, required: u64, available: u64 },
+    #[error("not enough space for the output file in {}: {} MB needed, {} MB available",
+        .dir.to_string_lossy(), .required / 1_000_000, .available / 1_000_000)]
+    OutputFile { dir: PathBuf, required: u64, available: u64 },
+}
+
+/// sets the directory where the intermediate files are written for the whole process, it is created if needed
+pub fn set_temp_dir<P: AsRef<Path>>(dir: P) -> Result<(), io::Error> {
+    fs_err::create_dir_all(dir.as_ref())?;
+    // the paths of the intermediate files are absolute so that they can be referenced from other intermediate files
+    // like the concat demuxer lists
+    crate::process::set_temp_dir(fs_err::canonicalize(dir.as_ref())?);
+    Ok(())
+}
+
+pub fn temp_dir() -> Option<PathBuf> {
+    crate::process::temp_dir()
+}
+
+fn output_dir(output_file: &Path) -> PathBuf {
+    match output_file.parent() {
+        Some(dir) if ! dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// directory where the intermediate files of the output file are written: the temporary directory if set else the
+/// directory of the output file
+pub fn workspace_dir(output_file: &Path) -> PathBuf {
+    temp_dir().unwrap_or_else(|| output_dir(output_file))
+}
+
+/// estimated size of the part of the video lasting `duration` seconds, the input size being proportional to the
+/// duration, 0 if the size of the video is unknown, e.g. for URLs
+pub fn estimated_size(video_file: &Path, video_duration: f64, duration: f64) -> u64 {
+    if video_duration <= 0.0 { return 0 }
+    match video_file.metadata() {
+        Ok(metadata) => (metadata.len() as f64 * (duration / video_duration).clamp(0.0, 1.0)) as u64,
+        Err(_) => 0,
+    }
+}
+
+/// space available in the directory, `None` with a warning if it cannot be read
+fn available_space(dir: &Path, files: &str) -> Option<u64> {
+    match fs2::available_space(dir) {
+        Ok(available) => Some(available),
+        Err(error) => {
+            log::warn!("failed to get the available space in {}, the space needed by the {files} is not checked: {error}",
+                dir.to_string_lossy());
+            None
+        },
+    }
+}
+
+/// checks that there is enough space for `required` bytes of intermediate files of the output file, the check is
+/// skipped with a warning if the available space cannot be read
+pub fn check_available_space(output_file: &Path, required: u64) -> Result<(), InsufficientSpaceError> {
+    let dir = workspace_dir(output_file);
+    let required = (required as f64 * REQUIRED_SPACE_MARGIN) as u64;
+    let available = match available_space(&dir, "intermediate files") {
+        Some(available) => available,
+        None => return Ok(()),
+    };
+    log::debug!("intermediate files: {} MB needed, {} MB available in {}", required / 1_000_000, available / 1_000_000, dir.to_string_lossy());
+    if required > available { return Err(InsufficientSpaceError::IntermediateFiles { dir, required, available }) }
+    Ok(())
+}
+
+/// checks that there is enough space for the output file of about `required` bytes, the check is skipped for the
+/// standard output and with a warning if the available space cannot be read
+pub fn check_output_available_space(output_file: &Path, required: u64) -> Result<(), InsufficientSpaceError> {
+    if crate::file::is_stdout(output_file) { return Ok(()) }
+    let dir = output_dir(output_file);
+    let required = (required as f64 * REQUIRED_SPACE_MARGIN) as u64;
+    let available = match available_space(&dir, "output file") {
+        Some(available) => available,
+        None => return Ok(()),
+    };
+    log::debug!("output file: {} MB needed, {} MB available in {}", required / 1_000_000, available / 1_000_000, dir.to_string_lossy());
+    if required > available { return Err(InsufficientSpaceError::OutputFile { dir, required, available }) }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn size_proportional_to_the_duration() {
+        let video_file = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let file_size = video_file.metadata().unwrap().len();
+        assert_eq!(estimated_size(&video_file, 10.0, 5.0), file_size / 2);
+        assert_eq!(estimated_size(&video_file, 10.0, 20.0), file_size);
+        assert_eq!(estimated_size(&video_file, 0.0, 5.0), 0);
+        assert_eq!(estimated_size(Path::new("missing.mp4"), 10.0, 5.0), 0);
+    }
+
+    #[test]
+    fn output_directory_of_the_output_file() {
+        assert_eq!(output_dir(Path::new("videos/flight.mp4")), Path::new("videos"));
+        assert_eq!(output_dir(Path::new("flight.mp4")), Path::new("."));
+    }
+
+    #[test]
+    fn missing_space_reported() {
+        let output_file = Path::new(env!("CARGO_MANIFEST_DIR")).join("flight.mp4");
+        assert!(check_output_available_space(&output_file, 0).is_ok());
+        assert!(matches!(check_output_available_space(&output_file, u64::MAX / 2), Err(InsufficientSpaceError::OutputFile { .. })));
+        assert!(check_output_available_space(Path::new("-"), u64::MAX / 2).is_ok());
+    }
+
+}
@@ -5,7 +5,7 @@ use std::process::ExitStatus;
 use std::path::Path;
 
 use derive_more::From;
-use itertools::Itertools;
+use getset::{CopyGetters, Setters};
 use thiserror::Error;
 use std::io::Error as IOError;
 use ffmpeg_next::Rational;
@@ -14,12 +14,16 @@ use crate::cli::font_options::OSDFontDirError;
 use crate::cli::start_end_args::StartEndArgs;
 use crate::cli::transcode_video_args::OutputVideoFileError;
 use crate::file::TouchError;
-use crate::osd::overlay::SendFramesToFFMpegError;
+use crate::osd::overlay::{SendFramesToFFMpegError, FramesIterError, interpolation::{InterpolatedFramesIter, osd_frame_index}};
 use crate::osd::tile_indices::UnknownOSDItem;
 use crate::{prelude::*, osd::overlay::scaling::ScalingArgsError};
-use crate::{prelude::{TranscodeVideoArgs, Scaling}, cli::transcode_video_args::TranscodeVideoOSDArgs};
-use crate::osd::file::{ReadError as OSDFileReadError, GenericReader, UnrecognizedOSDFile};
-use crate::ffmpeg;
+use crate::cli::transcode_video_args::RequestedOSDButNoFileProvidedNorFound;
+use crate::osd::file::{ReadError as OSDFileReadError, GenericReader, UnrecognizedOSDFile, find_associated_to_video_file};
+use crate::video::timestamp::StartEndOverlayFrameIndex;
+use crate::video::container::ContainerError;
+use crate::osd::flights::FlightDetectionError;
+use crate::temp_dir::InsufficientSpaceError;
+use crate::{ffmpeg, jobs, osd};
 pub use self::probe::probe;
 use crate::process::Command as ProcessCommand;
 
@@ -28,12 +32,47 @@ pub mod resolution;
 pub mod probe;
 pub mod coordinates;
 pub mod region;
+pub mod encoder;
+pub mod transcoder;
+pub mod estimate;
+pub mod thumbnail;
+pub mod frame_extraction;
+pub mod osd_sync_calibration;
+pub mod cut;
+pub mod concat;
+pub mod chapters;
+pub mod remux;
+pub mod repair;
+mod mpv;
+pub mod osd_subtitles;
+pub mod srt_telemetry;
+pub mod watermark;
+pub mod pip;
+pub mod text_annotation;
+pub mod clock;
+pub mod layers;
+pub mod undistort;
+pub mod container;
+pub mod audio_analysis;
+pub mod vertical;
+pub mod stream;
+pub mod ground_station;
+pub mod gpu;
+pub mod verify;
+pub mod card;
+pub mod autocrop;
 
 pub use coordinates::{Coordinate, Coordinates, FormatError as CoordinatesFormatError, SignedCoordinate, SignedCoordinates};
 pub use region::Region;
 pub use resolution::Resolution;
 pub(crate) use resolution::margins;
-pub use timestamp::Timestamp;
+pub use timestamp::{Timestamp, TimestampRange};
+pub use transcoder::Transcoder;
+pub use cut::CutMode;
+pub use thumbnail::ThumbnailGenerator;
+pub use osd_subtitles::export_mkv_with_osd_track;
+pub use stream::LiveStreamer;
+pub use ground_station::GroundStation;
 
 
 pub type Dimension = u16;
@@ -48,6 +87,8 @@ pub enum CutVideoError {
     InputVideoFileDoesNotExist,
     #[error("output video file exists")]
     OutputVideoFileExists,
+    #[error("output OSD file exists")]
+    OutputOSDFileExists,
     #[error("input file and output file are the same file")]
     InputAndOutputFileIsTheSame,
     #[error("input has no file name")]
@@ -62,16 +103,65 @@ pub enum CutVideoError {
     FFMpegExitedWithError(ffmpeg::ProcessError),
     #[error(transparent)]
     WriteToFileError(TouchError),
+    #[error("requested to cut the OSD file but no OSD file associated to the input video was found")]
+    OSDFileNotFound,
+    #[error("only DJI FPV OSD files can be cut")]
+    CanOnlyCutDJIOSDFiles,
+    #[error(transparent)]
+    UnrecognizedOSDFile(UnrecognizedOSDFile),
+    #[error("OSD file read error: {0}")]
+    OSDFileReadError(OSDFileReadError),
+    #[error(transparent)]
+    OSDFileCreateError(OSDFileCreateError),
+    #[error("failed writing OSD file: {0}")]
+    OSDFileWriteError(IOError),
+    #[error("cannot re-encode {0} video, only H.264 and H.265 videos can be re-encoded")]
+    #[from(ignore)]
+    CannotReencodeVideoCodec(String),
+    #[error("failed writing temporary file: {0}")]
+    #[from(ignore)]
+    FailedWritingTempFile(IOError),
+    #[error("cannot cut the OSD file when joining segments")]
+    CannotCutOSDWhenJoiningSegments,
+    #[error(transparent)]
+    InsufficientTempSpace(InsufficientSpaceError),
+    #[error(transparent)]
+    JobStateError(jobs::JobStateError),
+    #[error(transparent)]
+    FlightDetectionError(FlightDetectionError),
+    #[error(transparent)]
+    ChaptersError(chapters::ChaptersError),
 }
 
-pub async fn cut<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: &Option<Q>,
-        overwrite: bool, start_end: &StartEndArgs) -> Result<(), CutVideoError> {
+/// the video being cut on its frames, the OSD is cut from the OSD frame displayed with the first video frame to the last
+/// OSD frame displayed with the last video frame
+fn cut_osd_file<P: AsRef<Path>, Q: AsRef<Path>>(osd_file: P, output_osd_file: Q, overwrite: bool, start_end: &StartEndArgs,
+        video_frame_rate: Rational) -> Result<(), CutVideoError> {
+    let (osd_file, output_osd_file) = (osd_file.as_ref(), output_osd_file.as_ref());
+    log::info!("cutting OSD file: {} -> {}", osd_file.to_string_lossy(), output_osd_file.to_string_lossy());
 
-    let input_video_file = input_video_file.as_ref();
+    let mut reader = match osd::file::open(osd_file)? {
+        osd::file::Reader::DJI(reader) => reader,
+        osd::file::Reader::WSA(_) => return Err(CutVideoError::CanOnlyCutDJIOSDFiles),
+    };
 
-    if ! input_video_file.exists() { return Err(CutVideoError::InputVideoFileDoesNotExist); }
+    let video_frame_rate_f64 = video_frame_rate.numerator() as f64 / video_frame_rate.denominator() as f64;
+    let first_frame = osd_frame_index(start_end.start().start_frame_count(video_frame_rate) as u64, video_frame_rate_f64);
+    let last_frame = start_end.end().end_frame_index(video_frame_rate)
+        .map(|last_video_frame| osd_frame_index(last_video_frame as u64 + 1, video_frame_rate_f64).saturating_sub(1).max(first_frame));
+    let frames = reader.frames()?.trimmed(first_frame, last_frame);
+    if ! crate::process::plan_file_write(output_osd_file, "cut OSD file") { return Ok(()) }
 
-    let output_video_file = match output_video_file {
+    let mut writer = OSDFileWriter::create(output_osd_file, reader.header(), overwrite)?;
+    writer.write_frames(frames.iter())?;
+    writer.finish()?;
+
+    log::info!("OSD file cut successfully: {} frames", frames.len());
+    Ok(())
+}
+
+fn cut_output_video_file<Q: AsRef<Path>>(input_video_file: &Path, output_video_file: &Option<Q>) -> Result<PathBuf, CutVideoError> {
+    Ok(match output_video_file {
         Some(output_video_file) => {
             let output_video_file = output_video_file.as_ref();
             if input_video_file == output_video_file { return Err(CutVideoError::InputAndOutputFileIsTheSame) }
@@ -79,40 +169,249 @@ pub async fn cut<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_vid
             output_video_file.to_path_buf()
         },
         None => {
+            let input_video_file = file::local_path(input_video_file);
             let mut output_file_stem = Path::new(input_video_file.file_stem().ok_or(CutVideoError::InputHasNoFileName)?).as_os_str().to_os_string();
             output_file_stem.push("_cut");
             let input_file_extension = input_video_file.extension().ok_or(CutVideoError::InputHasNoExtension)?;
             input_video_file.with_file_name(output_file_stem).with_extension(input_file_extension)
         },
-    };
+    })
+}
+
+pub async fn cut<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: &Option<Q>,
+        overwrite: bool, start_end: &StartEndArgs, cut_osd: bool, mode: CutMode, strip_metadata: bool) -> Result<(), CutVideoError> {
+
+    let input_video_file = input_video_file.as_ref();
+
+    if ! file::input_exists(input_video_file) { return Err(CutVideoError::InputVideoFileDoesNotExist); }
+
+    let output_video_file = cut_output_video_file(input_video_file, output_video_file)?;
 
     if ! overwrite && output_video_file.exists() { return Err(CutVideoError::OutputVideoFileExists); }
 
+    let osd_files = if cut_osd {
+        let osd_file = find_associated_to_video_file(input_video_file).ok_or(CutVideoError::OSDFileNotFound)?;
+        let output_osd_file = output_video_file.with_extension("osd");
+        if ! overwrite && output_osd_file.exists() { return Err(CutVideoError::OutputOSDFileExists); }
+        Some((osd_file, output_osd_file))
+    } else {
+        None
+    };
+
     file::touch(&output_video_file)?;
 
-    log::info!("cutting video: {} -> {}", input_video_file.to_string_lossy(), output_video_file.to_string_lossy());
+    log::info!("cutting video ({mode} mode): {} -> {}", input_video_file.to_string_lossy(), output_video_file.to_string_lossy());
 
     let video_info = probe(input_video_file)?;
     let frame_count = frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &start_end.start(), &start_end.end());
 
-    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    match mode {
+
+        CutMode::Copy => {
+            let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+
+            ffmpeg_command
+                .add_input_file_slice(input_video_file, start_end.start(), start_end.end())
+                .set_output_video_codec(Some("copy"))
+                .set_output_file(&output_video_file)
+                .set_overwrite_output_file(true);
+
+            if video_info.has_audio() {
+                ffmpeg_command.set_output_audio_codec(Some("copy"));
+            }
+            cut::set_metadata(&mut ffmpeg_command, 0, strip_metadata);
+
+            ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
+        },
+
+        CutMode::Smart =>
+            cut::cut_smart(input_video_file, &output_video_file, start_end.start(), start_end.end(), &video_info, strip_metadata).await?,
+
+        CutMode::Reencode =>
+            cut::cut_reencode(input_video_file, &output_video_file, start_end.start(), start_end.end(), &video_info, frame_count,
+                strip_metadata).await?,
+
+    }
+
+    log::info!("video file cut successfully");
+
+    if ! strip_metadata && ! file::is_url(input_video_file) {
+        preserve_modification_time(input_video_file, &output_video_file);
+    }
+
+    if let Some((osd_file, output_osd_file)) = osd_files {
+        cut_osd_file(osd_file, output_osd_file, overwrite, start_end, video_info.frame_rate())?;
+    }
+
+    Ok(())
+}
+
+/// cuts several segments of the video, either into separate files or joined into a single file
+///
+/// The separate files are named after the output video file, or the input video file with the `_cut` suffix when
+/// not provided, with the segment number appended. When joining, the segments are cut into temporary files which
+/// are then joined with the FFMpeg concat demuxer which starts the audio and video streams of each segment at
+/// the same position so that the audio stays in sync across the joins.
+///
+/// The state of the separate files is recorded in the output directory until they are all cut so that an
+/// interrupted run can be resumed with `resume`: the complete files are then skipped and the partial ones cut again.
+pub async fn cut_segments<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: &Option<Q>,
+        overwrite: bool, segments: &[TimestampRange], cut_osd: bool, mode: CutMode, join: bool, resume: bool, strip_metadata: bool)
+        -> Result<(), CutVideoError> {
+
+    let input_video_file = input_video_file.as_ref();
+
+    if ! file::input_exists(input_video_file) { return Err(CutVideoError::InputVideoFileDoesNotExist); }
+
+    let output_video_file = cut_output_video_file(input_video_file, output_video_file)?;
+    let output_file_extension = output_video_file.extension().ok_or(CutVideoError::InputHasNoExtension)?.to_os_string();
+
+    if ! join {
+        let output_file_stem = output_video_file.file_stem().ok_or(CutVideoError::InputHasNoFileName)?.to_os_string();
+        let output_dir = match output_video_file.parent() {
+            Some(dir) if ! dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let job_state = if resume { jobs::JobState::load(output_dir)? } else { jobs::JobState::new(output_dir) };
+        let mut scheduler = jobs::Scheduler::with_state(job_state);
+        for (index, segment) in segments.iter().enumerate() {
+            let mut segment_file_stem = output_file_stem.clone();
+            segment_file_stem.push(format!("_{}", index + 1));
+            let segment_file = output_video_file.with_file_name(segment_file_stem).with_extension(&output_file_extension);
+            let input_video_file = input_video_file.to_path_buf();
+            let segment = *segment;
+            let expected_duration = match (segment.start(), segment.end()) {
+                (Some(start), Some(end)) => Some((end.total_milliseconds() - start.total_milliseconds()) as f64 / 1000.0),
+                _ => None,
+            };
+            // the partial files written by the interrupted run are overwritten
+            let overwrite = overwrite || scheduler.is_output_recorded(&segment_file);
+            let job_segment_file = segment_file.clone();
+            scheduler.add_with_output(segment_file, expected_duration, async move {
+                let start_end = StartEndArgs::new(segment.start(), segment.end());
+                cut(&input_video_file, &Some(job_segment_file), overwrite, &start_end, cut_osd, mode, strip_metadata).await
+            });
+        }
+        return scheduler.run_all_ok().await;
+    }
 
+    if cut_osd { return Err(CutVideoError::CannotCutOSDWhenJoiningSegments) }
+    if ! overwrite && output_video_file.exists() { return Err(CutVideoError::OutputVideoFileExists); }
+
+    let video_info = probe(input_video_file)?;
+    let frame_count = segments.iter().map(|segment|
+        frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &segment.start(), &segment.end())
+    ).sum();
+
+    let segments_duration = segments.iter().map(|segment| {
+        let start = segment.start().map(|start| start.total_milliseconds() as f64 / 1000.0).unwrap_or(0.0);
+        let end = segment.end().map(|end| end.total_milliseconds() as f64 / 1000.0).unwrap_or_else(|| video_info.duration());
+        (end - start).max(0.0)
+    }).sum();
+    crate::temp_dir::check_available_space(&output_video_file,
+        crate::temp_dir::estimated_size(input_video_file, video_info.duration(), segments_duration))?;
+
+    let mut temp_files = cut::TempFiles(vec![]);
+    let mut scheduler = jobs::Scheduler::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let mut suffix = std::ffi::OsString::from(format!(".segment{}.", index + 1));
+        suffix.push(&output_file_extension);
+        let segment_file = cut::temp_file_path(&output_video_file, &suffix.to_string_lossy());
+        temp_files.0.push(segment_file.clone());
+        let input_video_file = input_video_file.to_path_buf();
+        let segment = *segment;
+        scheduler.add(segment_file.to_string_lossy(), async move {
+            let start_end = StartEndArgs::new(segment.start(), segment.end());
+            cut(&input_video_file, &Some(segment_file), true, &start_end, false, mode, strip_metadata).await
+        });
+    }
+    scheduler.run_all_ok().await?;
+
+    log::info!("joining {} segments: {}", segments.len(), output_video_file.to_string_lossy());
+
+    let concat_list_file = cut::temp_file_path(&output_video_file, ".concat.txt");
+    cut::write_concat_list(&concat_list_file, &temp_files.0).map_err(CutVideoError::FailedWritingTempFile)?;
+    temp_files.0.push(concat_list_file.clone());
+
+    file::touch(&output_video_file)?;
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
     ffmpeg_command
-        .add_input_file_slice(input_video_file, start_end.start(), start_end.end())
+        .add_concat_list_input(&concat_list_file)
+        .add_mapping("0")
         .set_output_video_codec(Some("copy"))
-        .set_output_file(output_video_file)
+        .set_output_audio_codec(Some("copy"))
+        .set_output_file(&output_video_file)
         .set_overwrite_output_file(true);
-
-    if video_info.has_audio() {
-        ffmpeg_command.set_output_audio_codec(Some("copy"));
-    }
+    // the metadata are read from the input video, the concat demuxer does not provide them
+    if ! strip_metadata { ffmpeg_command.add_input_file(input_video_file); }
+    cut::set_metadata(&mut ffmpeg_command, 1, strip_metadata);
 
     ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
 
-    log::info!("video file cut successfully");
+    log::info!("segments joined successfully");
+
+    if ! strip_metadata && ! file::is_url(input_video_file) {
+        preserve_modification_time(input_video_file, &output_video_file);
+    }
     Ok(())
 }
 
+/// start and end of the video trimmed of the sections before the first arming and after the last disarming, the
+/// flights being detected from the associated OSD file and padded by `padding` seconds
+pub fn trim_disarmed_start_end<P: AsRef<Path>>(video_file: P, padding: f64) -> Result<StartEndArgs, FlightDetectionError> {
+    let osd_file = find_associated_to_video_file(video_file).ok_or(FlightDetectionError::OSDFileNotFound)?;
+    let flights = osd::flights::detect_osd_file_flights(osd_file)?;
+    let range = osd::flights::armed_range(&flights, padding).ok_or(FlightDetectionError::NoFlightDetected)?;
+    log::info!("trimming the disarmed sections: keeping {} - {}", range.start().unwrap_or_default(), range.end().unwrap_or_default());
+    Ok(StartEndArgs::new(range.start(), range.end()))
+}
+
+/// cuts the video into one file per flight, the flights being detected from the associated OSD file
+///
+/// The flights are padded by `padding` seconds on both sides and written to files named like the segments of
+/// [`cut_segments`], the OSD file is also cut for each flight when it is a DJI FPV OSD file. With `chapters` the
+/// chapters marking the flights in the input video are also written next to the output files in the YouTube
+/// description format.
+pub async fn split_flights<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: &Option<Q>,
+        overwrite: bool, padding: f64, mode: CutMode, strip_metadata: bool, chapters: bool) -> Result<(), CutVideoError> {
+
+    let input_video_file = input_video_file.as_ref();
+
+    if ! file::input_exists(input_video_file) { return Err(CutVideoError::InputVideoFileDoesNotExist); }
+
+    let osd_file = find_associated_to_video_file(input_video_file).ok_or(CutVideoError::OSDFileNotFound)?;
+    let cut_osd = matches!(osd::file::open(&osd_file)?, osd::file::Reader::DJI(_));
+    let flights = osd::flights::detect_osd_file_flights(&osd_file)?;
+
+    for (index, flight) in flights.iter().enumerate() {
+        log::info!("flight {}: {} - {} ({:.0}s)", index + 1, flight.start(), flight.end(), flight.duration());
+    }
+    if ! cut_osd {
+        log::warn!("only DJI FPV OSD files can be cut, the OSD file is not cut for each flight");
+    }
+
+    let output_video_file = match output_video_file {
+        Some(output_video_file) => output_video_file.as_ref().to_path_buf(),
+        None => {
+            let input_video_file = file::local_path(input_video_file);
+            let mut output_file_stem = input_video_file.file_stem().ok_or(CutVideoError::InputHasNoFileName)?.to_os_string();
+            output_file_stem.push("_flight");
+            let output_file_extension = input_video_file.extension().ok_or(CutVideoError::InputHasNoExtension)?;
+            input_video_file.with_file_name(output_file_stem).with_extension(output_file_extension)
+        },
+    };
+    if chapters {
+        let mut chapters_file_stem = output_video_file.file_stem().ok_or(CutVideoError::InputHasNoFileName)?.to_os_string();
+        chapters_file_stem.push("_chapters");
+        let chapters_file = output_video_file.with_file_name(chapters_file_stem).with_extension("txt");
+        let video_chapters = chapters::flight_chapters(&flights, probe(input_video_file)?.duration());
+        chapters::write_youtube_chapters(&video_chapters, chapters_file, overwrite)?;
+    }
+    let segments = flights.iter().map(|flight| flight.padded_range(padding)).collect::<Vec<_>>();
+    cut_segments(input_video_file, &Some(output_video_file), overwrite, &segments, cut_osd, mode, false, false, strip_metadata).await
+}
+
 #[derive(Debug, Error, From)]
 pub enum FixVideoFileAudioError {
     #[error("failed to get input video details")]
@@ -133,10 +432,14 @@ pub enum FixVideoFileAudioError {
     FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
     #[error(transparent)]
     FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error(transparent)]
+    FailedReadingAudio(ffmpeg::ReadAudioError),
     #[error("the input video file does not have an audio stream")]
     InputVideoDoesNotHaveAnAudioStream,
     #[error(transparent)]
     WriteToFileError(TouchError),
+    #[error(transparent)]
+    IOError(IOError),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -169,8 +472,65 @@ impl AudioFixType {
 
 }
 
+/// Manual audio corrections for the recordings needing other values than the DJI Air Unit audio fix
+#[derive(Debug, Clone, Copy, Default, PartialEq, CopyGetters, Setters)]
+#[getset(get_copy = "pub")]
+pub struct AudioAdjustments {
+    /// gain in dB
+    gain: Option<f64>,
+    /// delay in milliseconds, negative values make the audio play earlier
+    delay: Option<i64>,
+    /// tempo factor correcting the audio drift, replaces the sync fix
+    #[getset(set = "pub")]
+    tempo: Option<f64>,
+}
+
+impl AudioAdjustments {
+
+    pub fn new(gain: Option<f64>, delay: Option<i64>) -> Self {
+        Self { gain, delay, tempo: None }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gain.is_none() && matches!(self.delay, None | Some(0)) && self.tempo.is_none()
+    }
+
+    fn ffmpeg_audio_filters(&self) -> Vec<String> {
+        let mut filters = vec![];
+        if let Some(tempo) = self.tempo {
+            filters.push(format!("atempo={tempo}"));
+        }
+        if let Some(gain) = self.gain {
+            filters.push(format!("volume={gain}dB"));
+        }
+        match self.delay {
+            Some(delay) if delay > 0 => filters.push(format!("adelay=delays={delay}:all=1")),
+            Some(delay) if delay < 0 => filters.push(format!("atrim=start={},asetpts=PTS-STARTPTS", -delay as f64 / 1000.0)),
+            _ => {},
+        }
+        filters
+    }
+
+}
+
+/// audio filter applying the DJI Air Unit audio fix then the manual adjustments, the gain replacing the volume fix
+/// and the tempo replacing the sync fix
+fn audio_correction_filter(fix_type: Option<&AudioFixType>, adjustments: &AudioAdjustments) -> Option<String> {
+    use AudioFixType::*;
+    let fix_type = match (fix_type, adjustments.gain().is_some(), adjustments.tempo().is_some()) {
+        (Some(SyncAndVolume), true, true) | (Some(Volume), true, _) | (Some(Sync), _, true) => None,
+        (Some(SyncAndVolume), true, false) => Some(&Sync),
+        (Some(SyncAndVolume), false, true) => Some(&Volume),
+        (fix_type, _, _) => fix_type,
+    };
+    let filters = fix_type.map(AudioFixType::ffmpeg_audio_filter_string).into_iter()
+        .chain(adjustments.ffmpeg_audio_filters())
+        .collect::<Vec<_>>();
+    if filters.is_empty() { None } else { Some(filters.join(",")) }
+}
+
 pub async fn fix_dji_air_unit_audio<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: &Option<Q>,
-        overwrite: bool, fix_type: AudioFixType) -> Result<(), FixVideoFileAudioError> {
+        overwrite: bool, fix_type: Option<AudioFixType>, adjustments: &AudioAdjustments) -> Result<(), FixVideoFileAudioError> {
 
     let input_video_file = input_video_file.as_ref();
 
@@ -207,7 +567,7 @@ pub async fn fix_dji_air_unit_audio<P: AsRef<Path>, Q: AsRef<Path>>(input_video_
 
     ffmpeg_command
         .add_input_file(input_video_file)
-        .add_audio_filter(&fix_type.ffmpeg_audio_filter_string())
+        .add_audio_filter(&audio_correction_filter(fix_type.as_ref(), adjustments).unwrap_or_else(|| "anull".to_owned()))
         .set_output_video_codec(Some("copy"))
         .set_output_audio_settings(Some("aac"), Some("93k"))
         .set_output_file(output_video_file)
@@ -219,6 +579,14 @@ pub async fn fix_dji_air_unit_audio<P: AsRef<Path>, Q: AsRef<Path>>(input_video_
     Ok(())
 }
 
+/// gives the output video file the modification time of the input video file so that the videos sort the same way,
+/// only a warning is logged on failure since the video itself was written successfully
+fn preserve_modification_time(input_video_file: &Path, output_video_file: &Path) {
+    if let Err(error) = file::copy_modification_time(input_video_file, output_video_file) {
+        log::warn!("failed to set the modification time of {}: {error}", output_video_file.to_string_lossy());
+    }
+}
+
 fn frame_count_for_interval(total_frames: u64, frame_rate: Rational, start: &Option<Timestamp>, end: &Option<Timestamp>) -> u64 {
     match (start, end) {
         (None, None) => total_frames,
@@ -233,6 +601,8 @@ pub enum TranscodeVideoError {
     #[error(transparent)]
     OSDFontDirError(OSDFontDirError),
     #[error(transparent)]
+    ContainerError(ContainerError),
+    #[error(transparent)]
     OutputVideoFileError(OutputVideoFileError),
     #[error(transparent)]
     UnrecognizedOSDFile(UnrecognizedOSDFile),
@@ -242,8 +612,6 @@ pub enum TranscodeVideoError {
     DrawFrameOverlayError(DrawFrameOverlayError),
     #[error("failed to get input video details")]
     FailedToGetInputVideoDetails(VideoProbingError),
-    #[error("it is only possible to burn the OSD on 60FPS videos, given video is {0:.1}FPS")]
-    CanOnlyBurnOSDOn60FPSVideo(f64),
     #[error("requested to fix audio but input has no audio stream")]
     RequestedAudioFixingButInputHasNoAudio,
     #[error("input video file does not exist")]
@@ -266,6 +634,87 @@ pub enum TranscodeVideoError {
     UnknownOSDItem(UnknownOSDItem),
     #[error(transparent)]
     WriteToFileError(TouchError),
+    #[error(transparent)]
+    InvalidEncoderSettings(encoder::InvalidSettingsError),
+    #[error(transparent)]
+    OSDStyleArgsError(crate::cli::osd_style_args::StyleArgsError),
+    #[error(transparent)]
+    RequestedOSDButNoFileProvidedNorFound(RequestedOSDButNoFileProvidedNorFound),
+    #[error("crop region does not fit in the input video resolution {0}")]
+    #[from(ignore)]
+    CropRegionOutOfBounds(Resolution),
+    #[error("invalid denoise strength: {0}, it must be greater than 0")]
+    #[from(ignore)]
+    InvalidDenoiseStrength(f64),
+    #[error("invalid speed factor: {0}, it must be greater than 0")]
+    #[from(ignore)]
+    InvalidSpeed(f64),
+    #[error("invalid key frame interval: {0}, it must be greater than 0")]
+    #[from(ignore)]
+    InvalidKeyframeInterval(f64),
+    #[error("the frame extraction interval must be greater than 0")]
+    InvalidFrameExtractionInterval,
+    #[error("timestamp {0} is after the end of the video")]
+    #[from(ignore)]
+    TimestampAfterEndOfVideo(Timestamp),
+    #[error("output file extension needs to be .mkv")]
+    OutputFileExtensionNotMkv,
+    #[error("failed writing OSD subtitles file: {0}")]
+    #[from(ignore)]
+    FailedWritingOSDSubtitles(IOError),
+    #[error("unsupported stream URL: {0}, supported schemes are rtmp, rtmps, srt and udp")]
+    #[from(ignore)]
+    UnsupportedStreamURL(String),
+    #[error("the capture device resolution is required to render the OSD")]
+    CaptureResolutionRequiredForOSD,
+    #[error(transparent)]
+    SrtTelemetryError(srt_telemetry::SrtTelemetryError),
+    #[error(transparent)]
+    ClockError(clock::ClockError),
+    #[error(transparent)]
+    TelemetryError(crate::telemetry::TelemetryError),
+    #[error("watermark image does not exist: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    WatermarkImageDoesNotExist(PathBuf),
+    #[error("picture-in-picture video file does not exist: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    PipVideoFileDoesNotExist(PathBuf),
+    #[error("LUT file does not exist: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    LutFileDoesNotExist(PathBuf),
+    #[error(transparent)]
+    FlightDetectionError(FlightDetectionError),
+    #[error(transparent)]
+    GpuPipelineError(gpu::GpuPipelineError),
+    #[error("invalid checkpoint interval: {0}s, it must be greater than 0")]
+    #[from(ignore)]
+    InvalidCheckpointInterval(f64),
+    #[error("failed writing checkpoint: {0}")]
+    #[from(ignore)]
+    FailedWritingCheckpoint(IOError),
+    #[error(transparent)]
+    CheckpointStateError(jobs::JobStateError),
+    #[error(transparent)]
+    InsufficientTempSpace(InsufficientSpaceError),
+    #[error(transparent)]
+    VerifyError(verify::VerifyError),
+    #[error(transparent)]
+    OSDScriptError(crate::osd::overlay::script::ScriptError),
+    #[error("invalid card duration: {0}, must be greater than 0")]
+    #[from(ignore)]
+    InvalidCardDuration(f64),
+    #[error("card image does not exist: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    CardImageDoesNotExist(PathBuf),
+    #[error("failed writing temporary file: {0}")]
+    #[from(ignore)]
+    FailedWritingTempFile(IOError),
+    #[error("no flight stats could be read from the OSD file")]
+    NoFlightStats,
+    #[error(transparent)]
+    FlightStatsCardNotReplaced(card::FlightStatsCardNotReplacedError),
+    #[error(transparent)]
+    AutocropError(autocrop::AutocropError),
 }
 
 impl From<SendFramesToFFMpegError> for TranscodeVideoError {
@@ -274,148 +723,19 @@ impl From<SendFramesToFFMpegError> for TranscodeVideoError {
         match error {
             PipeError(error) => Self::FailedSendingOSDFramesToFFMpeg(error),
             UnknownOSDItem(error) => Self::UnknownOSDItem(error),
+            SendFramesToFFMpegError::ReadError(error) => Self::OSDFileReadError(error),
             FFMpegExitedWithError(error) => Self::FFMpegExitedWithError(error),
         }
     }
 }
 
-pub async fn transcode(args: &TranscodeVideoArgs) -> Result<(), TranscodeVideoError> {
-
-    let output_video_file = args.output_video_file(false)?;
-    if ! args.input_video_file().exists() { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
-    if ! args.overwrite() && output_video_file.exists() { return Err(TranscodeVideoError::OutputVideoFileExists); }
-    if *args.input_video_file() == output_video_file { return Err(TranscodeVideoError::InputAndOutputFileIsTheSame) }
-    file::touch(&output_video_file)?;
-    if args.start_end().start().is_some() && matches!(args.video_audio_fix(), Some(fix) if fix.sync()) {
-        return Err(TranscodeVideoError::IncompatibleArguments("cannot fix video audio sync while not starting at the beginning of the file".to_owned()));
-    }
-
-    log::info!("transcoding video: {} -> {}", args.input_video_file().to_string_lossy(), output_video_file.to_string_lossy());
-
-    let video_info = probe(args.input_video_file())?;
-    let frame_count = frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &args.start_end().start(), &args.start_end().end());
-
-    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
-
-    ffmpeg_command
-        .add_input_file_slice(args.input_video_file(), args.start_end().start(), args.start_end().end())
-        .set_output_video_settings(Some(args.video_encoder()), Some(args.video_bitrate()), Some(args.video_crf()))
-        .set_output_file(output_video_file)
-        .set_overwrite_output_file(true);
-
-    if ! args.remove_video_defects().is_empty() {
-        let defect_filter = args.remove_video_defects().iter().map(|region|
-            format!("delogo={}", region.to_ffmpeg_filter_string())
-        ).join(";");
-        let complex_filter = format!("[0]{}[vo]", defect_filter);
-        ffmpeg_command.add_complex_filter(&complex_filter).add_mapping("[vo]");
-    };
-
-    if let Some(video_audio_fix) = args.video_audio_fix() {
-        if video_info.has_audio() {
-            ffmpeg_command
-                .add_audio_filter(&video_audio_fix.ffmpeg_audio_filter_string())
-                .set_output_audio_settings(Some(args.audio_encoder()), Some(args.audio_bitrate()));
+impl From<FramesIterError> for TranscodeVideoError {
+    fn from(error: FramesIterError) -> Self {
+        match error {
+            FramesIterError::UnknownOSDItem(error) => Self::UnknownOSDItem(error),
+            FramesIterError::ReadError(error) => Self::OSDFileReadError(error),
         }
     }
-
-    ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
-
-    log::info!("{frame_count} frames transcoded successfully");
-    Ok(())
-}
-
-pub async fn transcode_burn_osd<P: AsRef<Path>>(args: &TranscodeVideoArgs, osd_file_path: P, osd_args: &TranscodeVideoOSDArgs) -> Result<(), TranscodeVideoError> {
-
-    let output_video_file = args.output_video_file(true)?;
-
-    if ! args.input_video_file().exists() { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
-    if ! args.overwrite() && output_video_file.exists() { return Err(TranscodeVideoError::OutputVideoFileExists); }
-    if *args.input_video_file() == output_video_file { return Err(TranscodeVideoError::InputAndOutputFileIsTheSame) }
-    file::touch(&output_video_file)?;
-    if args.start_end().start().is_some() && matches!(args.video_audio_fix(), Some(fix) if fix.sync()) {
-        return Err(TranscodeVideoError::IncompatibleArguments("cannot fix video audio sync while not starting at the beginning of the file".to_owned()));
-    }
-
-    let video_info = probe(args.input_video_file())?;
-
-    let osd_frame_shift = match osd_args.osd_frame_shift() {
-        Some(frame_shift) => frame_shift,
-        None => {
-            if video_info.has_audio() {
-                let frame_shift = crate::osd::dji::AU_OSD_FRAME_SHIFT;
-                log::info!("input video file contains audio, assuming DJI AU origin, applying {frame_shift} OSD frames shift");
-                frame_shift
-            } else {
-                0
-            }
-        },
-    };
-
-    log::info!("transcoding video: {} -> {}", args.input_video_file().to_string_lossy(), output_video_file.to_string_lossy());
-
-    if video_info.frame_rate().numerator() != 60 || video_info.frame_rate().denominator() != 1 {
-        return Err(TranscodeVideoError::CanOnlyBurnOSDOn60FPSVideo(video_info.frame_rate().numerator() as f64 / video_info.frame_rate().denominator() as f64))
-    }
-
-    let osd_scaling = Scaling::try_from_osd_args(osd_args.osd_scaling_args(), video_info.resolution())?;
-    let mut osd_file = osd::file::open(osd_file_path)?;
-    let osd_font_dir = FontDir::new(osd_args.osd_font_options().osd_font_dir()?);
-    let osd_frames_generator = OverlayGenerator::new(
-        osd_file.frames()?,
-        osd_file.font_variant(),
-        &osd_font_dir,
-        &osd_args.osd_font_options().osd_font_ident(),
-        osd_scaling,
-        osd_args.osd_hide_regions(),
-        osd_args.osd_hide_items()
-    )?;
-
-    let frame_count = frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &args.start_end().start(), &args.start_end().end());
-    log::debug!("frame count: video={}, transcode={}", video_info.frame_count(), frame_count);
-
-    let first_frame_index = args.start_end().start().map(|tstamp| tstamp.frame_count(video_info.frame_rate()) as u32).unwrap_or(0);
-    let last_frame_index = args.start_end().end().map(|end| end.frame_count(video_info.frame_rate()) as u32).unwrap_or(frame_count as u32);
-    let osd_overlay_resolution = osd_frames_generator.frame_dimensions();
-    let osd_frames_iter = osd_frames_generator.iter_advanced(first_frame_index, Some(last_frame_index), osd_frame_shift);
-
-    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
-
-    let complex_filter = if args.remove_video_defects().is_empty() {
-        "[0][1]overlay=eof_action=repeat:x=(W-w)/2:y=(H-h)/2[vo]".to_owned()
-    } else {
-        let defect_filter = args.remove_video_defects().iter().map(|region|
-            format!("delogo={}", region.to_ffmpeg_filter_string())
-        ).join(";");
-        format!("[0]{}[s1];[s1][1]overlay=eof_action=repeat:x=(W-w)/2:y=(H-h)/2[vo]", defect_filter)
-    };
-
-    ffmpeg_command
-        .add_input_file_slice(args.input_video_file(), args.start_end().start(), args.start_end().end())
-        .add_stdin_input(osd_overlay_resolution, 60).unwrap()
-        .add_complex_filter(&complex_filter)
-        .add_mapping("[vo]")
-        .set_output_video_settings(Some(args.video_encoder()), Some(args.video_bitrate()), Some(args.video_crf()))
-        .set_output_file(output_video_file)
-        .set_overwrite_output_file(true);
-
-    match (video_info.has_audio(), args.video_audio_fix()) {
-        (true, None) => { ffmpeg_command.add_mapping("0:a"); },
-        (true, Some(audio_fix_type)) => {
-            ffmpeg_command
-                .add_mapping_with_audio_filter("0:a", &audio_fix_type.ffmpeg_audio_filter_string())
-                .set_output_audio_settings(Some(args.audio_encoder()), Some(args.audio_bitrate()));
-            },
-        (false, None) => {},
-        (false, Some(_)) => return Err(TranscodeVideoError::RequestedAudioFixingButInputHasNoAudio),
-    }
-
-    let ffmpeg_process = ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?;
-
-    osd_frames_iter.send_frames_to_ffmpeg_and_wait(ffmpeg_process).await?;
-
-    log::info!("{frame_count} frames transcoded successfully");
-    Ok(())
 }
 
 #[derive(Debug, Error)]
@@ -432,20 +752,39 @@ pub enum PlayWithOSDError {
     FailedToStartMPV(IOError),
     #[error("MPV exited with an error: {0}")]
     MPVExitedWithAnError(ExitStatus),
+    #[error("failed writing MPV input configuration file: {0}")]
+    FailedWritingMPVInputConf(IOError),
+    #[error("failed writing frame shift file: {0}")]
+    FailedWritingFrameShiftFile(IOError),
+    #[error(transparent)]
+    OSDError(TranscodeVideoError),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error("failed sending OSD frames to ffmpeg process: {0}")]
+    FailedSendingOSDFramesToFFMpeg(SendFramesToFFMpegError),
 }
 
-pub fn play_with_osd<P: AsRef<Path>, Q: AsRef<Path>>(video_file: P, osd_video_file: &Option<Q>) -> Result<(), PlayWithOSDError> {
+/// Plays a video overlaying the OSD video with MPV
+///
+/// Key bindings sent to this process through the MPV IPC socket allow toggling the OSD visibility, adjusting its
+/// opacity and nudging its sync. The final frame shift is logged and written to `frame_shift_file` if provided so
+/// that it can be reused when burning the OSD.
+pub fn play_with_osd<P: AsRef<Path>, Q: AsRef<Path>>(video_file: P, osd_video_file: &Option<Q>, frame_shift_file: Option<&Path>)
+        -> Result<(), PlayWithOSDError> {
 
     let video_file = video_file.as_ref();
 
     let osd_video_file = match osd_video_file {
         Some(osd_video_file) => osd_video_file.as_ref().to_path_buf(),
         None => {
-            let video_file_stem = video_file.file_stem()
+            let local_video_file = file::local_path(video_file);
+            let video_file_stem = local_video_file.file_stem()
                 .ok_or_else(|| PlayWithOSDError::InvalidVideoFilePath(video_file.to_path_buf()))?;
             let mut osd_video_file_name = video_file_stem.to_os_string();
             osd_video_file_name.push("_osd");
-            let osd_video_file = video_file.with_file_name(osd_video_file_name).with_extension("webm");
+            let osd_video_file = local_video_file.with_file_name(osd_video_file_name).with_extension("webm");
             if ! osd_video_file.exists() { return Err(PlayWithOSDError::OSDVideoFileNotFound(osd_video_file)); }
             osd_video_file
         },
@@ -463,18 +802,122 @@ pub fn play_with_osd<P: AsRef<Path>, Q: AsRef<Path>>(video_file: P, osd_video_fi
     let mut external_file_arg = OsString::from("--external-file=");
     external_file_arg.push(osd_video_file.as_os_str());
 
+    let ipc_socket_file = std::env::temp_dir().join(format!("hd_fpv_video_tool_mpv_{}.sock", std::process::id()));
+    let input_conf_file = ipc_socket_file.with_extension("conf");
+    let _temp_files = cut::TempFiles::new(vec![ipc_socket_file.clone(), input_conf_file.clone()]);
+    if crate::process::plan_file_write(&input_conf_file, "MPV input configuration") {
+        mpv::write_input_conf(&input_conf_file).map_err(PlayWithOSDError::FailedWritingMPVInputConf)?;
+    }
+
+    let mut ipc_socket_arg = OsString::from("--input-ipc-server=");
+    ipc_socket_arg.push(ipc_socket_file.as_os_str());
+    let mut input_conf_arg = OsString::from("--input-conf=");
+    input_conf_arg.push(input_conf_file.as_os_str());
+
+    let controls = mpv::OverlayControls::default();
+
     let mut mpv_command = ProcessCommand::new("mpv");
 
     mpv_command
         .arg(format!("--vd={decode_lib}"))
         .arg(external_file_arg)
+        .arg(ipc_socket_arg)
+        .arg(input_conf_arg)
         .arg(video_file)
-        .arg("--lavfi-complex=[vid1][vid2]overlay=(main_w-overlay_w)/2:(main_h-overlay_h)/2[vo]");
+        .arg(format!("--lavfi-complex={}", controls.lavfi_complex()));
+
+    log::info!("OSD key bindings: {}", mpv::key_bindings_help());
+
+    let mut mpv_child_proc = match mpv_command.spawn_unless_dry_run().map_err(PlayWithOSDError::FailedToStartMPV)? {
+        Some(mpv_child_proc) => mpv_child_proc,
+        None => return Ok(()),
+    };
+
+    // the OSD controls are not essential to play the video so IPC errors are only reported
+    let controls = match mpv::IPCClient::connect(&ipc_socket_file).and_then(|client| client.run(controls)) {
+        Ok(controls) => controls,
+        Err(error) => {
+            log::warn!("OSD controls unavailable, failed communicating with MPV: {error}");
+            controls
+        },
+    };
+
+    let exit_result = mpv_child_proc.wait().unwrap();
+    if ! exit_result.success() { return Err(PlayWithOSDError::MPVExitedWithAnError(exit_result)) }
+
+    if controls.frame_shift() != 0 {
+        log::info!("OSD frame shift adjusted by {} frames, add it to the frame shift used to generate the OSD video \
+            or to --osd-frame-shift when burning the OSD", controls.frame_shift());
+    }
+    if let Some(frame_shift_file) = frame_shift_file {
+        fs_err::write(frame_shift_file, format!("{}\n", controls.frame_shift())).map_err(PlayWithOSDError::FailedWritingFrameShiftFile)?;
+    }
+
+    Ok(())
+}
+
+/// Plays a video with the OSD rendered on the fly without generating an overlay video beforehand
+///
+/// The OSD frames are sent to an FFMpeg process overlaying them onto the video which streams the result to MPV.
+/// Since the video is streamed it is not possible to seek.
+pub async fn play_with_live_osd<P: AsRef<Path>>(video_file: P, osd_settings: &transcoder::OSDSettings) -> Result<(), PlayWithOSDError> {
+
+    let video_file = video_file.as_ref();
+    let video_info = probe(video_file)?;
+
+    let osd_frame_shift = osd_settings.frame_shift_for_video(&video_info);
+    let osd_settings = osd_settings.with_detected_arm_beep(video_file, osd_frame_shift).await.map_err(PlayWithOSDError::OSDError)?;
+    let osd_frames_generator = osd_settings.overlay_generator(video_info.resolution()).map_err(PlayWithOSDError::OSDError)?;
+    // the OSD frames are mapped onto the video frames like when transcoding
+    let video_frame_rate = video_info.frame_rate().numerator() as f64 / video_info.frame_rate().denominator() as f64;
+    let osd_input_frame_rate = InterpolatedFramesIter::frame_rate_for_video(video_frame_rate, osd_settings.interpolate());
+    let osd_frames_iter = InterpolatedFramesIter::new(osd_frames_generator.iter_advanced(0, None, osd_frame_shift),
+        osd_input_frame_rate, osd_settings.interpolate());
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    ffmpeg_command
+        .add_input_file(video_file)
+        .add_stdin_input(osd_frames_generator.frame_dimensions(), osd_input_frame_rate).unwrap()
+        .add_complex_filter("[0][1]overlay=eof_action=repeat:x=(W-w)/2:y=(H-h)/2,format=yuv420p[vo]")
+        .add_mapping("[vo]")
+        .set_output_video_codec(Some("rawvideo"))
+        .add_args(&["-f", "nut"])
+        .set_output_file("-");
+    if video_info.has_audio() {
+        ffmpeg_command.add_mapping("0:a").set_output_audio_codec(Some("pcm_s16le"));
+    }
 
+    let mut ffmpeg_process = ffmpeg_command.build().unwrap().spawn_piped_stdout().map_err(PlayWithOSDError::FailedSpawningFFMpegProcess)?;
+
+    let mut mpv_command = ProcessCommand::new("mpv");
+    mpv_command
+        .arg(format!("--force-media-title={}", video_file.file_name().unwrap_or_default().to_string_lossy()))
+        .arg("-");
+    // there is no FFMpeg process in dry run mode
+    let ffmpeg_stdout = match ffmpeg_process.take_stdout() {
+        Some(ffmpeg_stdout) => ffmpeg_stdout,
+        None => {
+            mpv_command.spawn_unless_dry_run().map_err(PlayWithOSDError::FailedToStartMPV)?;
+            return Ok(());
+        },
+    };
+    mpv_command.stdin(std::process::Stdio::from(ffmpeg_stdout));
     let mut mpv_child_proc = mpv_command.spawn().map_err(PlayWithOSDError::FailedToStartMPV)?;
 
-    match mpv_child_proc.wait().unwrap() {
-        exit_result if ! exit_result.success() => Err(PlayWithOSDError::MPVExitedWithAnError(exit_result)),
-        _ => Ok(())
+    // the pipe is closed when the user quits MPV before the end of the video, this is not an error
+    let send_result = match osd_frames_iter.send_frames_to_ffmpeg(&mut ffmpeg_process) {
+        Err(SendFramesToFFMpegError::PipeError(error)) if error.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    };
+
+    let mpv_exit_status = mpv_child_proc.wait().map_err(PlayWithOSDError::FailedToStartMPV)?;
+    let ffmpeg_result = ffmpeg_process.wait().await;
+
+    // FFMpeg also fails when MPV is quit before the end of the video so only report its errors when MPV failed
+    if ! mpv_exit_status.success() {
+        ffmpeg_result.map_err(PlayWithOSDError::FFMpegExitedWithError)?;
+        return Err(PlayWithOSDError::MPVExitedWithAnError(mpv_exit_status));
     }
+    send_result.map_err(PlayWithOSDError::FailedSendingOSDFramesToFFMpeg)?;
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,96 @@
+
+//! Analysis of the audio of DJI Air Unit recordings deciding whether the audio fix is needed and by how much
+//!
+//! The Air Unit records an audio stream slightly longer than the video stream, the drift is measured from the
+//! durations of the streams and corrected with a tempo change. The audio is also recorded very quietly, its peak
+//! level gives the gain bringing it to a normal level. The sync fix only works from the start of the original
+//! recording, cut or remuxed videos are detected from the start time of their streams and the `encoder` tag
+//! written by FFMpeg based tools.
+
+use std::path::Path;
+
+use getset::CopyGetters;
+
+use crate::ffmpeg;
+
+use super::{AudioAdjustments, FixVideoFileAudioError, probe};
+
+
+const ANALYSIS_SAMPLE_RATE: u32 = 16000;
+
+/// drift ratio under which the audio is considered in sync, about 30ms per minute
+const SYNC_TOLERANCE: f64 = 0.0005;
+
+/// drift ratio above which the streams durations are not trusted, the video probably misses the end of a stream
+const MAX_DRIFT: f64 = 0.01;
+
+/// peak level in dBFS the gain brings the audio to
+const TARGET_PEAK_LEVEL: f64 = -1.0;
+
+/// gain under which the volume is considered normal
+const MIN_GAIN: f64 = 6.0;
+
+const MAX_GAIN: f64 = 40.0;
+
+/// Result of the analysis of the audio of a video
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct AudioFixAnalysis {
+    /// tempo factor correcting the drift of the audio, `None` when the audio is in sync
+    tempo: Option<f64>,
+    /// gain in dB bringing the audio to a normal level, `None` when the volume is normal
+    gain: Option<f64>,
+    /// peak level of the audio in dBFS
+    peak_level: f64,
+    /// whether the video does not look like an original recording, the drift being then wrongly corrected
+    cut: bool,
+}
+
+impl AudioFixAnalysis {
+
+    pub fn is_fix_needed(&self) -> bool {
+        self.tempo.is_some() || self.gain.is_some()
+    }
+
+    /// adjustments applying the fix, the manual adjustments being applied on top of it
+    pub fn adjustments(&self, manual_adjustments: &AudioAdjustments) -> AudioAdjustments {
+        let mut adjustments = AudioAdjustments::new(manual_adjustments.gain().or(self.gain), manual_adjustments.delay());
+        adjustments.set_tempo(self.tempo);
+        adjustments
+    }
+
+}
+
+/// peak level in dBFS of the audio of the video, decodes the whole audio stream
+async fn audio_peak_level<P: AsRef<Path>>(video_file: P) -> Result<f64, FixVideoFileAudioError> {
+    let samples = ffmpeg::read_audio_samples(video_file, ANALYSIS_SAMPLE_RATE, None).await?;
+    let peak = samples.iter().map(|sample| sample.abs()).fold(0.0f32, f32::max);
+    Ok(20.0 * (peak.max(1e-5) as f64).log10())
+}
+
+/// analyses the audio of the video, the volume is not analysed in dry run mode since FFMpeg is not run
+pub async fn analyze<P: AsRef<Path>>(video_file: P) -> Result<AudioFixAnalysis, FixVideoFileAudioError> {
+    let video_file = video_file.as_ref();
+    let video_info = probe(video_file)?;
+    let audio = video_info.audio().as_ref().ok_or(FixVideoFileAudioError::InputVideoDoesNotHaveAnAudioStream)?;
+
+    let remuxed = matches!(video_info.encoder(), Some(encoder) if encoder.starts_with("Lavf"));
+    let cut = remuxed || audio.start_offset().abs() > 0.05;
+
+    let tempo = match (audio.duration(), video_info.video_duration()) {
+        (Some(audio_duration), Some(video_duration)) => {
+            let ratio = audio_duration / video_duration;
+            log::debug!("audio duration {audio_duration:.3}s, video duration {video_duration:.3}s");
+            Some(ratio).filter(|ratio| (ratio - 1.0).abs() > SYNC_TOLERANCE && (ratio - 1.0).abs() < MAX_DRIFT)
+        },
+        _ => {
+            log::warn!("the duration of the audio or video stream is unknown, cannot check the audio sync");
+            None
+        },
+    };
+
+    let peak_level = if crate::process::dry_run() { TARGET_PEAK_LEVEL } else { audio_peak_level(video_file).await? };
+    let gain = Some((TARGET_PEAK_LEVEL - peak_level).min(MAX_GAIN)).filter(|gain| *gain >= MIN_GAIN);
+
+    Ok(AudioFixAnalysis { tempo, gain, peak_level, cut })
+}
@@ -0,0 +1,128 @@
+
+//! Detection of the black bars padding the picture, e.g. the 4:3 camera feeds recorded into 16:9 videos by some DVRs
+//!
+//! The FFMpeg `cropdetect` filter is run on a few samples spread over the video, the crop rectangle of each frame
+//! being written into a file by the `metadata` filter. The filter keeps the largest rectangle found since the start
+//! of the sample and the detected crop is the bounding box of the rectangles of all the samples so that the dark
+//! scenes do not crop the picture. No crop is detected in dry run mode since FFMpeg is not run.
+
+use std::{io::Error as IOError, path::Path};
+
+use derive_more::From;
+use thiserror::Error;
+
+use crate::ffmpeg;
+
+use super::{Region, Resolution, Timestamp, cut::{self, TempFiles}};
+
+
+/// number of samples analyzed
+const SAMPLE_COUNT: usize = 5;
+
+/// duration of the samples in seconds
+const SAMPLE_DURATION: f64 = 2.0;
+
+/// luminance under which a pixel is considered black
+const BLACK_LIMIT: u8 = 24;
+
+#[derive(Debug, Error, From)]
+pub enum AutocropError {
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error("failed reading the crop detection results: {0}")]
+    ResultsFileError(IOError),
+}
+
+/// crop rectangle as left, top, right and bottom edges, the right and bottom edges being excluded
+type Edges = (u32, u32, u32, u32);
+
+/// rectangles written by the metadata filter for each frame, the frames without picture being skipped
+fn parse_crop_rectangles(metadata: &str) -> Vec<Edges> {
+    let mut rectangles = vec![];
+    // x, y, width and height of the current frame
+    let mut values: [Option<i64>; 4] = [None; 4];
+    let mut push_rectangle = |values: &mut [Option<i64>; 4]| {
+        if let [Some(x), Some(y), Some(width), Some(height)] = *values {
+            if x >= 0 && y >= 0 && width > 0 && height > 0 {
+                rectangles.push((x as u32, y as u32, (x + width) as u32, (y + height) as u32));
+            }
+        }
+        *values = [None; 4];
+    };
+    for line in metadata.lines().map(str::trim) {
+        if line.starts_with("frame:") { push_rectangle(&mut values) }
+        let (key, value) = match line.strip_prefix("lavfi.cropdetect.").and_then(|line| line.split_once('=')) {
+            Some(key_value) => key_value,
+            None => continue,
+        };
+        let index = match key {
+            "x" => 0,
+            "y" => 1,
+            "w" => 2,
+            "h" => 3,
+            _ => continue,
+        };
+        values[index] = value.trim().parse().ok();
+    }
+    push_rectangle(&mut values);
+    rectangles
+}
+
+/// bounding box of the rectangles as a crop region, `None` if it is the whole video
+fn crop_region(rectangles: &[Edges], resolution: Resolution) -> Option<Region> {
+    let (left, top, right, bottom) = rectangles.iter().copied().reduce(|(left, top, right, bottom), rectangle|
+        (left.min(rectangle.0), top.min(rectangle.1), right.max(rectangle.2), bottom.max(rectangle.3)))?;
+    let (right, bottom) = (right.min(resolution.width), bottom.min(resolution.height));
+    if left == 0 && top == 0 && right == resolution.width && bottom == resolution.height { return None }
+    Some(Region::new4(left as i16, top as i16, (right - left) as u16, (bottom - top) as u16))
+}
+
+/// Crop region removing the black bars of the section of the video between `start` and `end` in seconds, `None`
+/// when there is no black bar, the temporary files being written next to `output_video_file`
+pub async fn detect_crop(video_file: &Path, resolution: Resolution, start: f64, end: f64, output_video_file: &Path)
+        -> Result<Option<Region>, AutocropError> {
+    if crate::process::dry_run() { return Ok(None) }
+    log::info!("detecting black bars: {}", video_file.to_string_lossy());
+    let sample_duration = SAMPLE_DURATION.min(end - start);
+    let timestamp = |seconds: f64| Some(Timestamp::from_milliseconds((seconds.max(0.0) * 1000.0) as u64));
+    let mut rectangles = vec![];
+    for sample_index in 0..SAMPLE_COUNT {
+        let sample_start = start + (end - start - sample_duration) * (sample_index as f64 + 0.5) / SAMPLE_COUNT as f64;
+        let temp_files = TempFiles(vec![cut::temp_file_path(output_video_file, &format!(".cropdetect{sample_index}.log"))]);
+        let metadata_file = ffmpeg::escape_filter_option_value(&temp_files.0[0].to_string_lossy());
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command
+            .add_input_file_slice(video_file, timestamp(sample_start), timestamp(sample_start + sample_duration))
+            .add_complex_filter(&format!("[0:v]cropdetect=limit={BLACK_LIMIT}:round=2:reset=0,metadata=mode=print:file={metadata_file}"))
+            .set_output_stdout("null");
+        ffmpeg_command.build().unwrap().spawn_no_output()?.wait().await?;
+        let metadata = fs_err::read_to_string(&temp_files.0[0]).map_err(AutocropError::ResultsFileError)?;
+        rectangles.extend(parse_crop_rectangles(&metadata));
+    }
+    let region = crop_region(&rectangles, resolution);
+    match &region {
+        Some(region) => log::info!("black bars detected, cropping to {}", region.to_ffmpeg_filter_string()),
+        None => log::info!("no black bars detected"),
+    }
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn pillarbox_crop() {
+        let metadata = "frame:0    pts:0       pts_time:0\nlavfi.cropdetect.x1=240\nlavfi.cropdetect.x2=1679\nlavfi.cropdetect.y1=0\n\
+            lavfi.cropdetect.y2=1079\nlavfi.cropdetect.w=1440\nlavfi.cropdetect.h=1080\nlavfi.cropdetect.x=240\nlavfi.cropdetect.y=0\n";
+        let rectangles = parse_crop_rectangles(metadata);
+        assert_eq!(rectangles, vec![(240, 0, 1680, 1080)]);
+        let region = crop_region(&rectangles, Resolution::new(1920, 1080)).unwrap();
+        assert_eq!(region.to_ffmpeg_filter_string(), Region::new4(240, 0, 1440, 1080).to_ffmpeg_filter_string());
+        assert!(crop_region(&[(0, 0, 1920, 1080)], Resolution::new(1920, 1080)).is_none());
+    }
+
+}
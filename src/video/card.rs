@@ -0,0 +1,182 @@
+
+//! Title and end cards shown for a few seconds before and after the transcoded video
+//!
+//! A card is either an image, scaled to fit the video and centered on a black background, or lines of text centered
+//! on a black background. The cards are encoded separately with the same encoding settings and pixel format as the
+//! video, with silent audio when the video has audio, then joined with the video without re-encoding it. They fade in
+//! and out.
+//!
+//! A flight stats card is a text card summarizing the flight read from the OSD file, see [`FlightStats`]. It is
+//! replaced by the text card before the transcoding with [`CardContent::with_flight_stats`].
+
+use std::{path::PathBuf, str::FromStr};
+
+use getset::{CopyGetters, Getters};
+use thiserror::Error;
+
+use crate::{ffmpeg, osd::flight_stats::FlightStats};
+
+use super::{Resolution, layers, probe};
+
+
+/// duration of the cards in seconds when not set
+pub const DEFAULT_DURATION: f64 = 3.0;
+
+/// duration of the fades in and out of the cards in seconds
+const FADE_DURATION: f64 = 0.5;
+
+/// prefix of the text card specifications
+const TEXT_PREFIX: &str = "text:";
+
+/// specification of the flight stats cards
+const FLIGHT_STATS: &str = "flight-stats";
+
+/// height of a text line relatively to the video height
+const LINE_HEIGHT_RATIO: u32 = 10;
+
+#[derive(Debug, Error)]
+#[error("invalid card: {0}, expected <image file>, text:<line>[|<line>...] or flight-stats")]
+pub struct InvalidCardError(String);
+
+#[derive(Debug, Error)]
+#[error("the flight stats card must be replaced by its text card before being encoded")]
+pub struct FlightStatsCardNotReplacedError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardContent {
+    Image(PathBuf),
+    /// lines of text
+    Text(Vec<String>),
+    /// summary of the flight read from the OSD file
+    FlightStats,
+}
+
+impl CardContent {
+
+    /// the text card of the flight stats for a flight stats card, the content itself otherwise
+    pub fn with_flight_stats(&self, flight_stats: &FlightStats) -> Self {
+        match self {
+            Self::FlightStats => Self::Text(flight_stats.summary_lines()),
+            content => content.clone(),
+        }
+    }
+
+}
+
+impl FromStr for CardContent {
+    type Err = InvalidCardError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.strip_prefix(TEXT_PREFIX) {
+            Some(text) => {
+                let lines = text.split('|').map(str::to_owned).collect::<Vec<_>>();
+                if lines.iter().all(|line| line.trim().is_empty()) { return Err(InvalidCardError(value.to_owned())) }
+                Ok(Self::Text(lines))
+            },
+            None if value.is_empty() => Err(InvalidCardError(value.to_owned())),
+            None if value == FLIGHT_STATS => Ok(Self::FlightStats),
+            None => Ok(Self::Image(PathBuf::from(value))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct Card {
+    #[getset(get = "pub")]
+    content: CardContent,
+    /// duration in seconds
+    #[getset(get_copy = "pub")]
+    duration: f64,
+}
+
+impl Card {
+
+    pub fn new(content: CardContent, duration: f64) -> Self {
+        Self { content, duration }
+    }
+
+    pub fn with_flight_stats(&self, flight_stats: &FlightStats) -> Self {
+        Self { content: self.content.with_flight_stats(flight_stats), duration: self.duration }
+    }
+
+    /// drawtext filters drawing the lines centered on the frame
+    fn text_filters(lines: &[String]) -> Vec<String> {
+        lines.iter().enumerate().map(|(line_index, line)| {
+            // offset of the line center from the frame center in line heights
+            let offset = line_index as f64 + 0.5 - lines.len() as f64 / 2.0;
+            format!("drawtext=expansion=none:text={}:fontcolor=white:fontsize=h/{}:x=(w-text_w)/2:y=h/2+({offset})*h/{LINE_HEIGHT_RATIO}-text_h/2",
+                ffmpeg::escape_filter_option_value(line), LINE_HEIGHT_RATIO * 3 / 2)
+        }).collect()
+    }
+
+    /// complex filter drawing the card onto the black background of the input `[0]` in the pixel format of the video,
+    /// the output being labelled `[vo]`
+    fn filter_graph(&self, resolution: Resolution, pixel_format: &str) -> Result<String, FlightStatsCardNotReplacedError> {
+        let mut filters = vec![];
+        if self.duration > 2.0 * FADE_DURATION {
+            filters.push(format!("fade=t=in:st=0:d={FADE_DURATION}"));
+            filters.push(format!("fade=t=out:st={}:d={FADE_DURATION}", self.duration - FADE_DURATION));
+        }
+        filters.push(format!("format={pixel_format}"));
+        Ok(match &self.content {
+            CardContent::Image(image_file) => format!(
+                "movie=filename={},scale={}:{}:force_original_aspect_ratio=decrease,setsar=1[card];[0][card]overlay=x=(W-w)/2:y=(H-h)/2,{}[vo]",
+                ffmpeg::escape_filter_option_value(&image_file.to_string_lossy()), resolution.width, resolution.height, filters.join(",")),
+            CardContent::Text(lines) => format!("[0]{},{}[vo]", Self::text_filters(lines).join(","), filters.join(",")),
+            CardContent::FlightStats => return Err(FlightStatsCardNotReplacedError),
+        })
+    }
+
+    /// Adds the inputs generating the card at the resolution, frame rate and pixel format of the video, with silence
+    /// matching the audio stream of the video if any, then maps the card streams. The encoders are set by the caller.
+    pub(super) fn add_to_ffmpeg_command(&self, ffmpeg_command: &mut ffmpeg::CommandBuilder, video_info: &probe::Result)
+            -> Result<(), FlightStatsCardNotReplacedError> {
+        let (resolution, frame_rate) = (video_info.resolution(), video_info.frame_rate());
+        ffmpeg_command
+            .add_lavfi_input(&format!("color=c=black:s={resolution}:r={}/{}:d={}", frame_rate.numerator(), frame_rate.denominator(), self.duration))
+            .add_complex_filter(&self.filter_graph(resolution, layers::ffmpeg_pixel_format(video_info))?)
+            .add_mapping("[vo]");
+        if let Some(audio) = video_info.audio() {
+            let channel_layout = match audio.channels() {
+                1 | 2 | 6 | 8 => audio.channel_layout(),
+                _ => "stereo".to_owned(),
+            };
+            ffmpeg_command
+                .add_lavfi_input(&format!("anullsrc=r={}:cl={channel_layout},atrim=duration={}", audio.sample_rate(), self.duration))
+                .add_mapping("1:a");
+        }
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_card_content() {
+        assert_eq!("text:My flight|Bando".parse::<CardContent>().unwrap(), CardContent::Text(vec!["My flight".to_owned(), "Bando".to_owned()]));
+        assert_eq!("intro.png".parse::<CardContent>().unwrap(), CardContent::Image(PathBuf::from("intro.png")));
+        assert_eq!("flight-stats".parse::<CardContent>().unwrap(), CardContent::FlightStats);
+        assert!("text: ".parse::<CardContent>().is_err());
+    }
+
+    #[test]
+    fn text_card_filter_graph() {
+        let card = Card::new(CardContent::Text(vec!["A".to_owned()]), 0.5);
+        assert_eq!(card.filter_graph(Resolution::new(1920, 1080), "yuv420p").unwrap(),
+            "[0]drawtext=expansion=none:text=A:fontcolor=white:fontsize=h/15:x=(w-text_w)/2:y=h/2+(0)*h/10-text_h/2,format=yuv420p[vo]");
+    }
+
+    #[test]
+    fn card_encoded_in_the_video_pixel_format() {
+        let card = Card::new(CardContent::Image(PathBuf::from("intro.png")), 3.0);
+        assert_eq!(card.filter_graph(Resolution::new(1920, 1080), "yuv420p10le").unwrap(),
+            "movie=filename=intro.png,scale=1920:1080:force_original_aspect_ratio=decrease,setsar=1[card];[0][card]overlay=x=(W-w)/2:y=(H-h)/2,\
+                fade=t=in:st=0:d=0.5,fade=t=out:st=2.5:d=0.5,format=yuv420p10le[vo]");
+        assert!(Card::new(CardContent::FlightStats, 3.0).filter_graph(Resolution::new(1920, 1080), "yuv420p").is_err());
+    }
+
+}
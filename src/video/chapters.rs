@@ -0,0 +1,217 @@
+
+//! Chapters marking the flights of a video, written in the YouTube description format or embedded into the video
+//!
+//! YouTube turns the list of timestamps of a video description into chapters when the first one is at 0:00, so the
+//! chapters cover the whole video: the flights and the disarmed sections before, between and after them.
+
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters};
+use thiserror::Error;
+
+use crate::{ffmpeg, file::{self, TouchError}, osd::flights::{Flight, FlightDetectionError}};
+
+use super::{Timestamp, VideoProbingError, cut::{self, TempFiles}, probe};
+
+
+/// disarmed sections shorter than this duration in seconds are merged into the adjacent flights
+const MIN_DISARMED_CHAPTER_DURATION: f64 = 10.0;
+
+#[derive(Debug, Error, From)]
+pub enum ChaptersError {
+    #[error("input video file does not exist")]
+    InputVideoFileDoesNotExist,
+    #[error("output file exists: {}", .0.to_string_lossy())]
+    #[from(ignore)]
+    OutputFileExists(PathBuf),
+    #[error("input file and output file are the same file")]
+    InputAndOutputFileIsTheSame,
+    #[error("failed to get input video details")]
+    FailedToGetInputVideoDetails(VideoProbingError),
+    #[error(transparent)]
+    FlightDetectionError(FlightDetectionError),
+    #[error("failed writing chapters file: {0}")]
+    FailedWritingChaptersFile(std::io::Error),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error(transparent)]
+    WriteToFileError(TouchError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct Chapter {
+    #[getset(get_copy = "pub")]
+    start: Timestamp,
+    #[getset(get_copy = "pub")]
+    end: Timestamp,
+    #[getset(get = "pub")]
+    title: String,
+}
+
+/// timestamp in the YouTube format: `M:SS` or `H:MM:SS`
+fn youtube_timestamp(timestamp: Timestamp) -> String {
+    if timestamp.hours() > 0 {
+        format!("{}:{:02}:{:02}", timestamp.hours(), timestamp.minutes(), timestamp.seconds())
+    } else {
+        format!("{}:{:02}", timestamp.minutes(), timestamp.seconds())
+    }
+}
+
+/// chapters covering the whole video, one per flight and one per disarmed section
+pub fn flight_chapters(flights: &[Flight], video_duration: f64) -> Vec<Chapter> {
+    let video_end = Timestamp::from_milliseconds((video_duration * 1000.0) as u64);
+    let is_long_enough = |start: Timestamp, end: Timestamp|
+        end.total_milliseconds() as f64 - start.total_milliseconds() as f64 >= MIN_DISARMED_CHAPTER_DURATION * 1000.0;
+
+    let mut chapters: Vec<Chapter> = vec![];
+    let mut position = Timestamp::default();
+    for (index, flight) in flights.iter().enumerate() {
+        let (start, end) = (flight.start().min(video_end), flight.end().min(video_end));
+        if start >= end { continue }
+        let start = if is_long_enough(position, start) {
+            chapters.push(Chapter { start: position, end: start, title: "Disarmed".to_owned() });
+            start
+        } else {
+            // the short disarmed section is merged into the previous flight, or this flight when it is the first one
+            match chapters.last_mut() {
+                Some(previous_chapter) => { previous_chapter.end = start; start },
+                None => position,
+            }
+        };
+        chapters.push(Chapter { start, end, title: format!("Flight {}", index + 1) });
+        position = end;
+    }
+    match chapters.last_mut() {
+        Some(last_chapter) if ! is_long_enough(position, video_end) => last_chapter.end = video_end,
+        _ if position < video_end => chapters.push(Chapter { start: position, end: video_end, title: "Disarmed".to_owned() }),
+        _ => {},
+    }
+    chapters
+}
+
+/// chapters in the YouTube description format, one `<timestamp> <title>` line per chapter
+pub fn youtube_chapters(chapters: &[Chapter]) -> String {
+    chapters.iter().map(|chapter| format!("{} {}\n", youtube_timestamp(chapter.start), chapter.title)).collect()
+}
+
+/// chapters in the FFMpeg metadata file format
+fn ffmetadata_chapters(chapters: &[Chapter]) -> String {
+    let mut ffmetadata = ";FFMETADATA1\n".to_owned();
+    for chapter in chapters {
+        ffmetadata.push_str(&format!("[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            chapter.start.total_milliseconds(), chapter.end.total_milliseconds(), chapter.title));
+    }
+    ffmetadata
+}
+
+/// chapters marking the flights of the video, detected from its associated OSD file
+pub fn video_flight_chapters<P: AsRef<Path>>(video_file: P) -> Result<Vec<Chapter>, ChaptersError> {
+    let video_file = video_file.as_ref();
+    let osd_file = crate::osd::file::find_associated_to_video_file(video_file).ok_or(FlightDetectionError::OSDFileNotFound)?;
+    let flights = crate::osd::flights::detect_osd_file_flights(osd_file)?;
+    let video_info = probe(video_file)?;
+    Ok(flight_chapters(&flights, video_info.duration()))
+}
+
+/// writes the chapters in the YouTube description format, to the standard output when the output file is `-`
+pub fn write_youtube_chapters<P: AsRef<Path>>(chapters: &[Chapter], output_file: P, overwrite: bool) -> Result<(), ChaptersError> {
+    let output_file = output_file.as_ref();
+    if file::is_stdout(output_file) {
+        print!("{}", youtube_chapters(chapters));
+        return Ok(());
+    }
+    if ! overwrite && output_file.exists() { return Err(ChaptersError::OutputFileExists(output_file.to_path_buf())) }
+    log::info!("writing chapters: {}", output_file.to_string_lossy());
+    if crate::process::dry_run() { return Ok(()) }
+    fs_err::write(output_file, youtube_chapters(chapters))?;
+    Ok(())
+}
+
+/// copies the video without re-encoding into the output video with the chapters embedded into it
+pub async fn embed_chapters<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: Q, chapters: &[Chapter],
+        overwrite: bool) -> Result<(), ChaptersError> {
+
+    let (input_video_file, output_video_file) = (input_video_file.as_ref(), output_video_file.as_ref());
+    if ! file::input_exists(input_video_file) { return Err(ChaptersError::InputVideoFileDoesNotExist) }
+    if input_video_file == output_video_file { return Err(ChaptersError::InputAndOutputFileIsTheSame) }
+    if ! overwrite && output_video_file.exists() { return Err(ChaptersError::OutputFileExists(output_video_file.to_path_buf())) }
+
+    let video_info = probe(input_video_file)?;
+    file::touch(output_video_file)?;
+
+    log::info!("embedding {} chapters: {} -> {}", chapters.len(), input_video_file.to_string_lossy(), output_video_file.to_string_lossy());
+
+    let metadata_file = cut::temp_file_path(output_video_file, ".chapters.txt");
+    let _temp_files = TempFiles(vec![metadata_file.clone()]);
+    if ! crate::process::dry_run() {
+        fs_err::write(&metadata_file, ffmetadata_chapters(chapters))?;
+    }
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    ffmpeg_command
+        .add_input_file(input_video_file)
+        .add_input_file(&metadata_file)
+        .add_mapping("0")
+        .set_output_video_codec(Some("copy"))
+        .set_output_audio_codec(Some("copy"))
+        .copy_metadata_from(0)
+        .add_args(&["-map_chapters", "1"])
+        .set_output_file(output_video_file)
+        .set_overwrite_output_file(true);
+
+    ffmpeg_command.build().unwrap().spawn_with_progress(video_info.frame_count())?.wait().await?;
+
+    if ! file::is_url(input_video_file) {
+        super::preserve_modification_time(input_video_file, output_video_file);
+    }
+    log::info!("chapters embedded successfully");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn chapter(start_seconds: u64, end_seconds: u64, title: &str) -> Chapter {
+        Chapter {
+            start: Timestamp::from_milliseconds(start_seconds * 1000),
+            end: Timestamp::from_milliseconds(end_seconds * 1000),
+            title: title.to_owned(),
+        }
+    }
+
+    fn flight(start_seconds: u32, end_seconds: u32) -> Flight {
+        Flight::new(start_seconds * crate::osd::FRAME_RATE, end_seconds * crate::osd::FRAME_RATE)
+    }
+
+    #[test]
+    fn youtube_timestamps() {
+        assert_eq!(youtube_timestamp(Timestamp::default()), "0:00");
+        assert_eq!(youtube_timestamp(Timestamp::from_milliseconds(65_500)), "1:05");
+        assert_eq!(youtube_timestamp(Timestamp::from_milliseconds(3_599_000)), "59:59");
+        assert_eq!(youtube_timestamp(Timestamp::from_milliseconds(3_600_000)), "1:00:00");
+        assert_eq!(youtube_timestamp(Timestamp::from_milliseconds(3_723_000)), "1:02:03");
+    }
+
+    #[test]
+    fn chapters_cover_the_whole_video() {
+        // the 5s disarmed section between the flights is merged into the first flight
+        assert_eq!(flight_chapters(&[flight(20, 60), flight(65, 120)], 150.0), vec![
+            chapter(0, 20, "Disarmed"), chapter(20, 65, "Flight 1"), chapter(65, 120, "Flight 2"), chapter(120, 150, "Disarmed"),
+        ]);
+        // the short disarmed section before the first flight is merged into it and the flight is cut at the video end
+        assert_eq!(flight_chapters(&[flight(5, 200)], 100.0), vec![chapter(0, 100, "Flight 1")]);
+        assert_eq!(flight_chapters(&[], 100.0), vec![chapter(0, 100, "Disarmed")]);
+    }
+
+    #[test]
+    fn youtube_chapters_past_one_hour() {
+        let chapters = flight_chapters(&[flight(3590, 3700)], 4000.0);
+        assert_eq!(youtube_chapters(&chapters), "0:00 Disarmed\n59:50 Flight 1\n1:01:40 Disarmed\n");
+    }
+
+}
@@ -0,0 +1,124 @@
+
+//! Wall-clock time of the recording drawn onto the video, derived from the recording start time plus the playback
+//! position
+
+use std::io::Error as IOError;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use derive_more::From;
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+
+use crate::{date_time, ffmpeg};
+
+use super::{Timestamp, probe, text_annotation::TextPosition};
+
+
+pub const DEFAULT_FORMAT: &str = "%Y-%m-%d %T";
+
+/// the goggles without a set clock write dates close to the epoch
+const MIN_PLAUSIBLE_YEAR: i64 = 2000;
+
+#[derive(Debug, Error, From)]
+pub enum ClockError {
+    #[error("video has no valid creation time metadata")]
+    NoCreationTime,
+    #[error("failed getting the video file modification time: {0}")]
+    ModificationTime(IOError),
+}
+
+/// where the recording start time is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ClockSource {
+    /// creation time metadata if valid, otherwise modification time
+    Auto,
+    /// `creation_time` metadata of the video file
+    Metadata,
+    /// modification time of the video file minus the video duration, the file being last modified when the
+    /// recording stopped
+    ModificationTime,
+}
+
+/// parses the ISO 8601 creation time of the video into seconds since the epoch, `None` for the implausible dates
+fn parse_creation_time(value: &str) -> Option<f64> {
+    let min_plausible_time = date_time::days_from_civil(MIN_PLAUSIBLE_YEAR, 1, 1) as f64 * 86400.0;
+    date_time::parse_iso8601(value).filter(|time| *time >= min_plausible_time)
+}
+
+fn modification_time_start(video_file: &Path, duration: f64) -> Result<f64, ClockError> {
+    let modified = crate::file::local_path(video_file).metadata().and_then(|metadata| metadata.modified())?;
+    let modified = modified.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs_f64()).unwrap_or(0.0);
+    Ok(modified - duration)
+}
+
+/// Settings used to draw the wall-clock time onto the video
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get_copy = "pub", set = "pub")]
+pub struct ClockSettings {
+    source: ClockSource,
+    position: TextPosition,
+    /// strftime format
+    #[getset(skip)]
+    #[getset(get = "pub", set = "pub")]
+    format: String,
+}
+
+impl ClockSettings {
+
+    pub fn new(source: ClockSource) -> Self {
+        Self { source, position: TextPosition::BottomRight, format: DEFAULT_FORMAT.to_owned() }
+    }
+
+    /// time the recording started in seconds since the epoch
+    pub fn recording_start(&self, video_file: &Path, video_info: &probe::Result) -> Result<f64, ClockError> {
+        let from_metadata = || video_info.creation_time().as_deref().and_then(parse_creation_time);
+        let start = match self.source {
+            ClockSource::Metadata => from_metadata().ok_or(ClockError::NoCreationTime)?,
+            ClockSource::ModificationTime => modification_time_start(video_file, video_info.duration())?,
+            ClockSource::Auto => match from_metadata() {
+                Some(start) => start,
+                None => {
+                    log::info!("no valid creation time metadata, deriving the recording start from the file modification time");
+                    modification_time_start(video_file, video_info.duration())?
+                },
+            },
+        };
+        Ok(start)
+    }
+
+    /// FFMpeg drawtext filter drawing the local time of the recording start plus the playback position, for a video
+    /// starting at the `start` timestamp of the input video
+    pub fn ffmpeg_filter(&self, recording_start: f64, start: Option<Timestamp>) -> String {
+        let offset = recording_start + start.map(|start| start.total_milliseconds()).unwrap_or(0) as f64 / 1000.0;
+        // the arguments of the text expansion function are separated with colons
+        let text = format!("%{{pts:localtime:{offset:.3}:{}}}", self.format.replace(':', "\\:"));
+        let (x, y) = self.position.drawtext_coordinates();
+        format!("drawtext=text={}:fontsize=h/25:fontcolor=white:borderw=2:bordercolor=black:x={x}:y={y}",
+            ffmpeg::escape_filter_option_value(&text))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn implausible_creation_times_rejected() {
+        assert_eq!(parse_creation_time("2023-05-06T12:34:56+02:00"), Some(1_683_369_296.0));
+        assert_eq!(parse_creation_time("1970-01-01T00:00:00Z"), None);
+        assert_eq!(parse_creation_time("2023-05-06"), None);
+    }
+
+    #[test]
+    fn drawtext_filter_with_the_start_offset() {
+        let mut settings = ClockSettings::new(ClockSource::Auto);
+        settings.set_format("%H:%M".to_owned());
+        assert_eq!(settings.ffmpeg_filter(1000.0, Some("2".parse().unwrap())),
+            r"drawtext=text=%{pts\\:localtime\\:1002.000\\:%H\\\\\\:%M}:fontsize=h/25:fontcolor=white:borderw=2:bordercolor=black:x=w-text_w-h/30:y=h-text_h-h/30");
+    }
+
+}
@@ -0,0 +1,270 @@
+
+//! Concatenation of several videos into a single video, e.g. to upload the clips of a session together
+//!
+//! Videos with the same codecs, resolution and frame rate are joined with the concat demuxer without re-encoding.
+//! Otherwise, or when crossfading, the videos are re-encoded: they are scaled and padded to the resolution and
+//! frame rate of the first video and the missing audio streams are replaced with silence.
+
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use ffmpeg_next::Rational;
+use thiserror::Error;
+
+use crate::{ffmpeg, file::{self, TouchError}, temp_dir::{self, InsufficientSpaceError}};
+
+use super::{
+    VideoProbingError,
+    cut::{self, REENCODE_AUDIO_BITRATE, REENCODE_AUDIO_ENCODER, REENCODE_VIDEO_CRF, TempFiles},
+    probe,
+};
+
+
+/// audio format the audio streams are converted to when re-encoding so that they can be joined
+const REENCODE_AUDIO_SAMPLE_RATE: u32 = 48000;
+const REENCODE_AUDIO_CHANNEL_LAYOUT: &str = "stereo";
+
+#[derive(Debug, Error, From)]
+pub enum ConcatVideosError {
+    #[error("at least 2 input videos are needed")]
+    NotEnoughInputVideos,
+    #[error("input video file does not exist: {0}")]
+    #[from(ignore)]
+    InputVideoFileDoesNotExist(PathBuf),
+    #[error("output video file exists")]
+    OutputVideoFileExists,
+    #[error("the output file is one of the input files")]
+    OutputIsAnInputFile,
+    #[error("failed to get input video details")]
+    FailedToGetInputVideoDetails(VideoProbingError),
+    #[error("crossfade duration {duration}s is longer than the input video {video_file}")]
+    #[from(ignore)]
+    CrossfadeLongerThanVideo { duration: f64, video_file: PathBuf },
+    #[error("invalid crossfade duration: {0}, it must be greater than 0")]
+    #[from(ignore)]
+    InvalidCrossfadeDuration(f64),
+    #[error("failed resolving the path of the input video file: {0}")]
+    #[from(ignore)]
+    FailedResolvingInputVideoPath(std::io::Error),
+    #[error("failed writing temporary file: {0}")]
+    FailedWritingTempFile(std::io::Error),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error(transparent)]
+    WriteToFileError(TouchError),
+    #[error(transparent)]
+    InsufficientSpace(InsufficientSpaceError),
+}
+
+/// whether the videos can be joined without re-encoding: same codecs, resolution, frame rate and audio format
+fn can_stream_copy(videos_info: &[probe::Result]) -> bool {
+    let first = &videos_info[0];
+    let audio_format = |video_info: &probe::Result| video_info.audio().as_ref()
+        .map(|audio| (audio.codec().clone(), audio.channels(), audio.sample_rate()));
+    videos_info.iter().all(|video_info|
+        video_info.video_codec() == first.video_codec()
+            && (video_info.resolution().width, video_info.resolution().height) == (first.resolution().width, first.resolution().height)
+            && video_info.frame_rate() == first.frame_rate()
+            && audio_format(video_info) == audio_format(first)
+    )
+}
+
+/// encoder producing the same codec as the first video for H.264 and H.265 videos, H.264 otherwise
+fn reencode_video_encoder(video_codec: &Option<String>) -> &'static str {
+    match video_codec.as_deref() {
+        Some("hevc") => "libx265",
+        _ => "libx264",
+    }
+}
+
+/// number of frames of the joined video, the videos overlapping during the crossfades
+fn output_frame_count(videos_info: &[probe::Result], crossfade: Option<f64>) -> u64 {
+    let frame_count = videos_info.iter().map(|video_info| video_info.frame_count()).sum::<u64>();
+    let crossfade_frame_count = crossfade.map_or(0, |crossfade| (crossfade * f64::from(videos_info[0].frame_rate())).round() as u64);
+    frame_count.saturating_sub(crossfade_frame_count * (videos_info.len() as u64).saturating_sub(1))
+}
+
+fn ffmpeg_rational(rational: Rational) -> String {
+    format!("{}/{}", rational.numerator(), rational.denominator())
+}
+
+/// filter graph normalizing the videos and joining them, with crossfades of `crossfade` seconds between the videos
+/// when set, outputs `[vo]` and `[ao]` when `with_audio`
+fn ffmpeg_filter_graph(videos_info: &[probe::Result], crossfade: Option<f64>, with_audio: bool) -> String {
+    let first = &videos_info[0];
+    let resolution = first.resolution();
+    let frame_rate = ffmpeg_rational(first.frame_rate());
+    let mut graph = vec![];
+
+    for (index, video_info) in videos_info.iter().enumerate() {
+        graph.push(format!(
+            "[{index}:v]scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={frame_rate},format=yuv420p,settb=AVTB[v{index}]",
+            width = resolution.width, height = resolution.height
+        ));
+        if with_audio {
+            let audio_format = format!("aformat=sample_rates={REENCODE_AUDIO_SAMPLE_RATE}:channel_layouts={REENCODE_AUDIO_CHANNEL_LAYOUT}");
+            match video_info.audio() {
+                Some(_) => graph.push(format!("[{index}:a]{audio_format}[a{index}]")),
+                None => graph.push(format!(
+                    "anullsrc=r={REENCODE_AUDIO_SAMPLE_RATE}:cl={REENCODE_AUDIO_CHANNEL_LAYOUT},atrim=duration={},{audio_format}[a{index}]",
+                    video_info.duration()
+                )),
+            }
+        }
+    }
+
+    match crossfade {
+        None => {
+            let inputs = (0..videos_info.len()).map(|index|
+                if with_audio { format!("[v{index}][a{index}]") } else { format!("[v{index}]") }
+            ).collect::<String>();
+            let outputs = if with_audio { "[vo][ao]" } else { "[vo]" };
+            graph.push(format!("{inputs}concat=n={}:v=1:a={}{outputs}", videos_info.len(), with_audio as u8));
+        },
+        Some(crossfade) => {
+            // each crossfade starts `crossfade` seconds before the end of the video joined so far
+            let mut offset = 0.0;
+            let last_index = videos_info.len() - 1;
+            for (index, video_info) in videos_info.iter().enumerate().take(last_index) {
+                offset += video_info.duration() - crossfade;
+                let input = if index == 0 { "v0".to_owned() } else { format!("vx{index}") };
+                let output = if index + 1 == last_index { "vo".to_owned() } else { format!("vx{}", index + 1) };
+                graph.push(format!("[{input}][v{}]xfade=transition=fade:duration={crossfade}:offset={offset:.3}[{output}]", index + 1));
+                if with_audio {
+                    let input = if index == 0 { "a0".to_owned() } else { format!("ax{index}") };
+                    let output = if index + 1 == last_index { "ao".to_owned() } else { format!("ax{}", index + 1) };
+                    graph.push(format!("[{input}][a{}]acrossfade=d={crossfade}[{output}]", index + 1));
+                }
+            }
+        },
+    }
+
+    graph.join(";")
+}
+
+/// joins the videos into the output video, re-encoding them when they cannot be stream copied, when crossfading
+/// or when `reencode` is set
+pub async fn concat_videos<P: AsRef<Path>, Q: AsRef<Path>>(input_video_files: &[P], output_video_file: Q, overwrite: bool,
+        crossfade: Option<f64>, reencode: bool) -> Result<(), ConcatVideosError> {
+
+    let output_video_file = output_video_file.as_ref();
+
+    if input_video_files.len() < 2 { return Err(ConcatVideosError::NotEnoughInputVideos) }
+    for input_video_file in input_video_files {
+        let input_video_file = input_video_file.as_ref();
+        if ! file::input_exists(input_video_file) { return Err(ConcatVideosError::InputVideoFileDoesNotExist(input_video_file.to_path_buf())) }
+        if input_video_file == output_video_file { return Err(ConcatVideosError::OutputIsAnInputFile) }
+    }
+    if let Some(crossfade) = crossfade {
+        if ! crossfade.is_finite() || crossfade <= 0.0 { return Err(ConcatVideosError::InvalidCrossfadeDuration(crossfade)) }
+    }
+    if ! overwrite && output_video_file.exists() { return Err(ConcatVideosError::OutputVideoFileExists) }
+
+    let videos_info = input_video_files.iter().map(probe).collect::<Result<Vec<_>, _>>()?;
+    if let Some(crossfade) = crossfade {
+        if let Some((input_video_file, _)) = input_video_files.iter().zip(&videos_info).find(|(_, video_info)| video_info.duration() <= crossfade) {
+            return Err(ConcatVideosError::CrossfadeLongerThanVideo { duration: crossfade, video_file: input_video_file.as_ref().to_path_buf() });
+        }
+    }
+    let frame_count = output_frame_count(&videos_info, crossfade);
+    let output_size = input_video_files.iter().zip(&videos_info)
+        .map(|(input_video_file, video_info)| temp_dir::estimated_size(input_video_file.as_ref(), video_info.duration(), video_info.duration()))
+        .sum();
+    temp_dir::check_output_available_space(output_video_file, output_size)?;
+
+    file::touch(output_video_file)?;
+
+    let stream_copy = ! reencode && crossfade.is_none() && can_stream_copy(&videos_info);
+    log::info!("joining {} videos ({}): {}", input_video_files.len(), if stream_copy { "stream copy" } else { "re-encoding" },
+        output_video_file.to_string_lossy());
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    let mut temp_files = TempFiles(vec![]);
+
+    if stream_copy {
+        let concat_list_file = cut::temp_file_path(output_video_file, ".concat.txt");
+        temp_files.0.push(concat_list_file.clone());
+        // the concat demuxer resolves the relative paths from the directory of the list file
+        let input_video_files = input_video_files.iter().map(fs_err::canonicalize).collect::<Result<Vec<_>, _>>()
+            .map_err(ConcatVideosError::FailedResolvingInputVideoPath)?;
+        cut::write_concat_list(&concat_list_file, &input_video_files)?;
+        ffmpeg_command
+            .add_concat_list_input(&concat_list_file)
+            .add_mapping("0")
+            .set_output_video_codec(Some("copy"))
+            .set_output_audio_codec(Some("copy"))
+            // the metadata are read from the first video, the concat demuxer does not provide them
+            .add_input_file(&input_video_files[0])
+            .copy_metadata_from(1);
+    } else {
+        let with_audio = videos_info.iter().any(|video_info| video_info.has_audio());
+        if with_audio && videos_info.iter().any(|video_info| ! video_info.has_audio()) {
+            log::info!("some videos have no audio, silence is used instead");
+        }
+        for input_video_file in input_video_files {
+            ffmpeg_command.add_input_file(input_video_file);
+        }
+        let video_encoder = reencode_video_encoder(videos_info[0].video_codec());
+        ffmpeg_command
+            .add_complex_filter(&ffmpeg_filter_graph(&videos_info, crossfade, with_audio))
+            .add_mapping("[vo]")
+            .set_output_video_settings(Some(video_encoder), None, Some(REENCODE_VIDEO_CRF));
+        if with_audio {
+            ffmpeg_command
+                .add_mapping("[ao]")
+                .set_output_audio_settings(Some(REENCODE_AUDIO_ENCODER), Some(REENCODE_AUDIO_BITRATE));
+        }
+        ffmpeg_command.copy_metadata_from(0);
+    }
+
+    ffmpeg_command
+        .set_output_file(output_video_file)
+        .set_overwrite_output_file(true);
+
+    ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
+
+    log::info!("videos joined successfully");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::video::Resolution;
+
+    fn video_info(width: u32, duration: f64, has_audio: bool) -> probe::Result {
+        probe::Result::for_test(Resolution::new(width, width * 9 / 16), Rational::new(60, 1), duration, has_audio)
+    }
+
+    #[test]
+    fn frame_count_without_the_crossfade_overlaps() {
+        let videos_info = [video_info(1280, 10.0, true), video_info(1280, 5.0, true), video_info(1280, 2.0, true)];
+        assert_eq!(output_frame_count(&videos_info, None), 1020);
+        assert_eq!(output_frame_count(&videos_info, Some(0.5)), 1020 - 2 * 30);
+        assert!(can_stream_copy(&videos_info));
+        assert!(! can_stream_copy(&[video_info(1280, 10.0, true), video_info(1920, 5.0, true)]));
+        assert!(! can_stream_copy(&[video_info(1280, 10.0, true), video_info(1280, 5.0, false)]));
+    }
+
+    #[test]
+    fn crossfaded_videos_filter_graph() {
+        let videos_info = [video_info(1280, 10.0, true), video_info(1920, 5.0, false), video_info(1280, 2.0, true)];
+        let graph = ffmpeg_filter_graph(&videos_info, Some(0.5), true);
+        let graph = graph.split(';').collect::<Vec<_>>();
+        assert_eq!(graph[0], "[0:v]scale=1280:720:force_original_aspect_ratio=decrease,pad=1280:720:(ow-iw)/2:(oh-ih)/2,setsar=1,fps=60/1,format=yuv420p,settb=AVTB[v0]");
+        assert_eq!(graph[3], "anullsrc=r=48000:cl=stereo,atrim=duration=5,aformat=sample_rates=48000:channel_layouts=stereo[a1]");
+        assert_eq!(&graph[6..], [
+            "[v0][v1]xfade=transition=fade:duration=0.5:offset=9.500[vx1]",
+            "[a0][a1]acrossfade=d=0.5[ax1]",
+            "[vx1][v2]xfade=transition=fade:duration=0.5:offset=14.000[vo]",
+            "[ax1][a2]acrossfade=d=0.5[ao]",
+        ]);
+
+        let graph = ffmpeg_filter_graph(&videos_info[..2], None, false);
+        assert!(graph.ends_with(";[v0][v1]concat=n=2:v=1:a=0[vo]"));
+    }
+
+}
@@ -0,0 +1,187 @@
+
+//! Output video containers and the codecs they can store
+
+use std::path::Path;
+
+use thiserror::Error;
+
+
+#[derive(Debug, Error)]
+pub enum ContainerError {
+    #[error("the {container} container cannot store {codec} video, encoded with {encoder}")]
+    UnsupportedVideoCodec { container: Container, codec: &'static str, encoder: String },
+    #[error("the {container} container cannot store {codec} audio, encoded with {encoder}")]
+    UnsupportedAudioCodec { container: Container, codec: &'static str, encoder: String },
+    #[error("output file extension `{extension}` does not match the {container} container, expected `{}`", .container.extension())]
+    ExtensionMismatch { container: Container, extension: String },
+    #[error("the {0} container cannot be written to the standard output, use mkv or webm")]
+    NotStreamable(Container),
+    #[error("the {container} container cannot store the {codec} stream of the input, it needs to be transcoded")]
+    UnsupportedStreamCodec { container: Container, codec: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Container {
+    Mp4,
+    Mkv,
+    Mov,
+    Webm,
+}
+
+/// codec of the streams written by the known FFMpeg encoders, the other encoders are not validated
+fn encoder_codec(encoder: &str) -> Option<&'static str> {
+    Some(match encoder {
+        "libx264" | "h264_nvenc" | "h264_vaapi" | "h264_qsv" | "h264_videotoolbox" => "h264",
+        "libx265" | "hevc_nvenc" | "hevc_vaapi" | "hevc_qsv" | "hevc_videotoolbox" => "hevc",
+        "libvpx" => "vp8",
+        "libvpx-vp9" | "vp9_vaapi" | "vp9_qsv" => "vp9",
+        "libaom-av1" | "libsvtav1" | "av1_nvenc" | "av1_vaapi" | "av1_qsv" => "av1",
+        "prores" | "prores_ks" | "prores_videotoolbox" => "prores",
+        "aac" | "libfdk_aac" | "aac_at" => "aac",
+        "libmp3lame" => "mp3",
+        "libopus" | "opus" => "opus",
+        "libvorbis" | "vorbis" => "vorbis",
+        "flac" => "flac",
+        "ac3" | "eac3" => "ac3",
+        encoder if encoder.starts_with("pcm_") => "pcm",
+        _ => return None,
+    })
+}
+
+impl Container {
+
+    /// container matching the extension of the file, `None` for the other extensions
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "mp4" | "m4v" => Self::Mp4,
+            "mkv" => Self::Mkv,
+            "mov" => Self::Mov,
+            "webm" => Self::Webm,
+            _ => return None,
+        })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        use Container::*;
+        match self {
+            Mp4 => "mp4",
+            Mkv => "mkv",
+            Mov => "mov",
+            Webm => "webm",
+        }
+    }
+
+    /// FFMpeg muxer name
+    pub fn ffmpeg_format(&self) -> &'static str {
+        use Container::*;
+        match self {
+            Mp4 => "mp4",
+            Mkv => "matroska",
+            Mov => "mov",
+            Webm => "webm",
+        }
+    }
+
+    /// whether the container can be written without seeking, e.g. to the standard output
+    pub fn is_streamable(&self) -> bool {
+        matches!(self, Self::Mkv | Self::Webm)
+    }
+
+    /// whether the container can store the transparency of the overlay videos
+    pub fn supports_transparency(&self) -> bool {
+        matches!(self, Self::Mkv | Self::Webm)
+    }
+
+    /// whether the MP4/MOV muxer flags like `faststart` apply
+    pub fn uses_movflags(&self) -> bool {
+        matches!(self, Self::Mp4 | Self::Mov)
+    }
+
+    fn supports_codec(&self, codec: &str) -> bool {
+        use Container::*;
+        match self {
+            Mkv => true,
+            Mp4 => matches!(codec, "h264" | "hevc" | "vp9" | "av1" | "aac" | "mp3" | "opus" | "flac" | "ac3"),
+            Mov => matches!(codec, "h264" | "hevc" | "prores" | "aac" | "mp3" | "ac3" | "pcm"),
+            Webm => matches!(codec, "vp8" | "vp9" | "av1" | "opus" | "vorbis"),
+        }
+    }
+
+    /// checks that the container can store the streams of the encoders, the unknown encoders are not checked
+    pub fn check_encoders(&self, video_encoder: &str, audio_encoder: Option<&str>) -> Result<(), ContainerError> {
+        if let Some(codec) = encoder_codec(video_encoder).filter(|codec| ! self.supports_codec(codec)) {
+            return Err(ContainerError::UnsupportedVideoCodec { container: *self, codec, encoder: video_encoder.to_owned() });
+        }
+        if let Some(audio_encoder) = audio_encoder {
+            if let Some(codec) = encoder_codec(audio_encoder).filter(|codec| ! self.supports_codec(codec)) {
+                return Err(ContainerError::UnsupportedAudioCodec { container: *self, codec, encoder: audio_encoder.to_owned() });
+            }
+        }
+        Ok(())
+    }
+
+    /// checks that the container can store a stream of the codec without transcoding it, the codec being the FFMpeg
+    /// name of the codec of an input stream, e.g. `h264` or `pcm_s16le`
+    pub fn check_stream_codec(&self, codec: &str) -> Result<(), ContainerError> {
+        let codec_family = if codec.starts_with("pcm_") { "pcm" } else { codec };
+        if self.supports_codec(codec_family) { return Ok(()) }
+        Err(ContainerError::UnsupportedStreamCodec { container: *self, codec: codec.to_owned() })
+    }
+
+    /// checks that the extension of the output file matches the container, files without extension are accepted
+    pub fn check_output_file<P: AsRef<Path>>(&self, output_file: P) -> Result<(), ContainerError> {
+        let output_file = output_file.as_ref();
+        if crate::file::is_stdout(output_file) {
+            return if self.is_streamable() { Ok(()) } else { Err(ContainerError::NotStreamable(*self)) };
+        }
+        match output_file.extension() {
+            Some(extension) if Self::from_extension(output_file) != Some(*self) =>
+                Err(ContainerError::ExtensionMismatch { container: *self, extension: extension.to_string_lossy().to_string() }),
+            _ => Ok(()),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn containers_of_the_extensions() {
+        assert_eq!(Container::from_extension("flight.M4V"), Some(Container::Mp4));
+        assert_eq!(Container::from_extension("flight.webm"), Some(Container::Webm));
+        assert_eq!(Container::from_extension("flight.avi"), None);
+        assert_eq!(Container::from_extension("flight"), None);
+    }
+
+    #[test]
+    fn encoders_checked_against_the_container() {
+        assert!(Container::Mp4.check_encoders("libx265", Some("aac")).is_ok());
+        assert!(matches!(Container::Webm.check_encoders("libx264", None), Err(ContainerError::UnsupportedVideoCodec { codec: "h264", .. })));
+        assert!(matches!(Container::Mov.check_encoders("prores_ks", Some("libopus")), Err(ContainerError::UnsupportedAudioCodec { codec: "opus", .. })));
+        assert!(Container::Webm.check_encoders("some_new_encoder", None).is_ok());
+    }
+
+    #[test]
+    fn output_files_checked_against_the_container() {
+        assert!(Container::Mkv.check_output_file("flight.MKV").is_ok());
+        assert!(Container::Mkv.check_output_file("flight").is_ok());
+        assert!(matches!(Container::Mkv.check_output_file("flight.mp4"), Err(ContainerError::ExtensionMismatch { .. })));
+        assert!(Container::Webm.check_output_file("-").is_ok());
+        assert!(matches!(Container::Mp4.check_output_file("-"), Err(ContainerError::NotStreamable(Container::Mp4))));
+    }
+
+    #[test]
+    fn input_stream_codecs_checked_against_the_container() {
+        assert!(Container::Mov.check_stream_codec("pcm_s16le").is_ok());
+        assert!(Container::Mkv.check_stream_codec("mjpeg").is_ok());
+        assert!(matches!(Container::Mp4.check_stream_codec("pcm_s16le"),
+            Err(ContainerError::UnsupportedStreamCodec { container: Container::Mp4, codec }) if codec == "pcm_s16le"));
+        assert!(matches!(Container::Webm.check_stream_codec("h264"), Err(ContainerError::UnsupportedStreamCodec { .. })));
+    }
+
+}
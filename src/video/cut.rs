@@ -0,0 +1,266 @@
+
+use std::path::{Path, PathBuf};
+
+use ffmpeg_next::Rational;
+
+use crate::ffmpeg;
+
+use super::{CutVideoError, Timestamp, probe};
+
+
+/// audio settings used when the audio needs to be re-encoded
+pub(super) const REENCODE_AUDIO_ENCODER: &str = "aac";
+pub(super) const REENCODE_AUDIO_BITRATE: &str = "192k";
+
+/// constant quality used when re-encoding, high so that the re-encoded parts are visually identical to the original
+pub(super) const REENCODE_VIDEO_CRF: u8 = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum CutMode {
+    /// stream copy, fast and lossless but the cut points snap to the key frames
+    Copy,
+    /// frame accurate, only the frames between the cut points and the nearest key frames are re-encoded
+    Smart,
+    /// frame accurate, the whole video is re-encoded
+    Reencode,
+}
+
+/// encoder producing the same codec as the input video so that re-encoded parts can be joined with copied parts
+fn reencode_video_encoder(video_codec: &Option<String>) -> Result<&'static str, CutVideoError> {
+    match video_codec.as_deref() {
+        Some("h264") => Ok("libx264"),
+        Some("hevc") => Ok("libx265"),
+        _ => Err(CutVideoError::CannotReencodeVideoCodec(video_codec.clone().unwrap_or_else(|| "unknown".to_owned()))),
+    }
+}
+
+/// timestamp of the frame, rounded down so that exact seeking does not skip it when re-encoding
+/// or rounded up so that seeking to a key frame does not land on the previous one when stream copying
+fn frame_timestamp(frame_index: u32, frame_rate: Rational, round_up: bool) -> Timestamp {
+    let milliseconds = frame_index as f64 * 1000.0 * frame_rate.denominator() as f64 / frame_rate.numerator() as f64;
+    Timestamp::from_milliseconds(if round_up { milliseconds.ceil() } else { milliseconds.floor() } as u64)
+}
+
+/// frames of a smart cut segment, `reencode` is false for the segments which can be stream copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    first_frame: u32,
+    frame_count: u32,
+    reencode: bool,
+}
+
+/// splits the interval into the parts before the first key frame and after the last key frame which need to be
+/// re-encoded and the part in between which can be stream copied, empty parts are omitted
+fn smart_cut_segments(first_frame: u32, end_frame: u32, end_is_end_of_video: bool, keyframes: &[u32]) -> Vec<Segment> {
+    let first_keyframe = keyframes.iter().copied().find(|&keyframe| keyframe >= first_frame && keyframe < end_frame);
+    let last_keyframe = if end_is_end_of_video {
+        first_keyframe.map(|_| end_frame)
+    } else {
+        keyframes.iter().copied().rev().find(|&keyframe| keyframe <= end_frame && keyframe > first_frame)
+    };
+
+    let boundaries = match (first_keyframe, last_keyframe) {
+        (Some(first_keyframe), Some(last_keyframe)) if first_keyframe < last_keyframe =>
+            [(first_frame, first_keyframe, true), (first_keyframe, last_keyframe, false), (last_keyframe, end_frame, true)],
+        _ => [(first_frame, end_frame, true), (end_frame, end_frame, false), (end_frame, end_frame, true)],
+    };
+
+    boundaries.iter()
+        .filter(|(first, end, _)| end > first)
+        .map(|&(first, end, reencode)| Segment { first_frame: first, frame_count: end - first, reencode })
+        .collect()
+}
+
+/// temporary files removed when dropped, the paths made with [`temp_file_path`] and the files created with
+/// [`TempFiles::new`] are also removed if the process is interrupted
+pub(crate) struct TempFiles(pub(crate) Vec<PathBuf>);
+
+impl TempFiles {
+
+    /// temporary files whose paths are not created with [`temp_file_path`], registered so that they are also
+    /// removed when the process is interrupted
+    pub(crate) fn new(paths: Vec<PathBuf>) -> Self {
+        for path in &paths {
+            crate::process::register_partial_file(path);
+        }
+        Self(paths)
+    }
+
+}
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            if let Err(error) = std::fs::remove_file(path) {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("failed to remove temporary file {}: {error}", path.to_string_lossy());
+                }
+            }
+            crate::process::unregister_partial_file(path);
+        }
+    }
+}
+
+pub(crate) fn temp_file_path(output_video_file: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = std::ffi::OsString::from(".");
+    file_name.push(output_video_file.file_name().unwrap());
+    file_name.push(suffix);
+    let path = crate::temp_dir::workspace_dir(output_video_file).join(file_name);
+    if crate::process::dry_run() {
+        println!("# intermediate file: {}", path.to_string_lossy());
+    } else {
+        crate::process::register_partial_file(&path);
+    }
+    path
+}
+
+/// writes the list of files to join for the FFMpeg concat demuxer
+///
+/// The demuxer resolving the relative paths from the directory of the list, the files in the same directory as the
+/// list are written with their file name.
+pub(super) fn write_concat_list(concat_list_file: &Path, files: &[PathBuf]) -> Result<(), std::io::Error> {
+    let concat_list = files.iter().map(|file| {
+        let file = match (file.parent(), file.file_name()) {
+            (Some(dir), Some(file_name)) if Some(dir) == concat_list_file.parent() => Path::new(file_name),
+            _ => file.as_path(),
+        };
+        let file = file.to_string_lossy().replace('\'', "'\\''");
+        format!("file '{file}'\n")
+    }).collect::<String>();
+    if crate::process::dry_run() { return Ok(()) }
+    fs_err::write(concat_list_file, concat_list)
+}
+
+/// copies the container metadata from the input or strips them
+pub(super) fn set_metadata(ffmpeg_command: &mut ffmpeg::CommandBuilder, input_index: usize, strip_metadata: bool) {
+    if strip_metadata {
+        ffmpeg_command.strip_metadata();
+    } else {
+        ffmpeg_command.copy_metadata_from(input_index);
+    }
+}
+
+/// cuts the video re-encoding it completely
+pub(super) async fn cut_reencode(input_video_file: &Path, output_video_file: &Path, start: Option<Timestamp>, end: Option<Timestamp>,
+        video_info: &probe::Result, frame_count: u64, strip_metadata: bool) -> Result<(), CutVideoError> {
+
+    let video_encoder = reencode_video_encoder(video_info.video_codec())?;
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    ffmpeg_command
+        .add_input_file_slice(input_video_file, start, end)
+        .set_output_video_settings(Some(video_encoder), None, Some(REENCODE_VIDEO_CRF))
+        .set_output_file(output_video_file)
+        .set_overwrite_output_file(true);
+    set_metadata(&mut ffmpeg_command, 0, strip_metadata);
+
+    if video_info.has_audio() {
+        ffmpeg_command.set_output_audio_settings(Some(REENCODE_AUDIO_ENCODER), Some(REENCODE_AUDIO_BITRATE));
+    }
+
+    ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
+    Ok(())
+}
+
+/// cuts the video re-encoding only the frames between the cut points and the nearest key frames
+///
+/// The video segments are written into temporary files next to the output file, joined with the concat demuxer
+/// and the audio and container metadata are stream copied from the input video.
+pub(super) async fn cut_smart(input_video_file: &Path, output_video_file: &Path, start: Option<Timestamp>, end: Option<Timestamp>,
+        video_info: &probe::Result, strip_metadata: bool) -> Result<(), CutVideoError> {
+
+    let frame_rate = video_info.frame_rate();
+    let first_frame = start.map(|start| start.frame_count(frame_rate) as u32).unwrap_or(0);
+    let end_frame = end.map(|end| (end.frame_count(frame_rate) as u32).min(video_info.frame_count() as u32))
+        .unwrap_or(video_info.frame_count() as u32);
+    let end_is_end_of_video = end_frame as u64 >= video_info.frame_count();
+
+    let keyframes = probe::keyframe_indices(input_video_file)?;
+    let segments = smart_cut_segments(first_frame, end_frame, end_is_end_of_video, &keyframes);
+    log::debug!("smart cut segments: {segments:?}");
+
+    let reencode_video_encoder = if segments.iter().any(|segment| segment.reencode) {
+        Some(reencode_video_encoder(video_info.video_codec())?)
+    } else {
+        None
+    };
+
+    let cut_duration = (end_frame - first_frame) as f64 * frame_rate.denominator() as f64 / frame_rate.numerator() as f64;
+    crate::temp_dir::check_available_space(output_video_file,
+        crate::temp_dir::estimated_size(input_video_file, video_info.duration(), cut_duration))?;
+
+    let mut temp_files = TempFiles(vec![]);
+
+    for (index, segment) in segments.iter().enumerate() {
+        let segment_file = temp_file_path(output_video_file, &format!(".part{index}.mp4"));
+        temp_files.0.push(segment_file.clone());
+
+        log::info!("{} frames {} to {}", if segment.reencode { "re-encoding" } else { "copying" },
+            segment.first_frame, segment.first_frame + segment.frame_count - 1);
+
+        let segment_start = frame_timestamp(segment.first_frame, frame_rate, ! segment.reencode);
+        let frame_count_arg = segment.frame_count.to_string();
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command
+            .add_input_file_slice(input_video_file, Some(segment_start), None)
+            .add_mapping("0:v")
+            .add_args(&["-frames:v", &frame_count_arg])
+            .set_output_file(&segment_file)
+            .set_overwrite_output_file(true);
+        match (segment.reencode, reencode_video_encoder) {
+            (true, Some(video_encoder)) => {
+                ffmpeg_command.set_output_video_settings(Some(video_encoder), None, Some(REENCODE_VIDEO_CRF));
+            },
+            _ => {
+                ffmpeg_command.set_output_video_codec(Some("copy"));
+            },
+        }
+        ffmpeg_command.build().unwrap().spawn_with_progress(segment.frame_count as u64)?.wait().await?;
+    }
+
+    let concat_list_file = temp_file_path(output_video_file, ".concat.txt");
+    temp_files.0.push(concat_list_file.clone());
+    let segment_files = temp_files.0.iter().filter(|path| **path != concat_list_file).cloned().collect::<Vec<_>>();
+    write_concat_list(&concat_list_file, &segment_files).map_err(CutVideoError::FailedWritingTempFile)?;
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    ffmpeg_command
+        .add_concat_list_input(&concat_list_file)
+        .add_mapping("0:v")
+        .set_output_video_codec(Some("copy"))
+        .set_output_file(output_video_file)
+        .set_overwrite_output_file(true);
+
+    ffmpeg_command.add_input_file_slice(input_video_file, start, end);
+    if video_info.has_audio() {
+        ffmpeg_command
+            .add_mapping("1:a")
+            .set_output_audio_codec(Some("copy"));
+    }
+    set_metadata(&mut ffmpeg_command, 1, strip_metadata);
+
+    ffmpeg_command.build().unwrap().spawn_with_progress((end_frame - first_frame) as u64)?.wait().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smart_cut_segments_between_keyframes() {
+        let keyframes = [0, 60, 120, 180, 240];
+        assert_eq!(smart_cut_segments(30, 200, false, &keyframes), vec![
+            Segment { first_frame: 30, frame_count: 30, reencode: true },
+            Segment { first_frame: 60, frame_count: 120, reencode: false },
+            Segment { first_frame: 180, frame_count: 20, reencode: true },
+        ]);
+        assert_eq!(smart_cut_segments(60, 300, true, &keyframes), vec![
+            Segment { first_frame: 60, frame_count: 240, reencode: false },
+        ]);
+        assert_eq!(smart_cut_segments(70, 110, false, &keyframes), vec![
+            Segment { first_frame: 70, frame_count: 40, reencode: true },
+        ]);
+    }
+
+}
@@ -0,0 +1,125 @@
+
+use std::ops::RangeInclusive;
+
+use getset::CopyGetters;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+use thiserror::Error;
+
+
+const X26X_PRESETS: &[&str] = &["ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow", "placebo"];
+const X264_TUNES: &[&str] = &["film", "animation", "grain", "stillimage", "fastdecode", "zerolatency", "psnr", "ssim"];
+const X265_TUNES: &[&str] = &["psnr", "ssim", "grain", "zerolatency", "fastdecode", "animation"];
+const NVENC_PRESETS: &[&str] = &["p1", "p2", "p3", "p4", "p5", "p6", "p7", "fast", "medium", "slow"];
+const NVENC_TUNES: &[&str] = &["hq", "ll", "ull", "lossless"];
+const SVTAV1_PRESETS: &[&str] = &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13"];
+
+/// Encoding options supported by a known FFMpeg video encoder
+#[derive(Debug, Clone, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct Capabilities {
+    #[getset(skip)]
+    crf_range: Option<RangeInclusive<u8>>,
+    presets: &'static [&'static str],
+    tunes: &'static [&'static str],
+}
+
+impl Capabilities {
+
+    const fn new(crf_range: Option<RangeInclusive<u8>>, presets: &'static [&'static str], tunes: &'static [&'static str]) -> Self {
+        Self { crf_range, presets, tunes }
+    }
+
+    pub fn crf_range(&self) -> Option<&RangeInclusive<u8>> {
+        self.crf_range.as_ref()
+    }
+
+    pub fn supports_crf(&self, crf: u8) -> bool {
+        matches!(&self.crf_range, Some(crf_range) if crf_range.contains(&crf))
+    }
+
+    pub fn supports_preset(&self, preset: &str) -> bool {
+        self.presets.contains(&preset)
+    }
+
+    pub fn supports_tune(&self, tune: &str) -> bool {
+        self.tunes.contains(&tune)
+    }
+
+}
+
+/// Returns the capabilities of the encoder if it is known, unknown encoders are not validated
+pub fn capabilities(encoder: &str) -> Option<Capabilities> {
+    Some(match encoder {
+        "libx264" => Capabilities::new(Some(0..=51), X26X_PRESETS, X264_TUNES),
+        "libx265" => Capabilities::new(Some(0..=51), X26X_PRESETS, X265_TUNES),
+        "h264_nvenc" | "hevc_nvenc" | "av1_nvenc" => Capabilities::new(None, NVENC_PRESETS, NVENC_TUNES),
+        "libvpx" => Capabilities::new(Some(4..=63), &[], &[]),
+        "libvpx-vp9" | "libaom-av1" => Capabilities::new(Some(0..=63), &[], &[]),
+        "libsvtav1" => Capabilities::new(Some(1..=63), SVTAV1_PRESETS, &[]),
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum InvalidSettingsError {
+    #[error("invalid bitrate value: {0}, expected a number optionally followed by a k/M/G suffix")]
+    InvalidBitrate(String),
+    #[error("encoder {encoder} does not support constant quality (CRF) encoding")]
+    CRFNotSupported { encoder: String },
+    #[error("invalid CRF value {crf} for encoder {encoder}, valid range: {min}-{max}")]
+    CRFOutOfRange { encoder: String, crf: u8, min: u8, max: u8 },
+    #[error("invalid preset `{preset}` for encoder {encoder}, valid presets: {valid}")]
+    InvalidPreset { encoder: String, preset: String, valid: String },
+    #[error("invalid tune `{tune}` for encoder {encoder}, valid tunes: {valid}")]
+    InvalidTune { encoder: String, tune: String, valid: String },
+}
+
+fn valid_list(values: &[&str]) -> String {
+    if values.is_empty() { "none".to_owned() } else { values.iter().join(", ") }
+}
+
+pub fn check_bitrate(bitrate: &str) -> Result<(), InvalidSettingsError> {
+    lazy_static! {
+        static ref BITRATE_RE: Regex = Regex::new(r"\A\d+(?:\.\d+)?[kKmMgG]?\z").unwrap();
+    }
+    if ! BITRATE_RE.is_match(bitrate) {
+        return Err(InvalidSettingsError::InvalidBitrate(bitrate.to_owned()));
+    }
+    Ok(())
+}
+
+/// Checks that the settings are valid for the encoder, settings for unknown encoders are not checked
+pub fn check_settings(encoder: &str, crf: Option<u8>, preset: Option<&str>, tune: Option<&str>) -> Result<(), InvalidSettingsError> {
+    let capabilities = match capabilities(encoder) {
+        Some(capabilities) => capabilities,
+        None => {
+            log::debug!("unknown encoder {encoder}, not checking encoder settings");
+            return Ok(());
+        },
+    };
+
+    if let Some(crf) = crf {
+        match capabilities.crf_range() {
+            None => return Err(InvalidSettingsError::CRFNotSupported { encoder: encoder.to_owned() }),
+            Some(crf_range) if ! crf_range.contains(&crf) =>
+                return Err(InvalidSettingsError::CRFOutOfRange { encoder: encoder.to_owned(), crf, min: *crf_range.start(), max: *crf_range.end() }),
+            Some(_) => {},
+        }
+    }
+
+    if let Some(preset) = preset {
+        if ! capabilities.supports_preset(preset) {
+            return Err(InvalidSettingsError::InvalidPreset { encoder: encoder.to_owned(), preset: preset.to_owned(), valid: valid_list(capabilities.presets()) });
+        }
+    }
+
+    if let Some(tune) = tune {
+        if ! capabilities.supports_tune(tune) {
+            return Err(InvalidSettingsError::InvalidTune { encoder: encoder.to_owned(), tune: tune.to_owned(), valid: valid_list(capabilities.tunes()) });
+        }
+    }
+
+    Ok(())
+}
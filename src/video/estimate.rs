@@ -0,0 +1,81 @@
+
+//! Estimation of the encoding time and output size of a long job from a short sample encoded with the same settings
+//!
+//! The sample is encoded exactly like the whole job into a temporary file, the estimates are then extrapolated from
+//! the ratio between the duration of the job and the duration of the sample. They are rough: the encoding speed and
+//! the bitrate vary with the content of the video, e.g. the sample may be taken while the quad is still on the ground.
+
+use std::{fmt::Display, path::Path, time::Duration};
+
+use getset::CopyGetters;
+use indicatif::{HumanBytes, HumanDuration};
+
+
+/// duration in seconds of the sample encoded by default to estimate a job
+pub const DEFAULT_SAMPLE_DURATION: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct Estimate {
+    /// duration of the sample in seconds
+    sample_duration: f64,
+    sample_encoding_time: Duration,
+    sample_size: u64,
+    /// duration of the whole job in seconds
+    duration: f64,
+}
+
+impl Estimate {
+
+    /// estimate extrapolated from the sample file encoded in `sample_encoding_time`, its size being 0 if it cannot
+    /// be read
+    pub(crate) fn from_sample(sample_file: &Path, sample_duration: f64, sample_encoding_time: Duration, duration: f64) -> Self {
+        let sample_size = sample_file.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+        Self { sample_duration, sample_encoding_time, sample_size, duration }
+    }
+
+    fn ratio(&self) -> f64 {
+        if self.sample_duration > 0.0 { self.duration / self.sample_duration } else { 0.0 }
+    }
+
+    pub fn encoding_time(&self) -> Duration {
+        self.sample_encoding_time.mul_f64(self.ratio())
+    }
+
+    pub fn output_size(&self) -> u64 {
+        (self.sample_size as f64 * self.ratio()) as u64
+    }
+
+}
+
+impl Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "estimated encoding time: {}, estimated output size: {} (from a {:.1}s sample encoded in {:.1}s)",
+            HumanDuration(self.encoding_time()), HumanBytes(self.output_size()), self.sample_duration,
+            self.sample_encoding_time.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn estimate_extrapolated_from_the_sample() {
+        let sample_file = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let sample_size = sample_file.metadata().unwrap().len();
+        let estimate = Estimate::from_sample(&sample_file, 5.0, Duration::from_secs(2), 60.0);
+        assert_eq!(estimate.sample_size(), sample_size);
+        assert_eq!(estimate.encoding_time(), Duration::from_secs(24));
+        assert_eq!(estimate.output_size(), sample_size * 12);
+    }
+
+    #[test]
+    fn estimate_without_sample() {
+        let estimate = Estimate::from_sample(Path::new("missing.mkv"), 0.0, Duration::from_secs(2), 60.0);
+        assert_eq!(estimate.output_size(), 0);
+        assert_eq!(estimate.encoding_time(), Duration::ZERO);
+    }
+
+}
@@ -0,0 +1,121 @@
+
+//! Extraction of video frames to image files, e.g. screenshots for documentation, each frame being generated like a
+//! thumbnail so that the OSD can be burnt onto it
+
+use std::path::{Path, PathBuf};
+
+use super::{
+    Timestamp,
+    ThumbnailGenerator,
+    TranscodeVideoError,
+    probe,
+    resolution::OutputScale,
+    transcoder::OSDSettings,
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+        }
+    }
+}
+
+/// Frames to extract
+#[derive(Debug, Clone)]
+pub enum FrameSelection {
+    Timestamps(Vec<Timestamp>),
+    /// one frame every interval from the start of the video
+    Interval(Timestamp),
+}
+
+impl FrameSelection {
+
+    fn timestamps(&self, video_duration: f64) -> Vec<Timestamp> {
+        match self {
+            Self::Timestamps(timestamps) => timestamps.clone(),
+            Self::Interval(interval) => {
+                let video_duration_ms = (video_duration * 1000.0) as u64;
+                (0..video_duration_ms).step_by(interval.total_milliseconds() as usize).map(Timestamp::from_milliseconds).collect()
+            },
+        }
+    }
+
+}
+
+/// path of the image of the frame at the timestamp: `<video file stem>_<HH>-<MM>-<SS>.<mmm>.<extension>`
+fn frame_file_path(video_file: &Path, output_dir: &Path, timestamp: Timestamp, format: ImageFormat) -> PathBuf {
+    let mut file_name = video_file.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("_{:02}-{:02}-{:02}.{:03}.{}", timestamp.hours(), timestamp.minutes(), timestamp.seconds(),
+        timestamp.milliseconds(), format.extension()));
+    output_dir.join(file_name)
+}
+
+/// extracts the selected frames of the video into images written in the output directory, or next to the video when
+/// not set, returns the paths of the images
+pub async fn extract_frames<P: AsRef<Path>>(video_file: P, output_dir: Option<&Path>, selection: &FrameSelection,
+        format: ImageFormat, scale: Option<OutputScale>, osd: Option<OSDSettings>, overwrite: bool) -> Result<Vec<PathBuf>, TranscodeVideoError> {
+
+    let video_file = video_file.as_ref();
+    if ! video_file.exists() { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
+    if let FrameSelection::Interval(interval) = selection {
+        if interval.total_milliseconds() == 0 { return Err(TranscodeVideoError::InvalidFrameExtractionInterval) }
+    }
+
+    let video_info = probe(video_file)?;
+    let timestamps = selection.timestamps(video_info.duration());
+    let output_dir = output_dir.map(Path::to_path_buf)
+        .unwrap_or_else(|| video_file.parent().map(Path::to_path_buf).unwrap_or_default());
+
+    log::info!("extracting {} frames from {}", timestamps.len(), video_file.to_string_lossy());
+
+    let mut frame_files = Vec::with_capacity(timestamps.len());
+    for timestamp in timestamps {
+        let frame_file = frame_file_path(video_file, &output_dir, timestamp, format);
+        let mut thumbnail_generator = ThumbnailGenerator::new(video_file, &frame_file);
+        thumbnail_generator
+            .set_overwrite(overwrite)
+            .set_timestamp(timestamp)
+            .set_scale(scale)
+            .set_osd(osd.clone());
+        thumbnail_generator.generate().await?;
+        frame_files.push(frame_file);
+    }
+
+    log::info!("{} frames extracted successfully", frame_files.len());
+    Ok(frame_files)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn interval_timestamps_within_the_video() {
+        let selection = FrameSelection::Interval(Timestamp::from_milliseconds(1500));
+        let timestamps = selection.timestamps(4.0);
+        assert_eq!(timestamps.iter().map(Timestamp::total_milliseconds).collect::<Vec<_>>(), vec![0, 1500, 3000]);
+
+        let timestamps = vec![Timestamp::from_milliseconds(500), Timestamp::from_milliseconds(12_000)];
+        assert_eq!(FrameSelection::Timestamps(timestamps.clone()).timestamps(4.0), timestamps);
+    }
+
+    #[test]
+    fn frame_file_named_after_the_video_and_timestamp() {
+        let timestamp = Timestamp::from_milliseconds(3_723_045);
+        assert_eq!(frame_file_path(Path::new("dir/DJIG0001.mp4"), Path::new("out"), timestamp, ImageFormat::Jpeg),
+            Path::new("out/DJIG0001_01-02-03.045.jpg"));
+        assert_eq!(frame_file_path(Path::new("DJIG0001.mp4"), Path::new("out"), timestamp, ImageFormat::Png),
+            Path::new("out/DJIG0001_01-02-03.045.png"));
+    }
+
+}
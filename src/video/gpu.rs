@@ -0,0 +1,138 @@
+
+//! Experimental GPU pipeline: decoding, OSD composition and encoding without the video frames leaving the GPU memory
+//!
+//! The input video is decoded into GPU surfaces which are scaled and overlaid with the OSD frames by the GPU filters
+//! of FFMpeg then encoded by the hardware encoder of the same API. Only the OSD frames are uploaded from the system
+//! memory, they are much smaller than the 4K video frames whose round-trip to the CPU dominates the burn time
+//! otherwise.
+//!
+//! The GPU filters only cover the scaling and the OSD overlay, the other video filters and layers are not supported.
+//!
+//! There is no wgpu compositor: wgpu cannot import the CUDA or VA-API surfaces of the hardware decoder nor export its
+//! textures to the hardware encoder, so composing with it would bring back the copies through the system memory this
+//! pipeline removes. The composition is only done by the FFMpeg filters of each hardware API.
+
+use thiserror::Error;
+
+use crate::ffmpeg::HwAccel;
+
+use super::Resolution;
+
+
+/// Hardware API used by the GPU pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum GpuApi {
+    /// NVIDIA GPUs: NVDEC decoding, CUDA filters and NVENC encoding
+    Cuda,
+    /// AMD and Intel GPUs on Linux
+    Vaapi,
+}
+
+#[derive(Debug, Error)]
+pub enum GpuPipelineError {
+    #[error("the {api} GPU pipeline needs a {api} hardware encoder, e.g. hevc{suffix}, not {encoder}")]
+    UnsupportedEncoder { api: GpuApi, encoder: String, suffix: &'static str },
+    #[error("not supported by the GPU pipeline: {0}")]
+    UnsupportedFeature(&'static str),
+}
+
+impl GpuApi {
+
+    pub fn hwaccel(&self) -> HwAccel {
+        match self {
+            Self::Cuda => HwAccel::Cuda,
+            Self::Vaapi => HwAccel::Vaapi,
+        }
+    }
+
+    fn encoder_suffix(&self) -> &'static str {
+        match self {
+            Self::Cuda => "_nvenc",
+            Self::Vaapi => "_vaapi",
+        }
+    }
+
+    /// checks that the encoder reads the frames from the GPU memory of this API
+    pub fn check_encoder(&self, encoder: &str) -> Result<(), GpuPipelineError> {
+        if encoder.ends_with(self.encoder_suffix()) { return Ok(()) }
+        Err(GpuPipelineError::UnsupportedEncoder { api: *self, encoder: encoder.to_owned(), suffix: self.encoder_suffix() })
+    }
+
+    fn scale_filter(&self, resolution: Resolution) -> String {
+        match self {
+            Self::Cuda => format!("scale_cuda={}:{}", resolution.width, resolution.height),
+            Self::Vaapi => format!("scale_vaapi=w={}:h={}", resolution.width, resolution.height),
+        }
+    }
+
+    /// filter uploading the RGBA OSD frames into a GPU surface format with alpha supported by the overlay filter
+    fn upload_filter(&self) -> &'static str {
+        match self {
+            Self::Cuda => "format=yuva420p,hwupload_cuda",
+            Self::Vaapi => "format=rgba,hwupload",
+        }
+    }
+
+    fn overlay_filter(&self, x: i64, y: i64) -> String {
+        match self {
+            Self::Cuda => format!("overlay_cuda=x={x}:y={y}"),
+            Self::Vaapi => format!("overlay_vaapi=x={x}:y={y}"),
+        }
+    }
+
+    /// Complex filter scaling the GPU decoded `video_input` when a resolution is provided, overlaying the frames of
+    /// `osd_input` at the `x`, `y` position if provided then applying the `speed_filter`, the output being labelled `[vo]`
+    pub fn filter_graph(&self, video_input: &str, osd_input: Option<(&str, i64, i64)>, scale: Option<Resolution>, speed_filter: Option<String>) -> String {
+        let scale_filter = scale.map(|resolution| self.scale_filter(resolution)).unwrap_or_else(|| "null".to_owned());
+        let speed_filter = speed_filter.unwrap_or_else(|| "null".to_owned());
+        match osd_input {
+            Some((osd_input, x, y)) => format!("{osd_input}{}[osd];{video_input}{scale_filter}[base];[base][osd]{},{speed_filter}[vo]",
+                self.upload_filter(), self.overlay_filter(x, y)),
+            None => format!("{video_input}{scale_filter},{speed_filter}[vo]"),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::ffmpeg::Input;
+
+    #[test]
+    fn cuda_filter_graph() {
+        let filter_graph = GpuApi::Cuda.filter_graph("[0]", Some(("[1]", 240, 0)), Some(Resolution::new(3840, 2160)), None);
+        assert_eq!(filter_graph, "[1]format=yuva420p,hwupload_cuda[osd];[0]scale_cuda=3840:2160[base];[base][osd]overlay_cuda=x=240:y=0,null[vo]");
+    }
+
+    #[test]
+    fn vaapi_filter_graph() {
+        let filter_graph = GpuApi::Vaapi.filter_graph("[0]", Some(("[1]", 0, -20)), None, Some("setpts=PTS/2".to_owned()));
+        assert_eq!(filter_graph, "[1]format=rgba,hwupload[osd];[0]null[base];[base][osd]overlay_vaapi=x=0:y=-20,setpts=PTS/2[vo]");
+        let filter_graph = GpuApi::Vaapi.filter_graph("[0]", None, Some(Resolution::new(1920, 1080)), None);
+        assert_eq!(filter_graph, "[0]scale_vaapi=w=1920:h=1080,null[vo]");
+    }
+
+    fn gpu_decoded_input_args(api: GpuApi) -> Vec<String> {
+        let input = Input::File { path: "DJIG0000.mp4".into(), start: None, end: None, hwaccel: Some(api.hwaccel()), gpu_frames: true, decoder: None };
+        input.to_args().iter().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn gpu_decoded_input_args_keep_frames_on_gpu() {
+        assert_eq!(gpu_decoded_input_args(GpuApi::Cuda),
+            ["-hwaccel", "cuda", "-hwaccel_output_format", "cuda", "-i", "DJIG0000.mp4"]);
+        assert_eq!(gpu_decoded_input_args(GpuApi::Vaapi),
+            ["-init_hw_device", "vaapi=gpu", "-filter_hw_device", "gpu", "-hwaccel_device", "gpu",
+                "-hwaccel", "vaapi", "-hwaccel_output_format", "vaapi", "-i", "DJIG0000.mp4"]);
+    }
+
+    #[test]
+    fn encoder_check() {
+        assert!(GpuApi::Cuda.check_encoder("hevc_nvenc").is_ok());
+        assert!(GpuApi::Vaapi.check_encoder("libx265").is_err());
+    }
+
+}
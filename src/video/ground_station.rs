@@ -0,0 +1,243 @@
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+    thread,
+    time::Duration,
+};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+use tokio::time::Instant;
+
+use crate::ffmpeg;
+use crate::osd::{
+    self,
+    FontDir,
+    msp::{self, DisplayPortScreen, FlightControllerFirmware},
+    overlay::{DrawFrameOverlayError, Frame as OverlayFrame, Generator as OverlayGenerator, scaling::{OSDScalingSettings, Scaling}},
+    tile_indices::{TileIndices, UnknownOSDItem},
+};
+use crate::process::Command as ProcessCommand;
+
+use super::Resolution;
+
+
+/// the OSD is drawn on the 60x22 grid which contains the grids of all the HD OSD layouts
+const OSD_KIND: osd::Kind = osd::Kind::DJI_FakeHD;
+
+/// timeout of the serial port reads after which the stop flag is checked
+const SERIAL_PORT_READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Error, From)]
+pub enum GroundStationError {
+    #[error("nothing to do, the video needs to be displayed and/or recorded")]
+    NothingToDo,
+    #[error("failed opening serial port {path}: {error}")]
+    FailedOpeningSerialPort {
+        path: PathBuf,
+        error: serialport::Error,
+    },
+    #[error(transparent)]
+    DrawFrameOverlayError(DrawFrameOverlayError),
+    #[error(transparent)]
+    UnknownOSDItem(UnknownOSDItem),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error("failed to start MPV: {0}")]
+    FailedToStartMPV(std::io::Error),
+    #[error("MPV exited with an error: {0}")]
+    MPVExitedWithAnError(ExitStatus),
+}
+
+/// Ground station DVR compositing the OSD received live from the flight controller onto the video of a capture device
+///
+/// The OSD is received with the MSP DisplayPort protocol on a serial port connected to the flight controller. The video
+/// with the OSD is displayed with MPV and/or recorded into a file.
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct GroundStation {
+    capture_device: PathBuf,
+    /// FFMpeg input format of the capture device, e.g. `v4l2`
+    input_format: String,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    capture_resolution: Resolution,
+    serial_port: PathBuf,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    baud_rate: u32,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    firmware: FlightControllerFirmware,
+    font_dir: PathBuf,
+    font_ident: Option<Option<String>>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    font_premultiplied_alpha: bool,
+    record_file: Option<PathBuf>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    display: bool,
+}
+
+impl GroundStation {
+
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(capture_device: P, input_format: &str, capture_resolution: Resolution,
+            serial_port: Q, font_dir: R) -> Self {
+        Self {
+            capture_device: capture_device.as_ref().to_path_buf(),
+            input_format: input_format.to_owned(),
+            capture_resolution,
+            serial_port: serial_port.as_ref().to_path_buf(),
+            baud_rate: msp::DEFAULT_BAUD_RATE,
+            firmware: FlightControllerFirmware::Betaflight,
+            font_dir: font_dir.as_ref().to_path_buf(),
+            font_ident: None,
+            font_premultiplied_alpha: false,
+            record_file: None,
+            display: true,
+        }
+    }
+
+    /// reads the MSP messages from the serial port until stopped, the last drawn OSD screen is stored into `screen`
+    fn read_osd_feed(mut serial_port: Box<dyn serialport::SerialPort>, mut display_port_screen: DisplayPortScreen,
+            screen: Arc<Mutex<Option<TileIndices>>>, stop: Arc<AtomicBool>) {
+        let mut parser = msp::Parser::default();
+        let mut buffer = [0; 256];
+        while ! stop.load(Ordering::Relaxed) {
+            let read_count = match serial_port.read(&mut buffer) {
+                Ok(read_count) => read_count,
+                Err(error) if error.kind() == ErrorKind::TimedOut || error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    log::error!("failed reading OSD feed from serial port: {error}");
+                    break;
+                },
+            };
+            for byte in &buffer[..read_count] {
+                if let Some(tile_indices) = parser.push(*byte).and_then(|message| display_port_screen.apply(&message)) {
+                    *screen.lock().unwrap() = Some(tile_indices);
+                }
+            }
+        }
+    }
+
+    fn ffmpeg_command(&self, osd_frame_dimensions: osd::Dimensions) -> ffmpeg::CommandBuilder {
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command
+            .add_live_input(&self.capture_device, Some(&self.input_format), Some(self.capture_resolution), false)
+            .add_stdin_input(osd_frame_dimensions, osd::FRAME_RATE as u16).unwrap()
+            .add_complex_filter("[0:v][1]overlay=eof_action=repeat:x=(W-w)/2:y=(H-h)/2,format=yuv420p[vo]")
+            .add_mapping("[vo]")
+            .set_output_video_settings(Some("libx264"), None, Some(20))
+            .set_output_video_preset(Some("ultrafast"))
+            .set_output_video_tune(Some("zerolatency"));
+
+        match (&self.record_file, self.display) {
+            // the encoded video is both written into the file and passed to MPV through the standard output
+            (Some(record_file), true) => {
+                let mut tee_outputs = std::ffi::OsString::from("[f=matroska]");
+                tee_outputs.push(record_file.as_os_str());
+                tee_outputs.push("|[f=nut]pipe:1");
+                ffmpeg_command.add_args(&["-f", "tee"]).set_output_file(tee_outputs);
+            },
+            (Some(record_file), false) => { ffmpeg_command.set_output_file(record_file); },
+            (None, _) => { ffmpeg_command.set_output_stdout("nut"); },
+        }
+        ffmpeg_command.set_overwrite_output_file(true);
+        ffmpeg_command
+    }
+
+    /// sends the OSD frames to FFMpeg in real time until the pipe is closed
+    async fn send_osd_frames(overlay_generator: &OverlayGenerator, ffmpeg_process: &mut ffmpeg::Process, screen: &Mutex<Option<TileIndices>>)
+            -> Result<(), GroundStationError> {
+        // no process in dry run mode
+        let mut ffmpeg_stdin = match ffmpeg_process.take_stdin() {
+            Some(ffmpeg_stdin) => ffmpeg_stdin,
+            None => return Ok(()),
+        };
+        let frame_interval = Duration::from_secs(1) / osd::FRAME_RATE as u32;
+        let mut frame = OverlayFrame::new(overlay_generator.frame_dimensions());
+        let mut next_frame_time = Instant::now();
+        loop {
+            if let Some(tile_indices) = screen.lock().unwrap().take() {
+                frame = overlay_generator.draw_tile_indices(&tile_indices)?;
+            }
+            if let Err(error) = ffmpeg_stdin.write_all(frame.as_raw()) {
+                log::debug!("stopped sending OSD frames: {error}");
+                return Ok(());
+            }
+            next_frame_time += frame_interval;
+            tokio::time::sleep_until(next_frame_time).await;
+        }
+    }
+
+    /// runs until FFMpeg is stopped or MPV is quit
+    pub async fn run(&self) -> Result<(), GroundStationError> {
+        if self.record_file.is_none() && ! self.display { return Err(GroundStationError::NothingToDo) }
+
+        let serial_port = serialport::new(self.serial_port.to_string_lossy(), self.baud_rate)
+            .timeout(SERIAL_PORT_READ_TIMEOUT)
+            .open()
+            .map_err(|error| GroundStationError::FailedOpeningSerialPort { path: self.serial_port.clone(), error })?;
+
+        let mut font_dir = FontDir::new(&self.font_dir);
+        font_dir.set_premultiplied_alpha(self.font_premultiplied_alpha);
+        let font_ident = self.font_ident.as_ref().map(|ident| ident.as_deref());
+        let scaling = Scaling::from_osd_settings(&OSDScalingSettings::default(), self.capture_resolution);
+        let overlay_generator = OverlayGenerator::new_live(OSD_KIND, self.firmware.highest_tile_index(), self.firmware.font_variant(),
+            &font_dir, &font_ident, scaling, &[], &[])?;
+
+        let screen = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+        let display_port_screen = DisplayPortScreen::new(OSD_KIND.dimensions_tiles(), self.firmware.highest_tile_index());
+        let osd_feed_thread = {
+            let (screen, stop) = (screen.clone(), stop.clone());
+            thread::spawn(move || Self::read_osd_feed(serial_port, display_port_screen, screen, stop))
+        };
+
+        log::info!("ground station started: {} + OSD from {}", self.capture_device.to_string_lossy(), self.serial_port.to_string_lossy());
+        if let Some(record_file) = &self.record_file {
+            log::info!("recording to {}", record_file.to_string_lossy());
+        }
+
+        let ffmpeg_command = self.ffmpeg_command(overlay_generator.frame_dimensions()).build().unwrap();
+        let mut ffmpeg_process = if self.display { ffmpeg_command.spawn_piped_stdout()? } else { ffmpeg_command.spawn_no_output()? };
+
+        let mut mpv_child_proc = if self.display {
+            let mut mpv_command = ProcessCommand::new("mpv");
+            mpv_command.args(["--profile=low-latency", "--untimed", "--force-media-title=ground station", "-"]);
+            // there is no FFMpeg output in dry run mode
+            if let Some(ffmpeg_stdout) = ffmpeg_process.take_stdout() {
+                mpv_command.stdin(std::process::Stdio::from(ffmpeg_stdout));
+            }
+            mpv_command.spawn_unless_dry_run().map_err(GroundStationError::FailedToStartMPV)?
+        } else {
+            None
+        };
+
+        let send_result = Self::send_osd_frames(&overlay_generator, &mut ffmpeg_process, &screen).await;
+
+        stop.store(true, Ordering::Relaxed);
+        osd_feed_thread.join().unwrap();
+
+        let ffmpeg_result = ffmpeg_process.wait().await;
+        if let Some(mpv_child_proc) = &mut mpv_child_proc {
+            let mpv_exit_status = mpv_child_proc.wait().map_err(GroundStationError::FailedToStartMPV)?;
+            if ! mpv_exit_status.success() { return Err(GroundStationError::MPVExitedWithAnError(mpv_exit_status)) }
+        } else {
+            // FFMpeg fails when MPV is quit so only report its errors when only recording
+            ffmpeg_result?;
+        }
+        send_result?;
+
+        log::info!("ground station stopped");
+        Ok(())
+    }
+
+}
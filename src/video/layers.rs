@@ -0,0 +1,552 @@
+
+//! Layers drawn onto the video in the same pass: picture-in-picture video, OSD, SRT telemetry, text annotations, clock
+//! and watermark
+//!
+//! Each layer is composited onto the video below it in z-order with an opacity and a blend mode. The layers are
+//! assembled into a single FFMpeg filter graph by [`LayerPipeline`]: the `normal` blend mode overlays the layer onto
+//! the video while the other blend modes draw the layer onto a canvas filled with the neutral color of the blend mode
+//! which is then blended with the video. The blending is done in planar RGB with the bit depth of the video which
+//! keeps its pixel format.
+//!
+//! The telemetry graphs, the sticks and the script widgets are layers of the OSD overlay frames, see
+//! [`crate::osd::overlay::layers`]: their z-order only orders them with the OSD tiles and the whole overlay is then
+//! composited onto the video as the `osd` layer.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use getset::{CopyGetters, Setters};
+use thiserror::Error;
+
+use super::{
+    Resolution,
+    Timestamp,
+    probe,
+    clock::{ClockError, ClockSettings},
+    cut::TempFiles,
+    pip::PipSettings,
+    srt_telemetry::{SrtTelemetryError, SrtTelemetrySettings},
+    text_annotation::TextAnnotation,
+    watermark::{WatermarkPosition, WatermarkSettings},
+};
+
+
+#[derive(Debug, Error)]
+#[error("invalid layer settings: {0}, expected <layer>[|z=<z-order>][|opacity=<0-1>][|blend=<blend mode>]")]
+pub struct InvalidLayerConfigError(String);
+
+/// kind of content drawn by a layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum LayerKind {
+    Pip,
+    Osd,
+    Graphs,
+    Sticks,
+    Script,
+    SrtTelemetry,
+    Text,
+    Clock,
+    Watermark,
+}
+
+impl LayerKind {
+
+    /// z-order of the layers when not configured, the layers are drawn from the lowest to the highest z-order
+    pub fn default_z_order(&self) -> i32 {
+        use LayerKind::*;
+        match self {
+            Pip => -10,
+            Osd => 0,
+            Graphs => 1,
+            Sticks => 2,
+            Script => 3,
+            SrtTelemetry => 10,
+            Text => 20,
+            Clock => 30,
+            Watermark => 40,
+        }
+    }
+
+}
+
+/// how the layer colors are combined with the colors of the video below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum BlendMode {
+    Normal,
+    Addition,
+    Screen,
+    Multiply,
+    Lighten,
+    Darken,
+    Overlay,
+    Difference,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl BlendMode {
+
+    /// mode of the FFMpeg blend filter
+    fn ffmpeg_mode(&self) -> &'static str {
+        use BlendMode::*;
+        match self {
+            Normal => "normal",
+            Addition => "addition",
+            Screen => "screen",
+            Multiply => "multiply",
+            Lighten => "lighten",
+            Darken => "darken",
+            Overlay => "overlay",
+            Difference => "difference",
+        }
+    }
+
+    /// color leaving the video unchanged when blended onto it, `None` for the normal mode
+    fn neutral_color(&self) -> Option<&'static str> {
+        use BlendMode::*;
+        match self {
+            Normal => None,
+            Addition | Screen | Lighten | Difference => Some("black"),
+            Multiply | Darken => Some("white"),
+            Overlay => Some("0x808080"),
+        }
+    }
+
+    /// color component from 0 to 1 of the layer color component `source` blended onto the `backdrop` component
+    pub fn blend(&self, backdrop: f64, source: f64) -> f64 {
+        use BlendMode::*;
+        match self {
+            Normal => source,
+            Addition => (backdrop + source).min(1.0),
+            Screen => backdrop + source - backdrop * source,
+            Multiply => backdrop * source,
+            Lighten => backdrop.max(source),
+            Darken => backdrop.min(source),
+            Overlay if backdrop <= 0.5 => 2.0 * backdrop * source,
+            Overlay => 1.0 - 2.0 * (1.0 - backdrop) * (1.0 - source),
+            Difference => (backdrop - source).abs(),
+        }
+    }
+
+}
+
+/// How a layer is composited onto the video below it
+#[derive(Debug, Clone, Copy, PartialEq, CopyGetters, Setters)]
+#[getset(get_copy = "pub", set = "pub")]
+pub struct LayerStyle {
+    z_order: i32,
+    /// from 0 (invisible) to 1 (opaque)
+    opacity: f64,
+    blend_mode: BlendMode,
+}
+
+impl LayerStyle {
+
+    pub fn new(kind: LayerKind) -> Self {
+        Self { z_order: kind.default_z_order(), opacity: 1.0, blend_mode: BlendMode::default() }
+    }
+
+}
+
+/// Style settings of the layers of a kind, parsed from `<layer>[|<key>=<value>]...`, e.g. `watermark|z=-1|blend=screen`
+///
+/// The settings which are not set keep their default value.
+#[derive(Debug, Clone, Copy, PartialEq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct LayerConfig {
+    kind: LayerKind,
+    z_order: Option<i32>,
+    opacity: Option<f64>,
+    blend_mode: Option<BlendMode>,
+}
+
+impl LayerConfig {
+
+    pub fn new(kind: LayerKind) -> Self {
+        Self { kind, z_order: None, opacity: None, blend_mode: None }
+    }
+
+    fn apply_to(&self, style: &mut LayerStyle) {
+        if let Some(z_order) = self.z_order { style.z_order = z_order }
+        if let Some(opacity) = self.opacity { style.opacity = opacity }
+        if let Some(blend_mode) = self.blend_mode { style.blend_mode = blend_mode }
+    }
+
+}
+
+impl FromStr for LayerConfig {
+    type Err = InvalidLayerConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidLayerConfigError(value.to_owned());
+        let mut parts = value.split('|');
+        let kind = LayerKind::from_str(parts.next().unwrap_or_default().trim()).map_err(|_| error())?;
+        let mut config = Self::new(kind);
+        for part in parts {
+            let (key, option_value) = part.split_once('=').ok_or_else(error)?;
+            match key.trim() {
+                "z" => config.z_order = Some(option_value.parse().map_err(|_| error())?),
+                "opacity" => {
+                    let opacity = option_value.parse::<f64>().map_err(|_| error())?;
+                    if ! (0.0..=1.0).contains(&opacity) { return Err(error()) }
+                    config.opacity = Some(opacity);
+                },
+                "blend" => config.blend_mode = Some(BlendMode::from_str(option_value).map_err(|_| error())?),
+                _ => return Err(error()),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// style of the layers of the kind with the configurations applied in order
+pub fn layer_style(kind: LayerKind, configs: &[LayerConfig]) -> LayerStyle {
+    let mut style = LayerStyle::new(kind);
+    for config in configs.iter().filter(|config| config.kind == kind) {
+        config.apply_to(&mut style);
+    }
+    style
+}
+
+/// What a layer draws, as parts of an FFMpeg filter graph
+pub enum LayerContent {
+    /// RGBA image stream overlaid onto the video at the overlay filter coordinates
+    Image {
+        /// label of the filter graph input with the images, `None` when the first filter is a source filter
+        input: Option<String>,
+        filters: Vec<String>,
+        x: String,
+        y: String,
+        /// whether the last image stays drawn once the image stream has ended, otherwise the layer disappears
+        repeat_last: bool,
+    },
+    /// filters drawing onto the video
+    Filters(Vec<String>),
+}
+
+pub trait Layer {
+    fn kind(&self) -> LayerKind;
+    fn content(&self) -> LayerContent;
+}
+
+/// OSD frames sent to an input of the FFMpeg command, centered on the video
+pub struct OsdLayer {
+    input: String,
+    /// horizontal position of the OSD frames in pixels when not centered, e.g. when the video is cropped
+    left: Option<i64>,
+}
+
+impl OsdLayer {
+
+    /// layer drawing the OSD frames of the filter graph input labelled `input`, e.g. `[1]`
+    pub fn new<S: Into<String>>(input: S) -> Self {
+        Self { input: input.into(), left: None }
+    }
+
+    pub fn set_left(&mut self, left: Option<i64>) -> &mut Self {
+        self.left = left;
+        self
+    }
+
+}
+
+impl Layer for OsdLayer {
+    fn kind(&self) -> LayerKind { LayerKind::Osd }
+
+    fn content(&self) -> LayerContent {
+        let x = self.left.map(|left| left.to_string()).unwrap_or_else(|| "(W-w)/2".to_owned());
+        LayerContent::Image { input: Some(self.input.clone()), filters: vec![], x, y: "(H-h)/2".to_owned(), repeat_last: true }
+    }
+}
+
+/// SRT telemetry drawn as a line of text, see [`SrtTelemetrySettings`]
+pub struct SrtTelemetryLayer {
+    filter: String,
+    /// formatted telemetry read by the filter, removed when the layer is dropped
+    _temp_files: TempFiles,
+}
+
+impl SrtTelemetryLayer {
+    /// writes the formatted telemetry into a temporary file next to the output file, see [`SrtTelemetrySettings`]
+    pub fn new(settings: &SrtTelemetrySettings, output_video_file: &Path, start: Option<Timestamp>) -> Result<Self, SrtTelemetryError> {
+        let (filter, temp_files) = settings.ffmpeg_filter(output_video_file, start)?;
+        Ok(Self { filter, _temp_files: temp_files })
+    }
+}
+
+impl Layer for SrtTelemetryLayer {
+    fn kind(&self) -> LayerKind { LayerKind::SrtTelemetry }
+
+    fn content(&self) -> LayerContent {
+        LayerContent::Filters(vec![self.filter.clone()])
+    }
+}
+
+/// Text annotation, see [`TextAnnotation`]
+pub struct TextLayer {
+    filter: String,
+}
+
+impl TextLayer {
+    /// layer drawing the annotation onto a video starting at the `start` timestamp of the input video
+    pub fn new(annotation: &TextAnnotation, start: Option<Timestamp>) -> Self {
+        Self { filter: annotation.ffmpeg_filter(start) }
+    }
+}
+
+impl Layer for TextLayer {
+    fn kind(&self) -> LayerKind { LayerKind::Text }
+
+    fn content(&self) -> LayerContent {
+        LayerContent::Filters(vec![self.filter.clone()])
+    }
+}
+
+/// Wall-clock time of the recording, see [`ClockSettings`]
+pub struct ClockLayer {
+    filter: String,
+}
+
+impl ClockLayer {
+    /// layer drawing the wall-clock time onto a video starting at the `start` timestamp of the input video
+    pub fn new(settings: &ClockSettings, video_file: &Path, video_info: &probe::Result, start: Option<Timestamp>) -> Result<Self, ClockError> {
+        let recording_start = settings.recording_start(video_file, video_info)?;
+        log::info!("drawing wall-clock time from recording start: {recording_start:.0}s since epoch ({} source)", settings.source());
+        Ok(Self { filter: settings.ffmpeg_filter(recording_start, start) })
+    }
+}
+
+impl Layer for ClockLayer {
+    fn kind(&self) -> LayerKind { LayerKind::Clock }
+
+    fn content(&self) -> LayerContent {
+        LayerContent::Filters(vec![self.filter.clone()])
+    }
+}
+
+/// Logo image, see [`WatermarkSettings`]
+pub struct WatermarkLayer {
+    settings: WatermarkSettings,
+}
+
+impl WatermarkLayer {
+    pub fn new(settings: &WatermarkSettings) -> Self {
+        Self { settings: settings.clone() }
+    }
+}
+
+impl Layer for WatermarkLayer {
+    fn kind(&self) -> LayerKind { LayerKind::Watermark }
+
+    fn content(&self) -> LayerContent {
+        let (x, y) = self.settings.position().overlay_coordinates();
+        LayerContent::Image { input: None, filters: self.settings.image_filters(), x: x.to_owned(), y: y.to_owned(), repeat_last: true }
+    }
+}
+
+/// Picture-in-picture video, see [`PipSettings`]
+pub struct PipLayer {
+    filters: Vec<String>,
+    position: WatermarkPosition,
+}
+
+impl PipLayer {
+    /// layer insetting the picture-in-picture video onto a video of the `resolution` starting at the `start`
+    /// timestamp of the input video
+    pub fn new(settings: &PipSettings, resolution: Resolution, start: Option<Timestamp>) -> Self {
+        Self { filters: settings.video_filters(resolution, start), position: settings.position() }
+    }
+}
+
+impl Layer for PipLayer {
+    fn kind(&self) -> LayerKind { LayerKind::Pip }
+
+    fn content(&self) -> LayerContent {
+        let (x, y) = self.position.overlay_coordinates();
+        LayerContent::Image { input: None, filters: self.filters.clone(), x: x.to_owned(), y: y.to_owned(), repeat_last: false }
+    }
+}
+
+/// Layers composited onto the video in z-order, the layers with the same z-order are drawn in the order they were added
+#[derive(Default)]
+pub struct LayerPipeline {
+    layers: Vec<(Box<dyn Layer>, LayerStyle)>,
+}
+
+impl LayerPipeline {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<L: Layer + 'static>(&mut self, layer: L, style: LayerStyle) -> &mut Self {
+        self.layers.push((Box::new(layer), style));
+        self
+    }
+
+    /// adds the layer with the style of its kind resulting from the configurations
+    pub fn add_configured<L: Layer + 'static>(&mut self, layer: L, configs: &[LayerConfig]) -> &mut Self {
+        let style = layer_style(layer.kind(), configs);
+        self.add(layer, style)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Part of the FFMpeg filter graph compositing the layers onto the video labelled `input`, the result being
+    /// labelled `output`
+    ///
+    /// The canvases of the layers which are not blended with the normal mode have the resolution and frame rate of
+    /// the video, the blended video keeps its `pixel_format`.
+    pub fn ffmpeg_filter_graph(&self, input: &str, output: &str, resolution: Resolution, frame_rate: &str, pixel_format: &str) -> String {
+        let mut layers = self.layers.iter().filter(|(_, style)| style.opacity > 0.0).collect::<Vec<_>>();
+        layers.sort_by_key(|(_, style)| style.z_order);
+        if layers.is_empty() { return format!("{input}null{output}") }
+
+        let mut graph = vec![];
+        let mut current = input.to_owned();
+        for (index, (layer, style)) in layers.iter().enumerate() {
+            let label = |suffix: &str| format!("[l{index}{suffix}]");
+            let next = if index == layers.len() - 1 { output.to_owned() } else { label("") };
+            let canvas = style.blend_mode.neutral_color().map(|color|
+                format!("color=c={color}:s={}x{}:r={frame_rate}", resolution.width, resolution.height));
+            match (layer.content(), canvas) {
+                (LayerContent::Image { input: image_input, mut filters, x, y, repeat_last }, canvas) => {
+                    filters.push("format=rgba".to_owned());
+                    if style.opacity < 1.0 { filters.push(format!("colorchannelmixer=aa={}", style.opacity)) }
+                    graph.push(format!("{}{}{}", image_input.unwrap_or_default(), filters.join(","), label("i")));
+                    let overlay = format!("overlay=x={x}:y={y}:eof_action={}", if repeat_last { "repeat" } else { "pass" });
+                    match canvas {
+                        None => graph.push(format!("{current}{}{overlay}{next}", label("i"))),
+                        Some(canvas) => {
+                            graph.push(format!("{canvas}{}", label("c")));
+                            graph.push(format!("{}{}{overlay}{}", label("c"), label("i"), label("d")));
+                            graph.push(Self::blend([&current, &label("d"), &next], [&label("v"), &label("l")], style.blend_mode, pixel_format, 1.0));
+                        },
+                    }
+                },
+                (LayerContent::Filters(filters), None) if style.opacity >= 1.0 =>
+                    graph.push(format!("{current}{}{next}", filters.join(","))),
+                (LayerContent::Filters(filters), None) => {
+                    // the layer drawn onto a copy of the video is mixed with the video
+                    graph.push(format!("{current}split{}{}", label("b"), label("t")));
+                    graph.push(format!("{}{}{}", label("t"), filters.join(","), label("d")));
+                    graph.push(format!("{}{}blend=all_mode=normal:all_opacity={}{next}", label("d"), label("b"), style.opacity));
+                },
+                (LayerContent::Filters(filters), Some(canvas)) => {
+                    graph.push(format!("{canvas},{}{}", filters.join(","), label("d")));
+                    graph.push(Self::blend([&current, &label("d"), &next], [&label("v"), &label("l")], style.blend_mode, pixel_format, style.opacity));
+                },
+            }
+            current = next;
+        }
+        graph.join(";")
+    }
+
+    /// blends the layer drawn onto its canvas with the video, the blending is done in RGB with the bit depth of the
+    /// video pixel format, the result being converted back to the pixel format
+    ///
+    /// `labels` are the video, layer and output labels and `rgb_labels` the labels of the video and layer in RGB.
+    fn blend(labels: [&str; 3], rgb_labels: [&str; 2], blend_mode: BlendMode, pixel_format: &str, opacity: f64) -> String {
+        let ([video, layer, output], [rgb_video, rgb_layer]) = (labels, rgb_labels);
+        let rgb_pixel_format = rgb_pixel_format(pixel_format);
+        format!("{video}format={rgb_pixel_format}{rgb_video};{layer}format={rgb_pixel_format}{rgb_layer};\
+            {rgb_video}{rgb_layer}blend=all_mode={}:all_opacity={opacity}:shortest=1,format={pixel_format}{output}",
+            blend_mode.ffmpeg_mode())
+    }
+
+}
+
+/// planar RGB pixel format with the bit depth of the pixel format, e.g. `gbrp10le` for `yuv420p10le`
+fn rgb_pixel_format(pixel_format: &str) -> &'static str {
+    // the bit depth is the number ending the name of the high bit depth formats before the endianness
+    let bit_depth = pixel_format.strip_suffix("le").or_else(|| pixel_format.strip_suffix("be"))
+        .and_then(|name| name[name.trim_end_matches(|c: char| c.is_ascii_digit()).len()..].parse::<u32>().ok());
+    match bit_depth {
+        Some(9..=10) => "gbrp10le",
+        Some(11..=12) => "gbrp12le",
+        Some(13..) => "gbrp16le",
+        _ => "gbrp",
+    }
+}
+
+/// pixel format of the video the layers are drawn onto, `yuv420p` when unknown
+pub fn ffmpeg_pixel_format(video_info: &probe::Result) -> &str {
+    video_info.pixel_format().as_deref().unwrap_or("yuv420p")
+}
+
+/// FFMpeg filter graph frame rate of the video
+pub fn ffmpeg_frame_rate(video_info: &probe::Result) -> String {
+    format!("{}/{}", video_info.frame_rate().numerator(), video_info.frame_rate().denominator())
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct DrawTextLayer;
+
+    impl Layer for DrawTextLayer {
+        fn kind(&self) -> LayerKind { LayerKind::Text }
+
+        fn content(&self) -> LayerContent {
+            LayerContent::Filters(vec!["drawtext=text=A".to_owned()])
+        }
+    }
+
+    fn filter_graph(pipeline: &LayerPipeline, pixel_format: &str) -> String {
+        pipeline.ffmpeg_filter_graph("[base]", "[out]", Resolution::new(1920, 1080), "60/1", pixel_format)
+    }
+
+    #[test]
+    fn parse_layer_config() {
+        let config = LayerConfig::from_str("Watermark|z=-1|opacity=0.5|blend=screen").unwrap();
+        assert_eq!(config.kind(), LayerKind::Watermark);
+        assert_eq!(config.z_order(), Some(-1));
+        assert_eq!(config.opacity(), Some(0.5));
+        assert_eq!(config.blend_mode(), Some(BlendMode::Screen));
+        assert_eq!(LayerConfig::from_str("srt-telemetry").unwrap(), LayerConfig::new(LayerKind::SrtTelemetry));
+        for invalid in ["logo", "osd|opacity=2", "osd|z=top", "osd|blend=burn", "osd|size=2", "osd|z"] {
+            assert!(LayerConfig::from_str(invalid).is_err(), "{invalid}");
+        }
+        let style = layer_style(LayerKind::Osd, &[LayerConfig::from_str("osd|z=5").unwrap(), LayerConfig::from_str("osd|opacity=0.8").unwrap()]);
+        assert_eq!((style.z_order(), style.opacity(), style.blend_mode()), (5, 0.8, BlendMode::Normal));
+    }
+
+    #[test]
+    fn layers_composited_in_z_order() {
+        let mut pipeline = LayerPipeline::new();
+        assert_eq!(filter_graph(&pipeline, "yuv420p"), "[base]null[out]");
+
+        pipeline.add_configured(DrawTextLayer, &[]).add_configured(OsdLayer::new("[1]"), &[]);
+        assert_eq!(filter_graph(&pipeline, "yuv420p"),
+            "[1]format=rgba[l0i];[base][l0i]overlay=x=(W-w)/2:y=(H-h)/2:eof_action=repeat[l0];[l0]drawtext=text=A[out]");
+
+        let mut pipeline = LayerPipeline::new();
+        pipeline.add_configured(DrawTextLayer, &[LayerConfig::from_str("text|opacity=0").unwrap()]);
+        assert_eq!(filter_graph(&pipeline, "yuv420p"), "[base]null[out]");
+    }
+
+    #[test]
+    fn blended_layers_keep_the_pixel_format() {
+        let mut pipeline = LayerPipeline::new();
+        pipeline.add_configured(OsdLayer::new("[1]"), &[LayerConfig::from_str("osd|blend=screen").unwrap()]);
+        assert_eq!(filter_graph(&pipeline, "yuv420p10le"),
+            "[1]format=rgba[l0i];color=c=black:s=1920x1080:r=60/1[l0c];[l0c][l0i]overlay=x=(W-w)/2:y=(H-h)/2:eof_action=repeat[l0d];\
+            [base]format=gbrp10le[l0v];[l0d]format=gbrp10le[l0l];[l0v][l0l]blend=all_mode=screen:all_opacity=1:shortest=1,format=yuv420p10le[out]");
+        assert_eq!(rgb_pixel_format("yuv420p"), "gbrp");
+        assert_eq!(rgb_pixel_format("p010le"), "gbrp10le");
+        assert_eq!(rgb_pixel_format("yuv444p12be"), "gbrp12le");
+        assert_eq!(rgb_pixel_format("nv16"), "gbrp");
+    }
+
+}
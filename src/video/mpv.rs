@@ -0,0 +1,169 @@
+
+use std::{
+    io::Error as IOError,
+    path::Path,
+};
+
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    time::{Duration, Instant},
+};
+
+use getset::CopyGetters;
+use serde_json::{json, Value as JsonValue};
+
+use crate::osd;
+
+
+/// target of the `script-message` commands sent by the key bindings
+const SCRIPT_MESSAGE_TARGET: &str = "hd-fpv-osd";
+
+/// key bindings sending the OSD overlay control commands to the IPC clients
+const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("Alt+o", "toggle"),
+    ("Alt+UP", "opacity+"),
+    ("Alt+DOWN", "opacity-"),
+    ("Alt+LEFT", "shift-1"),
+    ("Alt+RIGHT", "shift+1"),
+    ("Alt+Shift+LEFT", "shift-10"),
+    ("Alt+Shift+RIGHT", "shift+10"),
+];
+
+const OPACITY_STEP: u8 = 10;
+
+/// delay during which connecting to the MPV IPC socket is retried while MPV is starting
+#[cfg(unix)]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(super) fn write_input_conf(path: &Path) -> Result<(), IOError> {
+    let input_conf = KEY_BINDINGS.iter()
+        .map(|(key, action)| format!("{key} script-message {SCRIPT_MESSAGE_TARGET} {action}\n"))
+        .collect::<String>();
+    fs_err::write(path, input_conf)
+}
+
+pub(super) fn key_bindings_help() -> String {
+    KEY_BINDINGS.iter().map(|(key, action)| format!("{key}: {action}")).collect::<Vec<_>>().join(", ")
+}
+
+/// state of the OSD overlay adjusted with the key bindings while playing
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub(super)")]
+pub(super) struct OverlayControls {
+    visible: bool,
+    /// opacity in percent
+    opacity: u8,
+    /// shift in frames added to the OSD video timestamps
+    frame_shift: i32,
+}
+
+impl OverlayControls {
+
+    /// filter graph overlaying the OSD video onto the video
+    pub(super) fn lavfi_complex(&self) -> String {
+        let alpha = if self.visible { self.opacity as f64 / 100.0 } else { 0.0 };
+        format!("[vid2]setpts=PTS+{}/TB,format=rgba,colorchannelmixer=aa={alpha}[osd];\
+            [vid1][osd]overlay=(main_w-overlay_w)/2:(main_h-overlay_h)/2[vo]", self.frame_shift as f64 / osd::FRAME_RATE as f64)
+    }
+
+    /// applies the action sent by a key binding, returns the message to display or `None` if the action is unknown
+    #[cfg(unix)]
+    fn apply(&mut self, action: &str) -> Option<String> {
+        match action {
+            "toggle" => {
+                self.visible = ! self.visible;
+                Some(format!("OSD {}", if self.visible { "shown" } else { "hidden" }))
+            },
+            "opacity+" => {
+                self.opacity = (self.opacity + OPACITY_STEP).min(100);
+                Some(format!("OSD opacity: {}%", self.opacity))
+            },
+            "opacity-" => {
+                self.opacity = self.opacity.saturating_sub(OPACITY_STEP);
+                Some(format!("OSD opacity: {}%", self.opacity))
+            },
+            shift if shift.starts_with("shift") => {
+                self.frame_shift += shift.trim_start_matches("shift").parse::<i32>().ok()?;
+                Some(format!("OSD frame shift: {} frames", self.frame_shift))
+            },
+            _ => None,
+        }
+    }
+
+}
+
+impl Default for OverlayControls {
+    fn default() -> Self {
+        Self { visible: true, opacity: 100, frame_shift: 0 }
+    }
+}
+
+/// client of the MPV JSON IPC socket
+#[cfg(unix)]
+pub(super) struct IPCClient {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+}
+
+#[cfg(unix)]
+impl IPCClient {
+
+    /// connects to the socket, retrying while MPV is starting
+    pub(super) fn connect(socket_path: &Path) -> Result<Self, IOError> {
+        let start = Instant::now();
+        let stream = loop {
+            match UnixStream::connect(socket_path) {
+                Ok(stream) => break stream,
+                Err(error) if start.elapsed() > CONNECT_TIMEOUT => return Err(error),
+                Err(_) => std::thread::sleep(Duration::from_millis(100)),
+            }
+        };
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Self { stream, reader })
+    }
+
+    fn send_command(&mut self, command: &[JsonValue]) -> Result<(), IOError> {
+        writeln!(self.stream, "{}", json!({ "command": command }))
+    }
+
+    /// handles the key bindings actions until MPV exits, returns the final state of the controls
+    pub(super) fn run(mut self, mut controls: OverlayControls) -> Result<OverlayControls, IOError> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 { break }
+            let message: JsonValue = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(_) => continue,
+            };
+            if message["event"] != "client-message" || message["args"][0] != SCRIPT_MESSAGE_TARGET { continue }
+            let action = message["args"][1].as_str().unwrap_or_default();
+            if let Some(text) = controls.apply(action) {
+                log::debug!("mpv: {text}");
+                self.send_command(&[json!("set_property"), json!("lavfi-complex"), json!(controls.lavfi_complex())])?;
+                self.send_command(&[json!("show-text"), json!(text)])?;
+            }
+        }
+        Ok(controls)
+    }
+
+}
+
+/// MPV uses named pipes instead of unix sockets for its JSON IPC on other platforms, the OSD controls are not available
+#[cfg(not(unix))]
+pub(super) struct IPCClient;
+
+#[cfg(not(unix))]
+impl IPCClient {
+
+    pub(super) fn connect(_socket_path: &Path) -> Result<Self, IOError> {
+        Err(IOError::new(std::io::ErrorKind::Unsupported, "the MPV IPC socket is only supported on unix"))
+    }
+
+    pub(super) fn run(self, controls: OverlayControls) -> Result<OverlayControls, IOError> {
+        Ok(controls)
+    }
+
+}
@@ -0,0 +1,76 @@
+
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::ffmpeg;
+use crate::file;
+use crate::osd::overlay::{interpolation::osd_frame_index, pgs::{PGSWriter, WritePGSError}};
+
+use super::{
+    TranscodeVideoError,
+    probe,
+    transcoder::OSDSettings,
+    cut::{TempFiles, temp_file_path},
+};
+
+
+/// Exports the video into a MKV file with the OSD muxed as a PGS image subtitle track
+///
+/// The video and audio streams are copied untouched so that the OSD can be toggled in the players supporting
+/// image subtitles. The subtitles are written into a temporary `.sup` file next to the output file before muxing.
+pub async fn export_mkv_with_osd_track<P: AsRef<Path>, Q: AsRef<Path>>(video_file: P, output_file: Q, osd_settings: &OSDSettings,
+        overwrite: bool) -> Result<(), TranscodeVideoError> {
+
+    let video_file = video_file.as_ref();
+    let output_file = output_file.as_ref();
+
+    if ! video_file.exists() { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
+    if ! matches!(output_file.extension(), Some(extension) if extension.eq_ignore_ascii_case("mkv")) {
+        return Err(TranscodeVideoError::OutputFileExtensionNotMkv);
+    }
+    if ! overwrite && output_file.exists() { return Err(TranscodeVideoError::OutputVideoFileExists); }
+    if video_file == output_file { return Err(TranscodeVideoError::InputAndOutputFileIsTheSame) }
+
+    let video_info = probe(video_file)?;
+    let osd_frame_shift = osd_settings.frame_shift_for_video(&video_info);
+    let osd_frames_generator = osd_settings.overlay_generator(video_info.resolution())?;
+
+    file::touch(output_file)?;
+
+    let subtitles_file = temp_file_path(output_file, ".osd.sup");
+    let _temp_files = TempFiles(vec![subtitles_file.clone()]);
+
+    if ! crate::process::dry_run() {
+        log::info!("generating OSD subtitles");
+        let video_frame_rate = video_info.frame_rate().numerator() as f64 / video_info.frame_rate().denominator() as f64;
+        let video_frame_count = video_info.frame_count() as u32;
+        let last_osd_frame = osd_frame_index(video_frame_count.saturating_sub(1) as u64, video_frame_rate);
+        let osd_frames_iter = osd_frames_generator.iter_advanced(0, Some(last_osd_frame), osd_frame_shift);
+        let subtitles_writer = BufWriter::new(fs_err::File::create(&subtitles_file).map_err(TranscodeVideoError::FailedWritingOSDSubtitles)?);
+        PGSWriter::new(subtitles_writer, video_info.resolution(), video_frame_rate, osd_frames_generator.frame_dimensions())
+            .write_frames(osd_frames_iter, video_frame_count)
+            .map_err(|error| match error {
+                WritePGSError::UnknownOSDItem(error) => TranscodeVideoError::UnknownOSDItem(error),
+                WritePGSError::ReadError(error) => TranscodeVideoError::OSDFileReadError(error),
+                WritePGSError::WriteError(error) => TranscodeVideoError::FailedWritingOSDSubtitles(error),
+            })?;
+    }
+
+    log::info!("muxing OSD subtitles track: {}", output_file.to_string_lossy());
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    ffmpeg_command
+        .add_input_file(video_file)
+        .add_input_file(&subtitles_file)
+        .add_mapping("0")
+        .add_mapping("1")
+        .set_output_video_codec(Some("copy"))
+        .set_output_audio_codec(Some("copy"))
+        .add_args(&["-c:s", "copy", "-metadata:s:s:0", "title=OSD"])
+        .set_output_file(output_file)
+        .set_overwrite_output_file(true);
+
+    ffmpeg_command.build().unwrap().spawn_with_progress(video_info.frame_count())?.wait().await?;
+
+    log::info!("MKV file with OSD subtitles track exported successfully");
+    Ok(())
+}
@@ -0,0 +1,105 @@
+
+//! Calibration clip of the OSD sync: a short section of the video repeated with several candidate OSD frame shifts,
+//! each repetition being labelled with its frame shift so that the right `--osd-frame-shift` can be picked in a single
+//! viewing
+//!
+//! The candidate frame shifts are centered on the frame shift which would be applied when burning the OSD: the one
+//! set in the OSD settings or the one chosen automatically depending on the video origin.
+
+use std::path::Path;
+
+use derive_more::From;
+use thiserror::Error;
+
+use crate::temp_dir::{self, InsufficientSpaceError};
+
+use super::{
+    Timestamp,
+    TranscodeVideoError,
+    VideoProbingError,
+    concat::{self, ConcatVideosError},
+    cut::{self, TempFiles},
+    probe,
+    text_annotation::{TextAnnotation, TextPosition},
+    transcoder::{OSDSettings, TranscodePreset, Transcoder},
+};
+
+
+#[derive(Debug, Error, From)]
+pub enum OSDSyncCalibrationError {
+    #[error("input video file does not exist")]
+    InputVideoFileDoesNotExist,
+    #[error("output video file exists")]
+    OutputVideoFileExists,
+    #[error("invalid clip duration: {0}, it must be greater than 0")]
+    #[from(ignore)]
+    InvalidClipDuration(f64),
+    #[error("the clip start {0} is after the end of the video")]
+    #[from(ignore)]
+    StartAfterVideoEnd(Timestamp),
+    #[error("failed to get input video details")]
+    FailedToGetInputVideoDetails(VideoProbingError),
+    #[error(transparent)]
+    TranscodeVideoError(TranscodeVideoError),
+    #[error(transparent)]
+    ConcatVideosError(ConcatVideosError),
+    #[error(transparent)]
+    InsufficientTempSpace(InsufficientSpaceError),
+}
+
+/// label drawn onto the clip rendered with the frame shift
+fn frame_shift_label(frame_shift: i32) -> TextAnnotation {
+    let mut label = TextAnnotation::new(format!("--osd-frame-shift {frame_shift}"));
+    label.set_position(TextPosition::Top);
+    label
+}
+
+/// renders the `duration` seconds of the video from `start` with the OSD once per frame shift from `-max_shift` to
+/// `max_shift` OSD frames around the frame shift of the OSD settings, the clips being joined into the output video
+pub async fn calibrate_osd_sync<P: AsRef<Path>, Q: AsRef<Path>>(video_file: P, output_video_file: Q, osd_settings: &OSDSettings,
+        start: Timestamp, duration: f64, max_shift: u8, overwrite: bool) -> Result<(), OSDSyncCalibrationError> {
+
+    let (video_file, output_video_file) = (video_file.as_ref(), output_video_file.as_ref());
+    if ! video_file.exists() { return Err(OSDSyncCalibrationError::InputVideoFileDoesNotExist) }
+    if ! duration.is_finite() || duration <= 0.0 { return Err(OSDSyncCalibrationError::InvalidClipDuration(duration)) }
+    if ! overwrite && output_video_file.exists() { return Err(OSDSyncCalibrationError::OutputVideoFileExists) }
+
+    let video_info = probe(video_file)?;
+    let video_duration_ms = (video_info.duration() * 1000.0) as u64;
+    if start.total_milliseconds() >= video_duration_ms { return Err(OSDSyncCalibrationError::StartAfterVideoEnd(start)) }
+    let end = Timestamp::from_milliseconds((start.total_milliseconds() + (duration * 1000.0) as u64).min(video_duration_ms));
+
+    let center_frame_shift = osd_settings.frame_shift_for_video(&video_info);
+    let frame_shifts = (center_frame_shift - max_shift as i32)..=(center_frame_shift + max_shift as i32);
+    log::info!("rendering OSD sync calibration clip with frame shifts {} to {}: {} -> {}", frame_shifts.start(), frame_shifts.end(),
+        video_file.to_string_lossy(), output_video_file.to_string_lossy());
+
+    let clip_duration = (end.total_milliseconds() - start.total_milliseconds()) as f64 / 1000.0;
+    let clip_count = (2 * max_shift as u64) + 1;
+    temp_dir::check_available_space(output_video_file,
+        temp_dir::estimated_size(video_file, video_info.duration(), clip_duration) * clip_count)?;
+
+    let extension = output_video_file.extension().map(|extension| extension.to_string_lossy().into_owned()).unwrap_or_else(|| "mp4".to_owned());
+    let mut temp_files = TempFiles(vec![]);
+    for frame_shift in frame_shifts {
+        let clip_file = cut::temp_file_path(output_video_file, &format!(".shift{frame_shift}.{extension}"));
+        temp_files.0.push(clip_file.clone());
+        let mut clip_osd_settings = osd_settings.clone();
+        clip_osd_settings.set_frame_shift(Some(frame_shift.into()));
+        let mut transcoder = Transcoder::new(video_file, &clip_file);
+        transcoder
+            .set_encoding_settings(&TranscodePreset::Draft.encoding_settings())
+            .set_overwrite(true)
+            .set_start(Some(start))
+            .set_end(Some(end))
+            .set_osd(Some(clip_osd_settings))
+            .set_text_annotations(vec![frame_shift_label(frame_shift)]);
+        transcoder.transcode().await?;
+    }
+
+    // the clips all have the same encoding settings so they are joined without re-encoding
+    concat::concat_videos(&temp_files.0, output_video_file, overwrite, None, false).await?;
+
+    log::info!("OSD sync calibration clip rendered successfully, pick the frame shift of the repetition in sync and use it with --osd-frame-shift");
+    Ok(())
+}
@@ -0,0 +1,125 @@
+
+//! Picture-in-picture: a second video, e.g. the feed of a GoPro or HD camera, inset over the video in the same pass as
+//! the OSD, see [`super::layers::PipLayer`]
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+
+use crate::ffmpeg;
+
+use super::{Resolution, Timestamp, watermark::WatermarkPosition};
+
+
+#[derive(Debug, Error)]
+#[error("invalid picture-in-picture settings: {0}, expected <video>[:position[:size[:offset]]]")]
+pub struct InvalidPipSettingsError(String);
+
+/// Settings used to inset the picture-in-picture video, parsed from `<video>[:position[:size[:offset]]]`, e.g.
+/// `gopro.mp4:top-left:25:-3.5`
+///
+/// The empty settings keep their default value, e.g. `gopro.mp4::40`.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct PipSettings {
+    video_file: PathBuf,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    position: WatermarkPosition,
+    /// width of the inset in percent of the video width
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    size: u8,
+    /// position in seconds of the video at which the picture-in-picture video starts, negative to skip the beginning
+    /// of the picture-in-picture video
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    offset: f64,
+}
+
+impl PipSettings {
+
+    pub fn new<P: AsRef<Path>>(video_file: P) -> Self {
+        Self { video_file: video_file.as_ref().to_path_buf(), position: WatermarkPosition::TopRight, size: 30, offset: 0.0 }
+    }
+
+    /// parses the `position[:size[:offset]]` settings
+    fn parse_settings(mut self, settings: &str) -> Option<Self> {
+        let mut parts = settings.split(':');
+        if let Some(position) = parts.next().filter(|position| ! position.is_empty()) {
+            self.position = WatermarkPosition::from_str(position).ok()?;
+        }
+        if let Some(size) = parts.next().filter(|size| ! size.is_empty()) {
+            self.size = size.parse().ok().filter(|size| (1..=100).contains(size))?;
+        }
+        if let Some(offset) = parts.next().filter(|offset| ! offset.is_empty()) {
+            self.offset = offset.parse().ok().filter(|offset: &f64| offset.is_finite())?;
+        }
+        if parts.next().is_some() { return None }
+        Some(self)
+    }
+
+    /// filters reading the picture-in-picture video, synchronized with the video starting at the `start` timestamp
+    /// and scaled to its size relative to the `resolution` of the video, as the source of the picture-in-picture layer
+    ///
+    /// The video is read with the `movie` source filter so that the inputs of the FFMpeg command are not changed.
+    pub fn video_filters(&self, resolution: Resolution, start: Option<Timestamp>) -> Vec<String> {
+        let start = start.map(|start| start.total_milliseconds() as f64 / 1000.0).unwrap_or_default();
+        let (seek_point, delay) = if start >= self.offset { (start - self.offset, 0.0) } else { (0.0, self.offset - start) };
+        let width = (resolution.width * self.size as u32 / 100).max(2) & !1;
+        vec![
+            format!("movie=filename={}:seek_point={seek_point:.3}", ffmpeg::escape_filter_option_value(&self.video_file.to_string_lossy())),
+            format!("setpts=PTS-STARTPTS+{delay:.3}/TB"),
+            format!("scale={width}:-2"),
+        ]
+    }
+
+}
+
+impl FromStr for PipSettings {
+    type Err = InvalidPipSettingsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.is_empty() { return Err(InvalidPipSettingsError(value.to_owned())) }
+        // the video file path may contain `:` characters, e.g. Windows drive letters, so the settings start at the
+        // first `:` followed by valid settings, a value with invalid settings only being accepted as the path of an
+        // existing file
+        value.match_indices(':')
+            .filter(|(index, _)| *index > 0)
+            .find_map(|(index, _)| Self::new(&value[..index]).parse_settings(&value[index + 1..]))
+            .or_else(|| Some(Self::new(value)).filter(|_| ! value.contains(':') || Path::new(value).exists()))
+            .ok_or_else(|| InvalidPipSettingsError(value.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn settings_parsed() {
+        let settings = PipSettings::from_str("gopro.mp4:top-left:25:-3.5").unwrap();
+        assert_eq!(settings.video_file(), Path::new("gopro.mp4"));
+        assert_eq!((settings.position(), settings.size(), settings.offset()), (WatermarkPosition::TopLeft, 25, -3.5));
+
+        let settings = PipSettings::from_str("C:\\videos\\gopro.mp4::40").unwrap();
+        assert_eq!(settings.video_file(), Path::new("C:\\videos\\gopro.mp4"));
+        assert_eq!((settings.position(), settings.size(), settings.offset()), (WatermarkPosition::TopRight, 40, 0.0));
+
+        assert_eq!(PipSettings::from_str("gopro.mp4").unwrap(), PipSettings::new("gopro.mp4"));
+    }
+
+    #[test]
+    fn invalid_settings_rejected() {
+        assert!(PipSettings::from_str("").is_err());
+        assert!(PipSettings::from_str("gopro.mp4:sideways").is_err());
+        assert!(PipSettings::from_str("gopro.mp4:top-left:0").is_err());
+        assert!(PipSettings::from_str("gopro.mp4:top-left:101").is_err());
+        assert!(PipSettings::from_str("gopro.mp4:top-left:25:NaN").is_err());
+        assert!(PipSettings::from_str("gopro.mp4:top-left:25:1:2").is_err());
+    }
+
+}
@@ -4,6 +4,7 @@ use ffmpeg_next as ffmpeg;
 
 use ffmpeg::Rational;
 use getset::{CopyGetters, Getters};
+use serde_json::json;
 use thiserror::Error;
 
 use super::resolution::Resolution;
@@ -26,36 +27,240 @@ impl Error {
     }
 }
 
+#[derive(Debug, Clone, CopyGetters, Getters)]
+#[getset(get_copy = "pub")]
+pub struct AudioStreamInfo {
+    #[getset(skip)] #[getset(get = "pub")]
+    codec: Option<String>,
+    channels: u32,
+    sample_rate: u32,
+    /// bitrate in bits per second, when known
+    bitrate: Option<u64>,
+    /// start time in seconds relative to the start of the video stream
+    start_offset: f64,
+    /// duration of the audio stream in seconds, when known
+    duration: Option<f64>,
+}
+
+impl AudioStreamInfo {
+    pub fn channel_layout(&self) -> String {
+        match self.channels {
+            1 => "mono".to_owned(),
+            2 => "stereo".to_owned(),
+            6 => "5.1".to_owned(),
+            8 => "7.1".to_owned(),
+            channels => format!("{channels} channels"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, CopyGetters, Getters)]
 #[getset(get_copy = "pub")]
 pub struct Result {
     frame_count: u64,
     frame_rate: Rational,
+    /// average frame rate, differs from the frame rate for variable frame rate videos
+    avg_frame_rate: Rational,
     has_audio: bool,
     resolution: Resolution,
+    /// duration in seconds
+    duration: f64,
+    /// video stream bitrate in bits per second, when known
+    video_bitrate: Option<u64>,
+    /// duration of the video stream in seconds, when known
+    video_duration: Option<f64>,
 
     #[getset(skip)] #[getset(get = "pub")]
     video_codec: Option<String>,
+
+    /// pixel format of the video stream, e.g. `yuv420p10le` for 10-bit videos
+    #[getset(skip)] #[getset(get = "pub")]
+    pixel_format: Option<String>,
+
+    #[getset(skip)] #[getset(get = "pub")]
+    audio: Option<AudioStreamInfo>,
+
+    /// `creation_time` tag of the container, usually an ISO 8601 UTC date and time
+    #[getset(skip)] #[getset(get = "pub")]
+    creation_time: Option<String>,
+
+    /// `encoder` tag of the container, the software or firmware which wrote the file
+    #[getset(skip)] #[getset(get = "pub")]
+    encoder: Option<String>,
+}
+
+impl Result {
+
+    /// whether the video has a variable frame rate, the average frame rate is then different from the frame rate
+    pub fn is_variable_frame_rate(&self) -> bool {
+        self.avg_frame_rate.numerator() != 0 && self.avg_frame_rate != self.frame_rate
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let rational_json = |rational: Rational| json!({ "numerator": rational.numerator(), "denominator": rational.denominator() });
+        json!({
+            "resolution": { "width": self.resolution.width, "height": self.resolution.height },
+            "frame_rate": rational_json(self.frame_rate),
+            "avg_frame_rate": rational_json(self.avg_frame_rate),
+            "variable_frame_rate": self.is_variable_frame_rate(),
+            "frame_count": self.frame_count,
+            "duration": self.duration,
+            "video_codec": self.video_codec,
+            "pixel_format": self.pixel_format,
+            "video_bitrate": self.video_bitrate,
+            "creation_time": self.creation_time,
+            "encoder": self.encoder,
+            "audio": self.audio.as_ref().map(|audio| json!({
+                "codec": audio.codec,
+                "channels": audio.channels,
+                "channel_layout": audio.channel_layout(),
+                "sample_rate": audio.sample_rate,
+                "bitrate": audio.bitrate,
+            })),
+        })
+    }
+
+    /// probes the video file, see [`probe`]
+    pub fn probe<P: AsRef<Path>>(video_file: P) -> std::result::Result<Self, Error> {
+        probe(video_file)
+    }
+
+}
+
+#[cfg(test)]
+impl Result {
+
+    /// details of a constant frame rate H.264 video with a stereo AAC audio stream when `has_audio`, as probed from
+    /// an original recording
+    pub(crate) fn for_test(resolution: Resolution, frame_rate: Rational, duration: f64, has_audio: bool) -> Self {
+        let audio = Some(AudioStreamInfo { codec: Some("aac".to_owned()), channels: 2, sample_rate: 48000, bitrate: None,
+            start_offset: 0.0, duration: Some(duration) }).filter(|_| has_audio);
+        Self { frame_count: (duration * f64::from(frame_rate)).round() as u64, frame_rate, avg_frame_rate: frame_rate, has_audio,
+            resolution, duration, video_bitrate: None, video_duration: Some(duration), video_codec: Some("h264".to_owned()),
+            pixel_format: Some("yuv420p".to_owned()), audio, creation_time: None, encoder: None }
+    }
+
+    pub(crate) fn with_frame_count(mut self, frame_count: u64) -> Self {
+        self.frame_count = frame_count;
+        self
+    }
+
+    /// sets the start offset relative to the video and the duration of the audio stream, if any
+    pub(crate) fn with_audio_timing(mut self, start_offset: f64, duration: Option<f64>) -> Self {
+        if let Some(audio) = &mut self.audio {
+            audio.start_offset = start_offset;
+            audio.duration = duration;
+        }
+        self
+    }
+
 }
 
 pub fn probe<P: AsRef<Path>>(video_file: P) -> std::result::Result<Result, Error> {
     ffmpeg::init().unwrap();
     ffmpeg::log::set_level(ffmpeg::log::Level::Quiet);
+    if crate::file::is_url(&video_file) { ffmpeg::format::network::init(); }
 
     let input = ffmpeg::format::input(&video_file)
         .map_err(|error| Error::ffmpeg(&video_file, error))?;
 
-    let has_audio = input.streams().best(ffmpeg::media::Type::Audio).is_some();
-
     let video_stream = input.streams().best(ffmpeg::media::Type::Video)
         .ok_or_else(|| Error::CannotFindVideoStream(video_file.as_ref().to_path_buf()))?;
 
     let video_stream_parameters = video_stream.parameters();
-    let (width, height) = unsafe { ((*video_stream_parameters.as_ptr()).width, (*video_stream_parameters.as_ptr()).height) };
+    let (width, height, video_bitrate) = unsafe {
+        let params = video_stream_parameters.as_ptr();
+        ((*params).width, (*params).height, (*params).bit_rate)
+    };
     let resolution = Resolution::new(width as u32, height as u32);
+    let video_bitrate = u64::try_from(video_bitrate).ok().filter(|bitrate| *bitrate > 0);
+    let video_codec = codec_name(&video_stream_parameters);
+    let pixel_format = pixel_format_name(&video_stream_parameters);
+
+    let frame_rate = video_stream.rate();
+    let avg_frame_rate = video_stream.avg_frame_rate();
+
+    let frame_count = u64::try_from(video_stream.frames()).unwrap();
+    let video_start_time = stream_start_time(&video_stream);
+    let video_duration = stream_duration(&video_stream);
+
+    let duration = match input.duration() {
+        duration if duration > 0 => duration as f64 / ffmpeg::ffi::AV_TIME_BASE as f64,
+        _ => 0.0,
+    };
+
+    let audio = input.streams().best(ffmpeg::media::Type::Audio).map(|audio_stream| {
+        let audio_stream_parameters = audio_stream.parameters();
+        let (channels, sample_rate, bitrate) = unsafe {
+            let params = audio_stream_parameters.as_ptr();
+            ((*params).channels, (*params).sample_rate, (*params).bit_rate)
+        };
+        AudioStreamInfo {
+            codec: codec_name(&audio_stream_parameters),
+            channels: channels.max(0) as u32,
+            sample_rate: sample_rate.max(0) as u32,
+            bitrate: u64::try_from(bitrate).ok().filter(|bitrate| *bitrate > 0),
+            start_offset: stream_start_time(&audio_stream) - video_start_time,
+            duration: stream_duration(&audio_stream),
+        }
+    });
+    let has_audio = audio.is_some();
+
+    let creation_time = input.metadata().get("creation_time").map(str::to_owned);
+    let encoder = input.metadata().get("encoder").map(str::to_owned);
 
-    let video_codec = unsafe {
-        let av_codec_id = ffmpeg::ffi::avcodec_descriptor_get((*video_stream_parameters.as_ptr()).codec_id);
+    Ok(Result { frame_count, frame_rate, avg_frame_rate, has_audio, resolution, duration, video_bitrate, video_duration, video_codec,
+        pixel_format, audio, creation_time, encoder })
+}
+
+/// start time of the stream in seconds, 0 when unknown
+fn stream_start_time(stream: &ffmpeg::format::stream::Stream) -> f64 {
+    match stream.start_time() {
+        start_time if start_time != ffmpeg::ffi::AV_NOPTS_VALUE => start_time as f64 * f64::from(stream.time_base()),
+        _ => 0.0,
+    }
+}
+
+fn stream_duration(stream: &ffmpeg::format::stream::Stream) -> Option<f64> {
+    Some(stream.duration()).filter(|duration| *duration > 0).map(|duration| duration as f64 * f64::from(stream.time_base()))
+}
+
+/// returns the sorted indices of the key frames of the video stream, reads the whole file
+pub fn keyframe_indices<P: AsRef<Path>>(video_file: P) -> std::result::Result<Vec<u32>, Error> {
+    ffmpeg::init().unwrap();
+    ffmpeg::log::set_level(ffmpeg::log::Level::Quiet);
+
+    let mut input = ffmpeg::format::input(&video_file)
+        .map_err(|error| Error::ffmpeg(&video_file, error))?;
+
+    let video_stream = input.streams().best(ffmpeg::media::Type::Video)
+        .ok_or_else(|| Error::CannotFindVideoStream(video_file.as_ref().to_path_buf()))?;
+    let video_stream_index = video_stream.index();
+    let time_base = f64::from(video_stream.time_base());
+    let frame_rate = f64::from(video_stream.rate());
+    let start_time = video_stream.start_time().max(0);
+
+    let mut keyframe_indices = input.packets()
+        .filter(|(stream, packet)| stream.index() == video_stream_index && packet.is_key())
+        .filter_map(|(_, packet)| packet.pts())
+        .map(|pts| ((pts - start_time).max(0) as f64 * time_base * frame_rate).round() as u32)
+        .collect::<Vec<_>>();
+    keyframe_indices.sort_unstable();
+    keyframe_indices.dedup();
+
+    Ok(keyframe_indices)
+}
+
+/// name of the pixel format of the video stream, read from the decoder opened with the stream parameters
+fn pixel_format_name(parameters: &ffmpeg::codec::Parameters) -> Option<String> {
+    let context = ffmpeg::codec::context::Context::from_parameters(parameters.clone()).ok()?;
+    let decoder = context.decoder().video().ok()?;
+    decoder.format().descriptor().map(|descriptor| descriptor.name().to_owned())
+}
+
+fn codec_name(parameters: &ffmpeg::codec::Parameters) -> Option<String> {
+    unsafe {
+        let av_codec_id = ffmpeg::ffi::avcodec_descriptor_get((*parameters.as_ptr()).codec_id);
         if av_codec_id.is_null() {
             None
         } else {
@@ -64,11 +269,5 @@ pub fn probe<P: AsRef<Path>>(video_file: P) -> std::result::Result<Result, Error
                 name_ptr => Some(String::from_utf8_lossy(CStr::from_ptr(name_ptr).to_bytes()).to_string())
             }
         }
-    };
-
-    let frame_rate = video_stream.rate();
-
-    let frame_count = u64::try_from(video_stream.frames()).unwrap();
-
-    Ok(Result { frame_count, frame_rate, has_audio, resolution, video_codec })
+    }
 }
@@ -40,6 +40,12 @@ impl Region {
         super::coordinates::SignedRange::from(self)
     }
 
+    /// region with its width and height rounded down to even values as required by the encoders of 4:2:0 videos
+    pub fn with_even_dimensions(&self) -> Self {
+        let even = |dimension: super::Dimension| (dimension & !1).max(2);
+        Self::new(self.top_left_corner.clone(), super::Dimensions::new(even(self.dimensions.width), even(self.dimensions.height)))
+    }
+
 }
 
 #[derive(Debug, Error)]
@@ -0,0 +1,187 @@
+
+//! Remuxing of a video into another container without re-encoding, e.g. MP4 to MKV, optionally dropping streams and
+//! editing the container metadata
+
+use std::{path::{Path, PathBuf}, str::FromStr};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+
+use crate::{ffmpeg, file::{self, TouchError}};
+
+use super::{VideoProbingError, container::{Container, ContainerError}, probe};
+
+
+#[derive(Debug, Error, From)]
+pub enum RemuxError {
+    #[error("input video file does not exist")]
+    InputVideoFileDoesNotExist,
+    #[error("output video file exists")]
+    OutputVideoFileExists,
+    #[error("input file and output file are the same file")]
+    InputAndOutputFileIsTheSame,
+    #[error("unknown output container for {}, use a mp4, mkv, mov or webm extension", .0.to_string_lossy())]
+    #[from(ignore)]
+    UnknownOutputContainer(PathBuf),
+    #[error("failed to get input video details")]
+    FailedToGetInputVideoDetails(VideoProbingError),
+    #[error(transparent)]
+    ContainerError(ContainerError),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error(transparent)]
+    WriteToFileError(TouchError),
+}
+
+/// Container metadata entry set on the output video, parsed from `KEY=VALUE`
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[getset(get = "pub")]
+pub struct MetadataEntry {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Error)]
+#[error("invalid metadata entry, expected KEY=VALUE: {0}")]
+pub struct MetadataEntryFormatError(String);
+
+impl FromStr for MetadataEntry {
+    type Err = MetadataEntryFormatError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once('=') {
+            Some((key, entry_value)) if ! key.trim().is_empty() =>
+                Ok(Self { key: key.trim().to_owned(), value: entry_value.to_owned() }),
+            _ => Err(MetadataEntryFormatError(value.to_owned())),
+        }
+    }
+}
+
+/// Copies the streams of a video into another container
+///
+/// The output container is chosen from the extension of the output file. The video stream is always kept, the
+/// audio and subtitle streams are kept unless dropped, the subtitle streams only being kept with the MKV container
+/// since the other containers cannot store the subtitle formats of the inputs without converting them.
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct Remuxer {
+    input_video_file: PathBuf,
+    output_video_file: PathBuf,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    overwrite: bool,
+    /// moves the index of MP4/MOV files to the beginning of the file so that they can be played while downloaded
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    faststart: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    drop_audio: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    drop_subtitles: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    strip_metadata: bool,
+    /// metadata entries set on the output, on top of the copied ones unless stripped
+    metadata: Vec<MetadataEntry>,
+}
+
+impl Remuxer {
+
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: Q) -> Self {
+        Self {
+            input_video_file: input_video_file.as_ref().to_path_buf(),
+            output_video_file: output_video_file.as_ref().to_path_buf(),
+            overwrite: false,
+            faststart: false,
+            drop_audio: false,
+            drop_subtitles: false,
+            strip_metadata: false,
+            metadata: vec![],
+        }
+    }
+
+    pub async fn remux(&self) -> Result<(), RemuxError> {
+        let (input_video_file, output_video_file) = (&self.input_video_file, &self.output_video_file);
+        if ! file::input_exists(input_video_file) { return Err(RemuxError::InputVideoFileDoesNotExist) }
+        if input_video_file == output_video_file { return Err(RemuxError::InputAndOutputFileIsTheSame) }
+        if ! self.overwrite && output_video_file.exists() { return Err(RemuxError::OutputVideoFileExists) }
+
+        let container = Container::from_extension(output_video_file)
+            .ok_or_else(|| RemuxError::UnknownOutputContainer(output_video_file.clone()))?;
+        let video_info = probe(input_video_file)?;
+        if let Some(video_codec) = video_info.video_codec() {
+            container.check_stream_codec(video_codec)?;
+        }
+        let keep_audio = ! self.drop_audio && video_info.has_audio();
+        if keep_audio {
+            if let Some(audio_codec) = video_info.audio().as_ref().and_then(|audio| audio.codec().as_deref()) {
+                container.check_stream_codec(audio_codec)?;
+            }
+        }
+        let keep_subtitles = ! self.drop_subtitles && container == Container::Mkv;
+
+        file::touch(output_video_file)?;
+
+        log::info!("remuxing video to {container}: {} -> {}", input_video_file.to_string_lossy(), output_video_file.to_string_lossy());
+
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command
+            .add_input_file(input_video_file)
+            .add_mapping("0:v")
+            .set_output_video_codec(Some("copy"));
+        if keep_audio {
+            ffmpeg_command.add_mapping("0:a").set_output_audio_codec(Some("copy"));
+        }
+        if keep_subtitles {
+            ffmpeg_command.add_mapping("0:s?").add_args(&["-c:s", "copy"]);
+        }
+        if self.strip_metadata {
+            ffmpeg_command.strip_metadata();
+        } else {
+            ffmpeg_command.copy_metadata_from(0);
+        }
+        for entry in &self.metadata {
+            ffmpeg_command.add_args(&["-metadata", &format!("{}={}", entry.key, entry.value)]);
+        }
+        if self.faststart {
+            if container.uses_movflags() {
+                ffmpeg_command.add_movflag("faststart");
+            } else {
+                log::warn!("faststart ignored, it only applies to the mp4 and mov containers");
+            }
+        }
+        ffmpeg_command
+            .set_output_file(output_video_file)
+            .set_overwrite_output_file(true);
+
+        ffmpeg_command.build().unwrap().spawn_with_progress(video_info.frame_count())?.wait().await?;
+
+        if ! self.strip_metadata && ! file::is_url(input_video_file) {
+            super::preserve_modification_time(input_video_file, output_video_file);
+        }
+        log::info!("video remuxed successfully");
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn metadata_entries_parsed() {
+        let entry = MetadataEntry::from_str(" title = Flight=1").unwrap();
+        assert_eq!((entry.key().as_str(), entry.value().as_str()), ("title", " Flight=1"));
+        assert_eq!(MetadataEntry::from_str("comment=").unwrap().value(), "");
+        assert!(MetadataEntry::from_str("title").is_err());
+        assert!(MetadataEntry::from_str(" =value").is_err());
+    }
+
+}
@@ -0,0 +1,436 @@
+
+//! Recovery of the DVR recordings whose index was never written, e.g. when the goggles lost power while recording
+//!
+//! The MP4 files are written with the frames first (the `mdat` box) and the index of the frames (the `moov` box) last
+//! when the recording stops, so the frames of the interrupted recordings are there but the files cannot be read. The
+//! decoder configuration is taken from a healthy recording made with the same unit and settings, the video NAL units
+//! are extracted from the media data into a raw bitstream which FFMpeg then muxes into a new file with a new index.
+//!
+//! The audio chunks interleaved with the video are skipped, the recovered video has no audio.
+
+use std::{
+    io::{BufReader, BufWriter, Error as IOError, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+};
+
+use derive_more::From;
+use thiserror::Error;
+
+use crate::{ffmpeg, file::{self, TouchError}, temp_dir::{self, InsufficientSpaceError}};
+
+use super::{VideoProbingError, cut::{self, TempFiles}, probe};
+
+
+#[derive(Debug, Error, From)]
+pub enum RepairVideoError {
+    #[error("input video file does not exist")]
+    InputVideoFileDoesNotExist,
+    #[error("reference video file does not exist")]
+    ReferenceVideoFileDoesNotExist,
+    #[error("output video file exists")]
+    OutputVideoFileExists,
+    #[error("input file and output file are the same file")]
+    InputAndOutputFileIsTheSame,
+    #[error("no H.264 or H.265 video track found in the reference video, it needs to be a healthy recording made with the same unit")]
+    NoReferenceDecoderConfig,
+    #[error("no media data found in the input video file")]
+    NoMediaData,
+    #[error("no video frame found in the media data of the input video file, is the reference recorded with the same codec?")]
+    NoFrameFound,
+    #[error("failed to get reference video details")]
+    FailedToGetReferenceVideoDetails(VideoProbingError),
+    #[error(transparent)]
+    FileError(IOError),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error(transparent)]
+    WriteToFileError(TouchError),
+    #[error(transparent)]
+    InsufficientTempSpace(InsufficientSpaceError),
+}
+
+/// NAL units longer than this are considered as random bytes, the largest key frames of the DVR recordings are a few MB
+const MAX_NAL_UNIT_LEN: u64 = 16 * 1024 * 1024;
+
+/// start code preceding the NAL units in the raw bitstreams
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    H264,
+    Hevc,
+}
+
+impl Codec {
+
+    fn ffmpeg_format(&self) -> &'static str {
+        match self {
+            Self::H264 => "h264",
+            Self::Hevc => "hevc",
+        }
+    }
+
+    /// checks the NAL unit header against the NAL unit types used by the encoders, the reserved types being rejected
+    fn is_valid_nal_unit_header(&self, header: &[u8; 3]) -> bool {
+        if header[0] & 0x80 != 0 { return false }
+        match self {
+            Self::H264 => matches!(header[0] & 0x1f, 1..=12),
+            Self::Hevc => {
+                let nal_unit_type = (header[0] >> 1) & 0x3f;
+                let layer_id = ((header[0] & 1) << 5) | (header[1] >> 3);
+                let temporal_id_plus1 = header[1] & 0x07;
+                matches!(nal_unit_type, 0..=9 | 16..=21 | 32..=40) && layer_id == 0 && temporal_id_plus1 != 0
+            },
+        }
+    }
+
+    /// whether the NAL unit is the first slice of a frame: `first_mb_in_slice` is 0 for H.264 and
+    /// `first_slice_segment_in_pic_flag` is set for H.265, both being the first bit after the NAL unit header
+    fn is_first_slice(&self, header: &[u8; 3]) -> bool {
+        match self {
+            Self::H264 => matches!(header[0] & 0x1f, 1..=5) && header[1] & 0x80 != 0,
+            Self::Hevc => (header[0] >> 1) & 0x3f < 32 && header[2] & 0x80 != 0,
+        }
+    }
+
+}
+
+/// Decoder configuration of the video track of the reference recording: the parameter sets written at the beginning
+/// of the raw bitstream and the size of the NAL unit length prefixes of the media data
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DecoderConfig {
+    codec: Codec,
+    nal_length_size: usize,
+    parameter_sets: Vec<Vec<u8>>,
+}
+
+impl DecoderConfig {
+
+    /// parses an `avcC` box content
+    fn parse_avcc(data: &[u8]) -> Option<Self> {
+        let nal_length_size = (*data.get(4)? & 0x03) as usize + 1;
+        let mut parameter_sets = vec![];
+        let mut position = 5;
+        for count_mask in [0x1f, 0xff] {
+            let count = *data.get(position)? & count_mask;
+            position += 1;
+            for _ in 0..count {
+                parameter_sets.push(read_length_prefixed(data, &mut position)?);
+            }
+        }
+        Some(Self { codec: Codec::H264, nal_length_size, parameter_sets })
+    }
+
+    /// parses an `hvcC` box content
+    fn parse_hvcc(data: &[u8]) -> Option<Self> {
+        let nal_length_size = (*data.get(21)? & 0x03) as usize + 1;
+        let mut parameter_sets = vec![];
+        let mut position = 23;
+        for _ in 0..*data.get(22)? {
+            let count = u16::from_be_bytes(data.get(position + 1..position + 3)?.try_into().unwrap());
+            position += 3;
+            for _ in 0..count {
+                parameter_sets.push(read_length_prefixed(data, &mut position)?);
+            }
+        }
+        Some(Self { codec: Codec::Hevc, nal_length_size, parameter_sets })
+    }
+
+}
+
+/// reads a NAL unit prefixed with its 16 bits length, as stored in the decoder configuration boxes
+fn read_length_prefixed(data: &[u8], position: &mut usize) -> Option<Vec<u8>> {
+    let len = u16::from_be_bytes(data.get(*position..*position + 2)?.try_into().unwrap()) as usize;
+    let nal_unit = data.get(*position + 2..*position + 2 + len)?.to_vec();
+    *position += 2 + len;
+    Some(nal_unit)
+}
+
+/// Iterator over the boxes contained in a box content yielding their type and content, stops at the first invalid box
+struct Boxes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Boxes<'a> {
+    type Item = ([u8; 4], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let size = u32::from_be_bytes(self.data.get(0..4)?.try_into().unwrap()) as usize;
+        let box_type: [u8; 4] = self.data.get(4..8)?.try_into().unwrap();
+        let (header_len, size) = match size {
+            0 => (8, self.data.len()),
+            1 => (16, u64::from_be_bytes(self.data.get(8..16)?.try_into().unwrap()) as usize),
+            size => (8, size),
+        };
+        let content = self.data.get(header_len..size)?;
+        self.data = &self.data[size..];
+        Some((box_type, content))
+    }
+}
+
+fn boxes(data: &[u8]) -> Boxes {
+    Boxes { data }
+}
+
+fn child_box<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(data).find(|(child_type, _)| child_type == box_type).map(|(_, content)| content)
+}
+
+/// size of the fields of the visual sample entries preceding their child boxes
+const VISUAL_SAMPLE_ENTRY_FIELDS_LEN: usize = 78;
+
+/// decoder configuration of the first H.264 or H.265 track of the `moov` box content
+fn decoder_config_from_movie_box(moov: &[u8]) -> Option<DecoderConfig> {
+    boxes(moov).filter(|(box_type, _)| box_type == b"trak").find_map(|(_, trak)| {
+        let stbl = [b"mdia", b"minf", b"stbl"].iter().try_fold(trak, |data, box_type| child_box(data, box_type))?;
+        // the sample description box starts with its version, flags and entry count
+        let sample_entries = child_box(stbl, b"stsd")?.get(8..)?;
+        boxes(sample_entries).find_map(|(entry_type, entry)| {
+            let entry_boxes = entry.get(VISUAL_SAMPLE_ENTRY_FIELDS_LEN..)?;
+            match &entry_type {
+                b"avc1" | b"avc3" => DecoderConfig::parse_avcc(child_box(entry_boxes, b"avcC")?),
+                b"hvc1" | b"hev1" => DecoderConfig::parse_hvcc(child_box(entry_boxes, b"hvcC")?),
+                _ => None,
+            }
+        })
+    })
+}
+
+/// position and size of the header and content of the top level boxes of the file, the boxes whose size goes past the
+/// end of the file are truncated to the end of the file
+fn top_level_boxes<R: Read + Seek>(reader: &mut R) -> Result<Vec<([u8; 4], Range<u64>)>, IOError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let mut top_level_boxes = vec![];
+    let mut position = 0;
+    while position + 8 <= file_len {
+        reader.seek(SeekFrom::Start(position))?;
+        let mut header = [0; 16];
+        reader.read_exact(&mut header[..8])?;
+        let box_type: [u8; 4] = header[4..8].try_into().unwrap();
+        let (header_len, size) = match u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64 {
+            0 => (8, file_len - position),
+            1 => {
+                if position + 16 > file_len { break }
+                reader.read_exact(&mut header[8..16])?;
+                (16, u64::from_be_bytes(header[8..16].try_into().unwrap()))
+            },
+            size => (8, size),
+        };
+        if size < header_len { break }
+        let end = (position + size).min(file_len);
+        top_level_boxes.push((box_type, position + header_len..end));
+        position = end;
+    }
+    Ok(top_level_boxes)
+}
+
+fn read_reference_decoder_config(reference_video_file: &Path) -> Result<DecoderConfig, RepairVideoError> {
+    let mut reader = BufReader::new(fs_err::File::open(reference_video_file)?);
+    let moov_range = top_level_boxes(&mut reader)?.into_iter()
+        .find(|(box_type, _)| box_type == b"moov")
+        .map(|(_, range)| range)
+        .ok_or(RepairVideoError::NoReferenceDecoderConfig)?;
+    reader.seek(SeekFrom::Start(moov_range.start))?;
+    let mut moov = vec![0; (moov_range.end - moov_range.start) as usize];
+    reader.read_exact(&mut moov)?;
+    decoder_config_from_movie_box(&moov).ok_or(RepairVideoError::NoReferenceDecoderConfig)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct ExtractionStats {
+    nal_unit_count: u64,
+    frame_count: u64,
+    skipped_bytes: u64,
+}
+
+/// Reader of the length prefixed NAL units of the media data, the reads are buffered and the seeks are relative so
+/// that looking for the next NAL unit byte by byte after an audio chunk stays in the buffer
+struct MediaDataReader<R> {
+    reader: BufReader<R>,
+    position: u64,
+    end: u64,
+    config: DecoderConfig,
+}
+
+impl<R: Read + Seek> MediaDataReader<R> {
+
+    fn seek_to(&mut self, position: u64) -> Result<(), IOError> {
+        self.reader.seek_relative(position as i64 - self.position as i64)?;
+        self.position = position;
+        Ok(())
+    }
+
+    /// header and length of the NAL unit starting at the position if its length and header are plausible
+    fn nal_unit_at(&mut self, position: u64) -> Result<Option<([u8; 3], u64)>, IOError> {
+        let length_size = self.config.nal_length_size;
+        if position + length_size as u64 + 3 > self.end { return Ok(None) }
+        self.seek_to(position)?;
+        let mut bytes = [0; 7];
+        self.reader.read_exact(&mut bytes[..length_size + 3])?;
+        self.position += length_size as u64 + 3;
+        let len = bytes[..length_size].iter().fold(0, |len, byte| (len << 8) | *byte as u64);
+        let header: [u8; 3] = bytes[length_size..length_size + 3].try_into().unwrap();
+        let valid = (3..=MAX_NAL_UNIT_LEN).contains(&len)
+            && position + length_size as u64 + len <= self.end
+            && self.config.codec.is_valid_nal_unit_header(&header);
+        Ok(if valid { Some((header, len)) } else { None })
+    }
+
+    /// writes the parameter sets then the NAL units of the media data as a raw bitstream
+    ///
+    /// When the NAL unit following the previous one is invalid, e.g. because it is an audio chunk, the next NAL unit
+    /// is looked for byte by byte and only accepted if it is followed by another valid NAL unit to avoid taking
+    /// random bytes for a NAL unit.
+    fn extract_to<W: Write>(mut self, output: &mut W) -> Result<ExtractionStats, IOError> {
+        for parameter_set in &self.config.parameter_sets {
+            output.write_all(&START_CODE)?;
+            output.write_all(parameter_set)?;
+        }
+        let length_size = self.config.nal_length_size as u64;
+        let mut stats = ExtractionStats::default();
+        let mut position = self.position;
+        let mut in_sync = true;
+        while position < self.end {
+            let nal_unit = match self.nal_unit_at(position)? {
+                Some((header, len)) if in_sync => Some((header, len)),
+                Some((header, len)) => {
+                    let next_position = position + length_size + len;
+                    let followed_by_nal_unit = next_position == self.end || self.nal_unit_at(next_position)?.is_some();
+                    if followed_by_nal_unit { Some((header, len)) } else { None }
+                },
+                None => None,
+            };
+            match nal_unit {
+                Some((header, len)) => {
+                    self.seek_to(position + length_size)?;
+                    output.write_all(&START_CODE)?;
+                    let copied = std::io::copy(&mut (&mut self.reader).take(len), output)?;
+                    self.position += copied;
+                    stats.nal_unit_count += 1;
+                    if self.config.codec.is_first_slice(&header) { stats.frame_count += 1 }
+                    position += length_size + len;
+                    in_sync = true;
+                },
+                None => {
+                    stats.skipped_bytes += 1;
+                    position += 1;
+                    in_sync = false;
+                },
+            }
+        }
+        Ok(stats)
+    }
+
+}
+
+/// range of the content of the `mdat` box, up to the end of the file when it is truncated
+fn media_data_range(video_file: &Path) -> Result<Range<u64>, RepairVideoError> {
+    let mut reader = BufReader::new(fs_err::File::open(video_file)?);
+    top_level_boxes(&mut reader)?.into_iter()
+        .find(|(box_type, _)| box_type == b"mdat")
+        .map(|(_, range)| range)
+        .ok_or(RepairVideoError::NoMediaData)
+}
+
+/// rebuilds a playable video from the media data of a recording without index, see the module documentation
+pub async fn repair_video<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(input_video_file: P, reference_video_file: Q,
+        output_video_file: R, overwrite: bool) -> Result<(), RepairVideoError> {
+
+    let (input_video_file, reference_video_file, output_video_file) =
+        (input_video_file.as_ref(), reference_video_file.as_ref(), output_video_file.as_ref());
+    if ! input_video_file.exists() { return Err(RepairVideoError::InputVideoFileDoesNotExist) }
+    if ! reference_video_file.exists() { return Err(RepairVideoError::ReferenceVideoFileDoesNotExist) }
+    if input_video_file == output_video_file { return Err(RepairVideoError::InputAndOutputFileIsTheSame) }
+    if ! overwrite && output_video_file.exists() { return Err(RepairVideoError::OutputVideoFileExists) }
+
+    let config = read_reference_decoder_config(reference_video_file)?;
+    let frame_rate = probe(reference_video_file)?.frame_rate();
+    let media_data_range = media_data_range(input_video_file)?;
+    // the extracted video stream is about the size of the media data
+    temp_dir::check_available_space(output_video_file, media_data_range.end - media_data_range.start)?;
+
+    log::info!("repairing video: {} -> {}", input_video_file.to_string_lossy(), output_video_file.to_string_lossy());
+
+    let codec = config.codec;
+    let temp_files = TempFiles(vec![cut::temp_file_path(output_video_file, &format!(".{}", codec.ffmpeg_format()))]);
+    let stream_file = &temp_files.0[0];
+    let mut frame_count = 0;
+    if ! crate::process::dry_run() {
+        let mut reader = BufReader::with_capacity(4 * 1024 * 1024, fs_err::File::open(input_video_file)?);
+        reader.seek(SeekFrom::Start(media_data_range.start))?;
+        let media_data_reader = MediaDataReader { reader, position: media_data_range.start, end: media_data_range.end, config };
+        let mut output = BufWriter::new(fs_err::File::create(stream_file)?);
+        let stats = media_data_reader.extract_to(&mut output)?;
+        output.flush()?;
+        if stats.frame_count == 0 { return Err(RepairVideoError::NoFrameFound) }
+        log::info!("{} frames found in {} NAL units, {} bytes of audio or corrupted data skipped", stats.frame_count,
+            stats.nal_unit_count, stats.skipped_bytes);
+        frame_count = stats.frame_count;
+    }
+
+    file::touch(output_video_file)?;
+
+    let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+    ffmpeg_command
+        .add_elementary_stream_input(stream_file, codec.ffmpeg_format(), frame_rate)
+        .add_mapping("0:v")
+        .set_output_video_codec(Some("copy"));
+    if codec == Codec::Hevc {
+        // tag playable by the Apple players
+        ffmpeg_command.add_args(&["-tag:v", "hvc1"]);
+    }
+    ffmpeg_command
+        .set_output_file(output_video_file)
+        .set_overwrite_output_file(true);
+    ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
+
+    log::info!("video repaired successfully: {frame_count} frames recovered");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn h264_config() -> DecoderConfig {
+        DecoderConfig { codec: Codec::H264, nal_length_size: 4, parameter_sets: vec![vec![0x67, 0x64, 0x00], vec![0x68, 0xee]] }
+    }
+
+    fn length_prefixed(nal_unit: &[u8]) -> Vec<u8> {
+        [&(nal_unit.len() as u32).to_be_bytes()[..], nal_unit].concat()
+    }
+
+    #[test]
+    fn parse_avcc() {
+        let avcc = [0x01, 0x64, 0x00, 0x28, 0xff, 0xe1, 0x00, 0x03, 0x67, 0x64, 0x00, 0x01, 0x00, 0x02, 0x68, 0xee];
+        assert_eq!(DecoderConfig::parse_avcc(&avcc), Some(h264_config()));
+    }
+
+    #[test]
+    fn extract_skips_interleaved_data() {
+        let idr_slice = [0x65, 0x88, 0x84, 0x00, 0x33];
+        let slice = [0x41, 0x9a, 0x02, 0x10];
+        let audio_chunk = [0xde, 0x02, 0x00, 0x4c, 0x61, 0x76, 0x63, 0x35, 0x38];
+        let media_data = [length_prefixed(&idr_slice), audio_chunk.to_vec(), length_prefixed(&slice), length_prefixed(&slice),
+            length_prefixed(&slice)[..6].to_vec()].concat();
+        let media_data_reader = MediaDataReader {
+            reader: BufReader::new(Cursor::new(media_data.clone())),
+            position: 0,
+            end: media_data.len() as u64,
+            config: h264_config(),
+        };
+        let mut output = vec![];
+        let stats = media_data_reader.extract_to(&mut output).unwrap();
+        assert_eq!(stats, ExtractionStats { nal_unit_count: 3, frame_count: 3, skipped_bytes: (audio_chunk.len() + 6) as u64 });
+        let expected = [&START_CODE[..], &[0x67, 0x64, 0x00], &START_CODE, &[0x68, 0xee], &START_CODE, &idr_slice, &START_CODE, &slice,
+            &START_CODE, &slice].concat();
+        assert_eq!(output, expected);
+    }
+
+}
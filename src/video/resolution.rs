@@ -17,6 +17,9 @@ pub enum StandardResolution {
     Tr720p4By3,
     Tr1080p,
     Tr1080p4by3,
+    Tr1440p,
+    Tr2_7k,
+    Tr4k,
 }
 
 impl Display for StandardResolution {
@@ -27,6 +30,9 @@ impl Display for StandardResolution {
              Tr720p4By3 => "720p4:3",
              Tr1080p => "1080p",
              Tr1080p4by3 => "1080p4:3",
+             Tr1440p => "1440p",
+             Tr2_7k => "2.7k",
+             Tr4k => "4k",
         };
         f.write_str(value_str)
     }
@@ -46,6 +52,9 @@ impl StandardResolution {
             Tr720p4By3 => Resolution::new(960, 720),
             Tr1080p => Resolution::new(1920, 1080),
             Tr1080p4by3 => Resolution::new(1440, 1080),
+            Tr1440p => Resolution::new(2560, 1440),
+            Tr2_7k => Resolution::new(2704, 1520),
+            Tr4k => Resolution::new(3840, 2160),
         }
     }
 }
@@ -86,9 +95,12 @@ impl FromStr for TargetResolution {
         use TargetResolution::*;
         let resolution = match value {
             "720p" => Standard(StandardResolution::Tr720p),
-            "720p4:3" => Standard(StandardResolution::Tr1080p4by3),
+            "720p4:3" => Standard(StandardResolution::Tr720p4By3),
             "1080p" => Standard(StandardResolution::Tr1080p),
             "1080p4:3" => Standard(StandardResolution::Tr1080p4by3),
+            "1440p" => Standard(StandardResolution::Tr1440p),
+            "2.7k" | "2.7K" => Standard(StandardResolution::Tr2_7k),
+            "4k" | "4K" | "2160p" => Standard(StandardResolution::Tr4k),
             custom_res_str => {
                 lazy_static! {
                     static ref RES_RE: Regex = Regex::new(r"\A(?P<width>\d{1,5})x(?P<height>\d{1,5})\z").unwrap();
@@ -117,6 +129,64 @@ impl From<Resolution> for TargetResolution {
     }
 }
 
+/// Output video scaling, either to a standard height keeping the aspect ratio or to an exact resolution
+#[derive(Debug, Clone, Copy)]
+pub enum OutputScale {
+    Height(u32),
+    Exact(Resolution),
+}
+
+impl OutputScale {
+
+    pub fn valid_list() -> Vec<String> {
+        ["720p", "1080p", "1440p", "4k", "<width>x<height>"].into_iter().map(str::to_owned).collect()
+    }
+
+    /// resolution of the video scaled from `input_resolution`, widths are rounded to an even value as required by most encoders
+    pub fn output_resolution(&self, input_resolution: Resolution) -> Resolution {
+        match self {
+            OutputScale::Height(height) => {
+                let width = (input_resolution.width as f64 * *height as f64 / input_resolution.height as f64 / 2.0).round() as u32 * 2;
+                Resolution::new(width, *height)
+            },
+            OutputScale::Exact(resolution) => *resolution,
+        }
+    }
+
+}
+
+#[derive(Debug, Error)]
+#[error("invalid output scale `{given}`, valid values are: {valid}")]
+pub struct InvalidOutputScaleError {
+    given: String,
+    valid: String
+}
+
+impl FromStr for OutputScale {
+    type Err = InvalidOutputScaleError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        use OutputScale::*;
+        Ok(match value {
+            "720p" => Height(720),
+            "1080p" => Height(1080),
+            "1440p" => Height(1440),
+            "4k" | "4K" | "2160p" => Height(2160),
+            custom_res_str => {
+                lazy_static! {
+                    static ref RES_RE: Regex = Regex::new(r"\A(?P<width>\d{1,5})x(?P<height>\d{1,5})\z").unwrap();
+                }
+                let invalid_error = || InvalidOutputScaleError { given: custom_res_str.to_owned(), valid: Self::valid_list().join(", ") };
+                let captures = RES_RE.captures(custom_res_str).ok_or_else(invalid_error)?;
+                let width: u32 = captures.name("width").unwrap().as_str().parse().unwrap();
+                let height: u32 = captures.name("height").unwrap().as_str().parse().unwrap();
+                if width == 0 || height == 0 { return Err(invalid_error()); }
+                Exact(Resolution::new(width, height))
+            }
+        })
+    }
+}
+
 pub(crate) fn dimensions_diff(d1: Resolution, d2: Resolution) -> (i32, i32) {
     (d1.width as i32 - d2.width as i32, d1.height as i32 - d2.height as i32)
 }
@@ -0,0 +1,257 @@
+
+//! Telemetry recorded by the DJI O3 and Walksnail goggles into `.srt` files alongside the video, e.g.
+//! `signal:4 ch:3 flightTime:12 uavBat:16.2V delay:26ms bitrate:40.1Mbps`, drawn onto the video as a line of text
+
+use std::io::Error as IOError;
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use getset::{CopyGetters, Getters, Setters};
+use lazy_static::lazy_static;
+use regex::Regex;
+use thiserror::Error;
+
+use crate::ffmpeg;
+
+use super::{Timestamp, cut::{TempFiles, temp_file_path}};
+
+
+pub const DEFAULT_FONT_SIZE: u16 = 12;
+
+#[derive(Debug, Error, From)]
+pub enum SrtTelemetryError {
+    #[error("failed accessing SRT telemetry file: {0}")]
+    IOError(IOError),
+    #[error("invalid SRT telemetry file, line {line}: {reason}")]
+    #[from(ignore)]
+    InvalidFile { line: usize, reason: String },
+    #[error("requested SRT telemetry but no SRT file associated to the video was found")]
+    SrtFileNotFound,
+}
+
+/// position of the telemetry line on the video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtTelemetryPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl SrtTelemetryPosition {
+
+    /// ASS style alignment, numbered like the keys of a numeric keypad
+    fn ass_alignment(&self) -> u8 {
+        use SrtTelemetryPosition::*;
+        match self {
+            TopLeft => 7,
+            Top => 8,
+            TopRight => 9,
+            BottomLeft => 1,
+            Bottom => 2,
+            BottomRight => 3,
+        }
+    }
+
+}
+
+/// SRT cue with its start and end in milliseconds
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct Cue {
+    #[getset(get_copy = "pub")]
+    start: u64,
+    #[getset(get_copy = "pub")]
+    end: u64,
+    #[getset(get = "pub")]
+    text: String,
+}
+
+impl Cue {
+
+    /// value of the telemetry item, the key case is ignored
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.text.split_whitespace().find_map(|token| {
+            let (token_key, value) = token.split_once(':')?;
+            if token_key.eq_ignore_ascii_case(key) { Some(value) } else { None }
+        })
+    }
+
+    /// text of the cue formatted with the template whose `{key}` placeholders are replaced with the values of the
+    /// telemetry items, placeholders of missing items are kept as is
+    pub fn formatted(&self, format: Option<&str>) -> String {
+        lazy_static! {
+            static ref PLACEHOLDER_RE: Regex = Regex::new(r"\{([^{}]+)\}").unwrap();
+        }
+        match format {
+            Some(format) => PLACEHOLDER_RE.replace_all(format, |captures: &regex::Captures| {
+                self.value(&captures[1]).unwrap_or(&captures[0]).to_owned()
+            }).into_owned(),
+            None => self.text.clone(),
+        }
+    }
+
+}
+
+fn parse_srt_timestamp(timestamp: &str) -> Option<u64> {
+    let (hms, milliseconds) = timestamp.trim().split_once(|c| c == ',' || c == '.')?;
+    let mut total_seconds = 0;
+    for part in hms.split(':') {
+        total_seconds = total_seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(total_seconds * 1000 + milliseconds.parse::<u64>().ok()?)
+}
+
+fn format_srt_timestamp(milliseconds: u64) -> String {
+    let seconds = milliseconds / 1000;
+    format!("{:02}:{:02}:{:02},{:03}", seconds / 3600, seconds / 60 % 60, seconds % 60, milliseconds % 1000)
+}
+
+/// parses the SRT content, the HTML tags the DJI goggles surround the telemetry with are removed
+pub fn parse(content: &str) -> Result<Vec<Cue>, SrtTelemetryError> {
+    lazy_static! {
+        static ref TAG_RE: Regex = Regex::new(r"<[^>]*>").unwrap();
+    }
+    let mut cues = vec![];
+    let mut lines = content.trim_start_matches('\u{feff}').lines().enumerate().peekable();
+    while let Some((line_index, line)) = lines.next() {
+        if line.trim().is_empty() { continue }
+        // the cue index line is optional, some tools omit it
+        let (line_index, timing_line) = if line.contains("-->") {
+            (line_index, line)
+        } else {
+            match lines.next() {
+                Some(next_line) => next_line,
+                None => break,
+            }
+        };
+        let invalid_timing = || SrtTelemetryError::InvalidFile { line: line_index + 1, reason: format!("invalid cue timing: {timing_line}") };
+        let (start, end) = timing_line.split_once("-->").ok_or_else(invalid_timing)?;
+        let start = parse_srt_timestamp(start).ok_or_else(invalid_timing)?;
+        let end = parse_srt_timestamp(end).ok_or_else(invalid_timing)?;
+        let mut text_lines = vec![];
+        while let Some((_, text_line)) = lines.next_if(|(_, text_line)| ! text_line.trim().is_empty()) {
+            text_lines.push(TAG_RE.replace_all(text_line.trim(), "").into_owned());
+        }
+        cues.push(Cue { start, end, text: text_lines.join(" ") });
+    }
+    Ok(cues)
+}
+
+pub fn read<P: AsRef<Path>>(srt_file: P) -> Result<Vec<Cue>, SrtTelemetryError> {
+    parse(&fs_err::read_to_string(srt_file.as_ref())?)
+}
+
+/// the SRT file next to the video file with the same name, the extension case is ignored
+pub fn find_associated_to_video_file<P: AsRef<Path>>(video_file_path: P) -> Option<PathBuf> {
+    let video_file_path = crate::file::local_path(video_file_path);
+    for extension in ["srt", "SRT"] {
+        let srt_file_path = video_file_path.with_extension(extension);
+        if srt_file_path.is_file() {
+            log::info!("found SRT telemetry file: {}", srt_file_path.to_string_lossy());
+            return Some(srt_file_path);
+        }
+    }
+    log::info!("SRT telemetry file not found: {}", video_file_path.with_extension("srt").to_string_lossy());
+    None
+}
+
+/// Settings used to draw the SRT telemetry onto the video
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct SrtTelemetrySettings {
+    srt_file: PathBuf,
+    /// template with `{key}` placeholders, e.g. `{bitrate} {delay}`, `None` to draw the telemetry as recorded
+    format: Option<String>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    position: SrtTelemetryPosition,
+    /// font size relative to a 288 lines high video as with all the SRT subtitles rendered by FFMpeg
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    font_size: u16,
+}
+
+impl SrtTelemetrySettings {
+
+    pub fn new<P: AsRef<Path>>(srt_file: P) -> Self {
+        Self {
+            srt_file: srt_file.as_ref().to_path_buf(),
+            format: None,
+            position: SrtTelemetryPosition::Bottom,
+            font_size: DEFAULT_FONT_SIZE,
+        }
+    }
+
+    /// SRT content of the formatted telemetry shifted to start with the video at the start timestamp, consecutive
+    /// cues with the same formatted text are merged
+    fn formatted_srt(&self, start: Option<Timestamp>) -> Result<String, SrtTelemetryError> {
+        let shift = start.map(|start| start.total_milliseconds()).unwrap_or(0);
+        let mut cues: Vec<Cue> = vec![];
+        for cue in read(&self.srt_file)? {
+            if cue.end <= shift { continue }
+            let text = cue.formatted(self.format.as_deref());
+            let (start, end) = (cue.start.saturating_sub(shift), cue.end - shift);
+            match cues.last_mut() {
+                Some(last_cue) if last_cue.text == text && last_cue.end >= start => last_cue.end = end,
+                _ => cues.push(Cue { start, end, text }),
+            }
+        }
+        Ok(cues.iter().enumerate().map(|(index, cue)|
+            format!("{}\n{} --> {}\n{}\n\n", index + 1, format_srt_timestamp(cue.start), format_srt_timestamp(cue.end), cue.text)
+        ).collect())
+    }
+
+    /// Writes the formatted telemetry into a temporary file next to the output file and returns the FFMpeg filter
+    /// drawing it along with the temporary file to remove once transcoded
+    pub(super) fn ffmpeg_filter(&self, output_video_file: &Path, start: Option<Timestamp>) -> Result<(String, TempFiles), SrtTelemetryError> {
+        let formatted_srt = self.formatted_srt(start)?;
+        let formatted_srt_file = temp_file_path(output_video_file, ".telemetry.srt");
+        let temp_files = TempFiles(vec![formatted_srt_file.clone()]);
+        if ! crate::process::dry_run() {
+            fs_err::write(&formatted_srt_file, formatted_srt)?;
+        }
+        let style = format!("Alignment={},FontSize={},PrimaryColour=&H00FFFFFF,OutlineColour=&H00000000,BorderStyle=1,Outline=1,Shadow=0,MarginL=10,MarginR=10,MarginV=10",
+            self.position.ass_alignment(), self.font_size);
+        let filter = format!("subtitles=filename={}:force_style={}",
+            ffmpeg::escape_filter_option_value(&formatted_srt_file.to_string_lossy()), ffmpeg::escape_filter_option_value(&style));
+        Ok((filter, temp_files))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn srt_timestamps() {
+        assert_eq!(parse_srt_timestamp("01:02:03,045"), Some(3_723_045));
+        assert_eq!(parse_srt_timestamp(" 00:00:01.500 "), Some(1_500));
+        assert_eq!(parse_srt_timestamp("00:00:01"), None);
+        assert_eq!(format_srt_timestamp(3_723_045), "01:02:03,045");
+    }
+
+    #[test]
+    fn cues_parsed_with_optional_index_lines_and_tags_removed() {
+        let content = "\u{feff}1\n00:00:00,000 --> 00:00:00,033\n<font size=\"28\">signal:4 ch:3 delay:26ms\n\
+            bitrate:40.1Mbps</font>\n\n00:00:00,033 --> 00:00:00,066\nsignal:3\n";
+        let cues = parse(content).unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!((cues[0].start(), cues[0].end()), (0, 33));
+        assert_eq!(cues[0].text(), "signal:4 ch:3 delay:26ms bitrate:40.1Mbps");
+        assert_eq!(cues[1].value("Signal"), Some("3"));
+
+        assert!(matches!(parse("1\n00:00:00 --> 00:00:01,000\n"), Err(SrtTelemetryError::InvalidFile { line: 2, .. })));
+    }
+
+    #[test]
+    fn cue_formatted_with_placeholders() {
+        let cue = Cue { start: 0, end: 33, text: "signal:4 delay:26ms bitrate:40.1Mbps".to_owned() };
+        assert_eq!(cue.formatted(Some("{bitrate} / {delay} {missing}")), "40.1Mbps / 26ms {missing}");
+        assert_eq!(cue.formatted(None), cue.text().as_str());
+    }
+
+}
@@ -0,0 +1,149 @@
+
+use std::path::{Path, PathBuf};
+
+use getset::{CopyGetters, Getters, Setters};
+
+use crate::ffmpeg;
+use crate::file;
+use crate::osd;
+
+use super::{
+    Resolution,
+    TranscodeVideoError,
+    probe,
+    transcoder::OSDSettings,
+};
+
+
+pub const DEFAULT_STREAM_VIDEO_ENCODER: &str = "libx264";
+pub const DEFAULT_STREAM_VIDEO_BITRATE: &str = "6M";
+pub const DEFAULT_STREAM_AUDIO_BITRATE: &str = "128k";
+
+/// interval in seconds between the key frames so that the viewers can join the stream quickly
+const KEY_FRAME_INTERVAL: u32 = 2;
+
+/// container format used to push the stream, depends on the scheme of the URL
+fn output_format(url: &str) -> Option<&'static str> {
+    let (scheme, _) = url.split_once("://")?;
+    match scheme.to_ascii_lowercase().as_str() {
+        "rtmp" | "rtmps" => Some("flv"),
+        "srt" | "udp" => Some("mpegts"),
+        _ => None,
+    }
+}
+
+/// Pushes a live input to a RTMP/SRT/UDP endpoint, optionally compositing the OSD in real time
+///
+/// The input is either a capture device read with an FFMpeg input format, e.g. `v4l2`, or a video file read at
+/// its native frame rate which can be followed while it is still being recorded.
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct LiveStreamer {
+    input: PathBuf,
+    output_url: String,
+    /// FFMpeg input format of the capture device, `None` when the input is a file
+    input_format: Option<String>,
+    /// resolution of the capture device, needed to render the OSD since capture devices cannot be probed
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    capture_resolution: Option<Resolution>,
+    /// keep reading the input file while it grows
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    follow: bool,
+    video_encoder: String,
+    video_bitrate: String,
+    audio_bitrate: String,
+    osd: Option<OSDSettings>,
+}
+
+impl LiveStreamer {
+
+    pub fn new<P: AsRef<Path>>(input: P, output_url: &str) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            output_url: output_url.to_owned(),
+            input_format: None,
+            capture_resolution: None,
+            follow: false,
+            video_encoder: DEFAULT_STREAM_VIDEO_ENCODER.to_owned(),
+            video_bitrate: DEFAULT_STREAM_VIDEO_BITRATE.to_owned(),
+            audio_bitrate: DEFAULT_STREAM_AUDIO_BITRATE.to_owned(),
+            osd: None,
+        }
+    }
+
+    fn is_capture_device(&self) -> bool {
+        self.input_format.is_some()
+    }
+
+    pub async fn stream(&self) -> Result<(), TranscodeVideoError> {
+        let output_format = output_format(&self.output_url)
+            .ok_or_else(|| TranscodeVideoError::UnsupportedStreamURL(self.output_url.clone()))?;
+
+        // capture devices cannot be probed, their audio is not streamed
+        let (video_info, resolution) = if self.is_capture_device() {
+            (None, self.capture_resolution)
+        } else {
+            if ! file::input_exists(&self.input) { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
+            let video_info = probe(&self.input)?;
+            let resolution = video_info.resolution();
+            (Some(video_info), Some(resolution))
+        };
+
+        log::info!("streaming: {} -> {}", self.input.to_string_lossy(), self.output_url);
+
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command.add_live_input(&self.input, self.input_format.as_deref(), self.capture_resolution, self.follow);
+
+        let osd_frames_iter = match &self.osd {
+            Some(osd_settings) => {
+                let resolution = resolution.ok_or(TranscodeVideoError::CaptureResolutionRequiredForOSD)?;
+                let osd_frame_shift = match &video_info {
+                    Some(video_info) => osd_settings.frame_shift_for_video(video_info),
+                    None => osd_settings.frame_shift().map(|frame_shift| frame_shift.osd_frames()).unwrap_or(0),
+                };
+                let osd_frames_generator = osd_settings.overlay_generator(resolution)?;
+                ffmpeg_command
+                    .add_stdin_input(osd_frames_generator.frame_dimensions(), osd::FRAME_RATE as u16).unwrap()
+                    .add_complex_filter("[0:v][1]overlay=eof_action=repeat:x=(W-w)/2:y=(H-h)/2,format=yuv420p[vo]")
+                    .add_mapping("[vo]");
+                Some(osd_frames_generator.iter_advanced(0, None, osd_frame_shift))
+            },
+            None => {
+                ffmpeg_command.add_mapping("0:v").add_args(&["-pix_fmt", "yuv420p"]);
+                None
+            },
+        };
+
+        if matches!(&video_info, Some(video_info) if video_info.has_audio()) {
+            ffmpeg_command.add_mapping("0:a").set_output_audio_settings(Some("aac"), Some(&self.audio_bitrate));
+        }
+
+        ffmpeg_command.set_output_video_settings(Some(&self.video_encoder), Some(&self.video_bitrate), None);
+        if self.video_encoder == "libx264" {
+            ffmpeg_command.set_output_video_preset(Some("veryfast")).set_output_video_tune(Some("zerolatency"));
+        }
+        let force_key_frames = format!("expr:gte(t,n_forced*{KEY_FRAME_INTERVAL})");
+        ffmpeg_command
+            .add_args(&["-maxrate", &self.video_bitrate, "-bufsize", &self.video_bitrate, "-force_key_frames", &force_key_frames])
+            .add_args(&["-f", output_format])
+            .set_output_file(&self.output_url);
+
+        // the length of live inputs is unknown so the progress can only be displayed for complete files
+        let ffmpeg_command = ffmpeg_command.build().unwrap();
+        let mut ffmpeg_process = match &video_info {
+            Some(video_info) if ! self.follow => ffmpeg_command.spawn_with_progress(video_info.frame_count())?,
+            _ => ffmpeg_command.spawn_no_output()?,
+        };
+
+        match osd_frames_iter {
+            Some(osd_frames_iter) => osd_frames_iter.send_frames_to_ffmpeg_in_real_time_and_wait(ffmpeg_process).await?,
+            None => { ffmpeg_process.wait().await?; },
+        }
+
+        log::info!("stream ended");
+        Ok(())
+    }
+
+}
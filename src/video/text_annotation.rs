@@ -0,0 +1,153 @@
+
+//! Text annotations like the pilot name or a title drawn onto the video in the same pass as the OSD
+
+use std::str::FromStr;
+
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+
+use crate::ffmpeg;
+use crate::osd::overlay::style::Color;
+
+use super::{Timestamp, TimestampRange};
+
+
+#[derive(Debug, Error)]
+#[error("invalid text annotation: {0}, expected <text>[|position=<position>][|size=<px>][|color=<color[@alpha]>][|time=[START]..[END]]")]
+pub struct InvalidTextAnnotationError(String);
+
+/// position of a text annotation on the video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum TextPosition {
+    TopLeft,
+    Top,
+    TopRight,
+    Center,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl TextPosition {
+
+    /// drawtext filter coordinates of the text, the margin to the video edges is 1/30 of the video height
+    pub(super) fn drawtext_coordinates(&self) -> (&'static str, &'static str) {
+        use TextPosition::*;
+        match self {
+            TopLeft => ("h/30", "h/30"),
+            Top => ("(w-text_w)/2", "h/30"),
+            TopRight => ("w-text_w-h/30", "h/30"),
+            Center => ("(w-text_w)/2", "(h-text_h)/2"),
+            BottomLeft => ("h/30", "h-text_h-h/30"),
+            Bottom => ("(w-text_w)/2", "h-text_h-h/30"),
+            BottomRight => ("w-text_w-h/30", "h-text_h-h/30"),
+        }
+    }
+
+}
+
+/// Text drawn onto the video, parsed from `<text>[|<key>=<value>]...`, e.g. `PILOT: foo|position=top-left|time=..0:10`
+///
+/// The time range is relative to the input video, the text is drawn during the whole video when not set.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters, Setters)]
+pub struct TextAnnotation {
+    #[getset(get = "pub")]
+    text: String,
+    #[getset(get_copy = "pub", set = "pub")]
+    position: TextPosition,
+    /// font size in pixels, 1/20 of the video height when not set
+    #[getset(get_copy = "pub", set = "pub")]
+    font_size: Option<u32>,
+    #[getset(get_copy = "pub")]
+    color: Color,
+    #[getset(get_copy = "pub")]
+    time_range: Option<TimestampRange>,
+}
+
+impl TextAnnotation {
+
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Self {
+            text: text.into(),
+            position: TextPosition::BottomLeft,
+            font_size: None,
+            color: "white".parse().unwrap(),
+            time_range: None,
+        }
+    }
+
+    /// FFMpeg drawtext filter drawing the annotation onto a video starting at the `start` timestamp of the input video
+    pub fn ffmpeg_filter(&self, start: Option<Timestamp>) -> String {
+        let (x, y) = self.position.drawtext_coordinates();
+        let color = self.color.rgba();
+        let font_size = self.font_size.map(|font_size| font_size.to_string()).unwrap_or_else(|| "h/20".to_owned());
+        let mut filter = format!("drawtext=expansion=none:text={}:fontsize={font_size}:fontcolor=0x{:02x}{:02x}{:02x}@{}:borderw=2:bordercolor=black:x={x}:y={y}",
+            ffmpeg::escape_filter_option_value(&self.text), color.0[0], color.0[1], color.0[2], color.0[3] as f64 / 255.0);
+        if let Some(time_range) = self.time_range {
+            let shift = start.map(|start| start.total_milliseconds()).unwrap_or(0) as f64 / 1000.0;
+            let seconds = |timestamp: Timestamp| timestamp.total_milliseconds() as f64 / 1000.0 - shift;
+            let condition = match (time_range.start(), time_range.end()) {
+                (Some(range_start), Some(range_end)) => format!("between(t,{},{})", seconds(range_start), seconds(range_end)),
+                (Some(range_start), None) => format!("gte(t,{})", seconds(range_start)),
+                (None, Some(range_end)) => format!("lte(t,{})", seconds(range_end)),
+                (None, None) => "1".to_owned(),
+            };
+            filter.push_str(&format!(":enable={}", ffmpeg::escape_filter_option_value(&condition)));
+        }
+        filter
+    }
+
+}
+
+impl FromStr for TextAnnotation {
+    type Err = InvalidTextAnnotationError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidTextAnnotationError(value.to_owned());
+        let mut parts = value.split('|');
+        let text = parts.next().filter(|text| ! text.is_empty()).ok_or_else(error)?;
+        let mut annotation = Self::new(text);
+        for part in parts {
+            let (key, option_value) = part.split_once('=').ok_or_else(error)?;
+            match key.trim() {
+                "position" => annotation.position = TextPosition::from_str(option_value).map_err(|_| error())?,
+                "size" => annotation.font_size = Some(option_value.parse().map_err(|_| error())?),
+                "color" => annotation.color = option_value.parse().map_err(|_| error())?,
+                "time" => annotation.time_range = Some(option_value.parse().map_err(|_| error())?),
+                _ => return Err(error()),
+            }
+        }
+        Ok(annotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn annotations_parsed_with_options() {
+        let annotation = TextAnnotation::from_str("PILOT: foo|position=top|size=30|color=red|time=5..10").unwrap();
+        assert_eq!(annotation.text(), "PILOT: foo");
+        assert_eq!(annotation.position(), TextPosition::Top);
+        assert_eq!(annotation.font_size(), Some(30));
+        assert_eq!(annotation.time_range(), Some("5..10".parse().unwrap()));
+        assert_eq!(TextAnnotation::from_str("title").unwrap(), TextAnnotation::new("title"));
+        for invalid in ["", "|size=30", "title|size", "title|size=big", "title|weight=bold", "title|time=10..5"] {
+            assert!(TextAnnotation::from_str(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn drawtext_filter_enabled_relative_to_the_start() {
+        let annotation = TextAnnotation::from_str("PILOT: foo|position=top|size=30|color=red|time=5..10").unwrap();
+        assert_eq!(annotation.ffmpeg_filter(Some("2".parse().unwrap())),
+            "drawtext=expansion=none:text=PILOT\\\\: foo:fontsize=30:fontcolor=0xff0000@1:borderw=2:bordercolor=black:\
+            x=(w-text_w)/2:y=h/30:enable=between(t\\,3\\,8)");
+        assert_eq!(TextAnnotation::new("title").ffmpeg_filter(None),
+            "drawtext=expansion=none:text=title:fontsize=h/20:fontcolor=0xffffff@1:borderw=2:bordercolor=black:x=h/30:y=h-text_h-h/30");
+    }
+
+}
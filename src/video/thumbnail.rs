@@ -0,0 +1,147 @@
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use getset::{CopyGetters, Getters, Setters};
+
+use crate::ffmpeg;
+use crate::file;
+use crate::osd::{self, overlay::interpolation::osd_frame_index};
+
+use super::{
+    Timestamp,
+    TranscodeVideoError,
+    probe,
+    resolution::OutputScale,
+    transcoder::OSDSettings,
+};
+
+
+/// Generates a thumbnail image from a video frame, optionally with the OSD burnt onto it and a title
+///
+/// The image format is chosen by ffmpeg from the output file extension, JPEG files are written with a high quality.
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct ThumbnailGenerator {
+    video_file: PathBuf,
+    output_file: PathBuf,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    overwrite: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    timestamp: Timestamp,
+    title: Option<String>,
+    /// font file used to draw the title, the default ffmpeg font is used when not set
+    title_font_file: Option<PathBuf>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    scale: Option<OutputScale>,
+    osd: Option<OSDSettings>,
+}
+
+impl ThumbnailGenerator {
+
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(video_file: P, output_file: Q) -> Self {
+        Self {
+            video_file: video_file.as_ref().to_path_buf(),
+            output_file: output_file.as_ref().to_path_buf(),
+            overwrite: false,
+            timestamp: Timestamp::default(),
+            title: None,
+            title_font_file: None,
+            scale: None,
+            osd: None,
+        }
+    }
+
+    fn output_is_jpeg(&self) -> bool {
+        matches!(self.output_file.extension(), Some(extension)
+            if extension.eq_ignore_ascii_case("jpg") || extension.eq_ignore_ascii_case("jpeg"))
+    }
+
+    fn title_filter(&self, title: &str) -> String {
+        let mut filter = format!("drawtext=expansion=none:text={}", ffmpeg::escape_filter_option_value(title));
+        if let Some(font_file) = &self.title_font_file {
+            filter.push_str(&format!(":fontfile={}", ffmpeg::escape_filter_option_value(&font_file.to_string_lossy())));
+        }
+        filter.push_str(":fontcolor=white:fontsize=h/9:borderw=h/200:bordercolor=black:x=(w-text_w)/2:y=h/12");
+        filter
+    }
+
+    pub async fn generate(&self) -> Result<(), TranscodeVideoError> {
+        if ! self.video_file.exists() { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
+        if ! self.overwrite && self.output_file.exists() { return Err(TranscodeVideoError::OutputVideoFileExists); }
+        if self.video_file == self.output_file { return Err(TranscodeVideoError::InputAndOutputFileIsTheSame) }
+
+        let video_info = probe(&self.video_file)?;
+        let frame_index = self.timestamp.frame_index(video_info.frame_rate());
+        if frame_index as u64 >= video_info.frame_count() {
+            return Err(TranscodeVideoError::TimestampAfterEndOfVideo(self.timestamp));
+        }
+
+        log::info!("generating thumbnail from frame {frame_index} of {}: {}",
+            self.video_file.to_string_lossy(), self.output_file.to_string_lossy());
+
+        file::touch(&self.output_file)?;
+
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command.add_input_file_slice(&self.video_file, Some(self.timestamp), None);
+
+        let osd_frame = match &self.osd {
+            Some(osd_settings) => {
+                let osd_frame_shift = osd_settings.frame_shift_for_video(&video_info);
+                let osd_frames_generator = osd_settings.overlay_generator(video_info.resolution())?;
+                let video_frame_rate = video_info.frame_rate();
+                let video_frame_rate = video_frame_rate.numerator() as f64 / video_frame_rate.denominator() as f64;
+                let osd_frame = osd_frames_generator.frame_at(osd_frame_index(frame_index as u64, video_frame_rate), osd_frame_shift)?;
+                ffmpeg_command.add_stdin_input(osd_frames_generator.frame_dimensions(), osd::FRAME_RATE as u16).unwrap();
+                Some(osd_frame)
+            },
+            None => None,
+        };
+
+        let mut filters = vec![];
+        if osd_frame.is_some() {
+            filters.push("overlay=x=(W-w)/2:y=(H-h)/2".to_owned());
+        }
+        if let Some(scale) = self.scale {
+            let output_resolution = scale.output_resolution(video_info.resolution());
+            filters.push(format!("scale={}:{}:flags=lanczos", output_resolution.width, output_resolution.height));
+        }
+        if let Some(title) = &self.title {
+            filters.push(self.title_filter(title));
+        }
+
+        if ! filters.is_empty() {
+            let inputs = if osd_frame.is_some() { "[0][1]" } else { "[0]" };
+            ffmpeg_command.add_complex_filter(&format!("{inputs}{}[vo]", filters.join(","))).add_mapping("[vo]");
+        }
+
+        ffmpeg_command
+            .add_args(&["-frames:v", "1", "-update", "1"])
+            .set_output_file(&self.output_file)
+            .set_overwrite_output_file(true);
+        if self.output_is_jpeg() {
+            ffmpeg_command.add_args(&["-q:v", "2"]);
+        }
+
+        let mut ffmpeg_process = ffmpeg_command.build().unwrap().spawn_no_output()?;
+
+        let send_result = match osd_frame {
+            Some(osd_frame) => match ffmpeg_process.take_stdin() {
+                Some(mut ffmpeg_stdin) => ffmpeg_stdin.write_all(osd_frame.as_raw()),
+                // no process in dry run mode
+                None => Ok(()),
+            },
+            None => Ok(()),
+        };
+
+        ffmpeg_process.wait().await?;
+        send_result.map_err(TranscodeVideoError::FailedSendingOSDFramesToFFMpeg)?;
+
+        log::info!("thumbnail generated successfully");
+        Ok(())
+    }
+
+}
@@ -8,6 +8,8 @@ use regex::Regex;
 use thiserror::Error;
 use lazy_static::lazy_static;
 
+use crate::osd;
+
 
 #[derive(Debug, CopyGetters, Setters, Constructor, Clone, Copy, Default, PartialEq, Eq)]
 #[getset(get_copy = "pub", set = "pub")]
@@ -15,60 +17,85 @@ pub struct Timestamp {
     hours: u16,
     minutes: u8,
     seconds: u8,
+    milliseconds: u16,
 }
 
 impl Timestamp {
 
+    pub fn from_milliseconds(total_milliseconds: u64) -> Self {
+        let total_seconds = total_milliseconds / 1000;
+        Self {
+            hours: (total_seconds / 3600) as u16,
+            minutes: (total_seconds % 3600 / 60) as u8,
+            seconds: (total_seconds % 60) as u8,
+            milliseconds: (total_milliseconds % 1000) as u16,
+        }
+    }
+
     pub fn total_seconds(&self) -> u32 {
         self.hours as u32 * 3600 + self.minutes as u32 * 60 + self.seconds as u32
     }
 
+    pub fn total_milliseconds(&self) -> u64 {
+        self.total_seconds() as u64 * 1000 + self.milliseconds as u64
+    }
+
     pub fn to_ffmpeg_position(&self) -> String {
-        format!("{}:{}:{}", self.hours, self.minutes, self.seconds)
+        format!("{}:{}:{}.{:03}", self.hours, self.minutes, self.seconds, self.milliseconds)
     }
 
     pub fn frame_count(&self, fps: Rational) -> u64 {
-        let frame_exact = fps * ffmpeg_next::Rational::new(self.total_seconds() as i32, 1);
-        (frame_exact.numerator() as f64 / frame_exact.denominator() as f64).round() as u64
+        milliseconds_frame_count(self.total_milliseconds(), fps)
+    }
+
+    pub fn frame_index(&self, fps: Rational) -> u32 {
+        let frame_count = u32::try_from(self.frame_count(fps)).unwrap();
+        frame_count.saturating_sub(1)
     }
 
     pub fn overlay_frame_count(&self) -> u32 {
-        u32::try_from(self.frame_count(Rational::from((60, 1)))).unwrap()
+        u32::try_from(self.frame_count(Rational::from((osd::FRAME_RATE as i32, 1)))).unwrap()
     }
 
     pub fn overlay_frame_index(&self) -> u32 {
-        let frame_count = self.overlay_frame_count();
-        if frame_count < 1 {
-            return frame_count;
-        }
-        frame_count - 1
+        self.frame_index(Rational::from((osd::FRAME_RATE as i32, 1)))
     }
 
     pub fn interval_frames(start_timestamp: &Self, end_timestamp: &Self, fps: Rational) -> u64 {
-        let interval_seconds = end_timestamp.total_seconds() as i32 - start_timestamp.total_seconds() as i32;
-        if interval_seconds < 0 { return 0 }
-        let frames_exact = fps * ffmpeg_next::Rational::new(interval_seconds, 1);
-        (frames_exact.numerator() as f64 / frames_exact.denominator() as f64).round() as u64
+        let interval_milliseconds = end_timestamp.total_milliseconds().saturating_sub(start_timestamp.total_milliseconds());
+        milliseconds_frame_count(interval_milliseconds, fps)
     }
 
 }
 
+/// number of frames at `fps` in `milliseconds`, rounded to the nearest frame
+///
+/// computed in 128 bits since the millisecond count does not fit the 32 bits of an FFMpeg rational for long recordings
+fn milliseconds_frame_count(milliseconds: u64, fps: Rational) -> u64 {
+    let numerator = milliseconds as i128 * fps.numerator() as i128;
+    let denominator = fps.denominator() as i128 * 1000;
+    if numerator <= 0 || denominator <= 0 { return 0 }
+    u64::try_from((2 * numerator + denominator) / (2 * denominator)).unwrap_or(u64::MAX)
+}
+
 impl Display for Timestamp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.hours > 0 { write!(f, "{}:", self.hours)? }
-        write!(f, "{}:{}", self.minutes, self.seconds)
+        write!(f, "{}:{}", self.minutes, self.seconds)?;
+        if self.milliseconds > 0 { write!(f, ".{:03}", self.milliseconds)? }
+        Ok(())
     }
 }
 
 impl PartialOrd for Timestamp {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.total_seconds().partial_cmp(&other.total_seconds())
+        self.total_milliseconds().partial_cmp(&other.total_milliseconds())
     }
 }
 
 impl Ord for Timestamp {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.total_seconds().cmp(&other.total_seconds())
+        self.total_milliseconds().cmp(&other.total_milliseconds())
     }
 }
 
@@ -79,25 +106,68 @@ pub struct TimestampFormatError(String);
 impl FromStr for Timestamp {
     type Err = TimestampFormatError;
 
+    /// parses `[[HH:]MM:]SS[.mmm]` timestamps, when only seconds are provided they can be greater than 59
     fn from_str(value: &str) -> Result<Self, Self::Err> {
         lazy_static! {
-            static ref TIMESTAMP_RE: Regex = Regex::new(r"\A(?:(?P<hours>\d{1,3}):)?(?P<minutes>\d{1,2}):(?P<seconds>\d{1,2})\z").unwrap();
+            static ref TIMESTAMP_RE: Regex = Regex::new(r"\A(?:(?:(?P<hours>\d{1,3}):)?(?P<minutes>\d{1,2}):)?(?P<seconds>\d{1,6})(?:\.(?P<fraction>\d{1,3}))?\z").unwrap();
         }
-        Ok(match TIMESTAMP_RE.captures(value) {
-            Some(captures) => {
-                let hours = captures.name("hours").map(|hours_match| hours_match.as_str().parse().unwrap()).unwrap_or(0);
-                let minutes = captures.name("minutes").unwrap().as_str().parse().unwrap();
-                let seconds = captures.name("seconds").unwrap().as_str().parse().unwrap();
-                Timestamp::new(hours, minutes, seconds)
-            },
-            None => return Err(TimestampFormatError(value.to_owned())),
-        })
+        let captures = TIMESTAMP_RE.captures(value).ok_or_else(|| TimestampFormatError(value.to_owned()))?;
+        let parse_component = |name| captures.name(name).map(|component_match| component_match.as_str().parse::<u64>().unwrap()).unwrap_or(0);
+        let (hours, minutes, seconds) = (parse_component("hours"), parse_component("minutes"), parse_component("seconds"));
+        if captures.name("minutes").is_some() && seconds > 59 {
+            return Err(TimestampFormatError(value.to_owned()));
+        }
+        let milliseconds = match captures.name("fraction") {
+            Some(fraction) => fraction.as_str().parse::<u64>().unwrap() * 10_u64.pow(3 - fraction.as_str().len() as u32),
+            None => 0,
+        };
+        Ok(Timestamp::from_milliseconds(((hours * 60 + minutes) * 60 + seconds) * 1000 + milliseconds))
+    }
+}
+
+/// interval between two optional timestamps, parsed from `[START]..[END]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct TimestampRange {
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+}
+
+impl TimestampRange {
+    pub fn new(start: Option<Timestamp>, end: Option<Timestamp>) -> Self {
+        Self { start, end }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TimestampRangeFormatError {
+    #[error("invalid timestamp range, expected [START]..[END]: {0}")]
+    InvalidFormat(String),
+    #[error(transparent)]
+    InvalidTimestamp(#[from] TimestampFormatError),
+    #[error("timestamp range start is not before its end: {0}")]
+    StartNotBeforeEnd(String),
+}
+
+impl FromStr for TimestampRange {
+    type Err = TimestampRangeFormatError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (start, end) = value.split_once("..").ok_or_else(|| TimestampRangeFormatError::InvalidFormat(value.to_owned()))?;
+        let parse_bound = |bound: &str| if bound.is_empty() { Ok(None) } else { Timestamp::from_str(bound).map(Some) };
+        let (start, end) = (parse_bound(start)?, parse_bound(end)?);
+        if matches!((start, end), (Some(start), Some(end)) if start >= end) {
+            return Err(TimestampRangeFormatError::StartNotBeforeEnd(value.to_owned()));
+        }
+        Ok(Self { start, end })
     }
 }
 
 pub trait StartEndOverlayFrameIndex {
     fn start_overlay_frame_count(&self) -> u32;
     fn end_overlay_frame_index(&self) -> Option<u32>;
+    fn start_frame_count(&self, fps: Rational) -> u32;
+    fn end_frame_index(&self, fps: Rational) -> Option<u32>;
 }
 
 impl StartEndOverlayFrameIndex for Option<Timestamp> {
@@ -113,4 +183,53 @@ impl StartEndOverlayFrameIndex for Option<Timestamp> {
         self.as_ref().map(|end| end.overlay_frame_index())
     }
 
-}
\ No newline at end of file
+    fn start_frame_count(&self, fps: Rational) -> u32 {
+        match self {
+            Some(start) => u32::try_from(start.frame_count(fps)).unwrap(),
+            None => 0,
+        }
+    }
+
+    fn end_frame_index(&self, fps: Rational) -> Option<u32> {
+        self.as_ref().map(|end| end.frame_index(fps))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ffmpeg_next::Rational;
+
+    use super::Timestamp;
+
+    #[test]
+    fn parse_timestamps() {
+        let valid = [
+            ("1:02", Timestamp::new(0, 1, 2, 0)),
+            ("1:02:03", Timestamp::new(1, 2, 3, 0)),
+            ("1:02:03.5", Timestamp::new(1, 2, 3, 500)),
+            ("02:03.045", Timestamp::new(0, 2, 3, 45)),
+            ("90", Timestamp::new(0, 1, 30, 0)),
+            ("12.25", Timestamp::new(0, 0, 12, 250)),
+        ];
+        for (value, expected) in valid {
+            assert_eq!(Timestamp::from_str(value).unwrap(), expected, "{value}");
+        }
+        for value in ["", "1:2:3:4", "1:60", "1.", "1.2345", "a:00"] {
+            assert!(Timestamp::from_str(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn frame_counts_of_long_recordings() {
+        let ntsc = Rational::new(30000, 1001);
+        let end = Timestamp::new(30, 0, 0, 0);
+        assert_eq!(end.frame_count(Rational::new(60, 1)), 6_480_000);
+        assert_eq!(end.frame_count(ntsc), 3_236_763);
+        assert_eq!(Timestamp::interval_frames(&Timestamp::new(0, 0, 1, 0), &end, ntsc), 3_236_763 - 30);
+        assert_eq!(Timestamp::interval_frames(&end, &Timestamp::new(0, 0, 1, 0), ntsc), 0);
+    }
+
+}
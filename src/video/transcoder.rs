@@ -0,0 +1,1429 @@
+
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use ffmpeg_next::Rational;
+use getset::{CopyGetters, Getters, Setters};
+
+use crate::ffmpeg::{self, HwAccel};
+use crate::file;
+use crate::jobs;
+use crate::temp_dir;
+use crate::telemetry::{self, TelemetrySettings, TelemetrySync};
+use crate::osd::{self, FontDir, file::GenericReader, flight_stats::FlightStats, frame_shift::FrameShift, overlay::{Generator as OverlayGenerator, TileBlitMethod, composition::{Area as OSDArea, ComposedFramesIter, OSDSource}, interpolation::InterpolatedFramesIter, scaling::{Scaling, OSDScalingSettings}, graphs::GraphsSettings, sticks::SticksSettings, style::Style as OSDStyle}};
+
+use super::{
+    AudioFixType,
+    AudioAdjustments,
+    Region,
+    Resolution,
+    Timestamp,
+    TranscodeVideoError,
+    encoder,
+    cut::{self, TempFiles},
+    gpu::{GpuApi, GpuPipelineError},
+    estimate::Estimate,
+    frame_count_for_interval,
+    probe,
+    resolution::OutputScale,
+    srt_telemetry::SrtTelemetrySettings,
+    watermark::WatermarkSettings,
+    pip::PipSettings,
+    text_annotation::TextAnnotation,
+    clock::ClockSettings,
+    container::Container,
+    undistort::LensProfile,
+    vertical::{VerticalSettings, VerticalWindow},
+    verify::{self, QualitySettings},
+    autocrop,
+    card::{Card, CardContent},
+    layers::{self, ClockLayer, LayerConfig, LayerPipeline, OsdLayer, PipLayer, SrtTelemetryLayer, TextLayer, WatermarkLayer},
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscodePreset {
+    /// high quality H.265 encoding for long term storage
+    Archival,
+    /// H.264 encoding with settings suited for uploading to YouTube and other video platforms
+    Youtube,
+    /// fast H.264 encoding with low quality, useful to quickly preview the result
+    Draft,
+}
+
+/// What is done with the audio of the input video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioMode {
+    /// copy the audio stream without re-encoding it, the audio cannot be fixed nor adjusted
+    Copy,
+    /// re-encode the audio stream with the audio encoder settings
+    Reencode,
+    /// remove the audio
+    None,
+}
+
+impl Default for AudioMode {
+    fn default() -> Self { Self::Reencode }
+}
+
+/// clockwise rotation of the camera video
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoRotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl VideoRotation {
+
+    pub fn ffmpeg_filter_string(&self) -> &'static str {
+        use VideoRotation::*;
+        match self {
+            Rotate90 => "transpose=clock",
+            Rotate180 => "hflip,vflip",
+            Rotate270 => "transpose=cclock",
+        }
+    }
+
+    pub fn swaps_dimensions(&self) -> bool {
+        matches!(self, VideoRotation::Rotate90 | VideoRotation::Rotate270)
+    }
+
+}
+
+/// filter removing the noise of the camera video, e.g. of low-light footage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenoiseFilter {
+    /// fast 3D denoiser, smoothing the noise in space and time
+    Hqdn3d,
+    /// non-local means denoiser, keeping more details but much slower
+    Nlmeans,
+}
+
+impl Default for DenoiseFilter {
+    fn default() -> Self { Self::Hqdn3d }
+}
+
+impl DenoiseFilter {
+
+    /// the strength is the luma spatial strength of hqdn3d and the denoising strength of nlmeans
+    pub fn ffmpeg_filter_string(&self, strength: f64) -> String {
+        match self {
+            Self::Hqdn3d => format!("hqdn3d=luma_spatial={strength}"),
+            Self::Nlmeans => format!("nlmeans=s={strength}"),
+        }
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct EncodingSettings {
+    video_encoder: &'static str,
+    video_bitrate: Option<&'static str>,
+    video_crf: u8,
+    video_encoder_preset: Option<&'static str>,
+    audio_encoder: &'static str,
+    audio_bitrate: &'static str,
+    faststart: bool,
+    /// maximum interval between key frames in seconds, `None` for the encoder default
+    keyframe_interval: Option<f64>,
+}
+
+/// encoding settings used when no preset is selected
+pub const DEFAULT_ENCODING_SETTINGS: EncodingSettings = EncodingSettings {
+    video_encoder: "libx265",
+    video_bitrate: Some("25M"),
+    video_crf: 25,
+    video_encoder_preset: None,
+    audio_encoder: "aac",
+    audio_bitrate: "192k",
+    faststart: false,
+    keyframe_interval: None,
+};
+
+impl TranscodePreset {
+    pub const fn encoding_settings(&self) -> EncodingSettings {
+        use TranscodePreset::*;
+        match self {
+            Archival => EncodingSettings {
+                video_encoder: "libx265",
+                video_bitrate: None,
+                video_crf: 18,
+                video_encoder_preset: Some("slow"),
+                audio_encoder: "aac",
+                audio_bitrate: "256k",
+                faststart: false,
+                keyframe_interval: None,
+            },
+            Youtube => EncodingSettings {
+                video_encoder: "libx264",
+                video_bitrate: None,
+                video_crf: 20,
+                video_encoder_preset: Some("slow"),
+                audio_encoder: "aac",
+                audio_bitrate: "192k",
+                faststart: true,
+                // key frames every 2 seconds as recommended for the streaming platforms
+                keyframe_interval: Some(2.0),
+            },
+            Draft => EncodingSettings {
+                video_encoder: "libx264",
+                video_bitrate: None,
+                video_crf: 28,
+                video_encoder_preset: Some("veryfast"),
+                audio_encoder: "aac",
+                audio_bitrate: "128k",
+                faststart: true,
+                keyframe_interval: None,
+            },
+        }
+    }
+}
+
+/// Settings used to burn the OSD onto the video
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct OSDSettings {
+    osd_file: PathBuf,
+    font_dir: PathBuf,
+    /// `None`: font chosen from the OSD file font variant, `Some(None)`: generic font, `Some(Some(ident))`: font with this ident
+    font_ident: Option<Option<String>>,
+    /// the color channels of the font tiles are premultiplied by their alpha
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    font_premultiplied_alpha: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    scaling: OSDScalingSettings,
+    style: OSDStyle,
+    /// when `None` the frame shift is chosen automatically depending on the video origin
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    frame_shift: Option<FrameShift>,
+    hide_regions: Vec<osd::Region>,
+    hide_items: Vec<String>,
+    /// flight telemetry used by the graphs and the sticks
+    telemetry: Option<TelemetrySettings>,
+    graphs: GraphsSettings,
+    sticks: SticksSettings,
+    /// Rhai script drawing custom widgets on top of the OSD
+    script: Option<PathBuf>,
+    /// area of the video the OSD is drawn into, the whole video when not set
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    area: Option<OSDArea>,
+    /// other OSD files drawn onto the video, e.g. the OSD of a second pilot on a split screen video
+    other_sources: Vec<OSDSource>,
+    /// blend the OSD frames between the OSD updates instead of holding them
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    interpolate: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    tile_blit_method: TileBlitMethod,
+}
+
+impl OSDSettings {
+
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(osd_file: P, font_dir: Q) -> Self {
+        Self {
+            osd_file: osd_file.as_ref().to_path_buf(),
+            font_dir: font_dir.as_ref().to_path_buf(),
+            font_ident: None,
+            font_premultiplied_alpha: false,
+            scaling: OSDScalingSettings::default(),
+            style: OSDStyle::default(),
+            frame_shift: None,
+            hide_regions: vec![],
+            hide_items: vec![],
+            telemetry: None,
+            graphs: GraphsSettings::default(),
+            sticks: SticksSettings::default(),
+            script: None,
+            area: None,
+            other_sources: vec![],
+            interpolate: false,
+            tile_blit_method: TileBlitMethod::default(),
+        }
+    }
+
+    /// whether several OSD files or an OSD file drawn into an area are composed into the OSD frames
+    pub fn is_composed(&self) -> bool {
+        self.area.is_some() || ! self.other_sources.is_empty()
+    }
+
+    /// Settings of each OSD file: these settings then the settings of the other sources, which draw their OSD file
+    /// into their area with their frame shift, or the frame shift of these settings when not set, and the same font and
+    /// style but without the telemetry widgets
+    pub fn source_settings(&self) -> Vec<OSDSettings> {
+        let mut settings = self.clone();
+        settings.other_sources.clear();
+        let other_source_settings = self.other_sources.iter().map(|source| {
+            let mut source_settings = settings.clone();
+            source_settings.osd_file = source.file().clone();
+            source_settings.area = source.area();
+            source_settings.frame_shift = source.frame_shift().or(self.frame_shift);
+            source_settings.telemetry = None;
+            source_settings.graphs = GraphsSettings::default();
+            source_settings.sticks = SticksSettings::default();
+            source_settings.script = None;
+            source_settings
+        }).collect::<Vec<_>>();
+        std::iter::once(settings).chain(other_source_settings).collect()
+    }
+
+    /// frame shift to apply in OSD frames, DJI AU videos (which have audio) need the OSD to be shifted when not explicitly set
+    pub fn frame_shift_for_video(&self, video_info: &probe::Result) -> i32 {
+        match self.frame_shift {
+            Some(frame_shift) => frame_shift.osd_frames(),
+            None => {
+                if video_info.has_audio() {
+                    let frame_shift = crate::osd::dji::AU_OSD_FRAME_SHIFT;
+                    log::info!("input video file contains audio, assuming DJI AU origin, applying {frame_shift} OSD frames shift");
+                    frame_shift
+                } else {
+                    0
+                }
+            },
+        }
+    }
+
+    /// settings with the OSD frame index of the arm beep detected in the audio of the video when the telemetry is
+    /// aligned on the arm beep, the video frames being shifted by `frame_shift` OSD frames
+    pub async fn with_detected_arm_beep<P: AsRef<Path>>(&self, video_file: P, frame_shift: i32) -> Result<Cow<'_, Self>, TranscodeVideoError> {
+        match &self.telemetry {
+            Some(telemetry_settings) if telemetry_settings.sync() == TelemetrySync::ArmBeep => {
+                let arm_beep_time = telemetry::detect_arm_beep(video_file).await?;
+                if let Some(arm_beep_time) = arm_beep_time {
+                    log::info!("arm beep detected at {arm_beep_time:.2}s");
+                }
+                let mut telemetry_settings = telemetry_settings.clone();
+                telemetry_settings.set_arm_beep_frame_index(arm_beep_time.map(|time| (time * osd::FRAME_RATE as f64).round() as i64 - frame_shift as i64));
+                let mut settings = self.clone();
+                settings.telemetry = Some(telemetry_settings);
+                Ok(Cow::Owned(settings))
+            },
+            _ => Ok(Cow::Borrowed(self)),
+        }
+    }
+
+    /// overlay generator drawing the OSD frames for a video with the specified resolution
+    pub fn overlay_generator(&self, video_resolution: Resolution) -> Result<OverlayGenerator, TranscodeVideoError> {
+        let osd_scaling = Scaling::from_osd_settings(&self.scaling, video_resolution);
+        let osd_file = osd::file::open(&self.osd_file)?;
+        let mut osd_font_dir = FontDir::new(&self.font_dir);
+        osd_font_dir.set_premultiplied_alpha(self.font_premultiplied_alpha);
+        let font_ident = self.font_ident.as_ref().map(|ident| ident.as_deref());
+        let font_variant = osd_file.font_variant();
+        let mut overlay_generator = OverlayGenerator::new(
+            osd_file,
+            font_variant,
+            &osd_font_dir,
+            &font_ident,
+            osd_scaling,
+            &self.hide_regions,
+            &self.hide_items
+        )?;
+        overlay_generator.apply_style(&self.style);
+        overlay_generator.set_tile_blit_method(self.tile_blit_method);
+        overlay_generator.set_telemetry(self.telemetry.as_ref())?;
+        overlay_generator.set_telemetry_graphs(&self.graphs)?;
+        overlay_generator.set_stick_overlay(&self.sticks);
+        overlay_generator.set_script_layer(self.script.as_deref())?;
+        if self.scaling.explain() { overlay_generator.log_scaling_explanation() }
+        Ok(overlay_generator)
+    }
+
+}
+
+/// Video transcoder, optionally burning the OSD onto the video
+///
+/// Created with the input and output video file paths and the default encoding settings which can then be changed
+/// with the setters before calling [`Transcoder::transcode`].
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct Transcoder {
+    input_video_file: PathBuf,
+    output_video_file: PathBuf,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    overwrite: bool,
+    /// decode the input video on the GPU with this API
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    hw_decode: Option<HwAccel>,
+    /// decode, overlay the OSD and encode on the GPU with this API, see [`super::gpu`]
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    gpu: Option<GpuApi>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    start: Option<Timestamp>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    end: Option<Timestamp>,
+    video_encoder: String,
+    video_bitrate: Option<String>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    video_crf: Option<u8>,
+    video_encoder_preset: Option<String>,
+    video_encoder_tune: Option<String>,
+    audio_encoder: String,
+    audio_bitrate: String,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    audio: AudioMode,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    faststart: bool,
+    /// maximum interval between key frames in seconds so that the video can be seeked quickly when streamed
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    keyframe_interval: Option<f64>,
+    /// container of the output video, inferred from the extension of the output file when not set
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    container: Option<Container>,
+    /// do not copy the container metadata and the modification time of the input video to the output video
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    strip_metadata: bool,
+    audio_fix: Option<AudioFixType>,
+    /// gain and delay applied to the audio after the audio fix
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    audio_adjustments: AudioAdjustments,
+    remove_video_defects: Vec<Region>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    deinterlace: bool,
+    /// strength of the denoising filter
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    denoise: Option<f64>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    denoise_filter: DenoiseFilter,
+    crop: Option<Region>,
+    /// detect the black bars padding the picture and crop them, see [`autocrop`]
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    autocrop: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    scale: Option<OutputScale>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    rotate: Option<VideoRotation>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    hflip: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    vflip: bool,
+    /// playback speed factor, lower than 1 for slow motion and greater than 1 for timelapse
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    speed: Option<f64>,
+    /// lens distortion correction of the camera video
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    undistort: Option<LensProfile>,
+    /// 3D LUT file used to grade the colors of the video, e.g. a `.cube` file
+    lut: Option<PathBuf>,
+    /// vertical 9:16 crop applied after the other video filters, the OSD items being moved into the visible area
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    vertical: Option<VerticalSettings>,
+    osd: Option<OSDSettings>,
+    srt_telemetry: Option<SrtTelemetrySettings>,
+    watermark: Option<WatermarkSettings>,
+    /// second video inset over the video, e.g. the feed of an HD camera
+    pip: Option<PipSettings>,
+    text_annotations: Vec<TextAnnotation>,
+    clock: Option<ClockSettings>,
+    /// z-order, opacity and blend mode of the layers drawn onto the video
+    layers: Vec<LayerConfig>,
+    /// length in seconds of the chunks transcoded separately then joined, so that an interrupted transcoding can be
+    /// resumed from the last chunk
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    checkpoint_interval: Option<f64>,
+    /// keep the chunks completed by a previous interrupted transcoding
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    resume: bool,
+    /// check the duration, frame count and audio sync of the output video once transcoded
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    verify: bool,
+    /// also measure the quality of the output video against the input video when verifying it
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    verify_quality: Option<QualitySettings>,
+    /// card shown before the video
+    intro_card: Option<Card>,
+    /// card shown after the video
+    outro_card: Option<Card>,
+}
+
+impl Transcoder {
+
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(input_video_file: P, output_video_file: Q) -> Self {
+        let mut transcoder = Self {
+            input_video_file: input_video_file.as_ref().to_path_buf(),
+            output_video_file: output_video_file.as_ref().to_path_buf(),
+            overwrite: false,
+            hw_decode: None,
+            gpu: None,
+            start: None,
+            end: None,
+            video_encoder: String::new(),
+            video_bitrate: None,
+            video_crf: None,
+            video_encoder_preset: None,
+            video_encoder_tune: None,
+            audio_encoder: String::new(),
+            audio_bitrate: String::new(),
+            audio: AudioMode::default(),
+            faststart: false,
+            keyframe_interval: None,
+            container: None,
+            strip_metadata: false,
+            audio_fix: None,
+            audio_adjustments: AudioAdjustments::default(),
+            remove_video_defects: vec![],
+            deinterlace: false,
+            denoise: None,
+            denoise_filter: DenoiseFilter::default(),
+            crop: None,
+            autocrop: false,
+            scale: None,
+            rotate: None,
+            hflip: false,
+            vflip: false,
+            speed: None,
+            undistort: None,
+            lut: None,
+            vertical: None,
+            osd: None,
+            srt_telemetry: None,
+            watermark: None,
+            pip: None,
+            text_annotations: vec![],
+            clock: None,
+            layers: vec![],
+            checkpoint_interval: None,
+            resume: false,
+            verify: false,
+            verify_quality: None,
+            intro_card: None,
+            outro_card: None,
+        };
+        transcoder.set_encoding_settings(&DEFAULT_ENCODING_SETTINGS);
+        transcoder
+    }
+
+    /// sets all the encoding settings, the CRF and encoder preset are not set if the encoder is known to not support them
+    pub fn set_encoding_settings(&mut self, settings: &EncodingSettings) -> &mut Self {
+        let capabilities = encoder::capabilities(settings.video_encoder());
+        self.video_encoder = settings.video_encoder().to_owned();
+        self.video_bitrate = settings.video_bitrate().map(str::to_owned);
+        self.video_crf = match &capabilities {
+            Some(capabilities) if ! capabilities.supports_crf(settings.video_crf()) => None,
+            _ => Some(settings.video_crf()),
+        };
+        self.video_encoder_preset = match (&capabilities, settings.video_encoder_preset()) {
+            (Some(capabilities), Some(preset)) if capabilities.supports_preset(preset) => Some(preset.to_owned()),
+            _ => None,
+        };
+        self.audio_encoder = settings.audio_encoder().to_owned();
+        self.audio_bitrate = settings.audio_bitrate().to_owned();
+        self.faststart = settings.faststart();
+        self.keyframe_interval = settings.keyframe_interval();
+        self
+    }
+
+    /// checks the encoding settings against the selected encoder
+    pub fn check_valid_encoding_settings(&self) -> Result<(), encoder::InvalidSettingsError> {
+        for bitrate in [self.video_bitrate.as_ref(), Some(&self.audio_bitrate)].into_iter().flatten() {
+            encoder::check_bitrate(bitrate)?;
+        }
+        encoder::check_settings(&self.video_encoder, self.video_crf, self.video_encoder_preset.as_deref(), self.video_encoder_tune.as_deref())
+    }
+
+    /// Builds the list of filters applied to the input video before the OSD is overlaid: deinterlacing, defects removal, lens distortion correction,
+    /// crop, rotation/flips, denoising, scale then color grading so that the overlay colors are not graded. Also returns the resolution of the video coming out of these filters.
+    fn video_filters(&self, input_resolution: Resolution) -> Result<(Vec<String>, Resolution), TranscodeVideoError> {
+        let mut filters = vec![];
+
+        // one frame per frame so that the frame rate and the OSD sync are kept
+        if self.deinterlace {
+            filters.push("bwdif=mode=send_frame".to_owned());
+        }
+
+        filters.extend(self.remove_video_defects.iter().map(|region|
+            format!("delogo={}", region.to_ffmpeg_filter_string())
+        ));
+
+        // the distortion is centered on the camera sensor so it is corrected before cropping
+        if let Some(undistort) = &self.undistort {
+            filters.push(undistort.ffmpeg_filter());
+        }
+
+        let mut resolution = input_resolution;
+
+        if let Some(requested_crop) = &self.crop {
+            let crop = requested_crop.with_even_dimensions();
+            if (crop.dimensions().width, crop.dimensions().height) != (requested_crop.dimensions().width, requested_crop.dimensions().height) {
+                log::warn!("crop region dimensions rounded down to even values: {}x{}", crop.dimensions().width, crop.dimensions().height);
+            }
+            let bottom_right = crop.bottom_right_corner();
+            if bottom_right.x() as u32 >= input_resolution.width || bottom_right.y() as u32 >= input_resolution.height {
+                return Err(TranscodeVideoError::CropRegionOutOfBounds(input_resolution));
+            }
+            filters.push(format!("crop={}", crop.to_ffmpeg_filter_string()));
+            resolution = Resolution::new(crop.dimensions().width as u32, crop.dimensions().height as u32);
+        }
+
+        if let Some(rotation) = self.rotate {
+            filters.push(rotation.ffmpeg_filter_string().to_owned());
+            if rotation.swaps_dimensions() {
+                resolution = Resolution::new(resolution.height, resolution.width);
+            }
+        }
+
+        if self.hflip { filters.push("hflip".to_owned()); }
+        if self.vflip { filters.push("vflip".to_owned()); }
+
+        // the noise is removed before being scaled up and without the cropped off area
+        if let Some(strength) = self.denoise {
+            if ! strength.is_finite() || strength <= 0.0 { return Err(TranscodeVideoError::InvalidDenoiseStrength(strength)) }
+            filters.push(self.denoise_filter.ffmpeg_filter_string(strength));
+        }
+
+        if let Some(scale) = self.scale {
+            resolution = scale.output_resolution(resolution);
+            filters.push(format!("scale={}:{}:flags=lanczos", resolution.width, resolution.height));
+        }
+
+        if let Some(lut) = &self.lut {
+            filters.push(format!("lut3d=file={}", ffmpeg::escape_filter_option_value(&lut.to_string_lossy())));
+        }
+
+        Ok((filters, resolution))
+    }
+
+    /// Layers drawn onto the video of the `resolution`: the picture-in-picture video, the OSD when its layer is provided, the SRT
+    /// telemetry, the text annotations, the wall-clock time and the watermark, styled with the layer configurations
+    fn layer_pipeline(&self, video_info: &probe::Result, resolution: Resolution, osd_layer: Option<OsdLayer>) -> Result<LayerPipeline, TranscodeVideoError> {
+        let mut pipeline = LayerPipeline::new();
+        if let Some(pip) = &self.pip {
+            log::info!("insetting picture-in-picture video: {}", pip.video_file().to_string_lossy());
+            pipeline.add_configured(PipLayer::new(pip, resolution, self.start), &self.layers);
+        }
+        if let Some(osd_layer) = osd_layer {
+            pipeline.add_configured(osd_layer, &self.layers);
+        }
+        if let Some(srt_telemetry) = &self.srt_telemetry {
+            log::info!("drawing SRT telemetry: {}", srt_telemetry.srt_file().to_string_lossy());
+            pipeline.add_configured(SrtTelemetryLayer::new(srt_telemetry, &self.output_video_file, self.start)?, &self.layers);
+        }
+        for text_annotation in &self.text_annotations {
+            pipeline.add_configured(TextLayer::new(text_annotation, self.start), &self.layers);
+        }
+        if let Some(clock) = &self.clock {
+            pipeline.add_configured(ClockLayer::new(clock, &self.input_video_file, video_info, self.start)?, &self.layers);
+        }
+        if let Some(watermark) = &self.watermark {
+            pipeline.add_configured(WatermarkLayer::new(watermark), &self.layers);
+        }
+        Ok(pipeline)
+    }
+
+    /// Builds the complex filter applying the filters to the input, then compositing the layers and changing the speed
+    /// so that it applies to the layers as well, the output being labelled `[vo]`. `None` if there is nothing to apply.
+    fn complex_filter(&self, input: &str, filters: Vec<String>, layer_pipeline: &LayerPipeline, resolution: Resolution, video_info: &probe::Result)
+            -> Option<String> {
+        let speed_filter = self.speed_video_filter();
+        if filters.is_empty() && layer_pipeline.is_empty() && speed_filter.is_none() { return None }
+        let filters = if filters.is_empty() { "null".to_owned() } else { filters.join(",") };
+        let layers_graph = layer_pipeline.ffmpeg_filter_graph("[base]", "[layers]", resolution, &layers::ffmpeg_frame_rate(video_info),
+            layers::ffmpeg_pixel_format(video_info));
+        Some(format!("{input}{filters}[base];{layers_graph};[layers]{}[vo]", speed_filter.unwrap_or_else(|| "null".to_owned())))
+    }
+
+    /// container of the output video, set explicitly or inferred from the extension of the output file
+    pub fn output_container(&self) -> Option<Container> {
+        self.container.or_else(|| Container::from_extension(&self.output_video_file))
+    }
+
+    /// vertical crop window of the video coming out of the video filters, the crop is applied after the video filters
+    fn vertical_window(&self, resolution: Resolution) -> Option<VerticalWindow> {
+        self.vertical.map(|vertical| vertical.window(resolution))
+    }
+
+    /// filter changing the video speed, applied after the OSD is overlaid so that the OSD stays in sync with the video
+    fn speed_video_filter(&self) -> Option<String> {
+        self.speed.map(|speed| format!("setpts=PTS/{speed}"))
+    }
+
+    /// audio filters: audio fix, gain and delay then tempo change matching the video speed, the `atempo` filter only
+    /// supporting factors between 0.5 and 2 it is chained as many times as needed
+    fn audio_filter(&self) -> Option<String> {
+        let mut filters = vec![];
+        if let Some(correction_filter) = super::audio_correction_filter(self.audio_fix.as_ref(), &self.audio_adjustments) {
+            filters.push(correction_filter);
+        }
+        if let Some(mut speed) = self.speed {
+            while speed > 2.0 {
+                filters.push("atempo=2".to_owned());
+                speed /= 2.0;
+            }
+            while speed < 0.5 {
+                filters.push("atempo=0.5".to_owned());
+                speed /= 0.5;
+            }
+            filters.push(format!("atempo={speed}"));
+        }
+        if filters.is_empty() { None } else { Some(filters.join(",")) }
+    }
+
+    /// number of frames of the output video, used to report the progress
+    fn output_frame_count(&self, input_frame_count: u64) -> u64 {
+        match self.speed {
+            Some(speed) => (input_frame_count as f64 / speed) as u64,
+            None => input_frame_count,
+        }
+    }
+
+    fn check_before_transcoding(&self) -> Result<(), TranscodeVideoError> {
+        self.check_valid_encoding_settings()?;
+        if let Some(speed) = self.speed {
+            if ! speed.is_finite() || speed <= 0.0 { return Err(TranscodeVideoError::InvalidSpeed(speed)) }
+        }
+        if let Some(keyframe_interval) = self.keyframe_interval {
+            if ! keyframe_interval.is_finite() || keyframe_interval <= 0.0 { return Err(TranscodeVideoError::InvalidKeyframeInterval(keyframe_interval)) }
+        }
+        if ! file::input_exists(&self.input_video_file) { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
+        if let Some(watermark) = &self.watermark {
+            if ! watermark.image_file().is_file() { return Err(TranscodeVideoError::WatermarkImageDoesNotExist(watermark.image_file().clone())) }
+        }
+        if let Some(pip) = &self.pip {
+            if ! file::input_exists(pip.video_file()) { return Err(TranscodeVideoError::PipVideoFileDoesNotExist(pip.video_file().clone())) }
+        }
+        if let Some(lut) = &self.lut {
+            if ! lut.is_file() { return Err(TranscodeVideoError::LutFileDoesNotExist(lut.clone())) }
+        }
+        if let Some(container) = self.container {
+            container.check_output_file(&self.output_video_file)?;
+        }
+        if let Some(container) = self.output_container() {
+            let audio_encoder = Some(self.audio_encoder.as_str()).filter(|_| self.audio == AudioMode::Reencode);
+            container.check_encoders(&self.video_encoder, audio_encoder)?;
+        }
+        if ! file::is_stdout(&self.output_video_file) {
+            if ! self.overwrite && self.output_video_file.exists() { return Err(TranscodeVideoError::OutputVideoFileExists); }
+            if self.input_video_file == self.output_video_file { return Err(TranscodeVideoError::InputAndOutputFileIsTheSame) }
+            file::touch(&self.output_video_file)?;
+        }
+        if self.audio != AudioMode::Reencode && self.audio_filter().is_some() {
+            return Err(TranscodeVideoError::IncompatibleArguments(
+                "cannot fix, adjust or change the speed of the audio when it is copied or removed".to_owned()));
+        }
+        if self.start.is_some() && matches!(&self.audio_fix, Some(fix) if fix.sync()) {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot fix video audio sync while not starting at the beginning of the file".to_owned()));
+        }
+        if let Some(gpu) = self.gpu {
+            self.check_gpu_pipeline(gpu)?;
+        }
+        Ok(())
+    }
+
+    /// checks that the GPU pipeline can do everything requested, it only scales the video and overlays the OSD
+    fn check_gpu_pipeline(&self, gpu: GpuApi) -> Result<(), GpuPipelineError> {
+        gpu.check_encoder(&self.video_encoder)?;
+        let unsupported = [
+            (self.hw_decode.is_some(), "--hw-decode, the video is already decoded on the GPU"),
+            (! self.remove_video_defects.is_empty() || self.undistort.is_some() || self.crop.is_some() || self.rotate.is_some() ||
+                self.hflip || self.vflip || self.lut.is_some() || self.vertical.is_some() || self.deinterlace || self.denoise.is_some(),
+                "video filters other than scaling"),
+            (self.pip.is_some() || self.srt_telemetry.is_some() || self.watermark.is_some() || ! self.text_annotations.is_empty() ||
+                self.clock.is_some(), "layers other than the OSD"),
+            (! self.layers.is_empty(), "layer styling"),
+        ];
+        match unsupported.iter().find(|(requested, _)| *requested) {
+            Some((_, feature)) => Err(GpuPipelineError::UnsupportedFeature(feature)),
+            None => Ok(()),
+        }
+    }
+
+    /// complex filter of the GPU pipeline, the OSD frames of the input `[1]` being centered on the video if provided
+    fn gpu_filter_graph(&self, gpu: GpuApi, input_resolution: Resolution, output_resolution: Resolution, osd_resolution: Option<Resolution>) -> String {
+        let osd_input = osd_resolution.map(|osd_resolution| ("[1]",
+            (output_resolution.width as i64 - osd_resolution.width as i64) / 2,
+            (output_resolution.height as i64 - osd_resolution.height as i64) / 2,
+        ));
+        let scale = Some(output_resolution).filter(|output_resolution| *output_resolution != input_resolution);
+        gpu.filter_graph("[0]", osd_input, scale, self.speed_video_filter())
+    }
+
+    fn set_ffmpeg_output_settings(&self, ffmpeg_command: &mut ffmpeg::CommandBuilder, frame_rate: Rational) {
+        ffmpeg_command
+            .set_output_video_settings(Some(&self.video_encoder), self.video_bitrate.as_deref(), self.video_crf)
+            .set_output_video_preset(self.video_encoder_preset.as_deref())
+            .set_output_video_tune(self.video_encoder_tune.as_deref());
+
+        if let Some(keyframe_interval) = self.keyframe_interval {
+            // forcing the key frames by time works with all the encoders whatever the frame rate, the GOP size
+            // is only a maximum for the encoders placing key frames on scene changes
+            let force_key_frames = format!("expr:gte(t,n_forced*{keyframe_interval})");
+            let gop_size = ((keyframe_interval * f64::from(frame_rate)).round() as u64).max(1).to_string();
+            ffmpeg_command.add_args(&["-force_key_frames", &force_key_frames, "-g", &gop_size]);
+        }
+
+        if self.strip_metadata {
+            ffmpeg_command.strip_metadata();
+        } else {
+            ffmpeg_command.copy_metadata_from(0);
+        }
+
+        if file::is_stdout(&self.output_video_file) {
+            // the output is not seekable so it is streamed as matroska unless another streamable container is set
+            // and faststart does not apply
+            let format = self.container.map(|container| container.ffmpeg_format()).unwrap_or(ffmpeg::STDOUT_CONTAINER_FORMAT);
+            ffmpeg_command.set_output_stdout(format);
+        } else {
+            if let Some(container) = self.container {
+                ffmpeg_command.add_args(&["-f", container.ffmpeg_format()]);
+            }
+            ffmpeg_command
+                .set_output_file(&self.output_video_file)
+                .set_overwrite_output_file(true);
+            if self.faststart {
+                ffmpeg_command.add_movflag("faststart");
+            }
+        }
+    }
+
+    /// maps the audio of the input video according to the audio mode, the audio filter being applied when re-encoding
+    fn add_ffmpeg_audio_output(&self, ffmpeg_command: &mut ffmpeg::CommandBuilder, has_audio: bool) -> Result<(), TranscodeVideoError> {
+        match (has_audio, self.audio) {
+            (false, _) if self.audio_fix.is_some() || ! self.audio_adjustments.is_empty() =>
+                return Err(TranscodeVideoError::RequestedAudioFixingButInputHasNoAudio),
+            (false, _) | (true, AudioMode::None) => {},
+            (true, AudioMode::Copy) => { ffmpeg_command.add_mapping("0:a").set_output_audio_codec(Some("copy")); },
+            (true, AudioMode::Reencode) => {
+                match self.audio_filter() {
+                    Some(audio_filter) => ffmpeg_command.add_mapping_with_audio_filter("0:a", &audio_filter),
+                    None => ffmpeg_command.add_mapping("0:a"),
+                };
+                ffmpeg_command.set_output_audio_settings(Some(&self.audio_encoder), Some(&self.audio_bitrate));
+            },
+        }
+        Ok(())
+    }
+
+    /// transcodes the video, burning the OSD onto it if OSD settings are set
+    pub async fn transcode(&self) -> Result<(), TranscodeVideoError> {
+        // the crop is detected once for the whole video, not for each chunk or card
+        self.with_detected_crop().await?.transcode_with_crop().await
+    }
+
+    /// transcoder cropping the black bars detected in the input video when autocrop is set
+    async fn with_detected_crop(&self) -> Result<Cow<'_, Self>, TranscodeVideoError> {
+        if ! self.autocrop { return Ok(Cow::Borrowed(self)) }
+        if self.undistort.is_some() {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot detect the black bars of a video whose distortion is corrected".to_owned()));
+        }
+        let video_info = probe(&self.input_video_file)?;
+        let (section_start, section_end) = self.section(&video_info)?;
+        let crop = autocrop::detect_crop(&self.input_video_file, video_info.resolution(), section_start, section_end,
+            &self.output_video_file).await?;
+        let mut transcoder = self.clone();
+        transcoder.set_crop(crop).set_autocrop(false);
+        Ok(Cow::Owned(transcoder))
+    }
+
+    async fn transcode_with_crop(&self) -> Result<(), TranscodeVideoError> {
+        if self.verify { self.check_verify()? }
+        if self.cards().next().is_some() {
+            self.transcode_with_cards().await?;
+        } else {
+            self.transcode_video().await?;
+        }
+        if ! self.strip_metadata && ! file::is_stdout(&self.output_video_file) && ! file::is_url(&self.input_video_file) {
+            super::preserve_modification_time(&self.input_video_file, &self.output_video_file);
+        }
+        if self.verify { self.verify_output().await? }
+        Ok(())
+    }
+
+    async fn transcode_video(&self) -> Result<(), TranscodeVideoError> {
+        match self.checkpoint_interval {
+            Some(checkpoint_interval) => self.transcode_checkpointed(checkpoint_interval).await,
+            None => self.transcode_in_one_go().await,
+        }
+    }
+
+    /// intro and outro cards which are set
+    fn cards(&self) -> impl Iterator<Item = &Card> {
+        self.intro_card.iter().chain(self.outro_card.iter())
+    }
+
+    /// total duration of the cards in seconds
+    fn cards_duration(&self) -> f64 {
+        self.cards().map(Card::duration).sum()
+    }
+
+    /// start and end in seconds of the section of the input video to transcode
+    fn section(&self, video_info: &probe::Result) -> Result<(f64, f64), TranscodeVideoError> {
+        let seconds = |timestamp: Timestamp| timestamp.total_milliseconds() as f64 / 1000.0;
+        let section_start = self.start.map(seconds).unwrap_or(0.0);
+        if let Some(start) = self.start {
+            if section_start >= video_info.duration() { return Err(TranscodeVideoError::TimestampAfterEndOfVideo(start)) }
+        }
+        let section_end = self.end.map(seconds).unwrap_or(f64::MAX).min(video_info.duration());
+        Ok((section_start, section_end))
+    }
+
+    /// checks the output video can be verified, its quality can only be compared with the input video when the
+    /// frames are not moved nor retimed
+    fn check_verify(&self) -> Result<(), TranscodeVideoError> {
+        if file::is_stdout(&self.output_video_file) {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot verify the video written to the standard output".to_owned()));
+        }
+        let geometry_changed = self.crop.is_some() || self.rotate.is_some() || self.hflip || self.vflip || self.undistort.is_some() ||
+            self.vertical.is_some();
+        if self.verify_quality.is_some() && (geometry_changed || self.speed.is_some()) {
+            return Err(TranscodeVideoError::IncompatibleArguments(
+                "cannot measure the quality against the input video when cropping, rotating, flipping, undistorting or changing the speed".to_owned()));
+        }
+        if self.verify_quality.is_some() && self.intro_card.is_some() {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot measure the quality against the input video with an intro card".to_owned()));
+        }
+        Ok(())
+    }
+
+    /// checks the output video against the section of the input video transcoded, see [`verify`]
+    async fn verify_output(&self) -> Result<(), TranscodeVideoError> {
+        let video_info = probe(&self.input_video_file)?;
+        let (section_start, section_end) = self.section(&video_info)?;
+        let frame_count = frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &self.start, &self.end);
+        let cards_frame_count = (self.cards_duration() * f64::from(video_info.frame_rate())).round() as u64;
+        let expected = verify::Expected::new((section_end - section_start) / self.speed.unwrap_or(1.0) + self.cards_duration(),
+            self.output_frame_count(frame_count) + cards_frame_count, video_info.has_audio() && self.audio != AudioMode::None);
+        let reference = verify::Reference { video_file: &self.input_video_file, start: section_start, duration: section_end - section_start };
+        verify::verify(&self.output_video_file, &expected, self.verify_quality.as_ref().map(|settings| (&reference, settings))).await?;
+        Ok(())
+    }
+
+    async fn transcode_in_one_go(&self) -> Result<(), TranscodeVideoError> {
+        match &self.osd {
+            Some(osd_settings) => self.transcode_burn_osd(osd_settings).await,
+            None => self.transcode_without_osd().await,
+        }
+    }
+
+    /// Transcodes the section in chunks of `checkpoint_interval` seconds written into a checkpoint directory next to
+    /// the output file, or in the temporary directory if set, then joins them without re-encoding
+    ///
+    /// The completed chunks are recorded in the state of the checkpoint directory so that when resuming only the
+    /// chunk being transcoded when the transcoding was interrupted and the following ones are transcoded. The
+    /// checkpoint directory is removed once the chunks are joined.
+    async fn transcode_checkpointed(&self, checkpoint_interval: f64) -> Result<(), TranscodeVideoError> {
+        if ! checkpoint_interval.is_finite() || checkpoint_interval <= 0.0 {
+            return Err(TranscodeVideoError::InvalidCheckpointInterval(checkpoint_interval));
+        }
+        if file::is_stdout(&self.output_video_file) {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot checkpoint the transcoding when writing to the standard output".to_owned()));
+        }
+        if matches!(&self.audio_fix, Some(fix) if fix.sync()) {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot fix video audio sync when transcoding in chunks".to_owned()));
+        }
+        if ! file::input_exists(&self.input_video_file) { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
+        if ! self.overwrite && self.output_video_file.exists() { return Err(TranscodeVideoError::OutputVideoFileExists); }
+        if self.input_video_file == self.output_video_file { return Err(TranscodeVideoError::InputAndOutputFileIsTheSame) }
+
+        let video_info = probe(&self.input_video_file)?;
+        let (section_start, section_end) = self.section(&video_info)?;
+        let chunks = checkpoint_chunks(section_start, section_end, checkpoint_interval, self.start, self.end);
+        let chunk_count = chunks.len();
+
+        temp_dir::check_available_space(&self.output_video_file,
+            temp_dir::estimated_size(&self.input_video_file, video_info.duration(), section_end - section_start))?;
+
+        let mut chunk_dir_name = OsString::from(".");
+        chunk_dir_name.push(self.output_video_file.file_name().unwrap_or_default());
+        chunk_dir_name.push(".chunks");
+        let chunk_dir = temp_dir::workspace_dir(&self.output_video_file).join(chunk_dir_name);
+        if ! crate::process::dry_run() {
+            fs_err::create_dir_all(&chunk_dir).map_err(TranscodeVideoError::FailedWritingCheckpoint)?;
+        }
+        let job_state = if self.resume { jobs::JobState::load(&chunk_dir)? } else { jobs::JobState::new(&chunk_dir) };
+
+        log::info!("transcoding video in {chunk_count} chunks of {checkpoint_interval:.0}s: {}", chunk_dir.to_string_lossy());
+        let mut scheduler = jobs::Scheduler::with_state(job_state);
+        let mut chunk_files = vec![];
+        for (chunk_index, chunk) in chunks.into_iter().enumerate() {
+            let chunk_file = chunk_dir.join(format!("chunk{:04}.mkv", chunk_index + 1));
+            let mut chunk_transcoder = self.clone();
+            chunk_transcoder
+                .set_output_video_file(chunk_file.clone())
+                .set_overwrite(true)
+                .set_container(Some(Container::Mkv))
+                .set_faststart(false)
+                .set_checkpoint_interval(None)
+                .set_start(chunk.start)
+                .set_end(chunk.end);
+            let expected_duration = chunk.duration / self.speed.unwrap_or(1.0);
+            chunk_files.push(chunk_file.clone());
+            scheduler.add_with_output(chunk_file, Some(expected_duration), async move { chunk_transcoder.transcode_in_one_go().await });
+        }
+        scheduler.run_all_ok().await?;
+
+        log::info!("joining {chunk_count} chunks: {}", self.output_video_file.to_string_lossy());
+        let frame_count = frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &self.start, &self.end);
+        let concat_list_file = chunk_dir.join("concat.txt");
+        cut::write_concat_list(&concat_list_file, &chunk_files).map_err(TranscodeVideoError::FailedWritingCheckpoint)?;
+        self.join(&concat_list_file, self.output_frame_count(frame_count)).await?;
+
+        if ! crate::process::dry_run() {
+            if let Err(error) = fs_err::remove_dir_all(&chunk_dir) {
+                log::warn!("failed removing checkpoint directory: {error}");
+            }
+        }
+        log::info!("{frame_count} frames transcoded successfully");
+        Ok(())
+    }
+
+    /// joins the files of the concat list into the output file without re-encoding them, the metadata being copied
+    /// from the input video
+    async fn join(&self, concat_list_file: &Path, frame_count: u64) -> Result<(), TranscodeVideoError> {
+        file::touch(&self.output_video_file)?;
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command
+            .add_concat_list_input(concat_list_file)
+            .add_mapping("0")
+            .set_output_video_codec(Some("copy"))
+            .set_output_audio_codec(Some("copy"));
+        // the metadata were copied from the input video into the joined files but the concat demuxer does not provide them
+        let metadata_from_input = ! self.strip_metadata && ! file::is_url(&self.input_video_file);
+        if metadata_from_input { ffmpeg_command.add_input_file(&self.input_video_file); }
+        cut::set_metadata(&mut ffmpeg_command, 1, ! metadata_from_input);
+        if let Some(container) = self.container {
+            ffmpeg_command.add_args(&["-f", container.ffmpeg_format()]);
+        }
+        ffmpeg_command
+            .set_output_file(&self.output_video_file)
+            .set_overwrite_output_file(true);
+        if self.faststart {
+            ffmpeg_command.add_movflag("faststart");
+        }
+        ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
+        Ok(())
+    }
+
+    /// Transcodes the video into a temporary file then encodes the cards with the same encoding settings, the
+    /// resolution, frame rate and audio format of the transcoded video, and joins them without re-encoding
+    async fn transcode_with_cards(&self) -> Result<(), TranscodeVideoError> {
+        if file::is_stdout(&self.output_video_file) {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot add cards when writing to the standard output".to_owned()));
+        }
+        if self.gpu == Some(GpuApi::Vaapi) {
+            return Err(TranscodeVideoError::IncompatibleArguments("cannot encode the cards with the VAAPI GPU pipeline".to_owned()));
+        }
+        for card in self.cards() {
+            if card.duration() <= 0.0 || ! card.duration().is_finite() { return Err(TranscodeVideoError::InvalidCardDuration(card.duration())) }
+            if let CardContent::Image(image_file) = card.content() {
+                if ! image_file.is_file() { return Err(TranscodeVideoError::CardImageDoesNotExist(image_file.clone())) }
+            }
+        }
+        if ! self.overwrite && self.output_video_file.exists() { return Err(TranscodeVideoError::OutputVideoFileExists); }
+        if self.input_video_file == self.output_video_file { return Err(TranscodeVideoError::InputAndOutputFileIsTheSame) }
+        let cards_transcoder = self.with_flight_stats_cards()?;
+
+        let temp_files = TempFiles(vec![
+            cut::temp_file_path(&self.output_video_file, ".main.mkv"),
+            cut::temp_file_path(&self.output_video_file, ".intro.mkv"),
+            cut::temp_file_path(&self.output_video_file, ".outro.mkv"),
+            cut::temp_file_path(&self.output_video_file, ".cards.txt"),
+        ]);
+        let [main_file, intro_file, outro_file, concat_list_file] = [0, 1, 2, 3].map(|index| temp_files.0[index].clone());
+
+        let mut video_transcoder = self.clone();
+        video_transcoder
+            .set_output_video_file(main_file.clone())
+            .set_overwrite(true)
+            .set_container(Some(Container::Mkv))
+            .set_faststart(false)
+            .set_intro_card(None)
+            .set_outro_card(None)
+            .set_verify(false);
+        video_transcoder.transcode_video().await?;
+
+        // nothing is transcoded in dry run mode, the cards commands are shown with the input video format
+        let video_info = probe(if crate::process::dry_run() { &self.input_video_file } else { &main_file })?;
+        let mut files = vec![];
+        if let Some(intro_card) = &cards_transcoder.intro_card {
+            self.encode_card(intro_card, &intro_file, &video_info).await?;
+            files.push(intro_file);
+        }
+        files.push(main_file);
+        if let Some(outro_card) = &cards_transcoder.outro_card {
+            self.encode_card(outro_card, &outro_file, &video_info).await?;
+            files.push(outro_file);
+        }
+
+        log::info!("joining the cards and the video: {}", self.output_video_file.to_string_lossy());
+        cut::write_concat_list(&concat_list_file, &files).map_err(TranscodeVideoError::FailedWritingTempFile)?;
+        let cards_frame_count = (self.cards_duration() * f64::from(video_info.frame_rate())).round() as u64;
+        self.join(&concat_list_file, video_info.frame_count() + cards_frame_count).await
+    }
+
+    /// transcoder whose flight stats cards are replaced by the text cards of the stats of the flights within the
+    /// transcoded section, read from the OSD file
+    fn with_flight_stats_cards(&self) -> Result<Cow<'_, Self>, TranscodeVideoError> {
+        if ! self.cards().any(|card| *card.content() == CardContent::FlightStats) { return Ok(Cow::Borrowed(self)) }
+        let osd_settings = self.osd.as_ref().ok_or_else(||
+            TranscodeVideoError::IncompatibleArguments("the flight stats card needs an OSD file".to_owned()))?;
+        let frames = osd::file::open(osd_settings.osd_file())?.frames()?;
+        // the video frames are shifted by this number of OSD frames
+        let osd_frame_shift = osd_settings.frame_shift_for_video(&probe(&self.input_video_file)?) as i64;
+        let osd_frame_index = |timestamp: Timestamp|
+            ((timestamp.total_milliseconds() * osd::FRAME_RATE as u64 / 1000) as i64 - osd_frame_shift).clamp(0, u32::MAX as i64) as u32;
+        let first_frame_index = self.start.map(osd_frame_index).unwrap_or(0);
+        let last_frame_index = self.end.map(osd_frame_index).unwrap_or(u32::MAX);
+        let flight_stats = FlightStats::extract(&frames, frames.kind(), frames.font_variant(), first_frame_index, last_frame_index)?;
+        if flight_stats.is_empty() { return Err(TranscodeVideoError::NoFlightStats) }
+        log::info!("flight stats: {}", flight_stats.summary_lines().join(", "));
+        let mut transcoder = self.clone();
+        transcoder.intro_card = self.intro_card.as_ref().map(|card| card.with_flight_stats(&flight_stats));
+        transcoder.outro_card = self.outro_card.as_ref().map(|card| card.with_flight_stats(&flight_stats));
+        Ok(Cow::Owned(transcoder))
+    }
+
+    /// encodes the card into the file with the encoding settings and the format of the video
+    async fn encode_card(&self, card: &Card, card_file: &Path, video_info: &probe::Result) -> Result<(), TranscodeVideoError> {
+        log::info!("encoding {:.1}s card: {}", card.duration(), card_file.to_string_lossy());
+        let audio = video_info.audio().as_ref();
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        card.add_to_ffmpeg_command(&mut ffmpeg_command, video_info)?;
+        let mut card_transcoder = self.clone();
+        card_transcoder
+            .set_output_video_file(card_file.to_path_buf())
+            .set_container(Some(Container::Mkv))
+            .set_faststart(false)
+            .set_strip_metadata(true);
+        card_transcoder.set_ffmpeg_output_settings(&mut ffmpeg_command, video_info.frame_rate());
+        if let Some(audio) = audio {
+            // the audio of the video is the copied audio of the input video unless re-encoded
+            let (encoder, bitrate) = match (self.audio, audio.codec()) {
+                (AudioMode::Copy, Some(codec)) => (codec.clone(), audio.bitrate().map(|bitrate| bitrate.to_string())),
+                _ => (self.audio_encoder.clone(), Some(self.audio_bitrate.clone())),
+            };
+            ffmpeg_command.set_output_audio_settings(Some(&encoder), bitrate.as_deref());
+        }
+        let frame_count = (card.duration() * f64::from(video_info.frame_rate())).round() as u64;
+        ffmpeg_command.build().unwrap().spawn_with_progress(frame_count)?.wait().await?;
+        Ok(())
+    }
+
+    /// estimates the encoding time and output size by transcoding `sample_duration` seconds from the middle of the
+    /// section to transcode with the same settings, the sample is written to a temporary file removed afterwards
+    ///
+    /// The audio fix is not applied to the sample since the audio sync can only be fixed from the beginning of the video.
+    pub async fn estimate(&self, sample_duration: f64) -> Result<Estimate, TranscodeVideoError> {
+        if ! file::input_exists(&self.input_video_file) { return Err(TranscodeVideoError::InputVideoFileDoesNotExist); }
+        let video_info = probe(&self.input_video_file)?;
+        let (section_start, section_end) = self.section(&video_info)?;
+        let duration = section_end - section_start;
+        let sample_duration = sample_duration.min(duration);
+        let sample_start = section_start + (duration - sample_duration) / 2.0;
+        temp_dir::check_available_space(&self.output_video_file,
+            temp_dir::estimated_size(&self.input_video_file, video_info.duration(), sample_duration))?;
+
+        let extension = self.output_container().map(|container| container.extension()).unwrap_or("mkv");
+        let temp_files = TempFiles(vec![cut::temp_file_path(&self.output_video_file, &format!(".estimate.{extension}"))]);
+        let sample_file = &temp_files.0[0];
+        let mut sample_transcoder = self.clone();
+        sample_transcoder
+            .set_output_video_file(sample_file.clone())
+            .set_overwrite(true)
+            .set_start(Some(Timestamp::from_milliseconds((sample_start * 1000.0) as u64)))
+            .set_end(Some(Timestamp::from_milliseconds(((sample_start + sample_duration) * 1000.0) as u64)))
+            .set_audio_fix(None)
+            .set_checkpoint_interval(None)
+            .set_verify(false)
+            .set_intro_card(None)
+            .set_outro_card(None);
+
+        log::info!("transcoding a {sample_duration:.1}s sample to estimate the encoding time and output size");
+        let sample_start_time = Instant::now();
+        sample_transcoder.transcode().await?;
+        Ok(Estimate::from_sample(sample_file, sample_duration, sample_start_time.elapsed(), duration))
+    }
+
+    async fn transcode_without_osd(&self) -> Result<(), TranscodeVideoError> {
+
+        self.check_before_transcoding()?;
+
+        log::info!("transcoding video: {} -> {}", self.input_video_file.to_string_lossy(), self.output_video_file.to_string_lossy());
+
+        let video_info = probe(&self.input_video_file)?;
+        let frame_count = frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &self.start, &self.end);
+
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+
+        if let Some(gpu) = self.gpu {
+            let (_, output_resolution) = self.video_filters(video_info.resolution())?;
+            ffmpeg_command
+                .add_gpu_decoded_input_file_slice(&self.input_video_file, self.start, self.end, gpu.hwaccel())
+                .add_complex_filter(&self.gpu_filter_graph(gpu, video_info.resolution(), output_resolution, None))
+                .add_mapping("[vo]");
+            self.set_ffmpeg_output_settings(&mut ffmpeg_command, video_info.frame_rate());
+            self.add_ffmpeg_audio_output(&mut ffmpeg_command, video_info.has_audio())?;
+            log::info!("decoding, filtering and encoding on the GPU with {gpu}");
+            ffmpeg_command.build().unwrap().spawn_with_progress(self.output_frame_count(frame_count))?.wait().await?;
+            log::info!("{frame_count} frames transcoded successfully");
+            return Ok(());
+        }
+
+        ffmpeg_command.add_hw_decoded_input_file_slice(&self.input_video_file, self.start, self.end, self.hw_decode);
+        self.set_ffmpeg_output_settings(&mut ffmpeg_command, video_info.frame_rate());
+
+        let (mut video_filters, mut output_resolution) = self.video_filters(video_info.resolution())?;
+        if let Some(vertical_window) = self.vertical_window(output_resolution) {
+            video_filters.push(vertical_window.ffmpeg_filter());
+            output_resolution = vertical_window.resolution();
+        }
+        if output_resolution != video_info.resolution() {
+            log::info!("output video resolution: {output_resolution}");
+        }
+        // the layers are kept until the end of the transcoding, they may own temporary files read by FFMpeg
+        let layer_pipeline = self.layer_pipeline(&video_info, output_resolution, None)?;
+        match self.complex_filter("[0]", video_filters, &layer_pipeline, output_resolution, &video_info) {
+            Some(complex_filter) => ffmpeg_command.add_complex_filter(&complex_filter).add_mapping("[vo]"),
+            None => ffmpeg_command.add_mapping("0:v:0"),
+        };
+        self.add_ffmpeg_audio_output(&mut ffmpeg_command, video_info.has_audio())?;
+
+        ffmpeg_command.build().unwrap().spawn_with_progress(self.output_frame_count(frame_count))?.wait().await?;
+
+        log::info!("{frame_count} frames transcoded successfully");
+        Ok(())
+    }
+
+    async fn transcode_burn_osd(&self, osd_settings: &OSDSettings) -> Result<(), TranscodeVideoError> {
+
+        self.check_before_transcoding()?;
+
+        let video_info = probe(&self.input_video_file)?;
+
+        let osd_frame_shift = osd_settings.frame_shift_for_video(&video_info);
+        let osd_settings = osd_settings.with_detected_arm_beep(&self.input_video_file, osd_frame_shift).await?;
+
+        log::info!("transcoding video: {} -> {}", self.input_video_file.to_string_lossy(), self.output_video_file.to_string_lossy());
+
+        // the OSD is laid out on the video before the vertical crop
+        let (mut video_filters, osd_layout_resolution) = self.video_filters(video_info.resolution())?;
+        if osd_layout_resolution != video_info.resolution() {
+            log::info!("rendering OSD at {osd_layout_resolution} video resolution");
+        }
+        let vertical_window = self.vertical_window(osd_layout_resolution);
+        let output_resolution = match vertical_window {
+            Some(vertical_window) => {
+                video_filters.push(vertical_window.ffmpeg_filter());
+                vertical_window.resolution()
+            },
+            None => osd_layout_resolution,
+        };
+        if output_resolution != video_info.resolution() {
+            log::info!("output video resolution: {output_resolution}");
+        }
+
+        if osd_settings.is_composed() && (vertical_window.is_some() || self.gpu.is_some()) {
+            return Err(TranscodeVideoError::IncompatibleArguments(
+                "cannot draw several OSD files or an OSD file into an area of a vertical video or on the GPU".to_owned()));
+        }
+        // each OSD file is scaled to fit its area then centered in it
+        let mut osd_sources = vec![];
+        for source_settings in osd_settings.source_settings() {
+            let (left, top, area_resolution) = match source_settings.area() {
+                Some(area) => area.region(osd_layout_resolution),
+                None => (0, 0, osd_layout_resolution),
+            };
+            let mut frames_generator = source_settings.overlay_generator(area_resolution)?;
+            frames_generator.set_layer_configs(&self.layers);
+            let frame_dimensions = frames_generator.frame_dimensions();
+            let position = (
+                left + area_resolution.width.saturating_sub(frame_dimensions.width) / 2,
+                top + area_resolution.height.saturating_sub(frame_dimensions.height) / 2,
+            );
+            let frame_shift = if osd_sources.is_empty() { osd_frame_shift } else { source_settings.frame_shift_for_video(&video_info) };
+            osd_sources.push((frames_generator, position, frame_shift));
+        }
+        let mut osd_layer = OsdLayer::new("[1]");
+        if let Some(vertical_window) = vertical_window {
+            let osd_frames_generator = &mut osd_sources[0].0;
+            let osd_left = (osd_layout_resolution.width as i64 - osd_frames_generator.frame_dimensions().width as i64) / 2;
+            osd_frames_generator.set_visible_area(vertical_window.left() as i64 - osd_left, vertical_window.width());
+            osd_layer.set_left(Some(osd_left - vertical_window.left() as i64));
+        }
+
+        let frame_count = frame_count_for_interval(video_info.frame_count(), video_info.frame_rate(), &self.start, &self.end);
+        log::debug!("frame count: video={}, transcode={}", video_info.frame_count(), frame_count);
+
+        // the OSD frames are overlaid onto the video frames according to their timestamps
+        let osd_frame_rate = Rational::new(osd::FRAME_RATE as i32, 1);
+        let video_duration_frames = (video_info.duration() * osd::FRAME_RATE as f64).ceil() as u32;
+        let first_frame_index = self.start.map(|tstamp| tstamp.frame_count(osd_frame_rate) as u32).unwrap_or(0);
+        let last_frame_index = self.end.map(|end| end.frame_count(osd_frame_rate) as u32).unwrap_or(video_duration_frames);
+        let osd_overlay_resolution = if osd_settings.is_composed() { osd_layout_resolution } else { osd_sources[0].0.frame_dimensions() };
+        let video_frame_rate = video_info.frame_rate().numerator() as f64 / video_info.frame_rate().denominator() as f64;
+        let osd_input_frame_rate = InterpolatedFramesIter::frame_rate_for_video(video_frame_rate, osd_settings.interpolate);
+        let mut osd_frames_iters = osd_sources.iter().map(|(osd_frames_generator, position, frame_shift)| (InterpolatedFramesIter::new(
+            osd_frames_generator.iter_advanced(first_frame_index, Some(last_frame_index), *frame_shift),
+            osd_input_frame_rate,
+            osd_settings.interpolate
+        ), *position)).collect::<Vec<_>>();
+
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+
+        // the layers are kept until the end of the transcoding, they may own temporary files read by FFMpeg
+        let mut _layer_pipeline = None;
+        let complex_filter = match self.gpu {
+            Some(gpu) => {
+                log::info!("decoding, overlaying the OSD and encoding on the GPU with {gpu}");
+                ffmpeg_command.add_gpu_decoded_input_file_slice(&self.input_video_file, self.start, self.end, gpu.hwaccel());
+                self.gpu_filter_graph(gpu, video_info.resolution(), output_resolution, Some(osd_overlay_resolution))
+            },
+            None => {
+                let layer_pipeline = self.layer_pipeline(&video_info, output_resolution, Some(osd_layer))?;
+                let complex_filter = self.complex_filter("[0]", video_filters, &layer_pipeline, output_resolution, &video_info).unwrap();
+                _layer_pipeline = Some(layer_pipeline);
+                ffmpeg_command.add_hw_decoded_input_file_slice(&self.input_video_file, self.start, self.end, self.hw_decode);
+                complex_filter
+            },
+        };
+
+        ffmpeg_command
+            .add_stdin_input(osd_overlay_resolution, osd_input_frame_rate).unwrap()
+            .add_complex_filter(&complex_filter)
+            .add_mapping("[vo]");
+        self.set_ffmpeg_output_settings(&mut ffmpeg_command, video_info.frame_rate());
+
+        self.add_ffmpeg_audio_output(&mut ffmpeg_command, video_info.has_audio())?;
+
+        let ffmpeg_process = ffmpeg_command.build().unwrap().spawn_with_progress(self.output_frame_count(frame_count))?;
+
+        if osd_settings.is_composed() {
+            log::info!("composing {} OSD files", osd_frames_iters.len());
+            ComposedFramesIter::new(osd_overlay_resolution, osd_frames_iters).send_frames_to_ffmpeg_and_wait(ffmpeg_process).await?;
+        } else {
+            osd_frames_iters.remove(0).0.send_frames_to_ffmpeg_and_wait(ffmpeg_process).await?;
+        }
+
+        log::info!("{frame_count} frames transcoded successfully");
+        Ok(())
+    }
+
+}
+
+/// Section of the video transcoded into a chunk when checkpointing
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CheckpointChunk {
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+    /// duration in seconds
+    duration: f64,
+}
+
+/// splits the section between `section_start` and `section_end` seconds into chunks of `checkpoint_interval` seconds,
+/// the first chunk starts at the `start` of the transcoding and the last one goes to its `end`, which may be a little
+/// after the probed duration
+fn checkpoint_chunks(section_start: f64, section_end: f64, checkpoint_interval: f64, start: Option<Timestamp>, end: Option<Timestamp>) -> Vec<CheckpointChunk> {
+    let timestamp = |seconds: f64| Timestamp::from_milliseconds((seconds * 1000.0) as u64);
+    let chunk_count = ((section_end - section_start) / checkpoint_interval).ceil().max(1.0) as usize;
+    (0..chunk_count).map(|chunk_index| {
+        let chunk_start = section_start + chunk_index as f64 * checkpoint_interval;
+        let chunk_end = (chunk_start + checkpoint_interval).min(section_end);
+        CheckpointChunk {
+            start: if chunk_index > 0 { Some(timestamp(chunk_start)) } else { start },
+            end: if chunk_index + 1 < chunk_count { Some(timestamp(chunk_end)) } else { end },
+            duration: chunk_end - chunk_start,
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(start: Option<u64>, end: Option<u64>, duration: f64) -> CheckpointChunk {
+        CheckpointChunk { start: start.map(Timestamp::from_milliseconds), end: end.map(Timestamp::from_milliseconds), duration }
+    }
+
+    #[test]
+    fn checkpoint_chunks_of_the_whole_video() {
+        assert_eq!(checkpoint_chunks(0.0, 25.0, 10.0, None, None),
+            vec![chunk(None, Some(10_000), 10.0), chunk(Some(10_000), Some(20_000), 10.0), chunk(Some(20_000), None, 5.0)]);
+        assert_eq!(checkpoint_chunks(0.0, 20.0, 10.0, None, None),
+            vec![chunk(None, Some(10_000), 10.0), chunk(Some(10_000), None, 10.0)]);
+    }
+
+    #[test]
+    fn checkpoint_chunks_of_a_section() {
+        let (start, end) = (Some(Timestamp::from_milliseconds(5_000)), Some(Timestamp::from_milliseconds(30_000)));
+        assert_eq!(checkpoint_chunks(5.0, 30.0, 10.0, start, end),
+            vec![chunk(Some(5_000), Some(15_000), 10.0), chunk(Some(15_000), Some(25_000), 10.0), chunk(Some(25_000), Some(30_000), 5.0)]);
+        // a section shorter than the interval is transcoded in one chunk
+        assert_eq!(checkpoint_chunks(5.0, 8.0, 10.0, start, None), vec![chunk(Some(5_000), None, 3.0)]);
+    }
+
+    #[test]
+    fn crop_dimensions_rounded_to_even() {
+        let mut transcoder = Transcoder::new("input.mp4", "output.mp4");
+        transcoder.set_crop(Some(Region::new4(10, 20, 1279, 719)));
+        let (filters, resolution) = transcoder.video_filters(Resolution::new(1920, 1080)).unwrap();
+        assert_eq!(filters, vec!["crop=x=10:y=20:w=1278:h=718".to_owned()]);
+        assert_eq!((resolution.width, resolution.height), (1278, 718));
+    }
+
+    #[test]
+    fn deinterlaced_first_and_denoised_after_the_geometry_filters() {
+        let mut transcoder = Transcoder::new("input.mp4", "output.mp4");
+        transcoder
+            .set_deinterlace(true)
+            .set_rotate(Some(VideoRotation::Rotate90))
+            .set_denoise(Some(4.0))
+            .set_denoise_filter(DenoiseFilter::Nlmeans);
+        let (filters, resolution) = transcoder.video_filters(Resolution::new(1920, 1080)).unwrap();
+        assert_eq!(filters, vec!["bwdif=mode=send_frame".to_owned(), "transpose=clock".to_owned(), "nlmeans=s=4".to_owned()]);
+        assert_eq!((resolution.width, resolution.height), (1080, 1920));
+
+        transcoder.set_denoise(Some(0.0));
+        assert!(matches!(transcoder.video_filters(Resolution::new(1920, 1080)), Err(TranscodeVideoError::InvalidDenoiseStrength(_))));
+    }
+
+    #[test]
+    fn denoise_filter_strings() {
+        assert_eq!(DenoiseFilter::Hqdn3d.ffmpeg_filter_string(4.0), "hqdn3d=luma_spatial=4");
+        assert_eq!(DenoiseFilter::Nlmeans.ffmpeg_filter_string(1.5), "nlmeans=s=1.5");
+    }
+
+    #[test]
+    fn other_sources_frame_shift_defaults_to_the_global_one() {
+        let mut osd_settings = OSDSettings::new("DJIG0000.osd", "fonts");
+        osd_settings
+            .set_frame_shift(Some(FrameShift::Frames(-3)))
+            .set_other_sources(vec!["DJIG0001.osd|area=right".parse().unwrap(), "DJIG0002.osd|shift=120ms".parse().unwrap()]);
+        let frame_shifts = osd_settings.source_settings().iter().map(OSDSettings::frame_shift).collect::<Vec<_>>();
+        assert_eq!(frame_shifts, vec![Some(FrameShift::Frames(-3)), Some(FrameShift::Frames(-3)), Some(FrameShift::Milliseconds(120))]);
+    }
+
+}
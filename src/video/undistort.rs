@@ -0,0 +1,108 @@
+
+//! Lens distortion correction of the camera video, applied before the OSD is overlaid since the OSD is not distorted
+
+use std::str::FromStr;
+
+use strum::VariantNames;
+use thiserror::Error;
+
+
+#[derive(Debug, Error)]
+#[error("invalid lens profile: {0}, expected one of {} or custom coefficients <k1>,<k2>",
+    LensPreset::VARIANTS.join(", "))]
+pub struct InvalidLensProfileError(String);
+
+/// Cameras with known lens distortion coefficients
+///
+/// The coefficients are approximations for the stock lenses, custom coefficients can be used for other lenses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::EnumVariantNames)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum LensPreset {
+    /// DJI FPV Air Unit and Caddx Vista
+    DjiAirUnit,
+    /// DJI O3 Air Unit
+    DjiO3,
+    /// Walksnail Avatar HD camera
+    Walksnail,
+    /// GoPro in wide mode
+    GoproWide,
+}
+
+impl LensPreset {
+
+    /// k1 and k2 coefficients of the FFMpeg lenscorrection filter
+    fn coefficients(&self) -> (f64, f64) {
+        use LensPreset::*;
+        match self {
+            DjiAirUnit => (-0.28, 0.06),
+            DjiO3 => (-0.22, 0.02),
+            Walksnail => (-0.25, 0.03),
+            GoproWide => (-0.227, -0.022),
+        }
+    }
+
+}
+
+/// Lens profile, parsed from a preset name or from custom `<k1>,<k2>` coefficients
+///
+/// The coefficients are the quadratic and quartic radial distortion coefficients of the FFMpeg lenscorrection filter
+/// with the distances normalized to half the video diagonal, negative values correct the barrel distortion of the wide
+/// angle FPV lenses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LensProfile {
+    Preset(LensPreset),
+    Custom { k1: f64, k2: f64 },
+}
+
+impl LensProfile {
+
+    pub fn coefficients(&self) -> (f64, f64) {
+        match self {
+            Self::Preset(preset) => preset.coefficients(),
+            Self::Custom { k1, k2 } => (*k1, *k2),
+        }
+    }
+
+    /// FFMpeg filter correcting the distortion, the center of the distortion being the center of the video
+    pub fn ffmpeg_filter(&self) -> String {
+        let (k1, k2) = self.coefficients();
+        format!("lenscorrection=cx=0.5:cy=0.5:k1={k1}:k2={k2}:i=bilinear")
+    }
+
+}
+
+impl FromStr for LensProfile {
+    type Err = InvalidLensProfileError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(preset) = LensPreset::from_str(value) {
+            return Ok(Self::Preset(preset));
+        }
+        let error = || InvalidLensProfileError(value.to_owned());
+        let (k1, k2) = value.split_once(',').ok_or_else(error)?;
+        let coefficient = |text: &str| text.trim().parse::<f64>().ok().filter(|coefficient| coefficient.is_finite() && coefficient.abs() <= 1.0);
+        Ok(Self::Custom { k1: coefficient(k1).ok_or_else(error)?, k2: coefficient(k2).ok_or_else(error)? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn profiles_parsed_from_presets_and_coefficients() {
+        assert_eq!(LensProfile::from_str("DJI-O3").unwrap(), LensProfile::Preset(LensPreset::DjiO3));
+        assert_eq!(LensProfile::from_str("-0.3, 0.05").unwrap(), LensProfile::Custom { k1: -0.3, k2: 0.05 });
+        for invalid in ["fisheye", "-0.3", "-1.5,0", "NaN,0", "-0.3,x"] {
+            assert!(LensProfile::from_str(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn lenscorrection_filter() {
+        assert_eq!(LensProfile::Preset(LensPreset::DjiAirUnit).ffmpeg_filter(), "lenscorrection=cx=0.5:cy=0.5:k1=-0.28:k2=0.06:i=bilinear");
+        assert_eq!(LensProfile::Custom { k1: -0.2, k2: 0.0 }.ffmpeg_filter(), "lenscorrection=cx=0.5:cy=0.5:k1=-0.2:k2=0:i=bilinear");
+    }
+
+}
@@ -0,0 +1,295 @@
+
+//! Verification of a transcoded video against what was expected from the transcoding
+//!
+//! The output video is probed to check its duration, its number of frames and the sync of its audio stream: the
+//! start and end of the audio must be within [`MAX_AUDIO_DRIFT`] of the video. The container does not always record
+//! the number of frames and the duration of the streams, e.g. MKV files, the checks needing them are then skipped.
+//!
+//! The quality of the encoding can also be measured with the SSIM or VMAF metric on a few short samples spread over
+//! the transcoded section, the source video being scaled to the output resolution. The mean score of each sample must reach the
+//! minimum score. VMAF requires FFMpeg to be built with `libvmaf`.
+
+use std::{io::Error as IOError, path::Path};
+
+use derive_more::From;
+use getset::CopyGetters;
+use thiserror::Error;
+
+use crate::ffmpeg;
+
+use super::{Timestamp, cut::{self, TempFiles}, probe};
+
+
+/// number of samples whose quality is measured by default
+pub const DEFAULT_SAMPLE_COUNT: usize = 5;
+
+/// duration of the samples whose quality is measured in seconds
+const SAMPLE_DURATION: f64 = 2.0;
+
+const DURATION_TOLERANCE_SECONDS: f64 = 0.5;
+
+/// tolerated difference between the number of frames and the expected number of frames relatively to it
+const FRAME_COUNT_TOLERANCE: f64 = 0.01;
+
+/// maximum offset in seconds between the start or end of the audio stream and the video stream
+pub const MAX_AUDIO_DRIFT: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum QualityMetric {
+    /// structural similarity, from 0 to 1
+    Ssim,
+    /// Netflix video multi-method assessment fusion, from 0 to 100, needs FFMpeg built with libvmaf
+    Vmaf,
+}
+
+impl QualityMetric {
+
+    /// score under which a sample is considered badly encoded
+    pub fn default_min_score(&self) -> f64 {
+        match self {
+            Self::Ssim => 0.9,
+            Self::Vmaf => 80.0,
+        }
+    }
+
+    /// filter comparing the distorted video with the reference video and writing the score of each frame to the
+    /// statistics file
+    fn filter(&self, stats_file: &Path) -> String {
+        let stats_file = ffmpeg::escape_filter_option_value(&stats_file.to_string_lossy());
+        match self {
+            Self::Ssim => format!("ssim=stats_file={stats_file}"),
+            Self::Vmaf => format!("libvmaf=log_fmt=csv:log_path={stats_file}"),
+        }
+    }
+
+    /// scores of the frames read from the statistics file
+    fn parse_scores(&self, stats: &str) -> Vec<f64> {
+        match self {
+            // n:1 Y:0.981 U:0.990 V:0.989 All:0.984 (17.962)
+            Self::Ssim => stats.lines()
+                .filter_map(|line| line.split_whitespace().find_map(|field| field.strip_prefix("All:")))
+                .filter_map(|score| score.parse().ok())
+                .collect(),
+            // CSV with a header line, the score being in the `vmaf` column
+            Self::Vmaf => {
+                let mut lines = stats.lines();
+                let column = match lines.next().and_then(|header| header.split(',').position(|name| name.trim() == "vmaf")) {
+                    Some(column) => column,
+                    None => return vec![],
+                };
+                lines.filter_map(|line| line.split(',').nth(column)).filter_map(|score| score.trim().parse().ok()).collect()
+            },
+        }
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct QualitySettings {
+    metric: QualityMetric,
+    min_score: f64,
+    sample_count: usize,
+}
+
+impl QualitySettings {
+    pub fn new(metric: QualityMetric, min_score: Option<f64>) -> Self {
+        Self { metric, min_score: min_score.unwrap_or_else(|| metric.default_min_score()), sample_count: DEFAULT_SAMPLE_COUNT }
+    }
+}
+
+#[derive(Debug, Error, From)]
+pub enum VerifyError {
+    #[error("failed probing output video: {0}")]
+    ProbeError(probe::Error),
+    #[error(transparent)]
+    FailedSpawningFFMpegProcess(ffmpeg::SpawnError),
+    #[error(transparent)]
+    FFMpegExitedWithError(ffmpeg::ProcessError),
+    #[error("failed reading {0} statistics: {1}")]
+    #[from(ignore)]
+    StatsFileError(QualityMetric, IOError),
+    #[error("no {0} score computed, is FFMpeg built with the needed filter?")]
+    #[from(ignore)]
+    NoScore(QualityMetric),
+    #[error("output video verification failed:\n  {}", .0.join("\n  "))]
+    #[from(ignore)]
+    Failed(Vec<String>),
+}
+
+/// What the output video is expected to be
+#[derive(Debug, Clone, Copy, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct Expected {
+    /// duration in seconds
+    duration: f64,
+    frame_count: u64,
+    has_audio: bool,
+}
+
+impl Expected {
+    pub fn new(duration: f64, frame_count: u64, has_audio: bool) -> Self {
+        Self { duration, frame_count, has_audio }
+    }
+}
+
+/// Source video to which the quality of the output video is compared, the output video starting with the `duration`
+/// seconds of the source video from `start`, the cards added after them not being compared
+pub struct Reference<'a> {
+    pub video_file: &'a Path,
+    pub start: f64,
+    pub duration: f64,
+}
+
+/// problems found in the streams of the output video
+fn check_streams(output_info: &probe::Result, expected: &Expected) -> Vec<String> {
+    let mut problems = vec![];
+
+    if (output_info.duration() - expected.duration).abs() > DURATION_TOLERANCE_SECONDS {
+        problems.push(format!("duration is {:.3}s, expected {:.3}s", output_info.duration(), expected.duration));
+    }
+
+    match output_info.frame_count() {
+        0 => log::info!("the output video does not record its number of frames, it is not checked"),
+        frame_count => {
+            let tolerance = ((expected.frame_count as f64 * FRAME_COUNT_TOLERANCE) as u64).max(2);
+            if frame_count.abs_diff(expected.frame_count) > tolerance {
+                problems.push(format!("{frame_count} frames, expected {}", expected.frame_count));
+            }
+        },
+    }
+
+    match (output_info.audio(), expected.has_audio) {
+        (None, true) => problems.push("no audio stream, expected one".to_owned()),
+        (Some(_), false) => problems.push("unexpected audio stream".to_owned()),
+        (Some(audio), true) => {
+            if audio.start_offset().abs() > MAX_AUDIO_DRIFT {
+                problems.push(format!("audio starts {:+.3}s from the video", audio.start_offset()));
+            }
+            match (audio.duration(), output_info.video_duration()) {
+                (Some(audio_duration), Some(video_duration)) => {
+                    let end_drift = audio.start_offset() + audio_duration - video_duration;
+                    if end_drift.abs() > MAX_AUDIO_DRIFT {
+                        problems.push(format!("audio ends {end_drift:+.3}s from the video, the audio drifts"));
+                    }
+                },
+                _ => log::info!("the output video does not record the durations of its streams, the audio drift is not checked"),
+            }
+        },
+        (None, false) => {},
+    }
+
+    problems
+}
+
+/// start in seconds of the samples of `sample_duration` seconds spread over the section of `duration` seconds
+fn sample_starts(duration: f64, sample_duration: f64, sample_count: usize) -> Vec<f64> {
+    let sample_count = sample_count.max(1);
+    (0..sample_count).map(|sample_index| (duration - sample_duration) * (sample_index as f64 + 0.5) / sample_count as f64).collect()
+}
+
+/// mean score of each sample of the output video compared with the reference, the samples being spread over the
+/// section of the output video coming from the reference
+async fn sample_scores(output_video_file: &Path, output_info: &probe::Result, reference: &Reference<'_>,
+        settings: &QualitySettings) -> Result<Vec<(f64, f64)>, VerifyError> {
+    let resolution = output_info.resolution();
+    let section_duration = reference.duration.min(output_info.duration());
+    let sample_duration = SAMPLE_DURATION.min(section_duration);
+    let timestamp = |seconds: f64| Some(Timestamp::from_milliseconds((seconds.max(0.0) * 1000.0) as u64));
+    let metric = settings.metric;
+    let mut scores = vec![];
+    for (sample_index, sample_start) in sample_starts(section_duration, sample_duration, settings.sample_count).into_iter().enumerate() {
+        let temp_files = TempFiles(vec![cut::temp_file_path(output_video_file, &format!(".{metric}{sample_index}.log"))]);
+        let stats_file = &temp_files.0[0];
+        let mut ffmpeg_command = ffmpeg::CommandBuilder::default();
+        ffmpeg_command
+            .add_input_file_slice(output_video_file, timestamp(sample_start), timestamp(sample_start + sample_duration))
+            .add_input_file_slice(reference.video_file, timestamp(reference.start + sample_start),
+                timestamp(reference.start + sample_start + sample_duration))
+            .add_complex_filter(&format!("[0:v]setpts=PTS-STARTPTS[distorted];[1:v]scale={}:{},setpts=PTS-STARTPTS[reference];[distorted][reference]{}",
+                resolution.width, resolution.height, metric.filter(stats_file)))
+            .set_output_stdout("null");
+        ffmpeg_command.build().unwrap().spawn_no_output()?.wait().await?;
+        let stats = fs_err::read_to_string(stats_file).map_err(|error| VerifyError::StatsFileError(metric, error))?;
+        let frame_scores = metric.parse_scores(&stats);
+        if frame_scores.is_empty() { return Err(VerifyError::NoScore(metric)) }
+        scores.push((sample_start, frame_scores.iter().sum::<f64>() / frame_scores.len() as f64));
+    }
+    Ok(scores)
+}
+
+/// Checks the output video is what was expected and optionally measures its quality, the checks results are logged
+/// and all the problems found are returned in the error
+pub async fn verify(output_video_file: &Path, expected: &Expected, quality: Option<(&Reference<'_>, &QualitySettings)>) -> Result<(), VerifyError> {
+    if crate::process::dry_run() { return Ok(()) }
+    log::info!("verifying output video: {}", output_video_file.to_string_lossy());
+    let output_info = probe(output_video_file)?;
+    let mut problems = check_streams(&output_info, expected);
+
+    if let Some((reference, settings)) = quality {
+        for (sample_start, score) in sample_scores(output_video_file, &output_info, reference, settings).await? {
+            log::info!("{} at {:.1}s: {score:.3}", settings.metric, sample_start);
+            if score < settings.min_score {
+                problems.push(format!("{} of {score:.3} at {sample_start:.1}s, under the minimum of {}", settings.metric, settings.min_score));
+            }
+        }
+    }
+
+    if ! problems.is_empty() { return Err(VerifyError::Failed(problems)) }
+    log::info!("output video verified successfully");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_ssim_scores() {
+        let stats = "n:1 Y:0.981000 U:0.990000 V:0.989000 All:0.984000 (17.958800)\nn:2 Y:0.9 U:0.9 V:0.9 All:0.900000 (10.0)\n";
+        assert_eq!(QualityMetric::Ssim.parse_scores(stats), vec![0.984, 0.9]);
+    }
+
+    #[test]
+    fn parse_vmaf_scores() {
+        let stats = "Frame,integer_adm2,integer_motion,vmaf,\n0,0.98,0.0,93.5,\n1,0.97,1.2,91.25,\n";
+        assert_eq!(QualityMetric::Vmaf.parse_scores(stats), vec![93.5, 91.25]);
+    }
+
+    #[test]
+    fn samples_spread_over_the_section() {
+        assert_eq!(sample_starts(10.0, 2.0, 4), vec![1.0, 3.0, 5.0, 7.0]);
+        assert_eq!(sample_starts(1.0, 1.0, 0), vec![0.0]);
+    }
+
+    fn video_info(duration: f64) -> probe::Result {
+        probe::Result::for_test(super::super::Resolution::new(1920, 1080), ffmpeg_next::Rational::new(60, 1), duration, true)
+    }
+
+    #[test]
+    fn streams_as_expected() {
+        assert!(check_streams(&video_info(10.0), &Expected::new(10.2, 603, true)).is_empty());
+        // the number of frames is not checked when not recorded
+        assert!(check_streams(&video_info(10.0).with_frame_count(0), &Expected::new(10.0, 900, true)).is_empty());
+    }
+
+    #[test]
+    fn duration_and_frame_count_problems() {
+        assert_eq!(check_streams(&video_info(10.0), &Expected::new(12.0, 600, true)), vec!["duration is 10.000s, expected 12.000s"]);
+        assert_eq!(check_streams(&video_info(10.0).with_frame_count(590), &Expected::new(10.0, 600, true)), vec!["590 frames, expected 600"]);
+    }
+
+    #[test]
+    fn audio_problems() {
+        assert_eq!(check_streams(&video_info(10.0), &Expected::new(10.0, 600, false)), vec!["unexpected audio stream"]);
+        assert_eq!(check_streams(&video_info(10.0).with_audio_timing(0.2, Some(10.0)), &Expected::new(10.0, 600, true)),
+            vec!["audio starts +0.200s from the video", "audio ends +0.200s from the video, the audio drifts"]);
+        assert_eq!(check_streams(&video_info(10.0).with_audio_timing(0.0, Some(9.5)), &Expected::new(10.0, 600, true)),
+            vec!["audio ends -0.500s from the video, the audio drifts"]);
+        // the drift is not checked when the duration of the audio stream is not recorded
+        assert!(check_streams(&video_info(10.0).with_audio_timing(0.0, None), &Expected::new(10.0, 600, true)).is_empty());
+    }
+
+}
@@ -0,0 +1,51 @@
+
+//! Vertical 9:16 export for short video platforms, the video being cropped to a vertical window panned horizontally
+
+use getset::CopyGetters;
+
+use super::Resolution;
+
+
+/// Settings of the vertical crop window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct VerticalSettings {
+    /// horizontal position of the window in percent, from -100 (left edge) to 100 (right edge)
+    pan: i8,
+}
+
+impl VerticalSettings {
+
+    pub fn new(pan: i8) -> Self {
+        Self { pan: pan.clamp(-100, 100) }
+    }
+
+    /// crop window of a video with the resolution, the window width is even as required by the YUV 4:2:0 encoders
+    pub fn window(&self, resolution: Resolution) -> VerticalWindow {
+        let width = (resolution.height * 9 / 16).min(resolution.width) & !1;
+        let left = ((resolution.width - width) as f64 * (self.pan as f64 + 100.0) / 200.0).round() as u32;
+        VerticalWindow { left, width, height: resolution.height }
+    }
+
+}
+
+/// Vertical window of the video kept by the crop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct VerticalWindow {
+    left: u32,
+    width: u32,
+    height: u32,
+}
+
+impl VerticalWindow {
+
+    pub fn resolution(&self) -> Resolution {
+        Resolution::new(self.width, self.height)
+    }
+
+    pub fn ffmpeg_filter(&self) -> String {
+        format!("crop={}:{}:{}:0", self.width, self.height, self.left)
+    }
+
+}
@@ -0,0 +1,95 @@
+
+//! Logo image composited onto the video in the same pass as the OSD, see [`super::layers::WatermarkLayer`]
+
+use std::path::{Path, PathBuf};
+
+use getset::{CopyGetters, Getters, Setters};
+
+use crate::ffmpeg;
+
+
+/// position of the watermark on the video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl WatermarkPosition {
+
+    /// overlay filter coordinates of the watermark, the margin to the video edges is 2% of the video width
+    pub(super) fn overlay_coordinates(&self) -> (&'static str, &'static str) {
+        use WatermarkPosition::*;
+        match self {
+            TopLeft => ("W/50", "W/50"),
+            TopRight => ("W-w-W/50", "W/50"),
+            BottomLeft => ("W/50", "H-h-W/50"),
+            BottomRight => ("W-w-W/50", "H-h-W/50"),
+            Center => ("(W-w)/2", "(H-h)/2"),
+        }
+    }
+
+}
+
+/// Settings used to composite the watermark image onto the video
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct WatermarkSettings {
+    image_file: PathBuf,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    position: WatermarkPosition,
+    /// opacity in percent
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    opacity: u8,
+}
+
+impl WatermarkSettings {
+
+    pub fn new<P: AsRef<Path>>(image_file: P) -> Self {
+        Self { image_file: image_file.as_ref().to_path_buf(), position: WatermarkPosition::BottomRight, opacity: 100 }
+    }
+
+    /// filters reading the image, with its opacity applied, as the source of the watermark layer
+    ///
+    /// The image is read with the `movie` source filter so that the inputs of the FFMpeg command are not changed,
+    /// the overlay filter repeats its single frame for the whole video.
+    pub fn image_filters(&self) -> Vec<String> {
+        let mut filters = vec![format!("movie=filename={},format=rgba", ffmpeg::escape_filter_option_value(&self.image_file.to_string_lossy()))];
+        if self.opacity < 100 {
+            filters.push(format!("colorchannelmixer=aa={}", self.opacity as f64 / 100.0));
+        }
+        filters
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn positions_parsed_ignoring_the_case() {
+        assert_eq!(WatermarkPosition::from_str("top-right").unwrap(), WatermarkPosition::TopRight);
+        assert_eq!(WatermarkPosition::from_str("Bottom-Left").unwrap(), WatermarkPosition::BottomLeft);
+        assert!(WatermarkPosition::from_str("top").is_err());
+        assert_eq!(WatermarkPosition::BottomRight.overlay_coordinates(), ("W-w-W/50", "H-h-W/50"));
+    }
+
+    #[test]
+    fn image_filters_with_opacity() {
+        let mut settings = WatermarkSettings::new("logo.png");
+        assert_eq!(settings.image_filters(), ["movie=filename=logo.png,format=rgba"]);
+        settings.set_opacity(50).set_image_file(PathBuf::from("C:/logo.png"));
+        assert_eq!(settings.image_filters(), ["movie=filename=C\\\\:/logo.png,format=rgba", "colorchannelmixer=aa=0.5"]);
+    }
+
+}
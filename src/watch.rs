@@ -0,0 +1,370 @@
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Error as IOError, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use derive_more::From;
+use fs_err::OpenOptions;
+use getset::{CopyGetters, Getters, Setters};
+use thiserror::Error;
+
+use crate::jobs;
+use crate::osd::file::find_associated_to_video_file;
+use crate::osd::overlay::scaling::OSDScalingSettings;
+use crate::video::{self, AudioFixType, Transcoder, transcoder::{EncodingSettings, OSDSettings, DEFAULT_ENCODING_SETTINGS}};
+
+
+pub const LOCK_FILE_NAME: &str = ".hd_fpv_video_tool_watch.lock";
+pub const STATE_FILE_NAME: &str = ".hd_fpv_video_tool_watch.state";
+
+const OUTPUT_FILE_SUFFIXES: &[&str] = &["_with_osd", "_transcoded"];
+
+#[derive(Debug, Error, From)]
+pub enum WatchError {
+    #[error("watched directory does not exist: {0}")]
+    #[from(ignore)]
+    WatchedDirDoesNotExist(PathBuf),
+    #[error("lock file {0} exists, another watch process is probably running, remove the file if it is not the case")]
+    #[from(ignore)]
+    AlreadyWatched(PathBuf),
+    #[error(transparent)]
+    IOError(IOError),
+}
+
+/// Settings of the pipeline run on each new video file
+#[derive(Debug, Clone, Getters, CopyGetters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct WatchSettings {
+    watched_dir: PathBuf,
+    /// directory where the processed videos are written, defaults to the watched directory
+    output_dir: Option<PathBuf>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    poll_interval: Duration,
+    /// font directory, the OSD is burnt onto the videos for which an OSD file is found when set
+    osd_font_dir: Option<PathBuf>,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    osd_scaling: OSDScalingSettings,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    fix_audio: bool,
+    #[getset(skip)]
+    #[getset(get_copy = "pub", set = "pub")]
+    encoding_settings: EncodingSettings,
+}
+
+impl WatchSettings {
+
+    pub fn new<P: AsRef<Path>>(watched_dir: P) -> Self {
+        Self {
+            watched_dir: watched_dir.as_ref().to_path_buf(),
+            output_dir: None,
+            poll_interval: Duration::from_secs(10),
+            osd_font_dir: None,
+            osd_scaling: OSDScalingSettings::default(),
+            fix_audio: false,
+            encoding_settings: DEFAULT_ENCODING_SETTINGS,
+        }
+    }
+
+}
+
+/// Lock file preventing two watch processes from processing the same directory
+///
+/// The lock file contains the id of the watch process. It is removed when dropped and when the process is
+/// interrupted, a lock file left behind by a process which is not running anymore, e.g. killed, is taken over.
+struct Lock(PathBuf);
+
+impl Lock {
+
+    fn acquire(dir: &Path) -> Result<Self, WatchError> {
+        let path = dir.join(LOCK_FILE_NAME);
+        if Self::is_stale(&path) {
+            log::warn!("removing stale lock file: {}", path.to_string_lossy());
+            match std::fs::remove_file(&path) {
+                Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {},
+            }
+        }
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                crate::process::register_partial_file(&path);
+                let lock = Self(path);
+                writeln!(file, "{}", std::process::id())?;
+                Ok(lock)
+            },
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => Err(WatchError::AlreadyWatched(path)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// whether the lock file exists and the process which wrote it is not running anymore
+    fn is_stale(path: &Path) -> bool {
+        match fs_err::read_to_string(path).ok().and_then(|content| content.trim().parse::<u32>().ok()) {
+            Some(process_id) => ! crate::process::is_running(process_id),
+            None => false,
+        }
+    }
+
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if let Err(error) = std::fs::remove_file(&self.0) {
+            log::warn!("failed to remove lock file {}: {error}", self.0.to_string_lossy());
+        }
+        crate::process::unregister_partial_file(&self.0);
+    }
+}
+
+/// Video file identified by its canonical path, size and modification time so that a file replaced by another one with
+/// the same name, e.g. when the SD card is formatted and the recordings numbered again, is processed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ProcessedFile {
+    path: String,
+    size: u64,
+    /// milliseconds since the epoch
+    modified: u64,
+}
+
+impl ProcessedFile {
+
+    fn of(path: &Path) -> Option<Self> {
+        let path = path.canonicalize().ok()?;
+        let metadata = path.metadata().ok()?;
+        let modified = metadata.modified().ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_millis() as u64)
+            .unwrap_or(0);
+        Some(Self { path: path.to_string_lossy().into_owned(), size: metadata.len(), modified })
+    }
+
+    /// parses a `<size>\t<modified>\t<path>` state file line
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(3, '\t');
+        let size = parts.next()?.parse().ok()?;
+        let modified = parts.next()?.parse().ok()?;
+        let path = parts.next()?.to_owned();
+        Some(Self { path, size, modified })
+    }
+
+}
+
+impl std::fmt::Display for ProcessedFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\t{}\t{}", self.size, self.modified, self.path)
+    }
+}
+
+/// Video files already processed, persisted in the watched directory so that files are not processed twice
+///
+/// The state files written by the previous versions only contain the names of the files, the files of the watched
+/// directory with these names are considered processed and the state file is rewritten in the current format.
+struct State {
+    path: PathBuf,
+    processed: HashSet<ProcessedFile>,
+}
+
+impl State {
+
+    fn load(dir: &Path) -> Result<Self, WatchError> {
+        let path = dir.join(STATE_FILE_NAME);
+        let content = match fs_err::read_to_string(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        let mut processed = HashSet::new();
+        let mut legacy = false;
+        for line in content.lines().filter(|line| ! line.is_empty()) {
+            match ProcessedFile::parse(line) {
+                Some(processed_file) => { processed.insert(processed_file); },
+                None => {
+                    legacy = true;
+                    processed.extend(ProcessedFile::of(&dir.join(line)));
+                },
+            }
+        }
+        let state = Self { path, processed };
+        if legacy && ! crate::process::dry_run() { state.save()? }
+        Ok(state)
+    }
+
+    fn save(&self) -> Result<(), WatchError> {
+        let content = self.processed.iter().map(|processed_file| format!("{processed_file}\n")).collect::<String>();
+        fs_err::write(&self.path, content)?;
+        Ok(())
+    }
+
+    fn contains(&self, processed_file: &ProcessedFile) -> bool {
+        self.processed.contains(processed_file)
+    }
+
+    /// in dry run mode the file is only considered processed until the end of the process since it was not transcoded
+    fn add(&mut self, processed_file: ProcessedFile) -> Result<(), WatchError> {
+        if ! crate::process::dry_run() {
+            let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            writeln!(file, "{processed_file}")?;
+        }
+        self.processed.insert(processed_file);
+        Ok(())
+    }
+
+}
+
+fn is_candidate_video_file(path: &Path) -> bool {
+    let is_mp4 = matches!(path.extension(), Some(extension) if extension.eq_ignore_ascii_case("mp4"));
+    let is_output = match path.file_stem() {
+        Some(stem) => OUTPUT_FILE_SUFFIXES.iter().any(|suffix| stem.to_string_lossy().ends_with(suffix)),
+        None => true,
+    };
+    path.is_file() && is_mp4 && ! is_output
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    path.metadata().ok().map(|metadata| metadata.len())
+}
+
+async fn process_video_file(settings: &WatchSettings, video_file: &Path) -> Result<(), video::TranscodeVideoError> {
+    let osd_file = match settings.osd_font_dir {
+        Some(_) => find_associated_to_video_file(video_file),
+        None => None,
+    };
+
+    let output_dir = settings.output_dir.as_ref().unwrap_or(&settings.watched_dir);
+    let mut output_file_name = video_file.file_stem().unwrap().to_os_string();
+    output_file_name.push(if osd_file.is_some() { "_with_osd" } else { "_transcoded" });
+    let output_video_file = output_dir.join(output_file_name).with_extension("mp4");
+
+    let mut transcoder = Transcoder::new(video_file, output_video_file);
+    transcoder.set_encoding_settings(&settings.encoding_settings);
+
+    if settings.fix_audio && video::probe(video_file)?.has_audio() {
+        transcoder.set_audio_fix(Some(AudioFixType::SyncAndVolume));
+    }
+
+    if let (Some(osd_file), Some(font_dir)) = (osd_file, &settings.osd_font_dir) {
+        let mut osd_settings = OSDSettings::new(osd_file, font_dir);
+        osd_settings.set_scaling(settings.osd_scaling);
+        transcoder.set_osd(Some(osd_settings));
+    }
+
+    transcoder.transcode().await
+}
+
+/// Watches the directory for new video files and runs the pipeline on them
+///
+/// A video file is processed once its size (and the size of its OSD file if any) did not change between two polls
+/// so that files which are still being copied are not processed. The files found ready at the same time are processed
+/// concurrently according to the maximum number of jobs. Never returns unless an error occurs.
+pub async fn watch(settings: &WatchSettings) -> Result<(), WatchError> {
+    if ! settings.watched_dir.is_dir() { return Err(WatchError::WatchedDirDoesNotExist(settings.watched_dir.clone())); }
+
+    let _lock = Lock::acquire(&settings.watched_dir)?;
+    let mut state = State::load(&settings.watched_dir)?;
+    let mut previous_sizes: HashMap<PathBuf, (Option<u64>, Option<u64>)> = HashMap::new();
+    let mut failed = HashSet::new();
+
+    log::info!("watching directory: {}", settings.watched_dir.to_string_lossy());
+
+    loop {
+        let mut sizes = HashMap::new();
+        let mut ready_files = vec![];
+
+        for entry in fs_err::read_dir(&settings.watched_dir)? {
+            let video_file = entry?.path();
+            if ! is_candidate_video_file(&video_file) { continue }
+            let file_name = video_file.file_name().unwrap().to_string_lossy().to_string();
+            let processed_file = match ProcessedFile::of(&video_file) {
+                Some(processed_file) => processed_file,
+                None => continue,
+            };
+            if state.contains(&processed_file) || failed.contains(&processed_file) { continue }
+
+            let osd_file_size = if settings.osd_font_dir.is_some() {
+                find_associated_to_video_file(&video_file).and_then(|osd_file| file_size(&osd_file))
+            } else {
+                None
+            };
+            let current_sizes = (file_size(&video_file), osd_file_size);
+
+            if previous_sizes.get(&video_file) == Some(&current_sizes) {
+                log::info!("new video file: {file_name}");
+                ready_files.push((file_name, video_file, processed_file));
+            } else {
+                sizes.insert(video_file, current_sizes);
+            }
+        }
+
+        let mut scheduler = jobs::Scheduler::new();
+        for (file_name, video_file, _) in &ready_files {
+            let (settings, video_file) = (settings.clone(), video_file.clone());
+            scheduler.add(file_name, async move { process_video_file(&settings, &video_file).await });
+        }
+        for ((file_name, _, processed_file), result) in ready_files.into_iter().zip(scheduler.run().await) {
+            match result {
+                Ok(()) => state.add(processed_file)?,
+                Err(error) => {
+                    log::error!("failed processing {file_name}: {error}");
+                    failed.insert(processed_file);
+                },
+            }
+        }
+
+        previous_sizes = sizes;
+        tokio::time::sleep(settings.poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_lock_is_taken_over() {
+        let dir = std::env::temp_dir().join(format!("hd_fpv_video_tool_watch_test_{}", std::process::id()));
+        fs_err::create_dir_all(&dir).unwrap();
+        let lock_file = dir.join(LOCK_FILE_NAME);
+        // above the maximum process id of the systems so that no process is running with it
+        fs_err::write(&lock_file, format!("{}\n", i32::MAX)).unwrap();
+
+        let lock = Lock::acquire(&dir).unwrap();
+        assert_eq!(fs_err::read_to_string(&lock_file).unwrap().trim(), std::process::id().to_string());
+        assert!(matches!(Lock::acquire(&dir), Err(WatchError::AlreadyWatched(_))));
+        drop(lock);
+        assert!(! lock_file.exists());
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn processed_files_keyed_by_path_size_and_modification_time() {
+        let dir = std::env::temp_dir().join(format!("hd_fpv_video_tool_watch_state_test_{}", std::process::id()));
+        fs_err::create_dir_all(&dir).unwrap();
+        let video_file = dir.join("DJIG0000.mp4");
+        fs_err::write(&video_file, b"first recording").unwrap();
+        fs_err::write(dir.join(STATE_FILE_NAME), "DJIG0000.mp4\nDJIG0001.mp4\n").unwrap();
+
+        // legacy state file with file names converted
+        let mut state = State::load(&dir).unwrap();
+        let processed_file = ProcessedFile::of(&video_file).unwrap();
+        assert!(state.contains(&processed_file));
+        assert_eq!(state.processed.len(), 1);
+        assert_eq!(ProcessedFile::parse(&processed_file.to_string()), Some(processed_file.clone()));
+        assert_eq!(State::load(&dir).unwrap().processed, state.processed);
+
+        // recording replaced by another one with the same name
+        fs_err::write(&video_file, b"recording after formatting the SD card").unwrap();
+        let replaced_file = ProcessedFile::of(&video_file).unwrap();
+        assert!(! state.contains(&replaced_file));
+        state.add(replaced_file.clone()).unwrap();
+        assert!(State::load(&dir).unwrap().contains(&replaced_file));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+}